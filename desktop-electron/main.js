@@ -595,7 +595,9 @@ ipcMain.handle('telegram:set-enabled', async (_evt, enabled) => {
 ipcMain.handle('state:fetch', async (_evt, baseUrl) => {
   const url = `${baseUrl || 'http://127.0.0.1:9108'}/api/state`;
   try {
-    const response = await fetch(url, { method: 'GET' });
+    const token = process.env.MONITORD_API_TOKEN;
+    const headers = token ? { Authorization: `Bearer ${token}` } : {};
+    const response = await fetch(url, { method: 'GET', headers });
     if (!response.ok) {
       return { ok: false, message: `HTTP ${response.status}` };
     }
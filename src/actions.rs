@@ -0,0 +1,141 @@
+use crate::config::RemoteActionConfig;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time;
+
+const OUTPUT_TAIL_CHARS: usize = 1500;
+
+#[derive(Debug, Clone)]
+pub struct ActionOutcome {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+pub async fn run_action(action: &RemoteActionConfig) -> ActionOutcome {
+    run_command(
+        &action.command,
+        &action.args,
+        &[],
+        None,
+        action.timeout_secs,
+    )
+    .await
+}
+
+pub async fn run_command(
+    command: &str,
+    args: &[String],
+    envs: &[(&str, String)],
+    stdin: Option<&str>,
+    timeout_secs: u64,
+) -> ActionOutcome {
+    let child = Command::new(command)
+        .args(args)
+        .envs(envs.iter().map(|(k, v)| (*k, v.as_str())))
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            return ActionOutcome {
+                success: false,
+                exit_code: None,
+                output: format!("не удалось запустить команду: {err}"),
+            }
+        }
+    };
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        let input = input.to_string();
+        tokio::spawn(async move {
+            let _ = pipe.write_all(input.as_bytes()).await;
+        });
+    }
+
+    match time::timeout(Duration::from_secs(timeout_secs), child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !stderr.trim().is_empty() {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&stderr);
+            }
+            ActionOutcome {
+                success: output.status.success(),
+                exit_code: output.status.code(),
+                output: tail_chars(text.trim(), OUTPUT_TAIL_CHARS),
+            }
+        }
+        Ok(Err(err)) => ActionOutcome {
+            success: false,
+            exit_code: None,
+            output: format!("ошибка ожидания команды: {err}"),
+        },
+        Err(_elapsed) => ActionOutcome {
+            success: false,
+            exit_code: None,
+            output: format!("таймаут {timeout_secs} сек"),
+        },
+    }
+}
+
+fn tail_chars(s: &str, max: usize) -> String {
+    let count = s.chars().count();
+    if count <= max {
+        return s.to_string();
+    }
+    let tail: String = s.chars().skip(count - max).collect();
+    format!("…{tail}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_keeps_end_of_output() {
+        assert_eq!(tail_chars("abc", 5), "abc");
+        assert_eq!(tail_chars("abcdef", 3), "…def");
+    }
+
+    #[tokio::test]
+    async fn missing_command_is_reported() {
+        let action = RemoteActionConfig {
+            name: "missing".to_string(),
+            command: "monitord-definitely-missing-binary".to_string(),
+            args: vec![],
+            allowed_chat_ids: vec![],
+            timeout_secs: 5,
+        };
+        let outcome = run_action(&action).await;
+        assert!(!outcome.success);
+        assert!(outcome.exit_code.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_receives_env_and_stdin() {
+        let outcome = run_command(
+            "sh",
+            &["-c".to_string(), "printf \"$GREETING \"; cat".to_string()],
+            &[("GREETING", "hello".to_string())],
+            Some("world"),
+            5,
+        )
+        .await;
+        assert!(outcome.success);
+        assert_eq!(outcome.output, "hello world");
+    }
+}
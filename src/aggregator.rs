@@ -0,0 +1,576 @@
+use crate::config::{AggregatedHostConfig, AggregatorConfig, Config, IngestConfig};
+use crate::events::{Alert, Event, EventBus};
+use crate::metrics::Metrics;
+use crate::state::Snapshot;
+use reqwest::Client;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinSet;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostSource {
+    Pull,
+    Push,
+    Mdns,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HostState {
+    pub name: String,
+    pub source: HostSource,
+    pub url: Option<String>,
+    pub up: bool,
+    pub last_success_unix: Option<i64>,
+    pub error: Option<String>,
+    pub state: Option<Snapshot>,
+}
+
+pub type Hosts = Arc<RwLock<Vec<HostState>>>;
+
+// Агенты, найденные через mDNS; опрашиваются наравне с aggregator.hosts.
+pub type Discovered = Arc<RwLock<Vec<AggregatedHostConfig>>>;
+
+#[derive(Debug, Clone)]
+pub enum FleetAlert {
+    Host {
+        name: String,
+        up: bool,
+        error: Option<String>,
+    },
+    Check {
+        host: String,
+        check: String,
+        up: bool,
+    },
+}
+
+impl FleetAlert {
+    pub fn host(&self) -> &str {
+        match self {
+            Self::Host { name, .. } => name,
+            Self::Check { host, .. } => host,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IngestError {
+    InvalidName,
+    PolledHost,
+    NotAllowed,
+    TooManyHosts,
+}
+
+pub async fn run(
+    client: Client,
+    hosts: Hosts,
+    discovered: Discovered,
+    metrics: Arc<Metrics>,
+    bus: EventBus,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut cfg = config_rx.borrow_and_update().aggregator.clone();
+    let mut ticker = poll_interval(&cfg);
+    let mut tracker = FleetTracker::default();
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {
+                if config_rx.has_changed().unwrap_or(false) {
+                    cfg = config_rx.borrow_and_update().aggregator.clone();
+                    if ticker.period() != Duration::from_secs(cfg.interval_secs) {
+                        ticker = poll_interval(&cfg);
+                        ticker.reset();
+                    }
+                }
+                let found = discovered.read().await.clone();
+                poll_hosts(&client, &cfg, &found, &hosts).await;
+                let now = now_unix();
+                let mut guard = hosts.write().await;
+                mark_stale_pushes(&mut guard, &cfg.ingest, now);
+                metrics.update_aggregated(&guard);
+                for alert in tracker.diff(&guard) {
+                    let host = Some(alert.host().to_string());
+                    bus.publish(Event::alert(now, host, Alert::Fleet(alert)));
+                }
+            }
+        }
+    }
+}
+
+fn poll_interval(cfg: &AggregatorConfig) -> Interval {
+    let mut ticker = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+pub async fn poll_hosts(
+    client: &Client,
+    cfg: &AggregatorConfig,
+    discovered: &[AggregatedHostConfig],
+    hosts: &Hosts,
+) {
+    // Статически заданный хост важнее найденного в сети с тем же именем.
+    let targets: Vec<(&AggregatedHostConfig, HostSource)> = cfg
+        .hosts
+        .iter()
+        .map(|host| (host, HostSource::Pull))
+        .chain(
+            discovered
+                .iter()
+                .filter(|found| cfg.hosts.iter().all(|h| h.name != found.name))
+                .map(|found| (found, HostSource::Mdns)),
+        )
+        .collect();
+    let timeout = Duration::from_secs(cfg.timeout_secs);
+    let mut tasks = JoinSet::new();
+    for (idx, (host, _)) in targets.iter().enumerate() {
+        let client = client.clone();
+        let url = host.state_url();
+        let token = host.resolve_token();
+        tasks.spawn(async move {
+            (
+                idx,
+                fetch_state(&client, &url, token.as_deref(), timeout).await,
+            )
+        });
+    }
+    let mut results: Vec<Option<Result<Snapshot, String>>> = vec![None; targets.len()];
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((idx, result)) = joined {
+            results[idx] = Some(result);
+        }
+    }
+
+    let now = now_unix();
+    let mut guard = hosts.write().await;
+    let (pushed, pulled): (Vec<_>, Vec<_>) = guard
+        .drain(..)
+        .partition(|host| host.source == HostSource::Push);
+    let mut previous: HashMap<String, HostState> = pulled
+        .into_iter()
+        .map(|host| (host.name.clone(), host))
+        .collect();
+    *guard = targets
+        .iter()
+        .zip(results)
+        .map(|((host, source), result)| {
+            let mut entry = previous
+                .remove(&host.name)
+                .unwrap_or_else(|| new_host_state(host));
+            entry.source = *source;
+            apply_result(entry, host, result, now)
+        })
+        .collect();
+    // Присланные хосты живут до перезапуска, если только такое же имя не стали опрашивать.
+    guard.extend(
+        pushed
+            .into_iter()
+            .filter(|host| targets.iter().all(|(h, _)| h.name != host.name)),
+    );
+}
+
+pub fn record_push(
+    hosts: &mut Vec<HostState>,
+    name: &str,
+    state: Snapshot,
+    now: i64,
+    cfg: &IngestConfig,
+) -> Result<(), IngestError> {
+    if !crate::config::valid_host_name(name) {
+        return Err(IngestError::InvalidName);
+    }
+    if !cfg.allows(name) {
+        return Err(IngestError::NotAllowed);
+    }
+    let idx = match hosts.iter().position(|host| host.name == name) {
+        Some(idx) if hosts[idx].source != HostSource::Push => return Err(IngestError::PolledHost),
+        Some(idx) => idx,
+        None => {
+            let pushed = hosts
+                .iter()
+                .filter(|host| host.source == HostSource::Push)
+                .count();
+            if pushed >= cfg.max_hosts {
+                warn!(
+                    host = name,
+                    max_hosts = cfg.max_hosts,
+                    "достигнут предел присланных хостов"
+                );
+                return Err(IngestError::TooManyHosts);
+            }
+            info!(host = name, "новый хост прислал состояние");
+            hosts.push(HostState {
+                name: name.to_string(),
+                source: HostSource::Push,
+                url: None,
+                up: false,
+                last_success_unix: None,
+                error: None,
+                state: None,
+            });
+            hosts.len() - 1
+        }
+    };
+    let entry = &mut hosts[idx];
+    if entry.error.is_some() {
+        info!(host = name, "хост снова присылает состояние");
+    }
+    entry.up = true;
+    entry.last_success_unix = Some(now);
+    entry.error = None;
+    entry.state = Some(state);
+    Ok(())
+}
+
+fn mark_stale_pushes(hosts: &mut Vec<HostState>, cfg: &IngestConfig, now: i64) {
+    let age = |host: &HostState| now.saturating_sub(host.last_success_unix.unwrap_or(0));
+    hosts.retain(|host| {
+        let expired = host.source == HostSource::Push && age(host) > cfg.retention_secs as i64;
+        if expired {
+            info!(host = %host.name, "хост удалён: нет данных дольше retention_secs");
+        }
+        !expired
+    });
+    for host in hosts
+        .iter_mut()
+        .filter(|host| host.source == HostSource::Push && host.up)
+    {
+        let age = age(host);
+        if age > cfg.stale_secs as i64 {
+            warn!(host = %host.name, age_secs = age, "хост перестал присылать состояние");
+            host.up = false;
+            host.error = Some(format!("нет данных {age} с"));
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FleetTracker {
+    hosts: HashMap<String, (bool, HashSet<String>)>,
+}
+
+impl FleetTracker {
+    fn diff(&mut self, hosts: &[HostState]) -> Vec<FleetAlert> {
+        let mut alerts = Vec::new();
+        let mut seen = HashMap::new();
+        for host in hosts {
+            let (was_up, mut down) = self
+                .hosts
+                .remove(&host.name)
+                .unwrap_or_else(|| (true, HashSet::new()));
+            if host.up != was_up {
+                alerts.push(FleetAlert::Host {
+                    name: host.name.clone(),
+                    up: host.up,
+                    error: host.error.clone(),
+                });
+            }
+            // Пока хост недоступен, его проверки считаются неизменными.
+            if let Some(state) = host.state.as_ref().filter(|_| host.up) {
+                let now_down: HashSet<String> = state
+                    .checks
+                    .http
+                    .iter()
+                    .filter(|c| !c.up)
+                    .map(|c| c.name.clone())
+                    .chain(
+                        state
+                            .checks
+                            .tcp
+                            .iter()
+                            .filter(|c| !c.up)
+                            .map(|c| c.name.clone()),
+                    )
+                    .chain(
+                        state
+                            .checks
+                            .ssh
+                            .iter()
+                            .filter(|c| !c.up)
+                            .map(|c| c.name.clone()),
+                    )
+                    .collect();
+                let mut changed: Vec<&String> = now_down.symmetric_difference(&down).collect();
+                changed.sort();
+                alerts.extend(changed.into_iter().map(|check| FleetAlert::Check {
+                    host: host.name.clone(),
+                    check: check.clone(),
+                    up: down.contains(check),
+                }));
+                down = now_down;
+            }
+            seen.insert(host.name.clone(), (host.up, down));
+        }
+        self.hosts = seen;
+        alerts
+    }
+}
+
+async fn fetch_state(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+    timeout: Duration,
+) -> Result<Snapshot, String> {
+    let mut request = client.get(url).timeout(timeout);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let body = request
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| err.to_string())?
+        .bytes()
+        .await
+        .map_err(|err| err.to_string())?;
+    serde_json::from_slice(&body).map_err(|err| format!("некорректный ответ /api/state: {err}"))
+}
+
+fn new_host_state(host: &AggregatedHostConfig) -> HostState {
+    HostState {
+        name: host.name.clone(),
+        source: HostSource::Pull,
+        url: Some(host.url.clone()),
+        up: false,
+        last_success_unix: None,
+        error: None,
+        state: None,
+    }
+}
+
+fn apply_result(
+    mut entry: HostState,
+    host: &AggregatedHostConfig,
+    result: Option<Result<Snapshot, String>>,
+    now: i64,
+) -> HostState {
+    entry.url = Some(host.url.clone());
+    match result {
+        Some(Ok(state)) => {
+            if !entry.up && entry.last_success_unix.is_some() {
+                info!(host = %entry.name, "агрегируемый хост снова доступен");
+            }
+            entry.up = true;
+            entry.last_success_unix = Some(now);
+            entry.error = None;
+            entry.state = Some(state);
+        }
+        Some(Err(err)) => {
+            if entry.up || entry.error.is_none() {
+                warn!(host = %entry.name, error = %err, "не удалось получить состояние агрегируемого хоста");
+            }
+            // Последнее полученное состояние остаётся доступным для просмотра.
+            entry.up = false;
+            entry.error = Some(err);
+        }
+        None => {
+            entry.up = false;
+            entry.error = Some("задача опроса прервана".to_string());
+        }
+    }
+    entry
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::build_router;
+    use crate::logbuf::LogBuffer;
+    use crate::state::{DiskStat, State};
+    use arc_swap::ArcSwap;
+
+    #[tokio::test]
+    async fn polls_hosts_and_keeps_last_state_of_unreachable_ones() {
+        let mut remote = State::new(100);
+        remote.host_name = Some("db-1".to_string());
+        remote.cpu_usage_percent = 42.0;
+        remote.last_collect_timestamp_seconds = 200;
+        Arc::make_mut(&mut remote.disks).push(DiskStat {
+            mount: "/".to_string(),
+            used_bytes: 25,
+            total_bytes: 100,
+            removable: false,
+            device: None,
+            temperature_celsius: None,
+            read_only: false,
+            write_error: None,
+        });
+        let metrics = Metrics::new().unwrap();
+        let app = build_router(
+            metrics.clone(),
+            Arc::new(RwLock::new(State::default())),
+            Arc::new(ArcSwap::from_pointee(remote.snapshot())),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            Some("fleet-read".to_string()),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let cfg = AggregatorConfig {
+            timeout_secs: 2,
+            hosts: vec![
+                AggregatedHostConfig {
+                    name: "db".to_string(),
+                    url: format!("http://{addr}/"),
+                    token_env: None,
+                    token: Some("fleet-read".to_string()),
+                },
+                AggregatedHostConfig {
+                    name: "gone".to_string(),
+                    url: format!("http://{closed_addr}"),
+                    token_env: None,
+                    token: None,
+                },
+            ],
+            ..AggregatorConfig::default()
+        };
+        let hosts = Hosts::default();
+        let client = Client::new();
+        poll_hosts(&client, &cfg, &[], &hosts).await;
+        {
+            let guard = hosts.read().await;
+            assert_eq!(guard.len(), 2);
+            assert!(guard[0].up);
+            let state = guard[0].state.as_ref().unwrap();
+            assert_eq!(state.host_name.as_deref(), Some("db-1"));
+            assert!(!guard[1].up);
+            assert!(guard[1].error.is_some());
+            metrics.update_aggregated(&guard);
+        }
+        let text = String::from_utf8(metrics.encode_metrics().unwrap().to_vec()).unwrap();
+        assert!(text.contains("agent_aggregated_host_up{host=\"db\"} 1"));
+        assert!(text.contains("agent_aggregated_host_up{host=\"gone\"} 0"));
+        assert!(text.contains("agent_aggregated_cpu_usage_percent{host=\"db\"} 42"));
+        assert!(text.contains("agent_aggregated_disk_usage_percent{host=\"db\",mount=\"/\"} 25"));
+        assert!(!text.contains("agent_aggregated_cpu_usage_percent{host=\"gone\"}"));
+
+        server.abort();
+        let _ = server.await;
+        poll_hosts(&Client::new(), &cfg, &[], &hosts).await;
+        let guard = hosts.read().await;
+        assert!(!guard[0].up);
+        assert!(guard[0].last_success_unix.is_some());
+        assert!(guard[0].state.is_some());
+        // Упавший хост теряет серии значений, а убранный из списка — все свои серии.
+        metrics.update_aggregated(&guard[..1]);
+        let text = String::from_utf8(metrics.encode_metrics().unwrap().to_vec()).unwrap();
+        assert!(text.contains("agent_aggregated_host_up{host=\"db\"} 0"));
+        assert!(!text.contains("agent_aggregated_cpu_usage_percent{host=\"db\"}"));
+        assert!(!text.contains("agent_aggregated_disk_usage_percent{host=\"db\""));
+        assert!(!text.contains("host=\"gone\""));
+    }
+
+    #[test]
+    fn fleet_tracker_reports_host_and_check_transitions() {
+        let mut state = State::new(0).snapshot();
+        let mut hosts = Vec::new();
+        let ingest = IngestConfig {
+            stale_secs: 30,
+            ..IngestConfig::default()
+        };
+        record_push(&mut hosts, "nat-1", state.clone(), 100, &ingest).unwrap();
+        assert_eq!(
+            record_push(&mut hosts, "nat 1", state.clone(), 100, &ingest),
+            Err(IngestError::InvalidName)
+        );
+        let mut tracker = FleetTracker::default();
+        assert!(tracker.diff(&hosts).is_empty());
+
+        Arc::make_mut(&mut state.checks)
+            .tcp
+            .push(crate::state::TcpCheckResult {
+                name: "db".to_string(),
+                up: false,
+                latency_ms: 0,
+                tls_handshake_ms: None,
+                tls_cert_valid: None,
+                error: None,
+                affected_by_upstream: None,
+                cached: false,
+            });
+        record_push(&mut hosts, "nat-1", state.clone(), 110, &ingest).unwrap();
+        let alerts = tracker.diff(&hosts);
+        assert!(matches!(
+            alerts.as_slice(),
+            [FleetAlert::Check { check, up: false, .. }] if check == "db"
+        ));
+
+        mark_stale_pushes(&mut hosts, &ingest, 200);
+        let alerts = tracker.diff(&hosts);
+        assert!(matches!(
+            alerts.as_slice(),
+            [FleetAlert::Host {
+                up: false,
+                error: Some(_),
+                ..
+            }]
+        ));
+
+        Arc::make_mut(&mut state.checks).tcp[0].up = true;
+        record_push(&mut hosts, "nat-1", state, 210, &ingest).unwrap();
+        let alerts = tracker.diff(&hosts);
+        assert!(matches!(
+            alerts.as_slice(),
+            [
+                FleetAlert::Host { up: true, .. },
+                FleetAlert::Check { up: true, .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn pushed_hosts_are_capped_filtered_and_expire() {
+        let state = State::new(0).snapshot();
+        let mut hosts = Vec::new();
+        let ingest = IngestConfig {
+            stale_secs: 30,
+            retention_secs: 100,
+            max_hosts: 2,
+            allowed_hosts: vec!["nat-*".to_string()],
+            ..IngestConfig::default()
+        };
+        assert_eq!(
+            record_push(&mut hosts, "laptop", state.clone(), 0, &ingest),
+            Err(IngestError::NotAllowed)
+        );
+        record_push(&mut hosts, "nat-1", state.clone(), 0, &ingest).unwrap();
+        record_push(&mut hosts, "nat-2", state.clone(), 50, &ingest).unwrap();
+        assert_eq!(
+            record_push(&mut hosts, "nat-3", state.clone(), 50, &ingest),
+            Err(IngestError::TooManyHosts)
+        );
+        // Уже известный хост продолжает присылать состояние и при заполненном списке.
+        record_push(&mut hosts, "nat-2", state.clone(), 60, &ingest).unwrap();
+
+        mark_stale_pushes(&mut hosts, &ingest, 101);
+        let names = hosts.iter().map(|h| h.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["nat-2"]);
+        assert!(!hosts[0].up);
+        record_push(&mut hosts, "nat-3", state, 101, &ingest).unwrap();
+        assert_eq!(hosts.len(), 2);
+    }
+}
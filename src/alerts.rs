@@ -0,0 +1,979 @@
+use crate::config::{AlertsConfig, CheckAlertPolicy, Config, SensorAlertConfig, TelegramConfig};
+use crate::events::Alert;
+use crate::rules;
+use crate::state::{
+    self, AlertSeverity, CheckId, CheckKind, CollectorAlert, CpuStealAlert, DirSizeAlert,
+    DiskForecast, DiskLatencyAlert, NetErrorAlert, PageFileAlert, ResourceAlert, ResourceAlertKind,
+    ResourceAlertTransition, SensorAlert, State, TempRiseAlert, TimeSyncAlert, WireguardAlert,
+};
+use std::collections::HashMap;
+use tracing::error;
+
+// Оценка уведомлений по собранному состоянию. Пороги и гистерезис отслеживаются в State, а движок
+// хранит то, что живёт между тактами: время последней отправки и скомпилированные политики и правила.
+#[derive(Default)]
+pub struct AlertEngine {
+    last_sent: HashMap<String, i64>,
+    check_policies: HashMap<CheckId, CheckAlertPolicy>,
+    check_dependencies: HashMap<CheckId, Vec<String>>,
+    rules: Vec<rules::Rule>,
+    dir_size_thresholds: HashMap<String, u64>,
+}
+
+impl AlertEngine {
+    pub fn new(cfg: &Config) -> Self {
+        let mut engine = Self::default();
+        engine.reconfigure(cfg);
+        engine
+    }
+
+    // Время последних отправок сохраняется, чтобы перезагрузка конфигурации не повторяла уведомления.
+    pub fn reconfigure(&mut self, cfg: &Config) {
+        self.check_policies = check_alert_policies(cfg);
+        self.check_dependencies = check_alert_dependencies(cfg);
+        self.rules = compile_alert_rules(cfg);
+        self.dir_size_thresholds = cfg
+            .dir_sizes
+            .paths
+            .iter()
+            .filter_map(|dir| Some((dir.path.clone(), dir.max_size_bytes()?)))
+            .collect();
+    }
+
+    // Вызывается после update_collected; уведомления возвращаются в порядке доставки.
+    pub fn evaluate(
+        &mut self,
+        state: &mut State,
+        telegram: &TelegramConfig,
+        now_unix: i64,
+    ) -> Vec<Alert> {
+        let alerts = &telegram.alerts;
+        let chats = alert_chats(state, telegram);
+        let checks = state.apply_alert_rules(
+            alerts,
+            &self.check_policies,
+            &self.check_dependencies,
+            now_unix,
+        );
+        let resource = self.resource_alerts(state, alerts, &chats, now_unix);
+        let sensor = self.sensor_alerts(state, alerts, now_unix);
+        let temp_rise = self.temp_rise_alerts(state, alerts, now_unix);
+        let disk_latency = self.disk_latency_alerts(state, alerts, now_unix);
+        let dir_sizes = self.dir_size_alerts(state, alerts, now_unix);
+        let net_errors = self.net_error_alerts(state, alerts, now_unix);
+        let cpu_steal = self.cpu_steal_alerts(state, alerts, now_unix);
+        let time_sync = self.time_sync_alerts(state, alerts, now_unix);
+        let pagefile = self.pagefile_alerts(state, alerts, now_unix);
+        let wireguard = self.wireguard_alerts(state, alerts, now_unix);
+        let forecasts = self.disk_forecasts(state, alerts, now_unix);
+        let rule_vars = rules::rule_variables(state, state.cpu_temperature_celsius);
+        let rule_alerts = rules::evaluate_rules(
+            &self.rules,
+            state,
+            &rule_vars,
+            alerts.repeat_interval_secs,
+            alerts.recovery_notify,
+            now_unix,
+        );
+        let expired = crate::external::expire_external_alerts(state, now_unix);
+        checks
+            .into_iter()
+            .map(Alert::Check)
+            .chain(resource.into_iter().map(Alert::Resource))
+            .chain(sensor.into_iter().map(Alert::Sensor))
+            .chain(forecasts.into_iter().map(Alert::DiskForecast))
+            .chain(rule_alerts.into_iter().map(Alert::Rule))
+            .chain(temp_rise.into_iter().map(Alert::TempRise))
+            .chain(disk_latency.into_iter().map(Alert::DiskLatency))
+            .chain(dir_sizes.into_iter().map(Alert::DirSize))
+            .chain(net_errors.into_iter().map(Alert::NetErrors))
+            .chain(cpu_steal.into_iter().map(Alert::CpuSteal))
+            .chain(time_sync.into_iter().map(Alert::TimeSync))
+            .chain(pagefile.into_iter().map(Alert::PageFile))
+            .chain(wireguard.into_iter().map(Alert::Wireguard))
+            .chain(expired.into_iter().map(Alert::External))
+            .collect()
+    }
+
+    // Повторы ограничиваются отдельно для каждого чата (у чатов свои пороги и включённые виды) и
+    // отдельно для остальных каналов; уведомление выходит, если хотя бы одному получателю пора.
+    fn resource_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        chats: &[i64],
+        now_unix: i64,
+    ) -> Vec<ResourceAlert> {
+        if !alerts.resource_alerts_enabled {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let mut out = Vec::new();
+        let mut samples: Vec<(ResourceAlertKind, f64, Option<String>)> = Vec::new();
+
+        let gpu_load_max = state
+            .gpus
+            .iter()
+            .filter_map(|g| g.utilization_percent)
+            .fold(0.0_f64, f64::max);
+        samples.push((ResourceAlertKind::GpuLoad, gpu_load_max, None));
+
+        let gpu_temp_max = state
+            .gpus
+            .iter()
+            .filter_map(|g| g.temperature_celsius)
+            .fold(0.0_f64, f64::max);
+        samples.push((ResourceAlertKind::GpuTemp, gpu_temp_max, None));
+
+        if let Some(cpu_temp) = state.cpu_temperature_celsius {
+            samples.push((ResourceAlertKind::CpuTemp, cpu_temp, None));
+        }
+
+        samples.push((ResourceAlertKind::CpuLoad, state.cpu_usage_percent, None));
+
+        let ram_usage = if state.memory_total_bytes > 0 {
+            (state.memory_used_bytes as f64 / state.memory_total_bytes as f64) * 100.0
+        } else {
+            0.0
+        };
+        samples.push((ResourceAlertKind::RamUsage, ram_usage, None));
+
+        for disk in state.disks.iter() {
+            if alerts.mount_ignored(&disk.mount) {
+                continue;
+            }
+            let used_pct = if disk.total_bytes > 0 {
+                (disk.used_bytes as f64 / disk.total_bytes as f64) * 100.0
+            } else {
+                0.0
+            };
+            samples.push((
+                ResourceAlertKind::DiskUsage,
+                used_pct,
+                Some(disk.mount.clone()),
+            ));
+        }
+
+        for (kind, value, subject) in samples {
+            let alert_key = kind.alert_key(subject.as_deref());
+            match state.track_resource_alert(kind, subject.as_deref(), value, now_unix, alerts) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    let Some((severity, _)) = AlertSeverity::classify(
+                        value,
+                        state.lowest_resource_threshold(kind, subject.as_deref(), alerts),
+                        kind.warning_threshold(subject.as_deref(), alerts),
+                    ) else {
+                        continue;
+                    };
+                    let key = format!("{}:{}", alert_key, severity.key());
+                    let broadcast = self.should_emit(&key, now_unix, cooldown);
+                    let mut chat_ids = Vec::new();
+                    for &chat_id in chats {
+                        if !state.resource_alert_enabled_for_chat(chat_id, kind)
+                            || state.resource_alert_snoozed(chat_id, kind, now_unix)
+                        {
+                            continue;
+                        }
+                        let Some((chat_severity, _)) = state.resource_severity_for_chat(
+                            chat_id,
+                            kind,
+                            subject.as_deref(),
+                            value,
+                            alerts,
+                        ) else {
+                            continue;
+                        };
+                        let key = format!("{alert_key}:{}@{chat_id}", chat_severity.key());
+                        if self.should_emit(&key, now_unix, cooldown) {
+                            chat_ids.push(chat_id);
+                        }
+                    }
+                    if broadcast || !chat_ids.is_empty() {
+                        out.push(ResourceAlert {
+                            kind,
+                            severity,
+                            value,
+                            subject,
+                            recovered: false,
+                            gpu_context: None,
+                            chat_ids,
+                            broadcast,
+                        });
+                    }
+                }
+                ResourceAlertTransition::Cleared(severity) => {
+                    let prefix = format!("{alert_key}:");
+                    self.last_sent.retain(|key, _| {
+                        !key.strip_prefix(&prefix).is_some_and(|rest| {
+                            let severity = rest.split('@').next().unwrap_or(rest);
+                            severity == "warning" || severity == "critical"
+                        })
+                    });
+                    if alerts.recovery_notify {
+                        out.push(ResourceAlert {
+                            kind,
+                            severity,
+                            value,
+                            subject,
+                            recovered: true,
+                            gpu_context: None,
+                            chat_ids: chats
+                                .iter()
+                                .copied()
+                                .filter(|chat_id| {
+                                    state.resource_alert_enabled_for_chat(*chat_id, kind)
+                                        && !state.resource_alert_snoozed(*chat_id, kind, now_unix)
+                                })
+                                .collect(),
+                            broadcast: true,
+                        });
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    fn sensor_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<SensorAlert> {
+        if !alerts.resource_alerts_enabled
+            || (alerts.sensor_alerts.is_empty() && alerts.disk_temp_threshold_celsius.is_none())
+        {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let drive_rule =
+            alerts
+                .disk_temp_threshold_celsius
+                .map(|threshold_celsius| SensorAlertConfig {
+                    pattern: "*".to_string(),
+                    threshold_celsius,
+                    clear_threshold_celsius: alerts.disk_temp_clear_threshold_celsius,
+                    label: Some("Накопитель".to_string()),
+                });
+        let drive_readings = state.temps.iter().filter_map(|t| {
+            let device = t.device.as_ref()?;
+            Some((
+                t.sensor.clone(),
+                format!("/disk_temperature/{device}"),
+                t.temperature_celsius,
+                drive_rule.as_ref()?,
+            ))
+        });
+        let readings = state
+            .sensors
+            .iter()
+            .filter(|s| s.sensor_type == "temperature")
+            .filter_map(|s| {
+                let rule = alerts
+                    .sensor_alerts
+                    .iter()
+                    .find(|r| r.matches(&s.name) || r.matches(&s.identifier))?;
+                Some((s.name.clone(), s.identifier.clone(), s.value, rule))
+            })
+            .chain(drive_readings)
+            .collect::<Vec<_>>();
+
+        let mut out = Vec::new();
+        for (sensor, identifier, value, rule) in readings {
+            let key = format!("sensor:{identifier}");
+            let alert = |recovered| SensorAlert {
+                label: rule.label.clone(),
+                sensor: sensor.clone(),
+                value,
+                threshold: if recovered {
+                    rule.clear_threshold()
+                } else {
+                    rule.threshold_celsius
+                },
+                recovered,
+            };
+            match state.track_sensor_alert(
+                &key,
+                value,
+                rule.threshold_celsius,
+                rule.clear_threshold(),
+            ) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if value >= rule.threshold_celsius && self.should_emit(&key, now_unix, cooldown)
+                    {
+                        out.push(alert(false));
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(alert(true));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // Рост быстрее порога ловит отказ вентилятора раньше, чем температура дойдёт до абсолютного порога.
+    fn temp_rise_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<TempRiseAlert> {
+        let cfg = &alerts.temp_rise;
+        if !alerts.resource_alerts_enabled || !cfg.enabled {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let threshold = cfg.rate_celsius_per_min;
+        let window_secs = (cfg.window_minutes * 60) as i64;
+        let readings = state
+            .temps
+            .iter()
+            .filter(|t| cfg.sensor_matches(&t.sensor))
+            .filter_map(|t| {
+                let rate = state.temperature_rise_rate(&t.sensor, window_secs, now_unix)?;
+                Some((t.sensor.clone(), rate, t.temperature_celsius))
+            })
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for (sensor, rate, temperature_celsius) in readings {
+            let key = format!("temp_rise:{sensor}");
+            let alert = |recovered| TempRiseAlert {
+                sensor: sensor.clone(),
+                rate_celsius_per_min: rate,
+                threshold,
+                temperature_celsius,
+                recovered,
+            };
+            // Сброс с гистерезисом: рост должен замедлиться хотя бы вдвое.
+            match state.track_sensor_alert(&key, rate, threshold, threshold / 2.0) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if rate >= threshold && self.should_emit(&key, now_unix, cooldown) {
+                        out.push(alert(false));
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(alert(true));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // Диски с нагрузкой ниже min_ops_per_sec не оцениваются: их await держится прежним, чтобы
+    // простой не выглядел восстановлением.
+    fn disk_latency_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<DiskLatencyAlert> {
+        let cfg = &alerts.disk_latency;
+        if !alerts.resource_alerts_enabled || !cfg.enabled {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let readings = state
+            .disk_io
+            .iter()
+            .filter(|d| d.ops_per_sec >= cfg.min_ops_per_sec)
+            .map(|d| (d.device.clone(), d.await_ms))
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for (device, await_ms) in readings {
+            let key = format!("disk_latency:{device}");
+            let threshold_ms = cfg.threshold_ms;
+            match state.track_sensor_alert(&key, await_ms, threshold_ms, threshold_ms) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if await_ms >= threshold_ms && self.should_emit(&key, now_unix, cooldown) {
+                        out.push(DiskLatencyAlert {
+                            device,
+                            await_ms,
+                            threshold_ms,
+                            recovered: false,
+                        });
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(DiskLatencyAlert {
+                            device,
+                            await_ms,
+                            threshold_ms,
+                            recovered: true,
+                        });
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // Неудачный обход (каталог пропал, нет прав) не считается ни ростом, ни восстановлением.
+    fn dir_size_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<DirSizeAlert> {
+        if !alerts.resource_alerts_enabled || self.dir_size_thresholds.is_empty() {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let readings = state
+            .dir_sizes
+            .iter()
+            .filter(|d| d.error.is_none())
+            .filter_map(|d| {
+                let threshold = *self.dir_size_thresholds.get(&d.path)?;
+                Some((d.path.clone(), d.size_bytes, threshold))
+            })
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for (path, size_bytes, threshold_bytes) in readings {
+            let key = format!("dir_size:{path}");
+            let (value, threshold) = (size_bytes as f64, threshold_bytes as f64);
+            let alert = |recovered| DirSizeAlert {
+                path: path.clone(),
+                size_bytes,
+                threshold_bytes,
+                recovered,
+            };
+            match state.track_sensor_alert(&key, value, threshold, threshold) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if value >= threshold && self.should_emit(&key, now_unix, cooldown) {
+                        out.push(alert(false));
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(alert(true));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn net_error_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<NetErrorAlert> {
+        let cfg = &alerts.net_errors;
+        if !alerts.resource_alerts_enabled || !cfg.enabled {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let readings = state
+            .net
+            .iter()
+            .map(|n| (n.iface.clone(), n.errors_per_min))
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for (iface, errors_per_min) in readings {
+            let key = format!("net_errors:{iface}");
+            let threshold = cfg.threshold_per_min;
+            match state.track_sensor_alert(&key, errors_per_min, threshold, threshold) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if errors_per_min >= threshold && self.should_emit(&key, now_unix, cooldown) {
+                        out.push(NetErrorAlert {
+                            iface,
+                            errors_per_min,
+                            threshold,
+                            recovered: false,
+                        });
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(NetErrorAlert {
+                            iface,
+                            errors_per_min,
+                            threshold,
+                            recovered: true,
+                        });
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn cpu_steal_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<CpuStealAlert> {
+        let Some(threshold) = alerts.cpu_steal_threshold_percent else {
+            return Vec::new();
+        };
+        let Some(steal_percent) = state.cpu_steal.as_ref().map(|s| s.steal_percent) else {
+            return Vec::new();
+        };
+        if !alerts.resource_alerts_enabled {
+            return Vec::new();
+        }
+
+        let key = "cpu_steal";
+        let clear = alerts
+            .cpu_steal_clear_threshold_percent
+            .unwrap_or(threshold);
+        let virtualization = state.virtualization.clone();
+        let alert = |recovered| CpuStealAlert {
+            steal_percent,
+            threshold,
+            virtualization: virtualization.clone(),
+            recovered,
+        };
+        let mut out = Vec::new();
+        match state.track_sensor_alert(key, steal_percent, threshold, clear) {
+            ResourceAlertTransition::Idle => {}
+            ResourceAlertTransition::Active => {
+                let cooldown = alerts.resource_alert_cooldown_secs as i64;
+                if steal_percent >= threshold && self.should_emit(key, now_unix, cooldown) {
+                    out.push(alert(false));
+                }
+            }
+            ResourceAlertTransition::Cleared(_) => {
+                self.last_sent.remove(key);
+                if alerts.recovery_notify {
+                    out.push(alert(true));
+                }
+            }
+        }
+        out
+    }
+
+    fn time_sync_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<TimeSyncAlert> {
+        let cfg = &alerts.time_sync;
+        if !alerts.resource_alerts_enabled || !cfg.enabled {
+            return Vec::new();
+        }
+        let Some(stat) = state.time_sync.clone() else {
+            return Vec::new();
+        };
+
+        let key = "time_sync";
+        let out_of_sync = if cfg.out_of_sync(&stat) { 1.0 } else { 0.0 };
+        let alert = |recovered| TimeSyncAlert {
+            source: stat.source.clone(),
+            synchronized: stat.synchronized,
+            offset_seconds: stat.offset_seconds,
+            max_offset_ms: cfg.max_offset_ms,
+            recovered,
+        };
+        let mut out = Vec::new();
+        match state.track_sensor_alert(key, out_of_sync, 1.0, 1.0) {
+            ResourceAlertTransition::Idle => {}
+            ResourceAlertTransition::Active => {
+                let cooldown = alerts.resource_alert_cooldown_secs as i64;
+                if self.should_emit(key, now_unix, cooldown) {
+                    out.push(alert(false));
+                }
+            }
+            ResourceAlertTransition::Cleared(_) => {
+                self.last_sent.remove(key);
+                if alerts.recovery_notify {
+                    out.push(alert(true));
+                }
+            }
+        }
+        out
+    }
+
+    fn pagefile_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<PageFileAlert> {
+        let Some(threshold) = alerts.pagefile_usage_threshold_percent else {
+            return Vec::new();
+        };
+        let Some(page_file) = state
+            .page_file
+            .clone()
+            .filter(|p| p.pagefile_total_bytes > 0)
+        else {
+            return Vec::new();
+        };
+        if !alerts.resource_alerts_enabled {
+            return Vec::new();
+        }
+
+        let key = "pagefile_usage";
+        let clear = alerts
+            .pagefile_usage_clear_threshold_percent
+            .unwrap_or(threshold);
+        let usage_percent = page_file.pagefile_usage_percent();
+        let alert = |recovered| PageFileAlert {
+            usage_percent,
+            threshold,
+            pagefile_used_bytes: page_file.pagefile_used_bytes,
+            pagefile_total_bytes: page_file.pagefile_total_bytes,
+            recovered,
+        };
+        let mut out = Vec::new();
+        match state.track_sensor_alert(key, usage_percent, threshold, clear) {
+            ResourceAlertTransition::Idle => {}
+            ResourceAlertTransition::Active => {
+                let cooldown = alerts.resource_alert_cooldown_secs as i64;
+                if usage_percent >= threshold && self.should_emit(key, now_unix, cooldown) {
+                    out.push(alert(false));
+                }
+            }
+            ResourceAlertTransition::Cleared(_) => {
+                self.last_sent.remove(key);
+                if alerts.recovery_notify {
+                    out.push(alert(true));
+                }
+            }
+        }
+        out
+    }
+
+    // Пиры без единого рукопожатия не отслеживаются: на сервере это обычно неподключённые клиенты.
+    fn wireguard_alerts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<WireguardAlert> {
+        let cfg = &alerts.wireguard_handshake;
+        if !alerts.resource_alerts_enabled || !cfg.enabled {
+            return Vec::new();
+        }
+
+        let cooldown = alerts.resource_alert_cooldown_secs as i64;
+        let threshold = cfg.stale_secs;
+        let peers = state
+            .wireguard
+            .iter()
+            .filter_map(|p| Some((p.clone(), p.handshake_age_secs(now_unix)?)))
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        for (peer, age) in peers {
+            let key = format!("wireguard:{}:{}", peer.iface, peer.public_key);
+            let alert = |recovered| WireguardAlert {
+                iface: peer.iface.clone(),
+                public_key: peer.public_key.clone(),
+                endpoint: peer.endpoint.clone(),
+                handshake_age_secs: age,
+                threshold_secs: threshold,
+                recovered,
+            };
+            match state.track_sensor_alert(&key, age as f64, threshold as f64, threshold as f64) {
+                ResourceAlertTransition::Idle => {}
+                ResourceAlertTransition::Active => {
+                    if self.should_emit(&key, now_unix, cooldown) {
+                        out.push(alert(false));
+                    }
+                }
+                ResourceAlertTransition::Cleared(_) => {
+                    self.last_sent.remove(&key);
+                    if alerts.recovery_notify {
+                        out.push(alert(true));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn disk_forecasts(
+        &mut self,
+        state: &mut State,
+        alerts: &AlertsConfig,
+        now_unix: i64,
+    ) -> Vec<DiskForecast> {
+        let forecast = &alerts.disk_forecast;
+        if !alerts.resource_alerts_enabled || !forecast.enabled {
+            return Vec::new();
+        }
+
+        let window_secs = forecast.window_hours * 3600;
+        let horizon_secs = (forecast.horizon_hours * 3600) as f64;
+        let min_history_secs = (forecast.min_history_minutes * 60) as i64;
+        let repeat = alerts.repeat_interval_secs as i64;
+        let disks = state
+            .disks
+            .iter()
+            .filter(|d| d.total_bytes > 0 && !alerts.mount_ignored(&d.mount))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut out = Vec::new();
+        for disk in disks {
+            state.record_disk_sample(&disk.mount, now_unix, disk.used_bytes, window_secs);
+            let key = format!("disk_forecast:{}", disk.mount);
+            let eta = state.disk_history.get(&disk.mount).and_then(|history| {
+                state::predict_seconds_until_full(history, disk.total_bytes, min_history_secs)
+            });
+            match eta {
+                Some(eta) if eta <= horizon_secs => {
+                    if self.should_emit(&key, now_unix, repeat) {
+                        out.push(DiskForecast {
+                            used_percent: (disk.used_bytes as f64 / disk.total_bytes as f64)
+                                * 100.0,
+                            mount: disk.mount,
+                            seconds_until_full: eta as u64,
+                        });
+                    }
+                }
+                _ => {
+                    self.last_sent.remove(&key);
+                }
+            }
+        }
+        out
+    }
+
+    fn should_emit(&mut self, key: &str, now_unix: i64, cooldown_secs: i64) -> bool {
+        if let Some(last) = self.last_sent.get(key) {
+            if now_unix - *last < cooldown_secs {
+                return false;
+            }
+        }
+        self.last_sent.insert(key.to_string(), now_unix);
+        true
+    }
+}
+
+// Загрузка видеокарты по процессам добавляется только к новым уведомлениям о GPU.
+pub async fn attach_gpu_alert_context(alerts: &mut [Alert]) {
+    if !alerts
+        .iter()
+        .any(|alert| matches!(alert, Alert::Resource(a) if is_new_gpu_alert(a)))
+    {
+        return;
+    }
+    let Ok(Some(context)) =
+        tokio::task::spawn_blocking(crate::collectors::system::gpu_alert_context).await
+    else {
+        return;
+    };
+    for alert in alerts.iter_mut() {
+        if let Alert::Resource(alert) = alert {
+            if is_new_gpu_alert(alert) {
+                alert.gpu_context = Some(context.clone());
+            }
+        }
+    }
+}
+
+fn is_new_gpu_alert(alert: &ResourceAlert) -> bool {
+    !alert.recovered
+        && matches!(
+            alert.kind,
+            ResourceAlertKind::GpuLoad | ResourceAlertKind::GpuTemp
+        )
+}
+
+// Чаты, которым Telegram доставляет уведомления: разрешённые и привязанные, без выключивших их.
+fn alert_chats(state: &State, telegram: &TelegramConfig) -> Vec<i64> {
+    if !telegram.enabled {
+        return Vec::new();
+    }
+    let mut chats = telegram.allowed_chat_ids.clone();
+    let mut paired = state
+        .paired_chat_ids
+        .iter()
+        .copied()
+        .filter(|id| !chats.contains(id))
+        .collect::<Vec<_>>();
+    paired.sort_unstable();
+    chats.extend(paired);
+    chats.retain(|id| state.alerts_enabled_for_chat(*id, telegram.alerts.enabled_by_default));
+    chats
+}
+
+// Обновляет счётчики сбоев сборщиков; уведомления уходят только при включённом collector_failures.
+pub fn record_collector_health(
+    state: &mut State,
+    alerts: &AlertsConfig,
+    now_unix: i64,
+    results: &[(String, Option<String>)],
+    sources: &[(&'static str, bool)],
+) -> Vec<CollectorAlert> {
+    let threshold = alerts.collector_failures.threshold_intervals;
+    let mut out = Vec::new();
+    for (name, error) in results {
+        out.extend(state.record_collector_result(name, error.clone(), now_unix, threshold));
+    }
+    for (name, has_data) in sources {
+        out.extend(state.record_collector_presence(name, *has_data, now_unix, threshold));
+    }
+    if !alerts.collector_failures.enabled {
+        out.clear();
+    }
+    out
+}
+
+fn check_alert_policies(cfg: &Config) -> HashMap<CheckId, CheckAlertPolicy> {
+    let http = cfg.http_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Http,
+                name: c.name.clone(),
+            },
+            c.alerts.clone(),
+        )
+    });
+    let tcp = cfg.tcp_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Tcp,
+                name: c.name.clone(),
+            },
+            c.alerts.clone(),
+        )
+    });
+    let ssh = cfg.ssh_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Ssh,
+                name: c.name.clone(),
+            },
+            c.alerts.clone(),
+        )
+    });
+    http.chain(tcp).chain(ssh).collect()
+}
+
+fn check_alert_dependencies(cfg: &Config) -> HashMap<CheckId, Vec<String>> {
+    let http = cfg.http_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Http,
+                name: c.name.clone(),
+            },
+            c.depends_on.clone(),
+        )
+    });
+    let tcp = cfg.tcp_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Tcp,
+                name: c.name.clone(),
+            },
+            c.depends_on.clone(),
+        )
+    });
+    let ssh = cfg.ssh_checks.iter().map(|c| {
+        (
+            CheckId {
+                kind: CheckKind::Ssh,
+                name: c.name.clone(),
+            },
+            c.depends_on.clone(),
+        )
+    });
+    http.chain(tcp)
+        .chain(ssh)
+        .filter(|(_, parents)| !parents.is_empty())
+        .collect()
+}
+
+fn compile_alert_rules(cfg: &Config) -> Vec<rules::Rule> {
+    cfg.rules
+        .iter()
+        .filter_map(|rule| match rules::Rule::from_config(rule) {
+            Ok(compiled) => Some(compiled),
+            Err(err) => {
+                error!(rule = %rule.name, error = %err, "правило пропущено");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_alerts(alerts: Vec<Alert>) -> Vec<(f64, bool, Vec<i64>, bool)> {
+        alerts
+            .into_iter()
+            .filter_map(|alert| match alert {
+                Alert::Resource(a) if a.kind == ResourceAlertKind::CpuLoad => {
+                    Some((a.value, a.recovered, a.chat_ids, a.broadcast))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn engine_tracks_cooldown_per_chat_and_reports_recovery() {
+        let mut cfg: Config =
+            serde_yaml::from_str("listen: \"127.0.0.1:9108\"\ninterval_secs: 60\n").unwrap();
+        cfg.telegram.enabled = true;
+        cfg.telegram.allowed_chat_ids = vec![1, 2];
+        cfg.telegram.alerts.enabled_by_default = true;
+        cfg.telegram.alerts.cpu_load_threshold_percent = 90.0;
+        cfg.telegram.alerts.resource_alert_cooldown_secs = 600;
+        cfg.telegram.alerts.recovery_notify = true;
+        let mut engine = AlertEngine::new(&cfg);
+        let mut state = State::new(0);
+        state.set_resource_alert_enabled_for_chat(2, ResourceAlertKind::CpuLoad, false);
+
+        let mut tick = |state: &mut State, now, cpu| {
+            state.cpu_usage_percent = cpu;
+            cpu_alerts(engine.evaluate(state, &cfg.telegram, now))
+        };
+        assert_eq!(
+            tick(&mut state, 0, 95.0),
+            vec![(95.0, false, vec![1], true)]
+        );
+        // Чат, включивший вид позже, не ждёт cooldown, набранный другими получателями.
+        state.set_resource_alert_enabled_for_chat(2, ResourceAlertKind::CpuLoad, true);
+        assert_eq!(
+            tick(&mut state, 60, 96.0),
+            vec![(96.0, false, vec![2], false)]
+        );
+        assert_eq!(tick(&mut state, 120, 96.0), vec![]);
+        assert_eq!(
+            tick(&mut state, 600, 97.0),
+            vec![(97.0, false, vec![1], true)]
+        );
+        assert_eq!(
+            tick(&mut state, 660, 10.0),
+            vec![(10.0, true, vec![1, 2], true)]
+        );
+        // После восстановления новое превышение уведомляется сразу, без ожидания cooldown.
+        assert_eq!(
+            tick(&mut state, 720, 95.0),
+            vec![(95.0, false, vec![1, 2], true)]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::config::{CheckEventsConfig, Config};
+use crate::events::{next_event, Event};
+use crate::notify::{with_retries, NotifyError};
+use crate::state::Snapshot;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::warn;
+
+const QUEUE_CAPACITY: usize = 256;
+
+// Смена состояния проверки в машиночитаемом виде. В отличие от алертов не зависит от
+// fail_threshold, повторов и depends_on: уходит каждый переход up <-> down.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckTransition {
+    pub host: Option<String>,
+    pub check: String,
+    pub kind: &'static str,
+    pub state: &'static str,
+    pub previous_state: &'static str,
+    pub changed_at: i64,
+    // Момент предыдущего перехода; None, если прежнее состояние застали при запуске агента.
+    pub previous_change_at: Option<i64>,
+    pub duration_secs: Option<i64>,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+}
+
+struct Observed<'a> {
+    kind: &'static str,
+    name: &'a str,
+    up: bool,
+    latency_ms: u64,
+    status_code: Option<u16>,
+    upstream: Option<&'a str>,
+}
+
+#[derive(Debug, Default)]
+pub struct TransitionTracker {
+    known: HashMap<(&'static str, String), (bool, Option<i64>)>,
+}
+
+impl TransitionTracker {
+    pub fn observe(&mut self, now: i64, snapshot: &Snapshot) -> Vec<CheckTransition> {
+        let checks = &snapshot.checks;
+        let observed = checks
+            .http
+            .iter()
+            .map(|c| Observed {
+                kind: "http",
+                name: &c.name,
+                up: c.up,
+                latency_ms: c.latency_ms,
+                status_code: Some(c.status_code).filter(|code| *code != 0),
+                upstream: c.affected_by_upstream.as_deref(),
+            })
+            .chain(checks.tcp.iter().map(|c| Observed {
+                kind: "tcp",
+                name: &c.name,
+                up: c.up,
+                latency_ms: c.latency_ms,
+                status_code: None,
+                upstream: c.affected_by_upstream.as_deref(),
+            }))
+            .chain(checks.ssh.iter().map(|c| Observed {
+                kind: "ssh",
+                name: &c.name,
+                up: c.up,
+                latency_ms: c.latency_ms,
+                status_code: None,
+                upstream: c.affected_by_upstream.as_deref(),
+            }));
+
+        let mut transitions = Vec::new();
+        // Удалённые из конфигурации проверки забываются вместе с предыдущим снимком.
+        let mut known = HashMap::new();
+        for check in observed {
+            let key = (check.kind, check.name.to_string());
+            let entry = match self.known.get(&key) {
+                Some(&(was_up, since)) if was_up != check.up => {
+                    transitions.push(CheckTransition {
+                        host: snapshot.host_name.clone(),
+                        check: check.name.to_string(),
+                        kind: check.kind,
+                        state: state_name(check.up),
+                        previous_state: state_name(was_up),
+                        changed_at: now,
+                        previous_change_at: since,
+                        duration_secs: since.map(|since| now - since),
+                        latency_ms: check.latency_ms,
+                        status_code: check.status_code,
+                        upstream: check.upstream.map(str::to_string),
+                    });
+                    (check.up, Some(now))
+                }
+                Some(&state) => state,
+                None => (check.up, None),
+            };
+            known.insert(key, entry);
+        }
+        self.known = known;
+        transitions
+    }
+}
+
+fn state_name(up: bool) -> &'static str {
+    if up {
+        "up"
+    } else {
+        "down"
+    }
+}
+
+pub async fn run(
+    client: Client,
+    config_rx: watch::Receiver<Arc<Config>>,
+    mut events: broadcast::Receiver<Event>,
+) {
+    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+    // Отправка идёт отдельной задачей по одному событию: повторы не задерживают разбор
+    // снимков, а порядок переходов сохраняется.
+    let sender = tokio::spawn(deliver(client, config_rx.clone(), rx));
+    let mut tracker = TransitionTracker::default();
+    while let Some(event) = next_event(&mut events).await {
+        let Event::SnapshotUpdated { now, snapshot } = event else {
+            continue;
+        };
+        let transitions = tracker.observe(now, &snapshot);
+        if config_rx.borrow().check_events.url.is_none() {
+            continue;
+        }
+        for transition in transitions {
+            if tx.try_send(transition).is_err() {
+                warn!("очередь check_events переполнена, переход состояния проверки отброшен");
+            }
+        }
+    }
+    drop(tx);
+    let _ = sender.await;
+}
+
+async fn deliver(
+    client: Client,
+    config_rx: watch::Receiver<Arc<Config>>,
+    mut rx: mpsc::Receiver<CheckTransition>,
+) {
+    while let Some(transition) = rx.recv().await {
+        let cfg = config_rx.borrow().check_events.clone();
+        let Some(url) = cfg.url.as_deref() else {
+            continue;
+        };
+        let body = match serde_json::to_string(&transition) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!(error = %err, "не удалось сериализовать переход состояния проверки");
+                continue;
+            }
+        };
+        let sent = with_retries(&cfg.delivery, "check_events", &transition.check, || {
+            send_once(&client, &cfg, url, body.clone())
+        })
+        .await;
+        if let Err(err) = sent {
+            warn!(
+                check = %transition.check,
+                state = transition.state,
+                error = %err,
+                "не удалось отправить переход состояния проверки в check_events"
+            );
+        }
+    }
+}
+
+async fn send_once(
+    client: &Client,
+    cfg: &CheckEventsConfig,
+    url: &str,
+    body: String,
+) -> Result<(), NotifyError> {
+    let mut request = client
+        .post(url)
+        .timeout(Duration::from_millis(cfg.delivery.timeout_ms))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    for (name, value) in &cfg.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().await.map_err(reqwest::Error::without_url)?;
+    if !response.status().is_success() {
+        return Err(NotifyError::Status(response.status().as_u16()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{HttpCheckResult, TcpCheckResult};
+
+    fn snapshot(http_up: bool, tcp: Option<bool>) -> Snapshot {
+        let mut snapshot = Snapshot {
+            host_name: Some("web-1".to_string()),
+            ..Snapshot::default()
+        };
+        Arc::make_mut(&mut snapshot.checks)
+            .http
+            .push(HttpCheckResult {
+                name: "api".to_string(),
+                up: http_up,
+                latency_ms: 120,
+                status_code: if http_up { 200 } else { 503 },
+                error: None,
+                affected_by_upstream: None,
+                cached: false,
+            });
+        if let Some(up) = tcp {
+            Arc::make_mut(&mut snapshot.checks)
+                .tcp
+                .push(TcpCheckResult {
+                    name: "db".to_string(),
+                    up,
+                    latency_ms: 3,
+                    tls_handshake_ms: None,
+                    tls_cert_valid: None,
+                    error: None,
+                    affected_by_upstream: None,
+                    cached: false,
+                });
+        }
+        snapshot
+    }
+
+    #[test]
+    fn every_flip_is_reported_with_its_duration() {
+        let mut tracker = TransitionTracker::default();
+        assert!(tracker.observe(100, &snapshot(true, Some(true))).is_empty());
+        assert!(tracker.observe(110, &snapshot(true, Some(true))).is_empty());
+
+        let down = tracker.observe(120, &snapshot(false, Some(true)));
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].check, "api");
+        assert_eq!((down[0].previous_state, down[0].state), ("up", "down"));
+        assert_eq!(down[0].previous_change_at, None);
+        assert_eq!(down[0].status_code, Some(503));
+
+        let up = tracker.observe(180, &snapshot(true, Some(false)));
+        assert_eq!(up.len(), 2);
+        assert_eq!((up[0].state, up[0].duration_secs), ("up", Some(60)));
+        assert_eq!((up[1].kind, up[1].state), ("tcp", "down"));
+
+        // Проверка исчезла из конфигурации и вернулась: её прежнее состояние забыто.
+        assert!(tracker.observe(190, &snapshot(true, None)).is_empty());
+        assert!(tracker.observe(200, &snapshot(true, Some(true))).is_empty());
+
+        let json = serde_json::to_value(&up[0]).unwrap();
+        assert_eq!(json["host"], "web-1");
+        assert_eq!(json["previous_change_at"], 120);
+        assert!(json.get("upstream").is_none());
+    }
+}
@@ -1,86 +1,793 @@
-use crate::config::{Config, HttpCheckConfig, TcpCheckConfig};
-use crate::state::{CheckResults, HttpCheckResult, TcpCheckResult};
+use super::ssh::SshSession;
+use crate::config::{
+    default_max_redirects, parse_json_path, CheckHttpClientConfig, CheckTlsConfig, Config,
+    HttpCheckConfig, IpVersion, JsonAssertion, JsonPathSegment, SshCheckConfig, TcpCheckConfig,
+};
+use crate::state::{
+    CheckId, CheckKind, CheckResults, HttpCheckResult, SshCheckResult, TcpCheckResult,
+};
+use regex::bytes::Regex;
+use reqwest::redirect::Policy;
 use reqwest::Client;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::net::TcpStream;
-use tokio::time;
-use tracing::warn;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{self, JoinSet};
+use tokio::time::{self, MissedTickBehavior};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::WebPkiServerVerifier;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tokio_rustls::TlsConnector;
+use tracing::{debug, warn};
 
-pub async fn collect_checks(client: &Client, cfg: &Config) -> (CheckResults, u64) {
-    let mut http_results = Vec::with_capacity(cfg.http_checks.len());
-    let mut errors = 0_u64;
-    for check in &cfg.http_checks {
-        let (result, had_error) = run_http_check(client, check).await;
-        if had_error {
-            errors += 1;
+// Ответ сервера для expect_regex читается не дальше этого размера.
+const MAX_TCP_RESPONSE_BYTES: usize = 4096;
+// Тело HTTP-ответа для body_regex читается не дальше этого размера.
+const MAX_HTTP_BODY_BYTES: usize = 64 * 1024;
+// Для json_assertions нужен весь документ, поэтому предел больше.
+const MAX_HTTP_JSON_BYTES: usize = 1024 * 1024;
+
+// Последние результаты проверок: их пишет CheckRunner, а забирает такт сбора (take_check_results).
+#[derive(Debug, Default)]
+pub struct CheckSchedule {
+    next_due: HashMap<CheckId, i64>,
+    http: HashMap<String, HttpCheckResult>,
+    tcp: HashMap<String, TcpCheckResult>,
+    ssh: HashMap<String, SshCheckResult>,
+    // Проверки, выполнившиеся после прошлого такта сбора.
+    fresh: HashSet<CheckId>,
+    errors: u64,
+    clients: Option<CheckClients>,
+}
+
+pub type SharedCheckSchedule = Arc<Mutex<CheckSchedule>>;
+
+impl CheckSchedule {
+    // Клиенты пересобираются только при изменении checks.http_client, иначе пул сохраняется.
+    fn clients(&mut self, tuning: &CheckHttpClientConfig) -> CheckClients {
+        match &self.clients {
+            Some(clients) if clients.tuning == *tuning => clients.clone(),
+            _ => self.clients.insert(CheckClients::new(tuning)).clone(),
+        }
+    }
+
+    fn is_due(&self, id: &CheckId, now: i64) -> bool {
+        self.next_due.get(id).is_none_or(|due| now >= *due)
+    }
+
+    // Следующий запуск отсчитывается от начала текущего, а не от его завершения.
+    fn schedule(&mut self, id: CheckId, interval_secs: u64, jitter_secs: u64, now: i64) {
+        let due = now + (interval_secs + random_below(jitter_secs + 1)) as i64;
+        self.next_due.insert(id, due);
+    }
+
+    fn record(&mut self, run: CheckRun) {
+        if run.had_error {
+            self.errors += 1;
         }
-        http_results.push(result);
+        match run.outcome {
+            CheckOutcome::Http(result) => {
+                self.http.insert(run.id.name.clone(), result);
+            }
+            CheckOutcome::Tcp(result) => {
+                self.tcp.insert(run.id.name.clone(), result);
+            }
+            CheckOutcome::Ssh(result) => {
+                self.ssh.insert(run.id.name.clone(), result);
+            }
+        }
+        self.fresh.insert(run.id);
     }
 
-    let mut tcp_results = Vec::with_capacity(cfg.tcp_checks.len());
-    for check in &cfg.tcp_checks {
-        let (result, had_error) = run_tcp_check(check).await;
-        if had_error {
-            errors += 1;
+    fn retain(&mut self, cfg: &Config) {
+        let http = cfg
+            .http_checks
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        let tcp = cfg
+            .tcp_checks
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        let ssh = cfg
+            .ssh_checks
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        let configured = |id: &CheckId| match id.kind {
+            CheckKind::Http => http.contains(&id.name.as_str()),
+            CheckKind::Tcp => tcp.contains(&id.name.as_str()),
+            CheckKind::Ssh => ssh.contains(&id.name.as_str()),
+        };
+        self.next_due.retain(|id, _| configured(id));
+        self.fresh.retain(configured);
+        self.http.retain(|name, _| http.contains(&name.as_str()));
+        if let Some(clients) = &self.clients {
+            clients
+                .custom
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .retain(|name, _| http.contains(&name.as_str()));
         }
-        tcp_results.push(result);
+        self.tcp.retain(|name, _| tcp.contains(&name.as_str()));
+        self.ssh.retain(|name, _| ssh.contains(&name.as_str()));
     }
+}
 
+enum CheckOutcome {
+    Http(HttpCheckResult),
+    Tcp(TcpCheckResult),
+    Ssh(SshCheckResult),
+}
+
+struct CheckRun {
+    id: CheckId,
+    outcome: CheckOutcome,
+    had_error: bool,
+}
+
+// Запускает каждую проверку по её собственному таймеру (interval_secs проверки или глобальный),
+// независимо от такта сбора; результаты складываются в общий CheckSchedule.
+pub struct CheckRunner {
+    schedule: SharedCheckSchedule,
+    tasks: JoinSet<CheckRun>,
+    running: HashMap<task::Id, CheckId>,
+    limit: (usize, Arc<Semaphore>),
+}
+
+impl CheckRunner {
+    pub fn new(schedule: SharedCheckSchedule) -> Self {
+        Self {
+            schedule,
+            tasks: JoinSet::new(),
+            running: HashMap::new(),
+            limit: (0, Arc::new(Semaphore::new(0))),
+        }
+    }
+
+    pub async fn run(
+        mut self,
+        mut config_rx: watch::Receiver<Arc<Config>>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut ticker = time::interval(Duration::from_secs(1));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                Some(()) = self.join_next(), if !self.tasks.is_empty() => {}
+                _ = ticker.tick() => {
+                    let cfg = config_rx.borrow_and_update().clone();
+                    self.start_due(&cfg, now_unix());
+                }
+            }
+        }
+        self.tasks.abort_all();
+    }
+
+    fn start_due(&mut self, cfg: &Config, now: i64) {
+        let mut schedule = self
+            .schedule
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        schedule.retain(cfg);
+        let clients = schedule.clients(&cfg.checks.http_client);
+        // Проверки идут параллельно, но не больше max_concurrency одновременно; таймауты у каждой свои.
+        if self.limit.0 != cfg.checks.max_concurrency {
+            self.limit = (
+                cfg.checks.max_concurrency,
+                Arc::new(Semaphore::new(cfg.checks.max_concurrency)),
+            );
+        }
+        let preferred = cfg.checks.preferred_ip_version;
+        let mut due = |id: CheckId, interval_secs: Option<u64>, jitter_secs: u64| {
+            // Долгая проверка не запускается повторно, пока не завершится прошлый прогон.
+            if self.running.values().any(|running| *running == id) || !schedule.is_due(&id, now) {
+                return None;
+            }
+            let interval = interval_secs.unwrap_or(cfg.interval_secs);
+            schedule.schedule(id.clone(), interval, jitter_secs, now);
+            Some((id, self.limit.1.clone()))
+        };
+        let mut spawned = Vec::new();
+        for check in &cfg.http_checks {
+            let id = CheckId {
+                kind: CheckKind::Http,
+                name: check.name.clone(),
+            };
+            if let Some((id, limit)) = due(id, check.interval_secs, check.jitter_secs) {
+                let (clients, check) = (clients.clone(), check.clone());
+                spawned.push((
+                    id.clone(),
+                    self.tasks.spawn(async move {
+                        let _permit = limit.acquire_owned().await;
+                        let (result, had_error) = run_http_check(&clients, &check, preferred).await;
+                        CheckRun {
+                            id,
+                            outcome: CheckOutcome::Http(result),
+                            had_error,
+                        }
+                    }),
+                ));
+            }
+        }
+        for check in &cfg.tcp_checks {
+            let id = CheckId {
+                kind: CheckKind::Tcp,
+                name: check.name.clone(),
+            };
+            if let Some((id, limit)) = due(id, check.interval_secs, check.jitter_secs) {
+                let check = check.clone();
+                spawned.push((
+                    id.clone(),
+                    self.tasks.spawn(async move {
+                        let _permit = limit.acquire_owned().await;
+                        let (result, had_error) = run_tcp_check(&check, preferred).await;
+                        CheckRun {
+                            id,
+                            outcome: CheckOutcome::Tcp(result),
+                            had_error,
+                        }
+                    }),
+                ));
+            }
+        }
+        for check in &cfg.ssh_checks {
+            let id = CheckId {
+                kind: CheckKind::Ssh,
+                name: check.name.clone(),
+            };
+            if let Some((id, limit)) = due(id, check.interval_secs, check.jitter_secs) {
+                let check = check.clone();
+                spawned.push((
+                    id.clone(),
+                    self.tasks.spawn(async move {
+                        let _permit = limit.acquire_owned().await;
+                        let (result, had_error) = run_ssh_check(&check, preferred).await;
+                        CheckRun {
+                            id,
+                            outcome: CheckOutcome::Ssh(result),
+                            had_error,
+                        }
+                    }),
+                ));
+            }
+        }
+        for (id, handle) in spawned {
+            self.running.insert(handle.id(), id);
+        }
+    }
+
+    async fn join_next(&mut self) -> Option<()> {
+        let joined = self.tasks.join_next_with_id().await?;
+        let run = match joined {
+            Ok((task_id, run)) => {
+                self.running.remove(&task_id);
+                run
+            }
+            Err(err) => {
+                let id = self.running.remove(&err.id());
+                match (id, err.try_into_panic()) {
+                    (Some(id), Ok(panic)) => panicked_run(id, panic_message(&*panic)),
+                    // Отменённые задачи (abort при остановке) результата не дают.
+                    _ => return Some(()),
+                }
+            }
+        };
+        self.schedule
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .record(run);
+        Some(())
+    }
+}
+
+// Паника внутри проверки не должна оставлять её с устаревшим результатом: проверка считается
+// недоступной, а сообщение паники попадает в журнал как ошибка.
+fn panicked_run(id: CheckId, message: String) -> CheckRun {
+    warn!(check = %id.name, error = %message, "проверка завершилась паникой");
+    let (name, up, latency_ms, error) = (id.name.clone(), false, 0, Some(message));
+    let outcome = match id.kind {
+        CheckKind::Http => CheckOutcome::Http(HttpCheckResult {
+            name,
+            up,
+            latency_ms,
+            status_code: 0,
+            error,
+            affected_by_upstream: None,
+            cached: false,
+        }),
+        CheckKind::Tcp => CheckOutcome::Tcp(TcpCheckResult {
+            name,
+            up,
+            latency_ms,
+            tls_handshake_ms: None,
+            tls_cert_valid: None,
+            error,
+            affected_by_upstream: None,
+            cached: false,
+        }),
+        CheckKind::Ssh => CheckOutcome::Ssh(SshCheckResult {
+            name,
+            up,
+            latency_ms,
+            banner: None,
+            host_key_fingerprint: None,
+            host_key_matches: None,
+            error,
+            affected_by_upstream: None,
+            cached: false,
+        }),
+    };
+    CheckRun {
+        id,
+        outcome,
+        had_error: true,
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "паника без сообщения".to_string())
+}
+
+// Результаты для такта сбора в порядке конфигурации: выполнившиеся после прошлого такта — свежие,
+// остальные — последний результат с cached. Проверки, ещё ни разу не завершившиеся, не попадают.
+pub fn take_check_results(cfg: &Config, schedule: &SharedCheckSchedule) -> (CheckResults, u64) {
+    let mut schedule = schedule
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let schedule = &mut *schedule;
+    let mut fresh = |kind: CheckKind, name: &str| {
+        schedule.fresh.remove(&CheckId {
+            kind,
+            name: name.to_string(),
+        })
+    };
+    let http = cfg
+        .http_checks
+        .iter()
+        .filter_map(|check| {
+            let result = schedule.http.get(&check.name)?;
+            Some(HttpCheckResult {
+                cached: !fresh(CheckKind::Http, &check.name),
+                ..result.clone()
+            })
+        })
+        .collect();
+    let tcp = cfg
+        .tcp_checks
+        .iter()
+        .filter_map(|check| {
+            let result = schedule.tcp.get(&check.name)?;
+            Some(TcpCheckResult {
+                cached: !fresh(CheckKind::Tcp, &check.name),
+                ..result.clone()
+            })
+        })
+        .collect();
+    let ssh = cfg
+        .ssh_checks
+        .iter()
+        .filter_map(|check| {
+            let result = schedule.ssh.get(&check.name)?;
+            Some(SshCheckResult {
+                cached: !fresh(CheckKind::Ssh, &check.name),
+                ..result.clone()
+            })
+        })
+        .collect();
     (
-        CheckResults {
-            http: http_results,
-            tcp: tcp_results,
-        },
-        errors,
+        CheckResults { http, tcp, ssh },
+        std::mem::take(&mut schedule.errors),
     )
 }
 
-async fn run_http_check(client: &Client, cfg: &HttpCheckConfig) -> (HttpCheckResult, bool) {
-    let start = Instant::now();
-    let req = client
-        .get(&cfg.url)
-        .timeout(Duration::from_millis(cfg.timeout_ms));
+// Разовый прогон всех проверок, срок которых наступил, с ожиданием результатов (monitord collect, тесты).
+pub async fn collect_checks(
+    cfg: &Config,
+    schedule: &SharedCheckSchedule,
+    now: i64,
+) -> (CheckResults, u64) {
+    let mut runner = CheckRunner::new(schedule.clone());
+    runner.start_due(cfg, now);
+    while runner.join_next().await.is_some() {}
+    take_check_results(cfg, schedule)
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn random_below(bound: u64) -> u64 {
+    if bound <= 1 {
+        return 0;
+    }
+    RandomState::new().build_hasher().finish() % bound
+}
 
-    let (up, status_code, had_error) = match req.send().await {
-        Ok(resp) => {
-            let code = resp.status().as_u16();
-            (code == cfg.expected_status, code, false)
+async fn resolve_check_addrs(
+    host: &str,
+    port: u16,
+    resolve_to: Option<IpAddr>,
+    ip_version: Option<IpVersion>,
+    preferred: Option<IpVersion>,
+) -> io::Result<Vec<SocketAddr>> {
+    if let Some(ip) = resolve_to {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let mut addrs = lookup_host((host, port)).await?.collect::<Vec<_>>();
+    match (ip_version, preferred) {
+        (Some(version), _) => addrs.retain(|addr| version.matches(&addr.ip())),
+        // Как preferred_ip_protocol в blackbox_exporter: сначала предпочтительное семейство, затем остальные.
+        (None, Some(preferred)) => addrs.sort_by_key(|addr| !preferred.matches(&addr.ip())),
+        (None, None) => {}
+    }
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("для {host} нет адресов нужного семейства"),
+        ));
+    }
+    Ok(addrs)
+}
+
+// Общий клиент для проверок без особых настроек и отдельные — для проверок с собственными
+// редиректами, TLS или адресом подключения. Отдельный клиент пересобирается, только когда
+// меняется его ключ (например, DNS вернул другие адреса).
+#[derive(Debug, Clone)]
+struct CheckClients {
+    tuning: CheckHttpClientConfig,
+    shared: Client,
+    custom: Arc<Mutex<HashMap<String, (ClientKey, Client)>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ClientKey {
+    addrs: Vec<SocketAddr>,
+    follow_redirects: bool,
+    max_redirects: usize,
+    accept_invalid_certs: bool,
+    ca_file: Option<PathBuf>,
+}
+
+impl CheckClients {
+    fn new(tuning: &CheckHttpClientConfig) -> Self {
+        Self {
+            tuning: tuning.clone(),
+            shared: check_client_builder(tuning)
+                .build()
+                .unwrap_or_else(|_| Client::new()),
+            custom: Arc::default(),
+        }
+    }
+
+    async fn for_check(
+        &self,
+        cfg: &HttpCheckConfig,
+        preferred: Option<IpVersion>,
+    ) -> io::Result<Client> {
+        let tls = cfg.tls.as_ref();
+        let mut key = ClientKey {
+            addrs: Vec::new(),
+            follow_redirects: cfg.follow_redirects,
+            max_redirects: cfg.max_redirects,
+            accept_invalid_certs: tls.is_some_and(|tls| !tls.verify),
+            ca_file: tls.and_then(|tls| tls.ca_file.clone()),
+        };
+        let mut resolve_host = None;
+        if cfg.resolve_to.is_some() || cfg.ip_version.is_some() || preferred.is_some() {
+            let url = reqwest::Url::parse(&cfg.url)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                key.addrs =
+                    resolve_check_addrs(host, port, cfg.resolve_to, cfg.ip_version, preferred)
+                        .await?;
+                resolve_host = Some(host.to_string());
+            }
+        }
+        let custom_redirects =
+            !cfg.follow_redirects || cfg.max_redirects != default_max_redirects();
+        if key.addrs.is_empty()
+            && !custom_redirects
+            && !key.accept_invalid_certs
+            && key.ca_file.is_none()
+        {
+            return Ok(self.shared.clone());
         }
+
+        let mut custom = self
+            .custom
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((cached, client)) = custom.get(&cfg.name) {
+            if *cached == key {
+                return Ok(client.clone());
+            }
+        }
+        let redirect = if cfg.follow_redirects {
+            Policy::limited(cfg.max_redirects)
+        } else {
+            Policy::none()
+        };
+        let mut builder = check_client_builder(&self.tuning)
+            .redirect(redirect)
+            .danger_accept_invalid_certs(key.accept_invalid_certs);
+        if let Some(path) = &key.ca_file {
+            let pem = std::fs::read(path)?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).map_err(io::Error::other)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(host) = &resolve_host {
+            // Заголовок Host и SNI остаются от исходного URL, меняется только адрес подключения.
+            builder = builder.resolve_to_addrs(host, &key.addrs);
+        }
+        let client = builder.build().map_err(io::Error::other)?;
+        custom.insert(cfg.name.clone(), (key, client.clone()));
+        Ok(client)
+    }
+}
+
+fn check_client_builder(tuning: &CheckHttpClientConfig) -> reqwest::ClientBuilder {
+    let idle_timeout = (tuning.pool_idle_timeout_secs > 0)
+        .then(|| Duration::from_secs(tuning.pool_idle_timeout_secs));
+    let mut builder = Client::builder()
+        .user_agent("monitord/0.1.0")
+        .pool_idle_timeout(idle_timeout);
+    let max_idle = if tuning.keep_alive {
+        tuning.pool_max_idle_per_host
+    } else {
+        Some(0)
+    };
+    if let Some(max_idle) = max_idle {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(secs) = tuning.tcp_keepalive_secs {
+        builder = builder.tcp_keepalive(Duration::from_secs(secs));
+    }
+    builder
+}
+
+async fn run_http_check(
+    clients: &CheckClients,
+    cfg: &HttpCheckConfig,
+    preferred: Option<IpVersion>,
+) -> (HttpCheckResult, bool) {
+    let body_regex = match cfg.body_regex.as_deref().map(Regex::new).transpose() {
+        Ok(body_regex) => body_regex,
         Err(err) => {
+            warn!(check = %cfg.name, error = %err, "http check body_regex is invalid");
+            None
+        }
+    };
+    let mut attempt = 0;
+    loop {
+        let start = Instant::now();
+        let response = match clients.for_check(cfg, preferred).await {
+            Ok(client) => {
+                let mut request = client
+                    .get(&cfg.url)
+                    .timeout(Duration::from_millis(cfg.timeout_ms));
+                for (name, value) in &cfg.headers {
+                    request = request.header(name, value);
+                }
+                request.send().await.map_err(|err| err.to_string())
+            }
+            Err(err) => Err(err.to_string()),
+        };
+        // Задержка повтора не должна попадать в латентность.
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        // error — сбой запроса (он же had_error), reason — причина отказа для уведомлений.
+        let (up, status_code, error, reason) = match response {
+            Ok(resp) => {
+                let code = resp.status().as_u16();
+                let inspect_body = body_regex.is_some() || !cfg.json_assertions.is_empty();
+                if inspect_body && cfg.expected_status.matches(code) {
+                    let limit = if cfg.json_assertions.is_empty() {
+                        MAX_HTTP_BODY_BYTES
+                    } else {
+                        MAX_HTTP_JSON_BYTES
+                    };
+                    match read_body_prefix(resp, limit).await {
+                        Ok(body) => {
+                            let failure = body_regex
+                                .as_ref()
+                                .filter(|re| !re.is_match(&body))
+                                .map(|_| "body_regex did not match".to_string())
+                                .or_else(|| json_assertion_failure(&body, &cfg.json_assertions));
+                            if let Some(reason) = &failure {
+                                debug!(check = %cfg.name, reason = %reason, "http check body mismatch");
+                            }
+                            (failure.is_none(), code, None, failure)
+                        }
+                        Err(err) => (false, code, Some(err.to_string()), None),
+                    }
+                } else {
+                    let up = cfg.expected_status.matches(code);
+                    let reason = (!up).then(|| format!("unexpected status {code}"));
+                    (up, code, None, reason)
+                }
+            }
+            Err(err) => (false, 0, Some(err), None),
+        };
+        if !up && attempt < cfg.retries {
+            attempt += 1;
+            debug!(
+                check = %cfg.name,
+                attempt,
+                status_code,
+                error = error.as_deref().unwrap_or_default(),
+                "http check failed, retrying"
+            );
+            time::sleep(Duration::from_millis(cfg.retry_delay_ms)).await;
+            continue;
+        }
+        if let Some(err) = &error {
             warn!(check = %cfg.name, error = %err, "http check failed");
-            (false, 0, true)
         }
+
+        let had_error = error.is_some();
+        return (
+            HttpCheckResult {
+                name: cfg.name.clone(),
+                up,
+                latency_ms,
+                status_code,
+                error: error.or(reason),
+                affected_by_upstream: None,
+                cached: false,
+            },
+            had_error,
+        );
+    }
+}
+
+async fn read_body_prefix(mut resp: reqwest::Response, limit: usize) -> reqwest::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while body.len() < limit {
+        let Some(chunk) = resp.chunk().await? else {
+            break;
+        };
+        body.extend_from_slice(&chunk);
+    }
+    body.truncate(limit);
+    Ok(body)
+}
+
+// Причина первого невыполненного условия; None, если все условия выполнены.
+fn json_assertion_failure(body: &[u8], assertions: &[JsonAssertion]) -> Option<String> {
+    if assertions.is_empty() {
+        return None;
+    }
+    let document = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(document) => document,
+        Err(err) => return Some(format!("response is not valid JSON: {err}")),
     };
+    assertions.iter().find_map(|assertion| {
+        let path = &assertion.path;
+        let Some(value) = parse_json_path(path)
+            .ok()
+            .and_then(|segments| json_lookup(&document, &segments))
+        else {
+            return Some(format!("{path}: not found"));
+        };
+        if let Some(expected) = &assertion.equals {
+            if !json_values_equal(value, expected) {
+                return Some(format!("{path}: expected {expected}, got {value}"));
+            }
+        }
+        if assertion.min.is_none() && assertion.max.is_none() {
+            return None;
+        }
+        let Some(number) = value.as_f64() else {
+            return Some(format!("{path}: {value} is not a number"));
+        };
+        let below = assertion.min.is_some_and(|min| number < min);
+        let above = assertion.max.is_some_and(|max| number > max);
+        (below || above).then(|| format!("{path}: {number} is out of range"))
+    })
+}
 
-    (
-        HttpCheckResult {
-            name: cfg.name.clone(),
-            up,
-            latency_ms: start.elapsed().as_millis() as u64,
-            status_code,
-        },
-        had_error,
-    )
+fn json_lookup<'a>(
+    document: &'a serde_json::Value,
+    segments: &[JsonPathSegment],
+) -> Option<&'a serde_json::Value> {
+    segments
+        .iter()
+        .try_fold(document, |value, segment| match segment {
+            JsonPathSegment::Key(key) => value.get(key.as_str()),
+            JsonPathSegment::Index(idx) => value.get(*idx),
+        })
+}
+
+// Числа сравниваются по значению: 1 и 1.0 в JSON-ответе одинаковы.
+fn json_values_equal(actual: &serde_json::Value, expected: &serde_json::Value) -> bool {
+    match (actual.as_f64(), expected.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => actual == expected,
+    }
 }
 
-async fn run_tcp_check(cfg: &TcpCheckConfig) -> (TcpCheckResult, bool) {
+async fn run_tcp_check(
+    cfg: &TcpCheckConfig,
+    preferred: Option<IpVersion>,
+) -> (TcpCheckResult, bool) {
     let start = Instant::now();
     let addr = format!("{}:{}", cfg.host, cfg.port);
+    let expect = match cfg.expect_regex.as_deref().map(Regex::new).transpose() {
+        Ok(expect) => expect,
+        Err(err) => {
+            warn!(check = %cfg.name, error = %err, "tcp check expect_regex is invalid");
+            None
+        }
+    };
+    let cert_valid = Arc::new(OnceLock::new());
+    let probe = async {
+        let addrs = resolve_check_addrs(
+            &cfg.host,
+            cfg.port,
+            cfg.resolve_to,
+            cfg.ip_version,
+            preferred,
+        )
+        .await?;
+        let stream = TcpStream::connect(&addrs[..]).await?;
+        let (Some(tls), Some(server_name)) = (&cfg.tls, cfg.tls_server_name()) else {
+            let matched = tcp_exchange(stream, cfg.send.as_deref(), expect.as_ref()).await?;
+            return Ok((matched, None));
+        };
+        let handshake_start = Instant::now();
+        let stream = tls_handshake(stream, server_name, tls, cert_valid.clone()).await?;
+        let handshake_ms = handshake_start.elapsed().as_millis() as u64;
+        let matched = tcp_exchange(stream, cfg.send.as_deref(), expect.as_ref()).await?;
+        Ok::<_, io::Error>((matched, Some(handshake_ms)))
+    };
 
-    let (up, had_error) = match time::timeout(
+    let (up, had_error, error, tls_handshake_ms) = match time::timeout(
         Duration::from_millis(cfg.timeout_ms),
-        TcpStream::connect(&addr),
+        probe,
     )
     .await
     {
-        Ok(Ok(_stream)) => (true, false),
+        Ok(Ok((true, handshake_ms))) => (true, false, None, handshake_ms),
+        Ok(Ok((false, handshake_ms))) => {
+            warn!(check = %cfg.name, address = %addr, "tcp check response does not match expect_regex");
+            let reason = "response does not match expect_regex".to_string();
+            (false, false, Some(reason), handshake_ms)
+        }
         Ok(Err(err)) => {
             warn!(check = %cfg.name, address = %addr, error = %err, "tcp check failed");
-            (false, true)
+            (false, true, Some(err.to_string()), None)
         }
         Err(_elapsed) => {
             warn!(check = %cfg.name, address = %addr, "tcp check timeout");
-            (false, true)
+            (false, true, Some("timeout".to_string()), None)
         }
     };
 
@@ -89,7 +796,794 @@ async fn run_tcp_check(cfg: &TcpCheckConfig) -> (TcpCheckResult, bool) {
             name: cfg.name.clone(),
             up,
             latency_ms: start.elapsed().as_millis() as u64,
+            tls_handshake_ms,
+            tls_cert_valid: cert_valid.get().copied(),
+            error,
+            affected_by_upstream: None,
+            cached: false,
         },
         had_error,
     )
 }
+
+async fn run_ssh_check(
+    cfg: &SshCheckConfig,
+    preferred: Option<IpVersion>,
+) -> (SshCheckResult, bool) {
+    let start = Instant::now();
+    let addr = format!("{}:{}", cfg.host, cfg.port);
+    let probe = async {
+        let addrs = resolve_check_addrs(
+            &cfg.host,
+            cfg.port,
+            cfg.resolve_to,
+            cfg.ip_version,
+            preferred,
+        )
+        .await?;
+        let session = SshSession::connect(TcpStream::connect(&addrs[..]).await?).await?;
+        let banner = session.banner().to_string();
+        if cfg.host_key_fingerprint.is_none() {
+            return Ok::<_, io::Error>((banner, None));
+        }
+        let host_key = session.host_key().await?;
+        Ok((banner, Some(host_key.fingerprint)))
+    };
+
+    let (banner, fingerprint, error) =
+        match time::timeout(Duration::from_millis(cfg.timeout_ms), probe).await {
+            Ok(Ok((banner, fingerprint))) => (Some(banner), fingerprint, None),
+            Ok(Err(err)) => {
+                warn!(check = %cfg.name, address = %addr, error = %err, "ssh check failed");
+                (None, None, Some(err.to_string()))
+            }
+            Err(_elapsed) => {
+                warn!(check = %cfg.name, address = %addr, "ssh check timeout");
+                (None, None, Some("timeout".to_string()))
+            }
+        };
+    let had_error = error.is_some();
+    let host_key_matches = cfg
+        .host_key_fingerprint
+        .as_deref()
+        .zip(fingerprint.as_deref())
+        .map(|(expected, actual)| expected == actual);
+    if host_key_matches == Some(false) {
+        // Смена ключа хоста — возможный признак MITM, поэтому проверка считается упавшей.
+        warn!(
+            check = %cfg.name,
+            address = %addr,
+            fingerprint = fingerprint.as_deref().unwrap_or_default(),
+            "ssh host key fingerprint does not match"
+        );
+    }
+    let error = error.or_else(|| {
+        (host_key_matches == Some(false)).then(|| "host key fingerprint does not match".to_string())
+    });
+
+    (
+        SshCheckResult {
+            name: cfg.name.clone(),
+            up: banner.is_some() && host_key_matches != Some(false),
+            latency_ms: start.elapsed().as_millis() as u64,
+            banner,
+            host_key_fingerprint: fingerprint,
+            host_key_matches,
+            error,
+            affected_by_upstream: None,
+            cached: false,
+        },
+        had_error,
+    )
+}
+
+async fn tls_handshake(
+    stream: TcpStream,
+    server_name: &str,
+    tls: &CheckTlsConfig,
+    cert_valid: Arc<OnceLock<bool>>,
+) -> io::Result<TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = &tls.ca_file {
+        add_pem_roots(&mut roots, path)?;
+    }
+    let provider = Arc::new(ring::default_provider());
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(io::Error::other)?;
+    let verifier = RecordingVerifier {
+        inner,
+        verify: tls.verify,
+        valid: cert_valid,
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    let name = ServerName::try_from(server_name.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+}
+
+// Проверяет сертификат обычным способом и запоминает результат; при verify: false не обрывает рукопожатие.
+fn add_pem_roots(roots: &mut RootCertStore, path: &Path) -> io::Result<()> {
+    let pem = std::fs::read(path)?;
+    for cert in CertificateDer::pem_slice_iter(&pem) {
+        roots
+            .add(cert.map_err(io::Error::other)?)
+            .map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    verify: bool,
+    valid: Arc<OnceLock<bool>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        );
+        let _ = self.valid.set(result.is_ok());
+        match result {
+            Err(err) if self.verify => Err(err),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// Отправляет send и читает ответ, пока он не совпадёт с expect или сервер не закроет соединение.
+async fn tcp_exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    send: Option<&str>,
+    expect: Option<&Regex>,
+) -> io::Result<bool> {
+    if let Some(send) = send {
+        stream.write_all(send.as_bytes()).await?;
+    }
+    let Some(expect) = expect else {
+        return Ok(true);
+    };
+    let mut response = Vec::new();
+    let mut chunk = [0_u8; 512];
+    loop {
+        if expect.is_match(&response) {
+            return Ok(true);
+        }
+        if response.len() >= MAX_TCP_RESPONSE_BYTES {
+            return Ok(false);
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(false);
+        }
+        response.extend_from_slice(&chunk[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_with_own_interval_wait_until_due() {
+        let mut schedule = CheckSchedule::default();
+        let id = CheckId {
+            kind: CheckKind::Tcp,
+            name: "db".to_string(),
+        };
+        assert!(schedule.is_due(&id, 0));
+        schedule.schedule(id.clone(), 300, 0, 0);
+        assert!(!schedule.is_due(&id, 299));
+        assert!(schedule.is_due(&id, 300));
+
+        schedule.schedule(id.clone(), 60, 30, 1000);
+        let due = schedule.next_due[&id];
+        assert!((1060..=1090).contains(&due));
+    }
+
+    #[tokio::test]
+    async fn checks_run_on_their_own_timers_between_collection_ticks() {
+        use crate::config::CheckAlertPolicy;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { while listener.accept().await.is_ok() {} });
+        let tcp = |name: &str, interval_secs: Option<u64>| TcpCheckConfig {
+            name: name.to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            timeout_ms: 2000,
+            send: None,
+            expect_regex: None,
+            tls: None,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let mut cfg: Config =
+            serde_yaml::from_str("listen: \"127.0.0.1:0\"\ninterval_secs: 60\n").unwrap();
+        cfg.tcp_checks = vec![tcp("fast", Some(1)), tcp("slow", None)];
+        cfg.validate().unwrap();
+
+        let schedule = SharedCheckSchedule::default();
+        let mut runner = CheckRunner::new(schedule.clone());
+        runner.start_due(&cfg, 0);
+        assert_eq!(runner.running.len(), 2);
+        while runner.join_next().await.is_some() {}
+        let cached = |results: &CheckResults| {
+            results
+                .tcp
+                .iter()
+                .map(|r| (r.name.clone(), r.cached))
+                .collect::<Vec<_>>()
+        };
+        let (results, _) = take_check_results(&cfg, &schedule);
+        assert_eq!(
+            cached(&results),
+            [("fast".to_string(), false), ("slow".to_string(), false)]
+        );
+
+        // Каждую секунду снова запускается только проверка с interval_secs: 1, такт сбора ни при чём.
+        for now in 1..=3 {
+            runner.start_due(&cfg, now);
+            assert_eq!(runner.running.len(), 1);
+            while runner.join_next().await.is_some() {}
+        }
+        let (results, _) = take_check_results(&cfg, &schedule);
+        assert!(results.tcp.iter().all(|r| r.up));
+        assert_eq!(
+            cached(&results),
+            [("fast".to_string(), false), ("slow".to_string(), true)]
+        );
+        runner.start_due(&cfg, 60);
+        assert_eq!(runner.running.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn panicking_check_is_reported_down_with_panic_message() {
+        let cfg: Config = serde_yaml::from_str(
+            "listen: \"127.0.0.1:0\"\ninterval_secs: 60\ntcp_checks:\n  - name: db\n    host: 127.0.0.1\n    port: 1\n    timeout_ms: 100\n",
+        )
+        .unwrap();
+        let schedule = SharedCheckSchedule::default();
+        let mut runner = CheckRunner::new(schedule.clone());
+        let handle = runner.tasks.spawn(async { panic!("сбой разбора ответа") });
+        runner.running.insert(
+            handle.id(),
+            CheckId {
+                kind: CheckKind::Tcp,
+                name: "db".to_string(),
+            },
+        );
+        assert!(runner.join_next().await.is_some());
+        assert!(runner.running.is_empty());
+
+        let (results, errors) = take_check_results(&cfg, &schedule);
+        assert_eq!(errors, 1);
+        let [db] = results.tcp.as_slice() else {
+            panic!("ожидался один результат tcp: {:?}", results.tcp);
+        };
+        assert!(!db.up && !db.cached);
+        let formatted: Box<dyn std::any::Any + Send> = Box::new(format!("код {}", 7));
+        assert_eq!(panic_message(&*formatted), "код 7");
+    }
+
+    #[tokio::test]
+    async fn custom_check_clients_are_reused_until_settings_change() {
+        use crate::config::CheckAlertPolicy;
+
+        let mut check = HttpCheckConfig {
+            name: "api".to_string(),
+            url: "http://127.0.0.1:8080/".to_string(),
+            timeout_ms: 2000,
+            expected_status: 200.into(),
+            headers: HashMap::new(),
+            body_regex: None,
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: false,
+            max_redirects: 10,
+            retries: 0,
+            retry_delay_ms: 10,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let mut schedule = CheckSchedule::default();
+        let tuning = CheckHttpClientConfig::default();
+        let clients = schedule.clients(&tuning);
+        let cached_key = |clients: &CheckClients| {
+            let custom = clients.custom.lock().unwrap();
+            (custom.len(), custom.get("api").map(|(key, _)| key.clone()))
+        };
+        clients.for_check(&check, None).await.unwrap();
+        clients.for_check(&check, None).await.unwrap();
+        let (count, key) = cached_key(&clients);
+        assert_eq!(count, 1);
+        assert!(key.unwrap().addrs.is_empty());
+
+        check.resolve_to = Some("127.0.0.2".parse().unwrap());
+        clients.for_check(&check, None).await.unwrap();
+        let (count, key) = cached_key(&clients);
+        assert_eq!(count, 1);
+        assert_eq!(key.unwrap().addrs, vec!["127.0.0.2:8080".parse().unwrap()]);
+
+        assert_eq!(cached_key(&schedule.clients(&tuning)).0, 1);
+        let tuning = CheckHttpClientConfig {
+            keep_alive: false,
+            ..tuning
+        };
+        assert_eq!(cached_key(&schedule.clients(&tuning)).0, 0);
+    }
+
+    #[tokio::test]
+    async fn resolve_to_keeps_host_header_and_ip_version_filters_addresses() {
+        use crate::config::CheckAlertPolicy;
+        use axum::http::{HeaderMap, StatusCode};
+
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(|headers: HeaderMap| async move {
+                match headers.get("host").and_then(|v| v.to_str().ok()) {
+                    Some(host) if host.starts_with("backend.invalid:") => StatusCode::OK,
+                    _ => StatusCode::MISDIRECTED_REQUEST,
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let check = HttpCheckConfig {
+            name: "backend-1".to_string(),
+            url: format!("http://backend.invalid:{port}/"),
+            timeout_ms: 2000,
+            expected_status: 200.into(),
+            headers: HashMap::new(),
+            body_regex: None,
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            retries: 0,
+            retry_delay_ms: 0,
+            resolve_to: Some("127.0.0.1".parse().unwrap()),
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let (result, had_error) = run_http_check(
+            &CheckClients::new(&CheckHttpClientConfig::default()),
+            &check,
+            None,
+        )
+        .await;
+        assert!(result.up && !had_error);
+
+        let v4 = resolve_check_addrs("127.0.0.1", 80, None, Some(IpVersion::V4), None).await;
+        assert_eq!(v4.unwrap(), vec!["127.0.0.1:80".parse().unwrap()]);
+        assert!(
+            resolve_check_addrs("127.0.0.1", 80, None, Some(IpVersion::V6), None)
+                .await
+                .is_err()
+        );
+        let preferred = resolve_check_addrs("127.0.0.1", 80, None, None, Some(IpVersion::V6));
+        assert_eq!(preferred.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn http_check_retries_absorb_single_failures() {
+        use crate::config::CheckAlertPolicy;
+        use axum::http::StatusCode;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || async move {
+                // Каждый нечётный запрос завершается ошибкой.
+                if counter.fetch_add(1, Ordering::SeqCst).is_multiple_of(2) {
+                    StatusCode::BAD_GATEWAY
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut check = HttpCheckConfig {
+            name: "flaky".to_string(),
+            url: format!("http://{addr}/"),
+            timeout_ms: 2000,
+            expected_status: 200.into(),
+            headers: HashMap::new(),
+            body_regex: None,
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            retries: 0,
+            retry_delay_ms: 10,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let client = CheckClients::new(&CheckHttpClientConfig::default());
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert!(!result.up);
+        assert_eq!(result.status_code, 502);
+
+        hits.store(0, Ordering::SeqCst);
+        check.retries = 2;
+        let (result, had_error) = run_http_check(&client, &check, None).await;
+        assert!(result.up && !had_error);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expected_status_and_redirect_policy_are_applied() {
+        use crate::config::CheckAlertPolicy;
+        use axum::http::StatusCode;
+        use axum::response::Redirect;
+        use axum::routing::get;
+
+        let app = axum::Router::new()
+            .route("/chain", get(|| async { Redirect::permanent("/old") }))
+            .route("/old", get(|| async { Redirect::permanent("/new") }))
+            .route("/new", get(|| async { StatusCode::NO_CONTENT }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut check = HttpCheckConfig {
+            name: "moved".to_string(),
+            url: format!("http://{addr}/old"),
+            timeout_ms: 2000,
+            expected_status: 200.into(),
+            headers: HashMap::new(),
+            body_regex: None,
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            retries: 0,
+            retry_delay_ms: 0,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let client = CheckClients::new(&CheckHttpClientConfig::default());
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert!(!result.up);
+        assert_eq!(result.status_code, 204);
+
+        check.expected_status = serde_yaml::from_str("[200, 2xx]").unwrap();
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert!(result.up);
+
+        check.follow_redirects = false;
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert_eq!((result.up, result.status_code), (false, 308));
+        check.expected_status = serde_yaml::from_str("3xx").unwrap();
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert!(result.up);
+
+        check.follow_redirects = true;
+        check.max_redirects = 1;
+        check.url = format!("http://{addr}/chain");
+        let (result, had_error) = run_http_check(&client, &check, None).await;
+        assert!(!result.up && had_error);
+    }
+
+    #[tokio::test]
+    async fn http_check_sends_headers_and_matches_body() {
+        use crate::config::CheckAlertPolicy;
+        use axum::http::HeaderMap;
+        use axum::routing::get;
+
+        let app = axum::Router::new().route(
+            "/status",
+            get(|headers: HeaderMap| async move {
+                match headers.get("x-token").and_then(|v| v.to_str().ok()) {
+                    Some("secret") => "{\"status\":\"ok\"}",
+                    _ => "{\"status\":\"denied\"}",
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut check = HttpCheckConfig {
+            name: "status".to_string(),
+            url: format!("http://{addr}/status"),
+            timeout_ms: 2000,
+            expected_status: 200.into(),
+            headers: HashMap::from([("X-Token".to_string(), "secret".to_string())]),
+            body_regex: Some(r#""status":"ok""#.to_string()),
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            retries: 0,
+            retry_delay_ms: 0,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let client = CheckClients::new(&CheckHttpClientConfig::default());
+        let (result, _) = run_http_check(&client, &check, None).await;
+        assert!(result.up);
+
+        check.headers.clear();
+        let (result, had_error) = run_http_check(&client, &check, None).await;
+        assert!(!result.up && !had_error);
+        assert_eq!(result.status_code, 200);
+        assert_eq!(result.error.as_deref(), Some("body_regex did not match"));
+    }
+
+    #[test]
+    fn json_assertions_check_values_and_ranges() {
+        let assertion = |path: &str, equals: Option<serde_json::Value>, min, max| JsonAssertion {
+            path: path.to_string(),
+            equals,
+            min,
+            max,
+        };
+        let body = br#"{"status":"ok","queue":{"depth":42},"nodes":[{"up":true}],"a.b":1.0}"#;
+        let passing = [
+            assertion("$.status", Some("ok".into()), None, None),
+            assertion("$.queue.depth", None, None, Some(100.0)),
+            assertion("nodes[0].up", Some(true.into()), None, None),
+            assertion("$['a.b']", Some(1.into()), None, None),
+        ];
+        assert_eq!(json_assertion_failure(body, &passing), None);
+
+        let failure = |a: JsonAssertion| json_assertion_failure(body, &[a]).unwrap();
+        assert_eq!(
+            failure(assertion("$.status", Some("degraded".into()), None, None)),
+            r#"$.status: expected "degraded", got "ok""#
+        );
+        assert_eq!(
+            failure(assertion("$.queue.depth", None, Some(50.0), None)),
+            "$.queue.depth: 42 is out of range"
+        );
+        assert_eq!(
+            failure(assertion("$.nodes[3]", None, None, None)),
+            "$.nodes[3]: not found"
+        );
+        assert!(failure(assertion("$.status", None, Some(1.0), None)).contains("not a number"));
+        assert!(json_assertion_failure(b"<html>", &passing)
+            .unwrap()
+            .starts_with("response is not valid JSON"));
+
+        assert!(parse_json_path("$.items[x]").is_err());
+        assert!(parse_json_path("$..status").is_err());
+        assert_eq!(parse_json_path("$").unwrap(), Vec::new());
+    }
+
+    #[tokio::test]
+    async fn tcp_check_sends_and_matches_response() {
+        use crate::config::CheckAlertPolicy;
+
+        // Сервер в духе Redis: отвечает +PONG на PING и закрывает соединение.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0_u8; 64];
+                let read = socket.read(&mut buf).await.unwrap_or(0);
+                let reply: &[u8] = if buf[..read].starts_with(b"PING") {
+                    b"+PONG\r\n"
+                } else {
+                    b"-ERR unknown command\r\n"
+                };
+                let _ = socket.write_all(reply).await;
+            }
+        });
+
+        let mut check = TcpCheckConfig {
+            name: "redis".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            timeout_ms: 2000,
+            send: Some("PING\r\n".to_string()),
+            expect_regex: Some(r"^\+PONG".to_string()),
+            tls: None,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let (result, had_error) = run_tcp_check(&check, None).await;
+        assert!(result.up && !had_error);
+
+        check.send = Some("HELLO\r\n".to_string());
+        let (result, had_error) = run_tcp_check(&check, None).await;
+        assert!(!result.up && !had_error);
+    }
+
+    #[tokio::test]
+    async fn tcp_check_reports_tls_handshake_and_certificate_validity() {
+        use crate::config::{CheckAlertPolicy, CheckTlsConfig};
+        use tokio_rustls::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+        use tokio_rustls::rustls::ServerConfig;
+        use tokio_rustls::TlsAcceptor;
+
+        // IMAPS-подобный сервер с самоподписанным сертификатом: приветствует после рукопожатия.
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+            certified.signing_key.serialize_der(),
+        ));
+        let server = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                if let Ok(mut tls) = acceptor.accept(socket).await {
+                    let _ = tls.write_all(b"* OK IMAP4rev1 ready\r\n").await;
+                    let _ = tls.shutdown().await;
+                }
+            }
+        });
+
+        let mut check = TcpCheckConfig {
+            name: "imaps".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            timeout_ms: 2000,
+            send: None,
+            expect_regex: Some(r"^\* OK".to_string()),
+            tls: Some(CheckTlsConfig {
+                server_name: Some("localhost".to_string()),
+                verify: false,
+                ca_file: None,
+            }),
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let (result, had_error) = run_tcp_check(&check, None).await;
+        assert!(result.up && !had_error);
+        assert!(result.tls_handshake_ms.is_some());
+        assert_eq!(result.tls_cert_valid, Some(false));
+
+        check.tls.as_mut().unwrap().verify = true;
+        let (result, had_error) = run_tcp_check(&check, None).await;
+        assert!(!result.up && had_error);
+        assert_eq!(result.tls_handshake_ms, None);
+        assert_eq!(result.tls_cert_valid, Some(false));
+    }
+
+    #[tokio::test]
+    async fn checks_run_concurrently_up_to_the_limit() {
+        use crate::config::CheckAlertPolicy;
+        use axum::http::StatusCode;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (current, max) = (in_flight.clone(), peak.clone());
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || async move {
+                let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(now, Ordering::SeqCst);
+                time::sleep(Duration::from_millis(100)).await;
+                current.fetch_sub(1, Ordering::SeqCst);
+                StatusCode::OK
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut cfg: Config = serde_yaml::from_str(
+            "listen: \"127.0.0.1:0\"\ninterval_secs: 5\nchecks:\n  max_concurrency: 2\n",
+        )
+        .unwrap();
+        cfg.http_checks = (0..4)
+            .map(|i| HttpCheckConfig {
+                name: format!("api-{i}"),
+                url: format!("http://{addr}/"),
+                timeout_ms: 2000,
+                expected_status: 200.into(),
+                headers: HashMap::new(),
+                body_regex: None,
+                json_assertions: Vec::new(),
+                tls: None,
+                follow_redirects: true,
+                max_redirects: 10,
+                retries: 0,
+                retry_delay_ms: 0,
+                resolve_to: None,
+                ip_version: None,
+                interval_secs: None,
+                jitter_secs: 0,
+                depends_on: Vec::new(),
+                alerts: CheckAlertPolicy::default(),
+            })
+            .collect();
+
+        let (results, errors) = collect_checks(&cfg, &SharedCheckSchedule::default(), 0).await;
+        assert_eq!(errors, 0);
+        let names: Vec<_> = results.http.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["api-0", "api-1", "api-2", "api-3"]);
+        assert!(results.http.iter().all(|r| r.up));
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
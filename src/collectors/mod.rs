@@ -1,7 +1,16 @@
 ﻿pub mod checks;
+#[cfg(target_os = "windows")]
+mod pdh;
+pub mod plugins;
+mod ssh;
 pub mod system;
+#[cfg(target_os = "windows")]
+mod wmi_sensors;
 
-use crate::state::{DiskStat, GpuStat, NetStat, SensorStat, TempStat};
+use crate::state::{
+    CgroupStat, CpuStealStat, DiskIoStat, DiskStat, GpuStat, NetStat, PageFileStat, SensorStat,
+    TempStat, TimeSyncStat, WireguardPeerStat,
+};
 
 #[derive(Debug, Clone)]
 pub struct SystemSnapshot {
@@ -14,11 +23,20 @@ pub struct SystemSnapshot {
     pub process_count: u64,
     pub cpu_core_count: u32,
     pub cpu_usage_percent: f64,
+    pub cpu_steal: Option<CpuStealStat>,
+    pub virtualization: Option<String>,
+    pub time_sync: Option<TimeSyncStat>,
+    pub cgroup: Option<CgroupStat>,
     pub memory_used_bytes: u64,
     pub memory_total_bytes: u64,
+    pub page_file: Option<PageFileStat>,
     pub disks: Vec<DiskStat>,
+    pub disk_io: Vec<DiskIoStat>,
     pub net: Vec<NetStat>,
+    pub wireguard: Vec<WireguardPeerStat>,
     pub temps: Vec<TempStat>,
     pub gpus: Vec<GpuStat>,
     pub sensors: Vec<SensorStat>,
+    // Источники данных внутри системного сборщика и признак того, что они что-то вернули.
+    pub sources: Vec<(&'static str, bool)>,
 }
@@ -0,0 +1,180 @@
+use crate::config::WindowsCounterConfig;
+use std::cell::RefCell;
+use std::ptr;
+use tracing::debug;
+use windows_sys::Win32::System::Performance::{
+    PdhAddCounterW, PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData,
+    PdhGetFormattedCounterArrayW, PdhOpenQueryW, PDH_CSTATUS_NEW_DATA, PDH_CSTATUS_VALID_DATA,
+    PDH_FMT_COUNTERVALUE_ITEM_W, PDH_FMT_DOUBLE, PDH_HCOUNTER, PDH_HQUERY, PDH_MORE_DATA,
+};
+
+const ERROR_SUCCESS: u32 = 0;
+
+pub struct CounterSample {
+    // Индекс счётчика в windows_counters.
+    pub counter: usize,
+    pub instance: Option<String>,
+    pub value: f64,
+}
+
+struct Query {
+    handle: PDH_HQUERY,
+    paths: Vec<String>,
+    counters: Vec<(usize, PDH_HCOUNTER)>,
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe {
+            PdhCloseQuery(self.handle);
+        }
+    }
+}
+
+thread_local! {
+    // Запрос живёт в потоке сбора: счётчикам-скоростям (% Processor Time и т. п.) нужен предыдущий замер,
+    // а повторное открытие запроса на каждом такте дорого.
+    static QUERY: RefCell<Option<Query>> = const { RefCell::new(None) };
+}
+
+pub fn sample(counters: &[WindowsCounterConfig]) -> Vec<CounterSample> {
+    QUERY.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        let stale = slot
+            .as_ref()
+            .is_none_or(|query| query.paths.iter().ne(counters.iter().map(|c| &c.path)));
+        if stale {
+            // Список счётчиков поменялся при перезагрузке конфигурации: запрос собирается заново.
+            *slot = None;
+            *slot = open(counters);
+        }
+        let Some(query) = slot.as_ref() else {
+            return Vec::new();
+        };
+        let status = unsafe { PdhCollectQueryData(query.handle) };
+        if status != ERROR_SUCCESS {
+            debug!(
+                status = format!("{status:#x}"),
+                "PdhCollectQueryData завершился с ошибкой"
+            );
+            return Vec::new();
+        }
+        query
+            .counters
+            .iter()
+            .flat_map(|&(counter, handle)| {
+                read_counter(handle)
+                    .into_iter()
+                    .map(move |(instance, value)| CounterSample {
+                        counter,
+                        instance,
+                        value,
+                    })
+            })
+            .collect()
+    })
+}
+
+fn open(counters: &[WindowsCounterConfig]) -> Option<Query> {
+    if counters.is_empty() {
+        return None;
+    }
+    let mut handle = ptr::null_mut();
+    let status = unsafe { PdhOpenQueryW(ptr::null(), 0, &mut handle) };
+    if status != ERROR_SUCCESS {
+        debug!(
+            status = format!("{status:#x}"),
+            "не удалось открыть запрос PDH"
+        );
+        return None;
+    }
+    let mut query = Query {
+        handle,
+        paths: counters.iter().map(|c| c.path.clone()).collect(),
+        counters: Vec::new(),
+    };
+    for (idx, counter) in counters.iter().enumerate() {
+        let path = to_wide(&counter.path);
+        let mut counter_handle = ptr::null_mut();
+        // Сначала английские имена, затем имена на языке системы, как их показывает Get-Counter.
+        let mut status =
+            unsafe { PdhAddEnglishCounterW(handle, path.as_ptr(), 0, &mut counter_handle) };
+        if status != ERROR_SUCCESS {
+            status = unsafe { PdhAddCounterW(handle, path.as_ptr(), 0, &mut counter_handle) };
+        }
+        if status == ERROR_SUCCESS {
+            query.counters.push((idx, counter_handle));
+        } else {
+            debug!(path = %counter.path, status = format!("{status:#x}"), "счётчик Windows не найден");
+        }
+    }
+    // Первый замер — база для счётчиков-скоростей: их значения появятся со следующего такта.
+    unsafe {
+        PdhCollectQueryData(handle);
+    }
+    Some(query)
+}
+
+fn read_counter(counter: PDH_HCOUNTER) -> Vec<(Option<String>, f64)> {
+    let mut size = 0u32;
+    let mut count = 0u32;
+    let status = unsafe {
+        PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut size,
+            &mut count,
+            ptr::null_mut(),
+        )
+    };
+    if status != PDH_MORE_DATA || size == 0 {
+        return Vec::new();
+    }
+    // В том же буфере после массива лежат строки имён экземпляров, поэтому размер берётся в байтах.
+    let item_size = std::mem::size_of::<PDH_FMT_COUNTERVALUE_ITEM_W>();
+    let mut buffer =
+        vec![PDH_FMT_COUNTERVALUE_ITEM_W::default(); (size as usize).div_ceil(item_size)];
+    let status = unsafe {
+        PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut size,
+            &mut count,
+            buffer.as_mut_ptr(),
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return Vec::new();
+    }
+    buffer
+        .iter()
+        .take(count as usize)
+        .filter(|item| {
+            matches!(
+                item.FmtValue.CStatus,
+                PDH_CSTATUS_VALID_DATA | PDH_CSTATUS_NEW_DATA
+            )
+        })
+        .filter_map(|item| {
+            let value = unsafe { item.FmtValue.Anonymous.doubleValue };
+            let instance = unsafe { from_wide(item.szName) };
+            value.is_finite().then_some((instance, value))
+        })
+        .collect()
+}
+
+fn to_wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+unsafe fn from_wide(ptr: *const u16) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    let text = String::from_utf16_lossy(std::slice::from_raw_parts(ptr, len));
+    (!text.is_empty()).then_some(text)
+}
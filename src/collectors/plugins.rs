@@ -0,0 +1,247 @@
+use crate::config::PluginConfig;
+use crate::state::SensorStat;
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::task::JoinSet;
+
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+const STDERR_TAIL_CHARS: usize = 300;
+
+pub type CollectFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<SensorStat>, PluginError>> + Send + 'a>>;
+
+// Источник дополнительных показателей; результаты попадают в sensors наравне с системными датчиками.
+pub trait Collector: Send + Sync {
+    fn name(&self) -> &str;
+    fn collect(&self) -> CollectFuture<'_>;
+}
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("не удалось запустить: {0}")]
+    Spawn(std::io::Error),
+    #[error("таймаут {0} мс")]
+    Timeout(u64),
+    #[error("завершился с кодом {code:?}: {stderr}")]
+    Exit { code: Option<i32>, stderr: String },
+    #[error("некорректный ответ: {0}")]
+    Protocol(String),
+}
+
+#[derive(serde::Serialize)]
+struct PluginRequest<'a> {
+    protocol: u32,
+    plugin: &'a str,
+    ts_unix: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct PluginResponse {
+    #[serde(default)]
+    metrics: Vec<PluginMetric>,
+}
+
+#[derive(serde::Deserialize)]
+struct PluginMetric {
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    value: f64,
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default)]
+    max: Option<f64>,
+}
+
+pub struct ProcessCollector {
+    cfg: PluginConfig,
+}
+
+impl ProcessCollector {
+    pub fn new(cfg: PluginConfig) -> Self {
+        Self { cfg }
+    }
+
+    async fn run(&self) -> Result<Vec<SensorStat>, PluginError> {
+        let mut child = Command::new(&self.cfg.command)
+            .args(&self.cfg.args)
+            .envs(&self.cfg.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(PluginError::Spawn)?;
+
+        let request = serde_json::to_string(&PluginRequest {
+            protocol: PLUGIN_PROTOCOL_VERSION,
+            plugin: &self.cfg.name,
+            ts_unix: now_unix(),
+        })
+        .map_err(|err| PluginError::Protocol(err.to_string()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            tokio::spawn(async move {
+                let _ = stdin.write_all(request.as_bytes()).await;
+                let _ = stdin.write_all(b"\n").await;
+            });
+        }
+
+        let timeout = Duration::from_millis(self.cfg.timeout_ms);
+        let output = tokio::time::timeout(timeout, child.wait_with_output())
+            .await
+            .map_err(|_| PluginError::Timeout(self.cfg.timeout_ms))?
+            .map_err(PluginError::Spawn)?;
+        if !output.status.success() {
+            return Err(PluginError::Exit {
+                code: output.status.code(),
+                stderr: stderr_tail(&output.stderr),
+            });
+        }
+        parse_response(&self.cfg.name, &output.stdout)
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn name(&self) -> &str {
+        &self.cfg.name
+    }
+
+    fn collect(&self) -> CollectFuture<'_> {
+        Box::pin(self.run())
+    }
+}
+
+pub fn build_collectors(plugins: &[PluginConfig]) -> Vec<Arc<dyn Collector>> {
+    plugins
+        .iter()
+        .map(|cfg| Arc::new(ProcessCollector::new(cfg.clone())) as Arc<dyn Collector>)
+        .collect()
+}
+
+pub async fn collect_plugins(
+    collectors: &[Arc<dyn Collector>],
+) -> (Vec<SensorStat>, Vec<(String, PluginError)>) {
+    let mut tasks = JoinSet::new();
+    for (idx, collector) in collectors.iter().enumerate() {
+        let collector = collector.clone();
+        tasks.spawn(async move { (idx, collector.collect().await) });
+    }
+    let mut results = Vec::with_capacity(collectors.len());
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(result) = joined {
+            results.push(result);
+        }
+    }
+    // Порядок датчиков не должен зависеть от того, какой плагин ответил первым.
+    results.sort_by_key(|(idx, _)| *idx);
+
+    let mut sensors = Vec::new();
+    let mut errors = Vec::new();
+    for (idx, result) in results {
+        match result {
+            Ok(stats) => sensors.extend(stats),
+            Err(err) => errors.push((collectors[idx].name().to_string(), err)),
+        }
+    }
+    (sensors, errors)
+}
+
+fn parse_response(plugin: &str, stdout: &[u8]) -> Result<Vec<SensorStat>, PluginError> {
+    let response: PluginResponse =
+        serde_json::from_slice(stdout).map_err(|err| PluginError::Protocol(err.to_string()))?;
+    response
+        .metrics
+        .into_iter()
+        .map(|metric| {
+            if metric.name.trim().is_empty() {
+                return Err(PluginError::Protocol(
+                    "metrics[*].name не должен быть пустым".to_string(),
+                ));
+            }
+            if !metric.value.is_finite() {
+                return Err(PluginError::Protocol(format!(
+                    "значение '{}' не является числом",
+                    metric.name
+                )));
+            }
+            Ok(SensorStat {
+                sensor_type: metric.kind.unwrap_or_else(|| "plugin".to_string()),
+                identifier: format!("plugin/{plugin}/{}", metric.name),
+                name: metric.name,
+                parent: plugin.to_string(),
+                value: metric.value,
+                min: metric.min,
+                max: metric.max,
+            })
+        })
+        .collect()
+}
+
+fn stderr_tail(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let text = text.trim();
+    let count = text.chars().count();
+    if count <= STDERR_TAIL_CHARS {
+        return text.to_string();
+    }
+    text.chars().skip(count - STDERR_TAIL_CHARS).collect()
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, script: &str) -> PluginConfig {
+        PluginConfig {
+            name: name.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: [("QUEUE".to_string(), "orders".to_string())].into(),
+            timeout_ms: 2000,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn process_plugins_report_sensors_and_errors() {
+        let collectors = build_collectors(&[
+            plugin(
+                "queues",
+                r#"read req; case "$req" in *'"protocol":1'*) ;; *) exit 3;; esac
+                   echo "{\"metrics\":[{\"name\":\"$QUEUE\",\"type\":\"queue\",\"value\":42,\"max\":100}]}""#,
+            ),
+            plugin("broken", "echo oops >&2; exit 2"),
+            plugin("garbage", "echo not-json"),
+        ]);
+
+        let (sensors, errors) = collect_plugins(&collectors).await;
+        assert_eq!(sensors.len(), 1);
+        assert_eq!(sensors[0].sensor_type, "queue");
+        assert_eq!(sensors[0].name, "orders");
+        assert_eq!(sensors[0].parent, "queues");
+        assert_eq!(sensors[0].identifier, "plugin/queues/orders");
+        assert_eq!(sensors[0].value, 42.0);
+        assert_eq!(sensors[0].max, Some(100.0));
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, "broken");
+        assert!(matches!(
+            &errors[0].1,
+            PluginError::Exit { code: Some(2), stderr } if stderr == "oops"
+        ));
+        assert!(matches!(errors[1].1, PluginError::Protocol(_)));
+    }
+}
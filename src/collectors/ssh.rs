@@ -0,0 +1,491 @@
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, RsaPublicKeyComponents};
+use sha2::{Digest, Sha256};
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+// Минимальная часть протокола SSH (RFC 4253, RFC 8731): баннер и обмен ключами до получения
+// подписанного ключа хоста. Шифрованный канал не поднимается, соединение сразу закрывается.
+const CLIENT_BANNER: &str = "SSH-2.0-monitord_0.1.0";
+const MAX_BANNER_LINES: usize = 32;
+const MAX_LINE_BYTES: usize = 255;
+const MAX_PACKET_BYTES: usize = 256 * 1024;
+
+const MSG_DISCONNECT: u8 = 1;
+const MSG_IGNORE: u8 = 2;
+const MSG_UNIMPLEMENTED: u8 = 3;
+const MSG_DEBUG: u8 = 4;
+const MSG_KEXINIT: u8 = 20;
+const MSG_KEX_ECDH_INIT: u8 = 30;
+const MSG_KEX_ECDH_REPLY: u8 = 31;
+
+const KEX_ALGORITHMS: &[&str] = &["curve25519-sha256", "curve25519-sha256@libssh.org"];
+// Порядок важен: сервер выбирает первый алгоритм клиента, который поддерживает сам.
+const HOST_KEY_ALGORITHMS: &[&str] = &[
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+];
+const CIPHERS: &str = "chacha20-poly1305@openssh.com,aes128-ctr,aes256-ctr,aes128-gcm@openssh.com";
+const MACS: &str = "hmac-sha2-256,hmac-sha2-512";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    pub algorithm: String,
+    // Как у ssh-keygen -l: SHA256:<base64 без выравнивания>.
+    pub fingerprint: String,
+}
+
+pub struct SshSession<S> {
+    stream: BufReader<S>,
+    server_banner: String,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SshSession<S> {
+    pub async fn connect(stream: S) -> io::Result<Self> {
+        let mut stream = BufReader::new(stream);
+        stream
+            .write_all(format!("{CLIENT_BANNER}\r\n").as_bytes())
+            .await?;
+        stream.flush().await?;
+        let server_banner = read_banner(&mut stream).await?;
+        Ok(Self {
+            stream,
+            server_banner,
+        })
+    }
+
+    pub fn banner(&self) -> &str {
+        &self.server_banner
+    }
+
+    pub async fn host_key(mut self) -> io::Result<HostKey> {
+        let rng = SystemRandom::new();
+        let client_kexinit = kexinit_payload(&rng)?;
+        write_packet(&mut self.stream, &client_kexinit, &rng).await?;
+        let server_kexinit = self.read_message(MSG_KEXINIT).await?;
+        let host_key_algorithm = negotiate_host_key(&server_kexinit)?;
+
+        let private_key = EphemeralPrivateKey::generate(&X25519, &rng).map_err(crypto_error)?;
+        let client_public = private_key.compute_public_key().map_err(crypto_error)?;
+        let mut init = vec![MSG_KEX_ECDH_INIT];
+        put_string(&mut init, client_public.as_ref());
+        write_packet(&mut self.stream, &init, &rng).await?;
+
+        let reply = self.read_message(MSG_KEX_ECDH_REPLY).await?;
+        let mut reader = Reader::new(&reply[1..]);
+        let host_key = reader.string()?.to_vec();
+        let server_public = reader.string()?.to_vec();
+        let signature = reader.string()?.to_vec();
+        let shared = agreement::agree_ephemeral(
+            private_key,
+            &UnparsedPublicKey::new(&X25519, &server_public),
+            |secret| secret.to_vec(),
+        )
+        .map_err(crypto_error)?;
+
+        let mut exchange = Vec::new();
+        put_string(&mut exchange, CLIENT_BANNER.as_bytes());
+        put_string(&mut exchange, self.server_banner.as_bytes());
+        put_string(&mut exchange, &client_kexinit);
+        put_string(&mut exchange, &server_kexinit);
+        put_string(&mut exchange, &host_key);
+        put_string(&mut exchange, client_public.as_ref());
+        put_string(&mut exchange, &server_public);
+        put_mpint(&mut exchange, &shared);
+        let exchange_hash = Sha256::digest(&exchange);
+        // Без проверки подписи подменённый ключ нельзя отличить от настоящего.
+        verify_host_signature(&host_key, &signature, &exchange_hash)?;
+
+        Ok(HostKey {
+            algorithm: host_key_algorithm.to_string(),
+            fingerprint: fingerprint(&host_key),
+        })
+    }
+
+    async fn read_message(&mut self, expected: u8) -> io::Result<Vec<u8>> {
+        loop {
+            let payload = read_packet(&mut self.stream).await?;
+            match payload.first().copied() {
+                Some(code) if code == expected => return Ok(payload),
+                Some(MSG_IGNORE | MSG_DEBUG | MSG_UNIMPLEMENTED) => continue,
+                Some(MSG_DISCONNECT) => {
+                    let mut reader = Reader::new(&payload[1..]);
+                    let _code = reader.u32()?;
+                    let reason = String::from_utf8_lossy(reader.string()?).into_owned();
+                    return Err(protocol_error(format!(
+                        "сервер разорвал соединение: {reason}"
+                    )));
+                }
+                other => {
+                    return Err(protocol_error(format!(
+                        "неожиданное сообщение SSH {other:?} вместо {expected}"
+                    )))
+                }
+            }
+        }
+    }
+}
+
+pub fn fingerprint(host_key: &[u8]) -> String {
+    format!(
+        "SHA256:{}",
+        STANDARD_NO_PAD.encode(Sha256::digest(host_key))
+    )
+}
+
+async fn read_banner<S: AsyncRead + Unpin>(stream: &mut BufReader<S>) -> io::Result<String> {
+    // До строки версии сервер может прислать произвольные строки (RFC 4253, 4.2).
+    for _ in 0..MAX_BANNER_LINES {
+        let mut line = Vec::new();
+        (&mut *stream)
+            .take(MAX_LINE_BYTES as u64 + 2)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if line.is_empty() {
+            return Err(protocol_error("сервер закрыл соединение до баннера SSH"));
+        }
+        let line = String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        if line.starts_with("SSH-2.0-") || line.starts_with("SSH-1.99-") {
+            return Ok(line);
+        }
+        if line.starts_with("SSH-") {
+            return Err(protocol_error(format!(
+                "неподдерживаемая версия SSH: {line}"
+            )));
+        }
+    }
+    Err(protocol_error("сервер не прислал баннер SSH-2.0"))
+}
+
+fn kexinit_payload(rng: &SystemRandom) -> io::Result<Vec<u8>> {
+    let mut cookie = [0_u8; 16];
+    rng.fill(&mut cookie).map_err(crypto_error)?;
+    let mut payload = vec![MSG_KEXINIT];
+    payload.extend_from_slice(&cookie);
+    for list in [
+        KEX_ALGORITHMS.join(",").as_str(),
+        HOST_KEY_ALGORITHMS.join(",").as_str(),
+        CIPHERS,
+        CIPHERS,
+        MACS,
+        MACS,
+        "none",
+        "none",
+        "",
+        "",
+    ] {
+        put_string(&mut payload, list.as_bytes());
+    }
+    payload.push(0);
+    payload.extend_from_slice(&0_u32.to_be_bytes());
+    Ok(payload)
+}
+
+fn negotiate_host_key(server_kexinit: &[u8]) -> io::Result<&'static str> {
+    let mut reader = Reader::new(&server_kexinit[1..]);
+    reader.bytes(16)?;
+    let kex = reader.name_list()?;
+    if !KEX_ALGORITHMS.iter().any(|alg| kex.contains(alg)) {
+        return Err(protocol_error(format!(
+            "сервер не поддерживает обмен ключами curve25519-sha256 (предлагает {})",
+            kex.join(",")
+        )));
+    }
+    let host_keys = reader.name_list()?;
+    HOST_KEY_ALGORITHMS
+        .iter()
+        .copied()
+        .find(|alg| host_keys.contains(alg))
+        .ok_or_else(|| {
+            protocol_error(format!(
+                "нет общего алгоритма ключа хоста (сервер предлагает {})",
+                host_keys.join(",")
+            ))
+        })
+}
+
+fn verify_host_signature(host_key: &[u8], signature: &[u8], message: &[u8]) -> io::Result<()> {
+    let mut key = Reader::new(host_key);
+    let key_type = key.string()?;
+    let mut sig = Reader::new(signature);
+    let sig_type = sig.string()?;
+    let sig_blob = sig.string()?;
+    let verified = match (key_type, sig_type) {
+        (b"ssh-ed25519", b"ssh-ed25519") => {
+            let public = key.string()?;
+            signature::UnparsedPublicKey::new(&signature::ED25519, public).verify(message, sig_blob)
+        }
+        (b"ecdsa-sha2-nistp256", b"ecdsa-sha2-nistp256") => {
+            let public = key.skip_string()?.string()?;
+            let fixed = ecdsa_fixed_signature(sig_blob, 32)?;
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, public)
+                .verify(message, &fixed)
+        }
+        (b"ecdsa-sha2-nistp384", b"ecdsa-sha2-nistp384") => {
+            let public = key.skip_string()?.string()?;
+            let fixed = ecdsa_fixed_signature(sig_blob, 48)?;
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P384_SHA384_FIXED, public)
+                .verify(message, &fixed)
+        }
+        (b"ssh-rsa", b"rsa-sha2-256" | b"rsa-sha2-512") => {
+            let e = strip_leading_zeros(key.string()?);
+            let n = strip_leading_zeros(key.string()?);
+            let params = if sig_type == b"rsa-sha2-512" {
+                &signature::RSA_PKCS1_2048_8192_SHA512
+            } else {
+                &signature::RSA_PKCS1_2048_8192_SHA256
+            };
+            RsaPublicKeyComponents { n, e }.verify(params, message, sig_blob)
+        }
+        _ => {
+            return Err(protocol_error(format!(
+                "неподдерживаемая подпись {} для ключа {}",
+                String::from_utf8_lossy(sig_type),
+                String::from_utf8_lossy(key_type)
+            )))
+        }
+    };
+    verified.map_err(|_| protocol_error("подпись ключа хоста не сходится"))
+}
+
+// Подпись ECDSA в SSH — пара mpint r, s; ring ждёт их склеенными с фиксированной длиной.
+fn ecdsa_fixed_signature(blob: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let mut reader = Reader::new(blob);
+    let mut fixed = Vec::with_capacity(len * 2);
+    for _ in 0..2 {
+        let part = strip_leading_zeros(reader.string()?);
+        if part.len() > len {
+            return Err(protocol_error("некорректная подпись ECDSA"));
+        }
+        fixed.resize(fixed.len() + len - part.len(), 0);
+        fixed.extend_from_slice(part);
+    }
+    Ok(fixed)
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    payload: &[u8],
+    rng: &SystemRandom,
+) -> io::Result<()> {
+    // Без шифрования блок — 8 байт, выравнивание не короче 4 байт.
+    let mut padding = 8 - (5 + payload.len()) % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+    let mut packet = Vec::with_capacity(5 + payload.len() + padding);
+    packet.extend_from_slice(&((1 + payload.len() + padding) as u32).to_be_bytes());
+    packet.push(padding as u8);
+    packet.extend_from_slice(payload);
+    let start = packet.len();
+    packet.resize(start + padding, 0);
+    rng.fill(&mut packet[start..]).map_err(crypto_error)?;
+    stream.write_all(&packet).await?;
+    stream.flush().await
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Vec<u8>> {
+    let len = stream.read_u32().await? as usize;
+    if !(5..=MAX_PACKET_BYTES).contains(&len) {
+        return Err(protocol_error(format!(
+            "некорректная длина пакета SSH: {len}"
+        )));
+    }
+    let mut packet = vec![0_u8; len];
+    stream.read_exact(&mut packet).await?;
+    let padding = packet[0] as usize;
+    if padding + 1 >= len {
+        return Err(protocol_error("некорректное выравнивание пакета SSH"));
+    }
+    Ok(packet[1..len - padding].to_vec())
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+fn put_mpint(buf: &mut Vec<u8>, value: &[u8]) {
+    let value = strip_leading_zeros(value);
+    if value.first().is_some_and(|b| b & 0x80 != 0) {
+        buf.extend_from_slice(&(value.len() as u32 + 1).to_be_bytes());
+        buf.push(0);
+        buf.extend_from_slice(value);
+    } else {
+        put_string(buf, value);
+    }
+}
+
+fn strip_leading_zeros(value: &[u8]) -> &[u8] {
+    let start = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+    &value[start..]
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(protocol_error("обрезанное сообщение SSH"));
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn skip_string(&mut self) -> io::Result<&mut Self> {
+        self.string()?;
+        Ok(self)
+    }
+
+    fn name_list(&mut self) -> io::Result<Vec<&'a str>> {
+        let raw = std::str::from_utf8(self.string()?)
+            .map_err(|_| protocol_error("некорректный список алгоритмов SSH"))?;
+        Ok(raw.split(',').filter(|name| !name.is_empty()).collect())
+    }
+}
+
+fn protocol_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn crypto_error(_: ring::error::Unspecified) -> io::Error {
+    io::Error::other("ошибка криптографии SSH")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    // Сервер, который проходит обмен ключами curve25519-sha256 с ключом ssh-ed25519.
+    async fn fake_server(
+        stream: tokio::io::DuplexStream,
+        key_pair: Ed25519KeyPair,
+        tamper: bool,
+    ) -> io::Result<()> {
+        let rng = SystemRandom::new();
+        let server_banner = "SSH-2.0-OpenSSH_9.6";
+        let mut stream = BufReader::new(stream);
+        stream
+            .write_all(format!("welcome\r\n{server_banner}\r\n").as_bytes())
+            .await?;
+        let client_banner = read_banner(&mut stream).await?;
+
+        let mut server_kexinit = vec![MSG_KEXINIT];
+        server_kexinit.extend_from_slice(&[7_u8; 16]);
+        for list in [
+            "curve25519-sha256,diffie-hellman-group14-sha256",
+            "rsa-sha2-512,ssh-ed25519",
+            "aes128-ctr",
+            "aes128-ctr",
+            "hmac-sha2-256",
+            "hmac-sha2-256",
+            "none",
+            "none",
+            "",
+            "",
+        ] {
+            put_string(&mut server_kexinit, list.as_bytes());
+        }
+        server_kexinit.push(0);
+        server_kexinit.extend_from_slice(&0_u32.to_be_bytes());
+        write_packet(&mut stream, &[MSG_IGNORE, 0, 0, 0, 0], &rng).await?;
+        write_packet(&mut stream, &server_kexinit, &rng).await?;
+
+        let client_kexinit = read_packet(&mut stream).await?;
+        let init = read_packet(&mut stream).await?;
+        let client_public = Reader::new(&init[1..]).string()?.to_vec();
+        let private_key = EphemeralPrivateKey::generate(&X25519, &rng).map_err(crypto_error)?;
+        let server_public = private_key.compute_public_key().map_err(crypto_error)?;
+        let shared = agreement::agree_ephemeral(
+            private_key,
+            &UnparsedPublicKey::new(&X25519, &client_public),
+            |secret| secret.to_vec(),
+        )
+        .map_err(crypto_error)?;
+
+        let mut host_key = Vec::new();
+        put_string(&mut host_key, b"ssh-ed25519");
+        put_string(&mut host_key, key_pair.public_key().as_ref());
+        let mut exchange = Vec::new();
+        put_string(&mut exchange, client_banner.as_bytes());
+        put_string(&mut exchange, server_banner.as_bytes());
+        put_string(&mut exchange, &client_kexinit);
+        put_string(&mut exchange, &server_kexinit);
+        put_string(&mut exchange, &host_key);
+        put_string(&mut exchange, &client_public);
+        put_string(&mut exchange, server_public.as_ref());
+        put_mpint(&mut exchange, &shared);
+        if tamper {
+            exchange.push(0);
+        }
+        let mut signature = Vec::new();
+        put_string(&mut signature, b"ssh-ed25519");
+        put_string(
+            &mut signature,
+            key_pair.sign(&Sha256::digest(&exchange)).as_ref(),
+        );
+
+        let mut reply = vec![MSG_KEX_ECDH_REPLY];
+        put_string(&mut reply, &host_key);
+        put_string(&mut reply, server_public.as_ref());
+        put_string(&mut reply, &signature);
+        write_packet(&mut stream, &reply, &rng).await
+    }
+
+    fn ed25519_key() -> (Ed25519KeyPair, String) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut blob = Vec::new();
+        put_string(&mut blob, b"ssh-ed25519");
+        put_string(&mut blob, key_pair.public_key().as_ref());
+        (key_pair, fingerprint(&blob))
+    }
+
+    #[tokio::test]
+    async fn host_key_is_verified_and_fingerprinted() {
+        let (key_pair, expected) = ed25519_key();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(fake_server(server, key_pair, false));
+        let session = SshSession::connect(client).await.unwrap();
+        assert_eq!(session.banner(), "SSH-2.0-OpenSSH_9.6");
+        let host_key = session.host_key().await.unwrap();
+        assert_eq!(host_key.algorithm, "ssh-ed25519");
+        assert_eq!(host_key.fingerprint, expected);
+        assert!(expected.starts_with("SHA256:") && expected.len() == 50);
+        server.await.unwrap().unwrap();
+
+        let (key_pair, _) = ed25519_key();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(fake_server(server, key_pair, true));
+        let session = SshSession::connect(client).await.unwrap();
+        let err = session.host_key().await.unwrap_err();
+        assert!(err.to_string().contains("подпись"));
+        server.await.unwrap().unwrap();
+    }
+}
@@ -1,13 +1,75 @@
+#[cfg(target_os = "windows")]
+use crate::collectors::pdh;
+#[cfg(target_os = "windows")]
+use crate::collectors::wmi_sensors::{self, LhmSensor, WmiQueryError};
 use crate::collectors::SystemSnapshot;
-use crate::state::{DiskStat, GpuStat, NetStat, SensorStat, TempStat};
-use std::collections::HashMap;
-#[cfg(target_os = "linux")]
+use crate::config::{Config, WindowsCounterConfig};
+use crate::state::{
+    CgroupStat, CpuStealStat, DiskIoStat, DiskStat, GpuAlertContext, GpuProcess, GpuStat, NetStat,
+    PageFileStat, SensorStat, TempStat, TimeSyncStat, WireguardPeerStat,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::process::Command;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use sysinfo::{ComponentExt, CpuExt, DiskExt, NetworkExt, NetworksExt, System, SystemExt};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
 use tracing::debug;
 
-pub fn collect_system(system: &mut System) -> SystemSnapshot {
+// Внешние утилиты (PowerShell, nvidia-smi, wmic) не должны подвешивать поток сбора.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+type SystemRequest = (Arc<Config>, oneshot::Sender<SystemSnapshot>);
+
+#[derive(Debug, Error)]
+pub enum SystemCollectError {
+    #[error("предыдущий сбор ещё не завершён")]
+    Busy,
+    #[error("таймаут {0} с")]
+    Timeout(u64),
+    #[error("поток сбора остановлен")]
+    Stopped,
+}
+
+// Сбор через sysinfo и внешние утилиты блокирующий, поэтому выполняется в отдельном потоке.
+pub struct SystemCollector {
+    requests: mpsc::Sender<SystemRequest>,
+}
+
+impl SystemCollector {
+    pub fn spawn() -> std::io::Result<Self> {
+        let (requests, mut rx) = mpsc::channel::<SystemRequest>(1);
+        std::thread::Builder::new()
+            .name("system-collector".to_string())
+            .spawn(move || {
+                let mut system = System::new_all();
+                while let Some((cfg, reply)) = rx.blocking_recv() {
+                    let _ = reply.send(collect_system(&mut system, &cfg));
+                }
+            })?;
+        Ok(Self { requests })
+    }
+
+    pub async fn collect(&self, cfg: Arc<Config>) -> Result<SystemSnapshot, SystemCollectError> {
+        let timeout_secs = cfg.collect_timeout_secs;
+        let (reply, snapshot) = oneshot::channel();
+        self.requests
+            .try_send((cfg, reply))
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => SystemCollectError::Busy,
+                mpsc::error::TrySendError::Closed(_) => SystemCollectError::Stopped,
+            })?;
+        tokio::time::timeout(Duration::from_secs(timeout_secs), snapshot)
+            .await
+            .map_err(|_| SystemCollectError::Timeout(timeout_secs))?
+            .map_err(|_| SystemCollectError::Stopped)
+    }
+}
+
+pub fn collect_system(system: &mut System, cfg: &Config) -> SystemSnapshot {
     system.refresh_cpu();
     system.refresh_memory();
     system.refresh_processes();
@@ -33,42 +95,101 @@ pub fn collect_system(system: &mut System) -> SystemSnapshot {
         (sum / system.cpus().len() as f32) as f64
     };
 
-    let memory_total_bytes = system.total_memory() * 1024;
-    let memory_used_bytes = system.used_memory() * 1024;
+    let cpu_steal = collect_cpu_steal();
+    let virtualization = detect_virtualization();
+    let time_sync = collect_time_sync();
+    let mut memory_total_bytes = system.total_memory() * 1024;
+    let mut memory_used_bytes = system.used_memory() * 1024;
+    let cgroup = collect_cgroup(cfg.container.cgroup_limits, memory_total_bytes);
+    if let Some(cg) = cgroup.as_ref().filter(|c| c.applied) {
+        if let (Some(limit), Some(used)) = (cg.memory_limit_bytes, cg.memory_used_bytes) {
+            memory_total_bytes = limit;
+            memory_used_bytes = used.min(limit);
+        }
+    }
+    let page_file = collect_page_file();
 
+    let drive_temps = collect_drive_temps(cfg.disks.smartctl);
+    let read_only_mounts = collect_read_only_mounts();
     let disks: Vec<DiskStat> = system
         .disks()
         .iter()
+        .filter(|d| !cfg.disks.mount_ignored(&d.mount_point().to_string_lossy()))
         .map(|d| {
             let total = d.total_space();
             let used = total.saturating_sub(d.available_space());
+            let device = block_device(&d.name().to_string_lossy());
+            let mount = d.mount_point().to_string_lossy().to_string();
             DiskStat {
-                mount: d.mount_point().to_string_lossy().to_string(),
+                read_only: read_only_mounts.contains(&mount),
+                write_error: cfg
+                    .disks
+                    .write_probed(&mount)
+                    .then(|| probe_write(d.mount_point()).err())
+                    .flatten(),
+                mount,
                 used_bytes: used,
                 total_bytes: total,
+                removable: d.is_removable(),
+                temperature_celsius: device
+                    .as_deref()
+                    .and_then(|dev| drive_temperature(&drive_temps, dev)),
+                device,
             }
         })
         .collect();
 
+    let disk_io = collect_disk_io();
+
+    let drops = collect_net_drops();
     let net: Vec<NetStat> = system
         .networks()
         .iter()
-        .map(|(iface, data)| NetStat {
-            iface: iface.to_string(),
-            rx_bytes_total: data.total_received(),
-            tx_bytes_total: data.total_transmitted(),
-            rx_bytes_per_sec: 0,
-            tx_bytes_per_sec: 0,
+        .filter(|(iface, _)| !cfg.network.iface_ignored(iface))
+        .map(|(iface, data)| {
+            let (rx_drops_total, tx_drops_total) = drops.get(iface).copied().unwrap_or_default();
+            NetStat {
+                iface: iface.to_string(),
+                rx_bytes_total: data.total_received(),
+                tx_bytes_total: data.total_transmitted(),
+                rx_bytes_per_sec: 0,
+                tx_bytes_per_sec: 0,
+                rx_errors_total: data.total_errors_on_received(),
+                tx_errors_total: data.total_errors_on_transmitted(),
+                rx_drops_total,
+                tx_drops_total,
+                errors_per_min: 0.0,
+            }
         })
         .collect();
+    let wireguard = if cfg.wireguard.enabled {
+        collect_wireguard_peers()
+    } else {
+        Vec::new()
+    };
 
     let mut temps = collect_temps(system);
+    let has_temps = !temps.is_empty();
+    temps.extend(drive_temps.iter().map(|(device, celsius)| TempStat {
+        sensor: format!("Disk {device}"),
+        temperature_celsius: *celsius,
+        critical_temperature_celsius: None,
+        device: Some(device.clone()),
+    }));
     let gpus = collect_gpu_stats(system);
     let (lhm_temps, lhm_gpus, lhm_sensors) = collect_lhm_snapshot();
     if !lhm_temps.is_empty() {
         temps.extend(lhm_temps);
     }
     let gpus = merge_gpu_stats(gpus, lhm_gpus);
+    let counters = collect_windows_counters(&cfg.windows_counters);
+    let sources = vec![
+        ("temperatures", has_temps),
+        ("gpus", !gpus.is_empty()),
+        ("lhm_sensors", !lhm_sensors.is_empty()),
+        ("windows_counters", !counters.is_empty()),
+        ("time_sync", time_sync.is_some()),
+    ];
     let sensors = collect_builtin_sensor_stats(
         cpu_usage_percent,
         memory_used_bytes,
@@ -78,7 +199,8 @@ pub fn collect_system(system: &mut System) -> SystemSnapshot {
         &temps,
         &gpus,
     );
-    let sensors = merge_sensors(sensors, lhm_sensors);
+    let mut sensors = merge_sensors(sensors, lhm_sensors);
+    sensors.extend(counters);
 
     SystemSnapshot {
         host_name,
@@ -90,13 +212,21 @@ pub fn collect_system(system: &mut System) -> SystemSnapshot {
         process_count,
         cpu_core_count,
         cpu_usage_percent,
+        cpu_steal,
+        virtualization,
+        time_sync,
+        cgroup,
         memory_used_bytes,
         memory_total_bytes,
+        page_file,
         disks,
+        disk_io,
         net,
+        wireguard,
         temps,
         gpus,
         sensors,
+        sources,
     }
 }
 
@@ -211,7 +341,7 @@ fn collect_builtin_sensor_stats(
         out.push(SensorStat {
             sensor_type: "data".to_string(),
             name: format!("Disk {} Free", d.mount),
-            identifier: format!("{}/free", format!("/disk/{}", d.mount)),
+            identifier: format!("/disk/{}/free", d.mount),
             parent: format!("/disk/{}", d.mount),
             value: d.total_bytes.saturating_sub(d.used_bytes) as f64 / 1024.0 / 1024.0 / 1024.0,
             min: None,
@@ -360,6 +490,7 @@ fn collect_temps(system: &System) -> Vec<TempStat> {
             sensor: c.label().to_string(),
             temperature_celsius: c.temperature() as f64,
             critical_temperature_celsius: c.critical().map(|v| v as f64),
+            device: None,
         })
         .filter(|t| t.temperature_celsius > 0.0)
         .collect();
@@ -385,31 +516,62 @@ fn collect_temps(system: &System) -> Vec<TempStat> {
 
 #[cfg(target_os = "windows")]
 fn collect_windows_temps() -> Vec<TempStat> {
-    let wmic = collect_windows_temps_wmic();
-    if !wmic.is_empty() {
-        return wmic;
-    }
+    match wmi_sensors::thermal_zones() {
+        Ok(zones) => {
+            let temps: Vec<TempStat> = zones
+                .into_iter()
+                .filter_map(|z| {
+                    thermal_zone_temp(z.instance_name.as_deref()?, z.current_temperature?)
+                })
+                .collect();
+            if !temps.is_empty() {
+                return temps;
+            }
+        }
+        Err(WmiQueryError::Missing) => {}
+        Err(WmiQueryError::Failed(err)) => {
+            debug!(error = %err, "WMI недоступен, температуры читаются через wmic/PowerShell");
+            let wmic = collect_windows_temps_wmic();
+            if !wmic.is_empty() {
+                return wmic;
+            }
 
-    let cim = collect_windows_temps_cim();
-    if !cim.is_empty() {
-        return cim;
+            let cim = collect_windows_temps_cim();
+            if !cim.is_empty() {
+                return cim;
+            }
+        }
     }
 
     collect_windows_temps_typeperf()
 }
 
+#[cfg(target_os = "windows")]
+fn thermal_zone_temp(instance: &str, raw: f64) -> Option<TempStat> {
+    if instance.is_empty() || raw <= 0.0 {
+        return None;
+    }
+    Some(TempStat {
+        sensor: format!("ACPI {} (fallback)", instance),
+        temperature_celsius: normalize_windows_thermal_zone_temp(raw)?,
+        critical_temperature_celsius: None,
+        device: None,
+    })
+}
+
 #[cfg(target_os = "windows")]
 fn collect_windows_temps_wmic() -> Vec<TempStat> {
-    let output = Command::new("wmic")
-        .args([
+    let output = output_with_timeout(
+        Command::new("wmic").args([
             "/namespace:\\\\root\\wmi",
             "PATH",
             "MSAcpi_ThermalZoneTemperature",
             "get",
             "CurrentTemperature,InstanceName",
             "/format:csv",
-        ])
-        .output();
+        ]),
+        COMMAND_TIMEOUT,
+    );
 
     let Ok(output) = output else {
         return Vec::new();
@@ -426,17 +588,7 @@ fn collect_windows_temps_wmic() -> Vec<TempStat> {
             if parts.len() < 3 {
                 return None;
             }
-            let instance = parts[1];
-            let raw = parts[2].parse::<f64>().ok()?;
-            if raw <= 0.0 {
-                return None;
-            }
-            let celsius = normalize_windows_thermal_zone_temp(raw)?;
-            Some(TempStat {
-                sensor: format!("ACPI {} (fallback)", instance),
-                temperature_celsius: celsius,
-                critical_temperature_celsius: None,
-            })
+            thermal_zone_temp(parts[1], parts[2].parse::<f64>().ok()?)
         })
         .collect()
 }
@@ -453,25 +605,13 @@ fn collect_windows_temps_cim() -> Vec<TempStat> {
 
     let text = decode_cmd_stdout(&output.stdout);
 
-    let out: Vec<TempStat> = text
-        .lines()
+    text.lines()
         .filter_map(|line| {
             let mut parts = line.splitn(2, '|').map(str::trim);
             let sensor = parts.next()?;
-            let raw = parts.next()?.parse::<f64>().ok()?;
-            if sensor.is_empty() || raw <= 0.0 {
-                return None;
-            }
-
-            Some(TempStat {
-                sensor: format!("ACPI {} (fallback)", sensor),
-                temperature_celsius: normalize_windows_thermal_zone_temp(raw)?,
-                critical_temperature_celsius: None,
-            })
+            thermal_zone_temp(sensor, parts.next()?.parse::<f64>().ok()?)
         })
-        .collect();
-
-    out
+        .collect()
 }
 
 #[cfg(target_os = "windows")]
@@ -520,6 +660,7 @@ fn collect_windows_temps_typeperf() -> Vec<TempStat> {
         sensor: "ACPI CPU thermal zone (fallback)".to_string(),
         temperature_celsius: value,
         critical_temperature_celsius: None,
+        device: None,
     }]
 }
 
@@ -563,6 +704,7 @@ fn collect_linux_temps() -> Vec<TempStat> {
                 sensor: typ,
                 temperature_celsius: celsius,
                 critical_temperature_celsius: None,
+                device: None,
             });
         }
     }
@@ -575,6 +717,674 @@ fn collect_linux_temps() -> Vec<TempStat> {
     Vec::new()
 }
 
+// sysinfo не отдаёт отброшенные пакеты, на Linux они берутся из /proc/net/dev.
+#[cfg(target_os = "linux")]
+fn collect_net_drops() -> HashMap<String, (u64, u64)> {
+    fs::read_to_string("/proc/net/dev")
+        .map(|text| parse_proc_net_dev_drops(&text))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_net_drops() -> HashMap<String, (u64, u64)> {
+    HashMap::new()
+}
+
+#[cfg(target_os = "linux")]
+fn collect_cpu_steal() -> Option<CpuStealStat> {
+    fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|text| parse_proc_stat_steal(&text))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_cpu_steal() -> Option<CpuStealStat> {
+    None
+}
+
+// Строка cpu: user nice system idle iowait irq softirq steal guest guest_nice. guest уже входит в user,
+// поэтому в сумму идут только первые восемь полей; старые ядра без steal пропускаются.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_proc_stat_steal(text: &str) -> Option<CpuStealStat> {
+    let fields = text
+        .lines()
+        .find_map(|line| line.strip_prefix("cpu "))?
+        .split_whitespace()
+        .take(8)
+        .map(|v| v.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if fields.len() < 8 {
+        return None;
+    }
+    Some(CpuStealStat {
+        steal_ticks_total: fields[7],
+        cpu_ticks_total: fields.iter().sum(),
+        steal_percent: 0.0,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn detect_virtualization() -> Option<String> {
+    let read = |path: &str| fs::read_to_string(path).unwrap_or_default();
+    let dmi = format!(
+        "{} {}",
+        read("/sys/class/dmi/id/sys_vendor").trim(),
+        read("/sys/class/dmi/id/product_name").trim()
+    );
+    hypervisor_name(
+        &read("/proc/cpuinfo"),
+        &dmi,
+        read("/sys/hypervisor/type").trim(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_virtualization() -> Option<String> {
+    None
+}
+
+// Лимиты читаются только внутри контейнера: у агента на хосте своя cgroup службы, и её квота
+// не описывает машину целиком.
+#[cfg(target_os = "linux")]
+fn collect_cgroup(apply: bool, host_memory_bytes: u64) -> Option<CgroupStat> {
+    use std::path::Path;
+
+    let self_cgroup = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let runtime = container_runtime(
+        &self_cgroup,
+        Path::new("/.dockerenv").exists(),
+        Path::new("/run/.containerenv").exists(),
+        |name| std::env::var(name).ok(),
+    )?;
+    let read = |path: &Path| fs::read_to_string(path).ok();
+    let root = Path::new("/sys/fs/cgroup");
+    let mut stat = match cgroup_v2_path(&self_cgroup) {
+        Some(path) if root.join("cgroup.controllers").exists() => {
+            // С собственным пространством имён cgroup группа контейнера смонтирована корнем.
+            let nested = root.join(path.trim_start_matches('/'));
+            let dir = if nested.join("memory.max").exists() {
+                nested
+            } else {
+                root.to_path_buf()
+            };
+            let memory_used = read(&dir.join("memory.current"))
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|used| {
+                    let stat = read(&dir.join("memory.stat")).unwrap_or_default();
+                    used.saturating_sub(cgroup_stat_value(&stat, "inactive_file").unwrap_or(0))
+                });
+            CgroupStat {
+                version: 2,
+                cpu_limit_cores: read(&dir.join("cpu.max")).and_then(|v| parse_cpu_max(&v)),
+                memory_limit_bytes: read(&dir.join("memory.max"))
+                    .and_then(|v| parse_memory_limit(&v, host_memory_bytes)),
+                memory_used_bytes: memory_used,
+                cpu_usage_usec_total: read(&dir.join("cpu.stat"))
+                    .and_then(|v| cgroup_stat_value(&v, "usage_usec")),
+                ..CgroupStat::default()
+            }
+        }
+        _ => {
+            let memory = root.join("memory");
+            let memory_used = read(&memory.join("memory.usage_in_bytes"))
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|used| {
+                    let stat = read(&memory.join("memory.stat")).unwrap_or_default();
+                    used.saturating_sub(
+                        cgroup_stat_value(&stat, "total_inactive_file").unwrap_or(0),
+                    )
+                });
+            CgroupStat {
+                version: 1,
+                cpu_limit_cores: read(&root.join("cpu/cpu.cfs_quota_us"))
+                    .zip(read(&root.join("cpu/cpu.cfs_period_us")))
+                    .and_then(|(quota, period)| parse_cfs_quota(&quota, &period)),
+                memory_limit_bytes: read(&memory.join("memory.limit_in_bytes"))
+                    .and_then(|v| parse_memory_limit(&v, host_memory_bytes)),
+                memory_used_bytes: memory_used,
+                cpu_usage_usec_total: read(&root.join("cpuacct/cpuacct.usage"))
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|ns| ns / 1000),
+                ..CgroupStat::default()
+            }
+        }
+    };
+    stat.runtime = Some(runtime);
+    stat.applied = apply && (stat.cpu_limit_cores.is_some() || stat.memory_limit_bytes.is_some());
+    Some(stat)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_cgroup(_apply: bool, _host_memory_bytes: u64) -> Option<CgroupStat> {
+    None
+}
+
+// Переменную `container` выставляют podman, LXC и systemd-nspawn, в подах Kubernetes всегда есть
+// KUBERNETES_SERVICE_HOST; при общем с хостом пространстве имён cgroup видны пути docker/kubepods.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn container_runtime(
+    self_cgroup: &str,
+    dockerenv: bool,
+    containerenv: bool,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    if let Some(name) = env("container").filter(|v| !v.is_empty()) {
+        return Some(name);
+    }
+    if env("KUBERNETES_SERVICE_HOST").is_some() || self_cgroup.contains("kubepods") {
+        return Some("kubernetes".to_string());
+    }
+    if dockerenv || self_cgroup.contains("docker") {
+        return Some("docker".to_string());
+    }
+    if containerenv {
+        return Some("podman".to_string());
+    }
+    ["lxc", "containerd"]
+        .into_iter()
+        .find(|marker| self_cgroup.contains(marker))
+        .map(str::to_string)
+}
+
+// Единая иерархия v2 записана строкой «0::/путь»; в гибридном режиме есть и строки v1.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn cgroup_v2_path(self_cgroup: &str) -> Option<&str> {
+    let mut lines = self_cgroup.lines().filter(|l| !l.trim().is_empty());
+    let path = lines.next()?.strip_prefix("0::")?;
+    lines.next().is_none().then_some(path.trim())
+}
+
+// cpu.max: «квота период» в микросекундах или «max» без ограничения.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_cpu_max(text: &str) -> Option<f64> {
+    let (quota, period) = text.trim().split_once(' ')?;
+    parse_cfs_quota(quota, period)
+}
+
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.trim().parse::<i64>().ok().filter(|q| *q > 0)?;
+    let period = period.trim().parse::<i64>().ok().filter(|p| *p > 0)?;
+    Some(quota as f64 / period as f64)
+}
+
+// Без ограничения v2 пишет «max», а v1 — почти 2^63; всё, что не меньше памяти хоста, не лимит.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_memory_limit(text: &str, host_memory_bytes: u64) -> Option<u64> {
+    text.trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|limit| *limit > 0 && (host_memory_bytes == 0 || *limit < host_memory_bytes))
+}
+
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn cgroup_stat_value(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        (name == key).then(|| value.trim().parse().ok()).flatten()
+    })
+}
+
+// chrony сообщает смещение и сервер; если его нет, состояние берётся из timedatectl, который
+// отражает флаг синхронизации ядра при любой службе (systemd-timesyncd, ntpd).
+#[cfg(target_os = "linux")]
+fn collect_time_sync() -> Option<TimeSyncStat> {
+    let run = |program: &str, args: &[&str]| {
+        output_with_timeout(Command::new(program).args(args), COMMAND_TIMEOUT)
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+    };
+    run("chronyc", &["-c", "tracking"])
+        .and_then(|text| parse_chrony_tracking(&text))
+        .or_else(|| run("timedatectl", &["show"]).and_then(|text| parse_timedatectl_show(&text)))
+}
+
+#[cfg(target_os = "windows")]
+fn collect_time_sync() -> Option<TimeSyncStat> {
+    let output = output_with_timeout(
+        Command::new("w32tm").args(["/query", "/status"]),
+        COMMAND_TIMEOUT,
+    )
+    .ok()
+    .filter(|o| o.status.success())?;
+    parse_w32tm_status(&decode_cmd_stdout(&output.stdout))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn collect_time_sync() -> Option<TimeSyncStat> {
+    None
+}
+
+// Поля `chronyc -c tracking`: ref id, сервер, страта, время эталона, смещение системных часов, ...,
+// статус leap последним полем.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_chrony_tracking(text: &str) -> Option<TimeSyncStat> {
+    let fields = text.trim().split(',').collect::<Vec<_>>();
+    if fields.len() < 14 {
+        return None;
+    }
+    let leap = fields[fields.len() - 1].trim();
+    Some(TimeSyncStat {
+        source: "chrony".to_string(),
+        synchronized: !leap.eq_ignore_ascii_case("not synchronised"),
+        offset_seconds: fields[4].trim().parse().ok(),
+        server: Some(fields[1].trim())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string),
+    })
+}
+
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_timedatectl_show(text: &str) -> Option<TimeSyncStat> {
+    let synchronized = text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("NTPSynchronized="))?;
+    Some(TimeSyncStat {
+        source: "timedatectl".to_string(),
+        synchronized: synchronized == "yes",
+        offset_seconds: None,
+        server: None,
+    })
+}
+
+// Подписи в выводе w32tm локализованы, поэтому индикатор leap берётся из первой строки по значению
+// вида «3(not synchronized)»: 3 означает, что часы не синхронизированы.
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_w32tm_status(text: &str) -> Option<TimeSyncStat> {
+    let first = text.lines().find(|line| !line.trim().is_empty())?;
+    let (_, value) = first.split_once(':')?;
+    let leap = value
+        .trim()
+        .split('(')
+        .next()
+        .and_then(|v| v.trim().parse::<u8>().ok())?;
+    Some(TimeSyncStat {
+        source: "w32time".to_string(),
+        synchronized: leap != 3,
+        offset_seconds: None,
+        server: None,
+    })
+}
+
+// Гипервизор определяется по DMI (на ARM флага hypervisor нет) и /sys/hypervisor для Xen PV.
+// Если флаг в cpuinfo есть, а поставщик неизвестен, возвращается «unknown».
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn hypervisor_name(cpuinfo: &str, dmi: &str, xen_type: &str) -> Option<String> {
+    const KNOWN: [(&str, &str); 12] = [
+        ("vmware", "VMware"),
+        ("virtualbox", "VirtualBox"),
+        ("innotek", "VirtualBox"),
+        ("virtual machine", "Hyper-V"),
+        ("amazon ec2", "Amazon EC2"),
+        ("google compute engine", "Google Compute Engine"),
+        ("openstack", "OpenStack"),
+        ("parallels", "Parallels"),
+        ("bochs", "Bochs"),
+        ("xen", "Xen"),
+        ("kvm", "KVM"),
+        ("qemu", "KVM"),
+    ];
+    if xen_type == "xen" {
+        return Some("Xen".to_string());
+    }
+    let dmi = dmi.to_lowercase();
+    if let Some((_, name)) = KNOWN.iter().find(|(marker, _)| dmi.contains(marker)) {
+        return Some(name.to_string());
+    }
+    let flagged = cpuinfo.lines().any(|line| {
+        line.split_once(':').is_some_and(|(key, value)| {
+            key.trim() == "flags" && value.split_whitespace().any(|f| f == "hypervisor")
+        })
+    });
+    flagged.then(|| "unknown".to_string())
+}
+
+// Поля после имени: rx bytes packets errs drop fifo frame compressed multicast, затем то же для tx.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_proc_net_dev_drops(text: &str) -> HashMap<String, (u64, u64)> {
+    text.lines()
+        .filter_map(|line| {
+            let (iface, fields) = line.split_once(':')?;
+            let fields = fields
+                .split_whitespace()
+                .map(|v| v.parse::<u64>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            if fields.len() < 16 {
+                return None;
+            }
+            Some((iface.trim().to_string(), (fields[3], fields[11])))
+        })
+        .collect()
+}
+
+// Только целые накопители: разделы, loop- и ram-устройства в /sys/block не попадают или отбрасываются.
+#[cfg(target_os = "linux")]
+fn collect_disk_io() -> Vec<DiskIoStat> {
+    let Ok(text) = fs::read_to_string("/proc/diskstats") else {
+        return Vec::new();
+    };
+    parse_proc_diskstats(&text, |name| {
+        !["loop", "ram", "zram"].iter().any(|p| name.starts_with(p))
+            && std::path::Path::new("/sys/block").join(name).exists()
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn collect_disk_io() -> Vec<DiskIoStat> {
+    let script = "Get-CimInstance Win32_PerfRawData_PerfDisk_PhysicalDisk -ErrorAction SilentlyContinue | Where-Object { $_.Name -ne '_Total' } | ForEach-Object { \"$($_.Name)|$($_.AvgDisksecPerRead)|$($_.AvgDisksecPerRead_Base)|$($_.AvgDisksecPerWrite)|$($_.AvgDisksecPerWrite_Base)|$($_.Frequency_PerfTime)\" }";
+    let Some(output) = run_powershell(script) else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+    decode_cmd_stdout(&output.stdout)
+        .lines()
+        .filter_map(parse_perf_disk_line)
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn collect_disk_io() -> Vec<DiskIoStat> {
+    Vec::new()
+}
+
+// Поля после имени: reads merged sectors ms_reading writes merged sectors ms_writing ...
+// Накопители, на которых ещё не было ни одной операции (пустой привод), пропускаются.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_proc_diskstats(text: &str, keep: impl Fn(&str) -> bool) -> Vec<DiskIoStat> {
+    text.lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() < 11 || !keep(fields[2]) {
+                return None;
+            }
+            let num = |idx: usize| fields[idx].parse::<u64>().ok();
+            let stat = DiskIoStat {
+                device: fields[2].to_string(),
+                reads_total: num(3)?,
+                read_time_ms_total: num(6)?,
+                writes_total: num(7)?,
+                write_time_ms_total: num(10)?,
+                ..DiskIoStat::default()
+            };
+            (stat.reads_total + stat.writes_total > 0).then_some(stat)
+        })
+        .collect()
+}
+
+// Счётчики AvgDisksecPer* накапливают такты таймера (Frequency_PerfTime в секунду), а *_Base — число операций.
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_perf_disk_line(line: &str) -> Option<DiskIoStat> {
+    let parts = line.trim().split('|').collect::<Vec<_>>();
+    if parts.len() != 6 {
+        return None;
+    }
+    let num = |idx: usize| parts[idx].trim().parse::<u64>().ok();
+    let frequency = num(5).filter(|f| *f > 0)? as f64;
+    let millis = |ticks: u64| (ticks as f64 * 1000.0 / frequency) as u64;
+    Some(DiskIoStat {
+        device: parts[0].trim().to_string(),
+        reads_total: num(2)?,
+        read_time_ms_total: millis(num(1)?),
+        writes_total: num(4)?,
+        write_time_ms_total: millis(num(3)?),
+        ..DiskIoStat::default()
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn collect_read_only_mounts() -> HashSet<String> {
+    fs::read_to_string("/proc/mounts")
+        .map(|text| parse_read_only_mounts(&text))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_read_only_mounts() -> HashSet<String> {
+    HashSet::new()
+}
+
+// Поля: устройство, точка монтирования (пробелы как \040), тип, опции. Повторное монтирование поверх
+// той же точки перекрывает предыдущее, поэтому действует последняя строка.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_read_only_mounts(text: &str) -> HashSet<String> {
+    let mut flags = HashMap::new();
+    for line in text.lines() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 4 {
+            continue;
+        }
+        let mount = fields[1]
+            .replace("\\040", " ")
+            .replace("\\011", "\t")
+            .replace("\\134", "\\");
+        flags.insert(mount, fields[3].split(',').any(|opt| opt == "ro"));
+    }
+    flags
+        .into_iter()
+        .filter_map(|(mount, read_only)| read_only.then_some(mount))
+        .collect()
+}
+
+// Запись и удаление небольшого файла: так ловится и перемонтирование, и сбой самого накопителя.
+fn probe_write(mount: &std::path::Path) -> Result<(), String> {
+    let path = mount.join(format!(".monitord-write-probe-{}", std::process::id()));
+    let result = fs::write(&path, b"monitord").and_then(|_| fs::remove_file(&path));
+    result.map_err(|err| err.to_string())
+}
+
+// Температуры накопителей по имени устройства: hwmon (nvme, drivetemp), затем smartctl для остальных.
+fn collect_drive_temps(use_smartctl: bool) -> BTreeMap<String, f64> {
+    let mut temps = collect_hwmon_drive_temps();
+    if use_smartctl {
+        for (device, celsius) in collect_smartctl_drive_temps() {
+            if drive_temperature(&temps, &device).is_none() {
+                temps.insert(device, celsius);
+            }
+        }
+    }
+    temps
+}
+
+// smartctl называет NVMe по контроллеру (nvme0), а разделы лежат на пространствах имён (nvme0n1).
+fn drive_temperature(temps: &BTreeMap<String, f64>, device: &str) -> Option<f64> {
+    temps.get(device).copied().or_else(|| {
+        let controller = device
+            .strip_prefix("nvme")
+            .and_then(|rest| rest.split_once('n'))
+            .map(|(id, _)| format!("nvme{id}"))?;
+        temps.get(&controller).copied()
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn collect_hwmon_drive_temps() -> BTreeMap<String, f64> {
+    let mut out = BTreeMap::new();
+    let Ok(entries) = fs::read_dir("/sys/class/hwmon") else {
+        return out;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = fs::read_to_string(path.join("name")).unwrap_or_default();
+        if !matches!(name.trim(), "nvme" | "drivetemp") {
+            continue;
+        }
+        let Some(celsius) = fs::read_to_string(path.join("temp1_input"))
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map(|v| v / 1000.0)
+        else {
+            continue;
+        };
+        // drivetemp висит на SCSI-устройстве (device/block/sda), nvme — на контроллере (device/nvme0n1).
+        let device = path.join("device");
+        let block = device.join("block");
+        let (dir, namespaces_only) = if block.is_dir() {
+            (block, false)
+        } else {
+            (device, true)
+        };
+        let Ok(children) = fs::read_dir(dir) else {
+            continue;
+        };
+        for child in children.flatten() {
+            let child = child.file_name().to_string_lossy().to_string();
+            if !namespaces_only || is_nvme_namespace(&child) {
+                out.insert(child, celsius);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_hwmon_drive_temps() -> BTreeMap<String, f64> {
+    BTreeMap::new()
+}
+
+#[cfg(target_os = "linux")]
+fn is_nvme_namespace(name: &str) -> bool {
+    name.strip_prefix("nvme")
+        .and_then(|rest| rest.split_once('n'))
+        .is_some_and(|(ctrl, ns)| {
+            !ctrl.is_empty()
+                && ctrl.chars().all(|c| c.is_ascii_digit())
+                && !ns.is_empty()
+                && ns.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+// Раздел (/dev/nvme0n1p2) сводится к накопителю (nvme0n1) через sysfs.
+#[cfg(target_os = "linux")]
+fn block_device(disk_name: &str) -> Option<String> {
+    let name = disk_name.strip_prefix("/dev/")?;
+    let sys = std::path::Path::new("/sys/class/block").join(name);
+    if !sys.exists() {
+        return None;
+    }
+    if !sys.join("partition").exists() {
+        return Some(name.to_string());
+    }
+    fs::canonicalize(&sys)
+        .ok()?
+        .parent()?
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn block_device(_disk_name: &str) -> Option<String> {
+    None
+}
+
+fn collect_smartctl_drive_temps() -> BTreeMap<String, f64> {
+    let mut out = BTreeMap::new();
+    let Ok(scan) = output_with_timeout(
+        Command::new("smartctl").args(["--scan", "--json"]),
+        COMMAND_TIMEOUT,
+    ) else {
+        return out;
+    };
+    for (name, kind) in parse_smartctl_scan(&scan.stdout) {
+        // -n standby не будит спящие диски ради температуры.
+        let Ok(output) = output_with_timeout(
+            Command::new("smartctl").args(["--json", "-A", "-n", "standby", "-d", &kind, &name]),
+            COMMAND_TIMEOUT,
+        ) else {
+            continue;
+        };
+        if let Some(celsius) = parse_smartctl_temperature(&output.stdout) {
+            let device = name.strip_prefix("/dev/").unwrap_or(&name).to_string();
+            out.insert(device, celsius);
+        }
+    }
+    out
+}
+
+fn parse_smartctl_scan(stdout: &[u8]) -> Vec<(String, String)> {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(stdout) else {
+        return Vec::new();
+    };
+    json["devices"]
+        .as_array()
+        .map(|devices| {
+            devices
+                .iter()
+                .filter_map(|d| {
+                    Some((
+                        d["name"].as_str()?.to_string(),
+                        d["type"].as_str().unwrap_or("auto").to_string(),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_smartctl_temperature(stdout: &[u8]) -> Option<f64> {
+    let json = serde_json::from_slice::<serde_json::Value>(stdout).ok()?;
+    json["temperature"]["current"]
+        .as_f64()
+        .filter(|v| (1.0..=150.0).contains(v))
+}
+
+#[cfg(target_os = "linux")]
+fn collect_wireguard_peers() -> Vec<WireguardPeerStat> {
+    match output_with_timeout(
+        Command::new("wg").args(["show", "all", "dump"]),
+        COMMAND_TIMEOUT,
+    ) {
+        Ok(output) if output.status.success() => {
+            parse_wg_dump(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            debug!(
+                stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                "wg show завершился с ошибкой"
+            );
+            Vec::new()
+        }
+        Err(err) => {
+            debug!(error = %err, "не удалось запустить wg");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_wireguard_peers() -> Vec<WireguardPeerStat> {
+    Vec::new()
+}
+
+// Строки интерфейсов (5 полей, с приватным ключом) пропускаются. Поля пира: интерфейс, ключ, PSK,
+// endpoint, allowed-ips, время рукопожатия, rx, tx, keepalive; «(none)» и 0 означают отсутствие.
+#[cfg_attr(not(any(target_os = "linux", test)), allow(dead_code))]
+fn parse_wg_dump(text: &str) -> Vec<WireguardPeerStat> {
+    text.lines()
+        .filter_map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() != 9 {
+                return None;
+            }
+            let present = |v: &str| (!v.is_empty() && v != "(none)").then(|| v.to_string());
+            let latest_handshake = fields[5].parse::<i64>().ok()?;
+            Some(WireguardPeerStat {
+                iface: fields[0].to_string(),
+                public_key: fields[1].to_string(),
+                endpoint: present(fields[3]),
+                allowed_ips: present(fields[4])
+                    .map(|ips| ips.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                latest_handshake_unix: (latest_handshake > 0).then_some(latest_handshake),
+                rx_bytes_total: fields[6].parse().ok()?,
+                tx_bytes_total: fields[7].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
 fn collect_gpu_stats(system: &System) -> Vec<GpuStat> {
     let mut gpus = collect_nvidia_smi();
     if !gpus.is_empty() {
@@ -644,16 +1454,49 @@ fn merge_gpu_stats(base: Vec<GpuStat>, extra: Vec<GpuStat>) -> Vec<GpuStat> {
 
 #[cfg(target_os = "windows")]
 fn collect_lhm_snapshot() -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
+    let rows = match wmi_sensors::lhm_sensors() {
+        Ok(rows) => rows,
+        Err(err) => {
+            debug!(error = ?err, "WMI недоступен, датчики читаются через PowerShell");
+            collect_lhm_sensors_powershell()
+        }
+    };
+    lhm_snapshot(&rows)
+}
+
+#[cfg(target_os = "windows")]
+fn collect_lhm_sensors_powershell() -> Vec<LhmSensor> {
     let script = "$n=@('root/LibreHardwareMonitor','root/OpenHardwareMonitor'); foreach($ns in $n){ try { $s=Get-CimInstance -Namespace $ns -ClassName Sensor -ErrorAction Stop } catch { continue }; if($s){ $s | ForEach-Object { \"$($_.SensorType)|$($_.Name)|$($_.Value)|$($_.Min)|$($_.Max)|$($_.Identifier)|$($_.Parent)\" }; break } }";
     let Some(output) = run_powershell(script) else {
-        return (Vec::new(), Vec::new(), Vec::new());
+        return Vec::new();
     };
     if !output.status.success() {
-        return (Vec::new(), Vec::new(), Vec::new());
+        return Vec::new();
     }
 
     let text = decode_cmd_stdout(&output.stdout);
 
+    text.lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split('|').map(str::trim).collect();
+            if parts.len() < 7 {
+                return None;
+            }
+            Some(LhmSensor {
+                sensor_type: Some(parts[0].to_string()),
+                name: Some(parts[1].to_string()),
+                value: parse_f64_loose(parts[2]),
+                min: parse_f64_loose(parts[3]),
+                max: parse_f64_loose(parts[4]),
+                identifier: Some(parts[5].to_string()),
+                parent: Some(parts[6].to_string()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn lhm_snapshot(rows: &[LhmSensor]) -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
     #[derive(Default)]
     struct GpuAcc {
         name: String,
@@ -667,18 +1510,18 @@ fn collect_lhm_snapshot() -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
     let mut gpus: std::collections::HashMap<String, GpuAcc> = std::collections::HashMap::new();
     let mut sensors = Vec::new();
 
-    for line in text.lines() {
-        let parts: Vec<&str> = line.split('|').map(str::trim).collect();
-        if parts.len() < 7 {
-            continue;
-        }
-        let sensor_type = parts[0].to_ascii_lowercase();
-        let name = parts[1];
-        let value = parse_f64_loose(parts[2]);
-        let min = parse_f64_loose(parts[3]);
-        let max = parse_f64_loose(parts[4]);
-        let ident = parts[5].to_ascii_lowercase();
-        let parent = parts[6];
+    for row in rows {
+        let sensor_type = row
+            .sensor_type
+            .as_deref()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let name = row.name.as_deref().unwrap_or("");
+        let value = row.value;
+        let min = row.min;
+        let max = row.max;
+        let ident = row.identifier.as_deref().unwrap_or("").to_ascii_lowercase();
+        let parent = row.parent.as_deref().unwrap_or("");
         let parent_lc = parent.to_ascii_lowercase();
         let name_lc = name.to_ascii_lowercase();
 
@@ -703,6 +1546,7 @@ fn collect_lhm_snapshot() -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
                         sensor: format!("CPU {}", name),
                         temperature_celsius: v,
                         critical_temperature_celsius: None,
+                        device: None,
                     });
                 }
             }
@@ -774,6 +1618,7 @@ fn collect_lhm_snapshot() -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
                 sensor: format!("CPU {}", s.name),
                 temperature_celsius: s.value,
                 critical_temperature_celsius: s.max,
+                device: None,
             });
         }
     }
@@ -803,8 +1648,104 @@ fn collect_lhm_snapshot() -> (Vec<TempStat>, Vec<GpuStat>, Vec<SensorStat>) {
     (Vec::new(), Vec::new(), Vec::new())
 }
 
+// Счётчики читаются через PDH запросом, открытым в потоке сбора; несуществующие пути пропускаются.
+#[cfg(target_os = "windows")]
+fn collect_windows_counters(counters: &[WindowsCounterConfig]) -> Vec<SensorStat> {
+    if counters.is_empty() {
+        return Vec::new();
+    }
+    pdh::sample(counters)
+        .into_iter()
+        .map(|sample| {
+            counter_sensor(
+                &counters[sample.counter],
+                sample.instance.as_deref(),
+                sample.value,
+            )
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn collect_windows_counters(_counters: &[WindowsCounterConfig]) -> Vec<SensorStat> {
+    Vec::new()
+}
+
+// Для путей с * к имени датчика добавляется экземпляр, а в identifier он подставляется вместо шаблона.
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn counter_sensor(
+    counter: &WindowsCounterConfig,
+    instance: Option<&str>,
+    value: f64,
+) -> SensorStat {
+    let wildcard = counter.path.contains('*');
+    let path = match (instance, counter.path.split_once('(')) {
+        (Some(instance), Some((object, rest))) if wildcard => match rest.split_once(')') {
+            Some((_, tail)) => format!("{object}({instance}){tail}"),
+            None => counter.path.clone(),
+        },
+        _ => counter.path.clone(),
+    };
+    let name = match instance {
+        Some(instance) if wildcard => format!("{} ({instance})", counter.label()),
+        _ => counter.label().to_string(),
+    };
+    SensorStat {
+        sensor_type: counter.sensor_type.clone(),
+        name,
+        identifier: format!("counter{}", path.to_lowercase()),
+        parent: "windows_counters".to_string(),
+        value,
+        min: None,
+        max: None,
+    }
+}
+
 #[cfg(target_os = "windows")]
 fn collect_windows_gpu_stats() -> Vec<GpuStat> {
+    match collect_windows_gpu_stats_wmi() {
+        Ok(gpus) => gpus,
+        Err(WmiQueryError::Missing) => Vec::new(),
+        Err(WmiQueryError::Failed(err)) => {
+            debug!(error = %err, "WMI недоступен, видеокарты читаются через PowerShell");
+            collect_windows_gpu_stats_powershell()
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn collect_windows_gpu_stats_wmi() -> Result<Vec<GpuStat>, WmiQueryError> {
+    let controllers = wmi_sensors::video_controllers()?;
+    // Счётчиков GPU нет в старых версиях Windows: тогда остаются только имя и объём памяти.
+    let util = wmi_sensors::gpu_engines()
+        .map(|engines| {
+            engines
+                .iter()
+                .filter_map(|e| e.utilization_percentage)
+                .sum::<f64>()
+                .clamp(0.0, 100.0)
+        })
+        .unwrap_or(0.0);
+    let used = wmi_sensors::gpu_process_memory()
+        .map(|procs| procs.iter().filter_map(|p| p.dedicated_usage).sum::<f64>())
+        .unwrap_or(0.0);
+
+    Ok(controllers
+        .into_iter()
+        .enumerate()
+        .map(|(idx, c)| GpuStat {
+            id: idx.to_string(),
+            name: c.name.unwrap_or_default(),
+            utilization_percent: Some(util),
+            memory_used_bytes: Some(used.max(0.0) as u64),
+            memory_total_bytes: c.adapter_ram.map(|v| v.max(0.0) as u64),
+            temperature_celsius: None,
+        })
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+fn collect_windows_gpu_stats_powershell() -> Vec<GpuStat> {
     let script = "$controllers=Get-CimInstance Win32_VideoController -ErrorAction SilentlyContinue; if(-not $controllers){return}; $eng=Get-CimInstance Win32_PerfFormattedData_GPUPerformanceCounters_GPUEngine -ErrorAction SilentlyContinue; $proc=Get-Counter '\\GPU Process Memory(*)\\Dedicated Usage' -ErrorAction SilentlyContinue; $util=0; if($eng){ $util=($eng | Measure-Object -Property UtilizationPercentage -Sum).Sum }; if($util -lt 0){$util=0}; if($util -gt 100){$util=100}; $used=0; if($proc){ $used=($proc.CounterSamples | Measure-Object -Property CookedValue -Sum).Sum }; if($used -lt 0){$used=0}; $idx=0; foreach($c in $controllers){ $name=$c.Name; $total=0; if($c.AdapterRAM){$total=[double]$c.AdapterRAM}; \"${idx}|${name}|${util}|${used}|${total}\"; $idx++ }";
     let Some(output) = run_powershell(script) else {
         return Vec::new();
@@ -845,6 +1786,79 @@ fn collect_windows_gpu_stats() -> Vec<GpuStat> {
     Vec::new()
 }
 
+#[cfg(target_os = "windows")]
+fn collect_page_file() -> Option<PageFileStat> {
+    match collect_page_file_wmi() {
+        Ok(stat) => stat,
+        Err(WmiQueryError::Missing) => None,
+        Err(WmiQueryError::Failed(err)) => {
+            debug!(error = %err, "WMI недоступен, файл подкачки читается через PowerShell");
+            collect_page_file_powershell()
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn collect_page_file_wmi() -> Result<Option<PageFileStat>, WmiQueryError> {
+    let memory = wmi_sensors::perf_memory()?;
+    let Some(memory) = memory.first() else {
+        return Ok(None);
+    };
+    // Файлов подкачки может быть несколько, по одному на диск.
+    let (used_mb, total_mb) = wmi_sensors::page_file_usage()
+        .map(|files| {
+            files.iter().fold((0.0, 0.0), |(used, total), f| {
+                (
+                    used + f.current_usage.unwrap_or(0.0),
+                    total + f.allocated_base_size.unwrap_or(0.0),
+                )
+            })
+        })
+        .unwrap_or_default();
+    let bytes = |v: Option<f64>| v.unwrap_or(0.0).max(0.0) as u64;
+    Ok(Some(PageFileStat {
+        commit_used_bytes: bytes(memory.committed_bytes.as_ref().and_then(|v| v.value())),
+        commit_limit_bytes: bytes(memory.commit_limit.as_ref().and_then(|v| v.value())),
+        pagefile_used_bytes: bytes(Some(used_mb * 1024.0 * 1024.0)),
+        pagefile_total_bytes: bytes(Some(total_mb * 1024.0 * 1024.0)),
+    }))
+}
+
+#[cfg(target_os = "windows")]
+fn collect_page_file_powershell() -> Option<PageFileStat> {
+    let script = "$m=Get-CimInstance Win32_PerfFormattedData_PerfOS_Memory -ErrorAction SilentlyContinue | Select-Object -First 1; if(-not $m){return}; $used=0; $total=0; foreach($f in (Get-CimInstance Win32_PageFileUsage -ErrorAction SilentlyContinue)){ $used+=$f.CurrentUsage; $total+=$f.AllocatedBaseSize }; \"$($m.CommittedBytes)|$($m.CommitLimit)|${used}|${total}\"";
+    let output = run_powershell(script)?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    text.lines().find_map(parse_page_file_line)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn collect_page_file() -> Option<PageFileStat> {
+    None
+}
+
+// Строка PowerShell: выделено байт | предел выделения | занято МБ подкачки | размер подкачки МБ.
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn parse_page_file_line(line: &str) -> Option<PageFileStat> {
+    let parts: Vec<f64> = line
+        .split('|')
+        .map(parse_f64_loose)
+        .collect::<Option<_>>()?;
+    let [commit_used, commit_limit, used_mb, total_mb] = parts[..] else {
+        return None;
+    };
+    let mb = 1024.0 * 1024.0;
+    Some(PageFileStat {
+        commit_used_bytes: commit_used.max(0.0) as u64,
+        commit_limit_bytes: commit_limit.max(0.0) as u64,
+        pagefile_used_bytes: (used_mb * mb).max(0.0) as u64,
+        pagefile_total_bytes: (total_mb * mb).max(0.0) as u64,
+    })
+}
+
 fn collect_nvidia_smi() -> Vec<GpuStat> {
     let output = run_nvidia_smi(&[
         "--query-gpu=index,name,utilization.gpu,memory.used,memory.total,temperature.gpu",
@@ -927,22 +1941,108 @@ fn collect_temps_from_nvidia_smi() -> Vec<TempStat> {
                 sensor: format!("GPU {}", parts[0]),
                 temperature_celsius: temp,
                 critical_temperature_celsius: None,
+                device: None,
             })
         })
         .collect()
 }
 
-fn run_nvidia_smi(args: &[&str]) -> Option<std::process::Output> {
-    if let Ok(output) = Command::new("nvidia-smi").args(args).output() {
-        return Some(output);
+const GPU_ALERT_TOP_PROCESSES: usize = 3;
+
+// Собирается в момент GPU-уведомления, а не на каждом сборе: pmon занимает около секунды.
+pub fn gpu_alert_context() -> Option<GpuAlertContext> {
+    let utilization = run_nvidia_smi(&[
+        "--query-gpu=utilization.encoder,utilization.decoder",
+        "--format=csv,noheader,nounits",
+    ])
+    .filter(|o| o.status.success())?;
+    let mut context = parse_encoder_utilization(&String::from_utf8_lossy(&utilization.stdout));
+    if let Some(pmon) = run_nvidia_smi(&["pmon", "-c", "1", "-s", "um"]) {
+        context.processes = parse_pmon(&String::from_utf8_lossy(&pmon.stdout));
+    }
+    Some(context)
+}
+
+// При нескольких картах берётся самая загруженная.
+fn parse_encoder_utilization(text: &str) -> GpuAlertContext {
+    let mut context = GpuAlertContext::default();
+    for line in text.lines() {
+        let mut parts = line.split(',').map(parse_f64_loose);
+        let (Some(enc), Some(dec)) = (parts.next().flatten(), parts.next().flatten()) else {
+            continue;
+        };
+        context.encoder_percent = Some(context.encoder_percent.map_or(enc, |v| v.max(enc)));
+        context.decoder_percent = Some(context.decoder_percent.map_or(dec, |v| v.max(dec)));
+    }
+    context
+}
+
+// Набор столбцов pmon зависит от версии драйвера, поэтому индексы берутся из заголовка.
+fn parse_pmon(text: &str) -> Vec<GpuProcess> {
+    let mut columns: Vec<&str> = Vec::new();
+    let mut processes: Vec<GpuProcess> = Vec::new();
+    for line in text.lines() {
+        if let Some(header) = line.trim_start().strip_prefix('#') {
+            if columns.is_empty() {
+                columns = header.split_whitespace().collect();
+            }
+            continue;
+        }
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let field = |name: &str| {
+            columns
+                .iter()
+                .position(|c| *c == name)
+                .and_then(|i| fields.get(i).copied())
+        };
+        let Some(pid) = field("pid").and_then(|v| v.parse::<u32>().ok()) else {
+            continue;
+        };
+        let name = field("command").unwrap_or("?").to_string();
+        let sm_percent = field("sm").and_then(|v| v.parse::<f64>().ok());
+        let memory_mb = field("fb").and_then(|v| v.parse::<u64>().ok());
+        // Процесс может использовать несколько карт.
+        if let Some(existing) = processes.iter_mut().find(|p| p.pid == pid) {
+            existing.sm_percent = match (existing.sm_percent, sm_percent) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            };
+            existing.memory_mb = match (existing.memory_mb, memory_mb) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            };
+            continue;
+        }
+        processes.push(GpuProcess {
+            pid,
+            name,
+            sm_percent,
+            memory_mb,
+        });
+    }
+    processes.sort_by(|a, b| {
+        b.sm_percent
+            .unwrap_or(0.0)
+            .total_cmp(&a.sm_percent.unwrap_or(0.0))
+            .then(b.memory_mb.cmp(&a.memory_mb))
+    });
+    processes.truncate(GPU_ALERT_TOP_PROCESSES);
+    processes
+}
+
+fn run_nvidia_smi(args: &[&str]) -> Option<Output> {
+    match output_with_timeout(Command::new("nvidia-smi").args(args), COMMAND_TIMEOUT) {
+        Ok(output) => return Some(output),
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return None,
+        Err(_) => {}
     }
 
     #[cfg(target_os = "windows")]
     {
-        if let Ok(output) = Command::new(r"C:\Windows\System32\nvidia-smi.exe")
-            .args(args)
-            .output()
-        {
+        if let Ok(output) = output_with_timeout(
+            Command::new(r"C:\Windows\System32\nvidia-smi.exe").args(args),
+            COMMAND_TIMEOUT,
+        ) {
             return Some(output);
         }
     }
@@ -950,6 +2050,48 @@ fn run_nvidia_smi(args: &[&str]) -> Option<std::process::Output> {
     None
 }
 
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> std::io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Каналы читаются в отдельных потоках, иначе процесс может встать на заполненном буфере.
+    let stdout = child.stdout.take().map(read_pipe);
+    let stderr = child.stderr.take().map(read_pipe);
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("команда не завершилась за {} мс", timeout.as_millis()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    let join = |handle: Option<std::thread::JoinHandle<Vec<u8>>>| {
+        handle.and_then(|h| h.join().ok()).unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: join(stdout),
+        stderr: join(stderr),
+    })
+}
+
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
 fn parse_f64_loose(input: &str) -> Option<f64> {
     let trimmed = input.trim();
     if let Ok(v) = trimmed.parse::<f64>() {
@@ -979,6 +2121,7 @@ fn parse_f64_loose(input: &str) -> Option<f64> {
     filtered.replace(',', ".").parse::<f64>().ok()
 }
 
+#[cfg(target_os = "windows")]
 fn is_lhm_cpu_temp_sensor(identifier: &str, parent: &str, name: &str) -> bool {
     let combined = format!("{identifier}|{parent}|{name}");
     let has_gpu_marker = ["gpu", "nvidia", "amdgpu", "radeon"]
@@ -1017,6 +2160,7 @@ fn parse_u64_loose(input: &str) -> Option<u64> {
     parse_f64_loose(input).map(|v| if v < 0.0 { 0 } else { v as u64 })
 }
 
+#[cfg(target_os = "windows")]
 fn normalize_windows_thermal_zone_temp(raw: f64) -> Option<f64> {
     if !raw.is_finite() || raw <= 0.0 {
         return None;
@@ -1037,12 +2181,13 @@ fn normalize_windows_thermal_zone_temp(raw: f64) -> Option<f64> {
     Some(v)
 }
 
+#[cfg(target_os = "windows")]
 fn decode_cmd_stdout(bytes: &[u8]) -> String {
     if let Ok(utf8) = std::str::from_utf8(bytes) {
         return utf8.to_string();
     }
 
-    if bytes.len() >= 2 && bytes.len() % 2 == 0 {
+    if bytes.len() >= 2 && bytes.len().is_multiple_of(2) {
         let mut u16buf = Vec::with_capacity(bytes.len() / 2);
         let mut i = 0;
         while i + 1 < bytes.len() {
@@ -1058,31 +2203,331 @@ fn decode_cmd_stdout(bytes: &[u8]) -> String {
 }
 
 #[cfg(target_os = "windows")]
-fn run_powershell(script: &str) -> Option<std::process::Output> {
+fn run_powershell(script: &str) -> Option<Output> {
     let wrapped_script = format!(
         "[Console]::OutputEncoding=[System.Text.UTF8Encoding]::new($false); $OutputEncoding=[System.Text.UTF8Encoding]::new($false); chcp 65001 > $null; {script}"
     );
-    if let Ok(output) = Command::new("powershell")
-        .args(["-NoProfile", "-Command", &wrapped_script])
-        .output()
-    {
-        return Some(output);
+    match output_with_timeout(
+        Command::new("powershell").args(["-NoProfile", "-Command", &wrapped_script]),
+        COMMAND_TIMEOUT,
+    ) {
+        Ok(output) => return Some(output),
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return None,
+        Err(_) => {}
     }
 
-    Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
-        .args(["-NoProfile", "-Command", &wrapped_script])
-        .output()
-        .ok()
+    output_with_timeout(
+        Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe").args([
+            "-NoProfile",
+            "-Command",
+            &wrapped_script,
+        ]),
+        COMMAND_TIMEOUT,
+    )
+    .ok()
 }
 
 #[cfg(target_os = "windows")]
-fn run_typeperf<const N: usize>(args: [&str; N]) -> Option<std::process::Output> {
-    if let Ok(output) = Command::new("typeperf").args(args).output() {
-        return Some(output);
+fn run_typeperf<const N: usize>(args: [&str; N]) -> Option<Output> {
+    match output_with_timeout(Command::new("typeperf").args(args), COMMAND_TIMEOUT) {
+        Ok(output) => return Some(output),
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return None,
+        Err(_) => {}
     }
 
-    Command::new(r"C:\Windows\System32\typeperf.exe")
-        .args(args)
-        .output()
-        .ok()
+    output_with_timeout(
+        Command::new(r"C:\Windows\System32\typeperf.exe").args(args),
+        COMMAND_TIMEOUT,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn external_commands_are_killed_after_timeout() {
+        let output = output_with_timeout(
+            Command::new("sh").args(["-c", "echo ok"]),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"ok\n");
+
+        let started = Instant::now();
+        let err = output_with_timeout(
+            Command::new("sh").args(["-c", "sleep 10"]),
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn gpu_alert_context_is_parsed_from_nvidia_smi() {
+        let context = parse_encoder_utilization("12, 3\n40, [N/A]\n25, 7\n");
+        assert_eq!(context.encoder_percent, Some(25.0));
+        assert_eq!(context.decoder_percent, Some(7.0));
+
+        let pmon = "# gpu         pid   type     sm    mem    enc    dec    jpg    ofa     fb   ccpm    command
+# Idx           #    C/G      %      %      %      %      %      %     MB     MB    name
+    0       4242     C     71     30      -      -      -      -   8120      0    python
+    0       1337     C      5      1      -      -      -      -    512      0    ffmpeg
+    1       4242     C     20     10      -      -      -      -   4000      0    python
+    0          -     -      -      -      -      -      -      -      -      -    -
+    1        900     G      -      -      -      -      -      -   1024      0    Xorg
+    1        901     G      -      -      -      -      -      -     90      0    gnome-shell
+";
+        let processes = parse_pmon(pmon);
+        assert_eq!(processes.len(), 3);
+        assert_eq!(processes[0].name, "python");
+        assert_eq!(processes[0].sm_percent, Some(91.0));
+        assert_eq!(processes[0].memory_mb, Some(12120));
+        assert_eq!(processes[1].name, "ffmpeg");
+        // Без sm% процессы сортируются по видеопамяти.
+        assert_eq!(processes[2].name, "Xorg");
+    }
+
+    #[test]
+    fn proc_net_dev_drops_are_parsed() {
+        let text = "Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0
+  eth0: 52000    400    3   17    0     0          0         5    31000     300    1    2    0     0       0          0
+";
+        let drops = parse_proc_net_dev_drops(text);
+        assert_eq!(drops.get("eth0"), Some(&(17, 2)));
+        assert_eq!(drops.get("lo"), Some(&(0, 0)));
+        assert_eq!(drops.len(), 2);
+    }
+
+    #[test]
+    fn smartctl_output_is_parsed_and_matched_to_namespaces() {
+        let scan = br#"{"devices":[{"name":"/dev/sda","type":"sat"},{"name":"/dev/nvme0","type":"nvme"}]}"#;
+        assert_eq!(
+            parse_smartctl_scan(scan),
+            vec![
+                ("/dev/sda".to_string(), "sat".to_string()),
+                ("/dev/nvme0".to_string(), "nvme".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_smartctl_temperature(br#"{"temperature":{"current":41}}"#),
+            Some(41.0)
+        );
+        // Диск в standby: smartctl не отдаёт атрибуты.
+        assert_eq!(parse_smartctl_temperature(br#"{"smartctl":{}}"#), None);
+
+        let temps = BTreeMap::from([("nvme0".to_string(), 45.0), ("sda".to_string(), 38.0)]);
+        assert_eq!(drive_temperature(&temps, "nvme0n1"), Some(45.0));
+        assert_eq!(drive_temperature(&temps, "sda"), Some(38.0));
+        assert_eq!(drive_temperature(&temps, "sdb"), None);
+    }
+
+    #[test]
+    fn wg_dump_peers_are_parsed_without_interface_lines() {
+        let dump = "wg0\tPRIVATE=\tPUBLIC=\t51820\toff\n\
+            wg0\tpeerA+key/0000000000000000000000000000000=\t(none)\t203.0.113.5:51820\t10.0.0.2/32,fd00::2/128\t1700000000\t1024\t2048\t25\n\
+            wg0\tpeerB=\t(none)\t(none)\t(none)\t0\t0\t0\toff\n";
+        let peers = parse_wg_dump(dump);
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].iface, "wg0");
+        assert_eq!(peers[0].short_key(), "peerA+ke");
+        assert_eq!(peers[0].endpoint.as_deref(), Some("203.0.113.5:51820"));
+        assert_eq!(peers[0].allowed_ips, vec!["10.0.0.2/32", "fd00::2/128"]);
+        assert_eq!(peers[0].handshake_age_secs(1_700_000_090), Some(90));
+        assert_eq!(
+            (peers[0].rx_bytes_total, peers[0].tx_bytes_total),
+            (1024, 2048)
+        );
+        assert_eq!(peers[1].endpoint, None);
+        assert!(peers[1].allowed_ips.is_empty());
+        assert_eq!(peers[1].handshake_age_secs(1_700_000_090), None);
+    }
+
+    #[test]
+    fn counter_samples_become_sensors() {
+        let counters = [
+            WindowsCounterConfig {
+                path: r"\Processor(_Total)\% Privileged Time".to_string(),
+                name: Some("CPU privileged".to_string()),
+                sensor_type: "load".to_string(),
+            },
+            WindowsCounterConfig {
+                path: r"\LogicalDisk(*)\Avg. Disk sec/Read".to_string(),
+                name: None,
+                sensor_type: "counter".to_string(),
+            },
+        ];
+        let sensors = [
+            counter_sensor(&counters[0], Some("_Total"), 12.5),
+            counter_sensor(&counters[1], Some("C:"), 0.004),
+        ];
+        assert_eq!(sensors[0].name, "CPU privileged");
+        assert_eq!(sensors[0].sensor_type, "load");
+        assert_eq!(sensors[0].value, 12.5);
+        assert_eq!(
+            sensors[0].identifier,
+            r"counter\processor(_total)\% privileged time"
+        );
+        assert_eq!(sensors[1].name, r"\LogicalDisk(*)\Avg. Disk sec/Read (C:)");
+        assert_eq!(
+            sensors[1].identifier,
+            r"counter\logicaldisk(c:)\avg. disk sec/read"
+        );
+        assert_eq!(sensors[1].value, 0.004);
+    }
+
+    #[test]
+    fn cpu_steal_and_hypervisor_are_detected() {
+        let stat = parse_proc_stat_steal(
+            "cpu  100 0 50 800 10 0 0 40 30 0\ncpu0 50 0 25 400 5 0 0 20 15 0\n",
+        )
+        .unwrap();
+        assert_eq!((stat.steal_ticks_total, stat.cpu_ticks_total), (40, 1000));
+        assert!(parse_proc_stat_steal("cpu  100 0 50 800\n").is_none());
+
+        let flags = "processor\t: 0\nflags\t\t: fpu vme hypervisor lahf_lm\n";
+        assert_eq!(
+            hypervisor_name(flags, "QEMU Standard PC (Q35 + ICH9, 2009)", "").as_deref(),
+            Some("KVM")
+        );
+        assert_eq!(
+            hypervisor_name(flags, "Microsoft Corporation Virtual Machine", "").as_deref(),
+            Some("Hyper-V")
+        );
+        assert_eq!(hypervisor_name("", "", "xen").as_deref(), Some("Xen"));
+        assert_eq!(
+            hypervisor_name(flags, "Hetzner vServer", "").as_deref(),
+            Some("unknown")
+        );
+        assert_eq!(
+            hypervisor_name("flags\t: fpu vme\n", "ASUS PRIME B550", ""),
+            None
+        );
+    }
+
+    #[test]
+    fn read_only_flags_follow_last_mount_entry() {
+        let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+            /dev/loop0 /snap/core squashfs ro,nodev,relatime 0 0\n\
+            /dev/sdb1 /mnt/my\\040disk ext4 ro,relatime 0 0\n\
+            /dev/sdc1 /data ext4 ro,relatime 0 0\n\
+            /dev/sdc1 /data ext4 rw,relatime 0 0\n";
+        let read_only = parse_read_only_mounts(mounts);
+        assert_eq!(read_only.len(), 2);
+        assert!(read_only.contains("/snap/core"));
+        assert!(read_only.contains("/mnt/my disk"));
+    }
+
+    #[test]
+    fn disk_io_counters_are_parsed_from_both_platforms() {
+        let diskstats = "   8       0 sda 1000 10 8000 4000 500 5 4000 6000 0 7000 10000\n\
+               8       1 sda1 900 10 7000 3500 400 5 3000 5000 0 6000 9000\n\
+               7       0 loop0 50 0 100 10 0 0 0 0 0 10 10\n\
+              11       0 sr0 0 0 0 0 0 0 0 0 0 0 0\n";
+        let stats = parse_proc_diskstats(diskstats, |name| {
+            !name.starts_with("loop") && !name.ends_with('1')
+        });
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].device, "sda");
+        assert_eq!(
+            (stats[0].reads_total, stats[0].read_time_ms_total),
+            (1000, 4000)
+        );
+        assert_eq!(
+            (stats[0].writes_total, stats[0].write_time_ms_total),
+            (500, 6000)
+        );
+
+        let win = parse_perf_disk_line("0 C:|20000000|1000|50000000|2000|10000000").unwrap();
+        assert_eq!(win.device, "0 C:");
+        assert_eq!((win.reads_total, win.read_time_ms_total), (1000, 2000));
+        assert_eq!((win.writes_total, win.write_time_ms_total), (2000, 5000));
+        assert!(parse_perf_disk_line("0 C:|1|2|3|4|0").is_none());
+    }
+
+    #[test]
+    fn cgroup_limits_are_parsed_for_both_versions() {
+        let no_env = |_: &str| None;
+        assert_eq!(cgroup_v2_path("0::/\n"), Some("/"));
+        assert_eq!(cgroup_v2_path("12:memory:/docker/abc\n0::/\n"), None);
+        assert_eq!(
+            container_runtime("0::/\n", true, false, no_env).as_deref(),
+            Some("docker")
+        );
+        assert_eq!(
+            container_runtime("0::/\n", false, false, |name| {
+                (name == "KUBERNETES_SERVICE_HOST").then(|| "10.0.0.1".to_string())
+            })
+            .as_deref(),
+            Some("kubernetes")
+        );
+        assert_eq!(
+            container_runtime("0::/user.slice/user-1000.slice\n", false, false, no_env),
+            None
+        );
+
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cfs_quota("-1", "100000"), None);
+        assert_eq!(parse_cfs_quota("50000\n", "100000\n"), Some(0.5));
+        let host = 16 * 1024 * 1024 * 1024;
+        assert_eq!(parse_memory_limit("536870912\n", host), Some(536_870_912));
+        assert_eq!(parse_memory_limit("max\n", host), None);
+        assert_eq!(parse_memory_limit("9223372036854771712\n", host), None);
+        assert_eq!(
+            cgroup_stat_value("anon 100\ninactive_file 4096\n", "inactive_file"),
+            Some(4096)
+        );
+    }
+
+    #[test]
+    fn time_sync_status_is_parsed_for_each_daemon() {
+        let chrony = parse_chrony_tracking(
+            "A9FEA9FE,169.254.169.254,3,1760000000.1,-0.000123456,0.000002,0.00001,-12.3,0.001,0.02,0.0004,0.0005,64.5,Normal\n",
+        )
+        .unwrap();
+        assert!(chrony.synchronized);
+        assert_eq!(chrony.offset_seconds, Some(-0.000123456));
+        assert_eq!(chrony.server.as_deref(), Some("169.254.169.254"));
+        let lost = parse_chrony_tracking(
+            "00000000,,0,0.0,0.0,0.0,0.0,0.0,0.0,0.0,1.0,1.0,0.0,Not synchronised",
+        )
+        .unwrap();
+        assert!(!lost.synchronized);
+        assert_eq!(lost.server, None);
+
+        let timedatectl =
+            parse_timedatectl_show("Timezone=UTC\nNTP=yes\nNTPSynchronized=no\n").unwrap();
+        assert_eq!(timedatectl.source, "timedatectl");
+        assert!(!timedatectl.synchronized);
+        assert!(parse_timedatectl_show("Timezone=UTC\n").is_none());
+
+        assert!(
+            parse_w32tm_status("Leap Indicator: 0(no warning)\nStratum: 4\n")
+                .unwrap()
+                .synchronized
+        );
+        assert!(
+            !parse_w32tm_status("Индикатор коррекции: 3(не синхронизировано)\n")
+                .unwrap()
+                .synchronized
+        );
+    }
+
+    #[test]
+    fn page_file_line_is_parsed_from_megabytes() {
+        let stat = parse_page_file_line("12884901888|34359738368|2048|8192").unwrap();
+        assert_eq!(stat.commit_used_bytes, 12 << 30);
+        assert_eq!(stat.commit_limit_bytes, 32 << 30);
+        assert_eq!(stat.pagefile_used_bytes, 2 << 30);
+        assert_eq!(stat.pagefile_total_bytes, 8 << 30);
+        assert_eq!(stat.pagefile_usage_percent(), 25.0);
+        assert!(parse_page_file_line("1|2|3").is_none());
+    }
 }
@@ -0,0 +1,247 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use wmi::{COMLibrary, WMIConnection, WMIError};
+
+const CIMV2: &str = "ROOT\\CIMV2";
+const ACPI: &str = "ROOT\\WMI";
+const LHM_NAMESPACES: [&str; 2] = ["ROOT\\LibreHardwareMonitor", "ROOT\\OpenHardwareMonitor"];
+
+// LibreHardwareMonitor может быть запущен позже агента, поэтому отсутствующий класс перепроверяется.
+const MISSING_RECHECK: Duration = Duration::from_secs(300);
+
+const WBEM_E_NOT_FOUND: u32 = 0x8004_1002;
+const WBEM_E_NOT_SUPPORTED: u32 = 0x8004_100C;
+const WBEM_E_INVALID_NAMESPACE: u32 = 0x8004_100E;
+const WBEM_E_INVALID_CLASS: u32 = 0x8004_1010;
+
+#[derive(Debug)]
+pub enum WmiQueryError {
+    // Пространства имён или класса нет: через PowerShell данных тоже не будет.
+    Missing,
+    // WMI недоступен из процесса: имеет смысл запасной путь через PowerShell.
+    Failed(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LhmSensor {
+    pub sensor_type: Option<String>,
+    pub name: Option<String>,
+    pub value: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub identifier: Option<String>,
+    pub parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ThermalZone {
+    pub instance_name: Option<String>,
+    pub current_temperature: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VideoController {
+    pub name: Option<String>,
+    #[serde(rename = "AdapterRAM")]
+    pub adapter_ram: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GpuEngine {
+    pub utilization_percentage: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GpuProcessMemory {
+    pub dedicated_usage: Option<f64>,
+}
+
+// uint64-свойства WMI отдаёт строкой, остальные целые — числом.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum WmiNumber {
+    Number(f64),
+    Text(String),
+}
+
+impl WmiNumber {
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            Self::Number(v) => Some(*v),
+            Self::Text(text) => text.trim().parse().ok(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PerfMemory {
+    pub committed_bytes: Option<WmiNumber>,
+    pub commit_limit: Option<WmiNumber>,
+}
+
+// Размеры файлов подкачки в мегабайтах.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PageFileUsage {
+    pub allocated_base_size: Option<f64>,
+    pub current_usage: Option<f64>,
+}
+
+struct Session {
+    com: COMLibrary,
+    connections: HashMap<&'static str, WMIConnection>,
+    missing: HashMap<(&'static str, &'static str), Instant>,
+}
+
+thread_local! {
+    // COM инициализируется для потока, поэтому соединения живут в потоке сбора и переиспользуются.
+    static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+pub fn lhm_sensors() -> Result<Vec<LhmSensor>, WmiQueryError> {
+    let mut failed = None;
+    for namespace in LHM_NAMESPACES {
+        match query(
+            namespace,
+            "Sensor",
+            "SensorType, Name, Value, Min, Max, Identifier, Parent",
+        ) {
+            Ok(rows) if !rows.is_empty() => return Ok(rows),
+            Ok(_) | Err(WmiQueryError::Missing) => {}
+            Err(err) => failed = Some(err),
+        }
+    }
+    failed.map_or(Ok(Vec::new()), Err)
+}
+
+pub fn thermal_zones() -> Result<Vec<ThermalZone>, WmiQueryError> {
+    query(
+        ACPI,
+        "MSAcpi_ThermalZoneTemperature",
+        "InstanceName, CurrentTemperature",
+    )
+}
+
+pub fn video_controllers() -> Result<Vec<VideoController>, WmiQueryError> {
+    query(CIMV2, "Win32_VideoController", "Name, AdapterRAM")
+}
+
+pub fn gpu_engines() -> Result<Vec<GpuEngine>, WmiQueryError> {
+    query(
+        CIMV2,
+        "Win32_PerfFormattedData_GPUPerformanceCounters_GPUEngine",
+        "UtilizationPercentage",
+    )
+}
+
+pub fn gpu_process_memory() -> Result<Vec<GpuProcessMemory>, WmiQueryError> {
+    query(
+        CIMV2,
+        "Win32_PerfFormattedData_GPUPerformanceCounters_GPUProcessMemory",
+        "DedicatedUsage",
+    )
+}
+
+pub fn perf_memory() -> Result<Vec<PerfMemory>, WmiQueryError> {
+    query(
+        CIMV2,
+        "Win32_PerfFormattedData_PerfOS_Memory",
+        "CommittedBytes, CommitLimit",
+    )
+}
+
+pub fn page_file_usage() -> Result<Vec<PageFileUsage>, WmiQueryError> {
+    query(
+        CIMV2,
+        "Win32_PageFileUsage",
+        "AllocatedBaseSize, CurrentUsage",
+    )
+}
+
+fn query<T: DeserializeOwned>(
+    namespace: &'static str,
+    class: &'static str,
+    fields: &str,
+) -> Result<Vec<T>, WmiQueryError> {
+    SESSION.with(|cell| {
+        let mut session = cell.borrow_mut();
+        if session.is_none() {
+            let com = COMLibrary::new().map_err(|err| WmiQueryError::Failed(err.to_string()))?;
+            *session = Some(Session {
+                com,
+                connections: HashMap::new(),
+                missing: HashMap::new(),
+            });
+        }
+        match session.as_mut() {
+            Some(session) => session.query(namespace, class, fields),
+            None => Err(WmiQueryError::Failed("COM не инициализирован".to_string())),
+        }
+    })
+}
+
+impl Session {
+    fn query<T: DeserializeOwned>(
+        &mut self,
+        namespace: &'static str,
+        class: &'static str,
+        fields: &str,
+    ) -> Result<Vec<T>, WmiQueryError> {
+        let key = (namespace, class);
+        if let Some(since) = self.missing.get(&key) {
+            if since.elapsed() < MISSING_RECHECK {
+                return Err(WmiQueryError::Missing);
+            }
+            self.missing.remove(&key);
+        }
+
+        let result = self
+            .connection(namespace)
+            .and_then(|conn| conn.raw_query(format!("SELECT {fields} FROM {class}")));
+        result.map_err(|err| {
+            if is_missing(&err) {
+                debug!(namespace, class, "класс WMI недоступен");
+                self.missing.insert(key, Instant::now());
+                WmiQueryError::Missing
+            } else {
+                // Соединение могло испортиться (например, после перезапуска службы WMI): пересоздадим.
+                self.connections.remove(namespace);
+                WmiQueryError::Failed(err.to_string())
+            }
+        })
+    }
+
+    fn connection(&mut self, namespace: &'static str) -> Result<&WMIConnection, WMIError> {
+        let com = self.com;
+        match self.connections.entry(namespace) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                Ok(entry.insert(WMIConnection::with_namespace_path(namespace, com)?))
+            }
+        }
+    }
+}
+
+fn is_missing(err: &WMIError) -> bool {
+    let WMIError::HResultError { hres } = err else {
+        return false;
+    };
+    [
+        WBEM_E_NOT_FOUND,
+        WBEM_E_NOT_SUPPORTED,
+        WBEM_E_INVALID_NAMESPACE,
+        WBEM_E_INVALID_CLASS,
+    ]
+    .contains(&(*hres as u32))
+}
@@ -1,312 +1,4100 @@
+use crate::state::{AlertSeverity, TimeSyncStat};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::net::SocketAddr;
-use std::path::Path;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use thiserror::Error;
+use tokio_rustls::rustls::pki_types::ServerName;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
+    pub include: Vec<String>,
     pub listen: String,
     pub interval_secs: u64,
+    #[serde(default = "default_data_dir")]
+    pub data_dir: String,
+    #[serde(default = "default_collector_stale_secs")]
+    pub collector_stale_secs: u64,
+    #[serde(default = "default_collect_timeout_secs")]
+    pub collect_timeout_secs: u64,
+    // Общие профили проверок, подключаемые через module; раскрываются при загрузке.
+    #[serde(default)]
+    pub modules: HashMap<String, serde_yaml::Value>,
     #[serde(default)]
     pub http_checks: Vec<HttpCheckConfig>,
     #[serde(default)]
     pub tcp_checks: Vec<TcpCheckConfig>,
     #[serde(default)]
+    pub ssh_checks: Vec<SshCheckConfig>,
+    #[serde(default)]
+    pub rules: Vec<AlertRuleConfig>,
+    #[serde(default)]
     pub telegram: TelegramConfig,
+    #[serde(default)]
+    pub notifiers: NotifiersConfig,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+    #[serde(default)]
+    pub check_events: CheckEventsConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub disks: DisksConfig,
+    #[serde(default)]
+    pub wireguard: WireguardConfig,
+    #[serde(default)]
+    pub container: ContainerConfig,
+    #[serde(default)]
+    pub dir_sizes: DirSizesConfig,
+    #[serde(default)]
+    pub checks: ChecksConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub aggregator: AggregatorConfig,
+    #[serde(default)]
+    pub push: PushConfig,
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    pub alert_api: AlertApiConfig,
+    #[serde(default)]
+    pub status_page: StatusPageConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub speedtest: SpeedtestConfig,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    #[serde(default)]
+    pub windows_counters: Vec<WindowsCounterConfig>,
+    #[serde(default)]
+    pub relay: Vec<RelayConfig>,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub sensors: SensorsConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct HttpCheckConfig {
-    pub name: String,
-    pub url: String,
-    pub timeout_ms: u64,
-    #[serde(default = "default_expected_status")]
-    pub expected_status: u16,
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default = "default_log_stdout")]
+    pub stdout: bool,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub max_size_mb: u64,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    #[serde(default = "default_log_keep_files")]
+    pub keep_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            stdout: default_log_stdout(),
+            file: None,
+            max_size_mb: 0,
+            rotation: LogRotation::default(),
+            keep_files: default_log_keep_files(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct TcpCheckConfig {
-    pub name: String,
-    pub host: String,
-    pub port: u16,
-    pub timeout_ms: u64,
+pub struct AggregatorConfig {
+    #[serde(default = "default_aggregator_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_aggregator_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub hosts: Vec<AggregatedHostConfig>,
+    #[serde(default)]
+    pub ingest: IngestConfig,
+}
+
+impl Default for AggregatorConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_aggregator_interval_secs(),
+            timeout_secs: default_aggregator_timeout_secs(),
+            hosts: Vec::new(),
+            ingest: IngestConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct TelegramConfig {
+pub struct IngestConfig {
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_ingest_stale_secs")]
+    pub stale_secs: u64,
+    // Хост, не присылавший состояние дольше этого срока, удаляется из списка.
+    #[serde(default = "default_ingest_retention_secs")]
+    pub retention_secs: u64,
+    #[serde(default = "default_ingest_max_hosts")]
+    pub max_hosts: usize,
+    // Шаблоны имён (* и ?); пустой список принимает любые имена.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            token_env: None,
+            token: None,
+            stale_secs: default_ingest_stale_secs(),
+            retention_secs: default_ingest_retention_secs(),
+            max_hosts: default_ingest_max_hosts(),
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+impl IngestConfig {
+    pub fn enabled(&self) -> bool {
+        self.token_env.is_some() || self.token.is_some()
+    }
+
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        self.allowed_hosts.is_empty() || self.allowed_hosts.iter().any(|p| glob_match(p, host))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PushConfig {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default = "default_aggregator_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_aggregator_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            token_env: None,
+            token: None,
+            host: None,
+            interval_secs: default_aggregator_interval_secs(),
+            timeout_secs: default_aggregator_timeout_secs(),
+        }
+    }
+}
+
+impl PushConfig {
+    pub fn ingest_url(&self) -> Option<String> {
+        self.url
+            .as_deref()
+            .map(|url| format!("{}/api/ingest", url.trim_end_matches('/')))
+    }
+
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
+    }
+}
+
+// Приём уведомлений от скриптов и cron через POST /api/alerts.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AlertApiConfig {
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    // Через сколько секунд уведомление снимается, если запрос не задал ttl_secs; без него — до явного resolved.
+    #[serde(default)]
+    pub default_ttl_secs: Option<u64>,
+}
+
+impl AlertApiConfig {
+    pub fn enabled(&self) -> bool {
+        self.token_env.is_some() || self.token.is_some()
+    }
+
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
+    }
+}
+
+// Страница /status для посторонних: только имена и состояния проверок, без показателей хоста.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StatusPageConfig {
     #[serde(default)]
     pub enabled: bool,
-    #[serde(default = "default_bot_token_env")]
-    pub bot_token_env: String,
     #[serde(default)]
-    pub bot_token: Option<String>,
+    pub title: Option<String>,
+    // Без токена страница открыта всем.
     #[serde(default)]
-    pub allowed_chat_ids: Vec<i64>,
-    #[serde(default = "default_rate_limit_per_minute")]
-    pub rate_limit_per_minute: u32,
-    pub public_base_url: Option<String>,
+    pub token_env: Option<String>,
     #[serde(default)]
-    pub alerts: AlertsConfig,
+    pub token: Option<String>,
+}
+
+impl StatusPageConfig {
+    pub fn protected(&self) -> bool {
+        self.token_env.is_some() || self.token.is_some()
+    }
+
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
+    }
+}
+
+// Токен для чтения /api/state, /api/logs, /api/history/export и остальных GET /api/*.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ApiConfig {
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl ApiConfig {
+    pub fn protected(&self) -> bool {
+        self.token_env.is_some() || self.token.is_some()
+    }
+
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
+    }
 }
 
+// Пинги «dead man's switch» после успешных сборов (Healthchecks.io, Uptime Kuma push).
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct AlertsConfig {
+pub struct HeartbeatConfig {
     #[serde(default)]
-    pub enabled_by_default: bool,
-    #[serde(default = "default_repeat_interval_secs")]
-    pub repeat_interval_secs: u64,
-    #[serde(default = "default_fail_threshold")]
-    pub fail_threshold: u32,
-    #[serde(default = "default_recovery_notify")]
-    pub recovery_notify: bool,
-    #[serde(default = "default_resource_alerts_enabled")]
-    pub resource_alerts_enabled: bool,
-    #[serde(default = "default_gpu_load_threshold_percent")]
-    pub gpu_load_threshold_percent: f64,
-    #[serde(default = "default_gpu_temp_threshold_celsius")]
-    pub gpu_temp_threshold_celsius: f64,
-    #[serde(default = "default_cpu_temp_threshold_celsius")]
-    pub cpu_temp_threshold_celsius: f64,
-    #[serde(default = "default_cpu_load_threshold_percent")]
-    pub cpu_load_threshold_percent: f64,
-    #[serde(default = "default_ram_usage_threshold_percent")]
-    pub ram_usage_threshold_percent: f64,
-    #[serde(default = "default_disk_usage_threshold_percent")]
-    pub disk_usage_threshold_percent: f64,
-    #[serde(default = "default_resource_alert_cooldown_secs")]
-    pub resource_alert_cooldown_secs: u64,
+    pub url: Option<String>,
+    // Пингуется вместо url, если с прошлого пинга не было нового сбора.
+    #[serde(default)]
+    pub fail_url: Option<String>,
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_aggregator_timeout_secs")]
+    pub timeout_secs: u64,
 }
 
-impl Default for TelegramConfig {
+impl Default for HeartbeatConfig {
     fn default() -> Self {
         Self {
-            enabled: false,
-            bot_token_env: default_bot_token_env(),
-            bot_token: None,
-            allowed_chat_ids: Vec::new(),
-            rate_limit_per_minute: default_rate_limit_per_minute(),
-            public_base_url: None,
-            alerts: AlertsConfig::default(),
+            url: None,
+            fail_url: None,
+            interval_secs: default_heartbeat_interval_secs(),
+            timeout_secs: default_aggregator_timeout_secs(),
         }
     }
 }
 
-impl Default for AlertsConfig {
+// Настройки выдачи /metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MetricsConfig {
+    // Примеры в гистограмме задержек проверок; видны только в формате OpenMetrics.
+    #[serde(default)]
+    pub exemplars: bool,
+}
+
+// Выбор температуры CPU среди датчиков: ярусы по убыванию приоритета.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SensorsConfig {
+    #[serde(default = "default_cpu_temperature_tiers")]
+    pub cpu_temperature: Vec<SensorTierConfig>,
+}
+
+impl Default for SensorsConfig {
     fn default() -> Self {
         Self {
-            enabled_by_default: true,
-            repeat_interval_secs: default_repeat_interval_secs(),
-            fail_threshold: default_fail_threshold(),
-            recovery_notify: default_recovery_notify(),
-            resource_alerts_enabled: default_resource_alerts_enabled(),
-            gpu_load_threshold_percent: default_gpu_load_threshold_percent(),
-            gpu_temp_threshold_celsius: default_gpu_temp_threshold_celsius(),
-            cpu_temp_threshold_celsius: default_cpu_temp_threshold_celsius(),
-            cpu_load_threshold_percent: default_cpu_load_threshold_percent(),
-            ram_usage_threshold_percent: default_ram_usage_threshold_percent(),
-            disk_usage_threshold_percent: default_disk_usage_threshold_percent(),
-            resource_alert_cooldown_secs: default_resource_alert_cooldown_secs(),
+            cpu_temperature: default_cpu_temperature_tiers(),
         }
     }
 }
 
-#[derive(Debug, Error)]
-pub enum ConfigError {
-    #[error("не удалось прочитать файл конфигурации {path}: {source}")]
-    Read {
-        path: String,
-        source: std::io::Error,
-    },
-    #[error("не удалось разобрать YAML в {path}: {source}")]
-    Parse {
-        path: String,
-        source: serde_yaml::Error,
-    },
-    #[error("ошибка валидации конфигурации: {0}")]
-    Validation(String),
+// Подстроки имени датчика без учёта регистра; пустой include подходит любому датчику.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SensorTierConfig {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
-impl Config {
-    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
-        let path_ref = path.as_ref();
-        let path_display = path_ref.display().to_string();
-        let text = fs::read_to_string(path_ref).map_err(|source| ConfigError::Read {
-            path: path_display.clone(),
-            source,
-        })?;
+// Единицы и формат чисел в Telegram и других человекочитаемых представлениях.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DisplayConfig {
+    #[serde(default)]
+    pub bytes: ByteUnits,
+    #[serde(default)]
+    pub rate: RateUnits,
+    #[serde(default)]
+    pub temperature: TemperatureUnit,
+    #[serde(default)]
+    pub decimal_separator: DecimalSeparator,
+    // Часовой пояс IANA (Europe/Moscow); без него время выводится в UTC.
+    #[serde(default)]
+    pub timezone: Option<chrono_tz::Tz>,
+}
 
-        let cfg: Config = serde_yaml::from_str(&text).map_err(|source| ConfigError::Parse {
-            path: path_display,
-            source,
-        })?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteUnits {
+    // Кратность 1024: ГиБ, MiB/s.
+    #[default]
+    Binary,
+    // Кратность 1000: ГБ, MB/s.
+    Decimal,
+}
 
-        cfg.validate()?;
-        Ok(cfg)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateUnits {
+    #[default]
+    Bytes,
+    Bits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum DecimalSeparator {
+    #[default]
+    #[serde(rename = ".")]
+    Dot,
+    #[serde(rename = ",")]
+    Comma,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiscoveryConfig {
+    #[serde(default)]
+    pub advertise: bool,
+    #[serde(default)]
+    pub browse: bool,
+    #[serde(default)]
+    pub instance: Option<String>,
+}
+
+fn resolve_secret(env: Option<&str>, value: Option<&str>) -> Option<String> {
+    env.and_then(|name| std::env::var(name).ok())
+        .or_else(|| value.map(str::to_string))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// Имя хоста попадает в callback-данные Telegram (до 64 байт) и в метку host.
+pub fn valid_host_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AggregatedHostConfig {
+    pub name: String,
+    pub url: String,
+    // Токен api опрашиваемого агента, если его /api/state закрыт.
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl AggregatedHostConfig {
+    pub fn resolve_token(&self) -> Option<String> {
+        resolve_secret(self.token_env.as_deref(), self.token.as_deref())
     }
 
-    pub fn validate(&self) -> Result<(), ConfigError> {
-        if self.listen.trim().is_empty() {
-            return Err(ConfigError::Validation(
-                "поле listen обязательно".to_string(),
-            ));
-        }
-        if SocketAddr::from_str(&self.listen).is_err() {
-            return Err(ConfigError::Validation(
-                "поле listen должно быть корректным адресом host:port".to_string(),
-            ));
-        }
-        if self.interval_secs < 1 {
-            return Err(ConfigError::Validation(
-                "interval_secs должно быть >= 1".to_string(),
-            ));
+    pub fn state_url(&self) -> String {
+        format!("{}/api/state", self.url.trim_end_matches('/'))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default = "default_plugin_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+// Только Windows: счётчик производительности (путь вида \Processor(_Total)\% Privileged Time),
+// читается через Get-Counter и попадает в sensors.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WindowsCounterConfig {
+    pub path: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_windows_counter_sensor_type")]
+    pub sensor_type: String,
+}
+
+impl WindowsCounterConfig {
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RelayConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_plugin_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl RelayConfig {
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn metric_allowed(&self, metric: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, metric)))
+            && !self.exclude.iter().any(|p| glob_match(p, metric))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChecksConfig {
+    #[serde(default)]
+    pub preferred_ip_version: Option<IpVersion>,
+    #[serde(default = "default_checks_max_concurrency")]
+    pub max_concurrency: usize,
+    #[serde(default)]
+    pub http_client: CheckHttpClientConfig,
+}
+
+impl Default for ChecksConfig {
+    fn default() -> Self {
+        Self {
+            preferred_ip_version: None,
+            max_concurrency: default_checks_max_concurrency(),
+            http_client: CheckHttpClientConfig::default(),
+        }
+    }
+}
+
+// Пул соединений HTTP-проверок: открытые соединения переиспользуются между тактами.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CheckHttpClientConfig {
+    // При false соединение закрывается после каждого запроса.
+    #[serde(default = "default_check_keep_alive")]
+    pub keep_alive: bool,
+    // 0 — простаивающие соединения не закрываются по таймауту.
+    #[serde(default = "default_check_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    // Без значения число простаивающих соединений к хосту не ограничено.
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(default)]
+    pub tcp_keepalive_secs: Option<u64>,
+}
+
+impl Default for CheckHttpClientConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive: default_check_keep_alive(),
+            pool_idle_timeout_secs: default_check_pool_idle_timeout_secs(),
+            pool_max_idle_per_host: None,
+            tcp_keepalive_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    pub fn matches(self, ip: &IpAddr) -> bool {
+        match self {
+            Self::V4 => ip.is_ipv4(),
+            Self::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub ignore_ifaces: Vec<String>,
+}
+
+impl NetworkConfig {
+    pub fn iface_ignored(&self, iface: &str) -> bool {
+        self.ignore_ifaces.iter().any(|p| glob_match(p, iface))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DisksConfig {
+    #[serde(default)]
+    pub ignore_mounts: Vec<String>,
+    // Температура накопителей без hwmon-датчика через smartctl (нужны права root).
+    #[serde(default)]
+    pub smartctl: bool,
+    // Разделы, на которые на каждом такте записывается и удаляется пробный файл.
+    #[serde(default)]
+    pub write_probe: Vec<String>,
+}
+
+impl DisksConfig {
+    pub fn mount_ignored(&self, mount: &str) -> bool {
+        self.ignore_mounts.iter().any(|p| glob_match(p, mount))
+    }
+
+    pub fn write_probed(&self, mount: &str) -> bool {
+        self.write_probe.iter().any(|p| glob_match(p, mount))
+    }
+}
+
+// Только Linux: пиры туннелей из `wg show all dump` (нужны права root или CAP_NET_ADMIN).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct WireguardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+// Внутри контейнера проценты CPU и памяти считаются от лимитов cgroup, а не от ресурсов хоста.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContainerConfig {
+    #[serde(default = "default_cgroup_limits")]
+    pub cgroup_limits: bool,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            cgroup_limits: default_cgroup_limits(),
+        }
+    }
+}
+
+// Обход дерева каталогов бывает долгим, поэтому размеры пересчитываются по своему расписанию.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DirSizesConfig {
+    #[serde(default = "default_dir_sizes_interval_secs")]
+    pub interval_secs: u64,
+    // Сколько самых больших вложенных записей показывать для каждого каталога.
+    #[serde(default = "default_dir_sizes_top")]
+    pub top: usize,
+    #[serde(default)]
+    pub paths: Vec<DirSizePathConfig>,
+}
+
+impl Default for DirSizesConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_dir_sizes_interval_secs(),
+            top: default_dir_sizes_top(),
+            paths: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DirSizePathConfig {
+    pub path: String,
+    // Порог уведомления dir_size в гигабайтах (1024³ байт).
+    #[serde(default)]
+    pub max_size_gb: Option<f64>,
+}
+
+impl DirSizePathConfig {
+    pub fn max_size_bytes(&self) -> Option<u64> {
+        self.max_size_gb
+            .map(|gb| (gb * 1024.0 * 1024.0 * 1024.0) as u64)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryConfig {
+    #[serde(default = "default_history_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_history_retention_hours")]
+    pub retention_hours: u64,
+    #[serde(default = "default_history_sample_secs")]
+    pub sample_secs: u64,
+    // Замеры speedtest сохраняются в data_dir и переживают перезапуск независимо от enabled.
+    #[serde(default = "default_history_speedtest_retention_days")]
+    pub speedtest_retention_days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_history_enabled(),
+            retention_hours: default_history_retention_hours(),
+            sample_secs: default_history_sample_secs(),
+            speedtest_retention_days: default_history_speedtest_retention_days(),
+        }
+    }
+}
+
+// Замер скорости интернета через speed.cloudflare.com на такте сбора.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpeedtestConfig {
+    #[serde(default = "default_speedtest_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_speedtest_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_speedtest_download_bytes")]
+    pub download_bytes: u64,
+    #[serde(default = "default_speedtest_upload_bytes")]
+    pub upload_bytes: u64,
+    // На медленном канале загрузка обрывается по этому сроку, скорость считается по уже полученному.
+    #[serde(default = "default_speedtest_download_budget_secs")]
+    pub download_budget_secs: u64,
+}
+
+impl Default for SpeedtestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_speedtest_enabled(),
+            interval_secs: default_speedtest_interval_secs(),
+            download_bytes: default_speedtest_download_bytes(),
+            upload_bytes: default_speedtest_upload_bytes(),
+            download_budget_secs: default_speedtest_download_budget_secs(),
+        }
+    }
+}
+
+// Отправляемый буфер статический, поэтому его размер ограничен сверху.
+pub const MAX_SPEEDTEST_UPLOAD_BYTES: u64 = 4_000_000;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RouteConfig {
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    #[serde(default)]
+    pub severities: Vec<AlertSeverity>,
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub telegram_chat_ids: Vec<i64>,
+    #[serde(default, rename = "continue")]
+    pub continue_matching: bool,
+}
+
+impl RouteConfig {
+    pub fn matches(&self, kind: &str, severity: AlertSeverity, subject: &str) -> bool {
+        (self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind))
+            && (self.severities.is_empty() || self.severities.contains(&severity))
+            && (self.subjects.is_empty() || self.subjects.iter().any(|p| glob_match(p, subject)))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NotifiersConfig {
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub slack: Vec<ChatWebhookConfig>,
+    #[serde(default)]
+    pub discord: Vec<ChatWebhookConfig>,
+    #[serde(default)]
+    pub email: Vec<EmailConfig>,
+    #[serde(default)]
+    pub ntfy: Vec<NtfyConfig>,
+    #[serde(default)]
+    pub gotify: Vec<GotifyConfig>,
+    #[serde(default)]
+    pub pushover: Vec<PushoverConfig>,
+    #[serde(default)]
+    pub alertmanager: Vec<AlertmanagerConfig>,
+    #[serde(default)]
+    pub scripts: Vec<ScriptHookConfig>,
+    #[serde(default)]
+    pub telegram: Vec<TelegramNotifierConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScriptHookConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_action_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    #[serde(default = "default_script_on_recover")]
+    pub on_recover: bool,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertmanagerConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    #[serde(default = "default_alertmanager_alert_ttl_secs")]
+    pub alert_ttl_secs: u64,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NotifierFilter {
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    #[serde(default)]
+    pub subjects: Vec<String>,
+}
+
+impl NotifierFilter {
+    pub fn matches(&self, kind: &str, subject: &str) -> bool {
+        (self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind))
+            && (self.subjects.is_empty() || self.subjects.iter().any(|p| glob_match(p, subject)))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TelegramNotifierConfig {
+    pub name: String,
+    #[serde(default)]
+    pub bot_token_env: Option<String>,
+    #[serde(default)]
+    pub bot_token: Option<String>,
+    pub chat_ids: Vec<i64>,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+impl TelegramNotifierConfig {
+    pub fn resolve_token(&self) -> Option<String> {
+        self.bot_token_env
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .or_else(|| self.bot_token.clone())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+impl NotifiersConfig {
+    pub fn channel_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        names.extend(self.webhooks.iter().map(|c| c.name.as_str()));
+        names.extend(self.slack.iter().map(|c| c.name.as_str()));
+        names.extend(self.discord.iter().map(|c| c.name.as_str()));
+        names.extend(self.email.iter().map(|c| c.name.as_str()));
+        names.extend(self.ntfy.iter().map(|c| c.name.as_str()));
+        names.extend(self.gotify.iter().map(|c| c.name.as_str()));
+        names.extend(self.pushover.iter().map(|c| c.name.as_str()));
+        names.extend(self.alertmanager.iter().map(|c| c.name.as_str()));
+        names.extend(self.scripts.iter().map(|c| c.name.as_str()));
+        names.extend(self.telegram.iter().map(|c| c.name.as_str()));
+        names
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NtfyConfig {
+    pub name: String,
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GotifyConfig {
+    pub name: String,
+    pub server: String,
+    pub token: String,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PushoverConfig {
+    pub name: String,
+    pub user_key: String,
+    pub token: String,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailConfig {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password_env: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    // Разрешает AUTH с tls: none, когда пароль уходит открытым текстом.
+    #[serde(default)]
+    pub allow_insecure_auth: bool,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub body_template: Option<String>,
+    #[serde(default = "default_email_batch_secs")]
+    pub batch_secs: u64,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+impl EmailConfig {
+    pub fn resolve_password(&self) -> Option<String> {
+        self.password_env
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .or_else(|| self.password.clone())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+// Лента переходов состояния проверок для внешних систем (статус-страницы, инцидент-менеджмент).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CheckEventsConfig {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatWebhookConfig {
+    pub name: String,
+    pub webhook_url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub min_severity: Option<AlertSeverity>,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+    #[serde(flatten)]
+    pub delivery: DeliveryConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeliveryConfig {
+    #[serde(default = "default_webhook_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_webhook_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_webhook_timeout_ms(),
+            max_retries: default_webhook_max_retries(),
+            retry_backoff_ms: default_webhook_retry_backoff_ms(),
+        }
+    }
+}
+
+const MAX_WEBHOOK_RETRIES: u32 = 10;
+const MAX_CHECK_RETRIES: u32 = 5;
+const MAX_HTTP_REDIRECTS: usize = 20;
+const MAX_EMAIL_BATCH_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertRuleConfig {
+    pub name: String,
+    pub expr: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub severity: Option<AlertSeverity>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HttpCheckConfig {
+    pub name: String,
+    pub url: String,
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub expected_status: ExpectedStatus,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // Регулярное выражение, которому должно соответствовать начало тела ответа.
+    #[serde(default)]
+    pub body_regex: Option<String>,
+    // Условия на значения в JSON-ответе; проверяются все, если код ответа ожидаемый.
+    #[serde(default)]
+    pub json_assertions: Vec<JsonAssertion>,
+    #[serde(default)]
+    pub tls: Option<CheckTlsConfig>,
+    #[serde(default = "default_follow_redirects")]
+    pub follow_redirects: bool,
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    #[serde(default)]
+    pub resolve_to: Option<IpAddr>,
+    #[serde(default)]
+    pub ip_version: Option<IpVersion>,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub jitter_secs: u64,
+    // Имена проверок, при падении которых алерты этой проверки подавляются или понижаются.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub alerts: CheckAlertPolicy,
+}
+
+// Без equals, min и max достаточно, чтобы поле было в ответе; min и max включаются в диапазон.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct JsonAssertion {
+    pub path: String,
+    #[serde(default)]
+    pub equals: Option<serde_json::Value>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// Подмножество JSONPath: $.a.b, $.items[0], $["ключ.с.точкой"]; «$» без сегментов — весь документ.
+pub fn parse_json_path(path: &str) -> Result<Vec<JsonPathSegment>, String> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix('[') {
+            let (inner, after) = tail
+                .split_once(']')
+                .ok_or_else(|| "не закрыта квадратная скобка".to_string())?;
+            let inner = inner.trim();
+            let quoted = ['"', '\'']
+                .into_iter()
+                .find_map(|q| inner.strip_prefix(q)?.strip_suffix(q));
+            segments.push(match quoted {
+                Some(key) => JsonPathSegment::Key(key.to_string()),
+                None => JsonPathSegment::Index(
+                    inner
+                        .parse()
+                        .map_err(|_| format!("индекс '{inner}' должен быть числом"))?,
+                ),
+            });
+            rest = after;
+        } else {
+            let tail = rest.strip_prefix('.').unwrap_or(rest);
+            let end = tail.find(['.', '[']).unwrap_or(tail.len());
+            if end == 0 {
+                return Err("пустое имя поля".to_string());
+            }
+            segments.push(JsonPathSegment::Key(tail[..end].to_string()));
+            rest = &tail[end..];
+        }
+    }
+    Ok(segments)
+}
+
+// Ожидаемый код ответа: число, класс вида "2xx" или список из них.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "ExpectedStatusInput", into = "ExpectedStatusInput")]
+pub struct ExpectedStatus(Vec<StatusMatch>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusMatch {
+    Code(u16),
+    Class(u16),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ExpectedStatusInput {
+    One(StatusInput),
+    Many(Vec<StatusInput>),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum StatusInput {
+    Code(u16),
+    Text(String),
+}
+
+impl ExpectedStatus {
+    pub fn matches(&self, code: u16) -> bool {
+        self.0.iter().any(|m| match m {
+            StatusMatch::Code(expected) => *expected == code,
+            StatusMatch::Class(class) => code / 100 == *class,
+        })
+    }
+}
+
+impl Default for ExpectedStatus {
+    fn default() -> Self {
+        Self(vec![StatusMatch::Code(200)])
+    }
+}
+
+impl From<u16> for ExpectedStatus {
+    fn from(code: u16) -> Self {
+        Self(vec![StatusMatch::Code(code)])
+    }
+}
+
+impl TryFrom<ExpectedStatusInput> for ExpectedStatus {
+    type Error = String;
+
+    fn try_from(input: ExpectedStatusInput) -> Result<Self, Self::Error> {
+        let items = match input {
+            ExpectedStatusInput::One(item) => vec![item],
+            ExpectedStatusInput::Many(items) => items,
+        };
+        if items.is_empty() {
+            return Err("expected_status не должен быть пустым списком".to_string());
+        }
+        items
+            .into_iter()
+            .map(|item| {
+                let text = match item {
+                    StatusInput::Code(code) => code.to_string(),
+                    StatusInput::Text(text) => text.trim().to_ascii_lowercase(),
+                };
+                let parsed = match text.strip_suffix("xx") {
+                    Some(class) => class.parse().ok().map(StatusMatch::Class),
+                    None => text.parse().ok().map(StatusMatch::Code),
+                };
+                match parsed {
+                    Some(StatusMatch::Class(class)) if (1..=5).contains(&class) => {
+                        Ok(StatusMatch::Class(class))
+                    }
+                    Some(StatusMatch::Code(code)) if (100..=599).contains(&code) => {
+                        Ok(StatusMatch::Code(code))
+                    }
+                    _ => Err(format!(
+                        "expected_status '{text}': ожидается код 100..599 или класс 1xx..5xx"
+                    )),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl From<ExpectedStatus> for ExpectedStatusInput {
+    fn from(status: ExpectedStatus) -> Self {
+        let mut items: Vec<StatusInput> = status
+            .0
+            .into_iter()
+            .map(|m| match m {
+                StatusMatch::Code(code) => StatusInput::Code(code),
+                StatusMatch::Class(class) => StatusInput::Text(format!("{class}xx")),
+            })
+            .collect();
+        if items.len() == 1 {
+            Self::One(items.remove(0))
+        } else {
+            Self::Many(items)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TcpCheckConfig {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub timeout_ms: u64,
+    // Строка, отправляемая после подключения, например "PING\r\n" для Redis.
+    #[serde(default)]
+    pub send: Option<String>,
+    // Регулярное выражение, которому должен соответствовать ответ сервера (например, "^220 " для SMTP).
+    #[serde(default)]
+    pub expect_regex: Option<String>,
+    // TLS-рукопожатие после подключения (LDAPS, IMAPS и другие не-HTTP сервисы).
+    #[serde(default)]
+    pub tls: Option<CheckTlsConfig>,
+    #[serde(default)]
+    pub resolve_to: Option<IpAddr>,
+    #[serde(default)]
+    pub ip_version: Option<IpVersion>,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub jitter_secs: u64,
+    // Имена проверок, при падении которых алерты этой проверки подавляются или понижаются.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub alerts: CheckAlertPolicy,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SshCheckConfig {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    pub timeout_ms: u64,
+    // Отпечаток ключа хоста в формате ssh-keygen -l (SHA256:...); несовпадение роняет проверку.
+    #[serde(default)]
+    pub host_key_fingerprint: Option<String>,
+    #[serde(default)]
+    pub resolve_to: Option<IpAddr>,
+    #[serde(default)]
+    pub ip_version: Option<IpVersion>,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub jitter_secs: u64,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub alerts: CheckAlertPolicy,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CheckTlsConfig {
+    // Имя для SNI и проверки сертификата в TCP-проверках; по умолчанию host проверки.
+    #[serde(default)]
+    pub server_name: Option<String>,
+    // При false недоверенный сертификат не роняет проверку.
+    #[serde(default = "default_tls_verify")]
+    pub verify: bool,
+    // PEM с дополнительными корневыми сертификатами (например, внутреннего CA) к встроенным.
+    #[serde(default)]
+    pub ca_file: Option<PathBuf>,
+}
+
+impl TcpCheckConfig {
+    pub fn tls_server_name(&self) -> Option<&str> {
+        let tls = self.tls.as_ref()?;
+        Some(tls.server_name.as_deref().unwrap_or(&self.host))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CheckAlertPolicy {
+    #[serde(default)]
+    pub fail_threshold: Option<u32>,
+    #[serde(default)]
+    pub repeat_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub recovery_notify: Option<bool>,
+    #[serde(default)]
+    pub severity: Option<AlertSeverity>,
+    #[serde(default)]
+    pub upstream_down: Option<UpstreamDownAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamDownAction {
+    // Алерт не отправляется, пока недоступна вышестоящая проверка.
+    #[default]
+    Suppress,
+    // Алерт отправляется с severity warning и пометкой о вышестоящей проверке.
+    Downgrade,
+}
+
+impl CheckAlertPolicy {
+    pub fn fail_threshold(&self, global: &AlertsConfig) -> u32 {
+        self.fail_threshold.unwrap_or(global.fail_threshold)
+    }
+
+    pub fn repeat_interval_secs(&self, global: &AlertsConfig) -> u64 {
+        self.repeat_interval_secs
+            .unwrap_or(global.repeat_interval_secs)
+    }
+
+    pub fn recovery_notify(&self, global: &AlertsConfig) -> bool {
+        self.recovery_notify.unwrap_or(global.recovery_notify)
+    }
+
+    pub fn severity(&self) -> AlertSeverity {
+        self.severity.unwrap_or(AlertSeverity::Critical)
+    }
+
+    pub fn upstream_down(&self, global: &AlertsConfig) -> UpstreamDownAction {
+        self.upstream_down.unwrap_or(global.upstream_down)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TelegramConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bot_token_env")]
+    pub bot_token_env: String,
+    #[serde(default)]
+    pub bot_token: Option<String>,
+    #[serde(default)]
+    pub allowed_chat_ids: Vec<i64>,
+    #[serde(default)]
+    pub critical_chat_ids: Vec<i64>,
+    #[serde(default)]
+    pub pairing_enabled: bool,
+    #[serde(default = "default_rate_limit_per_minute")]
+    pub rate_limit_per_minute: u32,
+    pub public_base_url: Option<String>,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub actions: Vec<RemoteActionConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RemoteActionConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub allowed_chat_ids: Vec<i64>,
+    #[serde(default = "default_action_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl RemoteActionConfig {
+    // Пустой список ничего не разрешает: действия доступны только явно перечисленным чатам.
+    pub fn allowed_for_chat(&self, chat_id: i64) -> bool {
+        self.allowed_chat_ids.contains(&chat_id)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub enabled_by_default: bool,
+    #[serde(default = "default_repeat_interval_secs")]
+    pub repeat_interval_secs: u64,
+    #[serde(default = "default_fail_threshold")]
+    pub fail_threshold: u32,
+    #[serde(default = "default_recovery_notify")]
+    pub recovery_notify: bool,
+    #[serde(default = "default_resource_alerts_enabled")]
+    pub resource_alerts_enabled: bool,
+    #[serde(default = "default_gpu_load_threshold_percent")]
+    pub gpu_load_threshold_percent: f64,
+    #[serde(default = "default_gpu_temp_threshold_celsius")]
+    pub gpu_temp_threshold_celsius: f64,
+    #[serde(default = "default_cpu_temp_threshold_celsius")]
+    pub cpu_temp_threshold_celsius: f64,
+    #[serde(default = "default_cpu_load_threshold_percent")]
+    pub cpu_load_threshold_percent: f64,
+    #[serde(default = "default_ram_usage_threshold_percent")]
+    pub ram_usage_threshold_percent: f64,
+    #[serde(default = "default_disk_usage_threshold_percent")]
+    pub disk_usage_threshold_percent: f64,
+    #[serde(default)]
+    pub gpu_load_clear_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub gpu_temp_clear_threshold_celsius: Option<f64>,
+    #[serde(default)]
+    pub cpu_temp_clear_threshold_celsius: Option<f64>,
+    #[serde(default)]
+    pub cpu_load_clear_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub ram_usage_clear_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub disk_usage_clear_threshold_percent: Option<f64>,
+    #[serde(default = "default_resource_alert_cooldown_secs")]
+    pub resource_alert_cooldown_secs: u64,
+    #[serde(default)]
+    pub debounce_secs: u64,
+    #[serde(default)]
+    pub disk_alerts: HashMap<String, DiskAlertConfig>,
+    #[serde(default)]
+    pub ignore_mounts: Vec<String>,
+    #[serde(default)]
+    pub sensor_alerts: Vec<SensorAlertConfig>,
+    #[serde(default)]
+    pub disk_temp_threshold_celsius: Option<f64>,
+    #[serde(default)]
+    pub disk_temp_clear_threshold_celsius: Option<f64>,
+    // Только Linux: доля процессорного времени, отнятая гипервизором (steal).
+    #[serde(default)]
+    pub cpu_steal_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub cpu_steal_clear_threshold_percent: Option<f64>,
+    // Только Windows: заполненность файла подкачки.
+    #[serde(default)]
+    pub pagefile_usage_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub pagefile_usage_clear_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub sustained_secs: HashMap<String, u64>,
+    #[serde(default)]
+    pub disk_forecast: DiskForecastConfig,
+    #[serde(default)]
+    pub mount_changes: MountChangeConfig,
+    #[serde(default)]
+    pub read_only: ReadOnlyConfig,
+    #[serde(default)]
+    pub disk_latency: DiskLatencyConfig,
+    #[serde(default)]
+    pub time_sync: TimeSyncConfig,
+    #[serde(default)]
+    pub net_errors: NetErrorsConfig,
+    #[serde(default)]
+    pub wireguard_handshake: WireguardHandshakeConfig,
+    #[serde(default)]
+    pub temp_rise: TempRiseConfig,
+    #[serde(default)]
+    pub collector_failures: CollectorFailuresConfig,
+    #[serde(default)]
+    pub warning_thresholds: HashMap<String, f64>,
+    #[serde(default)]
+    pub templates: HashMap<String, String>,
+    #[serde(default)]
+    pub links: Vec<AlertLinkConfig>,
+    #[serde(default)]
+    pub notify_on_shutdown: bool,
+    // Через сколько секунд сплошных ошибок Telegram API предупреждать о потере уведомлений;
+    // 0 — не предупреждать.
+    #[serde(default = "default_delivery_warn_after_secs")]
+    pub delivery_warn_after_secs: u64,
+    #[serde(default)]
+    pub upstream_down: UpstreamDownAction,
+}
+
+// Кнопка-ссылка под уведомлением (Grafana, веб-дашборд). url — шаблон minijinja; host, kind,
+// subject и severity подставляются уже закодированными для URL, from/to — миллисекунды Unix.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertLinkConfig {
+    #[serde(default = "default_alert_link_label")]
+    pub label: String,
+    pub url: String,
+    #[serde(default = "default_alert_link_range_secs")]
+    pub range_secs: u64,
+    #[serde(flatten)]
+    pub filter: NotifierFilter,
+}
+
+const MAX_ALERT_DEBOUNCE_SECS: u64 = 600;
+const MAX_SUSTAINED_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiskAlertConfig {
+    #[serde(default)]
+    pub threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub warning_threshold_percent: Option<f64>,
+    #[serde(default)]
+    pub clear_threshold_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SensorAlertConfig {
+    pub pattern: String,
+    pub threshold_celsius: f64,
+    #[serde(default)]
+    pub clear_threshold_celsius: Option<f64>,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl SensorAlertConfig {
+    pub fn matches(&self, sensor_name: &str) -> bool {
+        glob_match(&self.pattern.to_lowercase(), &sensor_name.to_lowercase())
+    }
+
+    pub fn clear_threshold(&self) -> f64 {
+        self.clear_threshold_celsius
+            .unwrap_or(self.threshold_celsius)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiskForecastConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_disk_forecast_horizon_hours")]
+    pub horizon_hours: u64,
+    #[serde(default = "default_disk_forecast_window_hours")]
+    pub window_hours: u64,
+    #[serde(default = "default_disk_forecast_min_history_minutes")]
+    pub min_history_minutes: u64,
+}
+
+impl Default for DiskForecastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            horizon_hours: default_disk_forecast_horizon_hours(),
+            window_hours: default_disk_forecast_window_hours(),
+            min_history_minutes: default_disk_forecast_min_history_minutes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MountChangeConfig {
+    #[serde(default = "default_mount_changes_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_mount_changes_ignore_removable")]
+    pub ignore_removable: bool,
+}
+
+impl Default for MountChangeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_mount_changes_enabled(),
+            ignore_removable: default_mount_changes_ignore_removable(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReadOnlyConfig {
+    #[serde(default = "default_read_only_enabled")]
+    pub enabled: bool,
+}
+
+impl Default for ReadOnlyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_read_only_enabled(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiskLatencyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Среднее время операции (await) накопителя за интервал сбора.
+    #[serde(default = "default_disk_latency_threshold_ms")]
+    pub threshold_ms: f64,
+    // Почти простаивающий диск даёт случайные всплески await на единичных операциях.
+    #[serde(default = "default_disk_latency_min_ops_per_sec")]
+    pub min_ops_per_sec: f64,
+}
+
+impl Default for DiskLatencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_ms: default_disk_latency_threshold_ms(),
+            min_ops_per_sec: default_disk_latency_min_ops_per_sec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TimeSyncConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Сверх потери синхронизации: допустимое смещение часов по данным chrony.
+    #[serde(default)]
+    pub max_offset_ms: Option<f64>,
+}
+
+impl TimeSyncConfig {
+    pub fn out_of_sync(&self, stat: &TimeSyncStat) -> bool {
+        let offset_exceeded = match (self.max_offset_ms, stat.offset_seconds) {
+            (Some(max_ms), Some(offset)) => offset.abs() * 1000.0 > max_ms,
+            _ => false,
+        };
+        !stat.synchronized || offset_exceeded
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetErrorsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Суммарно ошибок приёма и передачи на интерфейсе в минуту.
+    #[serde(default = "default_net_errors_threshold_per_min")]
+    pub threshold_per_min: f64,
+}
+
+impl Default for NetErrorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_per_min: default_net_errors_threshold_per_min(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WireguardHandshakeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // При живом трафике или persistent-keepalive рукопожатие повторяется каждые 2 минуты.
+    #[serde(default = "default_wireguard_handshake_stale_secs")]
+    pub stale_secs: u64,
+}
+
+impl Default for WireguardHandshakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stale_secs: default_wireguard_handshake_stale_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TempRiseConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_temp_rise_rate_celsius_per_min")]
+    pub rate_celsius_per_min: f64,
+    #[serde(default = "default_temp_rise_window_minutes")]
+    pub window_minutes: u64,
+    // Шаблоны имён датчиков; пусто — все датчики.
+    #[serde(default)]
+    pub sensors: Vec<String>,
+}
+
+impl Default for TempRiseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate_celsius_per_min: default_temp_rise_rate_celsius_per_min(),
+            window_minutes: default_temp_rise_window_minutes(),
+            sensors: Vec::new(),
+        }
+    }
+}
+
+impl TempRiseConfig {
+    pub fn sensor_matches(&self, sensor: &str) -> bool {
+        self.sensors.is_empty()
+            || self
+                .sensors
+                .iter()
+                .any(|p| glob_match(&p.to_lowercase(), &sensor.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CollectorFailuresConfig {
+    #[serde(default = "default_collector_failures_enabled")]
+    pub enabled: bool,
+    // Сколько тактов подряд сборщик может не отдавать данные, прежде чем считаться деградировавшим.
+    #[serde(default = "default_collector_failures_threshold_intervals")]
+    pub threshold_intervals: u32,
+}
+
+impl Default for CollectorFailuresConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_collector_failures_enabled(),
+            threshold_intervals: default_collector_failures_threshold_intervals(),
+        }
+    }
+}
+
+pub const RESOURCE_ALERT_KEYS: [&str; 6] = [
+    "cpu_temp",
+    "gpu_temp",
+    "cpu_load",
+    "gpu_load",
+    "ram_usage",
+    "disk_usage",
+];
+
+pub const ALERT_TEMPLATE_KEYS: [&str; 11] = [
+    "cpu_temp",
+    "gpu_temp",
+    "cpu_load",
+    "gpu_load",
+    "ram_usage",
+    "disk_usage",
+    "resource_recovered",
+    "disk_forecast",
+    "sensor_temp",
+    "check_down",
+    "check_recovered",
+];
+
+impl AlertsConfig {
+    pub fn resource_threshold(&self, key: &str) -> Option<f64> {
+        match key {
+            "cpu_temp" => Some(self.cpu_temp_threshold_celsius),
+            "gpu_temp" => Some(self.gpu_temp_threshold_celsius),
+            "cpu_load" => Some(self.cpu_load_threshold_percent),
+            "gpu_load" => Some(self.gpu_load_threshold_percent),
+            "ram_usage" => Some(self.ram_usage_threshold_percent),
+            "disk_usage" => Some(self.disk_usage_threshold_percent),
+            _ => None,
+        }
+    }
+
+    pub fn sustained_secs(&self, key: &str) -> u64 {
+        self.sustained_secs.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn max_sustained_secs(&self) -> u64 {
+        self.sustained_secs.values().copied().max().unwrap_or(0)
+    }
+
+    pub fn mount_ignored(&self, mount: &str) -> bool {
+        self.ignore_mounts.iter().any(|p| glob_match(p, mount))
+    }
+
+    pub fn resource_clear_threshold(&self, key: &str) -> Option<f64> {
+        match key {
+            "cpu_temp" => self.cpu_temp_clear_threshold_celsius,
+            "gpu_temp" => self.gpu_temp_clear_threshold_celsius,
+            "cpu_load" => self.cpu_load_clear_threshold_percent,
+            "gpu_load" => self.gpu_load_clear_threshold_percent,
+            "ram_usage" => self.ram_usage_clear_threshold_percent,
+            "disk_usage" => self.disk_usage_clear_threshold_percent,
+            _ => None,
+        }
+    }
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bot_token_env: default_bot_token_env(),
+            bot_token: None,
+            allowed_chat_ids: Vec::new(),
+            critical_chat_ids: Vec::new(),
+            pairing_enabled: false,
+            rate_limit_per_minute: default_rate_limit_per_minute(),
+            public_base_url: None,
+            alerts: AlertsConfig::default(),
+            actions: Vec::new(),
+        }
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            enabled_by_default: true,
+            repeat_interval_secs: default_repeat_interval_secs(),
+            fail_threshold: default_fail_threshold(),
+            recovery_notify: default_recovery_notify(),
+            resource_alerts_enabled: default_resource_alerts_enabled(),
+            gpu_load_threshold_percent: default_gpu_load_threshold_percent(),
+            gpu_temp_threshold_celsius: default_gpu_temp_threshold_celsius(),
+            cpu_temp_threshold_celsius: default_cpu_temp_threshold_celsius(),
+            cpu_load_threshold_percent: default_cpu_load_threshold_percent(),
+            ram_usage_threshold_percent: default_ram_usage_threshold_percent(),
+            disk_usage_threshold_percent: default_disk_usage_threshold_percent(),
+            gpu_load_clear_threshold_percent: None,
+            gpu_temp_clear_threshold_celsius: None,
+            cpu_temp_clear_threshold_celsius: None,
+            cpu_load_clear_threshold_percent: None,
+            ram_usage_clear_threshold_percent: None,
+            disk_usage_clear_threshold_percent: None,
+            resource_alert_cooldown_secs: default_resource_alert_cooldown_secs(),
+            debounce_secs: 0,
+            disk_alerts: HashMap::new(),
+            ignore_mounts: Vec::new(),
+            sensor_alerts: Vec::new(),
+            disk_temp_threshold_celsius: None,
+            disk_temp_clear_threshold_celsius: None,
+            cpu_steal_threshold_percent: None,
+            cpu_steal_clear_threshold_percent: None,
+            pagefile_usage_threshold_percent: None,
+            pagefile_usage_clear_threshold_percent: None,
+            sustained_secs: HashMap::new(),
+            disk_forecast: DiskForecastConfig::default(),
+            mount_changes: MountChangeConfig::default(),
+            read_only: ReadOnlyConfig::default(),
+            disk_latency: DiskLatencyConfig::default(),
+            time_sync: TimeSyncConfig::default(),
+            net_errors: NetErrorsConfig::default(),
+            wireguard_handshake: WireguardHandshakeConfig::default(),
+            temp_rise: TempRiseConfig::default(),
+            collector_failures: CollectorFailuresConfig::default(),
+            warning_thresholds: HashMap::new(),
+            templates: HashMap::new(),
+            links: Vec::new(),
+            notify_on_shutdown: false,
+            delivery_warn_after_secs: default_delivery_warn_after_secs(),
+            upstream_down: UpstreamDownAction::default(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("не удалось прочитать файл конфигурации {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("не удалось разобрать YAML в {path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    #[error("ошибка валидации конфигурации: {0}")]
+    Validation(String),
+    #[error("не удалось загрузить конфигурацию {url}: {source}")]
+    Fetch { url: String, source: reqwest::Error },
+    #[error("контрольная сумма конфигурации {url} не совпадает: ожидалась {expected}, получена {actual}")]
+    Checksum {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Config {
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path_ref = path.as_ref();
+        let path_display = path_ref.display().to_string();
+        let text = fs::read_to_string(path_ref).map_err(|source| ConfigError::Read {
+            path: path_display.clone(),
+            source,
+        })?;
+
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str(&text).map_err(|source| ConfigError::Parse {
+                path: path_display.clone(),
+                source,
+            })?;
+        let include = match value.get_mut("include") {
+            Some(include) => include_patterns(include)?,
+            None => Vec::new(),
+        };
+        for file in resolve_includes(path_ref, &include)? {
+            let file_display = file.display().to_string();
+            let text = fs::read_to_string(&file).map_err(|source| ConfigError::Read {
+                path: file_display.clone(),
+                source,
+            })?;
+            let overlay: serde_yaml::Value =
+                serde_yaml::from_str(&text).map_err(|source| ConfigError::Parse {
+                    path: file_display.clone(),
+                    source,
+                })?;
+            if overlay.get("include").is_some() {
+                return Err(ConfigError::Validation(format!(
+                    "{file_display}: вложенные include не поддерживаются"
+                )));
+            }
+            merge_yaml(&mut value, overlay);
+        }
+        expand_check_modules(&mut value)?;
+        let cfg: Config = serde_yaml::from_value(value).map_err(|source| ConfigError::Parse {
+            path: path_display,
+            source,
+        })?;
+
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    pub fn load_from_str(text: &str, origin: &str) -> Result<Self, ConfigError> {
+        // Файлы, отданные веб-сервером как есть, могут начинаться с BOM.
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str(text).map_err(|source| ConfigError::Parse {
+                path: origin.to_string(),
+                source,
+            })?;
+        if let Some(include) = value.get_mut("include") {
+            if !include_patterns(include)?.is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "{origin}: include поддерживается только для локальных файлов"
+                )));
+            }
+        }
+        expand_check_modules(&mut value)?;
+        let cfg: Config = serde_yaml::from_value(value).map_err(|source| ConfigError::Parse {
+            path: origin.to_string(),
+            source,
+        })?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    pub fn include_targets(path: &Path, include: &[String]) -> Vec<(PathBuf, String)> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        include
+            .iter()
+            .map(|pattern| {
+                let full = base.join(pattern);
+                let dir = full.parent().map(Path::to_path_buf).unwrap_or_default();
+                let name = full
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (dir, name)
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "поле listen обязательно".to_string(),
+            ));
+        }
+        if SocketAddr::from_str(&self.listen).is_err() {
+            return Err(ConfigError::Validation(
+                "поле listen должно быть корректным адресом host:port".to_string(),
+            ));
+        }
+        if self.data_dir.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "поле data_dir не должно быть пустым".to_string(),
+            ));
+        }
+        if self.interval_secs < 1 {
+            return Err(ConfigError::Validation(
+                "interval_secs должно быть >= 1".to_string(),
+            ));
+        }
+        if self.collector_stale_secs <= self.interval_secs {
+            return Err(ConfigError::Validation(
+                "collector_stale_secs должно быть больше interval_secs".to_string(),
+            ));
+        }
+        if self.collect_timeout_secs == 0 || self.collect_timeout_secs >= self.collector_stale_secs
+        {
+            return Err(ConfigError::Validation(
+                "collect_timeout_secs должно быть в диапазоне 1..collector_stale_secs".to_string(),
+            ));
+        }
+
+        validate_http_checks(&self.http_checks, self.interval_secs)?;
+        validate_check_dependencies(&self.http_checks, &self.tcp_checks, &self.ssh_checks)?;
+        validate_tcp_checks(&self.tcp_checks)?;
+        validate_ssh_checks(&self.ssh_checks)?;
+        if !(1..=256).contains(&self.checks.max_concurrency) {
+            return Err(ConfigError::Validation(
+                "checks.max_concurrency должно быть в диапазоне 1..256".to_string(),
+            ));
+        }
+        if self.checks.http_client.tcp_keepalive_secs == Some(0) {
+            return Err(ConfigError::Validation(
+                "checks.http_client.tcp_keepalive_secs должно быть больше 0".to_string(),
+            ));
+        }
+        validate_rules(&self.rules)?;
+        validate_telegram(&self.telegram)?;
+        validate_notifiers(&self.notifiers)?;
+        validate_routes(&self.routes, &self.notifiers)?;
+        validate_check_events(&self.check_events)?;
+        validate_history(&self.history)?;
+        validate_speedtest(&self.speedtest)?;
+        validate_temp_rise(&self.telegram.alerts.temp_rise, &self.history)?;
+        validate_logging(&self.logging)?;
+        validate_aggregator(&self.aggregator)?;
+        validate_push(&self.push)?;
+        validate_heartbeat(&self.heartbeat)?;
+        if self.alert_api.default_ttl_secs == Some(0) {
+            return Err(ConfigError::Validation(
+                "alert_api.default_ttl_secs должно быть >= 1".to_string(),
+            ));
+        }
+        validate_discovery(&self.discovery)?;
+        validate_plugins(&self.plugins, self.interval_secs)?;
+        validate_windows_counters(&self.windows_counters)?;
+        validate_dir_sizes(&self.dir_sizes)?;
+        validate_relay(&self.relay, self.interval_secs)?;
+        if self
+            .network
+            .ignore_ifaces
+            .iter()
+            .any(|p| p.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(
+                "network.ignore_ifaces не должен содержать пустых шаблонов".to_string(),
+            ));
+        }
+        validate_sensors(&self.sensors)?;
+        if self.disks.ignore_mounts.iter().any(|p| p.trim().is_empty()) {
+            return Err(ConfigError::Validation(
+                "disks.ignore_mounts не должен содержать пустых шаблонов".to_string(),
+            ));
+        }
+        if self.disks.write_probe.iter().any(|p| p.trim().is_empty()) {
+            return Err(ConfigError::Validation(
+                "disks.write_probe не должен содержать пустых шаблонов".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn redacted(&self) -> Self {
+        let mut cfg = self.clone();
+        cfg.telegram.bot_token.iter_mut().for_each(redact);
+        for check in &mut cfg.http_checks {
+            check.headers.values_mut().for_each(redact);
+        }
+        for module in cfg.modules.values_mut() {
+            if let Some(serde_yaml::Value::Mapping(headers)) = module.get_mut("headers") {
+                for value in headers.values_mut() {
+                    *value = serde_yaml::Value::String("***".to_string());
+                }
+            }
+        }
+        let notifiers = &mut cfg.notifiers;
+        for webhook in &mut notifiers.webhooks {
+            webhook.headers.values_mut().for_each(redact);
+        }
+        cfg.check_events.headers.values_mut().for_each(redact);
+        for chat in notifiers
+            .slack
+            .iter_mut()
+            .chain(notifiers.discord.iter_mut())
+        {
+            redact(&mut chat.webhook_url);
+        }
+        for email in &mut notifiers.email {
+            email.password.iter_mut().for_each(redact);
+        }
+        for ntfy in &mut notifiers.ntfy {
+            ntfy.token.iter_mut().for_each(redact);
+        }
+        for gotify in &mut notifiers.gotify {
+            redact(&mut gotify.token);
+        }
+        for pushover in &mut notifiers.pushover {
+            redact(&mut pushover.user_key);
+            redact(&mut pushover.token);
+        }
+        for alertmanager in &mut notifiers.alertmanager {
+            alertmanager.bearer_token.iter_mut().for_each(redact);
+        }
+        for telegram in &mut notifiers.telegram {
+            telegram.bot_token.iter_mut().for_each(redact);
+        }
+        cfg.aggregator.ingest.token.iter_mut().for_each(redact);
+        cfg.push.token.iter_mut().for_each(redact);
+        cfg.alert_api.token.iter_mut().for_each(redact);
+        cfg.status_page.token.iter_mut().for_each(redact);
+        cfg.api.token.iter_mut().for_each(redact);
+        for host in &mut cfg.aggregator.hosts {
+            host.token.iter_mut().for_each(redact);
+        }
+        cfg
+    }
+
+    pub fn example_yaml() -> &'static str {
+        include_str!("../config.yaml.example")
+    }
+}
+
+fn validate_http_checks(
+    checks: &[HttpCheckConfig],
+    global_interval_secs: u64,
+) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for check in checks {
+        if check.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "http_checks[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(check.name.clone()) {
+            return Err(ConfigError::Validation(format!(
+                "имя HTTP-проверки '{}' должно быть уникальным",
+                check.name
+            )));
+        }
+        if check.timeout_ms == 0 {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}' timeout_ms должен быть > 0",
+                check.name
+            )));
+        }
+        if check.url.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}' url не должен быть пустым",
+                check.name
+            )));
+        }
+        if check.retries > MAX_CHECK_RETRIES {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}' retries должно быть не больше {MAX_CHECK_RETRIES}",
+                check.name
+            )));
+        }
+        if check.follow_redirects && !(1..=MAX_HTTP_REDIRECTS).contains(&check.max_redirects) {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}' max_redirects должно быть в диапазоне 1..{MAX_HTTP_REDIRECTS}",
+                check.name
+            )));
+        }
+        for (name, value) in &check.headers {
+            if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                || reqwest::header::HeaderValue::from_str(value).is_err()
+            {
+                return Err(ConfigError::Validation(format!(
+                    "http_checks '{}' заголовок '{name}' некорректен",
+                    check.name
+                )));
+            }
+        }
+        if let Some(pattern) = &check.body_regex {
+            regex::bytes::Regex::new(pattern).map_err(|err| {
+                ConfigError::Validation(format!(
+                    "http_checks '{}' body_regex некорректен: {err}",
+                    check.name
+                ))
+            })?;
+        }
+        for assertion in &check.json_assertions {
+            parse_json_path(&assertion.path).map_err(|err| {
+                ConfigError::Validation(format!(
+                    "http_checks '{}' json_assertions: путь '{}' некорректен: {err}",
+                    check.name, assertion.path
+                ))
+            })?;
+            if let (Some(min), Some(max)) = (assertion.min, assertion.max) {
+                if min > max {
+                    return Err(ConfigError::Validation(format!(
+                        "http_checks '{}' json_assertions '{}': min больше max",
+                        check.name, assertion.path
+                    )));
+                }
+            }
+        }
+        if check
+            .tls
+            .as_ref()
+            .is_some_and(|tls| tls.server_name.is_some())
+        {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}' tls.server_name не поддерживается: SNI берётся из url",
+                check.name
+            )));
+        }
+        validate_check_ca_file("http_checks", &check.name, check.tls.as_ref())?;
+        // Все попытки должны укладываться в интервал проверки, иначе такты начнут наезжать друг на друга.
+        let worst_case_ms = (check
+            .timeout_ms
+            .saturating_mul(u64::from(check.retries) + 1))
+        .saturating_add(
+            check
+                .retry_delay_ms
+                .saturating_mul(u64::from(check.retries)),
+        );
+        let interval_ms = check
+            .interval_secs
+            .unwrap_or(global_interval_secs)
+            .saturating_mul(1000);
+        if check.retries > 0 && worst_case_ms >= interval_ms {
+            return Err(ConfigError::Validation(format!(
+                "http_checks '{}': попытки с повторами занимают до {worst_case_ms} мс и не укладываются в интервал проверки",
+                check.name
+            )));
+        }
+        validate_check_schedule(
+            "http_checks",
+            &check.name,
+            check.interval_secs,
+            check.jitter_secs,
+        )?;
+        validate_check_address(
+            "http_checks",
+            &check.name,
+            check.resolve_to,
+            check.ip_version,
+        )?;
+        validate_check_alert_policy("http_checks", &check.name, &check.alerts)?;
+    }
+    Ok(())
+}
+
+fn validate_check_ca_file(
+    section: &str,
+    name: &str,
+    tls: Option<&CheckTlsConfig>,
+) -> Result<(), ConfigError> {
+    let Some(path) = tls.and_then(|tls| tls.ca_file.as_ref()) else {
+        return Ok(());
+    };
+    let pem = fs::read(path).map_err(|err| {
+        ConfigError::Validation(format!(
+            "{section} '{name}' tls.ca_file {} не читается: {err}",
+            path.display()
+        ))
+    })?;
+    match reqwest::Certificate::from_pem_bundle(&pem) {
+        Ok(certs) if !certs.is_empty() => Ok(()),
+        _ => Err(ConfigError::Validation(format!(
+            "{section} '{name}' tls.ca_file {} не содержит PEM-сертификатов",
+            path.display()
+        ))),
+    }
+}
+
+fn validate_tcp_checks(checks: &[TcpCheckConfig]) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for check in checks {
+        if check.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "tcp_checks[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(check.name.clone()) {
+            return Err(ConfigError::Validation(format!(
+                "имя TCP-проверки '{}' должно быть уникальным",
+                check.name
+            )));
+        }
+        if check.host.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "tcp_checks '{}' host не должен быть пустым",
+                check.name
+            )));
+        }
+        if check.port == 0 {
+            return Err(ConfigError::Validation(format!(
+                "tcp_checks '{}' port должен быть в диапазоне 1..65535",
+                check.name
+            )));
+        }
+        validate_check_ca_file("tcp_checks", &check.name, check.tls.as_ref())?;
+        if check.timeout_ms == 0 {
+            return Err(ConfigError::Validation(format!(
+                "tcp_checks '{}' timeout_ms должен быть > 0",
+                check.name
+            )));
+        }
+        if check.send.as_ref().is_some_and(|send| send.is_empty()) {
+            return Err(ConfigError::Validation(format!(
+                "tcp_checks '{}' send не должен быть пустым",
+                check.name
+            )));
+        }
+        if let Some(pattern) = &check.expect_regex {
+            regex::bytes::Regex::new(pattern).map_err(|err| {
+                ConfigError::Validation(format!(
+                    "tcp_checks '{}' expect_regex некорректен: {err}",
+                    check.name
+                ))
+            })?;
+        }
+        if let Some(server_name) = check.tls_server_name() {
+            ServerName::try_from(server_name.to_string()).map_err(|err| {
+                ConfigError::Validation(format!(
+                    "tcp_checks '{}' tls.server_name некорректен: {err}",
+                    check.name
+                ))
+            })?;
+        }
+        validate_check_schedule(
+            "tcp_checks",
+            &check.name,
+            check.interval_secs,
+            check.jitter_secs,
+        )?;
+        validate_check_address(
+            "tcp_checks",
+            &check.name,
+            check.resolve_to,
+            check.ip_version,
+        )?;
+        validate_check_alert_policy("tcp_checks", &check.name, &check.alerts)?;
+    }
+    Ok(())
+}
+
+fn validate_ssh_checks(checks: &[SshCheckConfig]) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for check in checks {
+        if check.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "ssh_checks[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(check.name.clone()) {
+            return Err(ConfigError::Validation(format!(
+                "имя SSH-проверки '{}' должно быть уникальным",
+                check.name
+            )));
+        }
+        if check.host.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "ssh_checks '{}' host не должен быть пустым",
+                check.name
+            )));
+        }
+        if check.port == 0 {
+            return Err(ConfigError::Validation(format!(
+                "ssh_checks '{}' port должен быть в диапазоне 1..65535",
+                check.name
+            )));
+        }
+        if check.timeout_ms == 0 {
+            return Err(ConfigError::Validation(format!(
+                "ssh_checks '{}' timeout_ms должен быть > 0",
+                check.name
+            )));
+        }
+        // SHA256 — 32 байта, в base64 без выравнивания это 43 символа.
+        if check.host_key_fingerprint.as_deref().is_some_and(|fp| {
+            fp.strip_prefix("SHA256:").is_none_or(|b64| {
+                b64.len() != 43
+                    || !b64
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+            })
+        }) {
+            return Err(ConfigError::Validation(format!(
+                "ssh_checks '{}' host_key_fingerprint должен иметь вид SHA256:<43 символа base64>, как в выводе ssh-keygen -l",
+                check.name
+            )));
+        }
+        validate_check_schedule(
+            "ssh_checks",
+            &check.name,
+            check.interval_secs,
+            check.jitter_secs,
+        )?;
+        validate_check_address(
+            "ssh_checks",
+            &check.name,
+            check.resolve_to,
+            check.ip_version,
+        )?;
+        validate_check_alert_policy("ssh_checks", &check.name, &check.alerts)?;
+    }
+    Ok(())
+}
+
+fn validate_check_address(
+    section: &str,
+    name: &str,
+    resolve_to: Option<IpAddr>,
+    ip_version: Option<IpVersion>,
+) -> Result<(), ConfigError> {
+    match (resolve_to, ip_version) {
+        (Some(ip), Some(version)) if !version.matches(&ip) => Err(ConfigError::Validation(
+            format!("{section} '{name}': resolve_to {ip} не соответствует ip_version"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_check_schedule(
+    section: &str,
+    name: &str,
+    interval_secs: Option<u64>,
+    jitter_secs: u64,
+) -> Result<(), ConfigError> {
+    // Проверки идут по своим таймерам, поэтому интервал может быть и короче глобального.
+    match interval_secs {
+        Some(0) => Err(ConfigError::Validation(format!(
+            "{section} '{name}' interval_secs должно быть >= 1"
+        ))),
+        Some(interval) if jitter_secs >= interval => Err(ConfigError::Validation(format!(
+            "{section} '{name}' jitter_secs должен быть меньше interval_secs"
+        ))),
+        None if jitter_secs > 0 => Err(ConfigError::Validation(format!(
+            "{section} '{name}' jitter_secs задаётся только вместе с interval_secs"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn validate_rules(rules: &[AlertRuleConfig]) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for rule in rules {
+        if rule.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "rules[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(rule.name.clone()) {
+            return Err(ConfigError::Validation(format!(
+                "имя правила '{}' должно быть уникальным",
+                rule.name
+            )));
+        }
+        if let Err(err) = crate::rules::compile(&rule.expr) {
+            return Err(ConfigError::Validation(format!(
+                "rules '{}': ошибка в выражении: {err}",
+                rule.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+// depends_on ссылается на имена HTTP-, TCP- и SSH-проверок; циклы запрещены.
+fn validate_check_dependencies(
+    http: &[HttpCheckConfig],
+    tcp: &[TcpCheckConfig],
+    ssh: &[SshCheckConfig],
+) -> Result<(), ConfigError> {
+    let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+    let checks = http
+        .iter()
+        .map(|c| ("http_checks", c.name.as_str(), &c.depends_on))
+        .chain(
+            tcp.iter()
+                .map(|c| ("tcp_checks", c.name.as_str(), &c.depends_on)),
+        )
+        .chain(
+            ssh.iter()
+                .map(|c| ("ssh_checks", c.name.as_str(), &c.depends_on)),
+        );
+    for (_, name, _) in checks.clone() {
+        graph.entry(name).or_default();
+    }
+    for (section, name, depends_on) in checks {
+        for parent in depends_on {
+            if parent == name {
+                return Err(ConfigError::Validation(format!(
+                    "{section} '{name}' depends_on не может ссылаться на саму проверку"
+                )));
+            }
+            if !graph.contains_key(parent.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "{section} '{name}' depends_on ссылается на неизвестную проверку '{parent}'"
+                )));
+            }
+        }
+        if let Some(edges) = graph.get_mut(name) {
+            edges.extend(depends_on.iter().map(String::as_str));
+        }
+    }
+
+    // Обход в глубину: вершина в стеке, встреченная повторно, означает цикл.
+    fn visit<'a>(
+        node: &'a str,
+        graph: &HashMap<&'a str, Vec<&'a str>>,
+        in_stack: &mut HashSet<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Result<(), ConfigError> {
+        if done.contains(node) {
+            return Ok(());
+        }
+        if !in_stack.insert(node) {
+            return Err(ConfigError::Validation(format!(
+                "depends_on образует цикл через проверку '{node}'"
+            )));
+        }
+        for parent in graph.get(node).into_iter().flatten() {
+            visit(parent, graph, in_stack, done)?;
+        }
+        in_stack.remove(node);
+        done.insert(node);
+        Ok(())
+    }
+    let (mut in_stack, mut done) = (HashSet::new(), HashSet::new());
+    for node in graph.keys() {
+        visit(node, &graph, &mut in_stack, &mut done)?;
+    }
+    Ok(())
+}
+
+fn validate_check_alert_policy(
+    section: &str,
+    name: &str,
+    policy: &CheckAlertPolicy,
+) -> Result<(), ConfigError> {
+    if policy.fail_threshold == Some(0) {
+        return Err(ConfigError::Validation(format!(
+            "{section} '{name}' alerts.fail_threshold должно быть >= 1"
+        )));
+    }
+    if policy.repeat_interval_secs.is_some_and(|secs| secs < 60) {
+        return Err(ConfigError::Validation(format!(
+            "{section} '{name}' alerts.repeat_interval_secs должно быть >= 60"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_notifiers(cfg: &NotifiersConfig) -> Result<(), ConfigError> {
+    let env = minijinja::Environment::new();
+    let mut names = HashSet::new();
+    for hook in &cfg.webhooks {
+        validate_notifier(
+            "webhooks",
+            &hook.name,
+            &hook.filter,
+            &hook.delivery,
+            &mut names,
+        )?;
+        validate_notifier_url("webhooks", &hook.name, &hook.url)?;
+        if let Some(template) = &hook.template {
+            if let Err(err) = env.template_from_str(template) {
+                return Err(ConfigError::Validation(format!(
+                    "notifiers.webhooks '{}': ошибка в шаблоне: {err}",
+                    hook.name
+                )));
+            }
+        }
+    }
+    for (section, hooks) in [("slack", &cfg.slack), ("discord", &cfg.discord)] {
+        for hook in hooks {
+            validate_notifier(
+                section,
+                &hook.name,
+                &hook.filter,
+                &hook.delivery,
+                &mut names,
+            )?;
+            validate_notifier_url(section, &hook.name, &hook.webhook_url)?;
+        }
+    }
+    for email in &cfg.email {
+        validate_email(email, &env, &mut names)?;
+    }
+    for ntfy in &cfg.ntfy {
+        validate_notifier("ntfy", &ntfy.name, &ntfy.filter, &ntfy.delivery, &mut names)?;
+        validate_notifier_url("ntfy", &ntfy.name, &ntfy.server)?;
+        validate_notifier_secret("ntfy", &ntfy.name, "topic", &ntfy.topic)?;
+    }
+    for gotify in &cfg.gotify {
+        validate_notifier(
+            "gotify",
+            &gotify.name,
+            &gotify.filter,
+            &gotify.delivery,
+            &mut names,
+        )?;
+        validate_notifier_url("gotify", &gotify.name, &gotify.server)?;
+        validate_notifier_secret("gotify", &gotify.name, "token", &gotify.token)?;
+    }
+    for pushover in &cfg.pushover {
+        validate_notifier(
+            "pushover",
+            &pushover.name,
+            &pushover.filter,
+            &pushover.delivery,
+            &mut names,
+        )?;
+        validate_notifier_secret("pushover", &pushover.name, "user_key", &pushover.user_key)?;
+        validate_notifier_secret("pushover", &pushover.name, "token", &pushover.token)?;
+    }
+    for am in &cfg.alertmanager {
+        validate_notifier(
+            "alertmanager",
+            &am.name,
+            &am.filter,
+            &am.delivery,
+            &mut names,
+        )?;
+        validate_notifier_url("alertmanager", &am.name, &am.url)?;
+        if am.alert_ttl_secs == 0 {
+            return Err(ConfigError::Validation(format!(
+                "notifiers.alertmanager '{}': alert_ttl_secs должно быть > 0",
+                am.name
+            )));
+        }
+        if let Some(label) = am.labels.keys().find(|k| !is_valid_label_name(k)) {
+            return Err(ConfigError::Validation(format!(
+                "notifiers.alertmanager '{}': некорректное имя метки '{label}'",
+                am.name
+            )));
+        }
+    }
+    for script in &cfg.scripts {
+        validate_script_hook(script, &mut names)?;
+    }
+    for telegram in &cfg.telegram {
+        validate_notifier(
+            "telegram",
+            &telegram.name,
+            &telegram.filter,
+            &telegram.delivery,
+            &mut names,
+        )?;
+        if telegram.bot_token.is_none() && telegram.bot_token_env.is_none() {
+            return Err(ConfigError::Validation(format!(
+                "notifiers.telegram '{}': нужен bot_token или bot_token_env",
+                telegram.name
+            )));
+        }
+        if telegram.chat_ids.is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "notifiers.telegram '{}': chat_ids не должен быть пустым",
+                telegram.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_script_hook(
+    script: &ScriptHookConfig,
+    names: &mut HashSet<String>,
+) -> Result<(), ConfigError> {
+    if script.name.trim().is_empty() || !names.insert(script.name.clone()) {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.scripts: имя '{}' должно быть непустым и уникальным",
+            script.name
+        )));
+    }
+    if script.command.trim().is_empty() {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.scripts '{}': command не должен быть пустым",
+            script.name
+        )));
+    }
+    if script.timeout_secs == 0 {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.scripts '{}': timeout_secs должно быть > 0",
+            script.name
+        )));
+    }
+    let known = crate::notify::NOTIFICATION_KINDS;
+    if let Some(kind) = script.kinds.iter().find(|k| !known.contains(&k.as_str())) {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.scripts '{}': неизвестный тип '{kind}' (допустимо: {})",
+            script.name,
+            known.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn validate_routes(routes: &[RouteConfig], notifiers: &NotifiersConfig) -> Result<(), ConfigError> {
+    let channels = notifiers.channel_names();
+    let known = crate::notify::NOTIFICATION_KINDS;
+    for (idx, route) in routes.iter().enumerate() {
+        if let Some(kind) = route.kinds.iter().find(|k| !known.contains(&k.as_str())) {
+            return Err(ConfigError::Validation(format!(
+                "routes[{idx}]: неизвестный тип '{kind}' (допустимо: {})",
+                known.join(", ")
+            )));
+        }
+        if let Some(name) = route
+            .channels
+            .iter()
+            .find(|c| !channels.contains(&c.as_str()))
+        {
+            return Err(ConfigError::Validation(format!(
+                "routes[{idx}]: канал '{name}' не найден в notifiers"
+            )));
+        }
+        if route.subjects.iter().any(|p| p.trim().is_empty()) {
+            return Err(ConfigError::Validation(format!(
+                "routes[{idx}].subjects не должен содержать пустых шаблонов"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_sensors(cfg: &SensorsConfig) -> Result<(), ConfigError> {
+    if cfg.cpu_temperature.is_empty() {
+        return Err(ConfigError::Validation(
+            "sensors.cpu_temperature должен содержать хотя бы один ярус".to_string(),
+        ));
+    }
+    for (idx, tier) in cfg.cpu_temperature.iter().enumerate() {
+        if tier
+            .include
+            .iter()
+            .chain(&tier.exclude)
+            .any(|m| m.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(format!(
+                "sensors.cpu_temperature[{idx}] не должен содержать пустых подстрок"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_history(cfg: &HistoryConfig) -> Result<(), ConfigError> {
+    if cfg.retention_hours == 0 || cfg.retention_hours > 24 * 30 {
+        return Err(ConfigError::Validation(
+            "history.retention_hours должно быть в диапазоне 1..720".to_string(),
+        ));
+    }
+    if cfg.sample_secs == 0 || cfg.sample_secs > 3600 {
+        return Err(ConfigError::Validation(
+            "history.sample_secs должно быть в диапазоне 1..3600".to_string(),
+        ));
+    }
+    if cfg.speedtest_retention_days < 7 || cfg.speedtest_retention_days > 3650 {
+        return Err(ConfigError::Validation(
+            "history.speedtest_retention_days должно быть в диапазоне 7..3650".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_speedtest(cfg: &SpeedtestConfig) -> Result<(), ConfigError> {
+    if cfg.interval_secs < 60 {
+        return Err(ConfigError::Validation(
+            "speedtest.interval_secs должно быть >= 60".to_string(),
+        ));
+    }
+    if !(1_000_000..=100_000_000).contains(&cfg.download_bytes) {
+        return Err(ConfigError::Validation(
+            "speedtest.download_bytes должно быть в диапазоне 1000000..100000000".to_string(),
+        ));
+    }
+    if !(100_000..=MAX_SPEEDTEST_UPLOAD_BYTES).contains(&cfg.upload_bytes) {
+        return Err(ConfigError::Validation(format!(
+            "speedtest.upload_bytes должно быть в диапазоне 100000..{MAX_SPEEDTEST_UPLOAD_BYTES}"
+        )));
+    }
+    if !(1..=60).contains(&cfg.download_budget_secs) {
+        return Err(ConfigError::Validation(
+            "speedtest.download_budget_secs должно быть в диапазоне 1..60".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Скорость считается по буферу истории, поэтому в окно должно попадать хотя бы два замера.
+fn validate_temp_rise(cfg: &TempRiseConfig, history: &HistoryConfig) -> Result<(), ConfigError> {
+    if !cfg.enabled {
+        return Ok(());
+    }
+    if !history.enabled {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.temp_rise требует включённой history".to_string(),
+        ));
+    }
+    if cfg.rate_celsius_per_min <= 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.temp_rise.rate_celsius_per_min должно быть > 0".to_string(),
+        ));
+    }
+    if cfg.window_minutes == 0 || cfg.window_minutes * 60 < history.sample_secs * 2 {
+        return Err(ConfigError::Validation(format!(
+            "telegram.alerts.temp_rise.window_minutes должно покрывать хотя бы два замера history (sample_secs = {})",
+            history.sample_secs
+        )));
+    }
+    if cfg.sensors.iter().any(|p| p.trim().is_empty()) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.temp_rise.sensors не должен содержать пустых шаблонов".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_notifier_secret(
+    section: &str,
+    name: &str,
+    field: &str,
+    value: &str,
+) -> Result<(), ConfigError> {
+    if value.trim().is_empty() {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section} '{name}': {field} не должен быть пустым"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_notifier_url(section: &str, name: &str, url: &str) -> Result<(), ConfigError> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section} '{name}': url должен начинаться с http:// или https://"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_email(
+    email: &EmailConfig,
+    env: &minijinja::Environment,
+    names: &mut HashSet<String>,
+) -> Result<(), ConfigError> {
+    validate_notifier("email", &email.name, &email.filter, &email.delivery, names)?;
+    if email.host.trim().is_empty() || email.port == 0 {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.email '{}': host и port обязательны",
+            email.name
+        )));
+    }
+    // Адреса подставляются в MAIL FROM:<…>, RCPT TO:<…> и заголовки как есть.
+    let valid_address =
+        |address: &str| address.contains('@') && !address.contains(['\r', '\n', '<', '>']);
+    if !valid_address(&email.from)
+        || email.to.is_empty()
+        || !email.to.iter().all(|a| valid_address(a))
+    {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.email '{}': from и to должны содержать корректные адреса",
+            email.name
+        )));
+    }
+    if email.username.is_some() && email.password.is_none() && email.password_env.is_none() {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.email '{}': для username нужен password или password_env",
+            email.name
+        )));
+    }
+    if email.username.is_some() && email.tls == SmtpTls::None && !email.allow_insecure_auth {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.email '{}': с tls: none пароль уйдёт открытым текстом; включите tls или задайте allow_insecure_auth: true",
+            email.name
+        )));
+    }
+    if email.batch_secs > MAX_EMAIL_BATCH_SECS {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.email '{}': batch_secs должно быть не больше {MAX_EMAIL_BATCH_SECS}",
+            email.name
+        )));
+    }
+    for template in [&email.subject_template, &email.body_template]
+        .into_iter()
+        .flatten()
+    {
+        if let Err(err) = env.template_from_str(template) {
+            return Err(ConfigError::Validation(format!(
+                "notifiers.email '{}': ошибка в шаблоне: {err}",
+                email.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_notifier(
+    section: &str,
+    name: &str,
+    filter: &NotifierFilter,
+    delivery: &DeliveryConfig,
+    names: &mut HashSet<String>,
+) -> Result<(), ConfigError> {
+    if name.trim().is_empty() {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section}[*].name не должен быть пустым"
+        )));
+    }
+    if !names.insert(name.to_string()) {
+        return Err(ConfigError::Validation(format!(
+            "имя канала уведомлений '{name}' должно быть уникальным"
+        )));
+    }
+    if delivery.timeout_ms == 0 {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section} '{name}': timeout_ms должно быть > 0"
+        )));
+    }
+    if delivery.max_retries > MAX_WEBHOOK_RETRIES {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section} '{name}': max_retries должно быть не больше {MAX_WEBHOOK_RETRIES}"
+        )));
+    }
+    let known = crate::notify::NOTIFICATION_KINDS;
+    if let Some(kind) = filter.kinds.iter().find(|k| !known.contains(&k.as_str())) {
+        return Err(ConfigError::Validation(format!(
+            "notifiers.{section} '{name}': неизвестный тип '{kind}' (допустимо: {})",
+            known.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn validate_telegram(cfg: &TelegramConfig) -> Result<(), ConfigError> {
+    if cfg.rate_limit_per_minute < 1 {
+        return Err(ConfigError::Validation(
+            "telegram.rate_limit_per_minute должно быть >= 1".to_string(),
+        ));
+    }
+    if cfg.alerts.fail_threshold < 1 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.fail_threshold должно быть >= 1".to_string(),
+        ));
+    }
+    if cfg.alerts.repeat_interval_secs < 60 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.repeat_interval_secs должно быть >= 60".to_string(),
+        ));
+    }
+    if cfg.alerts.debounce_secs > MAX_ALERT_DEBOUNCE_SECS {
+        return Err(ConfigError::Validation(format!(
+            "telegram.alerts.debounce_secs должно быть <= {MAX_ALERT_DEBOUNCE_SECS}"
+        )));
+    }
+    if !(0.0..=100.0).contains(&cfg.alerts.gpu_load_threshold_percent) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.gpu_load_threshold_percent должно быть в диапазоне 0..100".to_string(),
+        ));
+    }
+    if cfg.alerts.gpu_temp_threshold_celsius <= 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.gpu_temp_threshold_celsius должно быть > 0".to_string(),
+        ));
+    }
+    if cfg.alerts.cpu_temp_threshold_celsius <= 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.cpu_temp_threshold_celsius должно быть > 0".to_string(),
+        ));
+    }
+    if !(0.0..=100.0).contains(&cfg.alerts.cpu_load_threshold_percent) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.cpu_load_threshold_percent должно быть в диапазоне 0..100".to_string(),
+        ));
+    }
+    if !(0.0..=100.0).contains(&cfg.alerts.ram_usage_threshold_percent) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.ram_usage_threshold_percent должно быть в диапазоне 0..100"
+                .to_string(),
+        ));
+    }
+    if !(0.0..=100.0).contains(&cfg.alerts.disk_usage_threshold_percent) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_usage_threshold_percent должно быть в диапазоне 0..100"
+                .to_string(),
+        ));
+    }
+    if cfg.alerts.resource_alert_cooldown_secs < 1 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.resource_alert_cooldown_secs должно быть >= 1".to_string(),
+        ));
+    }
+    validate_warning_thresholds(&cfg.alerts)?;
+    validate_clear_thresholds(&cfg.alerts)?;
+    validate_disk_alerts(&cfg.alerts)?;
+    validate_sustained_secs(&cfg.alerts)?;
+    validate_sensor_alerts(&cfg.alerts.sensor_alerts)?;
+    validate_disk_temp_alerts(&cfg.alerts)?;
+    validate_cpu_steal_alerts(&cfg.alerts)?;
+    validate_pagefile_alerts(&cfg.alerts)?;
+    validate_disk_forecast(&cfg.alerts.disk_forecast)?;
+    if cfg.alerts.collector_failures.threshold_intervals == 0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.collector_failures.threshold_intervals должно быть >= 1".to_string(),
+        ));
+    }
+    let disk_latency = &cfg.alerts.disk_latency;
+    if disk_latency.enabled && disk_latency.threshold_ms <= 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_latency.threshold_ms должно быть > 0".to_string(),
+        ));
+    }
+    if disk_latency.enabled && disk_latency.min_ops_per_sec < 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_latency.min_ops_per_sec не может быть отрицательным".to_string(),
+        ));
+    }
+    if cfg
+        .alerts
+        .time_sync
+        .max_offset_ms
+        .is_some_and(|ms| !ms.is_finite() || ms <= 0.0)
+    {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.time_sync.max_offset_ms должно быть > 0".to_string(),
+        ));
+    }
+    if cfg.alerts.net_errors.enabled && cfg.alerts.net_errors.threshold_per_min <= 0.0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.net_errors.threshold_per_min должно быть > 0".to_string(),
+        ));
+    }
+    if cfg.alerts.wireguard_handshake.enabled && cfg.alerts.wireguard_handshake.stale_secs < 130 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.wireguard_handshake.stale_secs должно быть >= 130: WireGuard повторяет рукопожатие раз в 2 минуты".to_string(),
+        ));
+    }
+    validate_alert_templates(&cfg.alerts.templates)?;
+    validate_alert_links(&cfg.alerts.links)?;
+    validate_actions(&cfg.actions)?;
+
+    Ok(())
+}
+
+fn validate_clear_thresholds(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    for key in RESOURCE_ALERT_KEYS {
+        let Some(clear) = alerts.resource_clear_threshold(key) else {
+            continue;
+        };
+        let trigger = alerts
+            .warning_thresholds
+            .get(key)
+            .copied()
+            .into_iter()
+            .chain(alerts.resource_threshold(key))
+            .fold(f64::INFINITY, f64::min);
+        if clear <= 0.0 || clear >= trigger {
+            return Err(ConfigError::Validation(format!(
+                "порог сброса уведомления {key} должен быть > 0 и меньше порога срабатывания {trigger}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_disk_forecast(cfg: &DiskForecastConfig) -> Result<(), ConfigError> {
+    if !cfg.enabled {
+        return Ok(());
+    }
+    if cfg.horizon_hours == 0 || cfg.window_hours == 0 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_forecast: horizon_hours и window_hours должны быть > 0"
+                .to_string(),
+        ));
+    }
+    if cfg.min_history_minutes == 0 || cfg.min_history_minutes > cfg.window_hours * 60 {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_forecast.min_history_minutes должно быть > 0 и не больше окна window_hours"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_sensor_alerts(rules: &[SensorAlertConfig]) -> Result<(), ConfigError> {
+    for rule in rules {
+        if rule.pattern.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.sensor_alerts[*].pattern не должен быть пустым".to_string(),
+            ));
+        }
+        if !(1.0..=150.0).contains(&rule.threshold_celsius) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.sensor_alerts '{}': threshold_celsius должно быть в диапазоне 1..150",
+                rule.pattern
+            )));
+        }
+        if let Some(clear) = rule.clear_threshold_celsius {
+            if clear <= 0.0 || clear >= rule.threshold_celsius {
+                return Err(ConfigError::Validation(format!(
+                    "telegram.alerts.sensor_alerts '{}': clear_threshold_celsius должно быть > 0 и меньше threshold_celsius",
+                    rule.pattern
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_disk_temp_alerts(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    let Some(threshold) = alerts.disk_temp_threshold_celsius else {
+        return Ok(());
+    };
+    if !(1.0..=150.0).contains(&threshold) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.disk_temp_threshold_celsius должно быть в диапазоне 1..150"
+                .to_string(),
+        ));
+    }
+    if let Some(clear) = alerts.disk_temp_clear_threshold_celsius {
+        if clear <= 0.0 || clear >= threshold {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.disk_temp_clear_threshold_celsius должно быть > 0 и меньше disk_temp_threshold_celsius"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_cpu_steal_alerts(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    let Some(threshold) = alerts.cpu_steal_threshold_percent else {
+        return Ok(());
+    };
+    if !(1.0..=100.0).contains(&threshold) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.cpu_steal_threshold_percent должно быть в диапазоне 1..100"
+                .to_string(),
+        ));
+    }
+    if let Some(clear) = alerts.cpu_steal_clear_threshold_percent {
+        if clear <= 0.0 || clear >= threshold {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.cpu_steal_clear_threshold_percent должно быть > 0 и меньше cpu_steal_threshold_percent"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_pagefile_alerts(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    let Some(threshold) = alerts.pagefile_usage_threshold_percent else {
+        return Ok(());
+    };
+    if !(1.0..=100.0).contains(&threshold) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.pagefile_usage_threshold_percent должно быть в диапазоне 1..100"
+                .to_string(),
+        ));
+    }
+    if let Some(clear) = alerts.pagefile_usage_clear_threshold_percent {
+        if clear <= 0.0 || clear >= threshold {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.pagefile_usage_clear_threshold_percent должно быть > 0 и меньше pagefile_usage_threshold_percent"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_sustained_secs(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    for (key, secs) in &alerts.sustained_secs {
+        if !RESOURCE_ALERT_KEYS.contains(&key.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.sustained_secs: неизвестный ключ '{}' (допустимо: {})",
+                key,
+                RESOURCE_ALERT_KEYS.join(", ")
+            )));
+        }
+        if *secs > MAX_SUSTAINED_SECS {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.sustained_secs.{key} должно быть <= {MAX_SUSTAINED_SECS}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_disk_alerts(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    for (mount, disk) in &alerts.disk_alerts {
+        if mount.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.disk_alerts: точка монтирования не должна быть пустой".to_string(),
+            ));
+        }
+        let critical = disk
+            .threshold_percent
+            .unwrap_or(alerts.disk_usage_threshold_percent);
+        let warning = disk
+            .warning_threshold_percent
+            .or_else(|| alerts.warning_thresholds.get("disk_usage").copied());
+        let clear = disk
+            .clear_threshold_percent
+            .or(alerts.disk_usage_clear_threshold_percent);
+        if !(0.0..=100.0).contains(&critical) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.disk_alerts.{mount}.threshold_percent должно быть в диапазоне 0..100"
+            )));
+        }
+        if let Some(warning) = warning {
+            if warning <= 0.0 || warning >= critical {
+                return Err(ConfigError::Validation(format!(
+                    "telegram.alerts.disk_alerts.{mount}: порог предупреждения должен быть > 0 и меньше {critical}"
+                )));
+            }
+        }
+        if let Some(clear) = clear {
+            let trigger = warning.unwrap_or(critical).min(critical);
+            if clear <= 0.0 || clear >= trigger {
+                return Err(ConfigError::Validation(format!(
+                    "telegram.alerts.disk_alerts.{mount}: порог сброса должен быть > 0 и меньше {trigger}"
+                )));
+            }
+        }
+    }
+    if alerts.ignore_mounts.iter().any(|p| p.trim().is_empty()) {
+        return Err(ConfigError::Validation(
+            "telegram.alerts.ignore_mounts не должен содержать пустых шаблонов".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_logging(logging: &LoggingConfig) -> Result<(), ConfigError> {
+    if let Err(err) = tracing_subscriber::EnvFilter::try_new(&logging.level) {
+        return Err(ConfigError::Validation(format!(
+            "logging.level '{}' некорректен: {err}",
+            logging.level
+        )));
+    }
+    if logging.file.as_deref().is_some_and(|f| f.trim().is_empty()) {
+        return Err(ConfigError::Validation(
+            "logging.file не должен быть пустым".to_string(),
+        ));
+    }
+    if logging.file.is_none() && !logging.stdout {
+        return Err(ConfigError::Validation(
+            "logging: при stdout: false нужно указать file".to_string(),
+        ));
+    }
+    if !(1..=100).contains(&logging.keep_files) {
+        return Err(ConfigError::Validation(
+            "logging.keep_files должно быть в диапазоне 1..100".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_aggregator(cfg: &AggregatorConfig) -> Result<(), ConfigError> {
+    if !(1..=3600).contains(&cfg.interval_secs) {
+        return Err(ConfigError::Validation(
+            "aggregator.interval_secs должно быть в диапазоне 1..3600".to_string(),
+        ));
+    }
+    if cfg.timeout_secs == 0 || cfg.timeout_secs > cfg.interval_secs {
+        return Err(ConfigError::Validation(
+            "aggregator.timeout_secs должно быть в диапазоне 1..interval_secs".to_string(),
+        ));
+    }
+    let mut names = HashSet::new();
+    for host in &cfg.hosts {
+        if !valid_host_name(&host.name) {
+            return Err(ConfigError::Validation(format!(
+                "aggregator.hosts: имя '{}' должно состоять из 1..32 символов [A-Za-z0-9._-]",
+                host.name
+            )));
+        }
+        if !names.insert(host.name.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "aggregator.hosts: имя '{}' повторяется",
+                host.name
+            )));
+        }
+        if !is_http_url(&host.url) {
+            return Err(ConfigError::Validation(format!(
+                "aggregator.hosts '{}': url должен быть адресом http(s)://",
+                host.name
+            )));
+        }
+    }
+    if cfg.ingest.stale_secs == 0 {
+        return Err(ConfigError::Validation(
+            "aggregator.ingest.stale_secs должно быть >= 1".to_string(),
+        ));
+    }
+    if cfg.ingest.retention_secs < cfg.ingest.stale_secs {
+        return Err(ConfigError::Validation(
+            "aggregator.ingest.retention_secs должно быть >= stale_secs".to_string(),
+        ));
+    }
+    if !(1..=1000).contains(&cfg.ingest.max_hosts) {
+        return Err(ConfigError::Validation(
+            "aggregator.ingest.max_hosts должно быть в диапазоне 1..1000".to_string(),
+        ));
+    }
+    if cfg.ingest.allowed_hosts.iter().any(|p| p.trim().is_empty()) {
+        return Err(ConfigError::Validation(
+            "aggregator.ingest.allowed_hosts не должен содержать пустых шаблонов".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_discovery(cfg: &DiscoveryConfig) -> Result<(), ConfigError> {
+    if cfg
+        .instance
+        .as_deref()
+        .is_some_and(|instance| !valid_host_name(instance))
+    {
+        return Err(ConfigError::Validation(
+            "discovery.instance должен состоять из 1..32 символов [A-Za-z0-9._-]".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_check_events(cfg: &CheckEventsConfig) -> Result<(), ConfigError> {
+    let Some(url) = cfg.url.as_deref() else {
+        return Ok(());
+    };
+    if !is_http_url(url) {
+        return Err(ConfigError::Validation(
+            "check_events.url должен быть адресом http(s)://".to_string(),
+        ));
+    }
+    if cfg.delivery.timeout_ms == 0 {
+        return Err(ConfigError::Validation(
+            "check_events.timeout_ms должно быть > 0".to_string(),
+        ));
+    }
+    if cfg.delivery.max_retries > MAX_WEBHOOK_RETRIES {
+        return Err(ConfigError::Validation(format!(
+            "check_events.max_retries должно быть не больше {MAX_WEBHOOK_RETRIES}"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_push(cfg: &PushConfig) -> Result<(), ConfigError> {
+    let Some(url) = cfg.url.as_deref() else {
+        return Ok(());
+    };
+    if !is_http_url(url) {
+        return Err(ConfigError::Validation(
+            "push.url должен быть адресом http(s)://".to_string(),
+        ));
+    }
+    if cfg.token_env.is_none() && cfg.token.is_none() {
+        return Err(ConfigError::Validation(
+            "push: нужен token или token_env".to_string(),
+        ));
+    }
+    if cfg
+        .host
+        .as_deref()
+        .is_some_and(|host| !valid_host_name(host))
+    {
+        return Err(ConfigError::Validation(
+            "push.host должен состоять из 1..32 символов [A-Za-z0-9._-]".to_string(),
+        ));
+    }
+    if !(1..=3600).contains(&cfg.interval_secs) {
+        return Err(ConfigError::Validation(
+            "push.interval_secs должно быть в диапазоне 1..3600".to_string(),
+        ));
+    }
+    if cfg.timeout_secs == 0 || cfg.timeout_secs > cfg.interval_secs {
+        return Err(ConfigError::Validation(
+            "push.timeout_secs должно быть в диапазоне 1..interval_secs".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_heartbeat(cfg: &HeartbeatConfig) -> Result<(), ConfigError> {
+    if cfg.url.is_none() {
+        if cfg.fail_url.is_some() {
+            return Err(ConfigError::Validation(
+                "heartbeat.fail_url задан без heartbeat.url".to_string(),
+            ));
+        }
+        return Ok(());
+    }
+    if [&cfg.url, &cfg.fail_url]
+        .into_iter()
+        .flatten()
+        .any(|url| !is_http_url(url))
+    {
+        return Err(ConfigError::Validation(
+            "heartbeat.url и heartbeat.fail_url должны быть адресами http(s)://".to_string(),
+        ));
+    }
+    if !(1..=86_400).contains(&cfg.interval_secs) {
+        return Err(ConfigError::Validation(
+            "heartbeat.interval_secs должно быть в диапазоне 1..86400".to_string(),
+        ));
+    }
+    if cfg.timeout_secs == 0 || cfg.timeout_secs > cfg.interval_secs {
+        return Err(ConfigError::Validation(
+            "heartbeat.timeout_secs должно быть в диапазоне 1..interval_secs".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_plugins(plugins: &[PluginConfig], interval_secs: u64) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for plugin in plugins {
+        if plugin.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "plugins[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(plugin.name.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "имя плагина '{}' должно быть уникальным",
+                plugin.name
+            )));
+        }
+        if plugin.command.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "plugins '{}': command не должен быть пустым",
+                plugin.name
+            )));
+        }
+        // Плагины выполняются внутри такта сбора и не должны его растягивать.
+        if plugin.timeout_ms == 0 || plugin.timeout_ms > interval_secs.saturating_mul(1000) {
+            return Err(ConfigError::Validation(format!(
+                "plugins '{}': timeout_ms должен быть в диапазоне 1..interval_secs*1000",
+                plugin.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_windows_counters(counters: &[WindowsCounterConfig]) -> Result<(), ConfigError> {
+    let mut labels = HashSet::new();
+    for counter in counters {
+        // Минимальный путь: \объект\счётчик, экземпляр в скобках необязателен.
+        let parts = counter.path.split('\\').collect::<Vec<_>>();
+        if parts.len() != 3
+            || !parts[0].is_empty()
+            || parts[1..].iter().any(|p| p.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(format!(
+                "windows_counters '{}': path должен иметь вид \\Объект(экземпляр)\\Счётчик",
+                counter.path
+            )));
+        }
+        if counter.sensor_type.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "windows_counters '{}': sensor_type не должен быть пустым",
+                counter.path
+            )));
+        }
+        if !labels.insert(counter.label()) {
+            return Err(ConfigError::Validation(format!(
+                "windows_counters '{}': имя должно быть уникальным",
+                counter.label()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_dir_sizes(cfg: &DirSizesConfig) -> Result<(), ConfigError> {
+    if cfg.interval_secs < 60 {
+        return Err(ConfigError::Validation(
+            "dir_sizes.interval_secs должно быть >= 60".to_string(),
+        ));
+    }
+    if !(1..=50).contains(&cfg.top) {
+        return Err(ConfigError::Validation(
+            "dir_sizes.top должно быть от 1 до 50".to_string(),
+        ));
+    }
+    let mut paths = HashSet::new();
+    for dir in &cfg.paths {
+        if dir.path.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "dir_sizes.paths: path не должен быть пустым".to_string(),
+            ));
+        }
+        if !paths.insert(dir.path.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "dir_sizes.paths: каталог '{}' указан дважды",
+                dir.path
+            )));
+        }
+        if dir
+            .max_size_gb
+            .is_some_and(|gb| !gb.is_finite() || gb <= 0.0)
+        {
+            return Err(ConfigError::Validation(format!(
+                "dir_sizes.paths '{}': max_size_gb должно быть > 0",
+                dir.path
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_relay(relay: &[RelayConfig], interval_secs: u64) -> Result<(), ConfigError> {
+    let valid_namespace = |ns: &str| {
+        ns.chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && ns.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let mut names = HashSet::new();
+    let mut namespaces = HashSet::new();
+    for exporter in relay {
+        if exporter.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "relay[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        if !names.insert(exporter.name.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "имя relay '{}' должно быть уникальным",
+                exporter.name
+            )));
+        }
+        if !valid_namespace(exporter.namespace()) {
+            return Err(ConfigError::Validation(format!(
+                "relay '{}': namespace '{}' должен состоять из [a-zA-Z0-9_] и не начинаться с цифры",
+                exporter.name,
+                exporter.namespace()
+            )));
+        }
+        // Пространство agent занято собственными метриками, совпадения имён сломают /metrics.
+        if exporter.namespace() == "agent" || !namespaces.insert(exporter.namespace()) {
+            return Err(ConfigError::Validation(format!(
+                "relay '{}': namespace '{}' уже используется",
+                exporter.name,
+                exporter.namespace()
+            )));
+        }
+        if !is_http_url(&exporter.url) {
+            return Err(ConfigError::Validation(format!(
+                "relay '{}': url должен быть адресом http(s)://",
+                exporter.name
+            )));
+        }
+        if exporter.timeout_ms == 0 || exporter.timeout_ms > interval_secs.saturating_mul(1000) {
+            return Err(ConfigError::Validation(format!(
+                "relay '{}': timeout_ms должен быть в диапазоне 1..interval_secs*1000",
+                exporter.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn redact(value: &mut String) {
+    if !value.is_empty() {
+        *value = "***".to_string();
+    }
+}
+
+fn include_patterns(value: &mut serde_yaml::Value) -> Result<Vec<String>, ConfigError> {
+    use serde_yaml::Value;
+
+    if let Value::String(pattern) = value {
+        *value = Value::Sequence(vec![Value::String(pattern.clone())]);
+    }
+    let Value::Sequence(items) = value else {
+        return Err(ConfigError::Validation(
+            "include должен быть строкой или списком строк".to_string(),
+        ));
+    };
+    items
+        .iter()
+        .map(|item| match item.as_str() {
+            Some(pattern) if !pattern.trim().is_empty() => Ok(pattern.to_string()),
+            _ => Err(ConfigError::Validation(
+                "include должен содержать непустые пути к файлам".to_string(),
+            )),
+        })
+        .collect()
+}
+
+fn resolve_includes(path: &Path, include: &[String]) -> Result<Vec<PathBuf>, ConfigError> {
+    let mut files = Vec::new();
+    for (dir, name) in Config::include_targets(path, include) {
+        if dir.to_string_lossy().contains(['*', '?']) {
+            return Err(ConfigError::Validation(format!(
+                "include '{}': шаблоны * и ? допустимы только в имени файла",
+                dir.join(&name).display()
+            )));
+        }
+        if !name.contains(['*', '?']) {
+            files.push(dir.join(name));
+            continue;
+        }
+        let entries = fs::read_dir(&dir).map_err(|source| ConfigError::Read {
+            path: dir.display().to_string(),
+            source,
+        })?;
+        let mut matched = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| glob_match(&name, &n.to_string_lossy()))
+            })
+            .collect::<Vec<_>>();
+        matched.sort();
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+// Поля проверки, которые можно вынести в общий модуль.
+const MODULE_KEYS: [&str; 17] = [
+    "timeout_ms",
+    "headers",
+    "body_regex",
+    "json_assertions",
+    "tls",
+    "expected_status",
+    "follow_redirects",
+    "max_redirects",
+    "retries",
+    "retry_delay_ms",
+    "send",
+    "expect_regex",
+    "ip_version",
+    "interval_secs",
+    "jitter_secs",
+    "depends_on",
+    "alerts",
+];
+
+// Подставляет в проверки с полем module поля модуля; собственные поля проверки важнее.
+fn expand_check_modules(value: &mut serde_yaml::Value) -> Result<(), ConfigError> {
+    use serde_yaml::Value;
+
+    let modules = match value.get("modules") {
+        Some(Value::Mapping(modules)) => modules.clone(),
+        Some(Value::Null) | None => serde_yaml::Mapping::new(),
+        Some(_) => {
+            return Err(ConfigError::Validation(
+                "modules должен быть словарём".to_string(),
+            ))
+        }
+    };
+    for (name, module) in &modules {
+        let name = name.as_str().unwrap_or_default();
+        let Value::Mapping(fields) = module else {
+            return Err(ConfigError::Validation(format!(
+                "modules '{name}' должен быть словарём"
+            )));
+        };
+        for key in fields.keys() {
+            let key = key.as_str().unwrap_or_default();
+            if !MODULE_KEYS.contains(&key) {
+                return Err(ConfigError::Validation(format!(
+                    "modules '{name}': поле '{key}' нельзя задать в модуле"
+                )));
+            }
+        }
+    }
+
+    for section in ["http_checks", "tcp_checks"] {
+        let Some(Value::Sequence(checks)) = value.get_mut(section) else {
+            continue;
+        };
+        for check in checks {
+            let Value::Mapping(fields) = check else {
+                continue;
+            };
+            let Some(module) = fields.remove("module") else {
+                continue;
+            };
+            let check_name = fields
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let Some(base) = module.as_str().and_then(|name| modules.get(name)) else {
+                return Err(ConfigError::Validation(format!(
+                    "{section} '{check_name}' ссылается на неизвестный модуль {}",
+                    serde_yaml::to_string(&module).unwrap_or_default().trim()
+                )));
+            };
+            let own = Value::Mapping(std::mem::take(fields));
+            let mut merged = base.clone();
+            override_yaml(&mut merged, own);
+            *check = merged;
+        }
+    }
+    Ok(())
+}
+
+// В отличие от merge_yaml, списки заменяются целиком: expected_status модуля не должен дописываться к своему.
+fn override_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => override_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay)) => base.extend(overlay),
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp + 1;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn validate_warning_thresholds(alerts: &AlertsConfig) -> Result<(), ConfigError> {
+    for (key, value) in &alerts.warning_thresholds {
+        let Some(critical) = alerts.resource_threshold(key) else {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.warning_thresholds: неизвестный ключ '{}' (допустимо: {})",
+                key,
+                RESOURCE_ALERT_KEYS.join(", ")
+            )));
+        };
+        if *value <= 0.0 || *value >= critical {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.warning_thresholds.{key} должно быть > 0 и меньше критического порога {critical}"
+            )));
         }
+    }
+    Ok(())
+}
+
+const MAX_ACTION_NAME_BYTES: usize = 56;
+
+fn validate_actions(actions: &[RemoteActionConfig]) -> Result<(), ConfigError> {
+    let mut names = HashSet::new();
+    for action in actions {
+        if action.name.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "telegram.actions[*].name не должен быть пустым".to_string(),
+            ));
+        }
+        // Имя передаётся в callback_data кнопки, а Telegram ограничивает её 64 байтами.
+        if action.name.len() > MAX_ACTION_NAME_BYTES {
+            return Err(ConfigError::Validation(format!(
+                "имя действия '{}' длиннее {MAX_ACTION_NAME_BYTES} байт",
+                action.name
+            )));
+        }
+        if !names.insert(action.name.clone()) {
+            return Err(ConfigError::Validation(format!(
+                "имя действия '{}' должно быть уникальным",
+                action.name
+            )));
+        }
+        if action.command.trim().is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "telegram.actions '{}' command не должен быть пустым",
+                action.name
+            )));
+        }
+        if action.timeout_secs == 0 {
+            return Err(ConfigError::Validation(format!(
+                "telegram.actions '{}' timeout_secs должен быть > 0",
+                action.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_alert_templates(templates: &HashMap<String, String>) -> Result<(), ConfigError> {
+    let env = minijinja::Environment::new();
+    for (key, source) in templates {
+        if !ALERT_TEMPLATE_KEYS.contains(&key.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.templates: неизвестный ключ '{}' (допустимо: {})",
+                key,
+                ALERT_TEMPLATE_KEYS.join(", ")
+            )));
+        }
+        if let Err(err) = env.template_from_str(source) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.templates.{key}: ошибка шаблона: {err}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_alert_links(links: &[AlertLinkConfig]) -> Result<(), ConfigError> {
+    let env = minijinja::Environment::new();
+    let known = crate::notify::NOTIFICATION_KINDS;
+    for link in links {
+        if link.label.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "telegram.alerts.links: label не должен быть пустым".to_string(),
+            ));
+        }
+        if !is_http_url(&link.url) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.links '{}': url должен быть адресом http(s)://",
+                link.label
+            )));
+        }
+        if let Err(err) = env.template_from_str(&link.url) {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.links '{}': ошибка шаблона: {err}",
+                link.label
+            )));
+        }
+        if link.range_secs < 60 {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.links '{}': range_secs должно быть >= 60",
+                link.label
+            )));
+        }
+        if let Some(kind) = link
+            .filter
+            .kinds
+            .iter()
+            .find(|k| !known.contains(&k.as_str()))
+        {
+            return Err(ConfigError::Validation(format!(
+                "telegram.alerts.links '{}': неизвестный тип '{kind}' (допустимо: {})",
+                link.label,
+                known.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn default_data_dir() -> String {
+    "./data".to_string()
+}
+
+const fn default_collector_stale_secs() -> u64 {
+    120
+}
+
+const fn default_collect_timeout_secs() -> u64 {
+    10
+}
+
+const fn default_checks_max_concurrency() -> usize {
+    16
+}
+
+const fn default_delivery_warn_after_secs() -> u64 {
+    600
+}
+
+const fn default_history_enabled() -> bool {
+    true
+}
+
+const fn default_history_retention_hours() -> u64 {
+    24
+}
+
+const fn default_history_sample_secs() -> u64 {
+    60
+}
+
+fn default_cpu_temperature_tiers() -> Vec<SensorTierConfig> {
+    let markers = |list: &[&str]| list.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+    let gpu = ["gpu", "nvidia", "amdgpu", "radeon"];
+    let acpi = ["acpi", "thermal zone", "_tz"];
+    vec![
+        SensorTierConfig {
+            include: markers(&["cpu", "package", "tctl", "tdie", "coretemp", "k10temp"]),
+            exclude: markers(&[&gpu[..], &acpi[..]].concat()),
+        },
+        SensorTierConfig {
+            include: Vec::new(),
+            exclude: markers(&[&gpu[..], &acpi[..]].concat()),
+        },
+        SensorTierConfig {
+            include: markers(&acpi),
+            exclude: Vec::new(),
+        },
+    ]
+}
+
+const fn default_history_speedtest_retention_days() -> u64 {
+    90
+}
+
+const fn default_speedtest_enabled() -> bool {
+    true
+}
+
+const fn default_speedtest_interval_secs() -> u64 {
+    3600
+}
+
+const fn default_speedtest_download_bytes() -> u64 {
+    10_000_000
+}
+
+const fn default_speedtest_upload_bytes() -> u64 {
+    2_000_000
+}
+
+const fn default_speedtest_download_budget_secs() -> u64 {
+    10
+}
+
+const fn default_script_on_recover() -> bool {
+    true
+}
+
+const fn default_alertmanager_alert_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+const fn default_smtp_port() -> u16 {
+    587
+}
+
+const fn default_email_batch_secs() -> u64 {
+    60
+}
+
+const fn default_webhook_timeout_ms() -> u64 {
+    5000
+}
+
+const fn default_webhook_max_retries() -> u32 {
+    3
+}
+
+const fn default_webhook_retry_backoff_ms() -> u64 {
+    1000
+}
+
+const fn default_tls_verify() -> bool {
+    true
+}
+
+const fn default_check_keep_alive() -> bool {
+    true
+}
+
+const fn default_check_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+const fn default_follow_redirects() -> bool {
+    true
+}
+
+// Столько же переходов по умолчанию разрешает reqwest.
+pub const fn default_max_redirects() -> usize {
+    10
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+const fn default_log_stdout() -> bool {
+    true
+}
+
+const fn default_log_keep_files() -> usize {
+    5
+}
+
+const fn default_aggregator_interval_secs() -> u64 {
+    30
+}
 
-        validate_http_checks(&self.http_checks)?;
-        validate_tcp_checks(&self.tcp_checks)?;
-        validate_telegram(&self.telegram)?;
+const fn default_aggregator_timeout_secs() -> u64 {
+    10
+}
 
-        Ok(())
-    }
+const fn default_ssh_port() -> u16 {
+    22
+}
 
-    pub fn example_yaml() -> &'static str {
-        include_str!("../config.yaml.example")
-    }
+const fn default_heartbeat_interval_secs() -> u64 {
+    60
 }
 
-fn validate_http_checks(checks: &[HttpCheckConfig]) -> Result<(), ConfigError> {
-    let mut names = HashSet::new();
-    for check in checks {
-        if check.name.trim().is_empty() {
-            return Err(ConfigError::Validation(
-                "http_checks[*].name не должен быть пустым".to_string(),
-            ));
-        }
-        if !names.insert(check.name.clone()) {
-            return Err(ConfigError::Validation(format!(
-                "имя HTTP-проверки '{}' должно быть уникальным",
-                check.name
-            )));
-        }
-        if check.timeout_ms == 0 {
-            return Err(ConfigError::Validation(format!(
-                "http_checks '{}' timeout_ms должен быть > 0",
-                check.name
-            )));
-        }
-        if check.url.trim().is_empty() {
-            return Err(ConfigError::Validation(format!(
-                "http_checks '{}' url не должен быть пустым",
-                check.name
-            )));
-        }
-    }
-    Ok(())
+const fn default_ingest_stale_secs() -> u64 {
+    90
 }
 
-fn validate_tcp_checks(checks: &[TcpCheckConfig]) -> Result<(), ConfigError> {
-    let mut names = HashSet::new();
-    for check in checks {
-        if check.name.trim().is_empty() {
-            return Err(ConfigError::Validation(
-                "tcp_checks[*].name не должен быть пустым".to_string(),
-            ));
-        }
-        if !names.insert(check.name.clone()) {
-            return Err(ConfigError::Validation(format!(
-                "имя TCP-проверки '{}' должно быть уникальным",
-                check.name
-            )));
-        }
-        if check.host.trim().is_empty() {
-            return Err(ConfigError::Validation(format!(
-                "tcp_checks '{}' host не должен быть пустым",
-                check.name
-            )));
-        }
-        if check.port == 0 {
-            return Err(ConfigError::Validation(format!(
-                "tcp_checks '{}' port должен быть в диапазоне 1..65535",
-                check.name
-            )));
-        }
-        if check.timeout_ms == 0 {
-            return Err(ConfigError::Validation(format!(
-                "tcp_checks '{}' timeout_ms должен быть > 0",
-                check.name
-            )));
-        }
-    }
-    Ok(())
+const fn default_ingest_retention_secs() -> u64 {
+    7 * 86_400
 }
 
-fn validate_telegram(cfg: &TelegramConfig) -> Result<(), ConfigError> {
-    if cfg.rate_limit_per_minute < 1 {
-        return Err(ConfigError::Validation(
-            "telegram.rate_limit_per_minute должно быть >= 1".to_string(),
-        ));
-    }
-    if cfg.alerts.fail_threshold < 1 {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.fail_threshold должно быть >= 1".to_string(),
-        ));
-    }
-    if cfg.alerts.repeat_interval_secs < 60 {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.repeat_interval_secs должно быть >= 60".to_string(),
-        ));
-    }
-    if !(0.0..=100.0).contains(&cfg.alerts.gpu_load_threshold_percent) {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.gpu_load_threshold_percent должно быть в диапазоне 0..100".to_string(),
-        ));
-    }
-    if cfg.alerts.gpu_temp_threshold_celsius <= 0.0 {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.gpu_temp_threshold_celsius должно быть > 0".to_string(),
-        ));
-    }
-    if cfg.alerts.cpu_temp_threshold_celsius <= 0.0 {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.cpu_temp_threshold_celsius должно быть > 0".to_string(),
-        ));
-    }
-    if !(0.0..=100.0).contains(&cfg.alerts.cpu_load_threshold_percent) {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.cpu_load_threshold_percent должно быть в диапазоне 0..100".to_string(),
-        ));
-    }
-    if !(0.0..=100.0).contains(&cfg.alerts.ram_usage_threshold_percent) {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.ram_usage_threshold_percent должно быть в диапазоне 0..100"
-                .to_string(),
-        ));
-    }
-    if !(0.0..=100.0).contains(&cfg.alerts.disk_usage_threshold_percent) {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.disk_usage_threshold_percent должно быть в диапазоне 0..100"
-                .to_string(),
-        ));
-    }
-    if cfg.alerts.resource_alert_cooldown_secs < 1 {
-        return Err(ConfigError::Validation(
-            "telegram.alerts.resource_alert_cooldown_secs должно быть >= 1".to_string(),
-        ));
-    }
+const fn default_ingest_max_hosts() -> usize {
+    100
+}
 
-    Ok(())
+const fn default_plugin_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_windows_counter_sensor_type() -> String {
+    "counter".to_string()
 }
 
-const fn default_expected_status() -> u16 {
+const fn default_retry_delay_ms() -> u64 {
     200
 }
 
+fn default_alert_link_label() -> String {
+    "Открыть график".to_string()
+}
+
+const fn default_alert_link_range_secs() -> u64 {
+    3600
+}
+
 fn default_bot_token_env() -> String {
     "TELEGRAM_BOT_TOKEN".to_string()
 }
 
+const fn default_action_timeout_secs() -> u64 {
+    60
+}
+
 const fn default_rate_limit_per_minute() -> u32 {
     30
 }
@@ -355,26 +4143,231 @@ const fn default_resource_alert_cooldown_secs() -> u64 {
     10
 }
 
+const fn default_disk_forecast_horizon_hours() -> u64 {
+    48
+}
+
+const fn default_disk_forecast_window_hours() -> u64 {
+    6
+}
+
+const fn default_disk_forecast_min_history_minutes() -> u64 {
+    30
+}
+
+const fn default_mount_changes_enabled() -> bool {
+    true
+}
+
+const fn default_mount_changes_ignore_removable() -> bool {
+    true
+}
+
+const fn default_dir_sizes_interval_secs() -> u64 {
+    3600
+}
+
+const fn default_dir_sizes_top() -> usize {
+    10
+}
+
+const fn default_cgroup_limits() -> bool {
+    true
+}
+
+const fn default_read_only_enabled() -> bool {
+    true
+}
+
+const fn default_disk_latency_threshold_ms() -> f64 {
+    100.0
+}
+
+const fn default_disk_latency_min_ops_per_sec() -> f64 {
+    1.0
+}
+
+const fn default_net_errors_threshold_per_min() -> f64 {
+    10.0
+}
+
+const fn default_wireguard_handshake_stale_secs() -> u64 {
+    300
+}
+
+const fn default_collector_failures_enabled() -> bool {
+    true
+}
+
+const fn default_collector_failures_threshold_intervals() -> u32 {
+    3
+}
+
+const fn default_temp_rise_rate_celsius_per_min() -> f64 {
+    5.0
+}
+
+const fn default_temp_rise_window_minutes() -> u64 {
+    5
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn valid_config() -> Config {
         Config {
+            include: vec![],
             listen: "127.0.0.1:9108".to_string(),
             interval_secs: 5,
+            data_dir: default_data_dir(),
+            collector_stale_secs: default_collector_stale_secs(),
+            collect_timeout_secs: default_collect_timeout_secs(),
+            modules: HashMap::new(),
             http_checks: vec![],
             tcp_checks: vec![],
+            ssh_checks: vec![],
+            rules: vec![],
             telegram: TelegramConfig {
                 enabled: false,
                 bot_token_env: "TEST_TOKEN_ENV".to_string(),
                 bot_token: None,
                 allowed_chat_ids: vec![],
+                critical_chat_ids: vec![],
+                pairing_enabled: false,
                 rate_limit_per_minute: 30,
                 public_base_url: None,
                 alerts: AlertsConfig::default(),
+                actions: vec![],
             },
+            notifiers: NotifiersConfig::default(),
+            routes: vec![],
+            check_events: CheckEventsConfig::default(),
+            history: HistoryConfig::default(),
+            network: NetworkConfig::default(),
+            disks: DisksConfig::default(),
+            wireguard: WireguardConfig::default(),
+            container: ContainerConfig::default(),
+            dir_sizes: DirSizesConfig::default(),
+            checks: ChecksConfig::default(),
+            logging: LoggingConfig::default(),
+            aggregator: AggregatorConfig::default(),
+            push: PushConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            alert_api: AlertApiConfig::default(),
+            status_page: StatusPageConfig::default(),
+            api: ApiConfig::default(),
+            speedtest: SpeedtestConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            display: DisplayConfig::default(),
+            metrics: MetricsConfig::default(),
+            sensors: SensorsConfig::default(),
+            plugins: Vec::new(),
+            windows_counters: Vec::new(),
+            relay: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn includes_merge_checks_and_override_values() {
+        let dir = std::env::temp_dir().join(format!("monitord-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        let main = dir.join("config.yaml");
+        fs::write(
+            &main,
+            "include: conf.d/*.yaml\nlisten: \"127.0.0.1:9108\"\ninterval_secs: 5\ntcp_checks:\n  - name: db\n    host: 127.0.0.1\n    port: 5432\n    timeout_ms: 500\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("conf.d/10-web.yaml"),
+            "interval_secs: 10\ntcp_checks:\n  - name: web\n    host: 127.0.0.1\n    port: 80\n    timeout_ms: 500\n",
+        )
+        .unwrap();
+        fs::write(dir.join("conf.d/notes.txt"), "not yaml: [").unwrap();
+
+        let cfg = Config::load_from_file(&main).unwrap();
+        assert_eq!(cfg.include, vec!["conf.d/*.yaml".to_string()]);
+        assert_eq!(cfg.interval_secs, 10);
+        assert_eq!(
+            cfg.tcp_checks
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["db", "web"]
+        );
+
+        fs::write(
+            dir.join("conf.d/20-dup.yaml"),
+            "tcp_checks:\n  - name: db\n    host: 127.0.0.1\n    port: 5433\n    timeout_ms: 500\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Config::load_from_file(&main),
+            Err(ConfigError::Validation(_))
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn notifiers_require_http_url_template_and_unique_names() {
+        let mut cfg = valid_config();
+        cfg.notifiers.webhooks.push(WebhookConfig {
+            name: "ops".to_string(),
+            url: "https://hooks.example.com/alert".to_string(),
+            headers: HashMap::new(),
+            template: Some("{\"text\": {{ message|tojson }}}".to_string()),
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+        assert!(cfg.validate().is_ok());
+
+        cfg.notifiers.webhooks[0].template = Some("{{ message".to_string());
+        assert!(cfg.validate().is_err());
+
+        cfg.notifiers.webhooks[0].template = None;
+        cfg.notifiers.webhooks[0].url = "ftp://example.com".to_string();
+        assert!(cfg.validate().is_err());
+
+        cfg.notifiers.webhooks[0].url = "https://hooks.example.com/alert".to_string();
+        cfg.notifiers.discord.push(ChatWebhookConfig {
+            name: "ops".to_string(),
+            webhook_url: "https://discord.com/api/webhooks/1/x".to_string(),
+            username: None,
+            min_severity: Some(AlertSeverity::Critical),
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn email_rejects_cleartext_auth_and_unsafe_addresses() {
+        let mut cfg = valid_config();
+        cfg.notifiers.email.push(
+            serde_yaml::from_str(
+                "name: mail\nhost: smtp.example.com\ntls: none\nusername: agent\npassword: secret\nfrom: agent@example.com\nto: [ops@example.com]\n",
+            )
+            .unwrap(),
+        );
+        assert!(cfg.validate().is_err());
+        cfg.notifiers.email[0].allow_insecure_auth = true;
+        assert!(cfg.validate().is_ok());
+        cfg.notifiers.email[0].allow_insecure_auth = false;
+        cfg.notifiers.email[0].tls = SmtpTls::Starttls;
+        assert!(cfg.validate().is_ok());
+
+        for bad in [
+            "ops@example.com>\r\nRCPT TO:<x@evil.com",
+            "<ops@example.com>",
+            "ops@example.com\nBcc: x@evil.com",
+        ] {
+            cfg.notifiers.email[0].to = vec![bad.to_string()];
+            assert!(cfg.validate().is_err(), "{bad:?}");
         }
+        cfg.notifiers.email[0].to = vec!["ops@example.com".to_string()];
+        cfg.notifiers.email[0].from = "agent@example.com\r\n".to_string();
+        assert!(cfg.validate().is_err());
     }
 
     #[test]
@@ -397,4 +4390,272 @@ mod tests {
         cfg.validate()
             .expect("валидация должна проходить, chat id проверяется на этапе запуска");
     }
+
+    #[test]
+    fn warning_thresholds_must_be_below_critical() {
+        let mut cfg = valid_config();
+        cfg.telegram
+            .alerts
+            .warning_thresholds
+            .insert("disk_usage".to_string(), 85.0);
+        cfg.validate().expect("85 < 95");
+
+        cfg.telegram
+            .alerts
+            .warning_thresholds
+            .insert("disk_usage".to_string(), 99.0);
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn clear_thresholds_must_be_below_trigger() {
+        let mut cfg = valid_config();
+        cfg.telegram.alerts.cpu_load_clear_threshold_percent = Some(80.0);
+        cfg.validate().expect("80 < 92");
+
+        cfg.telegram
+            .alerts
+            .warning_thresholds
+            .insert("cpu_load".to_string(), 75.0);
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn ssh_check_fingerprint_must_be_sha256() {
+        let mut cfg = valid_config();
+        cfg.ssh_checks = vec![serde_yaml::from_str(
+            "name: bastion\nhost: 10.0.0.1\ntimeout_ms: 1000\nhost_key_fingerprint: \"SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s\"",
+        )
+        .unwrap()];
+        assert_eq!(cfg.ssh_checks[0].port, 22);
+        cfg.validate().expect("корректный отпечаток");
+
+        cfg.ssh_checks[0].host_key_fingerprint = Some("MD5:aa:bb".to_string());
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn ignore_mounts_match_globs() {
+        let alerts = AlertsConfig {
+            ignore_mounts: vec!["/snap/*".to_string(), "/mnt/backup?".to_string()],
+            ..AlertsConfig::default()
+        };
+        assert!(alerts.mount_ignored("/snap/core/123"));
+        assert!(alerts.mount_ignored("/mnt/backup1"));
+        assert!(!alerts.mount_ignored("/mnt/backup"));
+        assert!(!alerts.mount_ignored("/"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("/var/*/log*", "/var/lib/docker/logs"));
+    }
+
+    #[test]
+    fn redacted_config_hides_secrets() {
+        let mut cfg = valid_config();
+        cfg.telegram.bot_token = Some("123:abc".to_string());
+        cfg.notifiers.pushover.push(PushoverConfig {
+            name: "phone".to_string(),
+            user_key: "user".to_string(),
+            token: "app".to_string(),
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+        cfg.push.token = Some("push-secret".to_string());
+        let yaml = serde_yaml::to_string(&cfg.redacted()).unwrap();
+        assert!(!yaml.contains("123:abc"));
+        assert!(!yaml.contains("push-secret"));
+        assert!(!yaml.contains("user_key: user"));
+        assert!(yaml.contains("name: phone"));
+        assert_eq!(cfg.telegram.bot_token.as_deref(), Some("123:abc"));
+    }
+
+    #[test]
+    fn collection_ignore_lists_match_globs_and_reject_empty() {
+        let mut cfg = valid_config();
+        cfg.network.ignore_ifaces = vec!["lo".to_string(), "veth*".to_string()];
+        cfg.disks.ignore_mounts = vec!["/snap/*".to_string()];
+        assert!(cfg.validate().is_ok());
+        assert!(cfg.network.iface_ignored("lo"));
+        assert!(cfg.network.iface_ignored("veth1a2b3c"));
+        assert!(!cfg.network.iface_ignored("eth0"));
+        assert!(cfg.disks.mount_ignored("/snap/core22/1380"));
+        assert!(!cfg.disks.mount_ignored("/"));
+
+        cfg.network.ignore_ifaces.push(" ".to_string());
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn rules_are_compiled_on_validation() {
+        let mut cfg = valid_config();
+        cfg.rules.push(AlertRuleConfig {
+            name: "busy".to_string(),
+            expr: "cpu_usage > 80 && net.rx_total_bps < 1000".to_string(),
+            message: None,
+            severity: None,
+        });
+        cfg.validate().expect("валидное правило");
+
+        cfg.rules[0].expr = "cpu_usage >> 80".to_string();
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn expected_status_accepts_codes_classes_and_lists() {
+        let parse = |yaml: &str| serde_yaml::from_str::<ExpectedStatus>(yaml);
+        let single = parse("204").unwrap();
+        assert!(single.matches(204) && !single.matches(200));
+        let list = parse("[200, \"3XX\"]").unwrap();
+        assert!(list.matches(200) && list.matches(301) && !list.matches(204));
+        assert_eq!(serde_yaml::to_string(&list).unwrap(), "- 200\n- 3xx\n");
+        assert!(parse("6xx").is_err());
+        assert!(parse("[]").is_err());
+        assert!(parse("99").is_err());
+
+        let mut cfg = valid_config();
+        cfg.http_checks.push(HttpCheckConfig {
+            name: "api".to_string(),
+            url: "https://example.com/health".to_string(),
+            timeout_ms: 1000,
+            expected_status: ExpectedStatus::default(),
+            headers: HashMap::new(),
+            body_regex: None,
+            json_assertions: Vec::new(),
+            tls: None,
+            follow_redirects: true,
+            max_redirects: 0,
+            retries: 0,
+            retry_delay_ms: 0,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: Vec::new(),
+            alerts: CheckAlertPolicy::default(),
+        });
+        assert!(cfg.validate().is_err());
+        cfg.http_checks[0].follow_redirects = false;
+        cfg.validate()
+            .expect("без переходов max_redirects не проверяется");
+    }
+
+    #[test]
+    fn check_modules_fill_missing_fields() {
+        let base = "listen: 127.0.0.1:9108\ninterval_secs: 5\n";
+        let cfg = Config::load_from_str(
+            &format!(
+                "{base}modules:
+  internal:
+    timeout_ms: 3000
+    headers: {{Authorization: Bearer x}}
+    expected_status: [200, 204]
+    alerts: {{fail_threshold: 2, severity: warning}}
+http_checks:
+  - name: api
+    url: http://127.0.0.1/health
+    module: internal
+    expected_status: 2xx
+    alerts: {{fail_threshold: 5}}
+tcp_checks:
+  - name: db
+    host: 127.0.0.1
+    port: 5432
+    module: internal
+"
+            ),
+            "test",
+        )
+        .unwrap();
+        let api = &cfg.http_checks[0];
+        assert_eq!(api.timeout_ms, 3000);
+        assert_eq!(api.headers["Authorization"], "Bearer x");
+        assert!(api.expected_status.matches(201));
+        assert_eq!(api.alerts.fail_threshold, Some(5));
+        assert_eq!(api.alerts.severity, Some(AlertSeverity::Warning));
+        assert_eq!(cfg.tcp_checks[0].timeout_ms, 3000);
+        let redacted = serde_yaml::to_string(&cfg.redacted()).unwrap();
+        assert!(!redacted.contains("Bearer x"));
+
+        let unknown = format!(
+            "{base}http_checks:\n  - {{name: api, url: http://x/, timeout_ms: 1, module: missing}}\n"
+        );
+        assert!(Config::load_from_str(&unknown, "test").is_err());
+        let bad_key = format!("{base}modules:\n  internal: {{url: http://x/}}\n");
+        assert!(Config::load_from_str(&bad_key, "test").is_err());
+    }
+
+    #[test]
+    fn check_dependencies_must_exist_and_be_acyclic() {
+        let check = |name: &str, depends_on: &[&str]| TcpCheckConfig {
+            name: name.to_string(),
+            host: "127.0.0.1".to_string(),
+            port: 80,
+            timeout_ms: 500,
+            send: None,
+            expect_regex: None,
+            tls: None,
+            resolve_to: None,
+            ip_version: None,
+            interval_secs: None,
+            jitter_secs: 0,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            alerts: CheckAlertPolicy::default(),
+        };
+        let mut cfg = valid_config();
+        cfg.tcp_checks = vec![check("gateway", &[]), check("db", &["gateway"])];
+        cfg.validate()
+            .expect("зависимость от существующей проверки");
+
+        cfg.tcp_checks[1].depends_on = vec!["router".to_string()];
+        assert!(cfg.validate().is_err());
+
+        cfg.tcp_checks = vec![
+            check("gateway", &["db"]),
+            check("db", &["cache"]),
+            check("cache", &["gateway"]),
+        ];
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn sensor_alert_patterns_are_case_insensitive() {
+        let rule = SensorAlertConfig {
+            pattern: "*NVMe*".to_string(),
+            threshold_celsius: 70.0,
+            clear_threshold_celsius: Some(60.0),
+            label: None,
+        };
+        assert!(rule.matches("nvme0 Composite"));
+        assert!(!rule.matches("Chipset"));
+
+        let mut cfg = valid_config();
+        cfg.telegram.alerts.sensor_alerts.push(SensorAlertConfig {
+            clear_threshold_celsius: Some(80.0),
+            ..rule
+        });
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn alert_templates_are_validated() {
+        let mut cfg = valid_config();
+        cfg.telegram
+            .alerts
+            .templates
+            .insert("cpu_temp".to_string(), "CPU {{ value }}".to_string());
+        cfg.validate().expect("корректный шаблон");
+
+        cfg.telegram
+            .alerts
+            .templates
+            .insert("cpu_temp".to_string(), "CPU {{ value".to_string());
+        assert!(cfg.validate().is_err());
+
+        cfg.telegram.alerts.templates.clear();
+        cfg.telegram
+            .alerts
+            .templates
+            .insert("unknown".to_string(), "x".to_string());
+        assert!(cfg.validate().is_err());
+    }
 }
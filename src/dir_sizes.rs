@@ -0,0 +1,202 @@
+use crate::config::{Config, DirSizesConfig};
+use crate::state::{DirSizeEntry, DirSizeStat, State};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, RwLock};
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{debug, warn};
+
+// Обход идёт в блокирующем потоке вне такта сбора: на больших деревьях он занимает минуты.
+// Результат попадает в состояние и уходит в ближайший снимок.
+pub async fn run(
+    state: Arc<RwLock<State>>,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut cfg = config_rx.borrow_and_update().dir_sizes.clone();
+    let mut ticker = scan_interval(&cfg);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            Ok(()) = config_rx.changed() => {
+                let updated = config_rx.borrow_and_update().dir_sizes.clone();
+                // Изменённый список пересчитывается сразу, не дожидаясь следующего обхода.
+                if updated != cfg {
+                    cfg = updated;
+                    ticker = scan_interval(&cfg);
+                }
+            }
+            _ = ticker.tick() => {
+                if cfg.paths.is_empty() {
+                    state.write().await.dir_sizes.clear();
+                    continue;
+                }
+                let scan = cfg.clone();
+                let stop = Arc::new(AtomicBool::new(false));
+                let worker_stop = stop.clone();
+                let started = std::time::Instant::now();
+                let worker = tokio::task::spawn_blocking(move || scan_all(&scan, &worker_stop));
+                // Остановка агента не ждёт окончания долгого обхода.
+                let result = tokio::select! {
+                    result = worker => result,
+                    _ = shutdown.changed() => {
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                };
+                match result {
+                    Ok(stats) => {
+                        debug!(
+                            paths = stats.len(),
+                            elapsed_ms = started.elapsed().as_millis() as u64,
+                            "размеры каталогов пересчитаны"
+                        );
+                        state.write().await.dir_sizes = stats;
+                    }
+                    Err(err) => warn!(error = %err, "обход каталогов dir_sizes прервался"),
+                }
+            }
+        }
+    }
+}
+
+fn scan_interval(cfg: &DirSizesConfig) -> Interval {
+    let mut ticker = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+fn scan_all(cfg: &DirSizesConfig, stop: &AtomicBool) -> Vec<DirSizeStat> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    cfg.paths
+        .iter()
+        .map(|dir| scan_dir(Path::new(&dir.path), cfg.top, now, stop))
+        .collect()
+}
+
+// Считается видимый размер файлов. Символические ссылки не раскрываются, а на Unix обход не
+// выходит за пределы файловой системы каталога (смонтированные внутрь диски не учитываются).
+pub fn scan_dir(root: &Path, top: usize, now_unix: i64, stop: &AtomicBool) -> DirSizeStat {
+    let mut stat = DirSizeStat {
+        path: root.to_string_lossy().into_owned(),
+        scanned_at_unix: now_unix,
+        ..DirSizeStat::default()
+    };
+    let opened =
+        fs::symlink_metadata(root).and_then(|meta| Ok((device(&meta), fs::read_dir(root)?)));
+    let (root_device, entries) = match opened {
+        Ok(opened) => opened,
+        Err(err) => {
+            stat.error = Some(err.to_string());
+            return stat;
+        }
+    };
+    let mut children = Vec::new();
+    for entry in entries {
+        let Ok(entry) = entry else {
+            stat.skipped_entries += 1;
+            continue;
+        };
+        let (size_bytes, skipped) = tree_size(&entry.path(), root_device, stop);
+        stat.size_bytes += size_bytes;
+        stat.skipped_entries += skipped;
+        children.push(DirSizeEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            size_bytes,
+        });
+    }
+    children.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then(a.name.cmp(&b.name)));
+    children.truncate(top);
+    stat.top = children;
+    stat
+}
+
+fn tree_size(path: &Path, root_device: Option<u64>, stop: &AtomicBool) -> (u64, u64) {
+    let (mut size, mut skipped) = (0, 0);
+    let mut stack = vec![path.to_path_buf()];
+    while let Some(path) = stack.pop() {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            skipped += 1;
+            continue;
+        };
+        if meta.is_file() {
+            size += meta.len();
+        } else if meta.is_dir() && device(&meta) == root_device {
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        match entry {
+                            Ok(entry) => stack.push(entry.path()),
+                            Err(_) => skipped += 1,
+                        }
+                    }
+                }
+                Err(_) => skipped += 1,
+            }
+        }
+    }
+    (size, skipped)
+}
+
+#[cfg(unix)]
+fn device(meta: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_meta: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn largest_entries_are_reported_without_following_symlinks() {
+        let dir = std::env::temp_dir().join(format!("monitord-dirsize-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("logs/nested")).unwrap();
+        fs::create_dir_all(dir.join("cache")).unwrap();
+        fs::write(dir.join("logs/a.log"), vec![0u8; 3000]).unwrap();
+        fs::write(dir.join("logs/nested/b.log"), vec![0u8; 2000]).unwrap();
+        fs::write(dir.join("cache/blob"), vec![0u8; 1000]).unwrap();
+        fs::write(dir.join("small.txt"), vec![0u8; 10]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("logs"), dir.join("logs-link")).unwrap();
+
+        let stop = AtomicBool::new(false);
+        let stat = scan_dir(&dir, 2, 42, &stop);
+        assert_eq!(stat.error, None);
+        assert_eq!(stat.size_bytes, 6010);
+        assert_eq!(stat.scanned_at_unix, 42);
+        assert_eq!(
+            stat.top,
+            vec![
+                DirSizeEntry {
+                    name: "logs".to_string(),
+                    size_bytes: 5000,
+                },
+                DirSizeEntry {
+                    name: "cache".to_string(),
+                    size_bytes: 1000,
+                },
+            ]
+        );
+
+        let missing = scan_dir(&dir.join("missing"), 2, 42, &stop);
+        assert!(missing.error.is_some());
+        assert_eq!(missing.size_bytes, 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::aggregator::Discovered;
+use crate::config::{valid_host_name, AggregatedHostConfig, DiscoveryConfig};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+pub const SERVICE_TYPE: &str = "_monitord._tcp.local.";
+
+pub async fn run(
+    cfg: DiscoveryConfig,
+    listen: String,
+    instance: String,
+    discovered: Discovered,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    if !cfg.advertise && !cfg.browse {
+        return;
+    }
+    let daemon = match ServiceDaemon::new() {
+        Ok(daemon) => daemon,
+        Err(err) => {
+            warn!(error = %err, "не удалось запустить mDNS");
+            return;
+        }
+    };
+
+    let fullname = format!("{instance}.{SERVICE_TYPE}");
+    if cfg.advertise {
+        match advertise(&daemon, &listen, &instance) {
+            Ok(()) => info!(instance = %instance, "агент объявлен через mDNS"),
+            Err(err) => warn!(error = %err, "не удалось объявить агент через mDNS"),
+        }
+    }
+
+    let browser = if cfg.browse {
+        daemon
+            .browse(SERVICE_TYPE)
+            .map_err(|err| warn!(error = %err, "не удалось начать поиск агентов через mDNS"))
+            .ok()
+    } else {
+        None
+    };
+    match browser {
+        Some(events) => {
+            let mut found: HashMap<String, AggregatedHostConfig> = HashMap::new();
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    event = events.recv_async() => {
+                        let Ok(event) = event else { break };
+                        if apply_event(&mut found, event, &fullname) {
+                            let mut hosts: Vec<_> = found.values().cloned().collect();
+                            hosts.sort_by(|a, b| a.name.cmp(&b.name));
+                            hosts.dedup_by(|a, b| a.name == b.name);
+                            *discovered.write().await = hosts;
+                        }
+                    }
+                }
+            }
+        }
+        None => {
+            let _ = shutdown.changed().await;
+        }
+    }
+
+    if cfg.advertise {
+        let _ = daemon.unregister(&fullname);
+    }
+    let _ = daemon.shutdown();
+}
+
+fn advertise(daemon: &ServiceDaemon, listen: &str, instance: &str) -> Result<(), String> {
+    let addr: SocketAddr = listen
+        .parse()
+        .map_err(|err| format!("некорректный listen '{listen}': {err}"))?;
+    let properties = [("host", instance), ("version", env!("CARGO_PKG_VERSION"))];
+    let host_name = format!("{instance}.local.");
+    let info = if addr.ip().is_unspecified() {
+        // Слушаем все интерфейсы: объявляем их адреса и следим за их сменой.
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            instance,
+            &host_name,
+            "",
+            addr.port(),
+            &properties[..],
+        )
+        .map(ServiceInfo::enable_addr_auto)
+    } else {
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            instance,
+            &host_name,
+            addr.ip(),
+            addr.port(),
+            &properties[..],
+        )
+    }
+    .map_err(|err| err.to_string())?;
+    daemon.register(info).map_err(|err| err.to_string())
+}
+
+// Возвращает true, если список найденных агентов изменился.
+fn apply_event(
+    found: &mut HashMap<String, AggregatedHostConfig>,
+    event: ServiceEvent,
+    own_fullname: &str,
+) -> bool {
+    match event {
+        ServiceEvent::ServiceResolved(service) if service.get_fullname() != own_fullname => {
+            let Some(host) = found_host(&service) else {
+                return false;
+            };
+            let previous = found.insert(service.get_fullname().to_string(), host.clone());
+            if previous.as_ref().map(|p| &p.url) == Some(&host.url) {
+                return false;
+            }
+            info!(host = %host.name, url = %host.url, "найден агент через mDNS");
+            true
+        }
+        ServiceEvent::ServiceRemoved(_, fullname) => match found.remove(&fullname) {
+            Some(host) => {
+                info!(host = %host.name, "агент пропал из mDNS");
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn found_host(service: &ServiceInfo) -> Option<AggregatedHostConfig> {
+    let name = service
+        .get_property_val_str("host")
+        .filter(|name| valid_host_name(name))?;
+    // Link-local IPv6 без указания интерфейса из URL не открыть.
+    let mut addresses: Vec<&IpAddr> = service
+        .get_addresses()
+        .iter()
+        .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+        .collect();
+    addresses.sort_by_key(|ip| (ip.is_ipv6(), **ip));
+    let url = match addresses.first()? {
+        IpAddr::V4(ip) => format!("http://{ip}:{}", service.get_port()),
+        IpAddr::V6(ip) => format!("http://[{ip}]:{}", service.get_port()),
+    };
+    Some(AggregatedHostConfig {
+        name: name.to_string(),
+        url,
+        token_env: None,
+        token: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(instance: &str, ips: &str, properties: &[(&str, &str)]) -> ServiceInfo {
+        ServiceInfo::new(SERVICE_TYPE, instance, "db-1.local.", ips, 9100, properties).unwrap()
+    }
+
+    #[test]
+    fn discovered_services_become_polled_hosts() {
+        let own = format!("self.{SERVICE_TYPE}");
+        let mut found = HashMap::new();
+
+        let db = service("db-1", "fe80::1,192.168.1.20", &[("host", "db-1")]);
+        assert!(apply_event(
+            &mut found,
+            ServiceEvent::ServiceResolved(db.clone()),
+            &own
+        ));
+        assert_eq!(found[db.get_fullname()].name, "db-1");
+        assert_eq!(found[db.get_fullname()].url, "http://192.168.1.20:9100");
+        // Повторное объявление с тем же адресом список не меняет.
+        assert!(!apply_event(
+            &mut found,
+            ServiceEvent::ServiceResolved(db.clone()),
+            &own
+        ));
+
+        let me = service("self", "192.168.1.10", &[("host", "self")]);
+        assert!(!apply_event(
+            &mut found,
+            ServiceEvent::ServiceResolved(me),
+            &own
+        ));
+        let bad = service("bad", "192.168.1.30", &[("host", "bad name!")]);
+        assert!(!apply_event(
+            &mut found,
+            ServiceEvent::ServiceResolved(bad),
+            &own
+        ));
+
+        assert!(apply_event(
+            &mut found,
+            ServiceEvent::ServiceRemoved(SERVICE_TYPE.to_string(), db.get_fullname().to_string()),
+            &own
+        ));
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::aggregator::FleetAlert;
+use crate::config::Config;
+use crate::external::ExternalAlert;
+use crate::notify::Notification;
+use crate::rules::RuleAlert;
+use crate::state::{
+    AlertEvent, CollectorAlert, CpuStealAlert, DirSizeAlert, DiskForecast, DiskLatencyAlert,
+    HostReboot, MountChange, NetErrorAlert, PageFileAlert, ReadOnlyMount, ResourceAlert,
+    SensorAlert, Snapshot, TempRiseAlert, TimeSyncAlert, WireguardAlert,
+};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum Alert {
+    Check(AlertEvent),
+    Resource(ResourceAlert),
+    Sensor(SensorAlert),
+    DiskForecast(DiskForecast),
+    Rule(RuleAlert),
+    Mount(MountChange),
+    ReadOnly(ReadOnlyMount),
+    DiskLatency(DiskLatencyAlert),
+    DirSize(DirSizeAlert),
+    NetErrors(NetErrorAlert),
+    CpuSteal(CpuStealAlert),
+    TimeSync(TimeSyncAlert),
+    PageFile(PageFileAlert),
+    Wireguard(WireguardAlert),
+    TempRise(TempRiseAlert),
+    HostReboot(HostReboot),
+    Watchdog { stale: bool, age_secs: i64 },
+    Collector(CollectorAlert),
+    Fleet(FleetAlert),
+    External(ExternalAlert),
+}
+
+impl Alert {
+    pub fn notification(&self) -> Notification {
+        match self {
+            Self::Check(event) => Notification::from_alert_event(event),
+            Self::Resource(alert) => Notification::from_resource_alert(alert),
+            Self::Sensor(alert) => Notification::from_sensor_alert(alert),
+            Self::DiskForecast(forecast) => Notification::from_disk_forecast(forecast),
+            Self::Rule(alert) => Notification::from_rule_alert(alert),
+            Self::Mount(change) => Notification::from_mount_change(change),
+            Self::ReadOnly(mount) => Notification::from_read_only_mount(mount),
+            Self::DiskLatency(alert) => Notification::from_disk_latency_alert(alert),
+            Self::DirSize(alert) => Notification::from_dir_size_alert(alert),
+            Self::NetErrors(alert) => Notification::from_net_error_alert(alert),
+            Self::CpuSteal(alert) => Notification::from_cpu_steal_alert(alert),
+            Self::TimeSync(alert) => Notification::from_time_sync_alert(alert),
+            Self::PageFile(alert) => Notification::from_pagefile_alert(alert),
+            Self::Wireguard(alert) => Notification::from_wireguard_alert(alert),
+            Self::TempRise(alert) => Notification::from_temp_rise_alert(alert),
+            Self::HostReboot(reboot) => Notification::from_host_reboot(reboot),
+            Self::Watchdog { stale, age_secs } => Notification::watchdog(*stale, *age_secs),
+            Self::Collector(alert) => Notification::from_collector_alert(alert),
+            Self::Fleet(alert) => Notification::from_fleet_alert(alert),
+            Self::External(alert) => Notification::from_external_alert(alert),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    SnapshotUpdated {
+        now: i64,
+        snapshot: Arc<Snapshot>,
+    },
+    AlertFired {
+        now: i64,
+        host: Option<String>,
+        alert: Alert,
+    },
+    AlertResolved {
+        now: i64,
+        host: Option<String>,
+        alert: Alert,
+    },
+    ConfigReloaded {
+        config: Arc<Config>,
+        restart_required: Vec<&'static str>,
+    },
+    ConfigRejected {
+        error: String,
+    },
+    AgentStopping {
+        reason: &'static str,
+    },
+}
+
+impl Event {
+    pub fn alert(now: i64, host: Option<String>, alert: Alert) -> Self {
+        if alert.notification().recovered {
+            Self::AlertResolved { now, host, alert }
+        } else {
+            Self::AlertFired { now, host, alert }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { tx }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: Event) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+pub async fn next_event(rx: &mut broadcast::Receiver<Event>) -> Option<Event> {
+    loop {
+        match rx.recv().await {
+            Ok(event) => return Some(event),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    skipped,
+                    "подписчик шины событий не успевает, события пропущены"
+                );
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    #[tokio::test]
+    async fn subscribers_receive_events_until_bus_is_dropped() {
+        let bus = EventBus::default();
+        let mut rx = bus.subscribe();
+        bus.publish(Event::alert(
+            10,
+            None,
+            Alert::Watchdog {
+                stale: false,
+                age_secs: 5,
+            },
+        ));
+        bus.publish(Event::SnapshotUpdated {
+            now: 10,
+            snapshot: Arc::new(State::new(0).snapshot()),
+        });
+        drop(bus);
+
+        assert!(matches!(
+            next_event(&mut rx).await,
+            Some(Event::AlertResolved {
+                alert: Alert::Watchdog { stale: false, .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            next_event(&mut rx).await,
+            Some(Event::SnapshotUpdated { now: 10, .. })
+        ));
+        assert!(next_event(&mut rx).await.is_none());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::state::{AlertSeverity, State};
+
+const MAX_NAME_CHARS: usize = 64;
+const MAX_MESSAGE_CHARS: usize = 1000;
+// Защита от скриптов, которые шлют уникальное имя на каждый запуск.
+const MAX_ACTIVE_ALERTS: usize = 256;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExternalAlertRequest {
+    pub name: String,
+    #[serde(default = "default_severity")]
+    pub severity: AlertSeverity,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub resolved: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalAlert {
+    pub name: String,
+    pub severity: AlertSeverity,
+    pub message: Option<String>,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ActiveExternalAlert {
+    pub name: String,
+    pub severity: AlertSeverity,
+    pub message: Option<String>,
+    pub fired_at_unix: i64,
+    pub updated_at_unix: i64,
+    pub expires_at_unix: Option<i64>,
+}
+
+fn default_severity() -> AlertSeverity {
+    AlertSeverity::Critical
+}
+
+// Возвращает событие для рассылки; повтор активного уведомления с той же важностью только продлевает его.
+pub fn apply_external_alert(
+    state: &mut State,
+    req: ExternalAlertRequest,
+    default_ttl_secs: Option<u64>,
+    now: i64,
+) -> Result<Option<ExternalAlert>, String> {
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!(
+            "name должен содержать 1..{MAX_NAME_CHARS} символов"
+        ));
+    }
+    if req
+        .message
+        .as_deref()
+        .is_some_and(|m| m.chars().count() > MAX_MESSAGE_CHARS)
+    {
+        return Err(format!(
+            "message должен быть не длиннее {MAX_MESSAGE_CHARS} символов"
+        ));
+    }
+    if req.ttl_secs == Some(0) {
+        return Err("ttl_secs должно быть >= 1".to_string());
+    }
+
+    if req.resolved {
+        return Ok(state
+            .external_alerts
+            .remove(name)
+            .map(|active| ExternalAlert {
+                name: active.name,
+                severity: active.severity,
+                message: req.message,
+                recovered: true,
+            }));
+    }
+
+    let expires_at_unix = req
+        .ttl_secs
+        .or(default_ttl_secs)
+        .map(|ttl| now.saturating_add(ttl as i64));
+    if let Some(active) = state.external_alerts.get_mut(name) {
+        let changed = active.severity != req.severity;
+        active.severity = req.severity;
+        active.message = req.message.clone();
+        active.updated_at_unix = now;
+        active.expires_at_unix = expires_at_unix;
+        return Ok(changed.then(|| ExternalAlert {
+            name: name.to_string(),
+            severity: req.severity,
+            message: req.message,
+            recovered: false,
+        }));
+    }
+    if state.external_alerts.len() >= MAX_ACTIVE_ALERTS {
+        return Err(format!(
+            "активных внешних уведомлений не может быть больше {MAX_ACTIVE_ALERTS}"
+        ));
+    }
+    state.external_alerts.insert(
+        name.to_string(),
+        ActiveExternalAlert {
+            name: name.to_string(),
+            severity: req.severity,
+            message: req.message.clone(),
+            fired_at_unix: now,
+            updated_at_unix: now,
+            expires_at_unix,
+        },
+    );
+    Ok(Some(ExternalAlert {
+        name: name.to_string(),
+        severity: req.severity,
+        message: req.message,
+        recovered: false,
+    }))
+}
+
+// Уведомления, которые не продлили до истечения ttl, снимаются автоматически.
+pub fn expire_external_alerts(state: &mut State, now: i64) -> Vec<ExternalAlert> {
+    let expired = state
+        .external_alerts
+        .values()
+        .filter(|a| a.expires_at_unix.is_some_and(|at| at <= now))
+        .map(|a| a.name.clone())
+        .collect::<Vec<_>>();
+    expired
+        .into_iter()
+        .filter_map(|name| state.external_alerts.remove(&name))
+        .map(|active| ExternalAlert {
+            name: active.name,
+            severity: active.severity,
+            message: None,
+            recovered: true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(name: &str, severity: AlertSeverity) -> ExternalAlertRequest {
+        ExternalAlertRequest {
+            name: name.to_string(),
+            severity,
+            message: Some("backup failed".to_string()),
+            ttl_secs: None,
+            resolved: false,
+        }
+    }
+
+    #[test]
+    fn external_alerts_fire_once_and_resolve_by_request_or_ttl() {
+        let mut state = State::new(0);
+        let fired = apply_external_alert(
+            &mut state,
+            request("backup", AlertSeverity::Warning),
+            Some(60),
+            100,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(!fired.recovered);
+
+        // Повтор продлевает ttl без нового уведомления, смена важности уведомляет снова.
+        let repeat = request("backup", AlertSeverity::Warning);
+        assert!(apply_external_alert(&mut state, repeat, Some(60), 130)
+            .unwrap()
+            .is_none());
+        assert!(expire_external_alerts(&mut state, 160).is_empty());
+        let escalated = request("backup", AlertSeverity::Critical);
+        let escalated = apply_external_alert(&mut state, escalated, Some(60), 170)
+            .unwrap()
+            .unwrap();
+        assert_eq!(escalated.severity, AlertSeverity::Critical);
+
+        let expired = expire_external_alerts(&mut state, 230);
+        assert_eq!(expired.len(), 1);
+        assert!(expired[0].recovered);
+        assert!(state.external_alerts.is_empty());
+
+        apply_external_alert(
+            &mut state,
+            request("cron", AlertSeverity::Critical),
+            None,
+            300,
+        )
+        .unwrap();
+        assert!(expire_external_alerts(&mut state, 1_000_000).is_empty());
+        let mut resolve = request("cron", AlertSeverity::Critical);
+        resolve.resolved = true;
+        let resolved = apply_external_alert(&mut state, resolve, None, 400)
+            .unwrap()
+            .unwrap();
+        assert!(resolved.recovered);
+        assert!(
+            apply_external_alert(&mut state, request(" ", AlertSeverity::Warning), None, 0)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use crate::config::{Config, HeartbeatConfig};
+use crate::state::SharedSnapshot;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+// Внешний сервис (Healthchecks.io, Uptime Kuma) поднимает тревогу, если пинги перестают приходить.
+pub async fn run(
+    client: Client,
+    snapshot: SharedSnapshot,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut cfg = config_rx.borrow_and_update().heartbeat.clone();
+    let mut ticker = heartbeat_interval(&cfg);
+    let mut last_pinged_collect = 0_i64;
+    let mut last_error: Option<String> = None;
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {
+                if config_rx.has_changed().unwrap_or(false) {
+                    cfg = config_rx.borrow_and_update().heartbeat.clone();
+                    if ticker.period() != Duration::from_secs(cfg.interval_secs) {
+                        ticker = heartbeat_interval(&cfg);
+                        ticker.reset();
+                    }
+                }
+                let last_collect = snapshot.load().last_collect_timestamp_seconds;
+                let Some(url) = ping_url(&cfg, last_collect, last_pinged_collect) else {
+                    continue;
+                };
+                match ping(&client, url, cfg.timeout_secs).await {
+                    Ok(()) => {
+                        if last_collect > last_pinged_collect {
+                            last_pinged_collect = last_collect;
+                        }
+                        if last_error.take().is_some() {
+                            info!("heartbeat-пинги возобновились");
+                        }
+                    }
+                    Err(err) => {
+                        if last_error.as_deref() != Some(err.as_str()) {
+                            warn!(error = %err, "не удалось отправить heartbeat-пинг");
+                        }
+                        last_error = Some(err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn heartbeat_interval(cfg: &HeartbeatConfig) -> Interval {
+    let mut ticker = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+// Пингуем, только если с прошлого пинга был новый успешный сбор; иначе — fail_url, если он задан.
+fn ping_url(cfg: &HeartbeatConfig, last_collect: i64, last_pinged_collect: i64) -> Option<&str> {
+    let url = cfg.url.as_deref()?;
+    if last_collect > last_pinged_collect {
+        Some(url)
+    } else if last_pinged_collect > 0 {
+        cfg.fail_url.as_deref()
+    } else {
+        None
+    }
+}
+
+async fn ping(client: &Client, url: &str, timeout_secs: u64) -> Result<(), String> {
+    client
+        .get(url)
+        .timeout(Duration::from_secs(timeout_secs))
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heartbeat_pings_only_after_new_collection() {
+        let mut cfg = HeartbeatConfig {
+            url: Some("https://hc-ping.com/uuid".to_string()),
+            ..HeartbeatConfig::default()
+        };
+        // До первого сбора пинговать нечего.
+        assert_eq!(ping_url(&cfg, 0, 0), None);
+        assert_eq!(ping_url(&cfg, 100, 0), Some("https://hc-ping.com/uuid"));
+        assert_eq!(ping_url(&cfg, 100, 100), None);
+
+        cfg.fail_url = Some("https://hc-ping.com/uuid/fail".to_string());
+        assert_eq!(
+            ping_url(&cfg, 100, 100),
+            Some("https://hc-ping.com/uuid/fail")
+        );
+        assert_eq!(ping_url(&cfg, 0, 0), None);
+
+        cfg.url = None;
+        assert_eq!(ping_url(&cfg, 100, 0), None);
+    }
+}
@@ -1,87 +1,188 @@
-﻿use crate::metrics::Metrics;
+use crate::aggregator::{record_push, HostState, Hosts, IngestError};
+use crate::config::IngestConfig;
+use crate::events::{Alert, Event, EventBus};
+use crate::external::{apply_external_alert, ActiveExternalAlert, ExternalAlertRequest};
+use crate::logbuf::{LogBuffer, LogEntry};
+use crate::metrics::Metrics;
+use crate::openmetrics;
 use crate::state::{
-    CheckResults, DiskStat, GpuStat, InternetSpeedStat, NetStat, SensorStat, State as AgentState,
-    TempStat,
+    HistoryRow, InternetSpeedStat, SharedSnapshot, Snapshot, SpeedtestStats, State as AgentState,
+    HISTORY_METRICS, SPEEDTEST_STATS_WINDOWS_HOURS,
 };
-use axum::body::Body;
-use axum::extract::State;
-use axum::http::{header::CONTENT_TYPE, HeaderValue, StatusCode};
-use axum::response::{IntoResponse, Response};
-use axum::{routing::get, Json, Router};
+use crate::status_page;
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, State};
+use axum::http::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream;
+use ring::digest;
+use std::convert::Infallible;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::info;
 
 #[derive(Clone)]
 pub struct HttpAppState {
     pub metrics: Arc<Metrics>,
     pub state: Arc<RwLock<AgentState>>,
+    pub snapshot: SharedSnapshot,
+    pub logs: LogBuffer,
+    pub hosts: Hosts,
+    pub ingest: Option<IngestApi>,
+    pub alert_api: Option<AlertApi>,
+    pub status_page: Option<StatusPage>,
+    // Без токена GET /api/* открыты всем, кто видит адрес listen.
+    pub api_token: Option<Arc<str>>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct ApiState {
-    pub started_at_unix: i64,
-    pub last_collect_timestamp_seconds: i64,
-    pub host_name: Option<String>,
-    pub os_name: Option<String>,
-    pub os_version: Option<String>,
-    pub kernel_version: Option<String>,
-    pub cpu_brand: Option<String>,
-    pub system_uptime_seconds: u64,
-    pub process_count: u64,
-    pub cpu_core_count: u32,
-    pub cpu_usage_percent: f64,
-    pub memory_used_bytes: u64,
-    pub memory_total_bytes: u64,
-    pub disks: Vec<DiskStat>,
-    pub net: Vec<NetStat>,
-    pub internet_speed: Option<InternetSpeedStat>,
-    pub temps: Vec<TempStat>,
-    pub gpus: Vec<GpuStat>,
-    pub sensors: Vec<SensorStat>,
-    pub checks: CheckResults,
-}
-
-impl From<&AgentState> for ApiState {
-    fn from(value: &AgentState) -> Self {
-        Self {
-            started_at_unix: value.started_at_unix,
-            last_collect_timestamp_seconds: value.last_collect_timestamp_seconds,
-            host_name: value.host_name.clone(),
-            os_name: value.os_name.clone(),
-            os_version: value.os_version.clone(),
-            kernel_version: value.kernel_version.clone(),
-            cpu_brand: value.cpu_brand.clone(),
-            system_uptime_seconds: value.system_uptime_seconds,
-            process_count: value.process_count,
-            cpu_core_count: value.cpu_core_count,
-            cpu_usage_percent: value.cpu_usage_percent,
-            memory_used_bytes: value.memory_used_bytes,
-            memory_total_bytes: value.memory_total_bytes,
-            disks: value.disks.clone(),
-            net: value.net.clone(),
-            internet_speed: value.internet_speed.clone(),
-            temps: value.temps.clone(),
-            gpus: value.gpus.clone(),
-            sensors: value.sensors.clone(),
-            checks: value.checks.clone(),
-        }
-    }
+// Приём состояний от агентов в режиме push: токен и ограничения из aggregator.ingest.
+#[derive(Clone)]
+pub struct IngestApi {
+    pub token: Arc<str>,
+    pub config: Arc<IngestConfig>,
 }
 
-pub fn build_router(metrics: Arc<Metrics>, state: Arc<RwLock<AgentState>>) -> Router {
+// Приём внешних уведомлений: токен и шина, через которую они уходят в каналы уведомлений.
+#[derive(Clone)]
+pub struct AlertApi {
+    pub token: Arc<str>,
+    pub default_ttl_secs: Option<u64>,
+    pub bus: EventBus,
+}
+
+// Публичная страница /status; без токена открыта всем.
+#[derive(Clone)]
+pub struct StatusPage {
+    pub title: Arc<str>,
+    pub token: Option<Arc<str>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StatusPageQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LogsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct HistoryExportQuery {
+    pub metric: String,
+    pub subject: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SpeedtestHistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SpeedtestHistoryResponse {
+    samples: Vec<InternetSpeedStat>,
+    stats: Vec<SpeedtestStats>,
+}
+
+// Процентили отсутствуют, пока у проверки не было ни одного успешного свежего прогона.
+#[derive(Debug, serde::Serialize)]
+struct CheckEntry<'a> {
+    kind: &'a str,
+    name: &'a str,
+    up: bool,
+    latency_ms: u64,
+    samples: usize,
+    p50_ms: Option<u64>,
+    p95_ms: Option<u64>,
+    p99_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IngestPayload {
+    pub host: String,
+    pub state: Snapshot,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn build_router(
+    metrics: Arc<Metrics>,
+    state: Arc<RwLock<AgentState>>,
+    snapshot: SharedSnapshot,
+    logs: LogBuffer,
+    hosts: Hosts,
+    ingest: Option<IngestApi>,
+    alert_api: Option<AlertApi>,
+    status_page: Option<StatusPage>,
+    api_token: Option<String>,
+) -> Router {
     Router::new()
         .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/metrics", get(metrics_handler))
+        .route("/status", get(status_page_handler))
         .route("/api/state", get(state_handler))
-        .with_state(HttpAppState { metrics, state })
+        .route("/api/checks", get(checks_handler))
+        .route("/api/logs", get(logs_handler))
+        .route("/api/history/export", get(history_export_handler))
+        .route("/api/speedtest/history", get(speedtest_history_handler))
+        .route("/api/hosts", get(hosts_handler))
+        .route("/api/ingest", post(ingest_handler))
+        .route(
+            "/api/alerts",
+            get(alerts_handler).post(alert_ingest_handler),
+        )
+        .with_state(HttpAppState {
+            metrics,
+            state,
+            snapshot,
+            logs,
+            hosts,
+            ingest,
+            alert_api,
+            status_page,
+            api_token: api_token.map(Arc::from),
+        })
 }
 
 async fn healthz() -> impl IntoResponse {
     (StatusCode::OK, "ok")
 }
 
-async fn metrics_handler(State(state): State<HttpAppState>) -> Response {
+async fn readyz(State(state): State<HttpAppState>) -> impl IntoResponse {
+    let guard = state.state.read().await;
+    if guard.is_ready() {
+        (StatusCode::OK, "ready")
+    } else if guard.collector_stale {
+        (StatusCode::SERVICE_UNAVAILABLE, "collector stale")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+async fn metrics_handler(State(state): State<HttpAppState>, headers: HeaderMap) -> Response {
     state.metrics.inc_scrape_count();
+    let openmetrics = headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(openmetrics::accepts);
+    if openmetrics {
+        let mut response = Response::new(Body::from(state.metrics.encode_openmetrics()));
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(openmetrics::CONTENT_TYPE),
+        );
+        return response;
+    }
     match state.metrics.encode_metrics() {
         Ok(encoded) => {
             let mut response = Response::new(Body::from(encoded));
@@ -99,14 +200,415 @@ async fn metrics_handler(State(state): State<HttpAppState>) -> Response {
     }
 }
 
-async fn state_handler(State(state): State<HttpAppState>) -> impl IntoResponse {
+async fn state_handler(State(state): State<HttpAppState>, headers: HeaderMap) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    Json(&**state.snapshot.load()).into_response()
+}
+
+async fn checks_handler(State(state): State<HttpAppState>, headers: HeaderMap) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    let snapshot = state.snapshot.load();
+    let checks = snapshot
+        .check_runs()
+        .into_iter()
+        .map(|(kind, name, up, latency_ms)| {
+            let latency = snapshot.check_latency_for(kind, name);
+            CheckEntry {
+                kind,
+                name,
+                up,
+                latency_ms,
+                samples: latency.map_or(0, |l| l.samples),
+                p50_ms: latency.map(|l| l.p50_ms),
+                p95_ms: latency.map(|l| l.p95_ms),
+                p99_ms: latency.map(|l| l.p99_ms),
+            }
+        })
+        .collect::<Vec<_>>();
+    Json(checks).into_response()
+}
+
+async fn status_page_handler(
+    State(state): State<HttpAppState>,
+    Query(query): Query<StatusPageQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(page) = state.status_page.as_ref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Из браузера токен удобнее передать в ссылке, из скриптов — заголовком.
+    if let Some(token) = page.token.as_deref() {
+        let in_query = query
+            .token
+            .as_deref()
+            .is_some_and(|given| token_matches(given, token));
+        if !bearer_matches(&headers, token) && !in_query {
+            return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+        }
+    }
+    let rendered = {
+        let guard = state.state.read().await;
+        status_page::render(&page.title, &guard, now_unix())
+    };
+    match rendered {
+        Ok(html) => Html(html).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("ошибка отрисовки страницы: {err}"),
+        )
+            .into_response(),
+    }
+}
+
+async fn logs_handler(
+    State(state): State<HttpAppState>,
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    Json::<Vec<LogEntry>>(state.logs.tail(query.limit.unwrap_or(100))).into_response()
+}
+
+async fn hosts_handler(State(state): State<HttpAppState>, headers: HeaderMap) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    Json::<Vec<HostState>>(state.hosts.read().await.clone()).into_response()
+}
+
+async fn ingest_handler(
+    State(state): State<HttpAppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(ingest) = state.ingest.as_ref() else {
+        return (StatusCode::NOT_FOUND, "приём состояний не включён").into_response();
+    };
+    if !bearer_matches(&headers, &ingest.token) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    let payload = match parse_json::<IngestPayload>(&body) {
+        Ok(payload) => payload,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let now = now_unix();
+    let mut hosts = state.hosts.write().await;
+    match record_push(
+        &mut hosts,
+        &payload.host,
+        payload.state,
+        now,
+        &ingest.config,
+    ) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(IngestError::InvalidName) => (
+            StatusCode::BAD_REQUEST,
+            "имя хоста должно состоять из 1..32 символов [A-Za-z0-9._-]",
+        )
+            .into_response(),
+        Err(IngestError::PolledHost) => (
+            StatusCode::CONFLICT,
+            "хост с таким именем опрашивается агрегатором",
+        )
+            .into_response(),
+        Err(IngestError::NotAllowed) => (
+            StatusCode::FORBIDDEN,
+            "имя хоста не входит в aggregator.ingest.allowed_hosts",
+        )
+            .into_response(),
+        Err(IngestError::TooManyHosts) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            "достигнут предел aggregator.ingest.max_hosts",
+        )
+            .into_response(),
+    }
+}
+
+async fn alerts_handler(State(state): State<HttpAppState>, headers: HeaderMap) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    let mut alerts = state
+        .state
+        .read()
+        .await
+        .external_alerts
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    alerts.sort_by(|a, b| a.name.cmp(&b.name));
+    Json::<Vec<ActiveExternalAlert>>(alerts).into_response()
+}
+
+async fn alert_ingest_handler(
+    State(state): State<HttpAppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(api) = state.alert_api.as_ref() else {
+        return (StatusCode::NOT_FOUND, "приём уведомлений не включён").into_response();
+    };
+    if !bearer_matches(&headers, &api.token) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    let request = match parse_json::<ExternalAlertRequest>(&body) {
+        Ok(request) => request,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let now = now_unix();
+    let mut guard = state.state.write().await;
+    match apply_external_alert(&mut guard, request, api.default_ttl_secs, now) {
+        Ok(event) => {
+            let host = guard.host_name.clone();
+            drop(guard);
+            if let Some(alert) = event {
+                info!(
+                    name = %alert.name,
+                    severity = alert.severity.key(),
+                    resolved = alert.recovered,
+                    "получено внешнее уведомление"
+                );
+                api.bus
+                    .publish(Event::alert(now, host, Alert::External(alert)));
+            }
+            StatusCode::ACCEPTED.into_response()
+        }
+        Err(err) => (StatusCode::BAD_REQUEST, err).into_response(),
+    }
+}
+
+// Тело разбирается только после проверки токена, чтобы чужие запросы не стоили полного разбора.
+fn parse_json<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, (StatusCode, String)> {
+    serde_json::from_slice(body)
+        .map_err(|err| (StatusCode::BAD_REQUEST, format!("некорректный JSON: {err}")))
+}
+
+fn bearer_matches(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|v| token_matches(v, token))
+}
+
+// Сравниваются SHA-256 обоих значений без раннего выхода: время ответа не выдаёт ни совпавший префикс, ни длину.
+fn token_matches(given: &str, expected: &str) -> bool {
+    let given = digest::digest(&digest::SHA256, given.as_bytes());
+    let expected = digest::digest(&digest::SHA256, expected.as_bytes());
+    let diff = given
+        .as_ref()
+        .iter()
+        .zip(expected.as_ref())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+fn api_authorized(state: &HttpAppState, headers: &HeaderMap) -> bool {
+    state
+        .api_token
+        .as_deref()
+        .is_none_or(|token| bearer_matches(headers, token))
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+const HISTORY_EXPORT_CHUNK_ROWS: usize = 1000;
+const HISTORY_CSV_HEADER: &str = "ts_unix,metric,subject,value\n";
+
+async fn history_export_handler(
+    State(state): State<HttpAppState>,
+    Query(query): Query<HistoryExportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    if !HISTORY_METRICS.contains(&query.metric.as_str()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "неизвестная метрика '{}' (допустимо: {})",
+                query.metric,
+                HISTORY_METRICS.join(", ")
+            ),
+        )
+            .into_response();
+    }
+    let from = query.from.unwrap_or(i64::MIN);
+    let to = query.to.unwrap_or(i64::MAX);
+    if from > to {
+        return (
+            StatusCode::BAD_REQUEST,
+            "параметр from должен быть не больше to",
+        )
+            .into_response();
+    }
+    let csv = match query.format.as_deref().unwrap_or("json") {
+        "json" => false,
+        "csv" => true,
+        other => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("неизвестный формат '{other}' (допустимо: json, csv)"),
+            )
+                .into_response()
+        }
+    };
+    let disposition =
+        HeaderValue::from_str(&format!("attachment; filename=\"{}.csv\"", query.metric)).ok();
+    let shared = state.state.clone();
+    // Строки отдаются страницами по HISTORY_EXPORT_CHUNK_ROWS: блокировка состояния берётся на время
+    // одной страницы, а вся выгрузка целиком в памяти не собирается.
+    let chunks = stream::unfold(Some(None), move |page: Option<Option<(i64, String)>>| {
+        let shared = shared.clone();
+        let metric = query.metric.clone();
+        let subject = query.subject.clone();
+        async move {
+            let after = page?;
+            let rows = shared.read().await.export_metric_history(
+                &metric,
+                subject.as_deref(),
+                from,
+                to,
+                after.as_ref(),
+                HISTORY_EXPORT_CHUNK_ROWS,
+            );
+            let done = rows.len() < HISTORY_EXPORT_CHUNK_ROWS;
+            let mut chunk = String::new();
+            if after.is_none() {
+                chunk.push_str(if csv { HISTORY_CSV_HEADER } else { "[" });
+            }
+            for (idx, row) in rows.iter().enumerate() {
+                if csv {
+                    history_csv_row(&mut chunk, row);
+                    continue;
+                }
+                if after.is_some() || idx > 0 {
+                    chunk.push(',');
+                }
+                if let Ok(json) = serde_json::to_string(row) {
+                    chunk.push_str(&json);
+                }
+            }
+            if done && !csv {
+                chunk.push(']');
+            }
+            let next = rows.last().map(|row| (row.ts_unix, row.subject.clone()));
+            Some((Ok::<_, Infallible>(chunk), (!done).then_some(next)))
+        }
+    });
+    let mut response = Response::new(Body::from_stream(chunks));
+    let headers = response.headers_mut();
+    if csv {
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/csv; charset=utf-8"),
+        );
+        if let Some(value) = disposition {
+            headers.insert(CONTENT_DISPOSITION, value);
+        }
+    } else {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    response
+}
+
+async fn speedtest_history_handler(
+    State(state): State<HttpAppState>,
+    Query(query): Query<SpeedtestHistoryQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !api_authorized(&state, &headers) {
+        return (StatusCode::UNAUTHORIZED, "неверный токен").into_response();
+    }
+    let from = query.from.unwrap_or(i64::MIN);
+    let to = query.to.unwrap_or(i64::MAX);
+    if from > to {
+        return (
+            StatusCode::BAD_REQUEST,
+            "параметр from должен быть не больше to",
+        )
+            .into_response();
+    }
     let guard = state.state.read().await;
-    Json(ApiState::from(&*guard))
+    let samples = guard.speedtest_range(from, to);
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => {
+            let now = now_unix();
+            let stats = SPEEDTEST_STATS_WINDOWS_HOURS
+                .iter()
+                .filter_map(|hours| guard.speedtest_stats(now, *hours))
+                .collect();
+            Json(SpeedtestHistoryResponse { samples, stats }).into_response()
+        }
+        "csv" => {
+            let mut response = Response::new(Body::from(speedtest_csv(&samples)));
+            let headers = response.headers_mut();
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            );
+            headers.insert(
+                CONTENT_DISPOSITION,
+                HeaderValue::from_static("attachment; filename=\"speedtest.csv\""),
+            );
+            response
+        }
+        other => (
+            StatusCode::BAD_REQUEST,
+            format!("неизвестный формат '{other}' (допустимо: json, csv)"),
+        )
+            .into_response(),
+    }
+}
+
+fn speedtest_csv(samples: &[InternetSpeedStat]) -> String {
+    let mut out = String::from("ts_unix,download_mbps,upload_mbps,latency_ms\n");
+    for sample in samples {
+        out.push_str(&format!(
+            "{},{},{},{}\n",
+            sample.measured_at_unix,
+            sample.download_mbps,
+            sample.upload_mbps,
+            sample.latency_ms.map(|v| v.to_string()).unwrap_or_default()
+        ));
+    }
+    out
+}
+
+fn history_csv_row(out: &mut String, row: &HistoryRow) {
+    out.push_str(&format!(
+        "{},{},{},{}\n",
+        row.ts_unix,
+        row.metric,
+        csv_field(&row.subject),
+        row.value
+    ));
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::TempStat;
     use axum::body::to_bytes;
     use axum::http::Request;
     use tower::ServiceExt;
@@ -115,7 +617,17 @@ mod tests {
     async fn healthz_returns_ok() {
         let metrics = Metrics::new().expect("инициализация метрик");
         let state = Arc::new(RwLock::new(crate::state::State::new(0)));
-        let app = build_router(metrics, state);
+        let app = build_router(
+            metrics,
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
 
         let response = app
             .oneshot(
@@ -132,13 +644,56 @@ mod tests {
         assert_eq!(bytes.as_ref(), b"ok");
     }
 
+    #[tokio::test]
+    async fn readyz_reports_stale_collector() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        let app = build_router(
+            metrics,
+            state.clone(),
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let status = |app: Router| async move {
+            app.oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        };
+
+        assert_eq!(status(app.clone()).await, StatusCode::SERVICE_UNAVAILABLE);
+        state.write().await.last_collect_timestamp_seconds = 10;
+        assert_eq!(status(app.clone()).await, StatusCode::OK);
+        state.write().await.collector_stale = true;
+        assert_eq!(status(app).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
     #[tokio::test]
     async fn metrics_contains_uptime() {
         let metrics = Metrics::new().expect("инициализация метрик");
         let state = Arc::new(RwLock::new(crate::state::State::new(0)));
-        let app = build_router(metrics.clone(), state);
-        let snapshot_state = crate::state::State::new(0);
-        metrics.update_from_state(&snapshot_state);
+        let app = build_router(
+            metrics.clone(),
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        metrics.update_from_snapshot(&crate::state::State::new(0).snapshot());
 
         let response = app
             .oneshot(
@@ -156,11 +711,156 @@ mod tests {
         assert!(text.contains("agent_uptime_seconds"));
     }
 
+    #[tokio::test]
+    async fn metrics_negotiates_openmetrics_with_exemplars() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        let app = build_router(
+            metrics.clone(),
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        metrics.set_exemplars(true);
+        let mut snapshot = crate::state::State::new(0).snapshot();
+        snapshot.last_collect_timestamp_seconds = 1_700_000_000;
+        Arc::make_mut(&mut snapshot.checks).http = vec![crate::state::HttpCheckResult {
+            name: "api".to_string(),
+            up: false,
+            latency_ms: 300,
+            status_code: 503,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
+        }];
+        metrics.update_from_snapshot(&snapshot);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .header(ACCEPT, "application/openmetrics-text; version=1.0.0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[CONTENT_TYPE], openmetrics::CONTENT_TYPE);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.contains("# TYPE agent_scrape_count counter\n"));
+        assert!(text.contains(
+            "agent_check_latency_seconds_bucket{check=\"api\",kind=\"http\",le=\"0.5\"} 1.0 # {up=\"0\",status_code=\"503\"} 0.3 1700000000.0\n"
+        ));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn openmetrics_created_is_the_first_time_a_series_was_seen() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        metrics.inc_collect_error("cpu");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        metrics.inc_collect_error("disk");
+        metrics.inc_collect_error("cpu");
+        let mut snapshot = crate::state::State::new(0).snapshot();
+        Arc::make_mut(&mut snapshot.checks).http = vec![crate::state::HttpCheckResult {
+            name: "api".to_string(),
+            up: true,
+            latency_ms: 5,
+            status_code: 200,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
+        }];
+        metrics.update_from_snapshot(&snapshot);
+
+        let text = String::from_utf8(metrics.encode_openmetrics().to_vec()).unwrap();
+        let created = |series: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or_else(|| panic!("нет {series} в выдаче"))
+        };
+        let cpu = created("agent_collect_errors_created{collector=\"cpu\"}");
+        let disk = created("agent_collect_errors_created{collector=\"disk\"}");
+        assert!(
+            disk > cpu,
+            "повторный инкремент не должен сдвигать _created"
+        );
+        assert!(
+            created("agent_check_latency_seconds_created{check=\"api\",kind=\"http\"}") >= disk
+        );
+        assert!(created("agent_host_boots_created") <= cpu);
+    }
+
+    #[test]
+    fn encoded_metrics_are_cached_until_values_change() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        metrics.encode_metrics().unwrap();
+        // Значение, изменённое в обход update_*, не видно, пока кэш не сброшен; счётчик опросов — сразу.
+        metrics.agent_uptime_seconds.set(7.0);
+        metrics.inc_scrape_count();
+        let text = String::from_utf8(metrics.encode_metrics().unwrap().to_vec()).unwrap();
+        assert!(text.contains("agent_uptime_seconds 0"));
+        assert!(text.contains("agent_scrape_count_total 1"));
+        metrics.inc_scrape_count();
+        let text = String::from_utf8(metrics.encode_openmetrics().to_vec()).unwrap();
+        assert!(text.contains("agent_scrape_count_total 2.0\nagent_scrape_count_created "));
+        assert!(text.ends_with("# EOF\n"));
+
+        let mut state = crate::state::State::new(0);
+        state.cpu_usage_percent = 42.0;
+        metrics.update_from_snapshot(&state.snapshot());
+        let text = String::from_utf8(metrics.encode_metrics().unwrap().to_vec()).unwrap();
+        assert!(text.contains("agent_cpu_usage_percent 42"));
+        assert!(text.contains("agent_scrape_count_total 2"));
+    }
+
+    #[test]
+    fn vanished_series_are_removed_and_others_updated() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let disk = |mount: &str, used_bytes: u64| crate::state::DiskStat {
+            mount: mount.to_string(),
+            used_bytes,
+            total_bytes: 100,
+            removable: false,
+            device: None,
+            temperature_celsius: None,
+            read_only: false,
+            write_error: None,
+        };
+        let mut state = crate::state::State::new(0);
+        state.disks = vec![disk("/", 10), disk("/data", 20)].into();
+        metrics.update_from_snapshot(&state.snapshot());
+        state.disks = vec![disk("/", 30)].into();
+        metrics.update_from_snapshot(&state.snapshot());
+
+        let text = String::from_utf8(metrics.encode_metrics().unwrap().to_vec()).unwrap();
+        assert!(text.contains("agent_disk_used_bytes{mount=\"/\"} 30"));
+        assert!(!text.contains("mount=\"/data\""));
+    }
+
     #[tokio::test]
     async fn api_state_returns_json() {
         let metrics = Metrics::new().expect("инициализация метрик");
         let state = Arc::new(RwLock::new(crate::state::State::new(10)));
-        let app = build_router(metrics, state);
+        let app = build_router(
+            metrics,
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
 
         let response = app
             .oneshot(
@@ -177,4 +877,348 @@ mod tests {
         let text = String::from_utf8(bytes.to_vec()).unwrap();
         assert!(text.contains("\"cpu_usage_percent\""));
     }
+
+    #[tokio::test]
+    async fn history_export_returns_csv() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        {
+            let mut guard = state.write().await;
+            guard.temps = vec![TempStat {
+                sensor: "cpu, package".to_string(),
+                temperature_celsius: 55.5,
+                critical_temperature_celsius: None,
+                device: None,
+            }]
+            .into();
+            guard.record_metric_history(100, &crate::config::HistoryConfig::default());
+        }
+        let app = build_router(
+            metrics,
+            state.clone(),
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                "/api/history/export?metric=temperature_celsius&from=50&to=150&format=csv",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            bytes.as_ref(),
+            b"ts_unix,metric,subject,value\n100,temperature_celsius,\"cpu, package\",55.5\n"
+        );
+
+        // Выгрузка длиннее страницы склеивается в один корректный JSON-массив.
+        state
+            .write()
+            .await
+            .metric_history
+            .entry(("temperature_celsius", "gpu".to_string()))
+            .or_default()
+            .extend(
+                (0..HISTORY_EXPORT_CHUNK_ROWS as i64).map(|ts| crate::state::ResourceSample {
+                    ts_unix: 100 + ts,
+                    value: 40.0,
+                }),
+            );
+        let response = app
+            .clone()
+            .oneshot(request("/api/history/export?metric=temperature_celsius"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(rows.len(), HISTORY_EXPORT_CHUNK_ROWS + 1);
+        assert_eq!(rows[0]["subject"], "cpu, package");
+        assert_eq!(rows[1]["subject"], "gpu");
+        assert_eq!(rows[HISTORY_EXPORT_CHUNK_ROWS]["ts_unix"], 1099);
+
+        for uri in [
+            "/api/history/export?metric=unknown",
+            "/api/history/export?metric=temperature_celsius&format=xml",
+        ] {
+            let response = app.clone().oneshot(request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn api_logs_returns_tail() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        let logs = LogBuffer::new(10);
+        for i in 0..3 {
+            logs.push(LogEntry {
+                ts_unix: i,
+                level: "INFO".to_string(),
+                target: "monitord".to_string(),
+                message: format!("entry-{i}"),
+            });
+        }
+        let app = build_router(
+            metrics,
+            state,
+            SharedSnapshot::default(),
+            logs,
+            Hosts::default(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/logs?limit=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(!text.contains("entry-0"));
+        assert!(text.contains("entry-2"));
+    }
+
+    #[tokio::test]
+    async fn ingest_requires_token_and_registers_pushed_host() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        let hosts = Hosts::default();
+        let app = build_router(
+            metrics,
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            hosts.clone(),
+            Some(IngestApi {
+                token: Arc::from("fleet-secret"),
+                config: Arc::new(IngestConfig::default()),
+            }),
+            None,
+            None,
+            None,
+        );
+        let payload = |host: &str| {
+            serde_json::to_vec(&IngestPayload {
+                host: host.to_string(),
+                state: crate::state::State::new(5).snapshot(),
+            })
+            .unwrap()
+        };
+        let post = |token: &str, body: Vec<u8>| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/ingest")
+                .header(CONTENT_TYPE, "application/json")
+                .header(AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post("wrong", payload("nat-1")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // Токен проверяется до разбора тела: мусор без токена получает 401, а не ошибку схемы.
+        let response = app
+            .clone()
+            .oneshot(post("wrong", b"{\"host\": 1".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(post("fleet-secret", b"{\"host\": 1".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app
+            .clone()
+            .oneshot(post("fleet-secret", payload("bad name")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app
+            .oneshot(post("fleet-secret", payload("nat-1")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let guard = hosts.read().await;
+        assert_eq!(guard.len(), 1);
+        assert_eq!(guard[0].name, "nat-1");
+        assert!(guard[0].up);
+        assert_eq!(guard[0].state.as_ref().unwrap().started_at_unix, 5);
+    }
+
+    #[tokio::test]
+    async fn alert_api_publishes_external_alerts() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        let bus = EventBus::default();
+        let mut events = bus.subscribe();
+        let app = build_router(
+            metrics,
+            state,
+            SharedSnapshot::default(),
+            LogBuffer::default(),
+            Hosts::default(),
+            None,
+            Some(AlertApi {
+                token: Arc::from("alert-secret"),
+                default_ttl_secs: Some(3600),
+                bus,
+            }),
+            None,
+            None,
+        );
+        let post = |token: &str, body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/alerts")
+                .header(CONTENT_TYPE, "application/json")
+                .header(AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let body = r#"{"name":"nightly-backup","severity":"warning","message":"rsync exit 23"}"#;
+
+        let response = app.clone().oneshot(post("wrong", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(post("wrong", "{\"name\": [1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(post("alert-secret", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        match events.try_recv().unwrap() {
+            Event::AlertFired {
+                alert: Alert::External(alert),
+                ..
+            } => {
+                assert_eq!(alert.name, "nightly-backup");
+                assert_eq!(alert.message.as_deref(), Some("rsync exit 23"));
+            }
+            other => panic!("неожиданное событие: {other:?}"),
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/alerts")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let active: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(active[0]["name"], "nightly-backup");
+        assert!(active[0]["expires_at_unix"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn status_page_and_api_require_their_tokens() {
+        let metrics = Metrics::new().expect("инициализация метрик");
+        let state = Arc::new(RwLock::new(crate::state::State::new(0)));
+        {
+            let mut guard = state.write().await;
+            for (ts, up) in [(now_unix() - 120, false), (now_unix() - 60, true)] {
+                Arc::make_mut(&mut guard.checks).http = vec![crate::state::HttpCheckResult {
+                    name: "<api>".to_string(),
+                    up,
+                    latency_ms: 12,
+                    status_code: 200,
+                    error: None,
+                    affected_by_upstream: None,
+                    cached: false,
+                }];
+                guard.record_check_uptime(ts);
+            }
+        }
+        let page = |token: Option<&str>| {
+            build_router(
+                metrics.clone(),
+                state.clone(),
+                SharedSnapshot::default(),
+                LogBuffer::default(),
+                Hosts::default(),
+                None,
+                None,
+                token.map(|_| StatusPage {
+                    title: Arc::from("Статус"),
+                    token: token.map(Arc::from),
+                }),
+                Some("api-secret".to_string()),
+            )
+        };
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = page(None).oneshot(get("/status")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = page(Some("s3cret")).oneshot(get("/status")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = page(Some("s3cret"))
+            .oneshot(get("/status?token=s3cre"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // Токен страницы не открывает /api/*: у них свой токен api.
+        for uri in [
+            "/api/state",
+            "/api/logs",
+            "/api/history/export?metric=check_up",
+        ] {
+            for (token, status) in [
+                ("s3cret", StatusCode::UNAUTHORIZED),
+                ("api-secret", StatusCode::OK),
+            ] {
+                let request = Request::builder()
+                    .uri(uri)
+                    .header(AUTHORIZATION, format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = page(Some("s3cret")).oneshot(request).await.unwrap();
+                assert_eq!(response.status(), status, "{uri}");
+            }
+        }
+
+        let response = page(Some("s3cret"))
+            .oneshot(get("/status?token=s3cret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(html.contains("<b>&lt;api&gt;</b>"));
+        assert!(html.contains("50.00% доступности"));
+        assert!(html.contains("badge up"));
+        assert_eq!(html.matches(" title=\"").count(), 90);
+    }
 }
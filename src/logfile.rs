@@ -0,0 +1,150 @@
+use crate::config::{LogRotation, LoggingConfig};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+pub struct RotatingFile {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    period: i64,
+    max_bytes: u64,
+    rotation: LogRotation,
+    keep_files: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, cfg: &LoggingConfig) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                path: path.to_path_buf(),
+                file,
+                size,
+                period: period(cfg.rotation, now_unix()),
+                max_bytes: cfg.max_size_mb.saturating_mul(1024 * 1024),
+                rotation: cfg.rotation,
+                keep_files: cfg.keep_files,
+            }),
+        })
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.write(buf, now_unix())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.file.flush()
+    }
+}
+
+impl Inner {
+    fn write(&mut self, buf: &[u8], now: i64) -> io::Result<usize> {
+        let too_big = self.max_bytes > 0
+            && self.size > 0
+            && self.size.saturating_add(buf.len() as u64) > self.max_bytes;
+        if too_big || period(self.rotation, now) != self.period {
+            self.rotate(now)?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    // monitord.log -> monitord.log.1 -> ... -> monitord.log.<keep_files>, самый старый удаляется.
+    fn rotate(&mut self, now: i64) -> io::Result<()> {
+        self.file.flush()?;
+        let archive = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        let _ = fs::remove_file(archive(self.keep_files));
+        for n in (1..self.keep_files).rev() {
+            let from = archive(n);
+            if from.exists() {
+                fs::rename(&from, archive(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, archive(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.period = period(self.rotation, now);
+        Ok(())
+    }
+}
+
+fn period(rotation: LogRotation, now: i64) -> i64 {
+    match rotation {
+        LogRotation::Never => 0,
+        LogRotation::Hourly => now.div_euclid(3600),
+        LogRotation::Daily => now.div_euclid(86_400),
+    }
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_by_size_and_period_keeping_limited_archives() {
+        let dir = std::env::temp_dir().join(format!("monitord-logfile-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("monitord.log");
+        let cfg = LoggingConfig {
+            file: Some(path.display().to_string()),
+            rotation: LogRotation::Hourly,
+            keep_files: 2,
+            ..LoggingConfig::default()
+        };
+        let log = RotatingFile::open(&path, &cfg).unwrap();
+        let mut inner = log.inner.lock().unwrap();
+        inner.max_bytes = 10;
+        let hour = inner.period * 3600;
+
+        inner.write(b"first-line", hour).unwrap();
+        inner.write(b"second", hour).unwrap();
+        inner.write(b"third", hour + 3600).unwrap();
+        inner.write(b"fourth", hour + 3600).unwrap();
+
+        let read = |p: PathBuf| fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "fourth");
+        assert_eq!(read(dir.join("monitord.log.1")), "third");
+        assert_eq!(read(dir.join("monitord.log.2")), "second");
+        assert!(!dir.join("monitord.log.3").exists());
+        drop(inner);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
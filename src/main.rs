@@ -1,66 +1,419 @@
+mod actions;
+mod aggregator;
+mod alerts;
+mod check_feed;
 mod collectors;
 mod config;
+mod dir_sizes;
+mod discovery;
+mod events;
+mod external;
+mod heartbeat;
 mod http;
+mod logbuf;
+mod logfile;
 mod metrics;
+mod notify;
+mod openmetrics;
+mod pairing;
+mod push;
+mod relay;
+mod reload;
+mod remote;
+mod routing;
+mod rules;
+mod sensors;
+mod smtp;
+mod speedtest_log;
 mod state;
+mod status_page;
+mod systemd;
 mod telegram;
+mod units;
+mod uptime_log;
 
+use alerts::{attach_gpu_alert_context, record_collector_health, AlertEngine};
+use arc_swap::ArcSwap;
 use axum::serve;
-use clap::Parser;
-use collectors::checks::collect_checks;
-use collectors::system::collect_system;
-use config::Config;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use collectors::checks::{collect_checks, take_check_results, CheckRunner, SharedCheckSchedule};
+use collectors::plugins::{build_collectors, collect_plugins};
+use collectors::system::{collect_system, SystemCollector};
+use config::{Config, LogFormat, LoggingConfig, SpeedtestConfig, MAX_SPEEDTEST_UPLOAD_BYTES};
+use events::{next_event, Alert, Event, EventBus};
+use logbuf::LogBuffer;
+use logfile::RotatingFile;
 use metrics::Metrics;
+use notify::Notifier;
+use pairing::Pairing;
+use remote::RemoteConfig;
 use reqwest::Client;
-use state::{InternetSpeedStat, ResourceAlert, ResourceAlertKind, State};
-use std::collections::HashMap;
+use routing::Router;
+use speedtest_log::SpeedtestLog;
+use state::{InternetSpeedStat, SharedSnapshot, State, CHECK_UPTIME_DAYS};
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use sysinfo::SystemExt;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
 use teloxide::Bot;
 use tokio::net::TcpListener;
-use tokio::sync::{watch, RwLock};
+use tokio::sync::{broadcast, watch, RwLock};
 use tokio::time::MissedTickBehavior;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
+use units::Units;
+use uptime_log::UptimeLog;
 
 #[derive(Parser, Debug)]
 #[command(name = "monitord")]
 #[command(version)]
 struct Cli {
-    #[arg(long, default_value = "./config.yaml")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(long, global = true, default_value = "./config.yaml")]
     config: String,
-    #[arg(long)]
+    /// Заголовок запроса удалённой конфигурации, например 'Authorization: Bearer ...'
+    #[arg(
+        long,
+        global = true,
+        env = "MONITORD_CONFIG_HEADER",
+        hide_env_values = true
+    )]
+    config_header: Vec<String>,
+    /// Ожидаемый SHA-256 удалённой конфигурации
+    #[arg(long, global = true)]
+    config_sha256: Option<String>,
+    /// Период повторной загрузки удалённой конфигурации, например 5m
+    #[arg(long, global = true, value_parser = humantime::parse_duration)]
+    config_refresh: Option<Duration>,
+    #[arg(long, hide = true)]
     print_default_config: bool,
-    #[arg(long, conflicts_with = "telegram_off")]
+    /// Проверить конфигурацию, Telegram и адрес listen и выйти
+    #[arg(long)]
+    dry_run: bool,
+    /// При --dry-run отправить тестовое сообщение в каждый разрешённый чат
+    #[arg(long, requires = "dry_run")]
+    send_test_message: bool,
+    #[arg(long, global = true, conflicts_with = "telegram_off")]
     telegram_on: bool,
-    #[arg(long, conflicts_with = "telegram_on")]
+    #[arg(long, global = true, conflicts_with = "telegram_on")]
     telegram_off: bool,
 }
 
+#[derive(Subcommand, Debug, Clone, Copy, Default)]
+enum Command {
+    /// Запустить агент (по умолчанию)
+    #[default]
+    Run,
+    /// Проверить конфигурацию и вывести итоговый конфиг с учётом include
+    CheckConfig,
+    /// Вывести шаблон конфигурации
+    PrintDefaultConfig,
+    /// Выполнить один сбор и вывести состояние
+    Collect {
+        /// Формат вывода
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+        /// Замерить скорость интернета
+        #[arg(long)]
+        speedtest: bool,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum OutputFormat {
+    Json,
+    Yaml,
+}
+
 #[tokio::main]
 async fn main() {
-    init_tracing();
-
     let cli = Cli::parse();
-    if cli.print_default_config {
-        println!("{}", Config::example_yaml());
-        return;
+    let command = if cli.print_default_config {
+        Command::PrintDefaultConfig
+    } else {
+        cli.command.unwrap_or_default()
+    };
+    if cli.dry_run && !matches!(command, Command::Run) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--dry-run применим только к run",
+            )
+            .exit();
     }
+    match command {
+        Command::Run if cli.dry_run => dry_run(&cli).await,
+        Command::Run => run(cli).await,
+        Command::CheckConfig => check_config(&cli).await,
+        Command::PrintDefaultConfig => println!("{}", Config::example_yaml()),
+        Command::Collect { format, speedtest } => collect_once(&cli, format, speedtest).await,
+    }
+}
 
-    let mut cfg = match Config::load_from_file(&cli.config) {
-        Ok(cfg) => cfg,
-        Err(err) => {
-            error!(error = %err, "РЅРµ СѓРґР°Р»РѕСЃСЊ Р·Р°РіСЂСѓР·РёС‚СЊ РєРѕРЅС„РёРіСѓСЂР°С†РёСЋ");
-            std::process::exit(1);
-        }
+fn remote_config(cli: &Cli) -> Result<Option<RemoteConfig>, config::ConfigError> {
+    if !remote::is_remote(&cli.config) {
+        return Ok(None);
+    }
+    RemoteConfig::new(
+        &cli.config,
+        &cli.config_header,
+        cli.config_sha256.as_deref(),
+    )
+    .map(Some)
+}
+
+fn config_client() -> Client {
+    Client::builder()
+        .user_agent("monitord/0.1.0")
+        .build()
+        .unwrap_or_else(|_| Client::new())
+}
+
+async fn load_config(cli: &Cli) -> Result<Config, config::ConfigError> {
+    let mut cfg = match remote_config(cli)? {
+        Some(remote) => remote.fetch(&config_client()).await?,
+        None => Config::load_from_file(&cli.config)?,
     };
     if cli.telegram_on {
         cfg.telegram.enabled = true;
     } else if cli.telegram_off {
         cfg.telegram.enabled = false;
     }
+    Ok(cfg)
+}
+
+async fn check_config(cli: &Cli) {
+    let cfg = match load_config(cli).await {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    if cfg.telegram.enabled {
+        if let Err(err) = ensure_telegram_settings(&cfg) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+    match serde_yaml::to_string(&cfg.redacted()) {
+        Ok(yaml) => print!("{yaml}"),
+        Err(err) => {
+            eprintln!("не удалось вывести конфигурацию: {err}");
+            std::process::exit(1);
+        }
+    }
+    eprintln!("конфигурация {} корректна", cli.config);
+}
+
+async fn dry_run(cli: &Cli) {
+    let mut failed = 0;
+    let mut report = |ok: bool, line: String| {
+        println!("{} {line}", if ok { "✓" } else { "✗" });
+        if !ok {
+            failed += 1;
+        }
+    };
+
+    let cfg = match load_config(cli).await {
+        Ok(cfg) => {
+            report(true, format!("конфигурация {} корректна", cli.config));
+            cfg
+        }
+        Err(err) => {
+            report(false, err.to_string());
+            std::process::exit(1);
+        }
+    };
+
+    let probe = Path::new(&cfg.data_dir).join(".monitord-dry-run");
+    match std::fs::create_dir_all(&cfg.data_dir).and_then(|_| std::fs::write(&probe, b"")) {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            report(
+                true,
+                format!("каталог data_dir {} доступен для записи", cfg.data_dir),
+            );
+        }
+        Err(err) => report(false, format!("каталог data_dir {}: {err}", cfg.data_dir)),
+    }
+
+    match TcpListener::bind(&cfg.listen).await {
+        Ok(listener) => {
+            drop(listener);
+            report(true, format!("адрес {} свободен", cfg.listen));
+        }
+        Err(err) => report(
+            false,
+            format!("не удалось занять адрес {}: {err}", cfg.listen),
+        ),
+    }
+
+    let mut bots = Vec::new();
+    if cfg.telegram.enabled {
+        match ensure_telegram_settings(&cfg) {
+            Ok(token) => {
+                let mut chats = cfg.telegram.allowed_chat_ids.clone();
+                if cfg.telegram.pairing_enabled {
+                    chats.extend(Pairing::new(&cfg.data_dir).load().unwrap_or_default());
+                }
+                bots.push(("telegram".to_string(), token, chats));
+            }
+            Err(err) => report(false, format!("Telegram: {err}")),
+        }
+    } else {
+        println!("– Telegram выключен");
+    }
+    for target in &cfg.notifiers.telegram {
+        match target.resolve_token() {
+            Some(token) => bots.push((target.name.clone(), token, target.chat_ids.clone())),
+            None => report(
+                false,
+                format!("notifiers.telegram '{}': не найден токен бота", target.name),
+            ),
+        }
+    }
+
+    let host = sysinfo::System::new().host_name();
+    for (name, token, chats) in bots {
+        let bot = Bot::new(token);
+        match bot.get_me().await {
+            Ok(me) => report(
+                true,
+                format!("{name}: токен принят, бот @{}", me.username()),
+            ),
+            Err(err) => {
+                report(false, format!("{name}: Telegram не принял токен: {err}"));
+                continue;
+            }
+        }
+        if !cli.send_test_message {
+            continue;
+        }
+        let text = match &host {
+            Some(host) => format!("🧪 Тестовое сообщение monitord с {host}"),
+            None => "🧪 Тестовое сообщение monitord".to_string(),
+        };
+        for chat_id in chats {
+            match bot.send_message(ChatId(chat_id), text.clone()).await {
+                Ok(_) => report(
+                    true,
+                    format!("{name}: сообщение в чат {chat_id} доставлено"),
+                ),
+                Err(err) => report(false, format!("{name}: чат {chat_id}: {err}")),
+            }
+        }
+    }
+
+    if failed > 0 {
+        println!("проверка завершена: ошибок — {failed}");
+        std::process::exit(1);
+    }
+    println!("проверка завершена успешно");
+}
+
+async fn collect_once(cli: &Cli, format: OutputFormat, speedtest: bool) {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+    let cfg = match load_config(cli).await {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let client = Client::builder()
+        .user_agent("monitord/0.1.0")
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    let mut system = sysinfo::System::new_all();
+    // Загрузка CPU считается по разнице двух замеров.
+    collect_system(&mut system, &cfg);
+    tokio::time::sleep(sysinfo::System::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    let mut snapshot = collect_system(&mut system, &cfg);
+    let (plugin_sensors, plugin_errors) = collect_plugins(&build_collectors(&cfg.plugins)).await;
+    snapshot.sensors.extend(plugin_sensors);
+    for (plugin, err) in plugin_errors {
+        eprintln!("плагин '{plugin}' не вернул данные: {err}");
+    }
+    let now = now_unix();
+    let (checks, _) = collect_checks(&cfg, &SharedCheckSchedule::default(), now).await;
+    let internet = if speedtest {
+        match collect_internet_speed(&client, &cfg.speedtest).await {
+            Ok(stat) => Some(stat),
+            Err(err) => {
+                eprintln!("не удалось замерить скорость интернета: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut state = State::new(now);
+    state.update_collected(
+        now,
+        snapshot.host_name,
+        snapshot.os_name,
+        snapshot.os_version,
+        snapshot.kernel_version,
+        snapshot.cpu_brand,
+        snapshot.uptime_seconds,
+        snapshot.process_count,
+        snapshot.cpu_core_count,
+        snapshot.cpu_usage_percent,
+        snapshot.cpu_steal,
+        snapshot.virtualization,
+        snapshot.time_sync,
+        snapshot.cgroup,
+        snapshot.memory_used_bytes,
+        snapshot.memory_total_bytes,
+        snapshot.page_file,
+        snapshot.disks,
+        snapshot.disk_io,
+        snapshot.net,
+        snapshot.wireguard,
+        internet,
+        snapshot.temps,
+        snapshot.gpus,
+        snapshot.sensors,
+        checks,
+    );
+    state.cpu_temperature_celsius = sensors::cpu_temperature(&state.temps, &cfg.sensors);
+    let api_state = state.snapshot();
+    let output = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(&api_state).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(&api_state).map_err(|e| e.to_string()),
+    };
+    match output {
+        Ok(text) => println!("{}", text.trim_end()),
+        Err(err) => {
+            eprintln!("не удалось сериализовать состояние: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run(cli: Cli) {
+    let cfg = match load_config(&cli).await {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            init_tracing(&LoggingConfig::default());
+            error!(error = %err, "РЅРµ СѓРґР°Р»РѕСЃСЊ Р·Р°РіСЂСѓР·РёС‚СЊ РєРѕРЅС„РёРіСѓСЂР°С†РёСЋ");
+            std::process::exit(1);
+        }
+    };
+    let log_buffer = init_tracing(&cfg.logging);
 
     let telegram_token = if cfg.telegram.enabled {
         match ensure_telegram_settings(&cfg) {
@@ -82,6 +435,38 @@ async fn main() {
 
     let now = now_unix();
     let shared_state = Arc::new(RwLock::new(State::new(now)));
+    let snapshot: SharedSnapshot = Arc::new(ArcSwap::from_pointee(State::new(now).snapshot()));
+    let pairing = if cfg.telegram.enabled && cfg.telegram.pairing_enabled {
+        let pairing = Arc::new(Pairing::new(&cfg.data_dir));
+        match pairing.load() {
+            Ok(chats) => shared_state.write().await.paired_chat_ids = chats,
+            Err(err) => {
+                error!(error = %err, "не удалось прочитать привязанные Telegram-чаты");
+            }
+        }
+        Some(pairing)
+    } else {
+        None
+    };
+    let speedtest_log = Arc::new(SpeedtestLog::new(&cfg.data_dir));
+    let speedtest_retention_days = cfg.history.speedtest_retention_days;
+    match speedtest_log.load(now - (speedtest_retention_days * 86_400) as i64) {
+        Ok(samples) => {
+            let mut state = shared_state.write().await;
+            for sample in samples {
+                state.record_speedtest(sample, speedtest_retention_days);
+            }
+            if let Err(err) = speedtest_log.rewrite(&state.speedtest_history) {
+                warn!(error = %err, "не удалось сжать историю speedtest");
+            }
+        }
+        Err(err) => error!(error = %err, "не удалось прочитать историю speedtest"),
+    }
+    let uptime_log = Arc::new(UptimeLog::new(&cfg.data_dir));
+    match uptime_log.load(now.div_euclid(86_400) - CHECK_UPTIME_DAYS + 1) {
+        Ok(days) => shared_state.write().await.restore_check_uptime(days),
+        Err(err) => error!(error = %err, "не удалось прочитать историю доступности проверок"),
+    }
     let metrics = match Metrics::new() {
         Ok(m) => m,
         Err(err) => {
@@ -89,16 +474,90 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    metrics.set_exemplars(cfg.metrics.exemplars);
+
+    let ingest = if cfg.aggregator.ingest.enabled() {
+        match cfg.aggregator.ingest.resolve_token() {
+            Some(token) => Some(http::IngestApi {
+                token: token.into(),
+                config: Arc::new(cfg.aggregator.ingest.clone()),
+            }),
+            None => {
+                error!("не найден токен aggregator.ingest (token или token_env)");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let bus = EventBus::default();
+    let alert_api = if cfg.alert_api.enabled() {
+        match cfg.alert_api.resolve_token() {
+            Some(token) => Some(http::AlertApi {
+                token: token.into(),
+                default_ttl_secs: cfg.alert_api.default_ttl_secs,
+                bus: bus.clone(),
+            }),
+            None => {
+                error!("не найден токен alert_api (token или token_env)");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let status_page = if cfg.status_page.enabled {
+        let token = cfg.status_page.resolve_token();
+        if cfg.status_page.protected() && token.is_none() {
+            error!("не найден токен status_page (token или token_env)");
+            std::process::exit(1);
+        }
+        let title = cfg
+            .status_page
+            .title
+            .clone()
+            .or_else(|| snapshot.load().host_name.clone())
+            .unwrap_or_else(|| "monitord".to_string());
+        Some(http::StatusPage {
+            title: title.into(),
+            token: token.map(Into::into),
+        })
+    } else {
+        None
+    };
+
+    let api_token = cfg.api.resolve_token();
+    if cfg.api.protected() && api_token.is_none() {
+        error!("не найден токен api (token или token_env)");
+        std::process::exit(1);
+    }
 
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let hosts = aggregator::Hosts::default();
+    let discovered = aggregator::Discovered::default();
 
     let http_task = {
         let cfg = cfg.clone();
         let metrics = metrics.clone();
         let http_state = shared_state.clone();
+        let http_snapshot = snapshot.clone();
+        let log_buffer = log_buffer.clone();
+        let hosts = hosts.clone();
         let mut shutdown_rx = shutdown_rx.clone();
         tokio::spawn(async move {
-            let app = http::build_router(metrics, http_state);
+            let app = http::build_router(
+                metrics,
+                http_state,
+                http_snapshot,
+                log_buffer,
+                hosts,
+                ingest,
+                alert_api,
+                status_page,
+                api_token,
+            );
             let addr: SocketAddr = match cfg.listen.parse() {
                 Ok(addr) => addr,
                 Err(err) => {
@@ -115,6 +574,8 @@ async fn main() {
                 }
             };
 
+            systemd::notify("READY=1");
+
             let server = serve(listener, app).with_graceful_shutdown(async move {
                 let _ = shutdown_rx.changed().await;
             });
@@ -131,12 +592,35 @@ async fn main() {
         None
     };
 
+    let speed_history = telegram::SpeedHistory::default();
+    let bot_health = telegram::BotHealth::new(metrics.clone());
     let telegram_task = if let Some(bot) = telegram_bot.clone() {
         let telegram_cfg = cfg.telegram.clone();
         let state = shared_state.clone();
+        let snapshot = snapshot.clone();
+        let log_buffer = log_buffer.clone();
+        let pairing = pairing.clone();
+        let hosts = hosts.clone();
+        let speed_history = speed_history.clone();
+        let units = Units::from(&cfg.display);
+        let health = bot_health.clone();
         let shutdown = shutdown_rx.clone();
         Some(tokio::spawn(async move {
-            if let Err(err) = telegram::run_bot(bot, telegram_cfg, state, shutdown).await {
+            if let Err(err) = telegram::run_bot(
+                bot,
+                telegram_cfg,
+                state,
+                snapshot,
+                log_buffer,
+                pairing,
+                hosts,
+                speed_history,
+                units,
+                health,
+                shutdown,
+            )
+            .await
+            {
                 error!(error = %err, "РѕС€РёР±РєР° Р·Р°РґР°С‡Рё Telegram");
             }
         }))
@@ -144,52 +628,276 @@ async fn main() {
         None
     };
 
+    let (config_tx, config_rx) = watch::channel(Arc::new(cfg.clone()));
+    let router = Router::new(cfg.routes.clone());
+    let mut subscriber_tasks = vec![tokio::spawn(record_event_metrics(
+        metrics.clone(),
+        bus.subscribe(),
+    ))];
+    if let Some(notifier) = Notifier::new(&cfg.notifiers, router.clone(), metrics.clone()) {
+        subscriber_tasks.push(tokio::spawn(notifier.run(bus.subscribe())));
+    }
+    if let Some(bot) = telegram_bot.clone() {
+        subscriber_tasks.push(tokio::spawn(telegram::run_alert_delivery(
+            bot,
+            cfg.telegram.clone(),
+            Units::from(&cfg.display),
+            shared_state.clone(),
+            router.clone(),
+            metrics.clone(),
+            bot_health.clone(),
+            bus.subscribe(),
+        )));
+        subscriber_tasks.push(tokio::spawn(telegram::run_speed_history(
+            speed_history.clone(),
+            bus.subscribe(),
+        )));
+    }
+
+    let fleet_client = Client::builder()
+        .user_agent("monitord/0.1.0")
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    subscriber_tasks.push(tokio::spawn(check_feed::run(
+        fleet_client.clone(),
+        config_rx.clone(),
+        bus.subscribe(),
+    )));
+    let aggregator_task = tokio::spawn(aggregator::run(
+        fleet_client.clone(),
+        hosts.clone(),
+        discovered.clone(),
+        metrics.clone(),
+        bus.clone(),
+        config_rx.clone(),
+        shutdown_rx.clone(),
+    ));
+    let push_task = tokio::spawn(push::run(
+        fleet_client.clone(),
+        snapshot.clone(),
+        config_rx.clone(),
+        shutdown_rx.clone(),
+    ));
+    let heartbeat_task = tokio::spawn(heartbeat::run(
+        fleet_client.clone(),
+        snapshot.clone(),
+        config_rx.clone(),
+        shutdown_rx.clone(),
+    ));
+    let dir_sizes_task = tokio::spawn(dir_sizes::run(
+        shared_state.clone(),
+        config_rx.clone(),
+        shutdown_rx.clone(),
+    ));
+    let discovery_task = tokio::spawn(discovery::run(
+        cfg.discovery.clone(),
+        cfg.listen.clone(),
+        discovery_instance(&cfg),
+        discovered,
+        shutdown_rx.clone(),
+    ));
+    let relay_task = tokio::spawn(relay::run(
+        fleet_client,
+        metrics.clone(),
+        config_rx.clone(),
+        shutdown_rx.clone(),
+    ));
+
+    let systemd_watchdog_task = systemd::watchdog_interval().map(|interval| {
+        tokio::spawn(systemd::run_watchdog(
+            interval,
+            shared_state.clone(),
+            config_rx.clone(),
+            shutdown_rx.clone(),
+        ))
+    });
+
+    let watchdog_task = {
+        let config_rx = config_rx.clone();
+        let bus = bus.clone();
+        let shared_state = shared_state.clone();
+        let mut shutdown = shutdown_rx.clone();
+        tokio::spawn(async move {
+            let period =
+                Duration::from_secs((config_rx.borrow().collector_stale_secs / 4).clamp(1, 30));
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    _ = ticker.tick() => {
+                        let now = now_unix();
+                        let stale_secs = config_rx.borrow().collector_stale_secs;
+                        let (age, changed) = {
+                            let mut guard = shared_state.write().await;
+                            let age = guard.collection_age_secs(now);
+                            let stale = age > stale_secs as i64;
+                            let changed = stale != guard.collector_stale;
+                            guard.collector_stale = stale;
+                            (age, changed.then_some(stale))
+                        };
+                        let Some(stale) = changed else {
+                            continue;
+                        };
+                        if stale {
+                            error!(age_secs = age, "сбор данных не обновлялся дольше collector_stale_secs");
+                        } else {
+                            info!("сбор данных возобновился");
+                        }
+                        let host = shared_state.read().await.host_name.clone();
+                        bus.publish(Event::alert(
+                            now,
+                            host,
+                            Alert::Watchdog { stale, age_secs: age },
+                        ));
+                    }
+                }
+            }
+        })
+    };
+
+    let check_schedule = SharedCheckSchedule::default();
+    let check_task = tokio::spawn(
+        CheckRunner::new(check_schedule.clone()).run(config_rx.clone(), shutdown_rx.clone()),
+    );
+
     let collector_task = {
-        let cfg = cfg.clone();
+        let mut config_rx = config_rx.clone();
+        let bus = bus.clone();
         let metrics = metrics.clone();
         let shared_state = shared_state.clone();
+        let snapshot_store = snapshot.clone();
+        let speedtest_log = speedtest_log.clone();
+        let uptime_log = uptime_log.clone();
         let mut shutdown = shutdown_rx.clone();
-        let telegram_bot = telegram_bot.clone();
         tokio::spawn(async move {
             let client = Client::builder()
                 .user_agent("monitord/0.1.0")
                 .build()
                 .unwrap_or_else(|_| Client::new());
-            let mut system = sysinfo::System::new_all();
+            let system_collector = match SystemCollector::spawn() {
+                Ok(collector) => collector,
+                Err(err) => {
+                    error!(error = %err, "не удалось запустить поток сбора системных данных");
+                    return;
+                }
+            };
+            let mut cfg = config_rx.borrow_and_update().clone();
             let mut ticker = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
             ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
-            let mut resource_alert_last_sent: HashMap<String, i64> = HashMap::new();
+            let mut alert_engine = AlertEngine::new(&cfg);
+            let mut plugins = build_collectors(&cfg.plugins);
             let mut internet_speed: Option<InternetSpeedStat> = None;
             let mut last_speedtest_unix = 0_i64;
+            let mut speedtest_compacted_unix = now_unix();
+            let mut uptime_saved_unix = now_unix();
 
             loop {
                 tokio::select! {
                     _ = shutdown.changed() => {
                         info!("РїРѕР»СѓС‡РµРЅ СЃРёРіРЅР°Р» РѕСЃС‚Р°РЅРѕРІРєРё С†РёРєР»Р° СЃР±РѕСЂР°");
+                        save_check_uptime(&shared_state, &uptime_log).await;
                         break;
                     }
                     _ = ticker.tick() => {
-                        let system_snapshot = collect_system(&mut system);
-                        let (check_results, check_errors) = collect_checks(&client, &cfg).await;
+                        if config_rx.has_changed().unwrap_or(false) {
+                            cfg = config_rx.borrow_and_update().clone();
+                            alert_engine.reconfigure(&cfg);
+                            plugins = build_collectors(&cfg.plugins);
+                            let period = Duration::from_secs(cfg.interval_secs);
+                            if ticker.period() != period {
+                                ticker = tokio::time::interval_at(
+                                    tokio::time::Instant::now() + period,
+                                    period,
+                                );
+                                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                            }
+                            info!(interval_secs = cfg.interval_secs, "сборщик применил новую конфигурацию");
+                        }
+                        let mut system_snapshot = match system_collector.collect(cfg.clone()).await {
+                            Ok(snapshot) => snapshot,
+                            Err(err) => {
+                                metrics.inc_collect_error("system");
+                                warn!(error = %err, "системные данные не собраны, такт пропущен");
+                                let now = now_unix();
+                                let failures = &cfg.telegram.alerts.collector_failures;
+                                let alert = shared_state.write().await.record_collector_result(
+                                    "system",
+                                    Some(err.to_string()),
+                                    now,
+                                    failures.threshold_intervals,
+                                );
+                                if let Some(alert) = alert.filter(|_| failures.enabled) {
+                                    bus.publish(Event::alert(
+                                        now,
+                                        snapshot_store.load().host_name.clone(),
+                                        Alert::Collector(alert),
+                                    ));
+                                }
+                                continue;
+                            }
+                        };
+                        let mut collector_results: Vec<(String, Option<String>)> =
+                            vec![("system".to_string(), None)];
+                        let (plugin_sensors, plugin_errors) = collect_plugins(&plugins).await;
+                        system_snapshot.sensors.extend(plugin_sensors);
+                        for plugin in &plugins {
+                            let error = plugin_errors
+                                .iter()
+                                .find(|(name, _)| name == plugin.name())
+                                .map(|(_, err)| err.to_string());
+                            collector_results.push((format!("plugin:{}", plugin.name()), error));
+                        }
+                        for (plugin, err) in plugin_errors {
+                            metrics.inc_collect_error("plugins");
+                            warn!(plugin = %plugin, error = %err, "плагин сбора не вернул данные");
+                        }
+                        let (check_results, check_errors) = take_check_results(&cfg, &check_schedule);
                         for _ in 0..check_errors {
                             metrics.inc_collect_error("checks");
                         }
 
                         let now = now_unix();
-                        if now.saturating_sub(last_speedtest_unix) >= 30 {
-                            match collect_internet_speed(&client).await {
+                        if cfg.speedtest.enabled
+                            && now.saturating_sub(last_speedtest_unix) >= cfg.speedtest.interval_secs as i64
+                        {
+                            match collect_internet_speed(&client, &cfg.speedtest).await {
                                 Ok(sample) => {
+                                    record_speedtest(
+                                        &shared_state,
+                                        &speedtest_log,
+                                        &sample,
+                                        cfg.history.speedtest_retention_days,
+                                        &mut speedtest_compacted_unix,
+                                    )
+                                    .await;
                                     internet_speed = Some(sample);
                                     last_speedtest_unix = now;
+                                    collector_results.push(("internet_speed".to_string(), None));
                                 }
                                 Err(err) => {
                                     metrics.inc_collect_error("internet_speed");
+                                    collector_results
+                                        .push(("internet_speed".to_string(), Some(err.to_string())));
                                     tracing::debug!(error = %err, "speedtest РЅРµ РІС‹РїРѕР»РЅРµРЅ");
                                 }
                             }
                         }
-                        let (snapshot, alert_events) = {
+                        let (snapshot, mut alerts) = {
                             let mut guard = shared_state.write().await;
+                            let host_reboot =
+                                guard.detect_reboot(system_snapshot.uptime_seconds, now);
+                            let mount_changes = guard
+                                .detect_mount_changes(&system_snapshot.disks, &cfg.telegram.alerts);
+                            let read_only = guard
+                                .detect_read_only(&system_snapshot.disks, &cfg.telegram.alerts);
+                            let collector_alerts = record_collector_health(
+                                &mut guard,
+                                &cfg.telegram.alerts,
+                                now,
+                                &collector_results,
+                                &system_snapshot.sources,
+                            );
                             guard.update_collected(
                                 now,
                                 system_snapshot.host_name,
@@ -201,74 +909,208 @@ async fn main() {
                                 system_snapshot.process_count,
                                 system_snapshot.cpu_core_count,
                                 system_snapshot.cpu_usage_percent,
+                                system_snapshot.cpu_steal,
+                                system_snapshot.virtualization,
+                                system_snapshot.time_sync,
+                                system_snapshot.cgroup,
                                 system_snapshot.memory_used_bytes,
                                 system_snapshot.memory_total_bytes,
+                                system_snapshot.page_file,
                                 system_snapshot.disks,
+                                system_snapshot.disk_io,
                                 system_snapshot.net,
+                                system_snapshot.wireguard,
                                 internet_speed.clone(),
                                 system_snapshot.temps,
                                 system_snapshot.gpus,
                                 system_snapshot.sensors,
                                 check_results,
                             );
-                            let events = guard.apply_alert_rules(&cfg.telegram.alerts, now);
-                            (guard.clone(), events)
-                        };
-
-                        metrics.update_from_state(&snapshot);
-
-                        if let (Some(bot), true) = (&telegram_bot, cfg.telegram.enabled) {
-                            let sent_check_alerts = telegram::send_alert_events(
-                                bot,
-                                &cfg.telegram,
-                                shared_state.clone(),
-                                &alert_events,
-                            )
-                            .await;
-                            for _ in 0..sent_check_alerts {
-                                metrics.inc_alert_sent("check");
+                            guard.cpu_temperature_celsius =
+                                sensors::cpu_temperature(&guard.temps, &cfg.sensors);
+                            guard.record_metric_history(now, &cfg.history);
+                            guard.record_check_uptime(now);
+                            let mut alerts =
+                                alert_engine.evaluate(&mut guard, &cfg.telegram, now);
+                            if let Some(reboot) = &host_reboot {
+                                info!(
+                                    previous_uptime_secs = reboot.previous_uptime_secs,
+                                    boot_time_unix = reboot.boot_time_unix,
+                                    "обнаружена перезагрузка хоста"
+                                );
                             }
+                            alerts.extend(mount_changes.into_iter().map(Alert::Mount));
+                            alerts.extend(read_only.into_iter().map(Alert::ReadOnly));
+                            alerts.extend(host_reboot.into_iter().map(Alert::HostReboot));
+                            alerts.extend(collector_alerts.into_iter().map(Alert::Collector));
+                            (Arc::new(guard.snapshot()), alerts)
+                        };
 
-                            let texts = collect_resource_alerts(
-                                &snapshot,
-                                &cfg.telegram.alerts,
-                                now,
-                                &mut resource_alert_last_sent,
-                            );
-                            let sent_resource_alerts = telegram::send_text_alerts(
-                                bot,
-                                &cfg.telegram,
-                                shared_state.clone(),
-                                &texts,
-                            )
-                            .await;
-                            for _ in 0..sent_resource_alerts {
-                                metrics.inc_alert_sent("resource");
-                            }
+                        attach_gpu_alert_context(&mut alerts).await;
+                        for alert in alerts {
+                            bus.publish(Event::alert(now, snapshot.host_name.clone(), alert));
+                        }
+                        if now - uptime_saved_unix >= UPTIME_SAVE_SECS {
+                            uptime_saved_unix = now;
+                            save_check_uptime(&shared_state, &uptime_log).await;
                         }
+                        snapshot_store.store(snapshot.clone());
+                        bus.publish(Event::SnapshotUpdated { now, snapshot });
                     }
                 }
             }
         })
     };
+    let reload_task = match remote_config(&cli) {
+        Ok(Some(remote)) => cli.config_refresh.map(|refresh| {
+            reload::spawn_remote_config_poller(
+                remote,
+                config_client(),
+                refresh,
+                config_tx,
+                bus.clone(),
+                shutdown_rx.clone(),
+            )
+        }),
+        Ok(None) => match reload::spawn_config_watcher(
+            Path::new(&cli.config),
+            config_tx,
+            bus.clone(),
+            shutdown_rx.clone(),
+        ) {
+            Ok(task) => Some(task),
+            Err(err) => {
+                error!(error = %err, "не удалось включить отслеживание изменений конфигурации");
+                None
+            }
+        },
+        Err(err) => {
+            error!(error = %err, "не удалось включить обновление конфигурации");
+            None
+        }
+    };
 
-    if let Err(err) = tokio::signal::ctrl_c().await {
-        error!(error = %err, "РЅРµ СѓРґР°Р»РѕСЃСЊ РґРѕР¶РґР°С‚СЊСЃСЏ Ctrl+C");
-    }
-    info!("РїРѕР»СѓС‡РµРЅ Ctrl+C, РІС‹РїРѕР»РЅСЏРµС‚СЃСЏ РѕСЃС‚Р°РЅРѕРІРєР°");
+    let reason = shutdown_signal().await;
+    info!(
+        signal = reason,
+        "получен сигнал остановки, выполняется остановка"
+    );
+    bus.publish(Event::AgentStopping { reason });
+    drop(bus);
 
+    systemd::notify("STOPPING=1");
     let _ = shutdown_tx.send(true);
 
     let _ = collector_task.await;
+    let _ = check_task.await;
+    let _ = watchdog_task.await;
+    let _ = aggregator_task.await;
+    let _ = push_task.await;
+    let _ = heartbeat_task.await;
+    let _ = dir_sizes_task.await;
+    let _ = relay_task.await;
+    let _ = discovery_task.await;
+    if let Some(task) = systemd_watchdog_task {
+        let _ = task.await;
+    }
+    if let Some(task) = reload_task {
+        let _ = task.await;
+    }
+    for task in subscriber_tasks {
+        let _ = task.await;
+    }
     if let Some(task) = telegram_task {
         let _ = task.await;
     }
     let _ = http_task.await;
 }
 
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+#[cfg(unix)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut term, mut quit) = match (signal(SignalKind::terminate()), signal(SignalKind::quit())) {
+        (Ok(term), Ok(quit)) => (term, quit),
+        (Err(err), _) | (_, Err(err)) => {
+            error!(error = %err, "не удалось подписаться на сигналы остановки");
+            return ctrl_c().await;
+        }
+    };
+    tokio::select! {
+        reason = ctrl_c() => reason,
+        _ = term.recv() => "SIGTERM",
+        _ = quit.recv() => "SIGQUIT",
+    }
+}
+
+#[cfg(windows)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::windows::{ctrl_close, ctrl_logoff, ctrl_shutdown};
+
+    let (mut close, mut logoff, mut shutdown) = match (ctrl_close(), ctrl_logoff(), ctrl_shutdown())
+    {
+        (Ok(close), Ok(logoff), Ok(shutdown)) => (close, logoff, shutdown),
+        (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+            error!(error = %err, "не удалось подписаться на события консоли");
+            return ctrl_c().await;
+        }
+    };
+    // Windows ждёт обработчик CTRL_CLOSE всего несколько секунд, поэтому остановка начинается сразу.
+    tokio::select! {
+        reason = ctrl_c() => reason,
+        _ = close.recv() => "CTRL_CLOSE",
+        _ = logoff.recv() => "CTRL_LOGOFF",
+        _ = shutdown.recv() => "CTRL_SHUTDOWN",
+    }
+}
+
+async fn ctrl_c() -> &'static str {
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        error!(error = %err, "РЅРµ СѓРґР°Р»РѕСЃСЊ РґРѕР¶РґР°С‚СЊСЃСЏ Ctrl+C");
+        std::future::pending::<()>().await;
+    }
+    "Ctrl+C"
+}
+
+fn init_tracing(logging: &LoggingConfig) -> LogBuffer {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&logging.level))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let log_buffer = LogBuffer::default();
+    let mut file_error = None;
+    let file =
+        logging
+            .file
+            .as_ref()
+            .and_then(|path| match RotatingFile::open(Path::new(path), logging) {
+                Ok(file) => Some(Arc::new(file)),
+                Err(err) => {
+                    file_error = Some((path.clone(), err));
+                    None
+                }
+            });
+    let json = logging.format == LogFormat::Json;
+    // Если файл открыть не удалось, журнал не должен пропасть совсем.
+    let stdout = logging.stdout || file.is_none();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with((stdout && !json).then(tracing_subscriber::fmt::layer))
+        .with((stdout && json).then(|| tracing_subscriber::fmt::layer().json()))
+        .with(file.clone().filter(|_| !json).map(|file| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(file)
+        }))
+        .with(
+            file.filter(|_| json)
+                .map(|file| tracing_subscriber::fmt::layer().json().with_writer(file)),
+        )
+        .with(log_buffer.layer())
+        .init();
+    if let Some((path, err)) = file_error {
+        error!(path, error = %err, "не удалось открыть файл журнала, пишу в stdout");
+    }
+    log_buffer
 }
 
 fn now_unix() -> i64 {
@@ -278,10 +1120,53 @@ fn now_unix() -> i64 {
         .unwrap_or(0)
 }
 
-async fn collect_internet_speed(client: &Client) -> Result<InternetSpeedStat, reqwest::Error> {
-    const DOWNLOAD_BYTES: usize = 10_000_000;
-    const UPLOAD_BYTES: usize = 2_000_000;
+// Нулевой буфер для отправки лежит в статической памяти и не выделяется на каждый замер.
+static UPLOAD_BODY: [u8; MAX_SPEEDTEST_UPLOAD_BYTES as usize] =
+    [0; MAX_SPEEDTEST_UPLOAD_BYTES as usize];
+
+// Суточные итоги доступности сохраняются раз в UPTIME_SAVE_SECS и при остановке: после сбоя теряется не больше этого.
+const UPTIME_SAVE_SECS: i64 = 300;
+
+async fn save_check_uptime(shared_state: &RwLock<State>, log: &UptimeLog) {
+    let days = shared_state
+        .read()
+        .await
+        .check_uptime
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Err(err) = log.rewrite(&days) {
+        warn!(error = %err, "не удалось сохранить историю доступности проверок");
+    }
+}
 
+// Файл только дописывается; раз в сутки он перезаписывается без замеров старше срока хранения.
+async fn record_speedtest(
+    shared_state: &RwLock<State>,
+    log: &SpeedtestLog,
+    sample: &InternetSpeedStat,
+    retention_days: u64,
+    compacted_unix: &mut i64,
+) {
+    let mut state = shared_state.write().await;
+    if !state.record_speedtest(sample.clone(), retention_days) {
+        return;
+    }
+    let result = if sample.measured_at_unix - *compacted_unix >= 86_400 {
+        *compacted_unix = sample.measured_at_unix;
+        log.rewrite(&state.speedtest_history)
+    } else {
+        log.append(sample)
+    };
+    if let Err(err) = result {
+        warn!(error = %err, "не удалось сохранить замер speedtest");
+    }
+}
+
+async fn collect_internet_speed(
+    client: &Client,
+    cfg: &SpeedtestConfig,
+) -> Result<InternetSpeedStat, reqwest::Error> {
     let ping_start = Instant::now();
     let _ = client
         .head("https://speed.cloudflare.com/")
@@ -290,27 +1175,31 @@ async fn collect_internet_speed(client: &Client) -> Result<InternetSpeedStat, re
         .await?;
     let latency_ms = ping_start.elapsed().as_secs_f64() * 1000.0;
 
-    let down_url = format!("https://speed.cloudflare.com/__down?bytes={DOWNLOAD_BYTES}");
+    let budget = Duration::from_secs(cfg.download_budget_secs);
+    let down_url = format!(
+        "https://speed.cloudflare.com/__down?bytes={}",
+        cfg.download_bytes
+    );
     let down_start = Instant::now();
     let down = client
         .get(down_url)
-        .timeout(Duration::from_secs(20))
+        .timeout(budget + Duration::from_secs(10))
         .send()
         .await?;
-    let down_bytes = down.bytes().await?;
+    let down_bytes = stream_download(down, down_start, budget).await?;
     let down_secs = down_start.elapsed().as_secs_f64().max(0.001);
-    let download_mbps = ((down_bytes.len() as f64) * 8.0 / 1_000_000.0) / down_secs;
+    let download_mbps = ((down_bytes as f64) * 8.0 / 1_000_000.0) / down_secs;
 
-    let upload_buf = vec![0_u8; UPLOAD_BYTES];
+    let upload = &UPLOAD_BODY[..cfg.upload_bytes.min(MAX_SPEEDTEST_UPLOAD_BYTES) as usize];
     let up_start = Instant::now();
     let _ = client
         .post("https://speed.cloudflare.com/__up")
         .timeout(Duration::from_secs(20))
-        .body(upload_buf)
+        .body(upload)
         .send()
         .await?;
     let up_secs = up_start.elapsed().as_secs_f64().max(0.001);
-    let upload_mbps = ((UPLOAD_BYTES as f64) * 8.0 / 1_000_000.0) / up_secs;
+    let upload_mbps = ((upload.len() as f64) * 8.0 / 1_000_000.0) / up_secs;
 
     Ok(InternetSpeedStat {
         download_mbps,
@@ -320,189 +1209,51 @@ async fn collect_internet_speed(client: &Client) -> Result<InternetSpeedStat, re
     })
 }
 
-fn collect_resource_alerts(
-    state: &State,
-    alerts: &config::AlertsConfig,
-    now_unix: i64,
-    last_sent: &mut HashMap<String, i64>,
-) -> Vec<ResourceAlert> {
-    if !alerts.resource_alerts_enabled {
-        return Vec::new();
-    }
-
-    let cooldown = alerts.resource_alert_cooldown_secs as i64;
-    let mut out = Vec::new();
-
-    let gpu_load_max = state
-        .gpus
-        .iter()
-        .filter_map(|g| g.utilization_percent)
-        .fold(0.0_f64, f64::max);
-    if gpu_load_max >= alerts.gpu_load_threshold_percent
-        && should_emit("gpu_load", now_unix, cooldown, last_sent)
+// Тело читается по частям без накопления; по истечении budget от start чтение обрывается.
+async fn stream_download(
+    mut resp: reqwest::Response,
+    start: Instant,
+    budget: Duration,
+) -> Result<usize, reqwest::Error> {
+    let mut bytes = 0_usize;
+    while let Ok(chunk) =
+        tokio::time::timeout_at(tokio::time::Instant::from_std(start + budget), resp.chunk()).await
     {
-        out.push(ResourceAlert {
-            kind: ResourceAlertKind::GpuLoad,
-            text: format!(
-                "⚠ <b>Высокая нагрузка GPU</b>\nТекущее значение: {:.1}% (порог {:.1}%)",
-                gpu_load_max, alerts.gpu_load_threshold_percent
-            ),
-        });
-    }
-
-    let gpu_temp_max = state
-        .gpus
-        .iter()
-        .filter_map(|g| g.temperature_celsius)
-        .fold(0.0_f64, f64::max);
-    if gpu_temp_max >= alerts.gpu_temp_threshold_celsius
-        && should_emit("gpu_temp", now_unix, cooldown, last_sent)
-    {
-        out.push(ResourceAlert {
-            kind: ResourceAlertKind::GpuTemp,
-            text: format!(
-                "🔥 <b>Высокая температура GPU</b>\nТекущее значение: {:.1}°C (порог {:.1}°C)",
-                gpu_temp_max, alerts.gpu_temp_threshold_celsius
-            ),
-        });
-    }
-
-    if let Some(cpu_temp) = cpu_temperature_from_state(state) {
-        if cpu_temp >= alerts.cpu_temp_threshold_celsius
-            && should_emit("cpu_temp", now_unix, cooldown, last_sent)
-        {
-            out.push(ResourceAlert {
-                kind: ResourceAlertKind::CpuTemp,
-                text: format!(
-                    "🔥 <b>Высокая температура CPU</b>\nТекущее значение: {:.1}°C (порог {:.1}°C)",
-                    cpu_temp, alerts.cpu_temp_threshold_celsius
-                ),
-            });
-        }
-    }
-
-    if state.cpu_usage_percent >= alerts.cpu_load_threshold_percent
-        && should_emit("cpu_load", now_unix, cooldown, last_sent)
-    {
-        out.push(ResourceAlert {
-            kind: ResourceAlertKind::CpuLoad,
-            text: format!(
-                "⚠ <b>Высокая нагрузка CPU</b>\nТекущее значение: {:.1}% (порог {:.1}%)",
-                state.cpu_usage_percent, alerts.cpu_load_threshold_percent
-            ),
-        });
-    }
-
-    let ram_usage = if state.memory_total_bytes > 0 {
-        (state.memory_used_bytes as f64 / state.memory_total_bytes as f64) * 100.0
-    } else {
-        0.0
-    };
-    if ram_usage >= alerts.ram_usage_threshold_percent
-        && should_emit("ram_usage", now_unix, cooldown, last_sent)
-    {
-        out.push(ResourceAlert {
-            kind: ResourceAlertKind::RamUsage,
-            text: format!(
-                "⚠ <b>Высокое использование RAM</b>\nТекущее значение: {:.1}% (порог {:.1}%)",
-                ram_usage, alerts.ram_usage_threshold_percent
-            ),
-        });
-    }
-
-    let disk_worst = state
-        .disks
-        .iter()
-        .map(|d| {
-            let pct = if d.total_bytes > 0 {
-                (d.used_bytes as f64 / d.total_bytes as f64) * 100.0
-            } else {
-                0.0
-            };
-            (d.mount.as_str(), pct)
-        })
-        .max_by(|a, b| a.1.total_cmp(&b.1));
-    if let Some((mount, used_pct)) = disk_worst {
-        if used_pct >= alerts.disk_usage_threshold_percent
-            && should_emit("disk_usage", now_unix, cooldown, last_sent)
-        {
-            out.push(ResourceAlert {
-                kind: ResourceAlertKind::DiskUsage,
-                text: format!(
-                    "⚠ <b>Высокая заполненность диска</b>\nДиск: {mount}\nТекущее значение: {:.1}% (порог {:.1}%)",
-                    used_pct, alerts.disk_usage_threshold_percent
-                ),
-            });
+        match chunk? {
+            Some(chunk) => bytes += chunk.len(),
+            None => break,
         }
     }
+    Ok(bytes)
+}
 
-    out
+// Имя в mDNS: явно заданное, имя для push или имя хоста, если оно годится.
+fn discovery_instance(cfg: &Config) -> String {
+    cfg.discovery
+        .instance
+        .clone()
+        .or_else(|| cfg.push.host.clone())
+        .or_else(|| sysinfo::System::new().host_name())
+        .filter(|name| config::valid_host_name(name))
+        .unwrap_or_else(|| "monitord".to_string())
 }
-fn should_emit(
-    key: &str,
-    now_unix: i64,
-    cooldown_secs: i64,
-    last_sent: &mut HashMap<String, i64>,
-) -> bool {
-    if let Some(last) = last_sent.get(key) {
-        if now_unix - *last < cooldown_secs {
-            return false;
+
+async fn record_event_metrics(metrics: Arc<Metrics>, mut events: broadcast::Receiver<Event>) {
+    while let Some(event) = next_event(&mut events).await {
+        match event {
+            Event::SnapshotUpdated { snapshot, .. } => metrics.update_from_snapshot(&snapshot),
+            Event::AlertFired {
+                alert: Alert::HostReboot(_),
+                ..
+            } => metrics.inc_host_boot(),
+            Event::ConfigReloaded { config, .. } => {
+                metrics.set_exemplars(config.metrics.exemplars);
+                metrics.inc_config_reload("success");
+            }
+            Event::ConfigRejected { .. } => metrics.inc_config_reload("error"),
+            _ => {}
         }
     }
-    last_sent.insert(key.to_string(), now_unix);
-    true
-}
-
-fn cpu_temperature_from_state(state: &State) -> Option<f64> {
-    let primary_markers = ["cpu", "package", "tctl", "tdie", "coretemp", "k10temp"];
-    let primary = state
-        .temps
-        .iter()
-        .filter(|t| (0.0..=130.0).contains(&t.temperature_celsius))
-        .filter(|t| {
-            let s = t.sensor.to_lowercase();
-            primary_markers.iter().any(|m| s.contains(m))
-                && !s.contains("gpu")
-                && !s.contains("nvidia")
-                && !s.contains("amdgpu")
-                && !s.contains("radeon")
-                && !s.contains("acpi")
-                && !s.contains("thermal zone")
-                && !s.contains("_tz")
-        })
-        .map(|t| t.temperature_celsius)
-        .max_by(|a, b| a.total_cmp(b));
-    if primary.is_some() {
-        return primary;
-    }
-
-    let fallback_non_gpu = state
-        .temps
-        .iter()
-        .filter(|t| (0.0..=130.0).contains(&t.temperature_celsius))
-        .filter(|t| {
-            let s = t.sensor.to_lowercase();
-            !s.contains("gpu")
-                && !s.contains("nvidia")
-                && !s.contains("amdgpu")
-                && !s.contains("radeon")
-        })
-        .map(|t| t.temperature_celsius)
-        .max_by(|a, b| a.total_cmp(b));
-    if fallback_non_gpu.is_some() {
-        return fallback_non_gpu;
-    }
-
-    state
-        .temps
-        .iter()
-        .filter(|t| (0.0..=130.0).contains(&t.temperature_celsius))
-        .filter(|t| {
-            let s = t.sensor.to_lowercase();
-            s.contains("acpi") || s.contains("thermal zone") || s.contains("_tz")
-        })
-        .map(|t| t.temperature_celsius)
-        .max_by(|a, b| a.total_cmp(b))
 }
 
 fn resolve_telegram_token_from_env(env_name: &str) -> Option<String> {
@@ -524,7 +1275,7 @@ fn ensure_telegram_settings(cfg: &Config) -> Result<String, String> {
         .map(|v| v.trim().to_string())
         .filter(|v| !v.is_empty());
 
-    if cfg.telegram.allowed_chat_ids.is_empty() {
+    if cfg.telegram.allowed_chat_ids.is_empty() && !cfg.telegram.pairing_enabled {
         return Err(
             "telegram.allowed_chat_ids РїСѓСЃС‚: СѓРєР°Р¶РёС‚Рµ С…РѕС‚СЏ Р±С‹ РѕРґРёРЅ chat id РІ config".to_string(),
         );
@@ -542,3 +1293,65 @@ fn ensure_telegram_settings(cfg: &Config) -> Result<String, String> {
         env_name
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Сервер отдаёт тело чанками по 1000 байт каждые 50 мс.
+    async fn slow_server(chunks: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+                    if socket.write_all(head.as_bytes()).await.is_err() {
+                        return;
+                    }
+                    for _ in 0..chunks {
+                        let chunk = format!("3e8\r\n{}\r\n", "0".repeat(1000));
+                        if socket.write_all(chunk.as_bytes()).await.is_err() {
+                            return;
+                        }
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    let _ = socket.write_all(b"0\r\n\r\n").await;
+                });
+            }
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn speedtest_download_counts_streamed_bytes_within_budget() {
+        let url = slow_server(5).await;
+        let client = Client::new();
+
+        let start = Instant::now();
+        let resp = client.get(&url).send().await.unwrap();
+        let bytes = stream_download(resp, start, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(bytes, 5_000);
+
+        let url = slow_server(200).await;
+        let start = Instant::now();
+        let resp = client.get(&url).send().await.unwrap();
+        let bytes = stream_download(resp, start, Duration::from_millis(300))
+            .await
+            .unwrap();
+        assert!(bytes > 0 && bytes < 200_000, "bytes = {bytes}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn default_speedtest_fits_upload_buffer() {
+        let cfg = SpeedtestConfig::default();
+        assert!(cfg.upload_bytes <= MAX_SPEEDTEST_UPLOAD_BYTES);
+        assert!(cfg.interval_secs >= 60);
+    }
+}
@@ -1,8 +1,19 @@
-use crate::state::State;
-use prometheus::core::Collector;
-use prometheus::{opts, Counter, CounterVec, Encoder, Gauge, GaugeVec, Registry, TextEncoder};
-use std::collections::HashMap;
-use std::sync::Arc;
+use crate::aggregator::HostState;
+use crate::openmetrics::{self, Created, Exemplar, Exemplars};
+use crate::sensors::max_temperature_by_domain;
+use crate::state::Snapshot;
+use bytes::{Bytes, BytesMut};
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    histogram_opts, opts, Counter, CounterVec, Encoder, Gauge, GaugeVec, HistogramVec, Opts,
+    Registry, TextEncoder,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
@@ -14,42 +25,78 @@ pub struct Metrics {
     pub agent_ram_used_bytes: Gauge,
     pub agent_ram_total_bytes: Gauge,
     pub agent_ram_usage_percent: Gauge,
-    pub agent_disk_used_bytes: GaugeVec,
-    pub agent_disk_total_bytes: GaugeVec,
-    pub agent_disk_usage_percent: GaugeVec,
+    pub agent_disk_used_bytes: TrackedGaugeVec,
+    pub agent_disk_total_bytes: TrackedGaugeVec,
+    pub agent_disk_usage_percent: TrackedGaugeVec,
+    pub agent_disk_temperature_celsius: TrackedGaugeVec,
+    pub agent_disk_read_only: TrackedGaugeVec,
     pub agent_disk_count: Gauge,
-    pub agent_temperature_celsius: GaugeVec,
-    pub agent_temperature_critical_celsius: GaugeVec,
+    pub agent_memory_commit_used_bytes: TrackedGaugeVec,
+    pub agent_memory_commit_limit_bytes: TrackedGaugeVec,
+    pub agent_pagefile_used_bytes: TrackedGaugeVec,
+    pub agent_pagefile_total_bytes: TrackedGaugeVec,
+    pub agent_collector_consecutive_failures: TrackedGaugeVec,
+    pub agent_temperature_celsius: TrackedGaugeVec,
+    pub agent_temperature_critical_celsius: TrackedGaugeVec,
     pub agent_temperature_sensor_count: Gauge,
-    pub agent_net_rx_bytes_total: GaugeVec,
-    pub agent_net_tx_bytes_total: GaugeVec,
-    pub agent_net_rx_bytes_per_sec: GaugeVec,
-    pub agent_net_tx_bytes_per_sec: GaugeVec,
+    pub agent_cpu_temperature_celsius: TrackedGaugeVec,
+    pub agent_cpu_steal_percent: TrackedGaugeVec,
+    pub agent_virtualization_info: TrackedGaugeVec,
+    pub agent_time_synchronized: TrackedGaugeVec,
+    pub agent_time_offset_seconds: TrackedGaugeVec,
+    pub agent_cgroup_cpu_limit_cores: TrackedGaugeVec,
+    pub agent_cgroup_memory_limit_bytes: TrackedGaugeVec,
+    pub agent_cgroup_cpu_usage_percent: TrackedGaugeVec,
+    pub agent_max_temperature_celsius: TrackedGaugeVec,
+    pub agent_net_rx_bytes_total: TrackedGaugeVec,
+    pub agent_net_tx_bytes_total: TrackedGaugeVec,
+    pub agent_net_rx_bytes_per_sec: TrackedGaugeVec,
+    pub agent_net_tx_bytes_per_sec: TrackedGaugeVec,
+    pub agent_net_rx_errors_total: TrackedGaugeVec,
+    pub agent_net_tx_errors_total: TrackedGaugeVec,
+    pub agent_net_rx_drops_total: TrackedGaugeVec,
+    pub agent_net_tx_drops_total: TrackedGaugeVec,
+    pub agent_disk_io_await_ms: TrackedGaugeVec,
+    pub agent_disk_read_await_ms: TrackedGaugeVec,
+    pub agent_disk_write_await_ms: TrackedGaugeVec,
+    pub agent_disk_io_ops_per_sec: TrackedGaugeVec,
+    pub agent_dir_size_bytes: TrackedGaugeVec,
+    pub agent_dir_top_entry_size_bytes: TrackedGaugeVec,
+    pub agent_net_errors_per_min: TrackedGaugeVec,
+    pub agent_wireguard_peer_handshake_age_seconds: TrackedGaugeVec,
+    pub agent_wireguard_peer_rx_bytes_total: TrackedGaugeVec,
+    pub agent_wireguard_peer_tx_bytes_total: TrackedGaugeVec,
     pub agent_net_iface_count: Gauge,
     pub agent_net_rx_bytes_per_sec_total: Gauge,
     pub agent_net_tx_bytes_per_sec_total: Gauge,
-    pub agent_gpu_utilization_percent: GaugeVec,
-    pub agent_gpu_memory_used_bytes: GaugeVec,
-    pub agent_gpu_memory_total_bytes: GaugeVec,
-    pub agent_gpu_memory_usage_percent: GaugeVec,
-    pub agent_gpu_temperature_celsius: GaugeVec,
+    pub agent_gpu_utilization_percent: TrackedGaugeVec,
+    pub agent_gpu_memory_used_bytes: TrackedGaugeVec,
+    pub agent_gpu_memory_total_bytes: TrackedGaugeVec,
+    pub agent_gpu_memory_usage_percent: TrackedGaugeVec,
+    pub agent_gpu_temperature_celsius: TrackedGaugeVec,
     pub agent_gpu_count: Gauge,
-    pub agent_sensor_value: GaugeVec,
-    pub agent_sensor_min: GaugeVec,
-    pub agent_sensor_max: GaugeVec,
+    pub agent_sensor_value: TrackedGaugeVec,
+    pub agent_sensor_min: TrackedGaugeVec,
+    pub agent_sensor_max: TrackedGaugeVec,
     pub agent_sensor_count: Gauge,
-    pub agent_sensor_type_count: GaugeVec,
-    pub agent_sensor_type_avg: GaugeVec,
-    pub agent_sensor_type_min: GaugeVec,
-    pub agent_sensor_type_max: GaugeVec,
-    pub agent_sensor_parent_count: GaugeVec,
-    pub agent_sensor_parent_avg: GaugeVec,
-    pub agent_sensor_parent_max: GaugeVec,
-    pub agent_http_check_up: GaugeVec,
-    pub agent_http_check_latency_ms: GaugeVec,
-    pub agent_http_check_status_code: GaugeVec,
-    pub agent_tcp_check_up: GaugeVec,
-    pub agent_tcp_check_latency_ms: GaugeVec,
+    pub agent_sensor_type_count: TrackedGaugeVec,
+    pub agent_sensor_type_avg: TrackedGaugeVec,
+    pub agent_sensor_type_min: TrackedGaugeVec,
+    pub agent_sensor_type_max: TrackedGaugeVec,
+    pub agent_sensor_parent_count: TrackedGaugeVec,
+    pub agent_sensor_parent_avg: TrackedGaugeVec,
+    pub agent_sensor_parent_max: TrackedGaugeVec,
+    pub agent_http_check_up: TrackedGaugeVec,
+    pub agent_http_check_latency_ms: TrackedGaugeVec,
+    pub agent_http_check_status_code: TrackedGaugeVec,
+    pub agent_tcp_check_up: TrackedGaugeVec,
+    pub agent_tcp_check_latency_ms: TrackedGaugeVec,
+    pub agent_tcp_check_tls_handshake_ms: TrackedGaugeVec,
+    pub agent_tcp_check_tls_cert_valid: TrackedGaugeVec,
+    pub agent_ssh_check_up: TrackedGaugeVec,
+    pub agent_ssh_check_latency_ms: TrackedGaugeVec,
+    pub agent_ssh_check_host_key_match: TrackedGaugeVec,
+    pub agent_check_latency_quantile_ms: TrackedGaugeVec,
     pub agent_http_checks_total: Gauge,
     pub agent_http_checks_up: Gauge,
     pub agent_http_checks_down: Gauge,
@@ -64,7 +111,32 @@ pub struct Metrics {
     pub agent_scrape_count_total: Counter,
     pub agent_collect_errors_total: CounterVec,
     pub agent_alerts_sent_total: CounterVec,
+    pub agent_host_boots_total: Counter,
+    pub agent_notifications_failed_total: CounterVec,
+    pub agent_telegram_api_errors_total: CounterVec,
+    pub agent_telegram_requests_throttled_total: Counter,
+    pub agent_telegram_last_success_timestamp_seconds: Gauge,
+    pub agent_telegram_failing_since_timestamp_seconds: Gauge,
+    pub agent_config_reloads_total: CounterVec,
     pub agent_last_collect_timestamp_seconds: Gauge,
+    pub agent_aggregated_host_up: TrackedGaugeVec,
+    pub agent_aggregated_cpu_usage_percent: TrackedGaugeVec,
+    pub agent_aggregated_memory_used_bytes: TrackedGaugeVec,
+    pub agent_aggregated_memory_total_bytes: TrackedGaugeVec,
+    pub agent_aggregated_disk_usage_percent: TrackedGaugeVec,
+    pub agent_aggregated_checks_down: TrackedGaugeVec,
+    pub agent_aggregated_last_collect_timestamp_seconds: TrackedGaugeVec,
+    pub agent_relay_up: TrackedGaugeVec,
+    pub agent_check_latency_seconds: HistogramVec,
+    exemplars_enabled: Arc<AtomicBool>,
+    exemplars: Arc<Mutex<Exemplars>>,
+    // Время появления серий счётчиков и гистограммы для _created в OpenMetrics.
+    created: Arc<Mutex<Created>>,
+    // Готовый текст метрик внешних экспортеров, дописывается к /metrics как есть.
+    relay_output: Arc<Mutex<Vec<u8>>>,
+    // Закодированный ответ /metrics; сбрасывается при любом изменении значений.
+    encoded: Arc<Mutex<Option<Bytes>>>,
+    encoded_openmetrics: Arc<Mutex<Option<Bytes>>>,
 }
 
 impl Metrics {
@@ -85,28 +157,77 @@ impl Metrics {
             Gauge::with_opts(opts!("agent_ram_total_bytes", "Total RAM in bytes"))?;
         let agent_ram_usage_percent =
             Gauge::with_opts(opts!("agent_ram_usage_percent", "RAM usage in percent"))?;
-        let agent_disk_used_bytes = GaugeVec::new(
+        let agent_disk_used_bytes = TrackedGaugeVec::new(
             opts!("agent_disk_used_bytes", "Disk used bytes by mount"),
             &["mount"],
         )?;
-        let agent_disk_total_bytes = GaugeVec::new(
+        let agent_disk_total_bytes = TrackedGaugeVec::new(
             opts!("agent_disk_total_bytes", "Disk total bytes by mount"),
             &["mount"],
         )?;
-        let agent_disk_usage_percent = GaugeVec::new(
+        let agent_disk_usage_percent = TrackedGaugeVec::new(
             opts!("agent_disk_usage_percent", "Disk usage in percent by mount"),
             &["mount"],
         )?;
+        let agent_disk_temperature_celsius = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_temperature_celsius",
+                "Drive temperature in Celsius by block device"
+            ),
+            &["device"],
+        )?;
+        let agent_disk_read_only = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_read_only",
+                "1 when the mount is read-only or its write probe failed"
+            ),
+            &["mount"],
+        )?;
+        let agent_collector_consecutive_failures = TrackedGaugeVec::new(
+            opts!(
+                "agent_collector_consecutive_failures",
+                "Consecutive collection intervals without data by collector"
+            ),
+            &["collector"],
+        )?;
+        let agent_memory_commit_used_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_memory_commit_used_bytes",
+                "Committed memory (commit charge) in bytes, Windows only"
+            ),
+            &[],
+        )?;
+        let agent_memory_commit_limit_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_memory_commit_limit_bytes",
+                "Commit limit (RAM plus page files) in bytes, Windows only"
+            ),
+            &[],
+        )?;
+        let agent_pagefile_used_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_pagefile_used_bytes",
+                "Page file usage in bytes across all page files, Windows only"
+            ),
+            &[],
+        )?;
+        let agent_pagefile_total_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_pagefile_total_bytes",
+                "Allocated page file size in bytes across all page files, Windows only"
+            ),
+            &[],
+        )?;
         let agent_disk_count =
             Gauge::with_opts(opts!("agent_disk_count", "Number of mounted disks"))?;
-        let agent_temperature_celsius = GaugeVec::new(
+        let agent_temperature_celsius = TrackedGaugeVec::new(
             opts!(
                 "agent_temperature_celsius",
                 "Temperature by sensor in Celsius"
             ),
             &["sensor"],
         )?;
-        let agent_temperature_critical_celsius = GaugeVec::new(
+        let agent_temperature_critical_celsius = TrackedGaugeVec::new(
             opts!(
                 "agent_temperature_critical_celsius",
                 "Critical temperature threshold by sensor in Celsius"
@@ -117,34 +238,195 @@ impl Metrics {
             "agent_temperature_sensor_count",
             "Number of detected temperature sensors"
         ))?;
-        let agent_net_rx_bytes_total = GaugeVec::new(
+        let agent_cpu_temperature_celsius = TrackedGaugeVec::new(
+            opts!(
+                "agent_cpu_temperature_celsius",
+                "CPU temperature in Celsius picked from sensors the same way as for alerts"
+            ),
+            &[],
+        )?;
+        let agent_cpu_steal_percent = TrackedGaugeVec::new(
+            opts!(
+                "agent_cpu_steal_percent",
+                "Share of CPU time taken by the hypervisor since the previous collection, Linux only"
+            ),
+            &[],
+        )?;
+        let agent_virtualization_info = TrackedGaugeVec::new(
+            opts!(
+                "agent_virtualization_info",
+                "Always 1 when running inside a virtual machine, labelled with the hypervisor"
+            ),
+            &["hypervisor"],
+        )?;
+        let agent_time_synchronized = TrackedGaugeVec::new(
+            opts!(
+                "agent_time_synchronized",
+                "1 when the time synchronization daemon reports the clock as synchronized, labelled with the source"
+            ),
+            &["source"],
+        )?;
+        let agent_time_offset_seconds = TrackedGaugeVec::new(
+            opts!(
+                "agent_time_offset_seconds",
+                "Offset of the system clock from the reference in seconds, chrony only"
+            ),
+            &["source"],
+        )?;
+        let agent_cgroup_cpu_limit_cores = TrackedGaugeVec::new(
+            opts!(
+                "agent_cgroup_cpu_limit_cores",
+                "CPU quota of the container cgroup in cores"
+            ),
+            &[],
+        )?;
+        let agent_cgroup_memory_limit_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_cgroup_memory_limit_bytes",
+                "Memory limit of the container cgroup in bytes"
+            ),
+            &[],
+        )?;
+        let agent_cgroup_cpu_usage_percent = TrackedGaugeVec::new(
+            opts!(
+                "agent_cgroup_cpu_usage_percent",
+                "Container CPU usage since the previous collection relative to its quota, or to all cores without one"
+            ),
+            &[],
+        )?;
+        let agent_max_temperature_celsius = TrackedGaugeVec::new(
+            opts!(
+                "agent_max_temperature_celsius",
+                "Highest temperature in Celsius by domain (cpu, gpu, disk)"
+            ),
+            &["domain"],
+        )?;
+        let agent_net_rx_bytes_total = TrackedGaugeVec::new(
             opts!(
                 "agent_net_rx_bytes_total",
                 "Current total received bytes per interface"
             ),
             &["iface"],
         )?;
-        let agent_net_tx_bytes_total = GaugeVec::new(
+        let agent_net_tx_bytes_total = TrackedGaugeVec::new(
             opts!(
                 "agent_net_tx_bytes_total",
                 "Current total transmitted bytes per interface"
             ),
             &["iface"],
         )?;
-        let agent_net_rx_bytes_per_sec = GaugeVec::new(
+        let agent_net_rx_bytes_per_sec = TrackedGaugeVec::new(
             opts!(
                 "agent_net_rx_bytes_per_sec",
                 "Current receive speed in bytes per second by interface"
             ),
             &["iface"],
         )?;
-        let agent_net_tx_bytes_per_sec = GaugeVec::new(
+        let agent_net_tx_bytes_per_sec = TrackedGaugeVec::new(
             opts!(
                 "agent_net_tx_bytes_per_sec",
                 "Current transmit speed in bytes per second by interface"
             ),
             &["iface"],
         )?;
+        let agent_net_rx_errors_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_net_rx_errors_total",
+                "Total receive errors per interface"
+            ),
+            &["iface"],
+        )?;
+        let agent_net_tx_errors_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_net_tx_errors_total",
+                "Total transmit errors per interface"
+            ),
+            &["iface"],
+        )?;
+        let agent_net_rx_drops_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_net_rx_drops_total",
+                "Total dropped received packets per interface"
+            ),
+            &["iface"],
+        )?;
+        let agent_net_tx_drops_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_net_tx_drops_total",
+                "Total dropped transmitted packets per interface"
+            ),
+            &["iface"],
+        )?;
+        let agent_disk_io_await_ms = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_io_await_ms",
+                "Average I/O operation time in milliseconds since the previous collection by device"
+            ),
+            &["device"],
+        )?;
+        let agent_disk_read_await_ms = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_read_await_ms",
+                "Average read operation time in milliseconds since the previous collection by device"
+            ),
+            &["device"],
+        )?;
+        let agent_disk_write_await_ms = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_write_await_ms",
+                "Average write operation time in milliseconds since the previous collection by device"
+            ),
+            &["device"],
+        )?;
+        let agent_disk_io_ops_per_sec = TrackedGaugeVec::new(
+            opts!(
+                "agent_disk_io_ops_per_sec",
+                "Completed read and write operations per second by device"
+            ),
+            &["device"],
+        )?;
+        let agent_dir_size_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_dir_size_bytes",
+                "Apparent size of a tracked directory from the latest scan"
+            ),
+            &["path"],
+        )?;
+        let agent_dir_top_entry_size_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_dir_top_entry_size_bytes",
+                "Size of the largest entries inside a tracked directory from the latest scan"
+            ),
+            &["path", "entry"],
+        )?;
+        let agent_net_errors_per_min = TrackedGaugeVec::new(
+            opts!(
+                "agent_net_errors_per_min",
+                "Receive and transmit errors per minute since the previous collection by interface"
+            ),
+            &["iface"],
+        )?;
+        let agent_wireguard_peer_handshake_age_seconds = TrackedGaugeVec::new(
+            opts!(
+                "agent_wireguard_peer_handshake_age_seconds",
+                "Seconds since the latest WireGuard handshake per peer"
+            ),
+            &["iface", "peer"],
+        )?;
+        let agent_wireguard_peer_rx_bytes_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_wireguard_peer_rx_bytes_total",
+                "Total bytes received from a WireGuard peer"
+            ),
+            &["iface", "peer"],
+        )?;
+        let agent_wireguard_peer_tx_bytes_total = TrackedGaugeVec::new(
+            opts!(
+                "agent_wireguard_peer_tx_bytes_total",
+                "Total bytes sent to a WireGuard peer"
+            ),
+            &["iface", "peer"],
+        )?;
         let agent_net_iface_count = Gauge::with_opts(opts!(
             "agent_net_iface_count",
             "Number of network interfaces"
@@ -157,35 +439,35 @@ impl Metrics {
             "agent_net_tx_bytes_per_sec_total",
             "Total transmit speed in bytes per second across all interfaces"
         ))?;
-        let agent_gpu_utilization_percent = GaugeVec::new(
+        let agent_gpu_utilization_percent = TrackedGaugeVec::new(
             opts!(
                 "agent_gpu_utilization_percent",
                 "GPU utilization in percent (if available)"
             ),
             &["id", "name"],
         )?;
-        let agent_gpu_memory_used_bytes = GaugeVec::new(
+        let agent_gpu_memory_used_bytes = TrackedGaugeVec::new(
             opts!(
                 "agent_gpu_memory_used_bytes",
                 "GPU memory used in bytes (if available)"
             ),
             &["id", "name"],
         )?;
-        let agent_gpu_memory_total_bytes = GaugeVec::new(
+        let agent_gpu_memory_total_bytes = TrackedGaugeVec::new(
             opts!(
                 "agent_gpu_memory_total_bytes",
                 "GPU memory total in bytes (if available)"
             ),
             &["id", "name"],
         )?;
-        let agent_gpu_memory_usage_percent = GaugeVec::new(
+        let agent_gpu_memory_usage_percent = TrackedGaugeVec::new(
             opts!(
                 "agent_gpu_memory_usage_percent",
                 "GPU memory usage in percent (if used and total are available)"
             ),
             &["id", "name"],
         )?;
-        let agent_gpu_temperature_celsius = GaugeVec::new(
+        let agent_gpu_temperature_celsius = TrackedGaugeVec::new(
             opts!(
                 "agent_gpu_temperature_celsius",
                 "GPU temperature in Celsius (if available)"
@@ -194,21 +476,21 @@ impl Metrics {
         )?;
         let agent_gpu_count =
             Gauge::with_opts(opts!("agent_gpu_count", "Number of detected GPUs"))?;
-        let agent_sensor_value = GaugeVec::new(
+        let agent_sensor_value = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_value",
                 "Raw sensor value exported from collectors/LibreHardwareMonitor"
             ),
             &["sensor_type", "name", "identifier", "parent"],
         )?;
-        let agent_sensor_min = GaugeVec::new(
+        let agent_sensor_min = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_min",
                 "Sensor min value exported from collectors/LibreHardwareMonitor"
             ),
             &["sensor_type", "name", "identifier", "parent"],
         )?;
-        let agent_sensor_max = GaugeVec::new(
+        let agent_sensor_max = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_max",
                 "Sensor max value exported from collectors/LibreHardwareMonitor"
@@ -219,49 +501,49 @@ impl Metrics {
             "agent_sensor_count",
             "Total number of collected sensors"
         ))?;
-        let agent_sensor_type_count = GaugeVec::new(
+        let agent_sensor_type_count = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_type_count",
                 "Number of collected sensors grouped by sensor_type"
             ),
             &["sensor_type"],
         )?;
-        let agent_sensor_type_avg = GaugeVec::new(
+        let agent_sensor_type_avg = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_type_avg",
                 "Average sensor value grouped by sensor_type"
             ),
             &["sensor_type"],
         )?;
-        let agent_sensor_type_min = GaugeVec::new(
+        let agent_sensor_type_min = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_type_min",
                 "Minimum sensor value grouped by sensor_type"
             ),
             &["sensor_type"],
         )?;
-        let agent_sensor_type_max = GaugeVec::new(
+        let agent_sensor_type_max = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_type_max",
                 "Maximum sensor value grouped by sensor_type"
             ),
             &["sensor_type"],
         )?;
-        let agent_sensor_parent_count = GaugeVec::new(
+        let agent_sensor_parent_count = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_parent_count",
                 "Number of sensors grouped by sensor_type and parent"
             ),
             &["sensor_type", "parent"],
         )?;
-        let agent_sensor_parent_avg = GaugeVec::new(
+        let agent_sensor_parent_avg = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_parent_avg",
                 "Average sensor value grouped by sensor_type and parent"
             ),
             &["sensor_type", "parent"],
         )?;
-        let agent_sensor_parent_max = GaugeVec::new(
+        let agent_sensor_parent_max = TrackedGaugeVec::new(
             opts!(
                 "agent_sensor_parent_max",
                 "Maximum sensor value grouped by sensor_type and parent"
@@ -269,26 +551,62 @@ impl Metrics {
             &["sensor_type", "parent"],
         )?;
 
-        let agent_http_check_up = GaugeVec::new(
+        let agent_check_latency_quantile_ms = TrackedGaugeVec::new(
+            opts!(
+                "agent_check_latency_quantile_ms",
+                "Check latency percentile over the last successful runs in ms"
+            ),
+            &["kind", "check", "quantile"],
+        )?;
+        let agent_http_check_up = TrackedGaugeVec::new(
             opts!("agent_http_check_up", "HTTP check up status 0/1"),
             &["name"],
         )?;
-        let agent_http_check_latency_ms = GaugeVec::new(
+        let agent_http_check_latency_ms = TrackedGaugeVec::new(
             opts!("agent_http_check_latency_ms", "HTTP check latency in ms"),
             &["name"],
         )?;
-        let agent_http_check_status_code = GaugeVec::new(
+        let agent_http_check_status_code = TrackedGaugeVec::new(
             opts!("agent_http_check_status_code", "HTTP check status code"),
             &["name"],
         )?;
-        let agent_tcp_check_up = GaugeVec::new(
+        let agent_tcp_check_up = TrackedGaugeVec::new(
             opts!("agent_tcp_check_up", "TCP check up status 0/1"),
             &["name"],
         )?;
-        let agent_tcp_check_latency_ms = GaugeVec::new(
+        let agent_tcp_check_latency_ms = TrackedGaugeVec::new(
             opts!("agent_tcp_check_latency_ms", "TCP check latency in ms"),
             &["name"],
         )?;
+        let agent_tcp_check_tls_handshake_ms = TrackedGaugeVec::new(
+            opts!(
+                "agent_tcp_check_tls_handshake_ms",
+                "TCP check TLS handshake duration in ms"
+            ),
+            &["name"],
+        )?;
+        let agent_tcp_check_tls_cert_valid = TrackedGaugeVec::new(
+            opts!(
+                "agent_tcp_check_tls_cert_valid",
+                "TCP check TLS certificate validity (1/0)"
+            ),
+            &["name"],
+        )?;
+        let agent_ssh_check_up = TrackedGaugeVec::new(
+            opts!("agent_ssh_check_up", "SSH check up status 0/1"),
+            &["name"],
+        )?;
+        let agent_ssh_check_latency_ms = TrackedGaugeVec::new(
+            opts!("agent_ssh_check_latency_ms", "SSH check latency in ms"),
+            &["name"],
+        )?;
+        let agent_ssh_check_host_key_match = TrackedGaugeVec::new(
+            opts!(
+                "agent_ssh_check_host_key_match",
+                "SSH host key matches configured fingerprint (1/0)"
+            ),
+            &["name"],
+        )?;
 
         let agent_http_checks_total = Gauge::with_opts(opts!(
             "agent_http_checks_total",
@@ -333,10 +651,103 @@ impl Metrics {
             opts!("agent_alerts_sent_total", "Sent alerts total by kind"),
             &["kind"],
         )?;
+        let agent_notifications_failed_total = CounterVec::new(
+            opts!(
+                "agent_notifications_failed_total",
+                "Notifications that could not be delivered by channel"
+            ),
+            &["channel"],
+        )?;
+        let agent_telegram_api_errors_total = CounterVec::new(
+            opts!(
+                "agent_telegram_api_errors_total",
+                "Failed Telegram Bot API requests by reason"
+            ),
+            &["reason"],
+        )?;
+        let agent_telegram_requests_throttled_total = Counter::with_opts(opts!(
+            "agent_telegram_requests_throttled_total",
+            "Chat requests rejected by the bot rate limit"
+        ))?;
+        let agent_telegram_last_success_timestamp_seconds = Gauge::with_opts(opts!(
+            "agent_telegram_last_success_timestamp_seconds",
+            "Unix timestamp of the last successful Telegram Bot API request"
+        ))?;
+        let agent_telegram_failing_since_timestamp_seconds = Gauge::with_opts(opts!(
+            "agent_telegram_failing_since_timestamp_seconds",
+            "Unix timestamp since which every Telegram Bot API request fails, 0 when healthy"
+        ))?;
+        let agent_config_reloads_total = CounterVec::new(
+            opts!(
+                "agent_config_reloads_total",
+                "Configuration reload attempts by result"
+            ),
+            &["result"],
+        )?;
+        let agent_host_boots_total = Counter::with_opts(opts!(
+            "agent_host_boots_total",
+            "Host reboots detected by the agent"
+        ))?;
         let agent_last_collect_timestamp_seconds = Gauge::with_opts(opts!(
             "agent_last_collect_timestamp_seconds",
             "Unix timestamp of the last collection"
         ))?;
+        let agent_aggregated_host_up = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_host_up",
+                "Whether an aggregated host answers polls or keeps pushing its state"
+            ),
+            &["host"],
+        )?;
+        let agent_aggregated_cpu_usage_percent = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_cpu_usage_percent",
+                "CPU usage of an aggregated host in percent"
+            ),
+            &["host"],
+        )?;
+        let agent_aggregated_memory_used_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_memory_used_bytes",
+                "Used memory of an aggregated host in bytes"
+            ),
+            &["host"],
+        )?;
+        let agent_aggregated_memory_total_bytes = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_memory_total_bytes",
+                "Total memory of an aggregated host in bytes"
+            ),
+            &["host"],
+        )?;
+        let agent_aggregated_disk_usage_percent = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_disk_usage_percent",
+                "Disk usage of an aggregated host in percent by mount"
+            ),
+            &["host", "mount"],
+        )?;
+        let agent_aggregated_checks_down = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_checks_down",
+                "Failing checks on an aggregated host"
+            ),
+            &["host"],
+        )?;
+        let agent_aggregated_last_collect_timestamp_seconds = TrackedGaugeVec::new(
+            opts!(
+                "agent_aggregated_last_collect_timestamp_seconds",
+                "Unix timestamp of the last collection on an aggregated host"
+            ),
+            &["host"],
+        )?;
+        let agent_relay_up = TrackedGaugeVec::new(
+            opts!(
+                "agent_relay_up",
+                "Whether the last scrape of a relayed exporter succeeded"
+            ),
+            &["exporter"],
+        )?;
 
         register(&registry, &agent_cpu_usage_percent)?;
         register(&registry, &agent_memory_used_bytes)?;
@@ -347,14 +758,44 @@ impl Metrics {
         register(&registry, &agent_disk_used_bytes)?;
         register(&registry, &agent_disk_total_bytes)?;
         register(&registry, &agent_disk_usage_percent)?;
+        register(&registry, &agent_disk_temperature_celsius)?;
+        register(&registry, &agent_disk_read_only)?;
         register(&registry, &agent_disk_count)?;
+        register(&registry, &agent_memory_commit_used_bytes)?;
+        register(&registry, &agent_memory_commit_limit_bytes)?;
+        register(&registry, &agent_pagefile_used_bytes)?;
+        register(&registry, &agent_pagefile_total_bytes)?;
+        register(&registry, &agent_collector_consecutive_failures)?;
         register(&registry, &agent_temperature_celsius)?;
         register(&registry, &agent_temperature_critical_celsius)?;
         register(&registry, &agent_temperature_sensor_count)?;
+        register(&registry, &agent_cpu_temperature_celsius)?;
+        register(&registry, &agent_cpu_steal_percent)?;
+        register(&registry, &agent_virtualization_info)?;
+        register(&registry, &agent_time_synchronized)?;
+        register(&registry, &agent_time_offset_seconds)?;
+        register(&registry, &agent_cgroup_cpu_limit_cores)?;
+        register(&registry, &agent_cgroup_memory_limit_bytes)?;
+        register(&registry, &agent_cgroup_cpu_usage_percent)?;
+        register(&registry, &agent_max_temperature_celsius)?;
         register(&registry, &agent_net_rx_bytes_total)?;
         register(&registry, &agent_net_tx_bytes_total)?;
         register(&registry, &agent_net_rx_bytes_per_sec)?;
         register(&registry, &agent_net_tx_bytes_per_sec)?;
+        register(&registry, &agent_net_rx_errors_total)?;
+        register(&registry, &agent_net_tx_errors_total)?;
+        register(&registry, &agent_net_rx_drops_total)?;
+        register(&registry, &agent_net_tx_drops_total)?;
+        register(&registry, &agent_disk_io_await_ms)?;
+        register(&registry, &agent_disk_read_await_ms)?;
+        register(&registry, &agent_disk_write_await_ms)?;
+        register(&registry, &agent_disk_io_ops_per_sec)?;
+        register(&registry, &agent_dir_size_bytes)?;
+        register(&registry, &agent_dir_top_entry_size_bytes)?;
+        register(&registry, &agent_net_errors_per_min)?;
+        register(&registry, &agent_wireguard_peer_handshake_age_seconds)?;
+        register(&registry, &agent_wireguard_peer_rx_bytes_total)?;
+        register(&registry, &agent_wireguard_peer_tx_bytes_total)?;
         register(&registry, &agent_net_iface_count)?;
         register(&registry, &agent_net_rx_bytes_per_sec_total)?;
         register(&registry, &agent_net_tx_bytes_per_sec_total)?;
@@ -377,9 +818,15 @@ impl Metrics {
         register(&registry, &agent_sensor_parent_max)?;
         register(&registry, &agent_http_check_up)?;
         register(&registry, &agent_http_check_latency_ms)?;
+        register(&registry, &agent_check_latency_quantile_ms)?;
         register(&registry, &agent_http_check_status_code)?;
         register(&registry, &agent_tcp_check_up)?;
         register(&registry, &agent_tcp_check_latency_ms)?;
+        register(&registry, &agent_tcp_check_tls_handshake_ms)?;
+        register(&registry, &agent_tcp_check_tls_cert_valid)?;
+        register(&registry, &agent_ssh_check_up)?;
+        register(&registry, &agent_ssh_check_latency_ms)?;
+        register(&registry, &agent_ssh_check_host_key_match)?;
         register(&registry, &agent_http_checks_total)?;
         register(&registry, &agent_http_checks_up)?;
         register(&registry, &agent_http_checks_down)?;
@@ -391,12 +838,34 @@ impl Metrics {
         register(&registry, &agent_checks_down)?;
         register(&registry, &agent_checks_down_ratio_percent)?;
         register(&registry, &agent_uptime_seconds)?;
-        register(&registry, &agent_scrape_count_total)?;
         register(&registry, &agent_collect_errors_total)?;
         register(&registry, &agent_alerts_sent_total)?;
+        register(&registry, &agent_host_boots_total)?;
+        register(&registry, &agent_notifications_failed_total)?;
+        register(&registry, &agent_telegram_api_errors_total)?;
+        register(&registry, &agent_telegram_requests_throttled_total)?;
+        register(&registry, &agent_telegram_last_success_timestamp_seconds)?;
+        register(&registry, &agent_telegram_failing_since_timestamp_seconds)?;
+        register(&registry, &agent_config_reloads_total)?;
         register(&registry, &agent_last_collect_timestamp_seconds)?;
+        register(&registry, &agent_aggregated_host_up)?;
+        register(&registry, &agent_aggregated_cpu_usage_percent)?;
+        register(&registry, &agent_aggregated_memory_used_bytes)?;
+        register(&registry, &agent_aggregated_memory_total_bytes)?;
+        register(&registry, &agent_aggregated_disk_usage_percent)?;
+        register(&registry, &agent_aggregated_checks_down)?;
+        register(&registry, &agent_aggregated_last_collect_timestamp_seconds)?;
+        let agent_check_latency_seconds = HistogramVec::new(
+            histogram_opts!(
+                "agent_check_latency_seconds",
+                "Latency of fresh HTTP, TCP and SSH check runs in seconds"
+            ),
+            &["kind", "check"],
+        )?;
+        register(&registry, &agent_relay_up)?;
+        register(&registry, &agent_check_latency_seconds)?;
 
-        Ok(Arc::new(Self {
+        let metrics = Self {
             registry,
             agent_cpu_usage_percent,
             agent_memory_used_bytes,
@@ -407,14 +876,44 @@ impl Metrics {
             agent_disk_used_bytes,
             agent_disk_total_bytes,
             agent_disk_usage_percent,
+            agent_disk_temperature_celsius,
+            agent_disk_read_only,
             agent_disk_count,
+            agent_memory_commit_used_bytes,
+            agent_memory_commit_limit_bytes,
+            agent_pagefile_used_bytes,
+            agent_pagefile_total_bytes,
+            agent_collector_consecutive_failures,
             agent_temperature_celsius,
             agent_temperature_critical_celsius,
             agent_temperature_sensor_count,
+            agent_cpu_temperature_celsius,
+            agent_cpu_steal_percent,
+            agent_virtualization_info,
+            agent_time_synchronized,
+            agent_time_offset_seconds,
+            agent_cgroup_cpu_limit_cores,
+            agent_cgroup_memory_limit_bytes,
+            agent_cgroup_cpu_usage_percent,
+            agent_max_temperature_celsius,
             agent_net_rx_bytes_total,
             agent_net_tx_bytes_total,
             agent_net_rx_bytes_per_sec,
             agent_net_tx_bytes_per_sec,
+            agent_net_rx_errors_total,
+            agent_net_tx_errors_total,
+            agent_net_rx_drops_total,
+            agent_net_tx_drops_total,
+            agent_disk_io_await_ms,
+            agent_disk_read_await_ms,
+            agent_disk_write_await_ms,
+            agent_disk_io_ops_per_sec,
+            agent_dir_size_bytes,
+            agent_dir_top_entry_size_bytes,
+            agent_net_errors_per_min,
+            agent_wireguard_peer_handshake_age_seconds,
+            agent_wireguard_peer_rx_bytes_total,
+            agent_wireguard_peer_tx_bytes_total,
             agent_net_iface_count,
             agent_net_rx_bytes_per_sec_total,
             agent_net_tx_bytes_per_sec_total,
@@ -437,9 +936,15 @@ impl Metrics {
             agent_sensor_parent_max,
             agent_http_check_up,
             agent_http_check_latency_ms,
+            agent_check_latency_quantile_ms,
             agent_http_check_status_code,
             agent_tcp_check_up,
             agent_tcp_check_latency_ms,
+            agent_tcp_check_tls_handshake_ms,
+            agent_tcp_check_tls_cert_valid,
+            agent_ssh_check_up,
+            agent_ssh_check_latency_ms,
+            agent_ssh_check_host_key_match,
             agent_http_checks_total,
             agent_http_checks_up,
             agent_http_checks_down,
@@ -454,11 +959,42 @@ impl Metrics {
             agent_scrape_count_total,
             agent_collect_errors_total,
             agent_alerts_sent_total,
+            agent_host_boots_total,
+            agent_notifications_failed_total,
+            agent_telegram_api_errors_total,
+            agent_telegram_requests_throttled_total,
+            agent_telegram_last_success_timestamp_seconds,
+            agent_telegram_failing_since_timestamp_seconds,
+            agent_config_reloads_total,
             agent_last_collect_timestamp_seconds,
-        }))
+            agent_aggregated_host_up,
+            agent_aggregated_cpu_usage_percent,
+            agent_aggregated_memory_used_bytes,
+            agent_aggregated_memory_total_bytes,
+            agent_aggregated_disk_usage_percent,
+            agent_aggregated_checks_down,
+            agent_aggregated_last_collect_timestamp_seconds,
+            agent_relay_up,
+            agent_check_latency_seconds,
+            exemplars_enabled: Arc::new(AtomicBool::new(false)),
+            exemplars: Arc::new(Mutex::new(Exemplars::new())),
+            created: Arc::new(Mutex::new(Created::new())),
+            relay_output: Arc::new(Mutex::new(Vec::new())),
+            encoded: Arc::new(Mutex::new(None)),
+            encoded_openmetrics: Arc::new(Mutex::new(None)),
+        };
+        // Счётчики без меток существуют с запуска процесса.
+        for counter in [
+            &metrics.agent_scrape_count_total,
+            &metrics.agent_host_boots_total,
+            &metrics.agent_telegram_requests_throttled_total,
+        ] {
+            metrics.note_created(counter, &[]);
+        }
+        Ok(Arc::new(metrics))
     }
 
-    pub fn update_from_state(&self, state: &State) {
+    pub fn update_from_snapshot(&self, state: &Snapshot) {
         self.agent_cpu_usage_percent.set(state.cpu_usage_percent);
         self.agent_memory_used_bytes
             .set(state.memory_used_bytes as f64);
@@ -477,108 +1013,151 @@ impl Metrics {
         self.agent_last_collect_timestamp_seconds
             .set(state.last_collect_timestamp_seconds as f64);
 
-        self.agent_disk_used_bytes.reset();
-        self.agent_disk_total_bytes.reset();
-        self.agent_disk_usage_percent.reset();
-        self.agent_temperature_celsius.reset();
-        self.agent_temperature_critical_celsius.reset();
-        self.agent_net_rx_bytes_total.reset();
-        self.agent_net_tx_bytes_total.reset();
-        self.agent_net_rx_bytes_per_sec.reset();
-        self.agent_net_tx_bytes_per_sec.reset();
-        self.agent_gpu_utilization_percent.reset();
-        self.agent_gpu_memory_used_bytes.reset();
-        self.agent_gpu_memory_total_bytes.reset();
-        self.agent_gpu_memory_usage_percent.reset();
-        self.agent_gpu_temperature_celsius.reset();
-        self.agent_sensor_value.reset();
-        self.agent_sensor_min.reset();
-        self.agent_sensor_max.reset();
-        self.agent_sensor_type_count.reset();
-        self.agent_sensor_type_avg.reset();
-        self.agent_sensor_type_min.reset();
-        self.agent_sensor_type_max.reset();
-        self.agent_sensor_parent_count.reset();
-        self.agent_sensor_parent_avg.reset();
-        self.agent_sensor_parent_max.reset();
-        self.agent_http_check_up.reset();
-        self.agent_http_check_latency_ms.reset();
-        self.agent_http_check_status_code.reset();
-        self.agent_tcp_check_up.reset();
-        self.agent_tcp_check_latency_ms.reset();
-
-        for d in &state.disks {
+        for d in state.disks.iter() {
             self.agent_disk_used_bytes
-                .with_label_values(&[&d.mount])
-                .set(d.used_bytes as f64);
+                .set(&[&d.mount], d.used_bytes as f64);
             self.agent_disk_total_bytes
-                .with_label_values(&[&d.mount])
-                .set(d.total_bytes as f64);
+                .set(&[&d.mount], d.total_bytes as f64);
             let pct = if d.total_bytes > 0 {
                 (d.used_bytes as f64 / d.total_bytes as f64) * 100.0
             } else {
                 0.0
             };
-            self.agent_disk_usage_percent
-                .with_label_values(&[&d.mount])
-                .set(pct);
+            self.agent_disk_usage_percent.set(&[&d.mount], pct);
+            self.agent_disk_read_only
+                .set(&[&d.mount], if d.writable() { 0.0 } else { 1.0 });
         }
         self.agent_disk_count.set(state.disks.len() as f64);
+        for d in state.dir_sizes.iter().filter(|d| d.error.is_none()) {
+            self.agent_dir_size_bytes
+                .set(&[&d.path], d.size_bytes as f64);
+            for e in &d.top {
+                self.agent_dir_top_entry_size_bytes
+                    .set(&[&d.path, &e.name], e.size_bytes as f64);
+            }
+        }
+        for d in &state.disk_io {
+            self.agent_disk_io_await_ms.set(&[&d.device], d.await_ms);
+            self.agent_disk_read_await_ms
+                .set(&[&d.device], d.read_await_ms);
+            self.agent_disk_write_await_ms
+                .set(&[&d.device], d.write_await_ms);
+            self.agent_disk_io_ops_per_sec
+                .set(&[&d.device], d.ops_per_sec);
+        }
 
         let mut total_rx_bps = 0_u64;
         let mut total_tx_bps = 0_u64;
-        for n in &state.net {
+        for n in state.net.iter() {
             self.agent_net_rx_bytes_total
-                .with_label_values(&[&n.iface])
-                .set(n.rx_bytes_total as f64);
+                .set(&[&n.iface], n.rx_bytes_total as f64);
             self.agent_net_tx_bytes_total
-                .with_label_values(&[&n.iface])
-                .set(n.tx_bytes_total as f64);
+                .set(&[&n.iface], n.tx_bytes_total as f64);
             self.agent_net_rx_bytes_per_sec
-                .with_label_values(&[&n.iface])
-                .set(n.rx_bytes_per_sec as f64);
+                .set(&[&n.iface], n.rx_bytes_per_sec as f64);
             self.agent_net_tx_bytes_per_sec
-                .with_label_values(&[&n.iface])
-                .set(n.tx_bytes_per_sec as f64);
+                .set(&[&n.iface], n.tx_bytes_per_sec as f64);
+            self.agent_net_rx_errors_total
+                .set(&[&n.iface], n.rx_errors_total as f64);
+            self.agent_net_tx_errors_total
+                .set(&[&n.iface], n.tx_errors_total as f64);
+            self.agent_net_rx_drops_total
+                .set(&[&n.iface], n.rx_drops_total as f64);
+            self.agent_net_tx_drops_total
+                .set(&[&n.iface], n.tx_drops_total as f64);
+            self.agent_net_errors_per_min
+                .set(&[&n.iface], n.errors_per_min);
             total_rx_bps = total_rx_bps.saturating_add(n.rx_bytes_per_sec);
             total_tx_bps = total_tx_bps.saturating_add(n.tx_bytes_per_sec);
         }
         self.agent_net_iface_count.set(state.net.len() as f64);
+        for p in &state.wireguard {
+            let labels = [p.iface.as_str(), p.public_key.as_str()];
+            if let Some(age) = p.handshake_age_secs(state.last_collect_timestamp_seconds) {
+                self.agent_wireguard_peer_handshake_age_seconds
+                    .set(&labels, age as f64);
+            }
+            self.agent_wireguard_peer_rx_bytes_total
+                .set(&labels, p.rx_bytes_total as f64);
+            self.agent_wireguard_peer_tx_bytes_total
+                .set(&labels, p.tx_bytes_total as f64);
+        }
         self.agent_net_rx_bytes_per_sec_total
             .set(total_rx_bps as f64);
         self.agent_net_tx_bytes_per_sec_total
             .set(total_tx_bps as f64);
 
-        for t in &state.temps {
+        for t in state.temps.iter() {
             self.agent_temperature_celsius
-                .with_label_values(&[&t.sensor])
-                .set(t.temperature_celsius);
+                .set(&[&t.sensor], t.temperature_celsius);
             if let Some(critical) = t.critical_temperature_celsius {
                 self.agent_temperature_critical_celsius
-                    .with_label_values(&[&t.sensor])
-                    .set(critical);
+                    .set(&[&t.sensor], critical);
+            }
+            if let Some(device) = &t.device {
+                self.agent_disk_temperature_celsius
+                    .set(&[device], t.temperature_celsius);
             }
         }
         self.agent_temperature_sensor_count
             .set(state.temps.len() as f64);
+        if let Some(cpu) = state.cpu_temperature_celsius {
+            self.agent_cpu_temperature_celsius.set(&[], cpu);
+        }
+        if let Some(steal) = &state.cpu_steal {
+            self.agent_cpu_steal_percent.set(&[], steal.steal_percent);
+        }
+        if let Some(hypervisor) = &state.virtualization {
+            self.agent_virtualization_info.set(&[hypervisor], 1.0);
+        }
+        if let Some(sync) = &state.time_sync {
+            self.agent_time_synchronized
+                .set(&[&sync.source], if sync.synchronized { 1.0 } else { 0.0 });
+            if let Some(offset) = sync.offset_seconds {
+                self.agent_time_offset_seconds.set(&[&sync.source], offset);
+            }
+        }
+        if let Some(cg) = &state.cgroup {
+            if let Some(cores) = cg.cpu_limit_cores {
+                self.agent_cgroup_cpu_limit_cores.set(&[], cores);
+            }
+            if let Some(limit) = cg.memory_limit_bytes {
+                self.agent_cgroup_memory_limit_bytes.set(&[], limit as f64);
+            }
+            if let Some(pct) = cg.cpu_percent {
+                self.agent_cgroup_cpu_usage_percent.set(&[], pct);
+            }
+        }
+        for (domain, value) in max_temperature_by_domain(state) {
+            self.agent_max_temperature_celsius.set(&[domain], value);
+        }
+        // На других ОС серий нет вовсе, а не нули.
+        if let Some(p) = &state.page_file {
+            self.agent_memory_commit_used_bytes
+                .set(&[], p.commit_used_bytes as f64);
+            self.agent_memory_commit_limit_bytes
+                .set(&[], p.commit_limit_bytes as f64);
+            self.agent_pagefile_used_bytes
+                .set(&[], p.pagefile_used_bytes as f64);
+            self.agent_pagefile_total_bytes
+                .set(&[], p.pagefile_total_bytes as f64);
+        }
+        for (name, health) in &state.collectors {
+            self.agent_collector_consecutive_failures
+                .set(&[name], health.consecutive_failures as f64);
+        }
 
         self.agent_gpu_count.set(state.gpus.len() as f64);
         for g in &state.gpus {
             let labels: [&str; 2] = [&g.id, &g.name];
             if let Some(v) = g.utilization_percent {
-                self.agent_gpu_utilization_percent
-                    .with_label_values(&labels)
-                    .set(v);
+                self.agent_gpu_utilization_percent.set(&labels, v);
             }
             if let Some(v) = g.memory_used_bytes {
-                self.agent_gpu_memory_used_bytes
-                    .with_label_values(&labels)
-                    .set(v as f64);
+                self.agent_gpu_memory_used_bytes.set(&labels, v as f64);
             }
             if let Some(v) = g.memory_total_bytes {
-                self.agent_gpu_memory_total_bytes
-                    .with_label_values(&labels)
-                    .set(v as f64);
+                self.agent_gpu_memory_total_bytes.set(&labels, v as f64);
             }
             if let (Some(used), Some(total)) = (g.memory_used_bytes, g.memory_total_bytes) {
                 let pct = if total > 0 {
@@ -586,35 +1165,29 @@ impl Metrics {
                 } else {
                     0.0
                 };
-                self.agent_gpu_memory_usage_percent
-                    .with_label_values(&labels)
-                    .set(pct);
+                self.agent_gpu_memory_usage_percent.set(&labels, pct);
             }
             if let Some(v) = g.temperature_celsius {
-                self.agent_gpu_temperature_celsius
-                    .with_label_values(&labels)
-                    .set(v);
+                self.agent_gpu_temperature_celsius.set(&labels, v);
             }
         }
 
         self.agent_sensor_count.set(state.sensors.len() as f64);
         let mut grouped: HashMap<&str, (f64, u64, f64, f64)> = HashMap::new();
         let mut grouped_parent: HashMap<(String, String), (f64, u64, f64)> = HashMap::new();
-        for s in &state.sensors {
+        for s in state.sensors.iter() {
             let labels = [
                 s.sensor_type.as_str(),
                 s.name.as_str(),
                 s.identifier.as_str(),
                 s.parent.as_str(),
             ];
-            self.agent_sensor_value
-                .with_label_values(&labels)
-                .set(s.value);
+            self.agent_sensor_value.set(&labels, s.value);
             if let Some(v) = s.min {
-                self.agent_sensor_min.with_label_values(&labels).set(v);
+                self.agent_sensor_min.set(&labels, v);
             }
             if let Some(v) = s.max {
-                self.agent_sensor_max.with_label_values(&labels).set(v);
+                self.agent_sensor_max.set(&labels, v);
             }
 
             let entry = grouped.entry(s.sensor_type.as_str()).or_insert((
@@ -644,41 +1217,41 @@ impl Metrics {
         }
         for (sensor_type, (sum, count, max_value, min_value)) in grouped {
             self.agent_sensor_type_count
-                .with_label_values(&[sensor_type])
-                .set(count as f64);
-            self.agent_sensor_type_avg
-                .with_label_values(&[sensor_type])
-                .set(if count > 0 { sum / count as f64 } else { 0.0 });
-            self.agent_sensor_type_min
-                .with_label_values(&[sensor_type])
-                .set(if min_value.is_finite() {
+                .set(&[sensor_type], count as f64);
+            self.agent_sensor_type_avg.set(
+                &[sensor_type],
+                if count > 0 { sum / count as f64 } else { 0.0 },
+            );
+            self.agent_sensor_type_min.set(
+                &[sensor_type],
+                if min_value.is_finite() {
                     min_value
                 } else {
                     0.0
-                });
-            self.agent_sensor_type_max
-                .with_label_values(&[sensor_type])
-                .set(if max_value.is_finite() {
+                },
+            );
+            self.agent_sensor_type_max.set(
+                &[sensor_type],
+                if max_value.is_finite() {
                     max_value
                 } else {
                     0.0
-                });
+                },
+            );
         }
         for ((sensor_type, parent), (sum, count, max_value)) in grouped_parent {
             let labels = [sensor_type.as_str(), parent.as_str()];
-            self.agent_sensor_parent_count
-                .with_label_values(&labels)
-                .set(count as f64);
+            self.agent_sensor_parent_count.set(&labels, count as f64);
             self.agent_sensor_parent_avg
-                .with_label_values(&labels)
-                .set(if count > 0 { sum / count as f64 } else { 0.0 });
-            self.agent_sensor_parent_max
-                .with_label_values(&labels)
-                .set(if max_value.is_finite() {
+                .set(&labels, if count > 0 { sum / count as f64 } else { 0.0 });
+            self.agent_sensor_parent_max.set(
+                &labels,
+                if max_value.is_finite() {
                     max_value
                 } else {
                     0.0
-                });
+                },
+            );
         }
 
         let http_total = state.checks.http.len() as f64;
@@ -695,12 +1268,17 @@ impl Metrics {
         self.agent_tcp_checks_up.set(tcp_up);
         self.agent_tcp_checks_down.set(tcp_down);
 
-        self.agent_checks_total.set(http_total + tcp_total);
-        self.agent_checks_up.set(http_up + tcp_up);
-        self.agent_checks_down.set(http_down + tcp_down);
-        let checks_total = http_total + tcp_total;
+        let ssh_total = state.checks.ssh.len() as f64;
+        let ssh_up = state.checks.ssh.iter().filter(|c| c.up).count() as f64;
+        let ssh_down = (ssh_total - ssh_up).max(0.0);
+
+        self.agent_checks_total
+            .set(http_total + tcp_total + ssh_total);
+        self.agent_checks_up.set(http_up + tcp_up + ssh_up);
+        self.agent_checks_down.set(http_down + tcp_down + ssh_down);
+        let checks_total = http_total + tcp_total + ssh_total;
         let down_ratio = if checks_total > 0.0 {
-            ((http_down + tcp_down) / checks_total) * 100.0
+            ((http_down + tcp_down + ssh_down) / checks_total) * 100.0
         } else {
             0.0
         };
@@ -708,52 +1286,520 @@ impl Metrics {
 
         for c in &state.checks.http {
             self.agent_http_check_up
-                .with_label_values(&[&c.name])
-                .set(if c.up { 1.0 } else { 0.0 });
+                .set(&[&c.name], if c.up { 1.0 } else { 0.0 });
             self.agent_http_check_latency_ms
-                .with_label_values(&[&c.name])
-                .set(c.latency_ms as f64);
+                .set(&[&c.name], c.latency_ms as f64);
             self.agent_http_check_status_code
-                .with_label_values(&[&c.name])
-                .set(c.status_code as f64);
+                .set(&[&c.name], c.status_code as f64);
         }
 
         for c in &state.checks.tcp {
             self.agent_tcp_check_up
-                .with_label_values(&[&c.name])
-                .set(if c.up { 1.0 } else { 0.0 });
+                .set(&[&c.name], if c.up { 1.0 } else { 0.0 });
             self.agent_tcp_check_latency_ms
-                .with_label_values(&[&c.name])
-                .set(c.latency_ms as f64);
+                .set(&[&c.name], c.latency_ms as f64);
+            if let Some(handshake_ms) = c.tls_handshake_ms {
+                self.agent_tcp_check_tls_handshake_ms
+                    .set(&[&c.name], handshake_ms as f64);
+            }
+            if let Some(valid) = c.tls_cert_valid {
+                self.agent_tcp_check_tls_cert_valid
+                    .set(&[&c.name], if valid { 1.0 } else { 0.0 });
+            }
+        }
+
+        for c in &state.checks.ssh {
+            self.agent_ssh_check_up
+                .set(&[&c.name], if c.up { 1.0 } else { 0.0 });
+            self.agent_ssh_check_latency_ms
+                .set(&[&c.name], c.latency_ms as f64);
+            if let Some(matches) = c.host_key_matches {
+                self.agent_ssh_check_host_key_match
+                    .set(&[&c.name], if matches { 1.0 } else { 0.0 });
+            }
+        }
+
+        self.observe_check_latency(state);
+        for latency in &state.check_latency {
+            for (quantile, value) in latency.quantiles() {
+                self.agent_check_latency_quantile_ms
+                    .set(&[&latency.kind, &latency.name, quantile], value as f64);
+            }
         }
 
         let now = now_unix();
         let uptime = now.saturating_sub(state.started_at_unix) as f64;
         self.agent_uptime_seconds.set(uptime);
+
+        // Серии пропавших дисков, датчиков и проверок удаляются, остальные обновляются на месте.
+        for gauge in [
+            &self.agent_disk_used_bytes,
+            &self.agent_disk_total_bytes,
+            &self.agent_disk_usage_percent,
+            &self.agent_disk_temperature_celsius,
+            &self.agent_disk_read_only,
+            &self.agent_collector_consecutive_failures,
+            &self.agent_memory_commit_used_bytes,
+            &self.agent_memory_commit_limit_bytes,
+            &self.agent_pagefile_used_bytes,
+            &self.agent_pagefile_total_bytes,
+            &self.agent_temperature_celsius,
+            &self.agent_temperature_critical_celsius,
+            &self.agent_cpu_temperature_celsius,
+            &self.agent_cpu_steal_percent,
+            &self.agent_virtualization_info,
+            &self.agent_time_synchronized,
+            &self.agent_time_offset_seconds,
+            &self.agent_cgroup_cpu_limit_cores,
+            &self.agent_cgroup_memory_limit_bytes,
+            &self.agent_cgroup_cpu_usage_percent,
+            &self.agent_max_temperature_celsius,
+            &self.agent_net_rx_bytes_total,
+            &self.agent_net_tx_bytes_total,
+            &self.agent_net_rx_bytes_per_sec,
+            &self.agent_net_tx_bytes_per_sec,
+            &self.agent_net_rx_errors_total,
+            &self.agent_net_tx_errors_total,
+            &self.agent_net_rx_drops_total,
+            &self.agent_net_tx_drops_total,
+            &self.agent_disk_io_await_ms,
+            &self.agent_disk_read_await_ms,
+            &self.agent_disk_write_await_ms,
+            &self.agent_disk_io_ops_per_sec,
+            &self.agent_dir_size_bytes,
+            &self.agent_dir_top_entry_size_bytes,
+            &self.agent_net_errors_per_min,
+            &self.agent_wireguard_peer_handshake_age_seconds,
+            &self.agent_wireguard_peer_rx_bytes_total,
+            &self.agent_wireguard_peer_tx_bytes_total,
+            &self.agent_gpu_utilization_percent,
+            &self.agent_gpu_memory_used_bytes,
+            &self.agent_gpu_memory_total_bytes,
+            &self.agent_gpu_memory_usage_percent,
+            &self.agent_gpu_temperature_celsius,
+            &self.agent_sensor_value,
+            &self.agent_sensor_min,
+            &self.agent_sensor_max,
+            &self.agent_sensor_type_count,
+            &self.agent_sensor_type_avg,
+            &self.agent_sensor_type_min,
+            &self.agent_sensor_type_max,
+            &self.agent_sensor_parent_count,
+            &self.agent_sensor_parent_avg,
+            &self.agent_sensor_parent_max,
+            &self.agent_http_check_up,
+            &self.agent_http_check_latency_ms,
+            &self.agent_http_check_status_code,
+            &self.agent_tcp_check_up,
+            &self.agent_tcp_check_latency_ms,
+            &self.agent_tcp_check_tls_handshake_ms,
+            &self.agent_tcp_check_tls_cert_valid,
+            &self.agent_ssh_check_up,
+            &self.agent_ssh_check_latency_ms,
+            &self.agent_ssh_check_host_key_match,
+            &self.agent_check_latency_quantile_ms,
+        ] {
+            gauge.retain_seen();
+        }
+        self.invalidate();
     }
 
-    pub fn inc_scrape_count(&self) {
-        self.agent_scrape_count_total.inc();
+    pub fn update_aggregated(&self, hosts: &[HostState]) {
+        for host in hosts {
+            let name = host.name.as_str();
+            self.agent_aggregated_host_up
+                .set(&[name], if host.up { 1.0 } else { 0.0 });
+            // Пока хост недоступен, его последние значения не выдаются за текущие.
+            let Some(state) = host.state.as_ref().filter(|_| host.up) else {
+                continue;
+            };
+            self.agent_aggregated_cpu_usage_percent
+                .set(&[name], state.cpu_usage_percent);
+            self.agent_aggregated_memory_used_bytes
+                .set(&[name], state.memory_used_bytes as f64);
+            self.agent_aggregated_memory_total_bytes
+                .set(&[name], state.memory_total_bytes as f64);
+            for d in state.disks.iter() {
+                let pct = if d.total_bytes > 0 {
+                    (d.used_bytes as f64 / d.total_bytes as f64) * 100.0
+                } else {
+                    0.0
+                };
+                self.agent_aggregated_disk_usage_percent
+                    .set(&[name, &d.mount], pct);
+            }
+            let down = state.checks.http.iter().filter(|c| !c.up).count()
+                + state.checks.tcp.iter().filter(|c| !c.up).count()
+                + state.checks.ssh.iter().filter(|c| !c.up).count();
+            self.agent_aggregated_checks_down.set(&[name], down as f64);
+            self.agent_aggregated_last_collect_timestamp_seconds
+                .set(&[name], state.last_collect_timestamp_seconds as f64);
+        }
+        // Исчезнувшие хосты и точки монтирования удаляются, остальные серии обновляются на месте.
+        for gauge in [
+            &self.agent_aggregated_host_up,
+            &self.agent_aggregated_cpu_usage_percent,
+            &self.agent_aggregated_memory_used_bytes,
+            &self.agent_aggregated_memory_total_bytes,
+            &self.agent_aggregated_disk_usage_percent,
+            &self.agent_aggregated_checks_down,
+            &self.agent_aggregated_last_collect_timestamp_seconds,
+        ] {
+            gauge.retain_seen();
+        }
+        self.invalidate();
     }
 
     pub fn inc_collect_error(&self, collector: &str) {
+        self.note_created(&self.agent_collect_errors_total, &[collector]);
         self.agent_collect_errors_total
             .with_label_values(&[collector])
             .inc();
+        self.invalidate();
     }
 
     pub fn inc_alert_sent(&self, kind: &str) {
+        self.note_created(&self.agent_alerts_sent_total, &[kind]);
         self.agent_alerts_sent_total
             .with_label_values(&[kind])
             .inc();
+        self.invalidate();
+    }
+
+    pub fn inc_notification_failed(&self, channel: &str) {
+        self.note_created(&self.agent_notifications_failed_total, &[channel]);
+        self.agent_notifications_failed_total
+            .with_label_values(&[channel])
+            .inc();
+        self.invalidate();
+    }
+
+    pub fn inc_telegram_api_error(&self, reason: &str) {
+        self.note_created(&self.agent_telegram_api_errors_total, &[reason]);
+        self.agent_telegram_api_errors_total
+            .with_label_values(&[reason])
+            .inc();
+        self.invalidate();
+    }
+
+    pub fn inc_telegram_throttled(&self) {
+        self.agent_telegram_requests_throttled_total.inc();
+        self.invalidate();
+    }
+
+    pub fn set_telegram_health(&self, last_success: Option<i64>, failing_since: Option<i64>) {
+        self.agent_telegram_last_success_timestamp_seconds
+            .set(last_success.unwrap_or(0) as f64);
+        self.agent_telegram_failing_since_timestamp_seconds
+            .set(failing_since.unwrap_or(0) as f64);
+        self.invalidate();
+    }
+
+    pub fn inc_config_reload(&self, result: &str) {
+        self.note_created(&self.agent_config_reloads_total, &[result]);
+        self.agent_config_reloads_total
+            .with_label_values(&[result])
+            .inc();
+        self.invalidate();
+    }
+
+    pub fn inc_host_boot(&self) {
+        self.agent_host_boots_total.inc();
+        self.invalidate();
+    }
+
+    pub fn update_relay(&self, exporters: &[(&str, bool)], output: Vec<u8>) {
+        for (exporter, up) in exporters {
+            self.agent_relay_up
+                .set(&[exporter], if *up { 1.0 } else { 0.0 });
+        }
+        // Удалённые из конфигурации экспортеры не должны оставаться в выдаче.
+        self.agent_relay_up.retain_seen();
+        if let Ok(mut guard) = self.relay_output.lock() {
+            *guard = output;
+        }
+        self.invalidate();
+    }
+
+    pub fn set_exemplars(&self, enabled: bool) {
+        if self.exemplars_enabled.swap(enabled, Ordering::Relaxed) != enabled {
+            self.invalidate();
+        }
+    }
+
+    // Результаты из кэша проверки (interval_secs) не наблюдаются повторно, иначе исказили бы гистограмму.
+    fn observe_check_latency(&self, state: &Snapshot) {
+        let checks = &state.checks;
+        let runs = checks
+            .http
+            .iter()
+            .map(|c| {
+                let code = Some(c.status_code);
+                ("http", c.name.as_str(), c.up, c.latency_ms, code, c.cached)
+            })
+            .chain(
+                checks
+                    .tcp
+                    .iter()
+                    .map(|c| ("tcp", c.name.as_str(), c.up, c.latency_ms, None, c.cached)),
+            )
+            .chain(
+                checks
+                    .ssh
+                    .iter()
+                    .map(|c| ("ssh", c.name.as_str(), c.up, c.latency_ms, None, c.cached)),
+            )
+            .collect::<Vec<_>>();
+
+        let mut exemplars = self
+            .exemplars
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let live = runs
+            .iter()
+            .map(|(kind, name, ..)| latency_series_key(kind, name))
+            .collect::<HashSet<_>>();
+        exemplars.retain(|key, _| {
+            if live.contains(key) {
+                return true;
+            }
+            let labels = key.1.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>();
+            // Метки ключа отсортированы по имени: check, kind.
+            let _ = self
+                .agent_check_latency_seconds
+                .remove_label_values(&[labels[1], labels[0]]);
+            self.created
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(key);
+            false
+        });
+
+        let buckets = prometheus::DEFAULT_BUCKETS;
+        for (kind, name, up, latency_ms, status_code, cached) in runs {
+            if cached {
+                continue;
+            }
+            let seconds = latency_ms as f64 / 1000.0;
+            self.note_created(&self.agent_check_latency_seconds, &[kind, name]);
+            self.agent_check_latency_seconds
+                .with_label_values(&[kind, name])
+                .observe(seconds);
+            let slots = exemplars
+                .entry(latency_series_key(kind, name))
+                .or_insert_with(|| vec![None; buckets.len() + 1]);
+            let idx = buckets
+                .iter()
+                .position(|upper| seconds <= *upper)
+                .unwrap_or(buckets.len());
+            let mut labels = vec![("up", if up { "1" } else { "0" }.to_string())];
+            if let Some(code) = status_code {
+                labels.push(("status_code", code.to_string()));
+            }
+            slots[idx] = Some(Exemplar {
+                labels,
+                value: seconds,
+                timestamp: state.last_collect_timestamp_seconds as f64,
+            });
+        }
+    }
+
+    // prometheus не хранит время появления серии: оно запоминается при первом обращении к ней.
+    fn note_created(&self, collector: &impl Collector, values: &[&str]) {
+        let Some(desc) = collector.desc().into_iter().next() else {
+            return;
+        };
+        let mut labels = desc
+            .variable_labels
+            .iter()
+            .cloned()
+            .zip(values.iter().map(|v| v.to_string()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as f64 / 1000.0)
+            .unwrap_or(0.0);
+        self.created
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry((desc.fq_name.clone(), labels))
+            .or_insert(now);
+    }
+
+    // Счётчик опросов не входит в реестр и кэш: он дописывается к закэшированному ответу при каждом
+    // опросе, иначе либо отставал бы, либо каждый опрос кодировал бы всё заново.
+    pub fn inc_scrape_count(&self) {
+        self.agent_scrape_count_total.inc();
+    }
+
+    fn with_scrape_count(
+        &self,
+        cached: &[u8],
+        encode: impl Fn(&[MetricFamily]) -> Vec<u8>,
+    ) -> Bytes {
+        let tail = encode(&self.agent_scrape_count_total.collect());
+        let mut buf = BytesMut::with_capacity(cached.len() + tail.len());
+        buf.extend_from_slice(cached);
+        buf.extend_from_slice(&tail);
+        buf.freeze()
+    }
+
+    pub fn encode_metrics(&self) -> Result<Bytes, prometheus::Error> {
+        let mut cached = self
+            .encoded
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let encode_text = |families: &[MetricFamily]| {
+            let mut buf = Vec::new();
+            TextEncoder::new().encode(families, &mut buf).map(|()| buf)
+        };
+        let encoded = match cached.as_ref() {
+            Some(encoded) => encoded.clone(),
+            None => {
+                let mut buf = encode_text(&self.registry.gather())?;
+                if let Ok(relay) = self.relay_output.lock() {
+                    buf.extend_from_slice(&relay);
+                }
+                cached.insert(Bytes::from(buf)).clone()
+            }
+        };
+        drop(cached);
+        Ok(self.with_scrape_count(&encoded, |families| {
+            encode_text(families).unwrap_or_default()
+        }))
+    }
+
+    pub fn encode_openmetrics(&self) -> Bytes {
+        let mut cached = self
+            .encoded_openmetrics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let encoded = match cached.as_ref() {
+            Some(encoded) => encoded.clone(),
+            None => cached.insert(self.encode_openmetrics_uncached()).clone(),
+        };
+        drop(cached);
+        // # EOF должен быть последней строкой, поэтому счётчик опросов идёт перед ним.
+        self.with_scrape_count(&encoded, |families| {
+            let created = self
+                .created
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let mut text = openmetrics::encode(families, &Exemplars::new(), &created);
+            text.push_str("# EOF\n");
+            text.into_bytes()
+        })
+    }
+
+    fn encode_openmetrics_uncached(&self) -> Bytes {
+        let empty = Exemplars::new();
+        let exemplars = self
+            .exemplars
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let exemplars = if self.exemplars_enabled.load(Ordering::Relaxed) {
+            &*exemplars
+        } else {
+            &empty
+        };
+        let created = self
+            .created
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut text = openmetrics::encode(&self.registry.gather(), exemplars, &created);
+        drop(created);
+        // Ретранслируемые экспортеры отдают классический текстовый формат: до # EOF он переводится в OpenMetrics.
+        if let Ok(relay) = self.relay_output.lock() {
+            text.push_str(&openmetrics::from_text_format(&String::from_utf8_lossy(
+                &relay,
+            )));
+        }
+        Bytes::from(text)
+    }
+
+    fn invalidate(&self) {
+        for cache in [&self.encoded, &self.encoded_openmetrics] {
+            *cache
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        }
+    }
+}
+
+fn latency_series_key(kind: &str, check: &str) -> (String, Vec<(String, String)>) {
+    (
+        "agent_check_latency_seconds".to_string(),
+        vec![
+            ("check".to_string(), check.to_string()),
+            ("kind".to_string(), kind.to_string()),
+        ],
+    )
+}
+
+// GaugeVec, который между обновлениями удаляет только пропавшие серии, а не пересоздаёт все.
+#[derive(Clone)]
+pub struct TrackedGaugeVec {
+    gauge: GaugeVec,
+    series: Arc<Mutex<TrackedSeries>>,
+}
+
+#[derive(Default)]
+struct TrackedSeries {
+    live: HashMap<u64, Vec<String>>,
+    seen: HashSet<u64>,
+}
+
+impl TrackedGaugeVec {
+    fn new(opts: Opts, label_names: &[&str]) -> Result<Self, prometheus::Error> {
+        Ok(Self {
+            gauge: GaugeVec::new(opts, label_names)?,
+            series: Arc::new(Mutex::new(TrackedSeries::default())),
+        })
+    }
+
+    pub fn set(&self, labels: &[&str], value: f64) {
+        self.gauge.with_label_values(labels).set(value);
+        let mut hasher = DefaultHasher::new();
+        labels.hash(&mut hasher);
+        let key = hasher.finish();
+        let mut series = self
+            .series
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        series.seen.insert(key);
+        series
+            .live
+            .entry(key)
+            .or_insert_with(|| labels.iter().map(|l| l.to_string()).collect());
+    }
+
+    // Удаляет серии, которые не выставлялись с прошлого вызова.
+    fn retain_seen(&self) {
+        let mut series = self
+            .series
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let TrackedSeries { live, seen } = &mut *series;
+        live.retain(|key, labels| {
+            if seen.contains(key) {
+                return true;
+            }
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            let _ = self.gauge.remove_label_values(&labels);
+            false
+        });
+        seen.clear();
+    }
+}
+
+impl Collector for TrackedGaugeVec {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
     }
 
-    pub fn encode_metrics(&self) -> Result<Vec<u8>, prometheus::Error> {
-        let mut buf = Vec::new();
-        let encoder = TextEncoder::new();
-        let mf = self.registry.gather();
-        encoder.encode(&mf, &mut buf)?;
-        Ok(buf)
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.gauge.collect()
     }
 }
 
@@ -0,0 +1,1704 @@
+use crate::actions::run_command;
+use crate::aggregator::FleetAlert;
+use crate::config::{
+    AlertmanagerConfig, ChatWebhookConfig, DeliveryConfig, EmailConfig, GotifyConfig,
+    NotifierFilter, NotifiersConfig, NtfyConfig, PushoverConfig, ScriptHookConfig,
+    TelegramNotifierConfig, WebhookConfig,
+};
+use crate::events::{next_event, Alert, Event};
+use crate::external::ExternalAlert;
+use crate::metrics::Metrics;
+use crate::routing::Router;
+use crate::rules::RuleAlert;
+use crate::smtp::{self, SmtpError};
+use crate::state::{
+    AlertEvent, AlertEventKind, AlertSeverity, CheckKind, CollectorAlert, CpuStealAlert,
+    DirSizeAlert, DiskForecast, DiskLatencyAlert, HostReboot, MountChange, NetErrorAlert,
+    PageFileAlert, ReadOnlyMount, ResourceAlert, SensorAlert, TempRiseAlert, TimeSyncAlert,
+    WireguardAlert,
+};
+use reqwest::Client;
+use serde::Serialize;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const NOTIFICATION_KINDS: [&str; 20] = [
+    "check",
+    "resource",
+    "sensor",
+    "disk_forecast",
+    "rule",
+    "mount",
+    "read_only",
+    "disk_latency",
+    "dir_size",
+    "net_errors",
+    "cpu_steal",
+    "time_sync",
+    "pagefile",
+    "wireguard",
+    "temp_rise",
+    "host_reboot",
+    "watchdog",
+    "collector",
+    "fleet",
+    "external",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub kind: &'static str,
+    pub subject: String,
+    pub severity: AlertSeverity,
+    pub recovered: bool,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    host: Option<&'a str>,
+    ts_unix: i64,
+    #[serde(flatten)]
+    notification: &'a Notification,
+}
+
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("ошибка шаблона: {0}")]
+    Template(String),
+    #[error("ошибка запроса: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("неожиданный статус ответа {0}")]
+    Status(u16),
+    #[error("ошибка SMTP: {0}")]
+    Smtp(#[from] SmtpError),
+    #[error("не найден токен бота")]
+    MissingToken,
+}
+
+impl Notification {
+    pub fn from_alert_event(event: &AlertEvent) -> Self {
+        let check_kind = match event.check_id.kind {
+            CheckKind::Http => "HTTP",
+            CheckKind::Tcp => "TCP",
+            CheckKind::Ssh => "SSH",
+        };
+        let label = match event.kind {
+            AlertEventKind::Down => "недоступен",
+            AlertEventKind::Repeat => "недоступен (повтор)",
+            AlertEventKind::Recovered => "восстановлен",
+        };
+        Self {
+            kind: "check",
+            subject: event.check_id.name.clone(),
+            severity: event.severity,
+            recovered: matches!(event.kind, AlertEventKind::Recovered),
+            message: match &event.upstream {
+                Some(parent) => format!(
+                    "{check_kind} '{}' {label} (недоступна вышестоящая проверка '{parent}')",
+                    event.check_id.name
+                ),
+                None => format!("{check_kind} '{}' {label}", event.check_id.name),
+            },
+        }
+    }
+
+    pub fn from_resource_alert(alert: &ResourceAlert) -> Self {
+        let subject = alert.kind.alert_key(alert.subject.as_deref());
+        let mut message = if alert.recovered {
+            format!("{subject} вернулся в норму: {:.1}", alert.value)
+        } else {
+            format!("{subject} превысил порог: {:.1}", alert.value)
+        };
+        let processes = alert
+            .gpu_context
+            .iter()
+            .flat_map(|c| &c.processes)
+            .map(|p| match p.sm_percent {
+                Some(sm) => format!("{} ({}) {sm:.0}%", p.name, p.pid),
+                None => format!("{} ({})", p.name, p.pid),
+            })
+            .collect::<Vec<_>>();
+        if !processes.is_empty() {
+            message.push_str(&format!("; процессы: {}", processes.join(", ")));
+        }
+        Self {
+            kind: "resource",
+            subject,
+            severity: alert.severity,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_sensor_alert(alert: &SensorAlert) -> Self {
+        let subject = alert.label.clone().unwrap_or_else(|| alert.sensor.clone());
+        let message = if alert.recovered {
+            format!("Датчик {subject} в норме: {:.1} °C", alert.value)
+        } else {
+            format!(
+                "Датчик {subject}: {:.1} °C (порог {:.1} °C)",
+                alert.value, alert.threshold
+            )
+        };
+        Self {
+            kind: "sensor",
+            subject,
+            severity: AlertSeverity::Critical,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_disk_forecast(forecast: &DiskForecast) -> Self {
+        Self {
+            kind: "disk_forecast",
+            subject: forecast.mount.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: false,
+            message: format!(
+                "Диск {} ({:.1}%) заполнится примерно через {}",
+                forecast.mount,
+                forecast.used_percent,
+                humantime::format_duration(Duration::from_secs(forecast.seconds_until_full))
+            ),
+        }
+    }
+
+    pub fn from_rule_alert(alert: &RuleAlert) -> Self {
+        let text = alert.message.clone().unwrap_or_else(|| alert.expr.clone());
+        Self {
+            kind: "rule",
+            subject: alert.name.clone(),
+            severity: alert.severity,
+            recovered: alert.recovered,
+            message: if alert.recovered {
+                format!("Правило '{}' больше не срабатывает", alert.name)
+            } else {
+                format!("Правило '{}': {text}", alert.name)
+            },
+        }
+    }
+
+    pub fn from_host_reboot(reboot: &HostReboot) -> Self {
+        Self {
+            kind: "host_reboot",
+            subject: "host".to_string(),
+            severity: AlertSeverity::Warning,
+            recovered: false,
+            message: format!(
+                "Хост перезагружен, аптайм до перезагрузки {}",
+                humantime::format_duration(Duration::from_secs(reboot.previous_uptime_secs))
+            ),
+        }
+    }
+
+    pub fn from_mount_change(change: &MountChange) -> Self {
+        let (severity, message) = if change.appeared {
+            (
+                AlertSeverity::Warning,
+                format!("Подключён новый диск {}", change.mount),
+            )
+        } else {
+            (
+                AlertSeverity::Critical,
+                format!("Диск {} пропал", change.mount),
+            )
+        };
+        Self {
+            kind: "mount",
+            subject: change.mount.clone(),
+            severity,
+            recovered: false,
+            message,
+        }
+    }
+
+    pub fn from_read_only_mount(mount: &ReadOnlyMount) -> Self {
+        let message = if mount.recovered {
+            format!("Диск {} снова доступен для записи", mount.mount)
+        } else if let Some(err) = &mount.write_error {
+            format!("Диск {}: пробная запись не удалась: {err}", mount.mount)
+        } else {
+            format!("Диск {} перемонтирован только для чтения", mount.mount)
+        };
+        Self {
+            kind: "read_only",
+            subject: mount.mount.clone(),
+            severity: AlertSeverity::Critical,
+            recovered: mount.recovered,
+            message,
+        }
+    }
+
+    pub fn from_disk_latency_alert(alert: &DiskLatencyAlert) -> Self {
+        let message = if alert.recovered {
+            format!(
+                "Задержка диска {} в норме: {:.1} мс",
+                alert.device, alert.await_ms
+            )
+        } else {
+            format!(
+                "Диск {}: среднее время операции {:.1} мс (порог {:.1} мс)",
+                alert.device, alert.await_ms, alert.threshold_ms
+            )
+        };
+        Self {
+            kind: "disk_latency",
+            subject: alert.device.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_dir_size_alert(alert: &DirSizeAlert) -> Self {
+        let gib = |bytes: u64| bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+        let message = if alert.recovered {
+            format!(
+                "Каталог {} уменьшился: {:.1} ГБ",
+                alert.path,
+                gib(alert.size_bytes)
+            )
+        } else {
+            format!(
+                "Каталог {} занимает {:.1} ГБ (порог {:.1} ГБ)",
+                alert.path,
+                gib(alert.size_bytes),
+                gib(alert.threshold_bytes)
+            )
+        };
+        Self {
+            kind: "dir_size",
+            subject: alert.path.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_net_error_alert(alert: &NetErrorAlert) -> Self {
+        let message = if alert.recovered {
+            format!("Ошибки на интерфейсе {} прекратились", alert.iface)
+        } else {
+            format!(
+                "Интерфейс {}: {:.1} ошибок/мин (порог {:.1})",
+                alert.iface, alert.errors_per_min, alert.threshold
+            )
+        };
+        Self {
+            kind: "net_errors",
+            subject: alert.iface.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_cpu_steal_alert(alert: &CpuStealAlert) -> Self {
+        let hypervisor = alert
+            .virtualization
+            .as_deref()
+            .map(|v| format!(" ({v})"))
+            .unwrap_or_default();
+        let message = if alert.recovered {
+            format!(
+                "Steal CPU{hypervisor} снизился: {:.1}%",
+                alert.steal_percent
+            )
+        } else {
+            format!(
+                "Гипервизор{hypervisor} отнимает {:.1}% процессорного времени (порог {:.1}%)",
+                alert.steal_percent, alert.threshold
+            )
+        };
+        Self {
+            kind: "cpu_steal",
+            subject: "cpu_steal".to_string(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_time_sync_alert(alert: &TimeSyncAlert) -> Self {
+        let message = if alert.recovered {
+            format!("Время синхронизировано ({})", alert.source)
+        } else if !alert.synchronized {
+            format!("Часы не синхронизированы ({})", alert.source)
+        } else {
+            format!(
+                "Смещение часов {:+.1} мс (допустимо {:.1} мс)",
+                alert.offset_seconds.unwrap_or_default() * 1000.0,
+                alert.max_offset_ms.unwrap_or_default()
+            )
+        };
+        Self {
+            kind: "time_sync",
+            subject: alert.source.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_pagefile_alert(alert: &PageFileAlert) -> Self {
+        let message = if alert.recovered {
+            format!("Файл подкачки освободился: {:.1}%", alert.usage_percent)
+        } else {
+            format!(
+                "Файл подкачки заполнен на {:.1}% (порог {:.1}%)",
+                alert.usage_percent, alert.threshold
+            )
+        };
+        Self {
+            kind: "pagefile",
+            subject: "pagefile".to_string(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_wireguard_alert(alert: &WireguardAlert) -> Self {
+        let peer = crate::state::wireguard_short_key(&alert.public_key);
+        let message = if alert.recovered {
+            format!(
+                "WireGuard {}: пир {peer} снова на связи, рукопожатие {} с назад",
+                alert.iface, alert.handshake_age_secs
+            )
+        } else {
+            format!(
+                "WireGuard {}: нет рукопожатия с пиром {peer}{} уже {} с (порог {} с)",
+                alert.iface,
+                alert
+                    .endpoint
+                    .as_deref()
+                    .map(|e| format!(" ({e})"))
+                    .unwrap_or_default(),
+                alert.handshake_age_secs,
+                alert.threshold_secs
+            )
+        };
+        Self {
+            kind: "wireguard",
+            subject: format!("{}:{peer}", alert.iface),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_temp_rise_alert(alert: &TempRiseAlert) -> Self {
+        let message = if alert.recovered {
+            format!(
+                "Рост температуры {} прекратился: {:.1} °C",
+                alert.sensor, alert.temperature_celsius
+            )
+        } else {
+            format!(
+                "Температура {} растёт на {:.1} °C/мин (порог {:.1}), сейчас {:.1} °C",
+                alert.sensor,
+                alert.rate_celsius_per_min,
+                alert.threshold,
+                alert.temperature_celsius
+            )
+        };
+        Self {
+            kind: "temp_rise",
+            subject: alert.sensor.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn from_collector_alert(alert: &CollectorAlert) -> Self {
+        let message = if alert.recovered {
+            format!("Сборщик {} снова отдаёт данные", alert.collector)
+        } else {
+            format!(
+                "Сборщик {} не отдаёт данные {} тактов подряд: {}",
+                alert.collector,
+                alert.consecutive_failures,
+                alert.error.as_deref().unwrap_or("нет данных")
+            )
+        };
+        Self {
+            kind: "collector",
+            subject: alert.collector.clone(),
+            severity: AlertSeverity::Warning,
+            recovered: alert.recovered,
+            message,
+        }
+    }
+
+    pub fn watchdog(stale: bool, age_secs: i64) -> Self {
+        Self {
+            kind: "watchdog",
+            subject: "collector".to_string(),
+            severity: AlertSeverity::Critical,
+            recovered: !stale,
+            message: if stale {
+                format!("Сбор данных не обновлялся {age_secs} с")
+            } else {
+                "Сбор данных возобновился".to_string()
+            },
+        }
+    }
+
+    pub fn from_external_alert(alert: &ExternalAlert) -> Self {
+        Self {
+            kind: "external",
+            subject: alert.name.clone(),
+            severity: alert.severity,
+            recovered: alert.recovered,
+            message: match (&alert.message, alert.recovered) {
+                (Some(text), false) => format!("{}: {text}", alert.name),
+                (None, false) => format!("Внешнее уведомление '{}'", alert.name),
+                (_, true) => format!("Внешнее уведомление '{}' снято", alert.name),
+            },
+        }
+    }
+
+    pub fn from_fleet_alert(alert: &FleetAlert) -> Self {
+        match alert {
+            FleetAlert::Host { name, up, error } => Self {
+                kind: "fleet",
+                subject: name.clone(),
+                severity: AlertSeverity::Critical,
+                recovered: *up,
+                message: match (up, error) {
+                    (true, _) => format!("Хост {name} снова на связи"),
+                    (false, Some(error)) => format!("Хост {name} недоступен: {error}"),
+                    (false, None) => format!("Хост {name} недоступен"),
+                },
+            },
+            FleetAlert::Check { host, check, up } => Self {
+                kind: "fleet",
+                subject: format!("{host}/{check}"),
+                severity: AlertSeverity::Warning,
+                recovered: *up,
+                message: if *up {
+                    format!("{host}: проверка '{check}' восстановлена")
+                } else {
+                    format!("{host}: проверка '{check}' недоступна")
+                },
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Channel {
+    Webhook(WebhookConfig),
+    Slack(ChatWebhookConfig),
+    Discord(ChatWebhookConfig),
+    Ntfy(NtfyConfig),
+    Gotify(GotifyConfig),
+    Pushover(PushoverConfig),
+    Alertmanager(AlertmanagerConfig),
+    Telegram(TelegramNotifierConfig, i64),
+}
+
+const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+impl Channel {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Webhook(_) => "webhook",
+            Self::Slack(_) => "slack",
+            Self::Discord(_) => "discord",
+            Self::Ntfy(_) => "ntfy",
+            Self::Gotify(_) => "gotify",
+            Self::Pushover(_) => "pushover",
+            Self::Alertmanager(_) => "alertmanager",
+            Self::Telegram(..) => "telegram",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Webhook(c) => &c.name,
+            Self::Slack(c) | Self::Discord(c) => &c.name,
+            Self::Ntfy(c) => &c.name,
+            Self::Gotify(c) => &c.name,
+            Self::Pushover(c) => &c.name,
+            Self::Alertmanager(c) => &c.name,
+            Self::Telegram(c, _) => &c.name,
+        }
+    }
+
+    fn url(&self) -> Result<String, NotifyError> {
+        Ok(match self {
+            Self::Webhook(c) => c.url.clone(),
+            Self::Slack(c) | Self::Discord(c) => c.webhook_url.clone(),
+            Self::Ntfy(c) => c.server.clone(),
+            Self::Gotify(c) => format!("{}/message", c.server.trim_end_matches('/')),
+            Self::Pushover(_) => PUSHOVER_API_URL.to_string(),
+            Self::Alertmanager(c) => format!("{}/api/v2/alerts", c.url.trim_end_matches('/')),
+            Self::Telegram(c, _) => {
+                let token = c.resolve_token().ok_or(NotifyError::MissingToken)?;
+                format!("https://api.telegram.org/bot{token}/sendMessage")
+            }
+        })
+    }
+
+    fn delivery(&self) -> &DeliveryConfig {
+        match self {
+            Self::Webhook(c) => &c.delivery,
+            Self::Slack(c) | Self::Discord(c) => &c.delivery,
+            Self::Ntfy(c) => &c.delivery,
+            Self::Gotify(c) => &c.delivery,
+            Self::Pushover(c) => &c.delivery,
+            Self::Alertmanager(c) => &c.delivery,
+            Self::Telegram(c, _) => &c.delivery,
+        }
+    }
+
+    fn accepts(&self, notification: &Notification) -> bool {
+        let (min, filter) = match self {
+            Self::Webhook(c) => (c.min_severity, &c.filter),
+            Self::Slack(c) | Self::Discord(c) => (c.min_severity, &c.filter),
+            Self::Ntfy(c) => (c.min_severity, &c.filter),
+            Self::Gotify(c) => (c.min_severity, &c.filter),
+            Self::Pushover(c) => (c.min_severity, &c.filter),
+            Self::Alertmanager(c) => (c.min_severity, &c.filter),
+            Self::Telegram(c, _) => (c.min_severity, &c.filter),
+        };
+        filter_allows(min, filter, notification)
+    }
+
+    fn render(
+        &self,
+        host: Option<&str>,
+        now: i64,
+        notification: &Notification,
+    ) -> Result<String, NotifyError> {
+        match self {
+            Self::Webhook(hook) => render_webhook(hook, host, now, notification),
+            Self::Slack(hook) => Ok(render_slack(hook, host, notification).to_string()),
+            Self::Discord(hook) => Ok(render_discord(hook, host, now, notification).to_string()),
+            Self::Ntfy(_) | Self::Gotify(_) | Self::Pushover(_) => {
+                Ok(render_push(self, host, notification).to_string())
+            }
+            Self::Alertmanager(am) => {
+                Ok(render_alertmanager(am, host, now, notification).to_string())
+            }
+            Self::Telegram(_, chat_id) => {
+                Ok(render_telegram(*chat_id, host, notification).to_string())
+            }
+        }
+    }
+
+    fn push_priority(&self, notification: &Notification) -> i64 {
+        let level = match (notification.recovered, notification.severity) {
+            (true, _) => 0,
+            (false, AlertSeverity::Warning) => 1,
+            (false, AlertSeverity::Critical) => 2,
+        };
+        match self {
+            Self::Ntfy(_) => [3, 4, 5][level],
+            Self::Gotify(_) => [2, 5, 8][level],
+            _ => [-1, 0, 1][level],
+        }
+    }
+}
+
+fn render_alertmanager(
+    am: &AlertmanagerConfig,
+    host: Option<&str>,
+    now: i64,
+    notification: &Notification,
+) -> serde_json::Value {
+    let rfc3339 = |ts: i64| {
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(ts.max(0) as u64))
+            .to_string()
+    };
+    let mut labels = serde_json::Map::new();
+    for (name, value) in &am.labels {
+        labels.insert(name.clone(), value.as_str().into());
+    }
+    labels.insert(
+        "alertname".to_string(),
+        format!("monitord_{}", notification.kind).into(),
+    );
+    labels.insert("instance".to_string(), host.unwrap_or("monitord").into());
+    labels.insert("subject".to_string(), notification.subject.as_str().into());
+    labels.insert("severity".to_string(), notification.severity.key().into());
+
+    let ends_at = if notification.recovered {
+        now
+    } else {
+        now.saturating_add(am.alert_ttl_secs as i64)
+    };
+    let mut alert = serde_json::json!({
+        "labels": labels,
+        "annotations": {
+            "summary": title(notification),
+            "description": notification.message,
+        },
+        "endsAt": rfc3339(ends_at),
+    });
+    if !notification.recovered {
+        alert["startsAt"] = rfc3339(now).into();
+    }
+    serde_json::json!([alert])
+}
+
+fn render_push(
+    channel: &Channel,
+    host: Option<&str>,
+    notification: &Notification,
+) -> serde_json::Value {
+    let title = match host {
+        Some(host) => format!("{} · {host}", title(notification)),
+        None => title(notification).to_string(),
+    };
+    let priority = channel.push_priority(notification);
+    match channel {
+        Channel::Ntfy(c) => serde_json::json!({
+            "topic": c.topic,
+            "title": title,
+            "message": notification.message,
+            "priority": priority,
+            "tags": [notification.kind, notification.severity.key()],
+        }),
+        Channel::Pushover(c) => serde_json::json!({
+            "token": c.token,
+            "user": c.user_key,
+            "title": title,
+            "message": notification.message,
+            "priority": priority,
+        }),
+        _ => serde_json::json!({
+            "title": title,
+            "message": notification.message,
+            "priority": priority,
+        }),
+    }
+}
+
+fn script_accepts(script: &ScriptHookConfig, notification: &Notification) -> bool {
+    (script.kinds.is_empty() || script.kinds.iter().any(|k| k == notification.kind))
+        && (script.on_recover || !notification.recovered)
+        && severity_allows(script.min_severity, notification)
+}
+
+fn script_env(
+    host: Option<&str>,
+    now: i64,
+    notification: &Notification,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("MONITORD_HOST", host.unwrap_or_default().to_string()),
+        ("MONITORD_TS_UNIX", now.to_string()),
+        ("MONITORD_EVENT_KIND", notification.kind.to_string()),
+        ("MONITORD_EVENT_SUBJECT", notification.subject.clone()),
+        (
+            "MONITORD_EVENT_SEVERITY",
+            notification.severity.key().to_string(),
+        ),
+        (
+            "MONITORD_EVENT_STATUS",
+            if notification.recovered {
+                "recovered"
+            } else {
+                "firing"
+            }
+            .to_string(),
+        ),
+        ("MONITORD_EVENT_MESSAGE", notification.message.clone()),
+    ]
+}
+
+fn severity_allows(min: Option<AlertSeverity>, notification: &Notification) -> bool {
+    min.is_none_or(|min| notification.severity >= min)
+}
+
+fn filter_allows(
+    min: Option<AlertSeverity>,
+    filter: &NotifierFilter,
+    notification: &Notification,
+) -> bool {
+    severity_allows(min, notification) && filter.matches(notification.kind, &notification.subject)
+}
+
+#[derive(Debug, Default)]
+struct EmailBatch {
+    since_unix: i64,
+    notifications: Vec<Notification>,
+}
+
+// Очередь каждого получателя в пакетах; переполненная очередь считается неудачной отправкой.
+const QUEUE_CAPACITY: usize = 64;
+
+// Уведомления одного такта сбора (или одно срочное событие) для всех получателей.
+struct Batch {
+    host: Option<String>,
+    now: i64,
+    notifications: Arc<[Notification]>,
+    // Последний пакет при остановке: письма уходят, не дожидаясь batch_secs.
+    flush: bool,
+}
+
+enum Sink {
+    Channel(Channel),
+    Script(ScriptHookConfig),
+    Email(EmailConfig),
+}
+
+impl Sink {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Channel(channel) => channel.label(),
+            Self::Script(_) => "script",
+            Self::Email(_) => "email",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Channel(channel) => channel.name(),
+            Self::Script(script) => &script.name,
+            Self::Email(email) => &email.name,
+        }
+    }
+}
+
+// У каждого канала, скрипта и почтового ящика своя задача доставки: уведомления одного получателя
+// уходят строго по порядку, а повторы медленного канала не задерживают остальные.
+struct Worker {
+    label: &'static str,
+    name: String,
+    email: bool,
+    tx: mpsc::Sender<Batch>,
+    task: JoinHandle<()>,
+}
+
+pub struct Notifier {
+    client: Client,
+    channels: Vec<Channel>,
+    emails: Vec<EmailConfig>,
+    scripts: Vec<ScriptHookConfig>,
+    router: Router,
+    metrics: Arc<Metrics>,
+}
+
+impl Notifier {
+    pub fn new(cfg: &NotifiersConfig, router: Router, metrics: Arc<Metrics>) -> Option<Self> {
+        let channels = cfg
+            .webhooks
+            .iter()
+            .cloned()
+            .map(Channel::Webhook)
+            .chain(cfg.slack.iter().cloned().map(Channel::Slack))
+            .chain(cfg.discord.iter().cloned().map(Channel::Discord))
+            .chain(cfg.ntfy.iter().cloned().map(Channel::Ntfy))
+            .chain(cfg.gotify.iter().cloned().map(Channel::Gotify))
+            .chain(cfg.pushover.iter().cloned().map(Channel::Pushover))
+            .chain(cfg.alertmanager.iter().cloned().map(Channel::Alertmanager))
+            .chain(cfg.telegram.iter().flat_map(|c| {
+                c.chat_ids
+                    .iter()
+                    .map(|chat_id| Channel::Telegram(c.clone(), *chat_id))
+            }))
+            .collect::<Vec<_>>();
+        if channels.is_empty() && cfg.email.is_empty() && cfg.scripts.is_empty() {
+            return None;
+        }
+        let client = Client::builder()
+            .user_agent("monitord/0.1.0")
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Some(Self {
+            client,
+            channels,
+            emails: cfg.email.clone(),
+            scripts: cfg.scripts.clone(),
+            router,
+            metrics,
+        })
+    }
+
+    pub async fn run(self, mut events: broadcast::Receiver<Event>) {
+        let notifier = Arc::new(self);
+        let workers = notifier.spawn_workers();
+        let mut pending = Vec::new();
+        let (mut host, mut last_now) = (None, 0);
+        while let Some(event) = next_event(&mut events).await {
+            match event {
+                // Повтор только для Telegram-чатов с собственным cooldown.
+                Event::AlertFired {
+                    alert:
+                        Alert::Resource(ResourceAlert {
+                            broadcast: false, ..
+                        }),
+                    ..
+                } => {}
+                Event::AlertFired { now, host, alert }
+                | Event::AlertResolved { now, host, alert } => {
+                    // Сторожевой таймер и события парка не ждут следующего локального сбора.
+                    if matches!(
+                        alert,
+                        Alert::Watchdog { .. }
+                            | Alert::Collector(_)
+                            | Alert::Fleet(_)
+                            | Alert::External(_)
+                    ) {
+                        notifier.dispatch(&workers, host, now, vec![alert.notification()]);
+                    } else {
+                        pending.push(alert.notification());
+                    }
+                }
+                Event::SnapshotUpdated { now, snapshot } => {
+                    host = snapshot.host_name.clone();
+                    last_now = now;
+                    notifier.dispatch(&workers, host.clone(), now, std::mem::take(&mut pending));
+                }
+                Event::ConfigReloaded { .. }
+                | Event::ConfigRejected { .. }
+                | Event::AgentStopping { .. } => {}
+            }
+        }
+        for worker in workers {
+            if worker.email {
+                let flush = Batch {
+                    host: host.clone(),
+                    now: last_now,
+                    notifications: Arc::from([]),
+                    flush: true,
+                };
+                let _ = worker.tx.send(flush).await;
+            }
+            drop(worker.tx);
+            let _ = worker.task.await;
+        }
+    }
+
+    fn spawn_workers(self: &Arc<Self>) -> Vec<Worker> {
+        let sinks = self
+            .channels
+            .iter()
+            .cloned()
+            .map(Sink::Channel)
+            .chain(self.scripts.iter().cloned().map(Sink::Script))
+            .chain(self.emails.iter().cloned().map(Sink::Email));
+        sinks
+            .map(|sink| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                Worker {
+                    label: sink.label(),
+                    name: sink.name().to_string(),
+                    email: matches!(sink, Sink::Email(_)),
+                    tx,
+                    task: tokio::spawn(self.clone().deliver(sink, rx)),
+                }
+            })
+            .collect()
+    }
+
+    fn dispatch(
+        &self,
+        workers: &[Worker],
+        host: Option<String>,
+        now: i64,
+        notifications: Vec<Notification>,
+    ) {
+        let notifications: Arc<[Notification]> = notifications.into();
+        for worker in workers {
+            // Пустой пакет нужен только письмам: он отмечает такт для batch_secs.
+            if notifications.is_empty() && !worker.email {
+                continue;
+            }
+            let batch = Batch {
+                host: host.clone(),
+                now,
+                notifications: notifications.clone(),
+                flush: false,
+            };
+            if worker.tx.try_send(batch).is_err() {
+                warn!(
+                    channel = worker.label,
+                    name = %worker.name,
+                    count = notifications.len(),
+                    "очередь уведомлений канала переполнена, пакет отброшен"
+                );
+                self.metrics.inc_notification_failed(worker.label);
+            }
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, sink: Sink, mut rx: mpsc::Receiver<Batch>) {
+        let mut email_batch = EmailBatch::default();
+        while let Some(batch) = rx.recv().await {
+            let host = batch.host.as_deref();
+            match &sink {
+                Sink::Channel(channel) => {
+                    self.send_to_channel(channel, host, batch.now, &batch.notifications)
+                        .await
+                }
+                Sink::Script(script) => {
+                    self.run_script(script, host, batch.now, &batch.notifications)
+                        .await
+                }
+                Sink::Email(email) => {
+                    self.deliver_email(email, &mut email_batch, host, &batch)
+                        .await
+                }
+            }
+        }
+    }
+
+    async fn send_to_channel(
+        &self,
+        channel: &Channel,
+        host: Option<&str>,
+        now: i64,
+        notifications: &[Notification],
+    ) {
+        let accepted = notifications
+            .iter()
+            .filter(|n| channel.accepts(n) && self.router.allows_channel(channel.name(), n));
+        for notification in accepted {
+            let result = match channel.render(host, now, notification) {
+                Ok(body) => send_with_retries(&self.client, channel, body).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                warn!(
+                    channel = channel.label(),
+                    name = channel.name(),
+                    error = %err,
+                    "не удалось отправить уведомление"
+                );
+                self.metrics.inc_notification_failed(channel.label());
+            }
+        }
+    }
+
+    async fn run_script(
+        &self,
+        script: &ScriptHookConfig,
+        host: Option<&str>,
+        now: i64,
+        notifications: &[Notification],
+    ) {
+        let accepted = notifications
+            .iter()
+            .filter(|n| script_accepts(script, n) && self.router.allows_channel(&script.name, n));
+        for notification in accepted {
+            let payload = Payload {
+                host,
+                ts_unix: now,
+                notification,
+            };
+            let stdin = serde_json::to_string(&payload).unwrap_or_default();
+            let envs = script_env(host, now, notification);
+            let outcome = run_command(
+                &script.command,
+                &script.args,
+                &envs,
+                Some(&stdin),
+                script.timeout_secs,
+            )
+            .await;
+            if outcome.success {
+                info!(script = %script.name, kind = notification.kind, "скрипт уведомления выполнен");
+            } else {
+                warn!(
+                    script = %script.name,
+                    exit_code = ?outcome.exit_code,
+                    output = %outcome.output,
+                    "скрипт уведомления завершился с ошибкой"
+                );
+                self.metrics.inc_notification_failed("script");
+            }
+        }
+    }
+
+    async fn deliver_email(
+        &self,
+        email: &EmailConfig,
+        pending: &mut EmailBatch,
+        host: Option<&str>,
+        batch: &Batch,
+    ) {
+        let now = batch.now;
+        let accepted = batch.notifications.iter().filter(|n| {
+            filter_allows(email.min_severity, &email.filter, n)
+                && self.router.allows_channel(&email.name, n)
+        });
+        for notification in accepted {
+            if pending.notifications.is_empty() {
+                pending.since_unix = now;
+            }
+            pending.notifications.push(notification.clone());
+        }
+        let elapsed = now.saturating_sub(pending.since_unix);
+        if pending.notifications.is_empty() || !(batch.flush || elapsed >= email.batch_secs as i64)
+        {
+            return;
+        }
+        let due = std::mem::take(&mut pending.notifications);
+        let password = email.resolve_password();
+        let result = match render_email(email, host, now, &due) {
+            Ok((subject, body)) => with_retries(&email.delivery, "email", &email.name, || {
+                smtp::send_mail(email, password.as_deref(), &subject, &body)
+            })
+            .await
+            .map_err(NotifyError::from),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            warn!(
+                channel = "email",
+                name = %email.name,
+                count = due.len(),
+                error = %err,
+                "не удалось отправить уведомление"
+            );
+            self.metrics.inc_notification_failed("email");
+        }
+    }
+}
+
+fn render_email(
+    email: &EmailConfig,
+    host: Option<&str>,
+    now: i64,
+    batch: &[Notification],
+) -> Result<(String, String), NotifyError> {
+    let host_label = host.unwrap_or("monitord");
+    let alerts = batch
+        .iter()
+        .map(|n| {
+            minijinja::context! {
+                title => title(n),
+                kind => n.kind,
+                subject => n.subject.as_str(),
+                severity => n.severity.key(),
+                recovered => n.recovered,
+                message => n.message.as_str(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let ctx = minijinja::context! {
+        host => host_label,
+        ts_unix => now,
+        count => batch.len(),
+        alerts => alerts,
+    };
+    let env = minijinja::Environment::new();
+    let render = |template: &str| {
+        env.render_str(template, ctx.clone())
+            .map_err(|e| NotifyError::Template(e.to_string()))
+    };
+
+    let subject = match &email.subject_template {
+        Some(template) => render(template)?,
+        None => match batch {
+            [single] => format!(
+                "[monitord] {host_label}: {}: {}",
+                title(single),
+                single.message
+            ),
+            _ => format!("[monitord] {host_label}: {} уведомлений", batch.len()),
+        },
+    };
+    let body = match &email.body_template {
+        Some(template) => render(template)?,
+        None => {
+            let lines = batch
+                .iter()
+                .map(|n| format!("{}: {}", title(n), n.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!(
+                "{lines}\n\nХост: {host_label}\nВремя: {}",
+                humantime::format_rfc3339_seconds(
+                    UNIX_EPOCH + Duration::from_secs(now.max(0) as u64)
+                )
+            )
+        }
+    };
+    Ok((subject.replace(['\r', '\n'], " "), body))
+}
+
+fn title(notification: &Notification) -> &'static str {
+    match (notification.recovered, notification.severity) {
+        (true, _) => "✅ Восстановлено",
+        (false, AlertSeverity::Critical) => "🚨 Критично",
+        (false, AlertSeverity::Warning) => "⚠ Предупреждение",
+    }
+}
+
+fn render_webhook(
+    hook: &WebhookConfig,
+    host: Option<&str>,
+    now: i64,
+    notification: &Notification,
+) -> Result<String, NotifyError> {
+    let Some(template) = &hook.template else {
+        let payload = Payload {
+            host,
+            ts_unix: now,
+            notification,
+        };
+        return serde_json::to_string(&payload).map_err(|e| NotifyError::Template(e.to_string()));
+    };
+    let ctx = minijinja::context! {
+        host => host,
+        ts_unix => now,
+        kind => notification.kind,
+        subject => notification.subject.as_str(),
+        severity => notification.severity.key(),
+        recovered => notification.recovered,
+        message => notification.message.as_str(),
+    };
+    minijinja::Environment::new()
+        .render_str(template, ctx)
+        .map_err(|e| NotifyError::Template(e.to_string()))
+}
+
+fn render_telegram(
+    chat_id: i64,
+    host: Option<&str>,
+    notification: &Notification,
+) -> serde_json::Value {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let host = host
+        .map(|host| format!(" · {}", escape(host)))
+        .unwrap_or_default();
+    serde_json::json!({
+        "chat_id": chat_id,
+        "text": format!(
+            "<b>{}</b>{host}\n{}",
+            title(notification),
+            escape(&notification.message)
+        ),
+        "parse_mode": "HTML",
+        "disable_web_page_preview": true,
+    })
+}
+
+fn render_slack(
+    hook: &ChatWebhookConfig,
+    host: Option<&str>,
+    notification: &Notification,
+) -> serde_json::Value {
+    let title = title(notification);
+    let context = format!(
+        "{} · {} · {}",
+        host.unwrap_or("monitord"),
+        notification.kind,
+        notification.severity.key()
+    );
+    let mut payload = serde_json::json!({
+        "text": format!("{title}: {}", notification.message),
+        "blocks": [
+            {
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format!("*{title}*\n{}", notification.message),
+                },
+            },
+            {
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": context }],
+            },
+        ],
+    });
+    if let Some(username) = &hook.username {
+        payload["username"] = username.as_str().into();
+    }
+    payload
+}
+
+fn render_discord(
+    hook: &ChatWebhookConfig,
+    host: Option<&str>,
+    now: i64,
+    notification: &Notification,
+) -> serde_json::Value {
+    let color = match (notification.recovered, notification.severity) {
+        (true, _) => 0x2EB67D,
+        (false, AlertSeverity::Critical) => 0xE01E5A,
+        (false, AlertSeverity::Warning) => 0xECB22E,
+    };
+    let timestamp =
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(now.max(0) as u64));
+    let mut payload = serde_json::json!({
+        "embeds": [{
+            "title": title(notification),
+            "description": notification.message,
+            "color": color,
+            "timestamp": timestamp.to_string(),
+            "footer": {
+                "text": format!(
+                    "{} · {}",
+                    host.unwrap_or("monitord"),
+                    notification.kind
+                ),
+            },
+        }],
+    });
+    if let Some(username) = &hook.username {
+        payload["username"] = username.as_str().into();
+    }
+    payload
+}
+
+async fn send_with_retries(
+    client: &Client,
+    channel: &Channel,
+    body: String,
+) -> Result<(), NotifyError> {
+    with_retries(channel.delivery(), channel.label(), channel.name(), || {
+        send_once(client, channel, body.clone())
+    })
+    .await
+}
+
+pub async fn with_retries<F, Fut, E>(
+    delivery: &DeliveryConfig,
+    label: &str,
+    name: &str,
+    mut send: F,
+) -> Result<(), E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let mut attempt = 0;
+    loop {
+        match send().await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= delivery.max_retries => return Err(err),
+            Err(err) => {
+                let backoff = delivery
+                    .retry_backoff_ms
+                    .saturating_mul(1 << attempt.min(16));
+                warn!(
+                    channel = label,
+                    name,
+                    attempt = attempt + 1,
+                    error = %err,
+                    "уведомление не принято, повтор"
+                );
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+async fn send_once(client: &Client, channel: &Channel, body: String) -> Result<(), NotifyError> {
+    let mut request = client
+        .post(channel.url()?)
+        .timeout(Duration::from_millis(channel.delivery().timeout_ms))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    match channel {
+        Channel::Webhook(hook) => {
+            for (name, value) in &hook.headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+        Channel::Ntfy(NtfyConfig {
+            token: Some(token), ..
+        }) => request = request.bearer_auth(token),
+        Channel::Gotify(gotify) => request = request.header("X-Gotify-Key", gotify.token.as_str()),
+        Channel::Alertmanager(AlertmanagerConfig {
+            bearer_token: Some(token),
+            ..
+        }) => request = request.bearer_auth(token),
+        _ => {}
+    }
+    // URL вебхуков и Telegram содержит секреты, поэтому в ошибки он не попадает.
+    let response = request.send().await.map_err(reqwest::Error::without_url)?;
+    if !response.status().is_success() {
+        return Err(NotifyError::Status(response.status().as_u16()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn hook(template: Option<&str>) -> WebhookConfig {
+        WebhookConfig {
+            name: "ops".to_string(),
+            url: "https://hooks.example.com".to_string(),
+            headers: HashMap::new(),
+            template: template.map(str::to_string),
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        }
+    }
+
+    fn chat_hook(min_severity: Option<AlertSeverity>) -> ChatWebhookConfig {
+        ChatWebhookConfig {
+            name: "team".to_string(),
+            webhook_url: "https://hooks.example.com/chat".to_string(),
+            username: Some("monitord".to_string()),
+            min_severity,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        }
+    }
+
+    fn disk_gone() -> Notification {
+        Notification::from_mount_change(&MountChange {
+            mount: "/backup \"old\"".to_string(),
+            total_bytes: 10,
+            appeared: false,
+        })
+    }
+
+    #[test]
+    fn payload_defaults_to_json_and_supports_templates() {
+        let notification = disk_gone();
+
+        let body = render_webhook(&hook(None), Some("srv"), 42, &notification).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["host"], "srv");
+        assert_eq!(json["ts_unix"], 42);
+        assert_eq!(json["kind"], "mount");
+        assert_eq!(json["severity"], "critical");
+        assert_eq!(json["recovered"], false);
+
+        let templated = hook(Some(
+            r#"{"text": {{ message|tojson }}, "level": "{{ severity }}"}"#,
+        ));
+        let body = render_webhook(&templated, None, 0, &notification).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["text"], "Диск /backup \"old\" пропал");
+        assert_eq!(json["level"], "critical");
+    }
+
+    #[test]
+    fn slack_and_discord_payloads_and_severity_routing() {
+        let critical = disk_gone();
+        let warning = Notification::watchdog(false, 0);
+        let warning = Notification {
+            severity: AlertSeverity::Warning,
+            recovered: false,
+            ..warning
+        };
+
+        let slack = render_slack(&chat_hook(None), Some("srv"), &critical);
+        assert_eq!(slack["username"], "monitord");
+        assert_eq!(slack["blocks"][0]["type"], "section");
+        assert!(slack["text"].as_str().unwrap().starts_with("🚨 Критично"));
+
+        let discord = render_discord(&chat_hook(None), Some("srv"), 0, &critical);
+        assert_eq!(discord["embeds"][0]["color"], 0xE01E5A);
+        assert_eq!(discord["embeds"][0]["timestamp"], "1970-01-01T00:00:00Z");
+
+        let channel = Channel::Discord(chat_hook(Some(AlertSeverity::Critical)));
+        assert!(channel.accepts(&critical));
+        assert!(!channel.accepts(&warning));
+        assert!(Channel::Slack(chat_hook(None)).accepts(&warning));
+    }
+
+    #[test]
+    fn email_subject_summarises_batch() {
+        let mut email = EmailConfig {
+            name: "oncall".to_string(),
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            tls: crate::config::SmtpTls::Starttls,
+            username: None,
+            password_env: None,
+            password: None,
+            allow_insecure_auth: false,
+            from: "agent@example.com".to_string(),
+            to: vec!["oncall@example.com".to_string()],
+            subject_template: None,
+            body_template: None,
+            batch_secs: 60,
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        };
+        let batch = vec![disk_gone(), Notification::watchdog(true, 300)];
+
+        let (subject, body) = render_email(&email, Some("srv"), 0, &batch[..1]).unwrap();
+        assert_eq!(
+            subject,
+            "[monitord] srv: 🚨 Критично: Диск /backup \"old\" пропал"
+        );
+        assert!(body.ends_with("Хост: srv\nВремя: 1970-01-01T00:00:00Z"));
+
+        let (subject, body) = render_email(&email, Some("srv"), 0, &batch).unwrap();
+        assert_eq!(subject, "[monitord] srv: 2 уведомлений");
+        assert_eq!(body.lines().filter(|l| l.starts_with("🚨")).count(), 2);
+
+        email.subject_template = Some("{{ count }} alerts\non {{ host }}".to_string());
+        email.body_template =
+            Some("{% for a in alerts %}{{ a.severity }} {{ a.kind }};{% endfor %}".to_string());
+        let (subject, body) = render_email(&email, None, 0, &batch).unwrap();
+        assert_eq!(subject, "2 alerts on monitord");
+        assert_eq!(body, "critical mount;critical watchdog;");
+    }
+
+    #[test]
+    fn push_services_map_severity_to_priority() {
+        let critical = disk_gone();
+        let recovered = Notification::watchdog(false, 0);
+        let ntfy = Channel::Ntfy(NtfyConfig {
+            name: "phone".to_string(),
+            server: "https://ntfy.sh".to_string(),
+            topic: "alerts".to_string(),
+            token: None,
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+        let gotify = Channel::Gotify(GotifyConfig {
+            name: "gotify".to_string(),
+            server: "https://push.example.com/".to_string(),
+            token: "app".to_string(),
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+        let pushover = Channel::Pushover(PushoverConfig {
+            name: "pushover".to_string(),
+            user_key: "user".to_string(),
+            token: "app".to_string(),
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        });
+
+        let body = render_push(&ntfy, Some("srv"), &critical);
+        assert_eq!(body["topic"], "alerts");
+        assert_eq!(body["priority"], 5);
+        assert_eq!(body["title"], "🚨 Критично · srv");
+        assert_eq!(render_push(&ntfy, None, &recovered)["priority"], 3);
+
+        assert_eq!(gotify.url().unwrap(), "https://push.example.com/message");
+        assert_eq!(render_push(&gotify, None, &critical)["priority"], 8);
+
+        let body = render_push(&pushover, None, &critical);
+        assert_eq!(body["user"], "user");
+        assert_eq!(body["priority"], 1);
+        assert_eq!(render_push(&pushover, None, &recovered)["priority"], -1);
+    }
+
+    #[test]
+    fn alertmanager_payload_sets_labels_and_ends_at() {
+        let am = AlertmanagerConfig {
+            name: "am".to_string(),
+            url: "http://am:9093/".to_string(),
+            labels: HashMap::from([("team".to_string(), "ops".to_string())]),
+            bearer_token: None,
+            alert_ttl_secs: 600,
+            min_severity: None,
+            filter: NotifierFilter::default(),
+            delivery: DeliveryConfig::default(),
+        };
+        assert_eq!(
+            Channel::Alertmanager(am.clone()).url().unwrap(),
+            "http://am:9093/api/v2/alerts"
+        );
+
+        let firing = render_alertmanager(&am, Some("srv"), 0, &disk_gone());
+        let alert = &firing[0];
+        assert_eq!(alert["labels"]["alertname"], "monitord_mount");
+        assert_eq!(alert["labels"]["instance"], "srv");
+        assert_eq!(alert["labels"]["team"], "ops");
+        assert_eq!(alert["labels"]["severity"], "critical");
+        assert_eq!(alert["startsAt"], "1970-01-01T00:00:00Z");
+        assert_eq!(alert["endsAt"], "1970-01-01T00:10:00Z");
+
+        let resolved = render_alertmanager(&am, None, 60, &Notification::watchdog(false, 0));
+        assert_eq!(resolved[0]["endsAt"], "1970-01-01T00:01:00Z");
+        assert!(resolved[0].get("startsAt").is_none());
+    }
+
+    #[test]
+    fn script_hooks_filter_by_kind_and_recovery() {
+        let mut script = ScriptHookConfig {
+            name: "restart".to_string(),
+            command: "systemctl".to_string(),
+            args: vec![],
+            timeout_secs: 10,
+            kinds: vec!["watchdog".to_string()],
+            on_recover: false,
+            min_severity: None,
+        };
+        let firing = Notification::watchdog(true, 300);
+        let recovered = Notification::watchdog(false, 0);
+        assert!(script_accepts(&script, &firing));
+        assert!(!script_accepts(&script, &recovered));
+        assert!(!script_accepts(&script, &disk_gone()));
+
+        script.on_recover = true;
+        assert!(script_accepts(&script, &recovered));
+
+        let env = script_env(Some("srv"), 5, &recovered);
+        assert!(env.contains(&("MONITORD_EVENT_STATUS", "recovered".to_string())));
+        assert!(env.contains(&("MONITORD_EVENT_KIND", "watchdog".to_string())));
+    }
+
+    #[test]
+    fn telegram_targets_fan_out_per_chat_and_apply_filters() {
+        let team_bot = TelegramNotifierConfig {
+            name: "storage-team".to_string(),
+            bot_token_env: None,
+            bot_token: Some("123:abc".to_string()),
+            chat_ids: vec![-100, 42],
+            min_severity: None,
+            filter: NotifierFilter {
+                kinds: vec!["mount".to_string()],
+                subjects: vec!["/backup*".to_string()],
+            },
+            delivery: DeliveryConfig::default(),
+        };
+        let cfg = NotifiersConfig {
+            telegram: vec![team_bot],
+            ..NotifiersConfig::default()
+        };
+        let notifier = Notifier::new(&cfg, Router::default(), Metrics::new().unwrap()).unwrap();
+        let chats = notifier
+            .channels
+            .iter()
+            .map(|channel| match channel {
+                Channel::Telegram(_, chat_id) => *chat_id,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(chats, vec![-100, 42]);
+
+        let channel = &notifier.channels[0];
+        assert!(channel.accepts(&disk_gone()));
+        assert!(!channel.accepts(&Notification::watchdog(true, 60)));
+        assert_eq!(
+            channel.url().unwrap(),
+            "https://api.telegram.org/bot123:abc/sendMessage"
+        );
+
+        let body = render_telegram(-100, Some("srv<1>"), &disk_gone());
+        assert_eq!(body["chat_id"], -100);
+        assert_eq!(body["parse_mode"], "HTML");
+        let text = body["text"].as_str().unwrap();
+        assert!(text.starts_with("<b>🚨 Критично</b> · srv&lt;1&gt;\n"));
+    }
+
+    #[tokio::test]
+    async fn channels_deliver_in_order_and_independently() {
+        use axum::extract::State;
+        use axum::http::StatusCode;
+        use std::sync::Mutex as StdMutex;
+
+        type Received = Arc<StdMutex<Vec<(String, String)>>>;
+        let received = Received::default();
+        // Первый запрос к /slow отклоняется, дальше принимаются все.
+        let app = axum::Router::new()
+            .route(
+                "/:channel",
+                axum::routing::post(
+                    |State(received): State<Received>,
+                     axum::extract::Path(channel): axum::extract::Path<String>,
+                     body: String| async move {
+                        let mut received = received.lock().unwrap();
+                        let first_slow = channel == "slow"
+                            && !received.iter().any(|(c, _)| c == "slow-rejected");
+                        if first_slow {
+                            received.push(("slow-rejected".to_string(), body));
+                            return StatusCode::INTERNAL_SERVER_ERROR;
+                        }
+                        received.push((channel, body));
+                        StatusCode::OK
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let channel = |name: &str| WebhookConfig {
+            name: name.to_string(),
+            url: format!("http://{addr}/{name}"),
+            template: Some("{{ 'resolved' if recovered else 'firing' }}".to_string()),
+            delivery: DeliveryConfig {
+                timeout_ms: 2000,
+                max_retries: 2,
+                retry_backoff_ms: 400,
+            },
+            ..hook(None)
+        };
+        let cfg = NotifiersConfig {
+            webhooks: vec![channel("slow"), channel("fast")],
+            ..NotifiersConfig::default()
+        };
+        let metrics = Metrics::new().unwrap();
+        let notifier = Arc::new(Notifier::new(&cfg, Router::default(), metrics.clone()).unwrap());
+        let workers = notifier.spawn_workers();
+        notifier.dispatch(&workers, None, 1, vec![Notification::watchdog(true, 60)]);
+        notifier.dispatch(&workers, None, 2, vec![Notification::watchdog(false, 0)]);
+
+        // Пока /slow ждёт повтора, /fast уже получил оба уведомления.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let fast = |received: &[(String, String)]| {
+            received
+                .iter()
+                .filter(|(c, _)| c == "fast")
+                .map(|(_, body)| body.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fast(&received.lock().unwrap()), ["firing", "resolved"]);
+
+        for worker in workers {
+            drop(worker.tx);
+            worker.task.await.unwrap();
+        }
+        let slow = received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(c, _)| c == "slow")
+            .map(|(_, body)| body.clone())
+            .collect::<Vec<_>>();
+        // Срабатывание доставлено раньше восстановления, хотя ждало повтора.
+        assert_eq!(slow, ["firing", "resolved"]);
+
+        let (tx, _rx) = mpsc::channel(1);
+        let stalled = [Worker {
+            label: "webhook",
+            name: "stalled".to_string(),
+            email: false,
+            tx,
+            task: tokio::spawn(async {}),
+        }];
+        for now in 0..3 {
+            notifier.dispatch(&stalled, None, now, vec![disk_gone()]);
+        }
+        assert_eq!(
+            metrics
+                .agent_notifications_failed_total
+                .with_label_values(&["webhook"])
+                .get(),
+            2.0
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+    pub timestamp: f64,
+}
+
+// Ключ: имя гистограммы и её метки, отсортированные по имени (в таком порядке их отдаёт gather).
+// Значение: последний пример для каждого бакета, включая +Inf.
+pub type Exemplars = HashMap<(String, Vec<(String, String)>), Vec<Option<Exemplar>>>;
+
+// Время появления серии счётчика или гистограммы (секунды Unix) для <name>_created; ключ как у Exemplars.
+// Серии без записи выводятся без _created.
+pub type Created = HashMap<(String, Vec<(String, String)>), f64>;
+
+pub fn accepts(accept: &str) -> bool {
+    accept
+        .split(',')
+        .any(|part| part.trim().starts_with("application/openmetrics-text"))
+}
+
+pub fn encode(families: &[MetricFamily], exemplars: &Exemplars, created: &Created) -> String {
+    let mut out = String::new();
+    for family in families {
+        let name = family.get_name();
+        let (family_name, kind) = match family.get_field_type() {
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        let _ = writeln!(out, "# TYPE {family_name} {kind}");
+        let _ = writeln!(out, "# HELP {family_name} {}", escape(family.get_help()));
+        for metric in family.get_metric() {
+            let labels = metric
+                .get_label()
+                .iter()
+                .map(|l| (l.get_name().to_string(), l.get_value().to_string()))
+                .collect::<Vec<_>>();
+            let key = (name.to_string(), labels);
+            let labels = &key.1;
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    sample(
+                        &mut out,
+                        family_name,
+                        "_total",
+                        labels,
+                        None,
+                        metric.get_counter().get_value(),
+                    );
+                    if let Some(ts) = created.get(&key) {
+                        sample(&mut out, family_name, "_created", labels, None, *ts);
+                    }
+                }
+                MetricType::GAUGE => {
+                    sample(
+                        &mut out,
+                        family_name,
+                        "",
+                        labels,
+                        None,
+                        metric.get_gauge().get_value(),
+                    );
+                }
+                MetricType::UNTYPED => {
+                    sample(
+                        &mut out,
+                        family_name,
+                        "",
+                        labels,
+                        None,
+                        metric.get_untyped().get_value(),
+                    );
+                }
+                MetricType::HISTOGRAM => {
+                    let examples = exemplars.get(&key);
+                    histogram(&mut out, family_name, labels, metric, examples);
+                    if let Some(ts) = created.get(&key) {
+                        sample(&mut out, family_name, "_created", labels, None, *ts);
+                    }
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for q in summary.get_quantile() {
+                        let value = format_float(q.get_quantile());
+                        sample(
+                            &mut out,
+                            family_name,
+                            "",
+                            labels,
+                            Some(("quantile", &value)),
+                            q.get_value(),
+                        );
+                    }
+                    sample(
+                        &mut out,
+                        family_name,
+                        "_sum",
+                        labels,
+                        None,
+                        summary.get_sample_sum(),
+                    );
+                    sample(
+                        &mut out,
+                        family_name,
+                        "_count",
+                        labels,
+                        None,
+                        summary.get_sample_count() as f64,
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+// Классический текстовый формат ретранслируемых экспортеров переводится в OpenMetrics: untyped
+// становится unknown, у семейств-счётчиков отбрасывается _total, метки времени — из миллисекунд в секунды.
+pub fn from_text_format(text: &str) -> String {
+    let counters = text
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().strip_prefix("# TYPE ")?.split_whitespace();
+            let name = parts.next()?;
+            (parts.next()? == "counter").then_some(name)
+        })
+        .collect::<HashSet<_>>();
+    let mut out = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.trim_start().splitn(3, ' ');
+            let (Some(kind @ ("HELP" | "TYPE")), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let family = if counters.contains(name) {
+                name.strip_suffix("_total").unwrap_or(name)
+            } else {
+                name
+            };
+            let rest = parts.next().unwrap_or("");
+            let rest = match kind {
+                "TYPE" if rest.trim() == "untyped" => "unknown".to_string(),
+                // В классическом формате кавычки в HELP не экранируются, в OpenMetrics — обязательно.
+                "HELP" => rest.replace('"', "\\\""),
+                _ => rest.to_string(),
+            };
+            let _ = writeln!(out, "# {kind} {family} {rest}");
+            continue;
+        }
+        let name_end = line
+            .find(|c: char| c == '{' || c.is_whitespace())
+            .unwrap_or(line.len());
+        let (name, rest) = line.split_at(name_end);
+        let (labels, tail) = split_labels(rest);
+        let mut tail = tail.split_whitespace();
+        let Some(value) = tail.next() else {
+            continue;
+        };
+        let name = if counters.contains(name) && !name.ends_with("_total") {
+            format!("{name}_total")
+        } else {
+            name.to_string()
+        };
+        let _ = write!(out, "{name}{labels} {value}");
+        if let Some(ms) = tail.next().and_then(|ts| ts.parse::<i64>().ok()) {
+            let _ = write!(out, " {}.{:03}", ms.div_euclid(1000), ms.rem_euclid(1000));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Отделяет {метки} от значения с учётом экранированных кавычек и скобок внутри значений меток.
+fn split_labels(rest: &str) -> (&str, &str) {
+    if !rest.starts_with('{') {
+        return ("", rest);
+    }
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '}' if !quoted => return rest.split_at(idx + 1),
+            _ => {}
+        }
+    }
+    (rest, "")
+}
+
+fn histogram(
+    out: &mut String,
+    name: &str,
+    labels: &[(String, String)],
+    metric: &Metric,
+    exemplars: Option<&Vec<Option<Exemplar>>>,
+) {
+    let h = metric.get_histogram();
+    // gather не включает бакет +Inf: он равен общему числу наблюдений.
+    let bounds = h
+        .get_bucket()
+        .iter()
+        .map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+        .chain(std::iter::once((f64::INFINITY, h.get_sample_count())));
+    for (idx, (upper, count)) in bounds.enumerate() {
+        let le = format_float(upper);
+        sample(
+            out,
+            name,
+            "_bucket",
+            labels,
+            Some(("le", &le)),
+            count as f64,
+        );
+        if let Some(example) = exemplars.and_then(|e| e.get(idx)).and_then(Option::as_ref) {
+            // Пример дописывается к строке бакета перед переводом строки.
+            out.pop();
+            let labels = example
+                .labels
+                .iter()
+                .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(
+                out,
+                " # {{{labels}}} {} {}",
+                format_float(example.value),
+                format_float(example.timestamp)
+            );
+        }
+    }
+    sample(out, name, "_sum", labels, None, h.get_sample_sum());
+    sample(
+        out,
+        name,
+        "_count",
+        labels,
+        None,
+        h.get_sample_count() as f64,
+    );
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &[(String, String)],
+    extra: Option<(&str, &str)>,
+    value: f64,
+) {
+    let mut pairs = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+        .collect::<Vec<_>>();
+    if let Some((k, v)) = extra {
+        pairs.push(format!("{k}=\"{v}\""));
+    }
+    if pairs.is_empty() {
+        let _ = writeln!(out, "{name}{suffix} {}", format_float(value));
+    } else {
+        let _ = writeln!(
+            out,
+            "{name}{suffix}{{{}}} {}",
+            pairs.join(","),
+            format_float(value)
+        );
+    }
+}
+
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{value:.1}")
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Counter, HistogramOpts, HistogramVec, Opts, Registry};
+
+    #[test]
+    fn counters_and_histograms_follow_openmetrics() {
+        let registry = Registry::new();
+        let counter = Counter::with_opts(Opts::new("jobs_total", "Jobs \"done\"")).unwrap();
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("latency_seconds", "Latency").buckets(vec![0.1, 1.0]),
+            &["kind", "check"],
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        counter.inc();
+        histogram.with_label_values(&["http", "api"]).observe(0.05);
+
+        let mut exemplars = Exemplars::new();
+        let key = vec![
+            ("check".to_string(), "api".to_string()),
+            ("kind".to_string(), "http".to_string()),
+        ];
+        exemplars.insert(
+            ("latency_seconds".to_string(), key.clone()),
+            vec![
+                Some(Exemplar {
+                    labels: vec![("up", "1".to_string())],
+                    value: 0.05,
+                    timestamp: 1_700_000_000.0,
+                }),
+                None,
+                None,
+            ],
+        );
+        let mut created = Created::new();
+        created.insert(
+            ("latency_seconds".to_string(), key.clone()),
+            1_700_000_000.5,
+        );
+        let text = encode(&registry.gather(), &exemplars, &created);
+
+        assert!(text.contains("# TYPE jobs counter\n# HELP jobs Jobs \\\"done\\\"\n"));
+        assert!(text.contains("jobs_total 1.0\n"));
+        // У счётчика нет записи о появлении — _created для него не выводится.
+        assert!(!text.contains("jobs_created"));
+        assert!(text.contains(
+            "latency_seconds_count{check=\"api\",kind=\"http\"} 1.0\nlatency_seconds_created{check=\"api\",kind=\"http\"} 1700000000.5\n"
+        ));
+        assert!(text.contains(
+            "latency_seconds_bucket{check=\"api\",kind=\"http\",le=\"0.1\"} 1.0 # {up=\"1\"} 0.05 1700000000.0\n"
+        ));
+        assert!(
+            text.contains("latency_seconds_bucket{check=\"api\",kind=\"http\",le=\"+Inf\"} 1.0\n")
+        );
+        assert!(text.contains("latency_seconds_count{check=\"api\",kind=\"http\"} 1.0\n"));
+        assert!(accepts(
+            "application/openmetrics-text;version=1.0.0,text/plain;q=0.5"
+        ));
+        assert!(!accepts("text/plain"));
+    }
+
+    #[test]
+    fn relayed_text_format_becomes_openmetrics() {
+        let body = "\
+# HELP pg_xact_commit_total Commits \"ok\".
+# TYPE pg_xact_commit_total counter
+pg_xact_commit_total{datname=\"a}b\"} 42 1700000000123
+# TYPE pg_locks counter
+pg_locks 3
+# TYPE pg_misc untyped
+pg_misc 1
+";
+        assert_eq!(
+            from_text_format(body),
+            "\
+# HELP pg_xact_commit Commits \\\"ok\\\".
+# TYPE pg_xact_commit counter
+pg_xact_commit_total{datname=\"a}b\"} 42 1700000000.123
+# TYPE pg_locks counter
+pg_locks_total 3
+# TYPE pg_misc unknown
+pg_misc 1
+"
+        );
+    }
+}
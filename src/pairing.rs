@@ -0,0 +1,110 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, info};
+
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 8;
+const PAIRED_CHATS_FILE: &str = "paired_chats.json";
+
+pub struct Pairing {
+    path: PathBuf,
+    code: Mutex<String>,
+}
+
+impl Pairing {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        let pairing = Self {
+            path: data_dir.as_ref().join(PAIRED_CHATS_FILE),
+            code: Mutex::new(String::new()),
+        };
+        pairing.rotate_code();
+        pairing
+    }
+
+    pub fn load(&self) -> io::Result<HashSet<i64>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, chats: &HashSet<i64>) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut ids = chats.iter().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let text = serde_json::to_string_pretty(&ids)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(&self.path, text)
+    }
+
+    pub fn try_consume(&self, candidate: &str) -> bool {
+        let Ok(code) = self.code.lock() else {
+            return false;
+        };
+        let matched = !code.is_empty() && code.eq_ignore_ascii_case(candidate.trim());
+        drop(code);
+        if matched {
+            self.rotate_code();
+        }
+        matched
+    }
+
+    fn rotate_code(&self) {
+        // Пустой код не принимается: без генератора привязка просто недоступна.
+        let code = generate_code().unwrap_or_default();
+        if code.is_empty() {
+            error!("не удалось сгенерировать код привязки Telegram-чата");
+        } else {
+            // Код не должен попасть в журнал: его буфер отдаётся через /api/logs.
+            eprintln!("код привязки Telegram-чата: отправьте боту /pair {code}");
+            info!("сгенерирован новый код привязки Telegram-чата, он выведен в stderr");
+        }
+        if let Ok(mut guard) = self.code.lock() {
+            *guard = code;
+        }
+    }
+}
+
+fn generate_code() -> Option<String> {
+    let mut bytes = [0u8; CODE_LEN];
+    SystemRandom::new().fill(&mut bytes).ok()?;
+    // Длина алфавита делит 256, поэтому остаток от деления не смещает распределение.
+    Some(
+        bytes
+            .iter()
+            .map(|b| CODE_ALPHABET[*b as usize % CODE_ALPHABET.len()] as char)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_is_one_time_and_chats_persist() {
+        let dir = std::env::temp_dir().join(format!("monitord-pairing-{}", std::process::id()));
+        let pairing = Pairing::new(&dir);
+        let code = pairing.code.lock().unwrap().clone();
+        assert_eq!(code.len(), CODE_LEN);
+        assert!(code.bytes().all(|b| CODE_ALPHABET.contains(&b)));
+        assert_ne!(generate_code().unwrap(), generate_code().unwrap());
+
+        assert!(!pairing.try_consume("WRONG"));
+        assert!(pairing.try_consume(&code.to_lowercase()));
+        assert!(!pairing.try_consume(&code));
+
+        let chats: HashSet<i64> = [42, 7].into_iter().collect();
+        pairing.save(&chats).unwrap();
+        assert_eq!(pairing.load().unwrap(), chats);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
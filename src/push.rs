@@ -0,0 +1,98 @@
+use crate::config::{Config, PushConfig};
+use crate::http::IngestPayload;
+use crate::state::{SharedSnapshot, Snapshot};
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+pub async fn run(
+    client: Client,
+    snapshot: SharedSnapshot,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut cfg = config_rx.borrow_and_update().push.clone();
+    let mut ticker = push_interval(&cfg);
+    let mut last_error: Option<String> = None;
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {
+                if config_rx.has_changed().unwrap_or(false) {
+                    cfg = config_rx.borrow_and_update().push.clone();
+                    if ticker.period() != Duration::from_secs(cfg.interval_secs) {
+                        ticker = push_interval(&cfg);
+                        ticker.reset();
+                    }
+                }
+                let Some(payload) = payload(&cfg, &snapshot) else {
+                    continue;
+                };
+                match push_state(&client, &cfg, &payload).await {
+                    Ok(()) => {
+                        if last_error.take().is_some() {
+                            info!("отправка состояния на центральный агент возобновилась");
+                        }
+                    }
+                    Err(err) => {
+                        // Повторяющуюся ошибку не пишем в журнал на каждом такте.
+                        if last_error.as_deref() != Some(err.as_str()) {
+                            warn!(error = %err, "не удалось отправить состояние на центральный агент");
+                        }
+                        last_error = Some(err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn push_interval(cfg: &PushConfig) -> Interval {
+    let mut ticker = tokio::time::interval(Duration::from_secs(cfg.interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+fn payload(cfg: &PushConfig, snapshot: &SharedSnapshot) -> Option<IngestPayload> {
+    cfg.url.as_ref()?;
+    let snapshot = snapshot.load_full();
+    // До первого сбора отправлять нечего.
+    if snapshot.last_collect_timestamp_seconds <= 0 {
+        return None;
+    }
+    let host = cfg
+        .host
+        .clone()
+        .or_else(|| snapshot.host_name.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    Some(IngestPayload {
+        host,
+        state: Snapshot::clone(&snapshot),
+    })
+}
+
+async fn push_state(
+    client: &Client,
+    cfg: &PushConfig,
+    payload: &IngestPayload,
+) -> Result<(), String> {
+    let url = cfg.ingest_url().ok_or("push.url не задан")?;
+    let token = cfg
+        .resolve_token()
+        .ok_or("не найден токен push (token или token_env)")?;
+    let body = serde_json::to_vec(payload).map_err(|err| err.to_string())?;
+    client
+        .post(url)
+        .timeout(Duration::from_secs(cfg.timeout_secs))
+        .bearer_auth(token)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
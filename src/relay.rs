@@ -0,0 +1,192 @@
+use crate::config::{Config, RelayConfig};
+use crate::metrics::Metrics;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+pub async fn run(
+    client: Client,
+    metrics: Arc<Metrics>,
+    mut config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let (mut exporters, mut interval_secs) = {
+        let cfg = config_rx.borrow_and_update();
+        (cfg.relay.clone(), cfg.interval_secs)
+    };
+    let mut ticker = relay_interval(interval_secs);
+    let mut last_errors: HashMap<String, String> = HashMap::new();
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {
+                if config_rx.has_changed().unwrap_or(false) {
+                    {
+                        let cfg = config_rx.borrow_and_update();
+                        exporters = cfg.relay.clone();
+                        interval_secs = cfg.interval_secs;
+                    }
+                    if ticker.period() != Duration::from_secs(interval_secs) {
+                        ticker = relay_interval(interval_secs);
+                        ticker.reset();
+                    }
+                    last_errors.retain(|name, _| exporters.iter().any(|e| &e.name == name));
+                }
+                scrape_all(&client, &metrics, &exporters, &mut last_errors).await;
+            }
+        }
+    }
+}
+
+fn relay_interval(interval_secs: u64) -> Interval {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+async fn scrape_all(
+    client: &Client,
+    metrics: &Metrics,
+    exporters: &[RelayConfig],
+    last_errors: &mut HashMap<String, String>,
+) {
+    let mut tasks = JoinSet::new();
+    for (idx, exporter) in exporters.iter().enumerate() {
+        let client = client.clone();
+        let exporter = exporter.clone();
+        tasks.spawn(async move { (idx, scrape(&client, &exporter).await) });
+    }
+    let mut results = Vec::with_capacity(exporters.len());
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(result) = joined {
+            results.push(result);
+        }
+    }
+    // Порядок в выдаче /metrics не должен зависеть от того, кто ответил первым.
+    results.sort_by_key(|(idx, _)| *idx);
+
+    let mut output = String::new();
+    let mut statuses = Vec::with_capacity(results.len());
+    for (idx, result) in results {
+        let exporter = &exporters[idx];
+        match result {
+            Ok(body) => {
+                if last_errors.remove(&exporter.name).is_some() {
+                    info!(exporter = %exporter.name, "экспортер снова отвечает");
+                }
+                output.push_str(&relay_exposition(exporter, &body));
+                statuses.push((exporter.name.as_str(), true));
+            }
+            Err(err) => {
+                // Повторяющуюся ошибку не пишем в журнал на каждом такте.
+                if last_errors.get(&exporter.name) != Some(&err) {
+                    warn!(exporter = %exporter.name, error = %err, "не удалось опросить экспортер");
+                }
+                last_errors.insert(exporter.name.clone(), err);
+                statuses.push((exporter.name.as_str(), false));
+            }
+        }
+    }
+    metrics.update_relay(&statuses, output.into_bytes());
+}
+
+async fn scrape(client: &Client, exporter: &RelayConfig) -> Result<String, String> {
+    let resp = client
+        .get(&exporter.url)
+        .timeout(Duration::from_millis(exporter.timeout_ms))
+        .header(reqwest::header::ACCEPT, "text/plain;version=0.0.4")
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| err.to_string())?;
+    resp.text().await.map_err(|err| err.to_string())
+}
+
+// Переписывает текстовый формат Prometheus: фильтрует семейства и добавляет префикс пространства имён.
+fn relay_exposition(exporter: &RelayConfig, body: &str) -> String {
+    let namespace = exporter.namespace();
+    let mut out = String::new();
+    let mut family = String::new();
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.trim_start().splitn(3, ' ');
+            let (Some(kind @ ("HELP" | "TYPE")), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            family = name.to_string();
+            if exporter.metric_allowed(name) {
+                let rest = parts.next().unwrap_or("");
+                out.push_str(&format!("# {kind} {namespace}_{name} {rest}\n"));
+            }
+            continue;
+        }
+        let name_end = line
+            .find(|c: char| c == '{' || c.is_whitespace())
+            .unwrap_or(line.len());
+        let name = &line[..name_end];
+        // Сэмплы гистограмм и summary (_bucket, _sum, _count) фильтруются по имени семейства.
+        let filter_name = if !family.is_empty() && name.starts_with(family.as_str()) {
+            family.as_str()
+        } else {
+            name
+        };
+        if exporter.metric_allowed(filter_name) {
+            out.push_str(&format!("{namespace}_{line}\n"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_exposition_filters_and_namespaces_families() {
+        let exporter = RelayConfig {
+            name: "postgres".to_string(),
+            url: "http://127.0.0.1:9187/metrics".to_string(),
+            namespace: Some("pg".to_string()),
+            include: vec!["pg_*".to_string(), "query_duration_seconds".to_string()],
+            exclude: vec!["pg_settings_*".to_string()],
+            timeout_ms: 1000,
+        };
+        let body = "\
+# HELP pg_up Whether the last scrape was able to connect.
+# TYPE pg_up gauge
+pg_up 1
+# HELP pg_settings_max_connections Server setting.
+# TYPE pg_settings_max_connections gauge
+pg_settings_max_connections 100
+# TYPE go_goroutines gauge
+go_goroutines 12
+# TYPE query_duration_seconds histogram
+query_duration_seconds_bucket{le=\"0.1\"} 3
+query_duration_seconds_sum 0.2
+query_duration_seconds_count 3
+# EOF
+";
+
+        assert_eq!(
+            relay_exposition(&exporter, body),
+            "\
+# HELP pg_pg_up Whether the last scrape was able to connect.
+# TYPE pg_pg_up gauge
+pg_pg_up 1
+# TYPE pg_query_duration_seconds histogram
+pg_query_duration_seconds_bucket{le=\"0.1\"} 3
+pg_query_duration_seconds_sum 0.2
+pg_query_duration_seconds_count 3
+"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::config::{glob_match, Config, ConfigError};
+use crate::events::{Event, EventBus};
+use crate::remote::RemoteConfig;
+use ::notify::{recommended_watcher, RecursiveMode, Watcher};
+use reqwest::Client;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub fn spawn_config_watcher(
+    path: &Path,
+    config_tx: watch::Sender<Arc<Config>>,
+    bus: EventBus,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>, ::notify::Error> {
+    let path = std::path::absolute(path)?;
+    let dir = path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut targets = vec![(dir, file_name)];
+    targets.extend(Config::include_targets(&path, &config_tx.borrow().include));
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let matchers = targets.clone();
+    let mut watcher = recommended_watcher(move |res: ::notify::Result<::notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        let touches_config = event.paths.iter().any(|p| {
+            matchers.iter().any(|(dir, pattern)| {
+                p.parent() == Some(dir.as_path())
+                    && p.file_name()
+                        .is_some_and(|n| glob_match(pattern, &n.to_string_lossy()))
+            })
+        });
+        if touches_config && !event.kind.is_access() {
+            let _ = tx.send(());
+        }
+    })?;
+    // Каталоги, а не файлы: редакторы часто сохраняют через переименование временного файла.
+    let mut dirs = targets.into_iter().map(|(dir, _)| dir).collect::<Vec<_>>();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        if let Err(err) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            warn!(dir = %dir.display(), error = %err, "не удалось отслеживать каталог конфигурации");
+        }
+    }
+
+    Ok(tokio::spawn(async move {
+        let _watcher = watcher;
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                changed = rx.recv() => {
+                    if changed.is_none() {
+                        break;
+                    }
+                    tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                    while rx.try_recv().is_ok() {}
+                    apply(Config::load_from_file(&path), &config_tx, &bus);
+                }
+            }
+        }
+    }))
+}
+
+pub fn spawn_remote_config_poller(
+    remote: RemoteConfig,
+    client: Client,
+    refresh: Duration,
+    config_tx: watch::Sender<Arc<Config>>,
+    bus: EventBus,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(Instant::now() + refresh, refresh);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_error = None;
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                _ = ticker.tick() => {
+                    match remote.fetch(&client).await {
+                        Ok(loaded) => {
+                            last_error = None;
+                            apply(Ok(loaded), &config_tx, &bus);
+                        }
+                        // Недоступный сервер не должен слать одно и то же уведомление каждый период.
+                        Err(err) if last_error.as_ref() == Some(&err.to_string()) => {
+                            warn!(url = %remote.url, error = %err, "удалённая конфигурация по-прежнему недоступна");
+                        }
+                        Err(err) => {
+                            last_error = Some(err.to_string());
+                            apply(Err(err), &config_tx, &bus);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn apply(
+    loaded: Result<Config, ConfigError>,
+    config_tx: &watch::Sender<Arc<Config>>,
+    bus: &EventBus,
+) {
+    let current = config_tx.borrow().clone();
+    let loaded = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            error!(error = %err, "новая конфигурация отклонена, продолжаю со старой");
+            bus.publish(Event::ConfigRejected {
+                error: err.to_string(),
+            });
+            return;
+        }
+    };
+    let (next, restart_required) = merge_reloaded(&current, loaded);
+    if restart_required.is_empty() && same(&*current, &next) {
+        return;
+    }
+    if restart_required.is_empty() {
+        info!("конфигурация перезагружена");
+    } else {
+        warn!(
+            sections = %restart_required.join(", "),
+            "конфигурация перезагружена, часть изменений применится только после перезапуска"
+        );
+    }
+    let next = Arc::new(next);
+    config_tx.send_replace(next.clone());
+    bus.publish(Event::ConfigReloaded {
+        config: next,
+        restart_required,
+    });
+}
+
+pub fn merge_reloaded(current: &Config, mut loaded: Config) -> (Config, Vec<&'static str>) {
+    let mut restart_required = Vec::new();
+    if loaded.listen != current.listen {
+        restart_required.push("listen");
+        loaded.listen = current.listen.clone();
+    }
+    if loaded.data_dir != current.data_dir {
+        restart_required.push("data_dir");
+        loaded.data_dir = current.data_dir.clone();
+    }
+
+    loaded.telegram.enabled = current.telegram.enabled;
+    let mut telegram = current.telegram.clone();
+    telegram.alerts = loaded.telegram.alerts.clone();
+    if !same(&telegram, &loaded.telegram) {
+        restart_required.push("telegram");
+    }
+    loaded.telegram = telegram;
+
+    if !same(&loaded.notifiers, &current.notifiers) {
+        restart_required.push("notifiers");
+        loaded.notifiers = current.notifiers.clone();
+    }
+    if !same(&loaded.routes, &current.routes) {
+        restart_required.push("routes");
+        loaded.routes = current.routes.clone();
+    }
+    if !same(&loaded.logging, &current.logging) {
+        restart_required.push("logging");
+        loaded.logging = current.logging.clone();
+    }
+    if !same(&loaded.aggregator.ingest, &current.aggregator.ingest) {
+        restart_required.push("aggregator.ingest");
+        loaded.aggregator.ingest = current.aggregator.ingest.clone();
+    }
+    if !same(&loaded.alert_api, &current.alert_api) {
+        restart_required.push("alert_api");
+        loaded.alert_api = current.alert_api.clone();
+    }
+    if !same(&loaded.status_page, &current.status_page) {
+        restart_required.push("status_page");
+        loaded.status_page = current.status_page.clone();
+    }
+    if !same(&loaded.api, &current.api) {
+        restart_required.push("api");
+        loaded.api = current.api.clone();
+    }
+    if !same(&loaded.discovery, &current.discovery) {
+        restart_required.push("discovery");
+        loaded.discovery = current.discovery.clone();
+    }
+    if loaded.display != current.display {
+        restart_required.push("display");
+        loaded.display = current.display;
+    }
+    (loaded, restart_required)
+}
+
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_applies_checks_and_keeps_restart_only_sections() {
+        let mut current: Config = serde_yaml::from_str(
+            "listen: \"0.0.0.0:9108\"\ninterval_secs: 5\nhttp_checks:\n  - name: api\n    url: \"https://example.com\"\n    timeout_ms: 1000\n",
+        )
+        .unwrap();
+        current.telegram.enabled = true;
+        let mut loaded = current.clone();
+        loaded.interval_secs = 10;
+        loaded.http_checks.clear();
+        loaded.telegram.enabled = false;
+        loaded.telegram.alerts.cpu_load_threshold_percent = 50.0;
+        loaded.telegram.allowed_chat_ids = vec![1];
+        loaded.listen = "127.0.0.1:1".to_string();
+
+        let (next, restart_required) = merge_reloaded(&current, loaded);
+        assert_eq!(restart_required, vec!["listen", "telegram"]);
+        assert_eq!(next.interval_secs, 10);
+        assert!(next.http_checks.is_empty());
+        assert_eq!(next.telegram.alerts.cpu_load_threshold_percent, 50.0);
+        assert!(next.telegram.enabled);
+        assert!(next.telegram.allowed_chat_ids.is_empty());
+        assert_eq!(next.listen, current.listen);
+
+        let (_, restart_required) = merge_reloaded(&current, current.clone());
+        assert!(restart_required.is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::{Config, ConfigError};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    pub url: String,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    sha256: Option<String>,
+}
+
+pub fn is_remote(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+impl RemoteConfig {
+    pub fn new(url: &str, headers: &[String], sha256: Option<&str>) -> Result<Self, ConfigError> {
+        let headers = headers
+            .iter()
+            .map(|header| parse_header(header))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sha256 = match sha256.map(str::trim) {
+            Some(pin) if pin.len() == 64 && pin.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Some(pin.to_ascii_lowercase())
+            }
+            Some(_) => {
+                return Err(ConfigError::Validation(
+                    "--config-sha256 должен содержать 64 шестнадцатеричных символа".to_string(),
+                ))
+            }
+            None => None,
+        };
+        Ok(Self {
+            url: url.to_string(),
+            headers,
+            sha256,
+        })
+    }
+
+    pub async fn fetch(&self, client: &Client) -> Result<Config, ConfigError> {
+        let fetch_error = |source| ConfigError::Fetch {
+            url: self.url.clone(),
+            source,
+        };
+        let mut request = client.get(&self.url).timeout(FETCH_TIMEOUT);
+        for (name, value) in &self.headers {
+            request = request.header(name.clone(), value.clone());
+        }
+        let body = request
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(fetch_error)?
+            .bytes()
+            .await
+            .map_err(fetch_error)?;
+        self.verify(&body)?;
+        let text = std::str::from_utf8(&body).map_err(|_| {
+            ConfigError::Validation(format!("{}: конфигурация должна быть в UTF-8", self.url))
+        })?;
+        Config::load_from_str(text, &self.url)
+    }
+
+    fn verify(&self, body: &[u8]) -> Result<(), ConfigError> {
+        let Some(expected) = &self.sha256 else {
+            return Ok(());
+        };
+        let actual = sha256_hex(body);
+        if &actual != expected {
+            return Err(ConfigError::Checksum {
+                url: self.url.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn sha256_hex(body: &[u8]) -> String {
+    Sha256::digest(body)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), ConfigError> {
+    // Значение заголовка обычно секрет, поэтому в ошибку оно не попадает.
+    let invalid =
+        || ConfigError::Validation("--config-header должен иметь вид 'Имя: значение'".to_string());
+    let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+    let mut value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::get;
+
+    const CONFIG: &str = "listen: \"127.0.0.1:9108\"\ninterval_secs: 7\n";
+
+    async fn serve() -> String {
+        let app = axum::Router::new().route(
+            "/monitord.yaml",
+            get(|headers: HeaderMap| async move {
+                match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                    Some("Bearer fleet") => (StatusCode::OK, CONFIG),
+                    _ => (StatusCode::UNAUTHORIZED, ""),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/monitord.yaml")
+    }
+
+    #[tokio::test]
+    async fn fetches_remote_config_with_auth_and_checksum() {
+        let url = serve().await;
+        let client = Client::new();
+        let auth = vec!["Authorization: Bearer fleet".to_string()];
+        let pin = sha256_hex(CONFIG.as_bytes());
+
+        let cfg = RemoteConfig::new(&url, &auth, Some(&pin))
+            .unwrap()
+            .fetch(&client)
+            .await
+            .unwrap();
+        assert_eq!(cfg.interval_secs, 7);
+
+        let unauthorized = RemoteConfig::new(&url, &[], None).unwrap();
+        assert!(matches!(
+            unauthorized.fetch(&client).await,
+            Err(ConfigError::Fetch { .. })
+        ));
+        let tampered = RemoteConfig::new(&url, &auth, Some(&"0".repeat(64))).unwrap();
+        assert!(matches!(
+            tampered.fetch(&client).await,
+            Err(ConfigError::Checksum { .. })
+        ));
+        assert!(RemoteConfig::new(&url, &["Authorization".to_string()], None).is_err());
+        assert!(RemoteConfig::new(&url, &[], Some("abc")).is_err());
+    }
+}
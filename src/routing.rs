@@ -0,0 +1,102 @@
+use crate::config::RouteConfig;
+use crate::notify::Notification;
+
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    routes: Vec<RouteConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Destinations {
+    pub channels: Vec<String>,
+    pub telegram_chat_ids: Vec<i64>,
+}
+
+impl Router {
+    pub fn new(routes: Vec<RouteConfig>) -> Self {
+        Self { routes }
+    }
+
+    pub fn destinations(&self, notification: &Notification) -> Option<Destinations> {
+        let mut matched: Option<Destinations> = None;
+        for route in &self.routes {
+            if !route.matches(
+                notification.kind,
+                notification.severity,
+                &notification.subject,
+            ) {
+                continue;
+            }
+            let dest = matched.get_or_insert_with(Destinations::default);
+            for channel in &route.channels {
+                if !dest.channels.contains(channel) {
+                    dest.channels.push(channel.clone());
+                }
+            }
+            for chat_id in &route.telegram_chat_ids {
+                if !dest.telegram_chat_ids.contains(chat_id) {
+                    dest.telegram_chat_ids.push(*chat_id);
+                }
+            }
+            if !route.continue_matching {
+                break;
+            }
+        }
+        matched
+    }
+
+    pub fn allows_channel(&self, channel: &str, notification: &Notification) -> bool {
+        self.destinations(notification)
+            .is_none_or(|dest| dest.channels.iter().any(|c| c == channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AlertSeverity, MountChange};
+
+    #[test]
+    fn first_matching_route_wins_unless_continue() {
+        let router = Router::new(vec![
+            RouteConfig {
+                kinds: vec!["mount".to_string()],
+                channels: vec!["mail".to_string()],
+                continue_matching: true,
+                ..RouteConfig::default()
+            },
+            RouteConfig {
+                severities: vec![AlertSeverity::Critical],
+                subjects: vec!["/backup*".to_string()],
+                telegram_chat_ids: vec![42],
+                ..RouteConfig::default()
+            },
+            RouteConfig {
+                channels: vec!["ignored".to_string()],
+                ..RouteConfig::default()
+            },
+        ]);
+        let gone = Notification::from_mount_change(&MountChange {
+            mount: "/backup".to_string(),
+            total_bytes: 0,
+            appeared: false,
+        });
+        assert_eq!(
+            router.destinations(&gone),
+            Some(Destinations {
+                channels: vec!["mail".to_string()],
+                telegram_chat_ids: vec![42],
+            })
+        );
+        assert!(router.allows_channel("mail", &gone));
+        assert!(!router.allows_channel("ignored", &gone));
+
+        let watchdog = Notification::watchdog(true, 10);
+        assert_eq!(
+            router.destinations(&watchdog).unwrap().channels,
+            vec!["ignored".to_string()]
+        );
+        assert!(Router::default().allows_channel("any", &watchdog));
+        assert_eq!(Router::default().destinations(&watchdog), None);
+    }
+}
@@ -0,0 +1,558 @@
+use crate::config::AlertRuleConfig;
+use crate::state::{AlertSeverity, State};
+use std::collections::HashMap;
+use thiserror::Error;
+
+pub const KNOWN_VARIABLES: [&str; 19] = [
+    "cpu_usage",
+    "cpu_steal",
+    "cpu_temp",
+    "gpu_load",
+    "gpu_temp",
+    "ram_usage",
+    "memory_used_bytes",
+    "memory_total_bytes",
+    "disk_usage",
+    "process_count",
+    "uptime_secs",
+    "net.rx_total_bps",
+    "net.tx_total_bps",
+    "internet.download_mbps",
+    "internet.upload_mbps",
+    "internet.latency_ms",
+    "checks.up",
+    "checks.down",
+    "checks.total",
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RuleError {
+    #[error("неожиданный символ '{0}' в позиции {1}")]
+    UnexpectedChar(char, usize),
+    #[error("неожиданный конец выражения")]
+    UnexpectedEnd,
+    #[error("неожиданный токен '{0}'")]
+    UnexpectedToken(String),
+    #[error("неизвестная переменная '{0}'")]
+    UnknownVariable(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    Var(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(src: &str) -> Result<Self, RuleError> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(RuleError::UnexpectedToken(token.to_string())),
+            None => Ok(expr),
+        }
+    }
+
+    pub fn variables(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.collect_variables(&mut out);
+        out
+    }
+
+    fn collect_variables<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Self::Num(_) => {}
+            Self::Var(name) => out.push(name),
+            Self::Not(e) | Self::Neg(e) => e.collect_variables(out),
+            Self::Binary(l, _, r) => {
+                l.collect_variables(out);
+                r.collect_variables(out);
+            }
+        }
+    }
+
+    pub fn eval(&self, vars: &HashMap<&str, f64>) -> f64 {
+        match self {
+            Self::Num(v) => *v,
+            Self::Var(name) => vars.get(name.as_str()).copied().unwrap_or(f64::NAN),
+            Self::Not(e) => bool_value(!truthy(e.eval(vars))),
+            Self::Neg(e) => -e.eval(vars),
+            Self::Binary(l, op, r) => {
+                let l = l.eval(vars);
+                match op {
+                    BinOp::Or => bool_value(truthy(l) || truthy(r.eval(vars))),
+                    BinOp::And => bool_value(truthy(l) && truthy(r.eval(vars))),
+                    _ => {
+                        let r = r.eval(vars);
+                        match op {
+                            BinOp::Eq => bool_value(l == r),
+                            BinOp::Ne => bool_value(l != r),
+                            BinOp::Lt => bool_value(l < r),
+                            BinOp::Le => bool_value(l <= r),
+                            BinOp::Gt => bool_value(l > r),
+                            BinOp::Ge => bool_value(l >= r),
+                            BinOp::Add => l + r,
+                            BinOp::Sub => l - r,
+                            BinOp::Mul => l * r,
+                            BinOp::Div => l / r,
+                            BinOp::Or | BinOp::And => unreachable!(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn matches(&self, vars: &HashMap<&str, f64>) -> bool {
+        truthy(self.eval(vars))
+    }
+}
+
+fn truthy(v: f64) -> bool {
+    !v.is_nan() && v != 0.0
+}
+
+fn bool_value(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Num(v) => write!(f, "{v}"),
+            Self::Ident(s) => write!(f, "{s}"),
+            Self::Op(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+const OPERATORS: [&str; 15] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "(", ")",
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>, RuleError> {
+    let chars = src.char_indices().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|(_, n)| n.is_ascii_digit()))
+        {
+            let start = pos;
+            while i < chars.len() && (chars[i].1.is_ascii_digit() || chars[i].1 == '.') {
+                i += 1;
+            }
+            let end = chars.get(i).map_or(src.len(), |(p, _)| *p);
+            let value = src[start..end]
+                .parse::<f64>()
+                .map_err(|_| RuleError::UnexpectedChar(c, pos))?;
+            tokens.push(Token::Num(value));
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = pos;
+            while i < chars.len()
+                && (chars[i].1.is_ascii_alphanumeric() || chars[i].1 == '_' || chars[i].1 == '.')
+            {
+                i += 1;
+            }
+            let end = chars.get(i).map_or(src.len(), |(p, _)| *p);
+            tokens.push(Token::Ident(src[start..end].to_string()));
+            continue;
+        }
+        let op = OPERATORS
+            .iter()
+            .find(|op| src[pos..].starts_with(*op))
+            .ok_or(RuleError::UnexpectedChar(c, pos))?;
+        tokens.push(Token::Op(op));
+        i += op.len();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn parse_binary(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        next: fn(&mut Self) -> Result<Expr, RuleError>,
+    ) -> Result<Expr, RuleError> {
+        let mut left = next(self)?;
+        while let Some(op) = self
+            .peek_op()
+            .and_then(|p| ops.iter().find(|(s, _)| *s == p).map(|(_, op)| *op))
+        {
+            self.pos += 1;
+            let right = next(self)?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, RuleError> {
+        self.parse_binary(&[("||", BinOp::Or)], Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, RuleError> {
+        self.parse_binary(&[("&&", BinOp::And)], Self::parse_comparison)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, RuleError> {
+        self.parse_binary(
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            Self::parse_additive,
+        )
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, RuleError> {
+        self.parse_binary(
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            Self::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, RuleError> {
+        self.parse_binary(&[("*", BinOp::Mul), ("/", BinOp::Div)], Self::parse_unary)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, RuleError> {
+        match self.peek_op() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, RuleError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(RuleError::UnexpectedEnd)?;
+        self.pos += 1;
+        match token {
+            Token::Num(v) => Ok(Expr::Num(v)),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Num(1.0)),
+                "false" => Ok(Expr::Num(0.0)),
+                _ => Ok(Expr::Var(name)),
+            },
+            Token::Op("(") => {
+                let expr = self.parse_or()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Op(")")) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    Some(other) => Err(RuleError::UnexpectedToken(other.to_string())),
+                    None => Err(RuleError::UnexpectedEnd),
+                }
+            }
+            other => Err(RuleError::UnexpectedToken(other.to_string())),
+        }
+    }
+}
+
+pub fn compile(expr: &str) -> Result<Expr, RuleError> {
+    let parsed = Expr::parse(expr)?;
+    if let Some(unknown) = parsed
+        .variables()
+        .into_iter()
+        .find(|v| !KNOWN_VARIABLES.contains(v))
+    {
+        return Err(RuleError::UnknownVariable(unknown.to_string()));
+    }
+    Ok(parsed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub expr: Expr,
+    pub source: String,
+    pub message: Option<String>,
+    pub severity: AlertSeverity,
+}
+
+impl Rule {
+    pub fn from_config(cfg: &AlertRuleConfig) -> Result<Self, RuleError> {
+        Ok(Self {
+            name: cfg.name.clone(),
+            expr: compile(&cfg.expr)?,
+            source: cfg.expr.clone(),
+            message: cfg.message.clone(),
+            severity: cfg.severity.unwrap_or(AlertSeverity::Critical),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleAlert {
+    pub name: String,
+    pub expr: String,
+    pub message: Option<String>,
+    pub severity: AlertSeverity,
+    pub recovered: bool,
+}
+
+pub fn rule_variables(state: &State, cpu_temp: Option<f64>) -> HashMap<&'static str, f64> {
+    let percent = |used: u64, total: u64| {
+        if total > 0 {
+            used as f64 / total as f64 * 100.0
+        } else {
+            f64::NAN
+        }
+    };
+    let max_of = |values: Vec<f64>| values.into_iter().reduce(f64::max).unwrap_or(f64::NAN);
+    let checks_up = state.checks.http.iter().filter(|c| c.up).count()
+        + state.checks.tcp.iter().filter(|c| c.up).count()
+        + state.checks.ssh.iter().filter(|c| c.up).count();
+    let checks_total = state.checks.http.len() + state.checks.tcp.len() + state.checks.ssh.len();
+
+    HashMap::from([
+        ("cpu_usage", state.cpu_usage_percent),
+        (
+            "cpu_steal",
+            state
+                .cpu_steal
+                .as_ref()
+                .map_or(f64::NAN, |s| s.steal_percent),
+        ),
+        ("cpu_temp", cpu_temp.unwrap_or(f64::NAN)),
+        (
+            "gpu_load",
+            max_of(
+                state
+                    .gpus
+                    .iter()
+                    .filter_map(|g| g.utilization_percent)
+                    .collect(),
+            ),
+        ),
+        (
+            "gpu_temp",
+            max_of(
+                state
+                    .gpus
+                    .iter()
+                    .filter_map(|g| g.temperature_celsius)
+                    .collect(),
+            ),
+        ),
+        (
+            "ram_usage",
+            percent(state.memory_used_bytes, state.memory_total_bytes),
+        ),
+        ("memory_used_bytes", state.memory_used_bytes as f64),
+        ("memory_total_bytes", state.memory_total_bytes as f64),
+        (
+            "disk_usage",
+            max_of(
+                state
+                    .disks
+                    .iter()
+                    .map(|d| percent(d.used_bytes, d.total_bytes))
+                    .filter(|v| !v.is_nan())
+                    .collect(),
+            ),
+        ),
+        ("process_count", state.process_count as f64),
+        ("uptime_secs", state.system_uptime_seconds as f64),
+        (
+            "net.rx_total_bps",
+            state.net.iter().map(|n| n.rx_bytes_per_sec as f64).sum(),
+        ),
+        (
+            "net.tx_total_bps",
+            state.net.iter().map(|n| n.tx_bytes_per_sec as f64).sum(),
+        ),
+        (
+            "internet.download_mbps",
+            state
+                .internet_speed
+                .as_ref()
+                .map_or(f64::NAN, |s| s.download_mbps),
+        ),
+        (
+            "internet.upload_mbps",
+            state
+                .internet_speed
+                .as_ref()
+                .map_or(f64::NAN, |s| s.upload_mbps),
+        ),
+        (
+            "internet.latency_ms",
+            state
+                .internet_speed
+                .as_ref()
+                .and_then(|s| s.latency_ms)
+                .unwrap_or(f64::NAN),
+        ),
+        ("checks.up", checks_up as f64),
+        ("checks.down", (checks_total - checks_up) as f64),
+        ("checks.total", checks_total as f64),
+    ])
+}
+
+pub fn evaluate_rules(
+    rules: &[Rule],
+    state: &mut State,
+    vars: &HashMap<&str, f64>,
+    repeat_interval_secs: u64,
+    recovery_notify: bool,
+    now_unix: i64,
+) -> Vec<RuleAlert> {
+    let mut out = Vec::new();
+    for rule in rules {
+        let firing = rule.expr.matches(vars);
+        let last_sent = state.active_rules.get(&rule.name).copied();
+        let emit = match (firing, last_sent) {
+            (true, None) => true,
+            (true, Some(last)) => now_unix - last >= repeat_interval_secs as i64,
+            (false, Some(_)) => {
+                state.active_rules.remove(&rule.name);
+                recovery_notify
+            }
+            (false, None) => false,
+        };
+        if firing && emit {
+            state.active_rules.insert(rule.name.clone(), now_unix);
+        }
+        if emit {
+            out.push(RuleAlert {
+                name: rule.name.clone(),
+                expr: rule.source.clone(),
+                message: rule.message.clone(),
+                severity: rule.severity,
+                recovered: !firing,
+            });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<&'static str, f64> {
+        HashMap::from([("cpu_usage", 85.0), ("net.rx_total_bps", 500.0)])
+    }
+
+    #[test]
+    fn expressions_parse_and_evaluate() {
+        let expr = compile("cpu_usage > 80 && net.rx_total_bps < 1000").expect("валидное");
+        assert!(expr.matches(&vars()));
+        assert!(!compile("cpu_usage > 80 && !(net.rx_total_bps < 1000)")
+            .unwrap()
+            .matches(&vars()));
+        assert!(compile("cpu_usage * 2 - 70 >= 100 || false")
+            .unwrap()
+            .matches(&vars()));
+        assert!(!compile("cpu_temp > 50").unwrap().matches(&vars()));
+
+        assert_eq!(
+            compile("foo > 1"),
+            Err(RuleError::UnknownVariable("foo".to_string()))
+        );
+        assert_eq!(compile("cpu_usage >"), Err(RuleError::UnexpectedEnd));
+        assert!(compile("cpu_usage > 1)").is_err());
+        assert!(compile("cpu_usage # 1").is_err());
+    }
+
+    #[test]
+    fn rules_fire_repeat_and_recover() {
+        let rules = [Rule {
+            name: "busy".to_string(),
+            expr: compile("cpu_usage > 80").unwrap(),
+            source: "cpu_usage > 80".to_string(),
+            message: None,
+            severity: AlertSeverity::Warning,
+        }];
+        let mut state = State::new(0);
+        let mut vars = vars();
+
+        assert_eq!(
+            evaluate_rules(&rules, &mut state, &vars, 60, true, 0).len(),
+            1
+        );
+        assert!(evaluate_rules(&rules, &mut state, &vars, 60, true, 30).is_empty());
+        assert_eq!(
+            evaluate_rules(&rules, &mut state, &vars, 60, true, 60).len(),
+            1
+        );
+
+        vars.insert("cpu_usage", 10.0);
+        let alerts = evaluate_rules(&rules, &mut state, &vars, 60, true, 70);
+        assert!(alerts[0].recovered);
+        assert!(evaluate_rules(&rules, &mut state, &vars, 60, true, 80).is_empty());
+    }
+
+    #[test]
+    fn rule_variables_cover_known_names() {
+        let vars = rule_variables(&State::new(0), None);
+        for name in KNOWN_VARIABLES {
+            assert!(vars.contains_key(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::SensorsConfig;
+use crate::state::{Snapshot, TempStat};
+
+// Показания вне этого диапазона считаются сбоем датчика и не участвуют в выборе.
+const PLAUSIBLE_CELSIUS: std::ops::RangeInclusive<f64> = 0.0..=130.0;
+const GPU_MARKERS: [&str; 4] = ["gpu", "nvidia", "amdgpu", "radeon"];
+
+// Ярусы sensors.cpu_temperature перебираются по порядку: побеждает первый, в котором нашёлся
+// хотя бы один датчик, а внутри яруса — самый горячий. Температуры накопителей не учитываются.
+pub fn cpu_temperature(temps: &[TempStat], cfg: &SensorsConfig) -> Option<f64> {
+    cfg.cpu_temperature.iter().find_map(|tier| {
+        temps
+            .iter()
+            .filter(|t| t.device.is_none() && PLAUSIBLE_CELSIUS.contains(&t.temperature_celsius))
+            .filter(|t| {
+                let name = t.sensor.to_lowercase();
+                (tier.include.is_empty() || contains_any(&name, &tier.include))
+                    && !contains_any(&name, &tier.exclude)
+            })
+            .map(|t| t.temperature_celsius)
+            .max_by(|a, b| a.total_cmp(b))
+    })
+}
+
+// Наибольшая температура по областям: CPU берётся уже выбранной, GPU — по видеокартам и датчикам
+// amdgpu/nvidia/radeon, disk — по температурам накопителей.
+pub fn max_temperature_by_domain(state: &Snapshot) -> Vec<(&'static str, f64)> {
+    let gpu = state
+        .gpus
+        .iter()
+        .filter_map(|g| g.temperature_celsius)
+        .chain(
+            state
+                .temps
+                .iter()
+                .filter(|t| {
+                    t.device.is_none() && contains_any(&t.sensor.to_lowercase(), &GPU_MARKERS)
+                })
+                .map(|t| t.temperature_celsius),
+        )
+        .max_by(|a, b| a.total_cmp(b));
+    let disk = state
+        .temps
+        .iter()
+        .filter(|t| t.device.is_some())
+        .map(|t| t.temperature_celsius)
+        .max_by(|a, b| a.total_cmp(b));
+    [
+        ("cpu", state.cpu_temperature_celsius),
+        ("gpu", gpu),
+        ("disk", disk),
+    ]
+    .into_iter()
+    .filter_map(|(domain, value)| value.map(|v| (domain, v)))
+    .collect()
+}
+
+fn contains_any(name: &str, markers: &[impl AsRef<str>]) -> bool {
+    markers
+        .iter()
+        .any(|m| name.contains(&m.as_ref().to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SensorTierConfig;
+    use crate::state::GpuStat;
+
+    fn temp(sensor: &str, celsius: f64, device: Option<&str>) -> TempStat {
+        TempStat {
+            sensor: sensor.to_string(),
+            temperature_celsius: celsius,
+            critical_temperature_celsius: None,
+            device: device.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn cpu_temperature_follows_tier_priority() {
+        let cfg = SensorsConfig::default();
+        let mut temps = vec![
+            temp("amdgpu edge", 80.0, None),
+            temp("Samsung SSD", 90.0, Some("nvme0n1")),
+        ];
+        assert_eq!(cpu_temperature(&temps, &cfg), None);
+        temps.push(temp("acpitz temp1", 70.0, None));
+        assert_eq!(cpu_temperature(&temps, &cfg), Some(70.0));
+        temps.push(temp("nct6798 SYSTIN", 35.0, None));
+        assert_eq!(cpu_temperature(&temps, &cfg), Some(35.0));
+        temps.push(temp("coretemp Package id 0", 61.0, None));
+        temps.push(temp("k10temp Tctl", 255.0, None));
+        assert_eq!(cpu_temperature(&temps, &cfg), Some(61.0));
+
+        let custom = SensorsConfig {
+            cpu_temperature: vec![SensorTierConfig {
+                include: vec!["SYSTIN".to_string()],
+                exclude: Vec::new(),
+            }],
+        };
+        assert_eq!(cpu_temperature(&temps, &custom), Some(35.0));
+
+        let mut state = crate::state::State::default().snapshot();
+        state.temps = temps.into();
+        state.cpu_temperature_celsius = cpu_temperature(&state.temps, &cfg);
+        state.gpus = vec![GpuStat {
+            id: "0".to_string(),
+            name: "RTX".to_string(),
+            utilization_percent: None,
+            memory_used_bytes: None,
+            memory_total_bytes: None,
+            temperature_celsius: Some(67.0),
+        }];
+        assert_eq!(
+            max_temperature_by_domain(&state),
+            vec![("cpu", 61.0), ("gpu", 80.0), ("disk", 90.0)]
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::config::{EmailConfig, SmtpTls};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::DateTime;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+#[derive(Debug, Error)]
+pub enum SmtpError {
+    #[error("ошибка соединения: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ошибка TLS: {0}")]
+    Tls(String),
+    #[error("сервер ответил {code}: {text}")]
+    Reply { code: u16, text: String },
+    #[error("превышен таймаут")]
+    Timeout,
+}
+
+trait SmtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SmtpStream for T {}
+
+struct Connection {
+    stream: BufReader<Box<dyn SmtpStream>>,
+}
+
+impl Connection {
+    async fn read_reply(&mut self) -> Result<(u16, String), SmtpError> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(SmtpError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|c| c.parse::<u16>().ok())
+                .ok_or_else(|| SmtpError::Reply {
+                    code: 0,
+                    text: line.to_string(),
+                })?;
+            text.push_str(line.get(4..).unwrap_or_default());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+            text.push('\n');
+        }
+    }
+
+    async fn expect(&mut self, expected: &[u16]) -> Result<(), SmtpError> {
+        let (code, text) = self.read_reply().await?;
+        if expected.contains(&code) {
+            Ok(())
+        } else {
+            Err(SmtpError::Reply { code, text })
+        }
+    }
+
+    async fn command(&mut self, line: &str, expected: &[u16]) -> Result<(), SmtpError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        self.expect(expected).await
+    }
+}
+
+pub async fn send_mail(
+    cfg: &EmailConfig,
+    password: Option<&str>,
+    subject: &str,
+    body: &str,
+) -> Result<(), SmtpError> {
+    let timeout = Duration::from_millis(cfg.delivery.timeout_ms);
+    tokio::time::timeout(timeout, send_mail_inner(cfg, password, subject, body))
+        .await
+        .map_err(|_| SmtpError::Timeout)?
+}
+
+async fn send_mail_inner(
+    cfg: &EmailConfig,
+    password: Option<&str>,
+    subject: &str,
+    body: &str,
+) -> Result<(), SmtpError> {
+    let tcp = TcpStream::connect((cfg.host.as_str(), cfg.port)).await?;
+    let stream: Box<dyn SmtpStream> = match cfg.tls {
+        SmtpTls::Tls => Box::new(wrap_tls(tcp, &cfg.host).await?),
+        SmtpTls::Starttls | SmtpTls::None => Box::new(tcp),
+    };
+    let mut conn = Connection {
+        stream: BufReader::new(stream),
+    };
+    conn.expect(&[220]).await?;
+    conn.command("EHLO monitord", &[250]).await?;
+
+    if cfg.tls == SmtpTls::Starttls {
+        conn.command("STARTTLS", &[220]).await?;
+        let plain = conn.stream.into_inner();
+        let tls = wrap_tls(plain, &cfg.host).await?;
+        conn = Connection {
+            stream: BufReader::new(Box::new(tls)),
+        };
+        conn.command("EHLO monitord", &[250]).await?;
+    }
+
+    if let Some(username) = &cfg.username {
+        let token = BASE64.encode(format!("\0{username}\0{}", password.unwrap_or_default()));
+        conn.command(&format!("AUTH PLAIN {token}"), &[235]).await?;
+    }
+
+    conn.command(&format!("MAIL FROM:<{}>", cfg.from), &[250])
+        .await?;
+    for to in &cfg.to {
+        conn.command(&format!("RCPT TO:<{to}>"), &[250, 251])
+            .await?;
+    }
+    conn.command("DATA", &[354]).await?;
+    let message = build_message(&cfg.from, &cfg.to, subject, body, SystemTime::now());
+    conn.command(&format!("{message}\r\n."), &[250]).await?;
+    let _ = conn.command("QUIT", &[221]).await;
+    Ok(())
+}
+
+async fn wrap_tls<S>(stream: S, host: &str) -> Result<tokio_rustls::client::TlsStream<S>, SmtpError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| SmtpError::Tls(e.to_string()))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string()).map_err(|e| SmtpError::Tls(e.to_string()))?;
+    TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .map_err(|e| SmtpError::Tls(e.to_string()))
+}
+
+// Одно encoded-word (RFC 2047) не длиннее 75 символов: 45 байт дают 60 символов base64.
+const ENCODED_WORD_BYTES: usize = 45;
+
+fn build_message(
+    from: &str,
+    to: &[String],
+    subject: &str,
+    body: &str,
+    sent_at: SystemTime,
+) -> String {
+    let encoded = BASE64.encode(body.as_bytes());
+    let wrapped = encoded
+        .as_bytes()
+        .chunks(76)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    let secs = sent_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let date = DateTime::from_timestamp(secs, 0)
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S +0000");
+    format!(
+        "Date: {date}\r\nMessage-ID: {}\r\nFrom: {from}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{wrapped}",
+        message_id(from, secs),
+        to.join(", "),
+        encode_subject(subject)
+    )
+}
+
+// Тема режется по границам символов на слова, которые переносятся на строки продолжения.
+fn encode_subject(subject: &str) -> String {
+    let mut words = Vec::new();
+    let mut rest = subject;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(ENCODED_WORD_BYTES);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (word, tail) = rest.split_at(end);
+        words.push(format!("=?UTF-8?B?{}?=", BASE64.encode(word.as_bytes())));
+        rest = tail;
+    }
+    if words.is_empty() {
+        words.push("=?UTF-8?B??=".to_string());
+    }
+    words.join("\r\n ")
+}
+
+fn message_id(from: &str, secs: i64) -> String {
+    let mut random = [0u8; 8];
+    let _ = SystemRandom::new().fill(&mut random);
+    let token = random
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let domain = from
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .filter(|domain| !domain.is_empty())
+        .unwrap_or("monitord.local");
+    format!("<{secs}.{token}@{domain}>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_encodes_subject_and_wraps_body() {
+        let body = "ж".repeat(100);
+        let message = build_message(
+            "agent@example.com",
+            &["a@example.com".to_string(), "b@example.com".to_string()],
+            "Тест",
+            &body,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+        assert!(message.starts_with("Date: Tue, 14 Nov 2023 22:13:20 +0000\r\n"));
+        let id = message
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix("Message-ID: "))
+            .unwrap();
+        assert!(id.starts_with("<1700000000.") && id.ends_with("@example.com>"));
+        assert!(message.contains("To: a@example.com, b@example.com\r\n"));
+        assert!(message.contains("Subject: =?UTF-8?B?0KLQtdGB0YI=?=\r\n"));
+        let (_, encoded) = message.split_once("\r\n\r\n").unwrap();
+        assert!(encoded.split("\r\n").all(|line| line.len() <= 76));
+        let decoded = BASE64.decode(encoded.replace("\r\n", "")).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), body);
+    }
+
+    #[test]
+    fn long_subject_is_split_into_short_encoded_words() {
+        let subject = "Проверка недоступна: ".repeat(6);
+        let encoded = encode_subject(&subject);
+        let words = encoded.split("\r\n ").collect::<Vec<_>>();
+        assert!(words.len() > 1);
+        assert!(words.iter().all(|word| word.len() <= 75));
+        let decoded = words
+            .iter()
+            .map(|word| {
+                let inner = word
+                    .strip_prefix("=?UTF-8?B?")
+                    .unwrap()
+                    .strip_suffix("?=")
+                    .unwrap();
+                String::from_utf8(BASE64.decode(inner).unwrap()).unwrap()
+            })
+            .collect::<String>();
+        assert_eq!(decoded, subject);
+    }
+}
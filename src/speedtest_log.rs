@@ -0,0 +1,124 @@
+use crate::state::InternetSpeedStat;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const SPEEDTEST_FILE: &str = "speedtest_history.jsonl";
+
+// Замеры дописываются по одному JSON на строку; устаревшие вычищаются перезаписью файла.
+pub struct SpeedtestLog {
+    path: PathBuf,
+}
+
+impl SpeedtestLog {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: data_dir.as_ref().join(SPEEDTEST_FILE),
+        }
+    }
+
+    // Повреждённые строки (например, оборванная запись при аварийной остановке) пропускаются.
+    pub fn load(&self, since_unix: i64) -> io::Result<Vec<InternetSpeedStat>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut samples = text
+            .lines()
+            .filter_map(|line| serde_json::from_str::<InternetSpeedStat>(line).ok())
+            .filter(|s| s.measured_at_unix >= since_unix)
+            .collect::<Vec<_>>();
+        samples.sort_by_key(|s| s.measured_at_unix);
+        samples.dedup_by_key(|s| s.measured_at_unix);
+        Ok(samples)
+    }
+
+    pub fn append(&self, sample: &InternetSpeedStat) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line(sample)?.as_bytes())
+    }
+
+    pub fn rewrite<'a>(
+        &self,
+        samples: impl IntoIterator<Item = &'a InternetSpeedStat>,
+    ) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for sample in samples {
+            text.push_str(&line(sample)?);
+        }
+        let tmp = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+fn line(sample: &InternetSpeedStat) -> io::Result<String> {
+    let mut line = serde_json::to_string(sample)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    fn sample(ts: i64, down: f64) -> InternetSpeedStat {
+        InternetSpeedStat {
+            download_mbps: down,
+            upload_mbps: down / 10.0,
+            latency_ms: Some(12.0),
+            measured_at_unix: ts,
+        }
+    }
+
+    #[test]
+    fn samples_survive_restart_and_feed_window_stats() {
+        let dir = std::env::temp_dir().join(format!("monitord-speedtest-{}", std::process::id()));
+        let log = SpeedtestLog::new(&dir);
+        let now = 10 * 86_400;
+        for (ts, down) in [(now - 9 * 86_400, 500.0), (now - 3 * 86_400, 50.0)] {
+            log.append(&sample(ts, down)).unwrap();
+        }
+        log.append(&sample(now - 3600, 100.0)).unwrap();
+        fs::write(
+            &log.path,
+            fs::read_to_string(&log.path).unwrap() + "{\"download_mbps\":",
+        )
+        .unwrap();
+
+        let loaded = log.load(now - 7 * 86_400).unwrap();
+        assert_eq!(loaded.len(), 2);
+        let mut state = State::default();
+        for s in loaded {
+            assert!(state.record_speedtest(s, 7));
+        }
+        assert!(!state.record_speedtest(sample(now - 3600, 1.0), 7));
+        assert!(state.record_speedtest(sample(now, 300.0), 7));
+
+        let day = state.speedtest_stats(now, 24).unwrap();
+        assert_eq!(day.samples, 2);
+        assert_eq!(day.download_mbps.min, 100.0);
+        assert_eq!(day.download_mbps.avg, 200.0);
+        assert_eq!(day.download_mbps.max, 300.0);
+        let week = state.speedtest_stats(now, 24 * 7).unwrap();
+        assert_eq!(week.samples, 3);
+        assert_eq!(week.download_mbps.min, 50.0);
+        assert!(state.speedtest_stats(now + 30 * 86_400, 24).is_none());
+
+        log.rewrite(&state.speedtest_history).unwrap();
+        assert_eq!(log.load(0).unwrap().len(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,5 +1,15 @@
-use crate::config::AlertsConfig;
-use std::collections::HashMap;
+use crate::config::{
+    AlertsConfig, CheckAlertPolicy, DiskAlertConfig, HistoryConfig, UpstreamDownAction,
+};
+use arc_swap::ArcSwap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+// Последний собранный снимок: читатели получают его без блокировок.
+pub type SharedSnapshot = Arc<ArcSwap<Snapshot>>;
+
+// Столько UTC-суток доступности проверок хранится для страницы /status.
+pub const CHECK_UPTIME_DAYS: i64 = 90;
 
 #[derive(Debug, Clone, Default)]
 pub struct State {
@@ -14,51 +24,266 @@ pub struct State {
     pub process_count: u64,
     pub cpu_core_count: u32,
     pub cpu_usage_percent: f64,
+    pub cpu_steal: Option<CpuStealStat>,
+    pub virtualization: Option<String>,
+    pub time_sync: Option<TimeSyncStat>,
+    pub cgroup: Option<CgroupStat>,
     pub memory_used_bytes: u64,
     pub memory_total_bytes: u64,
-    pub disks: Vec<DiskStat>,
-    pub net: Vec<NetStat>,
+    pub page_file: Option<PageFileStat>,
+    pub disks: Arc<Vec<DiskStat>>,
+    pub disk_io: Vec<DiskIoStat>,
+    // Заполняет фоновый обход dir_sizes, а не такт сбора.
+    pub dir_sizes: Vec<DirSizeStat>,
+    pub net: Arc<Vec<NetStat>>,
+    pub wireguard: Vec<WireguardPeerStat>,
     pub internet_speed: Option<InternetSpeedStat>,
-    pub temps: Vec<TempStat>,
+    pub speedtest_history: VecDeque<InternetSpeedStat>,
+    // Тактов «доступна» и всего по проверкам за UTC-сутки, ключ — (номер суток, имя проверки).
+    pub check_uptime: BTreeMap<(i64, String), CheckUptimeDay>,
+    pub temps: Arc<Vec<TempStat>>,
+    pub cpu_temperature_celsius: Option<f64>,
     pub gpus: Vec<GpuStat>,
-    pub sensors: Vec<SensorStat>,
-    pub checks: CheckResults,
+    pub sensors: Arc<Vec<SensorStat>>,
+    pub checks: Arc<CheckResults>,
+    // Задержки последних успешных свежих прогонов каждой проверки, ключ — (вид, имя).
+    pub check_latency_samples: HashMap<(&'static str, String), VecDeque<u64>>,
     pub alert_tracking: HashMap<CheckId, AlertTrackState>,
     pub chat_alert_prefs: HashMap<i64, bool>,
     pub chat_check_alert_prefs: HashMap<i64, bool>,
     pub chat_resource_alert_prefs: HashMap<i64, ResourceAlertPrefs>,
+    pub chat_resource_thresholds: HashMap<i64, HashMap<ResourceAlertKind, f64>>,
+    pub chat_timezones: HashMap<i64, chrono_tz::Tz>,
+    // Период автообновления дашборда в минутах для чатов, где оно включено.
+    pub chat_auto_refresh_mins: HashMap<i64, u64>,
+    // Отложенные кнопками «💤» ресурсные уведомления: момент окончания (Unix) по видам.
+    pub chat_resource_snoozes: HashMap<i64, HashMap<ResourceAlertKind, i64>>,
+    pub paired_chat_ids: HashSet<i64>,
+    pub active_resource_alerts: HashMap<String, AlertSeverity>,
+    pub resource_history: HashMap<String, VecDeque<ResourceSample>>,
+    pub disk_history: HashMap<String, VecDeque<ResourceSample>>,
+    pub metric_history: HashMap<(&'static str, String), VecDeque<ResourceSample>>,
+    pub metric_history_sampled_at: i64,
+    pub active_rules: HashMap<String, i64>,
+    pub external_alerts: HashMap<String, crate::external::ActiveExternalAlert>,
+    pub collector_stale: bool,
+    pub collectors: BTreeMap<String, CollectorHealth>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CollectorHealth {
+    pub consecutive_failures: u32,
+    pub last_success_unix: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectorAlert {
+    pub collector: String,
+    pub consecutive_failures: u32,
+    pub error: Option<String>,
+    pub recovered: bool,
 }
 
-#[derive(Debug, Clone, Default, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct CheckResults {
     pub http: Vec<HttpCheckResult>,
     pub tcp: Vec<TcpCheckResult>,
+    #[serde(default)]
+    pub ssh: Vec<SshCheckResult>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DiskStat {
     pub mount: String,
     pub used_bytes: u64,
     pub total_bytes: u64,
+    pub removable: bool,
+    // Блочное устройство (nvme0n1, sda), на котором лежит раздел, и температура накопителя.
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub temperature_celsius: Option<f64>,
+    // Смонтирован только для чтения (Linux, /proc/mounts).
+    #[serde(default)]
+    pub read_only: bool,
+    // Ошибка пробной записи для разделов из disks.write_probe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_error: Option<String>,
+}
+
+impl DiskStat {
+    pub fn writable(&self) -> bool {
+        !self.read_only && self.write_error.is_none()
+    }
+}
+
+// Счётчики операций накопителя: /proc/diskstats на Linux, PerfDisk на Windows.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DiskIoStat {
+    pub device: String,
+    pub reads_total: u64,
+    pub writes_total: u64,
+    pub read_time_ms_total: u64,
+    pub write_time_ms_total: u64,
+    // Операций в секунду и среднее время операции (await) с прошлого сбора.
+    #[serde(default)]
+    pub ops_per_sec: f64,
+    #[serde(default)]
+    pub read_await_ms: f64,
+    #[serde(default)]
+    pub write_await_ms: f64,
+    #[serde(default)]
+    pub await_ms: f64,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct NetStat {
     pub iface: String,
     pub rx_bytes_total: u64,
     pub tx_bytes_total: u64,
     pub rx_bytes_per_sec: u64,
     pub tx_bytes_per_sec: u64,
+    #[serde(default)]
+    pub rx_errors_total: u64,
+    #[serde(default)]
+    pub tx_errors_total: u64,
+    #[serde(default)]
+    pub rx_drops_total: u64,
+    #[serde(default)]
+    pub tx_drops_total: u64,
+    // Ошибки приёма и передачи в минуту с прошлого сбора.
+    #[serde(default)]
+    pub errors_per_min: f64,
+}
+
+impl NetStat {
+    fn errors_total(&self) -> u64 {
+        self.rx_errors_total.saturating_add(self.tx_errors_total)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WireguardPeerStat {
+    pub iface: String,
+    pub public_key: String,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    // Нет, пока с пиром не было ни одного рукопожатия.
+    pub latest_handshake_unix: Option<i64>,
+    pub rx_bytes_total: u64,
+    pub tx_bytes_total: u64,
+}
+
+impl WireguardPeerStat {
+    pub fn handshake_age_secs(&self, now_unix: i64) -> Option<i64> {
+        self.latest_handshake_unix
+            .map(|ts| now_unix.saturating_sub(ts).max(0))
+    }
+
+    pub fn short_key(&self) -> &str {
+        wireguard_short_key(&self.public_key)
+    }
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+// Ключ целиком в подписи не нужен: первых символов хватает, чтобы различить пиров.
+pub fn wireguard_short_key(public_key: &str) -> &str {
+    public_key.get(..8).unwrap_or(public_key)
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TempStat {
     pub sensor: String,
     pub temperature_celsius: f64,
     pub critical_temperature_celsius: Option<f64>,
+    // Задано для температур накопителей.
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+// Время, отнятое у виртуальной машины гипервизором (Linux, /proc/stat).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CpuStealStat {
+    pub steal_ticks_total: u64,
+    pub cpu_ticks_total: u64,
+    // Доля steal во всём процессорном времени с прошлого сбора.
+    pub steal_percent: f64,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+// Состояние службы синхронизации времени: chrony, systemd (timedatectl) или w32time.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimeSyncStat {
+    pub source: String,
+    pub synchronized: bool,
+    // Смещение системных часов от эталона; сообщает только chrony.
+    #[serde(default)]
+    pub offset_seconds: Option<f64>,
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+// Размер каталога из dir_sizes.paths и его самые большие вложенные записи.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirSizeStat {
+    pub path: String,
+    pub size_bytes: u64,
+    pub scanned_at_unix: i64,
+    #[serde(default)]
+    pub top: Vec<DirSizeEntry>,
+    // Недоступные записи (нет прав, удалены во время обхода) в размер не вошли.
+    #[serde(default)]
+    pub skipped_entries: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirSizeEntry {
+    pub name: String,
+    pub size_bytes: u64,
+}
+
+// Лимиты cgroup контейнера, в котором работает агент (Linux, cgroup v1 или v2).
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CgroupStat {
+    pub version: u8,
+    #[serde(default)]
+    pub runtime: Option<String>,
+    #[serde(default)]
+    pub cpu_limit_cores: Option<f64>,
+    #[serde(default)]
+    pub memory_limit_bytes: Option<u64>,
+    #[serde(default)]
+    pub memory_used_bytes: Option<u64>,
+    #[serde(default)]
+    pub cpu_usage_usec_total: Option<u64>,
+    // Загрузка CPU от квоты с прошлого сбора.
+    #[serde(default)]
+    pub cpu_percent: Option<f64>,
+    // Проценты CPU и памяти агента посчитаны от этих лимитов (container.cgroup_limits).
+    #[serde(default)]
+    pub applied: bool,
+}
+
+// Выделенная память (commit charge) и файл подкачки Windows.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PageFileStat {
+    pub commit_used_bytes: u64,
+    pub commit_limit_bytes: u64,
+    pub pagefile_used_bytes: u64,
+    pub pagefile_total_bytes: u64,
+}
+
+impl PageFileStat {
+    pub fn pagefile_usage_percent(&self) -> f64 {
+        if self.pagefile_total_bytes == 0 {
+            return 0.0;
+        }
+        self.pagefile_used_bytes as f64 / self.pagefile_total_bytes as f64 * 100.0
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct InternetSpeedStat {
     pub download_mbps: f64,
     pub upload_mbps: f64,
@@ -66,7 +291,80 @@ pub struct InternetSpeedStat {
     pub measured_at_unix: i64,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CheckUptimeDay {
+    // Номер UTC-суток: Unix-время, делённое на 86 400.
+    pub day: i64,
+    pub check: String,
+    pub up: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpeedRange {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl SpeedRange {
+    fn from_values(values: impl Iterator<Item = f64>) -> Option<Self> {
+        let (mut min, mut max, mut sum, mut count) = (f64::MAX, f64::MIN, 0.0, 0_usize);
+        for value in values {
+            min = min.min(value);
+            max = max.max(value);
+            sum += value;
+            count += 1;
+        }
+        (count > 0).then(|| Self {
+            min,
+            avg: sum / count as f64,
+            max,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpeedtestStats {
+    pub window_hours: u64,
+    pub samples: usize,
+    pub download_mbps: SpeedRange,
+    pub upload_mbps: SpeedRange,
+    pub latency_ms: Option<SpeedRange>,
+}
+
+// Процентили задержки проверки по последним успешным прогонам.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CheckLatency {
+    pub kind: String,
+    pub name: String,
+    pub samples: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl CheckLatency {
+    pub fn quantiles(&self) -> [(&'static str, u64); 3] {
+        [
+            ("0.5", self.p50_ms),
+            ("0.95", self.p95_ms),
+            ("0.99", self.p99_ms),
+        ]
+    }
+}
+
+pub const CHECK_LATENCY_WINDOW: usize = 100;
+
+// Процентиль по методу ближайшего ранга; sorted не пуст.
+fn nearest_rank(sorted: &[u64], q: f64) -> u64 {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub const SPEEDTEST_STATS_WINDOWS_HOURS: [u64; 2] = [24, 24 * 7];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GpuStat {
     pub id: String,
     pub name: String,
@@ -76,7 +374,7 @@ pub struct GpuStat {
     pub temperature_celsius: Option<f64>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SensorStat {
     pub sensor_type: String,
     pub name: String,
@@ -87,25 +385,65 @@ pub struct SensorStat {
     pub max: Option<f64>,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct HttpCheckResult {
     pub name: String,
     pub up: bool,
     pub latency_ms: u64,
     pub status_code: u16,
+    // Причина последнего отказа: ошибка соединения, таймаут или несовпадение ответа.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Имя недоступной вышестоящей проверки из depends_on, из-за которой упала эта.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_by_upstream: Option<String>,
+    #[serde(skip)]
+    pub cached: bool,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TcpCheckResult {
     pub name: String,
     pub up: bool,
     pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_handshake_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert_valid: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Имя недоступной вышестоящей проверки из depends_on, из-за которой упала эта.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_by_upstream: Option<String>,
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SshCheckResult {
+    pub name: String,
+    pub up: bool,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    // Заполняется, только если в конфигурации задан host_key_fingerprint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key_fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key_matches: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_by_upstream: Option<String>,
+    #[serde(skip)]
+    pub cached: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CheckKind {
     Http,
     Tcp,
+    Ssh,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -120,6 +458,7 @@ pub struct AlertTrackState {
     pub is_down: bool,
     pub last_alert_sent_at: Option<i64>,
     pub last_state_change_at: Option<i64>,
+    pub suppressed_by_upstream: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -133,6 +472,12 @@ pub enum AlertEventKind {
 pub struct AlertEvent {
     pub check_id: CheckId,
     pub kind: AlertEventKind,
+    pub severity: AlertSeverity,
+    pub upstream: Option<String>,
+    // Замер, по которому сработало событие: задержка, код ответа (только HTTP) и причина отказа.
+    pub latency_ms: u64,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -145,10 +490,289 @@ pub enum ResourceAlertKind {
     DiskUsage,
 }
 
+impl ResourceAlertKind {
+    pub const ALL: [Self; 6] = [
+        Self::CpuTemp,
+        Self::GpuTemp,
+        Self::CpuLoad,
+        Self::GpuLoad,
+        Self::RamUsage,
+        Self::DiskUsage,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::CpuTemp => "cpu_temp",
+            Self::GpuTemp => "gpu_temp",
+            Self::CpuLoad => "cpu_load",
+            Self::GpuLoad => "gpu_load",
+            Self::RamUsage => "ram_usage",
+            Self::DiskUsage => "disk_usage",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.key() == key)
+    }
+
+    pub fn is_temperature(self) -> bool {
+        matches!(self, Self::CpuTemp | Self::GpuTemp)
+    }
+
+    pub fn global_threshold(self, cfg: &AlertsConfig) -> f64 {
+        match self {
+            Self::CpuTemp => cfg.cpu_temp_threshold_celsius,
+            Self::GpuTemp => cfg.gpu_temp_threshold_celsius,
+            Self::CpuLoad => cfg.cpu_load_threshold_percent,
+            Self::GpuLoad => cfg.gpu_load_threshold_percent,
+            Self::RamUsage => cfg.ram_usage_threshold_percent,
+            Self::DiskUsage => cfg.disk_usage_threshold_percent,
+        }
+    }
+
+    pub fn base_threshold(self, subject: Option<&str>, cfg: &AlertsConfig) -> f64 {
+        self.disk_alert(subject, cfg)
+            .and_then(|d| d.threshold_percent)
+            .unwrap_or_else(|| self.global_threshold(cfg))
+    }
+
+    pub fn warning_threshold(self, subject: Option<&str>, cfg: &AlertsConfig) -> Option<f64> {
+        self.disk_alert(subject, cfg)
+            .and_then(|d| d.warning_threshold_percent)
+            .or_else(|| cfg.warning_thresholds.get(self.key()).copied())
+    }
+
+    pub fn clear_threshold(self, subject: Option<&str>, cfg: &AlertsConfig) -> Option<f64> {
+        self.disk_alert(subject, cfg)
+            .and_then(|d| d.clear_threshold_percent)
+            .or_else(|| cfg.resource_clear_threshold(self.key()))
+    }
+
+    pub fn alert_key(self, subject: Option<&str>) -> String {
+        match (self, subject) {
+            (Self::DiskUsage, Some(mount)) => format!("{}:{}", self.key(), mount),
+            _ => self.key().to_string(),
+        }
+    }
+
+    fn disk_alert<'a>(
+        self,
+        subject: Option<&str>,
+        cfg: &'a AlertsConfig,
+    ) -> Option<&'a DiskAlertConfig> {
+        match (self, subject) {
+            (Self::DiskUsage, Some(mount)) => cfg.disk_alerts.get(mount),
+            _ => None,
+        }
+    }
+
+    pub fn threshold_range(self) -> std::ops::RangeInclusive<f64> {
+        if self.is_temperature() {
+            1.0..=130.0
+        } else {
+            0.0..=100.0
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Warning,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+
+    pub fn classify(value: f64, critical: f64, warning: Option<f64>) -> Option<(Self, f64)> {
+        if value >= critical {
+            return Some((Self::Critical, critical));
+        }
+        match warning {
+            Some(w) if value >= w => Some((Self::Warning, w)),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ResourceAlert {
     pub kind: ResourceAlertKind,
-    pub text: String,
+    pub severity: AlertSeverity,
+    pub value: f64,
+    pub subject: Option<String>,
+    pub recovered: bool,
+    // Для GPU-уведомлений: кто нагружает карту в момент срабатывания.
+    pub gpu_context: Option<GpuAlertContext>,
+    // Telegram-чаты, для которых истёк их собственный cooldown.
+    pub chat_ids: Vec<i64>,
+    // Истёк общий cooldown: уведомление получают маршруты, внешние каналы и critical_chat_ids.
+    pub broadcast: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct GpuAlertContext {
+    pub encoder_percent: Option<f64>,
+    pub decoder_percent: Option<f64>,
+    pub processes: Vec<GpuProcess>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub sm_percent: Option<f64>,
+    pub memory_mb: Option<u64>,
+}
+
+const DISK_HISTORY_SAMPLE_SECS: i64 = 60;
+
+pub const HISTORY_METRICS: [&str; 9] = [
+    "cpu_usage_percent",
+    "memory_used_bytes",
+    "disk_used_bytes",
+    "net_rx_bytes_per_sec",
+    "net_tx_bytes_per_sec",
+    "temperature_celsius",
+    "gpu_utilization_percent",
+    "check_up",
+    "check_latency_ms",
+];
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistoryRow {
+    pub ts_unix: i64,
+    pub metric: &'static str,
+    pub subject: String,
+    pub value: f64,
+}
+const DISK_FORECAST_MIN_SAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceSample {
+    pub ts_unix: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskForecast {
+    pub mount: String,
+    pub used_percent: f64,
+    pub seconds_until_full: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountChange {
+    pub mount: String,
+    pub total_bytes: u64,
+    pub appeared: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyMount {
+    pub mount: String,
+    pub read_only: bool,
+    pub write_error: Option<String>,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SensorAlert {
+    pub label: Option<String>,
+    pub sensor: String,
+    pub value: f64,
+    pub threshold: f64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TempRiseAlert {
+    pub sensor: String,
+    pub rate_celsius_per_min: f64,
+    pub threshold: f64,
+    pub temperature_celsius: f64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CpuStealAlert {
+    pub steal_percent: f64,
+    pub threshold: f64,
+    pub virtualization: Option<String>,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirSizeAlert {
+    pub path: String,
+    pub size_bytes: u64,
+    pub threshold_bytes: u64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeSyncAlert {
+    pub source: String,
+    pub synchronized: bool,
+    pub offset_seconds: Option<f64>,
+    pub max_offset_ms: Option<f64>,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageFileAlert {
+    pub usage_percent: f64,
+    pub threshold: f64,
+    pub pagefile_used_bytes: u64,
+    pub pagefile_total_bytes: u64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WireguardAlert {
+    pub iface: String,
+    pub public_key: String,
+    pub endpoint: Option<String>,
+    pub handshake_age_secs: i64,
+    pub threshold_secs: u64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskLatencyAlert {
+    pub device: String,
+    pub await_ms: f64,
+    pub threshold_ms: f64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct NetErrorAlert {
+    pub iface: String,
+    pub errors_per_min: f64,
+    pub threshold: f64,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostReboot {
+    pub previous_uptime_secs: u64,
+    pub boot_time_unix: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceAlertTransition {
+    Idle,
+    Active,
+    Cleared(AlertSeverity),
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +798,124 @@ impl Default for ResourceAlertPrefs {
     }
 }
 
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub started_at_unix: i64,
+    pub last_collect_timestamp_seconds: i64,
+    pub host_name: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub cpu_brand: Option<String>,
+    pub system_uptime_seconds: u64,
+    pub process_count: u64,
+    pub cpu_core_count: u32,
+    pub cpu_usage_percent: f64,
+    #[serde(default)]
+    pub cpu_steal: Option<CpuStealStat>,
+    // Гипервизор, если агент работает в виртуальной машине.
+    #[serde(default)]
+    pub virtualization: Option<String>,
+    #[serde(default)]
+    pub time_sync: Option<TimeSyncStat>,
+    #[serde(default)]
+    pub cgroup: Option<CgroupStat>,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    #[serde(default)]
+    pub page_file: Option<PageFileStat>,
+    pub disks: Arc<Vec<DiskStat>>,
+    #[serde(default)]
+    pub disk_io: Vec<DiskIoStat>,
+    #[serde(default)]
+    pub dir_sizes: Vec<DirSizeStat>,
+    pub net: Arc<Vec<NetStat>>,
+    #[serde(default)]
+    pub wireguard: Vec<WireguardPeerStat>,
+    pub internet_speed: Option<InternetSpeedStat>,
+    #[serde(default)]
+    pub speedtest_stats: Vec<SpeedtestStats>,
+    pub temps: Arc<Vec<TempStat>>,
+    // Выбирается агентом по sensors.cpu_temperature, чтобы все представления показывали одно значение.
+    #[serde(default)]
+    pub cpu_temperature_celsius: Option<f64>,
+    pub gpus: Vec<GpuStat>,
+    pub sensors: Arc<Vec<SensorStat>>,
+    pub checks: Arc<CheckResults>,
+    #[serde(default)]
+    pub check_latency: Vec<CheckLatency>,
+    #[serde(default)]
+    pub collectors: BTreeMap<String, CollectorHealth>,
+}
+
+impl From<Snapshot> for State {
+    fn from(value: Snapshot) -> Self {
+        Self {
+            started_at_unix: value.started_at_unix,
+            last_collect_timestamp_seconds: value.last_collect_timestamp_seconds,
+            host_name: value.host_name,
+            os_name: value.os_name,
+            os_version: value.os_version,
+            kernel_version: value.kernel_version,
+            cpu_brand: value.cpu_brand,
+            system_uptime_seconds: value.system_uptime_seconds,
+            process_count: value.process_count,
+            cpu_core_count: value.cpu_core_count,
+            cpu_usage_percent: value.cpu_usage_percent,
+            cpu_steal: value.cpu_steal,
+            virtualization: value.virtualization,
+            time_sync: value.time_sync,
+            cgroup: value.cgroup,
+            memory_used_bytes: value.memory_used_bytes,
+            memory_total_bytes: value.memory_total_bytes,
+            page_file: value.page_file,
+            disks: value.disks,
+            disk_io: value.disk_io,
+            dir_sizes: value.dir_sizes,
+            net: value.net,
+            wireguard: value.wireguard,
+            internet_speed: value.internet_speed,
+            temps: value.temps,
+            cpu_temperature_celsius: value.cpu_temperature_celsius,
+            gpus: value.gpus,
+            sensors: value.sensors,
+            checks: value.checks,
+            collectors: value.collectors,
+            ..Self::default()
+        }
+    }
+}
+
+impl Snapshot {
+    // Текущие результаты всех проверок: (вид, имя, доступна, задержка в мс).
+    pub fn check_runs(&self) -> Vec<(&'static str, &str, bool, u64)> {
+        let checks = &self.checks;
+        checks
+            .http
+            .iter()
+            .map(|c| ("http", c.name.as_str(), c.up, c.latency_ms))
+            .chain(
+                checks
+                    .tcp
+                    .iter()
+                    .map(|c| ("tcp", c.name.as_str(), c.up, c.latency_ms)),
+            )
+            .chain(
+                checks
+                    .ssh
+                    .iter()
+                    .map(|c| ("ssh", c.name.as_str(), c.up, c.latency_ms)),
+            )
+            .collect()
+    }
+
+    pub fn check_latency_for(&self, kind: &str, name: &str) -> Option<&CheckLatency> {
+        self.check_latency
+            .iter()
+            .find(|l| l.kind == kind && l.name == name)
+    }
+}
+
 impl State {
     pub fn new(now_unix: i64) -> Self {
         Self {
@@ -182,6 +924,207 @@ impl State {
         }
     }
 
+    // Только собранные показатели, без истории и настроек чатов; крупные списки разделяются
+    // со снимком через Arc, а не копируются на каждом такте.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            started_at_unix: self.started_at_unix,
+            last_collect_timestamp_seconds: self.last_collect_timestamp_seconds,
+            host_name: self.host_name.clone(),
+            os_name: self.os_name.clone(),
+            os_version: self.os_version.clone(),
+            kernel_version: self.kernel_version.clone(),
+            cpu_brand: self.cpu_brand.clone(),
+            system_uptime_seconds: self.system_uptime_seconds,
+            process_count: self.process_count,
+            cpu_core_count: self.cpu_core_count,
+            cpu_usage_percent: self.cpu_usage_percent,
+            cpu_steal: self.cpu_steal.clone(),
+            virtualization: self.virtualization.clone(),
+            time_sync: self.time_sync.clone(),
+            cgroup: self.cgroup.clone(),
+            memory_used_bytes: self.memory_used_bytes,
+            memory_total_bytes: self.memory_total_bytes,
+            page_file: self.page_file.clone(),
+            disks: Arc::clone(&self.disks),
+            disk_io: self.disk_io.clone(),
+            dir_sizes: self.dir_sizes.clone(),
+            net: Arc::clone(&self.net),
+            wireguard: self.wireguard.clone(),
+            internet_speed: self.internet_speed.clone(),
+            speedtest_stats: SPEEDTEST_STATS_WINDOWS_HOURS
+                .iter()
+                .filter_map(|hours| {
+                    self.speedtest_stats(self.last_collect_timestamp_seconds, *hours)
+                })
+                .collect(),
+            temps: Arc::clone(&self.temps),
+            cpu_temperature_celsius: self.cpu_temperature_celsius,
+            gpus: self.gpus.clone(),
+            sensors: Arc::clone(&self.sensors),
+            checks: Arc::clone(&self.checks),
+            check_latency: self.check_latency(),
+            collectors: self.collectors.clone(),
+        }
+    }
+
+    // Окно из CHECK_LATENCY_WINDOW последних успешных прогонов. Результаты из кэша не считаются
+    // новым замером, а окна удалённых из конфигурации проверок вычищаются.
+    fn record_check_latency(&mut self) {
+        let runs = self
+            .checks
+            .http
+            .iter()
+            .map(|c| ("http", &c.name, c.up, c.latency_ms, c.cached))
+            .chain(
+                self.checks
+                    .tcp
+                    .iter()
+                    .map(|c| ("tcp", &c.name, c.up, c.latency_ms, c.cached)),
+            )
+            .chain(
+                self.checks
+                    .ssh
+                    .iter()
+                    .map(|c| ("ssh", &c.name, c.up, c.latency_ms, c.cached)),
+            )
+            .collect::<Vec<_>>();
+        self.check_latency_samples
+            .retain(|(kind, name), _| runs.iter().any(|(k, n, ..)| k == kind && *n == name));
+        for (kind, name, up, latency_ms, cached) in runs {
+            if !up || cached {
+                continue;
+            }
+            let samples = self
+                .check_latency_samples
+                .entry((kind, name.clone()))
+                .or_default();
+            samples.push_back(latency_ms);
+            while samples.len() > CHECK_LATENCY_WINDOW {
+                samples.pop_front();
+            }
+        }
+    }
+
+    pub fn check_latency(&self) -> Vec<CheckLatency> {
+        let mut out = self
+            .check_latency_samples
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|((kind, name), samples)| {
+                let mut sorted = samples.iter().copied().collect::<Vec<_>>();
+                sorted.sort_unstable();
+                CheckLatency {
+                    kind: kind.to_string(),
+                    name: name.clone(),
+                    samples: sorted.len(),
+                    p50_ms: nearest_rank(&sorted, 0.50),
+                    p95_ms: nearest_rank(&sorted, 0.95),
+                    p99_ms: nearest_rank(&sorted, 0.99),
+                }
+            })
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| (&a.kind, &a.name).cmp(&(&b.kind, &b.name)));
+        out
+    }
+
+    // Замеры хранятся в порядке измерения; повтор того же замера (между запусками speedtest) не пишется.
+    pub fn record_speedtest(&mut self, sample: InternetSpeedStat, retention_days: u64) -> bool {
+        if self
+            .speedtest_history
+            .back()
+            .is_some_and(|last| last.measured_at_unix >= sample.measured_at_unix)
+        {
+            return false;
+        }
+        let cutoff = sample
+            .measured_at_unix
+            .saturating_sub((retention_days * 86_400) as i64);
+        self.speedtest_history.push_back(sample);
+        while self
+            .speedtest_history
+            .front()
+            .is_some_and(|s| s.measured_at_unix < cutoff)
+        {
+            self.speedtest_history.pop_front();
+        }
+        true
+    }
+
+    pub fn speedtest_range(&self, from_unix: i64, to_unix: i64) -> Vec<InternetSpeedStat> {
+        self.speedtest_history
+            .iter()
+            .filter(|s| (from_unix..=to_unix).contains(&s.measured_at_unix))
+            .cloned()
+            .collect()
+    }
+
+    pub fn speedtest_stats(&self, now_unix: i64, window_hours: u64) -> Option<SpeedtestStats> {
+        let cutoff = now_unix.saturating_sub((window_hours * 3600) as i64);
+        let window = || {
+            self.speedtest_history
+                .iter()
+                .filter(move |s| s.measured_at_unix >= cutoff)
+        };
+        Some(SpeedtestStats {
+            window_hours,
+            samples: window().count(),
+            download_mbps: SpeedRange::from_values(window().map(|s| s.download_mbps))?,
+            upload_mbps: SpeedRange::from_values(window().map(|s| s.upload_mbps))?,
+            latency_ms: SpeedRange::from_values(window().filter_map(|s| s.latency_ms)),
+        })
+    }
+
+    // Уведомление возвращается, когда серия сбоев достигает порога и когда сборщик восстанавливается после неё.
+    pub fn record_collector_result(
+        &mut self,
+        name: &str,
+        error: Option<String>,
+        now_unix: i64,
+        threshold: u32,
+    ) -> Option<CollectorAlert> {
+        let health = self.collectors.entry(name.to_string()).or_default();
+        let Some(error) = error else {
+            let was_degraded = health.consecutive_failures >= threshold;
+            let failures = health.consecutive_failures;
+            *health = CollectorHealth {
+                consecutive_failures: 0,
+                last_success_unix: Some(now_unix),
+                last_error: None,
+            };
+            return was_degraded.then(|| CollectorAlert {
+                collector: name.to_string(),
+                consecutive_failures: failures,
+                error: None,
+                recovered: true,
+            });
+        };
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.last_error = Some(error.clone());
+        (health.consecutive_failures == threshold).then(|| CollectorAlert {
+            collector: name.to_string(),
+            consecutive_failures: threshold,
+            error: Some(error),
+            recovered: false,
+        })
+    }
+
+    // Источник без данных (датчики LHM, GPU) считается сбоем, только если раньше он что-то отдавал.
+    pub fn record_collector_presence(
+        &mut self,
+        name: &str,
+        has_data: bool,
+        now_unix: i64,
+        threshold: u32,
+    ) -> Option<CollectorAlert> {
+        if !has_data && !self.collectors.contains_key(name) {
+            return None;
+        }
+        let error = (!has_data).then(|| "нет данных".to_string());
+        self.record_collector_result(name, error, now_unix, threshold)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn update_collected(
         &mut self,
         now_unix: i64,
@@ -193,11 +1136,18 @@ impl State {
         uptime_seconds: u64,
         process_count: u64,
         cpu_core_count: u32,
-        cpu_usage_percent: f64,
+        mut cpu_usage_percent: f64,
+        mut cpu_steal: Option<CpuStealStat>,
+        virtualization: Option<String>,
+        time_sync: Option<TimeSyncStat>,
+        mut cgroup: Option<CgroupStat>,
         memory_used_bytes: u64,
         memory_total_bytes: u64,
+        page_file: Option<PageFileStat>,
         disks: Vec<DiskStat>,
+        mut disk_io: Vec<DiskIoStat>,
         mut net: Vec<NetStat>,
+        wireguard: Vec<WireguardPeerStat>,
         internet_speed: Option<InternetSpeedStat>,
         temps: Vec<TempStat>,
         gpus: Vec<GpuStat>,
@@ -206,19 +1156,88 @@ impl State {
     ) {
         let prev_ts = self.last_collect_timestamp_seconds;
         let dt = now_unix.saturating_sub(prev_ts).max(1) as u64;
-        let prev_net: HashMap<String, (u64, u64)> = self
+        let prev_net: HashMap<String, (u64, u64, u64)> = self
             .net
             .iter()
-            .map(|n| (n.iface.clone(), (n.rx_bytes_total, n.tx_bytes_total)))
+            .map(|n| {
+                (
+                    n.iface.clone(),
+                    (n.rx_bytes_total, n.tx_bytes_total, n.errors_total()),
+                )
+            })
             .collect();
 
         for iface in &mut net {
-            if let Some((prev_rx, prev_tx)) = prev_net.get(&iface.iface) {
+            if let Some((prev_rx, prev_tx, prev_errors)) = prev_net.get(&iface.iface) {
                 iface.rx_bytes_per_sec = iface.rx_bytes_total.saturating_sub(*prev_rx) / dt;
                 iface.tx_bytes_per_sec = iface.tx_bytes_total.saturating_sub(*prev_tx) / dt;
+                iface.errors_per_min =
+                    iface.errors_total().saturating_sub(*prev_errors) as f64 * 60.0 / dt as f64;
             } else {
                 iface.rx_bytes_per_sec = 0;
                 iface.tx_bytes_per_sec = 0;
+                iface.errors_per_min = 0.0;
+            }
+        }
+
+        let prev_io: HashMap<&str, &DiskIoStat> = self
+            .disk_io
+            .iter()
+            .map(|d| (d.device.as_str(), d))
+            .collect();
+        for io in &mut disk_io {
+            let Some(prev) = prev_io.get(io.device.as_str()) else {
+                continue;
+            };
+            let reads = io.reads_total.saturating_sub(prev.reads_total);
+            let writes = io.writes_total.saturating_sub(prev.writes_total);
+            let read_ms = io
+                .read_time_ms_total
+                .saturating_sub(prev.read_time_ms_total);
+            let write_ms = io
+                .write_time_ms_total
+                .saturating_sub(prev.write_time_ms_total);
+            let average = |ms: u64, ops: u64| {
+                if ops > 0 {
+                    ms as f64 / ops as f64
+                } else {
+                    0.0
+                }
+            };
+            io.ops_per_sec = (reads + writes) as f64 / dt as f64;
+            io.read_await_ms = average(read_ms, reads);
+            io.write_await_ms = average(write_ms, writes);
+            io.await_ms = average(read_ms + write_ms, reads + writes);
+        }
+
+        if let Some(steal) = &mut cpu_steal {
+            steal.steal_percent = match &self.cpu_steal {
+                Some(prev) if steal.cpu_ticks_total > prev.cpu_ticks_total => {
+                    steal
+                        .steal_ticks_total
+                        .saturating_sub(prev.steal_ticks_total) as f64
+                        * 100.0
+                        / (steal.cpu_ticks_total - prev.cpu_ticks_total) as f64
+                }
+                _ => 0.0,
+            };
+        }
+
+        // Первый сбор без предыдущего счётчика оставляет загрузку хоста.
+        if let Some(cg) = &mut cgroup {
+            let prev_usage = self.cgroup.as_ref().and_then(|c| c.cpu_usage_usec_total);
+            let cores = cg.cpu_limit_cores.unwrap_or(cpu_core_count.max(1) as f64);
+            cg.cpu_percent = cg
+                .cpu_usage_usec_total
+                .zip(prev_usage)
+                .map(|(usage, prev)| {
+                    let used_usec = usage.saturating_sub(prev) as f64;
+                    (used_usec * 100.0 / (dt as f64 * 1_000_000.0 * cores)).min(100.0)
+                });
+            if cg.applied && cg.cpu_limit_cores.is_some() {
+                if let Some(pct) = cg.cpu_percent {
+                    cpu_usage_percent = pct;
+                }
             }
         }
 
@@ -232,15 +1251,23 @@ impl State {
         self.process_count = process_count;
         self.cpu_core_count = cpu_core_count;
         self.cpu_usage_percent = cpu_usage_percent;
+        self.cpu_steal = cpu_steal;
+        self.virtualization = virtualization;
+        self.time_sync = time_sync;
+        self.cgroup = cgroup;
         self.memory_used_bytes = memory_used_bytes;
         self.memory_total_bytes = memory_total_bytes;
-        self.disks = disks;
-        self.net = net;
+        self.page_file = page_file;
+        self.disks = Arc::new(disks);
+        self.disk_io = disk_io;
+        self.net = Arc::new(net);
+        self.wireguard = wireguard;
         self.internet_speed = internet_speed;
-        self.temps = temps;
+        self.temps = Arc::new(temps);
         self.gpus = gpus;
-        self.sensors = sensors;
-        self.checks = checks;
+        self.sensors = Arc::new(sensors);
+        self.checks = Arc::new(checks);
+        self.record_check_latency();
     }
 
     pub fn alerts_enabled_for_chat(&self, chat_id: i64, default_enabled: bool) -> bool {
@@ -298,34 +1325,625 @@ impl State {
         }
     }
 
-    pub fn apply_alert_rules(&mut self, cfg: &AlertsConfig, now_unix: i64) -> Vec<AlertEvent> {
-        let mut events = Vec::new();
+    pub fn resource_alert_snoozed(&self, chat_id: i64, kind: ResourceAlertKind, now: i64) -> bool {
+        self.chat_resource_snoozes
+            .get(&chat_id)
+            .and_then(|snoozes| snoozes.get(&kind))
+            .is_some_and(|until| *until > now)
+    }
 
-        for check in &self.checks.http {
-            let check_id = CheckId {
-                kind: CheckKind::Http,
-                name: check.name.clone(),
-            };
-            update_alert_state(
+    pub fn snooze_resource_alert(
+        &mut self,
+        chat_id: i64,
+        kind: ResourceAlertKind,
+        until: i64,
+        now: i64,
+    ) {
+        let snoozes = self.chat_resource_snoozes.entry(chat_id).or_default();
+        snoozes.retain(|_, until| *until > now);
+        snoozes.insert(kind, until);
+    }
+
+    pub fn clear_resource_snoozes(&mut self, chat_id: i64) {
+        self.chat_resource_snoozes.remove(&chat_id);
+    }
+
+    pub fn resource_snoozes_for_chat(
+        &self,
+        chat_id: i64,
+        now: i64,
+    ) -> Vec<(ResourceAlertKind, i64)> {
+        ResourceAlertKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let until = *self.chat_resource_snoozes.get(&chat_id)?.get(&kind)?;
+                (until > now).then_some((kind, until))
+            })
+            .collect()
+    }
+
+    pub fn resource_threshold_for_chat(
+        &self,
+        chat_id: i64,
+        kind: ResourceAlertKind,
+        cfg: &AlertsConfig,
+    ) -> f64 {
+        self.chat_resource_thresholds
+            .get(&chat_id)
+            .and_then(|m| m.get(&kind))
+            .copied()
+            .unwrap_or_else(|| kind.global_threshold(cfg))
+    }
+
+    pub fn set_resource_threshold_for_chat(
+        &mut self,
+        chat_id: i64,
+        kind: ResourceAlertKind,
+        threshold: Option<f64>,
+    ) {
+        match threshold {
+            Some(v) => {
+                self.chat_resource_thresholds
+                    .entry(chat_id)
+                    .or_default()
+                    .insert(kind, v);
+            }
+            None => {
+                if let Some(m) = self.chat_resource_thresholds.get_mut(&chat_id) {
+                    m.remove(&kind);
+                    if m.is_empty() {
+                        self.chat_resource_thresholds.remove(&chat_id);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn lowest_resource_threshold(
+        &self,
+        kind: ResourceAlertKind,
+        subject: Option<&str>,
+        cfg: &AlertsConfig,
+    ) -> f64 {
+        self.chat_resource_thresholds
+            .values()
+            .filter_map(|m| m.get(&kind).copied())
+            .fold(kind.base_threshold(subject, cfg), f64::min)
+    }
+
+    pub fn resource_severity_for_chat(
+        &self,
+        chat_id: i64,
+        kind: ResourceAlertKind,
+        subject: Option<&str>,
+        value: f64,
+        cfg: &AlertsConfig,
+    ) -> Option<(AlertSeverity, f64)> {
+        let critical = self
+            .chat_resource_thresholds
+            .get(&chat_id)
+            .and_then(|m| m.get(&kind))
+            .copied()
+            .unwrap_or_else(|| kind.base_threshold(subject, cfg));
+        AlertSeverity::classify(value, critical, kind.warning_threshold(subject, cfg))
+    }
+
+    pub fn resource_clear_threshold(
+        &self,
+        kind: ResourceAlertKind,
+        subject: Option<&str>,
+        cfg: &AlertsConfig,
+    ) -> f64 {
+        let trigger = kind
+            .warning_threshold(subject, cfg)
+            .unwrap_or(f64::INFINITY)
+            .min(self.lowest_resource_threshold(kind, subject, cfg));
+        kind.clear_threshold(subject, cfg)
+            .map_or(trigger, |c| c.min(trigger))
+    }
+
+    pub fn record_resource_sample(
+        &mut self,
+        key: &str,
+        now_unix: i64,
+        value: f64,
+        retention_secs: u64,
+    ) {
+        let history = self.resource_history.entry(key.to_string()).or_default();
+        history.push_back(ResourceSample {
+            ts_unix: now_unix,
+            value,
+        });
+        let cutoff = now_unix.saturating_sub(retention_secs as i64);
+        while history.len() > 1 && history.get(1).is_some_and(|s| s.ts_unix <= cutoff) {
+            history.pop_front();
+        }
+    }
+
+    pub fn record_disk_sample(
+        &mut self,
+        mount: &str,
+        now_unix: i64,
+        used_bytes: u64,
+        window_secs: u64,
+    ) {
+        let history = self.disk_history.entry(mount.to_string()).or_default();
+        if history
+            .back()
+            .is_some_and(|s| now_unix - s.ts_unix < DISK_HISTORY_SAMPLE_SECS)
+        {
+            return;
+        }
+        history.push_back(ResourceSample {
+            ts_unix: now_unix,
+            value: used_bytes as f64,
+        });
+        let cutoff = now_unix.saturating_sub(window_secs as i64);
+        while history.front().is_some_and(|s| s.ts_unix < cutoff) {
+            history.pop_front();
+        }
+    }
+
+    // Скорость роста температуры (°C/мин) между самым старым замером в окне и последним.
+    pub fn temperature_rise_rate(
+        &self,
+        sensor: &str,
+        window_secs: i64,
+        now_unix: i64,
+    ) -> Option<f64> {
+        let samples = self
+            .metric_history
+            .get(&("temperature_celsius", sensor.to_string()))?;
+        let last = samples.back()?;
+        let first = samples
+            .iter()
+            .find(|s| s.ts_unix >= now_unix.saturating_sub(window_secs))?;
+        let dt = last.ts_unix - first.ts_unix;
+        // Слишком короткий отрезок даёт шумную оценку.
+        if dt <= 0 || dt * 2 < window_secs {
+            return None;
+        }
+        Some((last.value - first.value) * 60.0 / dt as f64)
+    }
+
+    // Каждый такт добавляется к суточным итогам проверок; сутки старше CHECK_UPTIME_DAYS отбрасываются.
+    pub fn record_check_uptime(&mut self, now_unix: i64) {
+        let day = now_unix.div_euclid(86_400);
+        let checks = self
+            .checks
+            .http
+            .iter()
+            .map(|c| (&c.name, c.up))
+            .chain(self.checks.tcp.iter().map(|c| (&c.name, c.up)))
+            .chain(self.checks.ssh.iter().map(|c| (&c.name, c.up)));
+        for (name, up) in checks {
+            let entry = self
+                .check_uptime
+                .entry((day, name.clone()))
+                .or_insert_with(|| CheckUptimeDay {
+                    day,
+                    check: name.clone(),
+                    up: 0,
+                    total: 0,
+                });
+            entry.up += u64::from(up);
+            entry.total += 1;
+        }
+        let first_day = day - CHECK_UPTIME_DAYS + 1;
+        self.check_uptime
+            .retain(|(entry_day, _), _| *entry_day >= first_day);
+    }
+
+    // Итоги из файла в data_dir; запись за те же сутки заменяет накопленную.
+    pub fn restore_check_uptime(&mut self, days: impl IntoIterator<Item = CheckUptimeDay>) {
+        for day in days {
+            self.check_uptime.insert((day.day, day.check.clone()), day);
+        }
+    }
+
+    pub fn check_uptime_day(&self, check: &str, day: i64) -> Option<&CheckUptimeDay> {
+        self.check_uptime.get(&(day, check.to_string()))
+    }
+
+    pub fn record_metric_history(&mut self, now_unix: i64, cfg: &HistoryConfig) {
+        if !cfg.enabled || now_unix - self.metric_history_sampled_at < cfg.sample_secs as i64 {
+            return;
+        }
+        self.metric_history_sampled_at = now_unix;
+        for (metric, subject, value) in self.history_values() {
+            self.metric_history
+                .entry((metric, subject))
+                .or_default()
+                .push_back(ResourceSample {
+                    ts_unix: now_unix,
+                    value,
+                });
+        }
+        let cutoff = now_unix.saturating_sub((cfg.retention_hours * 3600) as i64);
+        self.metric_history.retain(|_, samples| {
+            while samples.front().is_some_and(|s| s.ts_unix < cutoff) {
+                samples.pop_front();
+            }
+            !samples.is_empty()
+        });
+    }
+
+    fn history_values(&self) -> Vec<(&'static str, String, f64)> {
+        let mut values = vec![
+            ("cpu_usage_percent", String::new(), self.cpu_usage_percent),
+            (
+                "memory_used_bytes",
+                String::new(),
+                self.memory_used_bytes as f64,
+            ),
+        ];
+        for disk in self.disks.iter() {
+            values.push((
+                "disk_used_bytes",
+                disk.mount.clone(),
+                disk.used_bytes as f64,
+            ));
+        }
+        for iface in self.net.iter() {
+            values.push((
+                "net_rx_bytes_per_sec",
+                iface.iface.clone(),
+                iface.rx_bytes_per_sec as f64,
+            ));
+            values.push((
+                "net_tx_bytes_per_sec",
+                iface.iface.clone(),
+                iface.tx_bytes_per_sec as f64,
+            ));
+        }
+        for temp in self.temps.iter() {
+            values.push((
+                "temperature_celsius",
+                temp.sensor.clone(),
+                temp.temperature_celsius,
+            ));
+        }
+        for gpu in &self.gpus {
+            if let Some(util) = gpu.utilization_percent {
+                values.push(("gpu_utilization_percent", gpu.id.clone(), util));
+            }
+        }
+        let checks = self
+            .checks
+            .http
+            .iter()
+            .map(|c| (&c.name, c.up, c.latency_ms))
+            .chain(
+                self.checks
+                    .tcp
+                    .iter()
+                    .map(|c| (&c.name, c.up, c.latency_ms)),
+            )
+            .chain(
+                self.checks
+                    .ssh
+                    .iter()
+                    .map(|c| (&c.name, c.up, c.latency_ms)),
+            );
+        for (name, up, latency_ms) in checks {
+            values.push(("check_up", name.clone(), if up { 1.0 } else { 0.0 }));
+            values.push(("check_latency_ms", name.clone(), latency_ms as f64));
+        }
+        values
+    }
+
+    // Выгрузка постранично: строки строго после курсора (ts_unix, subject), не больше limit.
+    // В пределах одной метрики пара (ts_unix, subject) уникальна, так что страницы не пересекаются.
+    pub fn export_metric_history(
+        &self,
+        metric: &str,
+        subject: Option<&str>,
+        from_unix: i64,
+        to_unix: i64,
+        after: Option<&(i64, String)>,
+        limit: usize,
+    ) -> Vec<HistoryRow> {
+        let mut rows = self
+            .metric_history
+            .iter()
+            .filter(|((m, s), _)| *m == metric && subject.is_none_or(|subject| subject == s))
+            .flat_map(|((m, s), samples)| {
+                samples
+                    .iter()
+                    .filter(|sample| (from_unix..=to_unix).contains(&sample.ts_unix))
+                    .filter(move |sample| {
+                        after.is_none_or(|(ts, subject)| (sample.ts_unix, s) > (*ts, subject))
+                    })
+                    .take(limit)
+                    .map(|sample| HistoryRow {
+                        ts_unix: sample.ts_unix,
+                        metric: m,
+                        subject: s.clone(),
+                        value: sample.value,
+                    })
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| {
+            a.ts_unix
+                .cmp(&b.ts_unix)
+                .then_with(|| a.subject.cmp(&b.subject))
+        });
+        rows.truncate(limit);
+        rows
+    }
+
+    pub fn resource_held_above(&self, key: &str, threshold: f64, since_unix: i64) -> bool {
+        let Some(history) = self.resource_history.get(key) else {
+            return false;
+        };
+        let covered = history.front().is_some_and(|s| s.ts_unix <= since_unix);
+        covered
+            && history
+                .iter()
+                .filter(|s| s.ts_unix >= since_unix)
+                .all(|s| s.value >= threshold)
+    }
+
+    pub fn track_resource_alert(
+        &mut self,
+        kind: ResourceAlertKind,
+        subject: Option<&str>,
+        value: f64,
+        now_unix: i64,
+        cfg: &AlertsConfig,
+    ) -> ResourceAlertTransition {
+        let key = kind.alert_key(subject);
+        let sustained_secs = cfg.sustained_secs(kind.key());
+        self.record_resource_sample(&key, now_unix, value, cfg.max_sustained_secs());
+
+        let trigger = self.lowest_resource_threshold(kind, subject, cfg);
+        let warning = kind.warning_threshold(subject, cfg);
+        let severity =
+            AlertSeverity::classify(value, trigger, warning).map(|(severity, _)| severity);
+        let clear = self.resource_clear_threshold(kind, subject, cfg);
+
+        if let Some(active) = self.active_resource_alerts.get_mut(&key) {
+            if value < clear {
+                let severity = *active;
+                self.active_resource_alerts.remove(&key);
+                return ResourceAlertTransition::Cleared(severity);
+            }
+            if let Some(severity) = severity {
+                *active = (*active).max(severity);
+            }
+            return ResourceAlertTransition::Active;
+        }
+
+        let Some(severity) = severity else {
+            return ResourceAlertTransition::Idle;
+        };
+        if sustained_secs > 0 {
+            let lowest_trigger = warning.map_or(trigger, |w| w.min(trigger));
+            let since = now_unix.saturating_sub(sustained_secs as i64);
+            if !self.resource_held_above(&key, lowest_trigger, since) {
+                return ResourceAlertTransition::Idle;
+            }
+        }
+        self.active_resource_alerts.insert(key, severity);
+        ResourceAlertTransition::Active
+    }
+
+    pub fn detect_mount_changes(&self, disks: &[DiskStat], cfg: &AlertsConfig) -> Vec<MountChange> {
+        if !cfg.mount_changes.enabled || self.last_collect_timestamp_seconds <= 0 {
+            return Vec::new();
+        }
+        let tracked = |d: &DiskStat| {
+            !cfg.mount_ignored(&d.mount) && (!cfg.mount_changes.ignore_removable || !d.removable)
+        };
+        let mut changes = Vec::new();
+        for disk in self.disks.iter().filter(|d| tracked(d)) {
+            if !disks.iter().any(|d| d.mount == disk.mount) {
+                changes.push(MountChange {
+                    mount: disk.mount.clone(),
+                    total_bytes: disk.total_bytes,
+                    appeared: false,
+                });
+            }
+        }
+        for disk in disks.iter().filter(|d| tracked(d)) {
+            if !self.disks.iter().any(|d| d.mount == disk.mount) {
+                changes.push(MountChange {
+                    mount: disk.mount.clone(),
+                    total_bytes: disk.total_bytes,
+                    appeared: true,
+                });
+            }
+        }
+        changes
+    }
+
+    // Раздел, смонтированный только для чтения с самого начала (squashfs, образы), проблемой не считается:
+    // срабатывает перемонтирование ранее записываемого раздела или сбой пробной записи.
+    pub fn detect_read_only(
+        &mut self,
+        disks: &[DiskStat],
+        cfg: &AlertsConfig,
+    ) -> Vec<ReadOnlyMount> {
+        if !cfg.read_only.enabled || self.last_collect_timestamp_seconds <= 0 {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        for disk in disks.iter().filter(|d| !cfg.mount_ignored(&d.mount)) {
+            let key = format!("read_only:{}", disk.mount);
+            let active = self.active_resource_alerts.contains_key(&key);
+            let was_writable = self
+                .disks
+                .iter()
+                .any(|d| d.mount == disk.mount && d.writable());
+            let alert = |recovered| ReadOnlyMount {
+                mount: disk.mount.clone(),
+                read_only: disk.read_only,
+                write_error: disk.write_error.clone(),
+                recovered,
+            };
+            if active && disk.writable() {
+                self.active_resource_alerts.remove(&key);
+                if cfg.recovery_notify {
+                    out.push(alert(true));
+                }
+            } else if !active && (disk.write_error.is_some() || (disk.read_only && was_writable)) {
+                self.active_resource_alerts
+                    .insert(key, AlertSeverity::Critical);
+                out.push(alert(false));
+            }
+        }
+        out
+    }
+
+    pub fn detect_reboot(&self, uptime_seconds: u64, now_unix: i64) -> Option<HostReboot> {
+        if self.last_collect_timestamp_seconds <= 0 || uptime_seconds >= self.system_uptime_seconds
+        {
+            return None;
+        }
+        Some(HostReboot {
+            previous_uptime_secs: self.system_uptime_seconds,
+            boot_time_unix: now_unix.saturating_sub(uptime_seconds as i64),
+        })
+    }
+
+    pub fn collection_age_secs(&self, now_unix: i64) -> i64 {
+        let reference = if self.last_collect_timestamp_seconds > 0 {
+            self.last_collect_timestamp_seconds
+        } else {
+            self.started_at_unix
+        };
+        now_unix.saturating_sub(reference).max(0)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.last_collect_timestamp_seconds > 0 && !self.collector_stale
+    }
+
+    pub fn track_sensor_alert(
+        &mut self,
+        key: &str,
+        value: f64,
+        threshold: f64,
+        clear: f64,
+    ) -> ResourceAlertTransition {
+        if self.active_resource_alerts.contains_key(key) {
+            if value < clear {
+                self.active_resource_alerts.remove(key);
+                return ResourceAlertTransition::Cleared(AlertSeverity::Critical);
+            }
+            return ResourceAlertTransition::Active;
+        }
+        if value >= threshold {
+            self.active_resource_alerts
+                .insert(key.to_string(), AlertSeverity::Critical);
+            return ResourceAlertTransition::Active;
+        }
+        ResourceAlertTransition::Idle
+    }
+
+    pub fn apply_alert_rules(
+        &mut self,
+        cfg: &AlertsConfig,
+        policies: &HashMap<CheckId, CheckAlertPolicy>,
+        dependencies: &HashMap<CheckId, Vec<String>>,
+        now_unix: i64,
+    ) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        let default_policy = CheckAlertPolicy::default();
+        let down: HashSet<String> = self
+            .checks
+            .http
+            .iter()
+            .filter(|c| !c.up)
+            .map(|c| c.name.clone())
+            .chain(
+                self.checks
+                    .tcp
+                    .iter()
+                    .filter(|c| !c.up)
+                    .map(|c| c.name.clone()),
+            )
+            .chain(
+                self.checks
+                    .ssh
+                    .iter()
+                    .filter(|c| !c.up)
+                    .map(|c| c.name.clone()),
+            )
+            .collect();
+        let upstream_of = |check_id: &CheckId, up: bool| {
+            if up {
+                return None;
+            }
+            dependencies
+                .get(check_id)?
+                .iter()
+                .find(|parent| down.contains(*parent))
+                .cloned()
+        };
+
+        for check in &mut Arc::make_mut(&mut self.checks).http {
+            let check_id = CheckId {
+                kind: CheckKind::Http,
+                name: check.name.clone(),
+            };
+            let policy = policies.get(&check_id).unwrap_or(&default_policy);
+            check.affected_by_upstream = upstream_of(&check_id, check.up);
+            update_alert_state(
                 &mut self.alert_tracking,
                 check_id,
                 check.up,
+                check.cached,
+                check.affected_by_upstream.as_deref(),
+                (
+                    check.latency_ms,
+                    Some(check.status_code),
+                    check.error.as_deref(),
+                ),
                 cfg,
+                policy,
                 now_unix,
                 &mut events,
             );
         }
 
-        for check in &self.checks.tcp {
+        for check in &mut Arc::make_mut(&mut self.checks).tcp {
             let check_id = CheckId {
                 kind: CheckKind::Tcp,
                 name: check.name.clone(),
             };
+            let policy = policies.get(&check_id).unwrap_or(&default_policy);
+            check.affected_by_upstream = upstream_of(&check_id, check.up);
             update_alert_state(
                 &mut self.alert_tracking,
                 check_id,
                 check.up,
+                check.cached,
+                check.affected_by_upstream.as_deref(),
+                (check.latency_ms, None, check.error.as_deref()),
                 cfg,
+                policy,
+                now_unix,
+                &mut events,
+            );
+        }
+
+        for check in &mut Arc::make_mut(&mut self.checks).ssh {
+            let check_id = CheckId {
+                kind: CheckKind::Ssh,
+                name: check.name.clone(),
+            };
+            let policy = policies.get(&check_id).unwrap_or(&default_policy);
+            check.affected_by_upstream = upstream_of(&check_id, check.up);
+            update_alert_state(
+                &mut self.alert_tracking,
+                check_id,
+                check.up,
+                check.cached,
+                check.affected_by_upstream.as_deref(),
+                (check.latency_ms, None, check.error.as_deref()),
+                cfg,
+                policy,
                 now_unix,
                 &mut events,
             );
@@ -335,60 +1953,124 @@ impl State {
     }
 }
 
+pub fn predict_seconds_until_full(
+    samples: &VecDeque<ResourceSample>,
+    total_bytes: u64,
+    min_history_secs: i64,
+) -> Option<f64> {
+    let (first, last) = (samples.front()?, samples.back()?);
+    if samples.len() < DISK_FORECAST_MIN_SAMPLES || last.ts_unix - first.ts_unix < min_history_secs
+    {
+        return None;
+    }
+
+    let n = samples.len() as f64;
+    let mean_t = samples
+        .iter()
+        .map(|s| (s.ts_unix - first.ts_unix) as f64)
+        .sum::<f64>()
+        / n;
+    let mean_v = samples.iter().map(|s| s.value).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for s in samples {
+        let dt = (s.ts_unix - first.ts_unix) as f64 - mean_t;
+        cov += dt * (s.value - mean_v);
+        var += dt * dt;
+    }
+    if var <= 0.0 {
+        return None;
+    }
+    let slope = cov / var;
+    if slope <= 0.0 {
+        return None;
+    }
+    let remaining = total_bytes as f64 - last.value;
+    Some((remaining / slope).max(0.0))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_alert_state(
     tracking: &mut HashMap<CheckId, AlertTrackState>,
     check_id: CheckId,
     is_up: bool,
+    cached: bool,
+    upstream: Option<&str>,
+    measured: (u64, Option<u16>, Option<&str>),
     cfg: &AlertsConfig,
+    policy: &CheckAlertPolicy,
     now_unix: i64,
     events: &mut Vec<AlertEvent>,
 ) {
     let entry = tracking.entry(check_id.clone()).or_default();
+    let upstream_action = upstream.map(|_| policy.upstream_down(cfg));
+    let suppressed = upstream_action == Some(UpstreamDownAction::Suppress);
+    let (severity, upstream) = match upstream_action {
+        Some(UpstreamDownAction::Downgrade) => {
+            (AlertSeverity::Warning, upstream.map(str::to_string))
+        }
+        _ => (policy.severity(), None),
+    };
+    let (latency_ms, status_code, error) = measured;
+    let event = |kind, severity, upstream| AlertEvent {
+        check_id: check_id.clone(),
+        kind,
+        severity,
+        upstream,
+        latency_ms,
+        status_code,
+        error: error.map(str::to_string),
+    };
 
     if is_up {
         let was_down = entry.is_down;
+        let was_suppressed = std::mem::take(&mut entry.suppressed_by_upstream);
         entry.consecutive_failures = 0;
         entry.is_down = false;
         if was_down {
             entry.last_state_change_at = Some(now_unix);
-            if cfg.recovery_notify {
-                events.push(AlertEvent {
-                    check_id,
-                    kind: AlertEventKind::Recovered,
-                });
+            // О падении не сообщали, значит и о восстановлении сообщать незачем.
+            if policy.recovery_notify(cfg) && !was_suppressed {
+                events.push(event(AlertEventKind::Recovered, severity, None));
             }
         }
         return;
     }
 
-    entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+    if !cached {
+        entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+    }
 
-    if !entry.is_down && entry.consecutive_failures >= cfg.fail_threshold {
+    if !entry.is_down && entry.consecutive_failures >= policy.fail_threshold(cfg) {
         entry.is_down = true;
         entry.last_state_change_at = Some(now_unix);
+        if suppressed {
+            entry.suppressed_by_upstream = true;
+            return;
+        }
         entry.last_alert_sent_at = Some(now_unix);
-        events.push(AlertEvent {
-            check_id,
-            kind: AlertEventKind::Down,
-        });
+        events.push(event(AlertEventKind::Down, severity, upstream.clone()));
         return;
     }
 
     if entry.is_down {
+        if suppressed {
+            return;
+        }
+        // Вышестоящая проверка восстановилась, а эта нет: теперь это самостоятельный отказ.
+        if std::mem::take(&mut entry.suppressed_by_upstream) {
+            entry.last_alert_sent_at = Some(now_unix);
+            events.push(event(AlertEventKind::Down, severity, upstream.clone()));
+            return;
+        }
+        let repeat_interval_secs = policy.repeat_interval_secs(cfg) as i64;
         match entry.last_alert_sent_at {
-            Some(last_sent) if (now_unix - last_sent) >= cfg.repeat_interval_secs as i64 => {
+            Some(last_sent) if (now_unix - last_sent) >= repeat_interval_secs => {
                 entry.last_alert_sent_at = Some(now_unix);
-                events.push(AlertEvent {
-                    check_id,
-                    kind: AlertEventKind::Repeat,
-                });
+                events.push(event(AlertEventKind::Repeat, severity, upstream.clone()));
             }
             None => {
                 entry.last_alert_sent_at = Some(now_unix);
-                events.push(AlertEvent {
-                    check_id,
-                    kind: AlertEventKind::Repeat,
-                });
+                events.push(event(AlertEventKind::Repeat, severity, upstream.clone()));
             }
             _ => {}
         }
@@ -409,59 +2091,670 @@ mod tests {
         }
     }
 
+    #[test]
+    fn cached_check_results_do_not_count_as_new_failures() {
+        let mut state = State::new(0);
+        let cfg = alerts_cfg();
+        for (i, cached) in [false, true, true, true, false].into_iter().enumerate() {
+            Arc::make_mut(&mut state.checks).tcp = vec![TcpCheckResult {
+                name: "db".to_string(),
+                up: false,
+                latency_ms: 500,
+                tls_handshake_ms: None,
+                tls_cert_valid: None,
+                error: None,
+                affected_by_upstream: None,
+                cached,
+            }];
+            let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), i as i64);
+            assert!(events.is_empty(), "unexpected event at tick {i}");
+        }
+        let id = CheckId {
+            kind: CheckKind::Tcp,
+            name: "db".to_string(),
+        };
+        assert_eq!(state.alert_tracking[&id].consecutive_failures, 2);
+    }
+
+    #[test]
+    fn check_latency_percentiles_use_fresh_successful_runs() {
+        let mut state = State::new(0);
+        let http = |latency_ms, up, cached| HttpCheckResult {
+            name: "api".to_string(),
+            up,
+            latency_ms,
+            status_code: 200,
+            error: None,
+            affected_by_upstream: None,
+            cached,
+        };
+        for ms in 1..=(CHECK_LATENCY_WINDOW as u64 + 20) {
+            Arc::make_mut(&mut state.checks).http = vec![http(ms, true, false)];
+            state.record_check_latency();
+        }
+        Arc::make_mut(&mut state.checks).http = vec![http(9_000, false, false)];
+        state.record_check_latency();
+        Arc::make_mut(&mut state.checks).http = vec![http(9_000, true, true)];
+        state.record_check_latency();
+
+        let snapshot = state.snapshot();
+        let latency = snapshot.check_latency_for("http", "api").unwrap();
+        assert_eq!(latency.samples, CHECK_LATENCY_WINDOW);
+        assert_eq!(
+            (latency.p50_ms, latency.p95_ms, latency.p99_ms),
+            (70, 115, 119)
+        );
+
+        Arc::make_mut(&mut state.checks).http.clear();
+        state.record_check_latency();
+        assert!(state.check_latency().is_empty());
+    }
+
     #[test]
     fn alerts_fail_threshold_and_repeat_and_recovery() {
         let mut state = State::new(0);
         let cfg = alerts_cfg();
 
         for i in 1..=2 {
-            state.checks.http = vec![HttpCheckResult {
+            Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
                 name: "my-api".to_string(),
                 up: false,
                 latency_ms: 100,
                 status_code: 500,
+                error: None,
+                affected_by_upstream: None,
+                cached: false,
             }];
-            let events = state.apply_alert_rules(&cfg, i);
+            let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), i);
             assert!(events.is_empty(), "unexpected event at fail {}", i);
         }
 
-        state.checks.http = vec![HttpCheckResult {
+        Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
             name: "my-api".to_string(),
             up: false,
             latency_ms: 100,
             status_code: 500,
+            error: Some("unexpected status 500".to_string()),
+            affected_by_upstream: None,
+            cached: false,
         }];
-        let events = state.apply_alert_rules(&cfg, 3);
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), 3);
         assert_eq!(events.len(), 1);
         assert!(matches!(events[0].kind, AlertEventKind::Down));
+        // Событие несёт замер, по которому сработало: его видят шаблоны уведомлений.
+        assert_eq!(
+            (events[0].latency_ms, events[0].status_code),
+            (100, Some(500))
+        );
+        assert_eq!(events[0].error.as_deref(), Some("unexpected status 500"));
 
-        state.checks.http = vec![HttpCheckResult {
+        Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
             name: "my-api".to_string(),
             up: false,
             latency_ms: 100,
             status_code: 500,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
         }];
-        let events = state.apply_alert_rules(&cfg, 4);
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), 4);
         assert!(events.is_empty());
 
-        state.checks.http = vec![HttpCheckResult {
+        Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
             name: "my-api".to_string(),
             up: false,
             latency_ms: 100,
             status_code: 500,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
         }];
-        let events = state.apply_alert_rules(&cfg, 3 + 1800);
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), 3 + 1800);
         assert_eq!(events.len(), 1);
         assert!(matches!(events[0].kind, AlertEventKind::Repeat));
 
-        state.checks.http = vec![HttpCheckResult {
+        Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
             name: "my-api".to_string(),
             up: true,
             latency_ms: 100,
             status_code: 200,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
         }];
-        let events = state.apply_alert_rules(&cfg, 20000);
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &HashMap::new(), 20000);
         assert_eq!(events.len(), 1);
         assert!(matches!(events[0].kind, AlertEventKind::Recovered));
     }
+
+    #[test]
+    fn check_policy_overrides_global_alert_settings() {
+        let mut state = State::new(0);
+        let cfg = alerts_cfg();
+        let check_id = CheckId {
+            kind: CheckKind::Tcp,
+            name: "db".to_string(),
+        };
+        let policies = HashMap::from([(
+            check_id,
+            CheckAlertPolicy {
+                fail_threshold: Some(1),
+                recovery_notify: Some(false),
+                severity: Some(AlertSeverity::Warning),
+                ..CheckAlertPolicy::default()
+            },
+        )]);
+
+        Arc::make_mut(&mut state.checks).tcp = vec![TcpCheckResult {
+            name: "db".to_string(),
+            up: false,
+            latency_ms: 0,
+            tls_handshake_ms: None,
+            tls_cert_valid: None,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
+        }];
+        let events = state.apply_alert_rules(&cfg, &policies, &HashMap::new(), 1);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].kind, AlertEventKind::Down));
+        assert_eq!(events[0].severity, AlertSeverity::Warning);
+
+        Arc::make_mut(&mut state.checks).tcp[0].up = true;
+        assert!(state
+            .apply_alert_rules(&cfg, &policies, &HashMap::new(), 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn upstream_down_suppresses_or_downgrades_dependent_alerts() {
+        let mut state = State::new(0);
+        let mut cfg = alerts_cfg();
+        cfg.fail_threshold = 1;
+        let tcp = |name: &str, up: bool| TcpCheckResult {
+            name: name.to_string(),
+            up,
+            latency_ms: 0,
+            tls_handshake_ms: None,
+            tls_cert_valid: None,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
+        };
+        let db = CheckId {
+            kind: CheckKind::Tcp,
+            name: "db".to_string(),
+        };
+        let dependencies = HashMap::from([(db.clone(), vec!["gateway".to_string()])]);
+
+        // Шлюз и база упали вместе: алерт только о шлюзе, база помечена.
+        Arc::make_mut(&mut state.checks).tcp = vec![tcp("gateway", false), tcp("db", false)];
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &dependencies, 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].check_id.name, "gateway");
+        assert_eq!(
+            state.checks.tcp[1].affected_by_upstream.as_deref(),
+            Some("gateway")
+        );
+
+        // Шлюз поднялся, база нет: теперь это её собственный отказ.
+        Arc::make_mut(&mut state.checks).tcp = vec![tcp("gateway", true), tcp("db", false)];
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &dependencies, 2);
+        let down = events
+            .iter()
+            .find(|e| e.check_id == db)
+            .expect("алерт о базе");
+        assert!(matches!(down.kind, AlertEventKind::Down));
+        assert_eq!(down.upstream, None);
+        assert_eq!(state.checks.tcp[1].affected_by_upstream, None);
+
+        // В режиме downgrade алерт уходит с severity warning и именем вышестоящей проверки.
+        let mut state = State::new(0);
+        cfg.upstream_down = UpstreamDownAction::Downgrade;
+        Arc::make_mut(&mut state.checks).tcp = vec![tcp("gateway", false), tcp("db", false)];
+        let events = state.apply_alert_rules(&cfg, &HashMap::new(), &dependencies, 1);
+        let down = events
+            .iter()
+            .find(|e| e.check_id == db)
+            .expect("алерт о базе");
+        assert_eq!(down.severity, AlertSeverity::Warning);
+        assert_eq!(down.upstream.as_deref(), Some("gateway"));
+    }
+
+    #[test]
+    fn severity_classification_uses_warning_tier() {
+        assert_eq!(AlertSeverity::classify(80.0, 95.0, None), None);
+        assert_eq!(
+            AlertSeverity::classify(90.0, 95.0, Some(85.0)),
+            Some((AlertSeverity::Warning, 85.0))
+        );
+        assert_eq!(
+            AlertSeverity::classify(96.0, 95.0, Some(85.0)),
+            Some((AlertSeverity::Critical, 95.0))
+        );
+    }
+
+    #[test]
+    fn resource_alert_clears_only_below_clear_threshold() {
+        let mut state = State::new(0);
+        let mut cfg = alerts_cfg();
+        cfg.cpu_load_threshold_percent = 92.0;
+        cfg.cpu_load_clear_threshold_percent = Some(80.0);
+        let kind = ResourceAlertKind::CpuLoad;
+
+        assert_eq!(
+            state.track_resource_alert(kind, None, 90.0, 0, &cfg),
+            ResourceAlertTransition::Idle
+        );
+        assert_eq!(
+            state.track_resource_alert(kind, None, 95.0, 5, &cfg),
+            ResourceAlertTransition::Active
+        );
+        assert_eq!(
+            state.track_resource_alert(kind, None, 85.0, 10, &cfg),
+            ResourceAlertTransition::Active
+        );
+        assert_eq!(
+            state.track_resource_alert(kind, None, 79.0, 15, &cfg),
+            ResourceAlertTransition::Cleared(AlertSeverity::Critical)
+        );
+        assert!(state.active_resource_alerts.is_empty());
+    }
+
+    #[test]
+    fn sustained_condition_filters_short_spikes() {
+        let mut state = State::new(0);
+        let mut cfg = alerts_cfg();
+        cfg.cpu_load_threshold_percent = 90.0;
+        cfg.sustained_secs.insert("cpu_load".to_string(), 30);
+        let kind = ResourceAlertKind::CpuLoad;
+
+        for (ts, value) in [(0, 95.0), (10, 50.0), (20, 95.0), (30, 96.0), (40, 97.0)] {
+            assert_eq!(
+                state.track_resource_alert(kind, None, value, ts, &cfg),
+                ResourceAlertTransition::Idle,
+                "ts {ts}"
+            );
+        }
+        assert_eq!(
+            state.track_resource_alert(kind, None, 95.0, 50, &cfg),
+            ResourceAlertTransition::Active
+        );
+        assert!(state.resource_history["cpu_load"].len() <= 5);
+    }
+
+    #[test]
+    fn disk_fill_prediction_uses_linear_trend() {
+        let mut state = State::new(0);
+        for minute in 0..=30 {
+            state.record_disk_sample("/data", minute * 60, 500 + minute as u64 * 10, 6 * 3600);
+            state.record_disk_sample("/data", minute * 60 + 5, 0, 6 * 3600);
+        }
+        let history = &state.disk_history["/data"];
+        assert_eq!(history.len(), 31);
+
+        let eta = predict_seconds_until_full(history, 1100, 1800).expect("растущий тренд");
+        assert!((eta - 30.0 * 60.0).abs() < 1.0, "eta {eta}");
+        assert_eq!(predict_seconds_until_full(history, 1100, 7200), None);
+
+        let flat = (0..10)
+            .map(|i| ResourceSample {
+                ts_unix: i * 60,
+                value: 500.0,
+            })
+            .collect::<VecDeque<_>>();
+        assert_eq!(predict_seconds_until_full(&flat, 1100, 60), None);
+    }
+
+    #[test]
+    fn mount_changes_skip_removable_and_ignored() {
+        let disk = |mount: &str, removable: bool| DiskStat {
+            mount: mount.to_string(),
+            used_bytes: 0,
+            total_bytes: 100,
+            removable,
+            device: None,
+            temperature_celsius: None,
+            read_only: false,
+            write_error: None,
+        };
+        let cfg = AlertsConfig {
+            ignore_mounts: vec!["/snap/*".to_string()],
+            ..AlertsConfig::default()
+        };
+        let mut state = State::new(0);
+        state.disks = vec![
+            disk("/", false),
+            disk("/backup", false),
+            disk("/media/usb", true),
+        ]
+        .into();
+        assert!(state.detect_mount_changes(&[], &cfg).is_empty());
+
+        state.last_collect_timestamp_seconds = 100;
+        let changes = state.detect_mount_changes(
+            &[
+                disk("/", false),
+                disk("/data", false),
+                disk("/snap/core", false),
+            ],
+            &cfg,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                MountChange {
+                    mount: "/backup".to_string(),
+                    total_bytes: 100,
+                    appeared: false,
+                },
+                MountChange {
+                    mount: "/data".to_string(),
+                    total_bytes: 100,
+                    appeared: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_only_remount_and_failed_probe_are_reported_once() {
+        let disk = |mount: &str, read_only: bool, write_error: Option<&str>| DiskStat {
+            mount: mount.to_string(),
+            used_bytes: 0,
+            total_bytes: 100,
+            removable: false,
+            device: None,
+            temperature_celsius: None,
+            read_only,
+            write_error: write_error.map(str::to_string),
+        };
+        let cfg = AlertsConfig::default();
+        let mut state = State::new(0);
+        state.last_collect_timestamp_seconds = 100;
+        state.disks = vec![
+            disk("/", false, None),
+            disk("/snap/core", true, None),
+            disk("/data", false, None),
+        ]
+        .into();
+        let next = vec![
+            disk("/", true, None),
+            disk("/snap/core", true, None),
+            disk("/data", false, Some("No space left on device")),
+        ];
+        let alerts = state.detect_read_only(&next, &cfg);
+        assert_eq!(
+            alerts.iter().map(|a| a.mount.as_str()).collect::<Vec<_>>(),
+            vec!["/", "/data"]
+        );
+        assert!(alerts.iter().all(|a| !a.recovered));
+        state.disks = next.clone().into();
+        assert!(state.detect_read_only(&next, &cfg).is_empty());
+
+        let healed = vec![disk("/", false, None), disk("/data", false, None)];
+        let alerts = state.detect_read_only(&healed, &cfg);
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts.iter().all(|a| a.recovered));
+    }
+
+    #[test]
+    fn reboot_detected_when_uptime_resets() {
+        let mut state = State::new(0);
+        assert_eq!(state.detect_reboot(10, 100), None);
+
+        state.last_collect_timestamp_seconds = 100;
+        state.system_uptime_seconds = 5000;
+        assert_eq!(state.detect_reboot(5005, 105), None);
+        assert_eq!(
+            state.detect_reboot(30, 200),
+            Some(HostReboot {
+                previous_uptime_secs: 5000,
+                boot_time_unix: 170,
+            })
+        );
+    }
+
+    #[test]
+    fn metric_history_samples_and_expires() {
+        let mut state = State::new(0);
+        state.disks = vec![DiskStat {
+            mount: "/data".to_string(),
+            used_bytes: 100,
+            total_bytes: 1000,
+            removable: false,
+            device: None,
+            temperature_celsius: None,
+            read_only: false,
+            write_error: None,
+        }]
+        .into();
+        let cfg = HistoryConfig {
+            enabled: true,
+            retention_hours: 1,
+            sample_secs: 60,
+            ..HistoryConfig::default()
+        };
+        for ts in [100, 130, 160, 3720, 3750] {
+            state.cpu_usage_percent = ts as f64;
+            state.record_metric_history(ts, &cfg);
+        }
+        let cpu =
+            state.export_metric_history("cpu_usage_percent", None, i64::MIN, i64::MAX, None, 10);
+        assert_eq!(
+            cpu.iter().map(|r| r.ts_unix).collect::<Vec<_>>(),
+            vec![160, 3720]
+        );
+        let first_page =
+            state.export_metric_history("cpu_usage_percent", None, i64::MIN, i64::MAX, None, 1);
+        let cursor = (first_page[0].ts_unix, first_page[0].subject.clone());
+        let next_page = state.export_metric_history(
+            "cpu_usage_percent",
+            None,
+            i64::MIN,
+            i64::MAX,
+            Some(&cursor),
+            1,
+        );
+        assert_eq!([&first_page[..], &next_page[..]].concat(), cpu);
+        let disk = state.export_metric_history("disk_used_bytes", Some("/data"), 0, 3000, None, 10);
+        assert_eq!(
+            disk,
+            vec![HistoryRow {
+                ts_unix: 160,
+                metric: "disk_used_bytes",
+                subject: "/data".to_string(),
+                value: 100.0,
+            }]
+        );
+        assert!(state
+            .export_metric_history(
+                "disk_used_bytes",
+                Some("/other"),
+                i64::MIN,
+                i64::MAX,
+                None,
+                10
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn temperature_rise_rate_uses_history_window() {
+        let mut state = State::new(0);
+        let cfg = HistoryConfig {
+            enabled: true,
+            retention_hours: 1,
+            sample_secs: 60,
+            ..HistoryConfig::default()
+        };
+        for (ts, celsius) in [
+            (60, 40.0),
+            (120, 41.0),
+            (180, 45.0),
+            (240, 51.0),
+            (300, 58.0),
+        ] {
+            state.temps = vec![TempStat {
+                sensor: "CPU Package".to_string(),
+                temperature_celsius: celsius,
+                critical_temperature_celsius: None,
+                device: None,
+            }]
+            .into();
+            state.record_metric_history(ts, &cfg);
+        }
+        // Окно 2 минуты: от 45 °C на 180 с до 58 °C на 300 с.
+        let rate = state
+            .temperature_rise_rate("CPU Package", 120, 300)
+            .unwrap();
+        assert!((rate - 6.5).abs() < 1e-9, "{rate}");
+        // Двух замеров на половину окна не хватает.
+        assert_eq!(state.temperature_rise_rate("CPU Package", 600, 900), None);
+        assert_eq!(state.temperature_rise_rate("GPU", 120, 300), None);
+    }
+
+    #[test]
+    fn disk_alerts_use_per_mount_thresholds() {
+        let state = State::new(0);
+        let mut cfg = alerts_cfg();
+        cfg.disk_usage_threshold_percent = 90.0;
+        cfg.disk_alerts.insert(
+            "/backup".to_string(),
+            DiskAlertConfig {
+                threshold_percent: Some(99.0),
+                warning_threshold_percent: None,
+                clear_threshold_percent: None,
+            },
+        );
+        let kind = ResourceAlertKind::DiskUsage;
+
+        assert_eq!(
+            state.resource_severity_for_chat(1, kind, Some("/backup"), 95.0, &cfg),
+            None
+        );
+        assert_eq!(
+            state.resource_severity_for_chat(1, kind, Some("/"), 95.0, &cfg),
+            Some((AlertSeverity::Critical, 90.0))
+        );
+        assert_eq!(kind.alert_key(Some("/backup")), "disk_usage:/backup");
+    }
+
+    #[test]
+    fn chat_threshold_overrides_fall_back_to_global() {
+        let mut state = State::new(0);
+        let cfg = alerts_cfg();
+
+        assert_eq!(
+            state.resource_threshold_for_chat(1, ResourceAlertKind::CpuTemp, &cfg),
+            cfg.cpu_temp_threshold_celsius
+        );
+
+        state.set_resource_threshold_for_chat(1, ResourceAlertKind::CpuTemp, Some(70.0));
+        assert_eq!(
+            state.resource_threshold_for_chat(1, ResourceAlertKind::CpuTemp, &cfg),
+            70.0
+        );
+        assert_eq!(
+            state.resource_threshold_for_chat(2, ResourceAlertKind::CpuTemp, &cfg),
+            cfg.cpu_temp_threshold_celsius
+        );
+        assert_eq!(
+            state.lowest_resource_threshold(ResourceAlertKind::CpuTemp, None, &cfg),
+            70.0
+        );
+
+        assert_eq!(
+            state.resource_severity_for_chat(1, ResourceAlertKind::CpuTemp, None, 72.0, &cfg),
+            Some((AlertSeverity::Critical, 70.0))
+        );
+
+        state.set_resource_threshold_for_chat(1, ResourceAlertKind::CpuTemp, None);
+        assert!(state.chat_resource_thresholds.is_empty());
+        assert_eq!(
+            state.lowest_resource_threshold(ResourceAlertKind::CpuTemp, None, &cfg),
+            cfg.cpu_temp_threshold_celsius
+        );
+    }
+
+    #[test]
+    fn collector_failures_alert_once_and_recover() {
+        let mut state = State::new(0);
+        let fail = || Some("timeout".to_string());
+        assert!(state
+            .record_collector_result("plugin:queues", fail(), 10, 3)
+            .is_none());
+        assert!(state
+            .record_collector_result("plugin:queues", fail(), 20, 3)
+            .is_none());
+        let alert = state
+            .record_collector_result("plugin:queues", fail(), 30, 3)
+            .expect("третий сбой подряд");
+        assert!(!alert.recovered);
+        assert_eq!(alert.consecutive_failures, 3);
+        assert!(state
+            .record_collector_result("plugin:queues", fail(), 40, 3)
+            .is_none());
+        assert_eq!(state.collectors["plugin:queues"].consecutive_failures, 4);
+
+        let recovered = state
+            .record_collector_result("plugin:queues", None, 50, 3)
+            .expect("восстановление");
+        assert!(recovered.recovered);
+        assert_eq!(
+            state.collectors["plugin:queues"],
+            CollectorHealth {
+                consecutive_failures: 0,
+                last_success_unix: Some(50),
+                last_error: None,
+            }
+        );
+
+        // Источник, который ни разу не отдавал данных, не считается сломанным.
+        assert!(state
+            .record_collector_presence("gpus", false, 60, 1)
+            .is_none());
+        assert!(!state.collectors.contains_key("gpus"));
+        state.record_collector_presence("gpus", true, 60, 1);
+        assert!(state
+            .record_collector_presence("gpus", false, 70, 1)
+            .is_some());
+    }
+
+    #[test]
+    fn snapshot_shares_collected_lists_with_state() {
+        let mut state = State::new(0);
+        state.disks = vec![DiskStat {
+            mount: "/".to_string(),
+            used_bytes: 1,
+            total_bytes: 2,
+            removable: false,
+            device: None,
+            temperature_celsius: None,
+            read_only: false,
+            write_error: None,
+        }]
+        .into();
+        Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
+            name: "api".to_string(),
+            up: true,
+            latency_ms: 1,
+            status_code: 200,
+            error: None,
+            affected_by_upstream: None,
+            cached: false,
+        }];
+        let snapshot = Arc::new(state.snapshot());
+        assert!(Arc::ptr_eq(&snapshot.disks, &state.disks));
+        assert!(Arc::ptr_eq(&snapshot.net, &state.net));
+        assert!(Arc::ptr_eq(&snapshot.temps, &state.temps));
+        assert!(Arc::ptr_eq(&snapshot.sensors, &state.sensors));
+        assert!(Arc::ptr_eq(&snapshot.checks, &state.checks));
+
+        // Следующий такт заменяет списки, а опубликованный снимок остаётся прежним.
+        let next = state.snapshot();
+        state.disks = Vec::new().into();
+        assert_eq!(snapshot.disks.len(), 1);
+        assert!(Arc::ptr_eq(&next.checks, &snapshot.checks));
+    }
 }
@@ -0,0 +1,159 @@
+use crate::state::{State, CHECK_UPTIME_DAYS};
+use chrono::DateTime;
+use serde::Serialize;
+
+const DAYS: i64 = CHECK_UPTIME_DAYS;
+const DAY_SECS: i64 = 86_400;
+
+// Имя с расширением .html включает в minijinja экранирование HTML.
+const TEMPLATE_NAME: &str = "status.html";
+const TEMPLATE: &str = r#"<!doctype html>
+<html lang="ru">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="60">
+<title>{{ title }}</title>
+<style>
+body { font-family: system-ui, sans-serif; background: #f5f6f8; color: #1f2328; margin: 0; }
+main { max-width: 760px; margin: 0 auto; padding: 24px 16px; }
+h1 { font-size: 24px; margin: 0 0 4px; }
+.summary { padding: 12px 16px; border-radius: 8px; color: #fff; margin: 16px 0; font-weight: 600; }
+.summary.up { background: #2da44e; } .summary.down { background: #cf222e; }
+.check { background: #fff; border-radius: 8px; padding: 12px 16px; margin-bottom: 12px; }
+.head { display: flex; justify-content: space-between; align-items: center; margin-bottom: 8px; }
+.badge { border-radius: 12px; padding: 2px 10px; font-size: 13px; color: #fff; }
+.badge.up { background: #2da44e; } .badge.down { background: #cf222e; }
+.kind { color: #656d76; font-size: 13px; margin-left: 6px; }
+.bars { display: flex; gap: 2px; height: 28px; }
+.bars span { flex: 1; border-radius: 2px; }
+.bars .up { background: #2da44e; } .bars .partial { background: #d4a72c; }
+.bars .down { background: #cf222e; } .bars .none { background: #d0d7de; }
+.foot { display: flex; justify-content: space-between; color: #656d76; font-size: 12px; margin-top: 4px; }
+footer { color: #656d76; font-size: 12px; margin-top: 24px; }
+</style>
+</head>
+<body>
+<main>
+<h1>{{ title }}</h1>
+{% if all_up %}<div class="summary up">Все сервисы работают</div>{% else %}<div class="summary down">Есть недоступные сервисы</div>{% endif %}
+{% for check in checks %}
+<div class="check">
+<div class="head">
+<div><b>{{ check.name }}</b><span class="kind">{{ check.kind }}</span></div>
+<span class="badge {{ 'up' if check.up else 'down' }}">{{ 'работает' if check.up else 'недоступен' }}</span>
+</div>
+<div class="bars">{% for day in check.days %}<span class="{{ day.class }}" title="{{ day.date }}: {{ day.label }}"></span>{% endfor %}</div>
+<div class="foot"><span>{{ days }} дней назад</span><span>{{ check.uptime }}</span><span>сегодня</span></div>
+</div>
+{% else %}
+<p>Проверки не настроены.</p>
+{% endfor %}
+<footer>Обновлено {{ updated }}</footer>
+</main>
+</body>
+</html>
+"#;
+
+#[derive(Serialize)]
+struct CheckRow {
+    name: String,
+    kind: &'static str,
+    up: bool,
+    uptime: String,
+    days: Vec<DayBar>,
+}
+
+#[derive(Serialize)]
+struct DayBar {
+    date: String,
+    label: String,
+    class: &'static str,
+}
+
+pub fn render(title: &str, state: &State, now_unix: i64) -> Result<String, minijinja::Error> {
+    let checks = &state.checks;
+    let current = checks
+        .http
+        .iter()
+        .map(|c| (c.name.as_str(), "HTTP", c.up))
+        .chain(checks.tcp.iter().map(|c| (c.name.as_str(), "TCP", c.up)))
+        .chain(checks.ssh.iter().map(|c| (c.name.as_str(), "SSH", c.up)));
+    let rows = current
+        .map(|(name, kind, up)| {
+            let days = uptime_days(state, name, now_unix);
+            let total = days
+                .iter()
+                .flatten()
+                .fold((0.0, 0_usize), |(sum, n), (up, count)| {
+                    (sum + up * *count as f64, n + count)
+                });
+            CheckRow {
+                name: name.to_string(),
+                kind,
+                up,
+                uptime: if total.1 == 0 {
+                    "нет истории".to_string()
+                } else {
+                    format!("{:.2}% доступности", total.0 / total.1 as f64 * 100.0)
+                },
+                days: days
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, day)| day_bar(now_unix, idx, *day))
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut env = minijinja::Environment::new();
+    env.add_template(TEMPLATE_NAME, TEMPLATE)?;
+    env.get_template(TEMPLATE_NAME)?
+        .render(minijinja::context! {
+            title,
+            all_up => rows.iter().all(|r| r.up),
+            checks => minijinja::value::Serde(&rows),
+            days => DAYS,
+            updated => DateTime::from_timestamp(state.last_collect_timestamp_seconds.max(0), 0)
+                .unwrap_or_default()
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+        })
+}
+
+// Доля тактов «доступна» и их число за каждые UTC-сутки, от самых старых к сегодняшним.
+fn uptime_days(state: &State, check: &str, now_unix: i64) -> Vec<Option<(f64, usize)>> {
+    let first_day = now_unix.div_euclid(DAY_SECS) - DAYS + 1;
+    (first_day..first_day + DAYS)
+        .map(|day| {
+            state
+                .check_uptime_day(check, day)
+                .filter(|d| d.total > 0)
+                .map(|d| (d.up as f64 / d.total as f64, d.total as usize))
+        })
+        .collect()
+}
+
+fn day_bar(now_unix: i64, idx: usize, day: Option<(f64, usize)>) -> DayBar {
+    let day_start = (now_unix.div_euclid(DAY_SECS) - DAYS + 1 + idx as i64) * DAY_SECS;
+    let date = DateTime::from_timestamp(day_start, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string();
+    let Some((up, _)) = day else {
+        return DayBar {
+            date,
+            label: "нет данных".to_string(),
+            class: "none",
+        };
+    };
+    DayBar {
+        date,
+        label: format!("{:.2}%", up * 100.0),
+        class: match up {
+            u if u >= 0.99 => "up",
+            u if u >= 0.9 => "partial",
+            _ => "down",
+        },
+    }
+}
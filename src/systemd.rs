@@ -0,0 +1,142 @@
+use crate::config::Config;
+use crate::state::State;
+use std::ffi::OsStr;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, RwLock};
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+pub fn notify(message: &str) -> bool {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    match send(&socket, message) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!(error = %err, message, "не удалось отправить уведомление systemd");
+            false
+        }
+    }
+}
+
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    let usec = usec?.trim().parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    if pid.is_some_and(|pid| pid.trim().parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+pub async fn run_watchdog(
+    interval: Duration,
+    state: Arc<RwLock<State>>,
+    config_rx: watch::Receiver<Arc<Config>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    info!(interval = ?interval, "включён сторожевой таймер systemd");
+    let mut ticker = tokio::time::interval(interval / 2);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                let stale_secs = config_rx.borrow().collector_stale_secs as i64;
+                let age = state.read().await.collection_age_secs(now);
+                // Пока сборщик стоит, пинги не отправляются, и systemd перезапустит агент по WatchdogSec.
+                if age <= stale_secs {
+                    notify("WATCHDOG=1");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &OsStr, message: &str) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        Some(name) => send_abstract(&datagram, name, message),
+        None => datagram.send_to(message.as_bytes(), socket).map(|_| ()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(
+    datagram: &std::os::unix::net::UnixDatagram,
+    name: &[u8],
+    message: &str,
+) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let addr = SocketAddr::from_abstract_name(name)?;
+    datagram.send_to_addr(message.as_bytes(), &addr).map(|_| ())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn send_abstract(
+    _datagram: &std::os::unix::net::UnixDatagram,
+    _name: &[u8],
+    _message: &str,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "абстрактные сокеты поддерживаются только в Linux",
+    ))
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &OsStr, _message: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval_requires_matching_pid() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 7),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn notify_message_reaches_socket() {
+        let path = std::env::temp_dir().join(format!("monitord-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0_u8; 16];
+        let len = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,18 +1,35 @@
-use crate::config::TelegramConfig;
+use crate::actions::run_action;
+use crate::aggregator::{FleetAlert, HostState, Hosts};
+use crate::config::{AlertLinkConfig, TelegramConfig};
+use crate::events::{next_event, Alert, Event};
+use crate::external::ExternalAlert;
+use crate::logbuf::{LogBuffer, LogEntry};
+use crate::metrics::Metrics;
+use crate::notify::Notification;
+use crate::pairing::Pairing;
+use crate::routing::Router;
+use crate::rules::RuleAlert;
 use crate::state::{
-    AlertEvent, AlertEventKind, CheckKind, ResourceAlert, ResourceAlertKind, State,
+    wireguard_short_key, AlertEvent, AlertEventKind, AlertSeverity, CheckKind, CollectorAlert,
+    CpuStealAlert, DirSizeAlert, DiskForecast, DiskLatencyAlert, GpuAlertContext, HostReboot,
+    MountChange, NetErrorAlert, PageFileAlert, ReadOnlyMount, ResourceAlert, ResourceAlertKind,
+    SensorAlert, SharedSnapshot, Snapshot, SpeedRange, SpeedtestStats, State, TempRiseAlert,
+    TimeSyncAlert, WireguardAlert,
 };
+use crate::units::Units;
+use chrono_tz::Tz;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use teloxide::prelude::*;
 use teloxide::types::{
-    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
-    ParseMode,
+    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery,
+    InlineQueryResult, InlineQueryResultArticle, InputFile, InputMessageContent,
+    InputMessageContentText, Message, MessageId, ParseMode,
 };
 use thiserror::Error;
-use tokio::sync::{watch, Mutex, RwLock};
-use tracing::{info, warn};
+use tokio::sync::{broadcast, watch, Mutex, RwLock};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Error)]
 pub enum TelegramError {
@@ -24,24 +41,173 @@ pub enum TelegramError {
 struct TelegramRuntime {
     cfg: TelegramConfig,
     shared_state: Arc<RwLock<State>>,
+    snapshot: SharedSnapshot,
     allowed_chats: HashSet<i64>,
     limiter: Arc<Mutex<RateLimiter>>,
-    dashboard_messages: Arc<Mutex<HashMap<i64, i32>>>,
-    speed_history: Arc<Mutex<VecDeque<SpeedSample>>>,
+    dashboard_messages: Arc<Mutex<HashMap<i64, DashboardMessage>>>,
+    view_pages: Arc<Mutex<HashMap<i64, ViewPages>>>,
+    speed_history: SpeedHistory,
+    logs: LogBuffer,
+    pairing: Option<Arc<Pairing>>,
+    hosts: Hosts,
+    selected_hosts: Arc<Mutex<HashMap<i64, String>>>,
+    // Чаты, чьё сообщение-дашборд сейчас показывает сводку, и время её последней отрисовки.
+    live_dashboards: Arc<Mutex<HashMap<i64, i64>>>,
+    units: Units,
+    health: BotHealth,
 }
 
-#[derive(Clone, Copy)]
+// Сообщение-дашборд чата, которое редактируется вместо отправки нового.
+#[derive(Debug, Clone, Copy)]
+struct DashboardMessage {
+    id: i32,
+    used_unix: i64,
+}
+
+// Вид, не поместившийся в одно сообщение: части листаются кнопкой «Показать ещё».
+#[derive(Debug, Clone)]
+struct ViewPages {
+    pages: Vec<String>,
+    keyboard: InlineKeyboardMarkup,
+}
+
+// Данные чатов, где давно не было запросов, вычищаются, чтобы не копиться в памяти.
+const CHAT_STATE_TTL_SECS: i64 = 86_400;
+const CHAT_STATE_PRUNE_EVERY: Duration = Duration::from_secs(600);
+// Ответ на inline-запрос кэшируется Telegram недолго: цифры должны оставаться свежими.
+const INLINE_CACHE_SECS: u32 = 10;
+// Чаще раза в минуту дашборд не редактируется: так заведомо хватает лимитов Telegram на правки.
+const AUTO_REFRESH_CHECK_EVERY: Duration = Duration::from_secs(30);
+const AUTO_REFRESH_MAX_MINS: u64 = 1440;
+// Спарклайн задержки в /checks: последний час истории, сжатый до 12 столбиков.
+const SPARKLINE_WINDOW_SECS: i64 = 3600;
+const SPARKLINE_WIDTH: usize = 12;
+// Варианты отсрочки ресурсного уведомления на кнопках под ним, в часах.
+const SNOOZE_HOURS: [u64; 3] = [1, 6, 24];
+
+pub type SpeedHistory = Arc<Mutex<VecDeque<SpeedSample>>>;
+
+// Сплошная полоса ошибок Telegram API: с момента since не прошло ни одного запроса.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FailureStreak {
+    since: i64,
+    errors: u64,
+    reported: bool,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    last_success: Option<i64>,
+    streak: Option<FailureStreak>,
+    // Полоса, о которой уже предупредили, и момент, когда запросы снова прошли.
+    recovered: Option<(FailureStreak, i64)>,
+}
+
+// Исходы запросов бота к Telegram API, общие для диалогов и доставки уведомлений.
+#[derive(Clone)]
+pub struct BotHealth {
+    metrics: Arc<Metrics>,
+    state: Arc<std::sync::Mutex<HealthState>>,
+}
+
+impl BotHealth {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            metrics,
+            state: Arc::default(),
+        }
+    }
+
+    fn observe<T>(&self, result: &Result<T, teloxide::RequestError>, now: i64) {
+        match result {
+            Ok(_) | Err(teloxide::RequestError::Api(teloxide::ApiError::MessageNotModified)) => {
+                self.succeeded(now)
+            }
+            Err(err) => self.failed(err, now),
+        }
+    }
+
+    // Уведомление, которое не удалось отправить, считается потерянным.
+    fn observe_delivery<T>(&self, result: &Result<T, teloxide::RequestError>, now: i64) {
+        self.observe(result, now);
+        if result.is_err() {
+            self.metrics.inc_notification_failed("telegram");
+        }
+    }
+
+    fn succeeded(&self, now: i64) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.last_success = Some(now);
+        if let Some(streak) = state.streak.take().filter(|streak| streak.reported) {
+            state.recovered = Some((streak, now));
+        }
+        self.metrics.set_telegram_health(state.last_success, None);
+    }
+
+    fn failed(&self, err: &teloxide::RequestError, now: i64) {
+        self.metrics.inc_telegram_api_error(api_error_reason(err));
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let streak = state.streak.get_or_insert(FailureStreak {
+            since: now,
+            errors: 0,
+            reported: false,
+        });
+        streak.errors += 1;
+        let since = streak.since;
+        self.metrics
+            .set_telegram_health(state.last_success, Some(since));
+    }
+
+    fn throttled(&self) {
+        self.metrics.inc_telegram_throttled();
+    }
+
+    // Полоса ошибок отдаётся один раз, когда она длится не меньше warn_after_secs.
+    fn overdue(&self, now: i64, warn_after_secs: u64) -> Option<FailureStreak> {
+        if warn_after_secs == 0 {
+            return None;
+        }
+        let mut state = self.state.lock().ok()?;
+        let streak = state.streak.as_mut()?;
+        if streak.reported || now - streak.since < warn_after_secs as i64 {
+            return None;
+        }
+        streak.reported = true;
+        Some(*streak)
+    }
+
+    fn take_recovered(&self) -> Option<(FailureStreak, i64)> {
+        self.state.lock().ok()?.recovered.take()
+    }
+}
+
+fn api_error_reason(err: &teloxide::RequestError) -> &'static str {
+    match err {
+        teloxide::RequestError::RetryAfter(_) => "rate_limited",
+        teloxide::RequestError::Network(_) => "network",
+        teloxide::RequestError::Api(_) => "api",
+        _ => "other",
+    }
+}
+
+#[derive(Clone)]
 enum Action {
     Start,
     Help,
     Refresh,
     Dashboard,
     System,
-    Sensors,
-    Network,
+    Sensors(usize),
+    Network(usize),
     Speed,
-    Disks,
+    Disks(usize),
+    DirSizes,
     Gpu,
+    Checks(usize),
     Alerts,
     ToggleAlerts,
     ToggleChecksAlert,
@@ -51,8 +217,34 @@ enum Action {
     ToggleGpuLoadAlert,
     ToggleRamUsageAlert,
     ToggleDiskUsageAlert,
+    Thresholds,
+    StepThreshold(ResourceAlertKind, f64),
+    SetThreshold(ResourceAlertKind, Option<f64>),
+    ResetThresholds,
+    SetThresholdUsage,
+    SetTimezone(Option<Tz>),
+    TimezoneUsage,
+    SetAutoRefresh(Option<u64>),
+    AutoRefreshUsage,
+    Snooze(ResourceAlertKind, u64),
+    ClearSnoozes,
+    Logs,
+    RemoteCommands,
+    ConfirmRemoteCommand(String),
+    RunRemoteCommand(String),
+    Export,
+    Hosts,
+    Compare,
+    SelectHost(Option<usize>),
+    MorePage(usize),
 }
 
+const THRESHOLD_STEP: f64 = 5.0;
+const LOGS_TAIL_LINES: usize = 30;
+const MESSAGE_TEXT_LIMIT: usize = 3800;
+// Сверх этого числа частей длинный вид обрезается.
+const MAX_VIEW_PAGES: usize = 10;
+
 impl Action {
     fn from_command(text: &str) -> Option<Self> {
         let first = text.split_whitespace().next()?;
@@ -62,26 +254,93 @@ impl Action {
             "/help" => Some(Self::Help),
             "/status" => Some(Self::Dashboard),
             "/system" => Some(Self::System),
-            "/sensors" => Some(Self::Sensors),
-            "/network" => Some(Self::Network),
+            "/sensors" => Some(Self::Sensors(0)),
+            "/network" => Some(Self::Network(0)),
             "/speed" | "/speedtest" => Some(Self::Speed),
-            "/disks" => Some(Self::Disks),
+            "/disks" => Some(Self::Disks(0)),
+            "/du" => Some(Self::DirSizes),
             "/gpu" => Some(Self::Gpu),
+            "/checks" => Some(Self::Checks(0)),
             "/alerts_on" | "/alerts_off" | "/alerts_status" => Some(Self::Alerts),
+            "/thresholds" => Some(Self::Thresholds),
+            "/logs" => Some(Self::Logs),
+            "/actions" => Some(Self::RemoteCommands),
+            "/export" => Some(Self::Export),
+            "/hosts" => Some(Self::Hosts),
+            "/compare" => Some(Self::Compare),
+            "/set" => Some(Self::parse_set_threshold(text).unwrap_or(Self::SetThresholdUsage)),
+            "/timezone" => Some(Self::parse_timezone(text).unwrap_or(Self::TimezoneUsage)),
+            "/autorefresh" => {
+                Some(Self::parse_auto_refresh(text).unwrap_or(Self::AutoRefreshUsage))
+            }
             _ => None,
         }
     }
 
+    fn parse_set_threshold(text: &str) -> Option<Self> {
+        let mut args = text.split_whitespace().skip(1);
+        let kind = ResourceAlertKind::from_key(&args.next()?.to_lowercase())?;
+        let raw = args.next()?;
+        if args.next().is_some() {
+            return None;
+        }
+        if raw.eq_ignore_ascii_case("reset") {
+            return Some(Self::SetThreshold(kind, None));
+        }
+        let value = raw.replace(',', ".").parse::<f64>().ok()?;
+        if !kind.threshold_range().contains(&value) {
+            return None;
+        }
+        Some(Self::SetThreshold(kind, Some(value)))
+    }
+
+    fn parse_timezone(text: &str) -> Option<Self> {
+        let mut args = text.split_whitespace().skip(1);
+        let raw = args.next()?;
+        if args.next().is_some() {
+            return None;
+        }
+        if raw.eq_ignore_ascii_case("reset") {
+            return Some(Self::SetTimezone(None));
+        }
+        raw.parse::<Tz>().ok().map(|tz| Self::SetTimezone(Some(tz)))
+    }
+
+    fn parse_auto_refresh(text: &str) -> Option<Self> {
+        let mut args = text.split_whitespace().skip(1);
+        let raw = args.next()?;
+        if args.next().is_some() {
+            return None;
+        }
+        if raw.eq_ignore_ascii_case("off") || raw == "0" {
+            return Some(Self::SetAutoRefresh(None));
+        }
+        let mins = raw.parse::<u64>().ok()?;
+        (1..=AUTO_REFRESH_MAX_MINS)
+            .contains(&mins)
+            .then_some(Self::SetAutoRefresh(Some(mins)))
+    }
+
+    // Виды, после которых сообщение-дашборд показывает сводку и может обновляться само.
+    fn shows_dashboard(&self) -> bool {
+        matches!(
+            self,
+            Self::Refresh | Self::Dashboard | Self::SetAutoRefresh(_)
+        )
+    }
+
     fn from_callback(data: &str) -> Option<Self> {
         match data {
             "refresh" => Some(Self::Refresh),
             "dashboard" => Some(Self::Dashboard),
             "system" => Some(Self::System),
-            "sensors" => Some(Self::Sensors),
-            "network" => Some(Self::Network),
+            "sensors" => Some(Self::Sensors(0)),
+            "network" => Some(Self::Network(0)),
             "speed" => Some(Self::Speed),
-            "disks" => Some(Self::Disks),
+            "disks" => Some(Self::Disks(0)),
+            "du" => Some(Self::DirSizes),
             "gpu" => Some(Self::Gpu),
+            "checks" => Some(Self::Checks(0)),
             "alerts" => Some(Self::Alerts),
             "alerts_toggle" => Some(Self::ToggleAlerts),
             "alerts_checks_toggle" => Some(Self::ToggleChecksAlert),
@@ -92,9 +351,68 @@ impl Action {
             "alerts_ram_usage_toggle" => Some(Self::ToggleRamUsageAlert),
             "alerts_disk_usage_toggle" => Some(Self::ToggleDiskUsageAlert),
             "help" => Some(Self::Help),
+            "thresholds" => Some(Self::Thresholds),
+            "thr_reset" => Some(Self::ResetThresholds),
+            "logs" => Some(Self::Logs),
+            "actions" => Some(Self::RemoteCommands),
+            "export" => Some(Self::Export),
+            "hosts" => Some(Self::Hosts),
+            "compare" => Some(Self::Compare),
+            "host_local" => Some(Self::SelectHost(None)),
+            "snooze_clear" => Some(Self::ClearSnoozes),
+            _ => Self::parse_threshold_step(data)
+                .or_else(|| Self::parse_snooze(data))
+                .or_else(|| Self::parse_host(data))
+                .or_else(|| Self::parse_remote_action(data))
+                .or_else(|| Self::parse_page(data))
+                .or_else(|| data.strip_prefix("more_")?.parse().ok().map(Self::MorePage)),
+        }
+    }
+
+    fn parse_page(data: &str) -> Option<Self> {
+        let (view, page) = data.split_once("_p")?;
+        let page = page.parse().ok()?;
+        match view {
+            "sensors" => Some(Self::Sensors(page)),
+            "network" => Some(Self::Network(page)),
+            "disks" => Some(Self::Disks(page)),
+            "checks" => Some(Self::Checks(page)),
             _ => None,
         }
     }
+
+    fn parse_host(data: &str) -> Option<Self> {
+        data.strip_prefix("host_")?
+            .parse()
+            .ok()
+            .map(|idx| Self::SelectHost(Some(idx)))
+    }
+
+    fn parse_remote_action(data: &str) -> Option<Self> {
+        if let Some(name) = data.strip_prefix("act_run_") {
+            return Some(Self::RunRemoteCommand(name.to_string()));
+        }
+        data.strip_prefix("act_ask_")
+            .map(|name| Self::ConfirmRemoteCommand(name.to_string()))
+    }
+
+    fn parse_snooze(data: &str) -> Option<Self> {
+        let (key, hours) = data.strip_prefix("snooze_")?.rsplit_once('_')?;
+        let kind = ResourceAlertKind::from_key(key)?;
+        let hours = hours.parse().ok().filter(|h| SNOOZE_HOURS.contains(h))?;
+        Some(Self::Snooze(kind, hours))
+    }
+
+    fn parse_threshold_step(data: &str) -> Option<Self> {
+        let rest = data.strip_prefix("thr_")?;
+        let (key, step) = if let Some(key) = rest.strip_suffix("_up") {
+            (key, THRESHOLD_STEP)
+        } else {
+            (rest.strip_suffix("_down")?, -THRESHOLD_STEP)
+        };
+        let kind = ResourceAlertKind::from_key(key)?;
+        Some(Self::StepThreshold(kind, step))
+    }
 }
 
 struct RenderedView {
@@ -102,56 +420,200 @@ struct RenderedView {
     keyboard: InlineKeyboardMarkup,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run_bot(
     bot: Bot,
     cfg: TelegramConfig,
     shared_state: Arc<RwLock<State>>,
+    snapshot: SharedSnapshot,
+    logs: LogBuffer,
+    pairing: Option<Arc<Pairing>>,
+    hosts: Hosts,
+    speed_history: SpeedHistory,
+    units: Units,
+    health: BotHealth,
     mut shutdown: watch::Receiver<bool>,
 ) -> Result<(), TelegramError> {
     let runtime = TelegramRuntime {
         cfg: cfg.clone(),
         shared_state,
+        snapshot,
         allowed_chats: cfg.allowed_chat_ids.iter().copied().collect(),
         limiter: Arc::new(Mutex::new(RateLimiter::new(cfg.rate_limit_per_minute))),
         dashboard_messages: Arc::new(Mutex::new(HashMap::new())),
-        speed_history: Arc::new(Mutex::new(VecDeque::new())),
+        view_pages: Arc::new(Mutex::new(HashMap::new())),
+        speed_history,
+        logs,
+        pairing,
+        hosts,
+        selected_hosts: Arc::new(Mutex::new(HashMap::new())),
+        live_dashboards: Arc::new(Mutex::new(HashMap::new())),
+        units,
+        health,
     };
 
+    // Итог обработчика отражает все его запросы к API: ошибка любого из них прерывает обработку.
     let handler = dptree::entry()
-        .branch(Update::filter_message().endpoint(handle_message))
-        .branch(Update::filter_callback_query().endpoint(handle_callback));
+        .branch(Update::filter_message().endpoint(
+            |bot: Bot, msg: Message, runtime: TelegramRuntime| async move {
+                let result = handle_message(bot, msg, runtime.clone()).await;
+                runtime.health.observe(&result, now_unix());
+                result
+            },
+        ))
+        .branch(Update::filter_callback_query().endpoint(
+            |bot: Bot, q: CallbackQuery, runtime: TelegramRuntime| async move {
+                let result = handle_callback(bot, q, runtime.clone()).await;
+                runtime.health.observe(&result, now_unix());
+                result
+            },
+        ))
+        .branch(Update::filter_inline_query().endpoint(
+            |bot: Bot, q: InlineQuery, runtime: TelegramRuntime| async move {
+                let result = handle_inline_query(bot, q, runtime.clone()).await;
+                runtime.health.observe(&result, now_unix());
+                result
+            },
+        ));
 
+    let refresh_bot = bot.clone();
     let mut dispatcher = Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![runtime])
+        .dependencies(dptree::deps![runtime.clone()])
         .build();
 
     let mut dispatch_handle = tokio::spawn(async move {
         dispatcher.dispatch().await;
     });
 
-    tokio::select! {
-        _ = shutdown.changed() => {
-            dispatch_handle.abort();
-            let _ = (&mut dispatch_handle).await;
-            info!("остановка Telegram-бота");
-            Ok(())
-        }
-        result = &mut dispatch_handle => {
-            match result {
-                Ok(()) => Ok(()),
-                Err(join_err) if join_err.is_cancelled() => Ok(()),
-                Err(join_err) => {
-                    warn!(error = %join_err, "задача Telegram завершилась с ошибкой");
-                    Ok(())
+    let mut prune = tokio::time::interval(CHAT_STATE_PRUNE_EVERY);
+    let mut auto_refresh = tokio::time::interval(AUTO_REFRESH_CHECK_EVERY);
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => {
+                dispatch_handle.abort();
+                let _ = (&mut dispatch_handle).await;
+                info!("остановка Telegram-бота");
+                return Ok(());
+            }
+            result = &mut dispatch_handle => {
+                return match result {
+                    Ok(()) => Ok(()),
+                    Err(join_err) if join_err.is_cancelled() => Ok(()),
+                    Err(join_err) => {
+                        warn!(error = %join_err, "задача Telegram завершилась с ошибкой");
+                        Ok(())
+                    }
+                };
+            }
+            _ = prune.tick() => prune_chat_state(&runtime, now_unix()).await,
+            _ = auto_refresh.tick() => {
+                refresh_live_dashboards(&refresh_bot, &runtime, now_unix()).await;
+            }
+        }
+    }
+}
+
+async fn prune_chat_state(runtime: &TelegramRuntime, now: i64) {
+    runtime.limiter.lock().await.prune(now);
+    let mut dashboards = runtime.dashboard_messages.lock().await;
+    prune_dashboards(&mut dashboards, now);
+    runtime
+        .view_pages
+        .lock()
+        .await
+        .retain(|chat_id, _| dashboards.contains_key(chat_id));
+    runtime
+        .live_dashboards
+        .lock()
+        .await
+        .retain(|chat_id, _| dashboards.contains_key(chat_id));
+}
+
+async fn mark_live_dashboard(runtime: &TelegramRuntime, chat_id: i64, live: bool) {
+    let mut live_dashboards = runtime.live_dashboards.lock().await;
+    if live {
+        live_dashboards.insert(chat_id, now_unix());
+    } else {
+        live_dashboards.remove(&chat_id);
+    }
+}
+
+fn due_auto_refreshes(
+    live: &HashMap<i64, i64>,
+    intervals: &HashMap<i64, u64>,
+    now: i64,
+) -> Vec<i64> {
+    live.iter()
+        .filter(|(chat_id, rendered_at)| {
+            intervals
+                .get(chat_id)
+                .is_some_and(|mins| now - **rendered_at >= *mins as i64 * 60)
+        })
+        .map(|(chat_id, _)| *chat_id)
+        .collect()
+}
+
+// Автообновление только правит уже отправленный дашборд и никогда не шлёт новое сообщение:
+// если чат открыл другой вид или сообщение пропало, обновление ждёт следующего запроса из чата.
+async fn refresh_live_dashboards(bot: &Bot, runtime: &TelegramRuntime, now: i64) {
+    let due = {
+        let intervals = runtime
+            .shared_state
+            .read()
+            .await
+            .chat_auto_refresh_mins
+            .clone();
+        due_auto_refreshes(&*runtime.live_dashboards.lock().await, &intervals, now)
+    };
+    for chat_id in due {
+        let message_id = runtime
+            .dashboard_messages
+            .lock()
+            .await
+            .get(&chat_id)
+            .map(|msg| msg.id);
+        let Some(message_id) = message_id else {
+            runtime.live_dashboards.lock().await.remove(&chat_id);
+            continue;
+        };
+        let units = chat_units(runtime, chat_id).await;
+        let view = dashboard_view(runtime, chat_id, &units).await;
+        let view = fit_view(runtime, chat_id, view).await;
+        let result = bot
+            .edit_message_text(ChatId(chat_id), MessageId(message_id), view.text)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(view.keyboard)
+            .await;
+        runtime.health.observe(&result, now);
+        match result {
+            Ok(_) | Err(teloxide::RequestError::Api(teloxide::ApiError::MessageNotModified)) => {
+                runtime.live_dashboards.lock().await.insert(chat_id, now);
+                if let Some(msg) = runtime.dashboard_messages.lock().await.get_mut(&chat_id) {
+                    msg.used_unix = now;
                 }
             }
+            // Превышен лимит правок: попробуем на следующем такте.
+            Err(teloxide::RequestError::RetryAfter(_)) => {}
+            Err(err) => {
+                warn!(chat_id, error = %err, "не удалось обновить дашборд, автообновление приостановлено");
+                runtime.live_dashboards.lock().await.remove(&chat_id);
+            }
         }
     }
 }
 
+fn prune_dashboards(dashboards: &mut HashMap<i64, DashboardMessage>, now: i64) {
+    dashboards.retain(|_, msg| now.saturating_sub(msg.used_unix) < CHAT_STATE_TTL_SECS);
+}
+
 async fn handle_message(bot: Bot, msg: Message, runtime: TelegramRuntime) -> ResponseResult<()> {
     let chat_id = msg.chat.id.0;
-    if !should_handle_message(msg.chat.is_private(), chat_id, &runtime.allowed_chats) {
+    if !is_authorized(&runtime, msg.chat.is_private(), chat_id).await {
+        if msg.chat.is_private() {
+            if let Some(code) = msg.text().and_then(parse_pair_command) {
+                handle_pair_request(&bot, msg.chat.id, &runtime, code).await?;
+            }
+        }
         return Ok(());
     }
 
@@ -169,8 +631,13 @@ async fn handle_message(bot: Bot, msg: Message, runtime: TelegramRuntime) -> Res
         .and_then(Action::from_command)
         .unwrap_or(Action::Start);
 
+    if matches!(action, Action::Export) {
+        send_state_export(&bot, msg.chat.id, &runtime).await?;
+    }
+    let live = action.shows_dashboard();
     let response = render_action(action, chat_id, &runtime).await;
     upsert_dashboard_message(&bot, msg.chat.id, &runtime, response).await?;
+    mark_live_dashboard(&runtime, chat_id, live).await;
     Ok(())
 }
 
@@ -188,7 +655,7 @@ async fn handle_callback(
     };
 
     let chat_id = message.chat.id.0;
-    if !should_handle_message(message.chat.is_private(), chat_id, &runtime.allowed_chats) {
+    if !is_authorized(&runtime, message.chat.is_private(), chat_id).await {
         bot.answer_callback_query(q.id).await?;
         return Ok(());
     }
@@ -200,21 +667,148 @@ async fn handle_callback(
         return Ok(());
     }
 
+    // Кнопки «💤» стоят под уведомлением: его текст остаётся, ответ приходит всплывающей подсказкой.
+    if let Some(Action::Snooze(kind, hours)) = Action::from_callback(data) {
+        let until = snooze_resource_alert(&runtime, chat_id, kind, hours).await;
+        let units = chat_units(&runtime, chat_id).await;
+        bot.answer_callback_query(q.id)
+            .text(format!(
+                "💤 {}: уведомления отложены до {}",
+                alert_kind_title(kind),
+                units.datetime(until)
+            ))
+            .await?;
+        return Ok(());
+    }
+
     {
         let mut map = runtime.dashboard_messages.lock().await;
-        map.insert(chat_id, message.id.0);
+        map.insert(
+            chat_id,
+            DashboardMessage {
+                id: message.id.0,
+                used_unix: now_unix(),
+            },
+        );
     }
 
     if let Some(action) = Action::from_callback(data) {
+        if matches!(action, Action::Export) {
+            send_state_export(&bot, message.chat.id, &runtime).await?;
+        }
+        let live = action.shows_dashboard();
+        let run = match &action {
+            Action::RunRemoteCommand(name) => {
+                allowed_remote_action(&runtime.cfg, chat_id, name).cloned()
+            }
+            _ => None,
+        };
         let response = render_action(action, chat_id, &runtime).await;
         upsert_dashboard_message(&bot, message.chat.id, &runtime, response).await?;
+        mark_live_dashboard(&runtime, chat_id, live).await;
+        if let Some(remote) = run {
+            spawn_remote_action(bot.clone(), message.chat.id, runtime.clone(), remote);
+        }
     }
 
     bot.answer_callback_query(q.id).await?;
     Ok(())
 }
 
+async fn handle_inline_query(
+    bot: Bot,
+    q: InlineQuery,
+    runtime: TelegramRuntime,
+) -> ResponseResult<()> {
+    // У inline-запроса нет чата: доступ проверяется как для личного чата пользователя.
+    let user_id = q.from.id.0 as i64;
+    let results = if is_authorized(&runtime, true, user_id).await
+        && consume_rate_limit(&runtime, user_id).await
+    {
+        let units = chat_units(&runtime, user_id).await;
+        inline_articles(&runtime.snapshot.load(), &q.query, &units)
+            .into_iter()
+            .map(|article| {
+                let content =
+                    InputMessageContentText::new(article.text).parse_mode(ParseMode::Html);
+                InlineQueryResult::Article(
+                    InlineQueryResultArticle::new(
+                        article.id,
+                        article.title,
+                        InputMessageContent::Text(content),
+                    )
+                    .description(article.description),
+                )
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    bot.answer_inline_query(q.id, results)
+        .cache_time(INLINE_CACHE_SECS)
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
+
+async fn is_authorized(runtime: &TelegramRuntime, is_private: bool, chat_id: i64) -> bool {
+    if should_handle_message(is_private, chat_id, &runtime.allowed_chats) {
+        return true;
+    }
+    is_private
+        && runtime
+            .shared_state
+            .read()
+            .await
+            .paired_chat_ids
+            .contains(&chat_id)
+}
+
+fn parse_pair_command(text: &str) -> Option<&str> {
+    let mut parts = text.split_whitespace();
+    let command = parts.next()?.split('@').next()?;
+    if !command.eq_ignore_ascii_case("/pair") {
+        return None;
+    }
+    parts.next()
+}
+
+async fn handle_pair_request(
+    bot: &Bot,
+    chat_id: ChatId,
+    runtime: &TelegramRuntime,
+    code: &str,
+) -> ResponseResult<()> {
+    let Some(pairing) = runtime.pairing.as_ref() else {
+        return Ok(());
+    };
+    if !consume_rate_limit(runtime, chat_id.0).await {
+        return Ok(());
+    }
+    if !pairing.try_consume(code) {
+        warn!(chat_id = chat_id.0, "неверный код привязки Telegram-чата");
+        bot.send_message(chat_id, "Неверный код привязки.").await?;
+        return Ok(());
+    }
+
+    let chats = {
+        let mut state = runtime.shared_state.write().await;
+        state.paired_chat_ids.insert(chat_id.0);
+        state.paired_chat_ids.clone()
+    };
+    if let Err(err) = pairing.save(&chats) {
+        warn!(error = %err, "не удалось сохранить привязанные Telegram-чаты");
+    }
+    info!(chat_id = chat_id.0, "Telegram-чат привязан по коду");
+    let view = RenderedView {
+        text: "✅ Чат привязан. Теперь доступны команды и уведомления.".to_string(),
+        keyboard: main_menu(),
+    };
+    upsert_dashboard_message(bot, chat_id, runtime, view).await
+}
+
 async fn render_action(action: Action, chat_id: i64, runtime: &TelegramRuntime) -> RenderedView {
+    let units = chat_units(runtime, chat_id).await;
     match action {
         Action::Start => RenderedView {
             text: "<b>monitord</b> запущен. Нажмите кнопку ниже для сводки.".to_string(),
@@ -224,92 +818,116 @@ async fn render_action(action: Action, chat_id: i64, runtime: &TelegramRuntime)
             text: help_text(),
             keyboard: main_menu(),
         },
-        Action::Refresh | Action::Dashboard => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_status(&state, &runtime.cfg);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
-                keyboard: main_menu(),
-            }
-        }
+        Action::Refresh | Action::Dashboard => dashboard_view(runtime, chat_id, &units).await,
         Action::System => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_system(&state);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
+            host_view(runtime, chat_id, |state, _| RenderedView {
+                text: format_system(state, &units),
                 keyboard: main_menu(),
-            }
+            })
+            .await
         }
-        Action::Sensors => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_sensors(&state);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
-                keyboard: main_menu(),
-            }
+        Action::Sensors(page) => {
+            host_view(runtime, chat_id, |state, _| {
+                let (text, pager) = format_sensors(state, page, &units);
+                RenderedView {
+                    text,
+                    keyboard: paged_menu("sensors", pager),
+                }
+            })
+            .await
         }
-        Action::Network => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_network(&state);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
-                keyboard: main_menu(),
-            }
+        Action::Network(page) => {
+            host_view(runtime, chat_id, |state, _| {
+                let (text, pager) = format_network(state, page, &units);
+                RenderedView {
+                    text,
+                    keyboard: paged_menu("network", pager),
+                }
+            })
+            .await
         }
         Action::Speed => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let snapshot = state.clone();
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            let history = {
-                let history = runtime.speed_history.lock().await;
-                history.clone()
-            };
-            RenderedView {
-                text: format_speedtest(&snapshot, &history),
+            host_view(runtime, chat_id, |state, history| RenderedView {
+                text: format_speedtest(state, history, &units),
                 keyboard: main_menu(),
-            }
+            })
+            .await
         }
-        Action::Disks => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_disks(&state);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
+        Action::Disks(page) => {
+            host_view(runtime, chat_id, |state, _| {
+                let (text, pager) = format_disks(state, page, &units);
+                let mut keyboard = paged_menu("disks", pager);
+                if !state.dir_sizes.is_empty() {
+                    keyboard
+                        .inline_keyboard
+                        .insert(0, vec![InlineKeyboardButton::callback("📂 Каталоги", "du")]);
+                }
+                RenderedView { text, keyboard }
+            })
+            .await
+        }
+        Action::DirSizes => {
+            host_view(runtime, chat_id, |state, _| RenderedView {
+                text: format_dir_sizes(state, &units),
                 keyboard: main_menu(),
-            }
+            })
+            .await
+        }
+        Action::Checks(page) => {
+            let sparklines = check_sparklines(runtime, chat_id).await;
+            host_view(runtime, chat_id, |state, _| {
+                let (text, pager) = format_checks(state, page, &sparklines, &units);
+                RenderedView {
+                    text,
+                    keyboard: paged_menu("checks", pager),
+                }
+            })
+            .await
         }
         Action::Gpu => {
-            let state = runtime.shared_state.read().await;
-            let sample = make_speed_sample(&state);
-            let text = format_gpu_details(&state);
-            drop(state);
-            push_speed_sample(runtime, sample).await;
-            RenderedView {
-                text,
+            host_view(runtime, chat_id, |state, _| RenderedView {
+                text: format_gpu_details(state, &units),
                 keyboard: main_menu(),
+            })
+            .await
+        }
+        Action::Hosts => hosts_view(runtime, chat_id).await,
+        Action::Compare => compare_view(runtime, chat_id).await,
+        Action::MorePage(page) => {
+            let pages = runtime.view_pages.lock().await.get(&chat_id).cloned();
+            match pages {
+                Some(pages) => pages.view(page),
+                // Части вида уже вычищены — показываем сводку заново.
+                None => Box::pin(render_action(Action::Dashboard, chat_id, runtime)).await,
+            }
+        }
+        Action::SelectHost(idx) => {
+            let name = match idx {
+                Some(idx) => match runtime.hosts.read().await.get(idx) {
+                    Some(host) => Some(host.name.clone()),
+                    None => return hosts_view(runtime, chat_id).await,
+                },
+                None => None,
+            };
+            {
+                let mut selected = runtime.selected_hosts.lock().await;
+                match name {
+                    Some(name) => selected.insert(chat_id, name),
+                    None => selected.remove(&chat_id),
+                };
             }
+            Box::pin(render_action(Action::Dashboard, chat_id, runtime)).await
         }
         Action::Alerts => {
             let state = runtime.shared_state.read().await;
             let enabled =
                 state.alerts_enabled_for_chat(chat_id, runtime.cfg.alerts.enabled_by_default);
-            let text = format_alerts_page(&state, chat_id, runtime.cfg.alerts.enabled_by_default);
+            let text = format_alerts_page(
+                &state,
+                chat_id,
+                runtime.cfg.alerts.enabled_by_default,
+                &units,
+            );
             let keyboard = alerts_menu(&state, chat_id, enabled);
             RenderedView { text, keyboard }
         }
@@ -326,7 +944,12 @@ async fn render_action(action: Action, chat_id: i64, runtime: &TelegramRuntime)
             state.set_resource_alert_enabled_for_chat(chat_id, ResourceAlertKind::GpuLoad, next);
             state.set_resource_alert_enabled_for_chat(chat_id, ResourceAlertKind::RamUsage, next);
             state.set_resource_alert_enabled_for_chat(chat_id, ResourceAlertKind::DiskUsage, next);
-            let text = format_alerts_page(&state, chat_id, runtime.cfg.alerts.enabled_by_default);
+            let text = format_alerts_page(
+                &state,
+                chat_id,
+                runtime.cfg.alerts.enabled_by_default,
+                &units,
+            );
             let keyboard = alerts_menu(&state, chat_id, next);
             RenderedView { text, keyboard }
         }
@@ -336,7 +959,12 @@ async fn render_action(action: Action, chat_id: i64, runtime: &TelegramRuntime)
             state.set_check_alerts_enabled_for_chat(chat_id, !current);
             let enabled =
                 state.alerts_enabled_for_chat(chat_id, runtime.cfg.alerts.enabled_by_default);
-            let text = format_alerts_page(&state, chat_id, runtime.cfg.alerts.enabled_by_default);
+            let text = format_alerts_page(
+                &state,
+                chat_id,
+                runtime.cfg.alerts.enabled_by_default,
+                &units,
+            );
             let keyboard = alerts_menu(&state, chat_id, enabled);
             RenderedView { text, keyboard }
         }
@@ -394,36 +1022,432 @@ async fn render_action(action: Action, chat_id: i64, runtime: &TelegramRuntime)
             )
             .await
         }
+        Action::Logs => RenderedView {
+            text: format_logs(&runtime.logs.tail(LOGS_TAIL_LINES), &units),
+            keyboard: InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::callback("Обновить", "logs"),
+                InlineKeyboardButton::callback("⬅ Назад", "dashboard"),
+            ]]),
+        },
+        Action::Export => RenderedView {
+            text: "📦 Снимок состояния отправлен файлом.".to_string(),
+            keyboard: main_menu(),
+        },
+        Action::RemoteCommands => remote_actions_view(&runtime.cfg, chat_id, None),
+        Action::ConfirmRemoteCommand(name) => {
+            let Some(action) = allowed_remote_action(&runtime.cfg, chat_id, &name) else {
+                return remote_actions_view(&runtime.cfg, chat_id, Some("Действие недоступно."));
+            };
+            let command_line = std::iter::once(action.command.as_str())
+                .chain(action.args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ");
+            RenderedView {
+                text: format!(
+                    "⚙ <b>Подтверждение</b>\n\nВыполнить «{}»?\n<code>{}</code>",
+                    escape_html(&action.name),
+                    escape_html(&command_line)
+                ),
+                keyboard: InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback(
+                        "✅ Выполнить",
+                        format!("act_run_{}", action.name),
+                    ),
+                    InlineKeyboardButton::callback("❌ Отмена", "actions"),
+                ]]),
+            }
+        }
+        // Сама команда запускается отдельной задачей (spawn_remote_action), здесь — только статус.
+        Action::RunRemoteCommand(name) => {
+            let Some(action) = allowed_remote_action(&runtime.cfg, chat_id, &name) else {
+                return remote_actions_view(&runtime.cfg, chat_id, Some("Действие недоступно."));
+            };
+            let notice = format!("⏳ Выполняется «{}»…", escape_html(&action.name));
+            remote_actions_view(&runtime.cfg, chat_id, Some(&notice))
+        }
+        Action::Thresholds => {
+            let state = runtime.shared_state.read().await;
+            thresholds_view(&state, chat_id, &runtime.cfg, None)
+        }
+        Action::StepThreshold(kind, step) => {
+            let mut state = runtime.shared_state.write().await;
+            let current = state.resource_threshold_for_chat(chat_id, kind, &runtime.cfg.alerts);
+            let range = kind.threshold_range();
+            let next = (current + step).clamp(*range.start(), *range.end());
+            state.set_resource_threshold_for_chat(chat_id, kind, Some(next));
+            thresholds_view(&state, chat_id, &runtime.cfg, None)
+        }
+        Action::SetThreshold(kind, value) => {
+            let mut state = runtime.shared_state.write().await;
+            state.set_resource_threshold_for_chat(chat_id, kind, value);
+            thresholds_view(&state, chat_id, &runtime.cfg, None)
+        }
+        Action::Snooze(kind, hours) => {
+            snooze_resource_alert(runtime, chat_id, kind, hours).await;
+            Box::pin(render_action(Action::Alerts, chat_id, runtime)).await
+        }
+        Action::ClearSnoozes => {
+            runtime
+                .shared_state
+                .write()
+                .await
+                .clear_resource_snoozes(chat_id);
+            Box::pin(render_action(Action::Alerts, chat_id, runtime)).await
+        }
+        Action::ResetThresholds => {
+            let mut state = runtime.shared_state.write().await;
+            state.chat_resource_thresholds.remove(&chat_id);
+            thresholds_view(&state, chat_id, &runtime.cfg, None)
+        }
+        Action::SetTimezone(tz) => {
+            let mut state = runtime.shared_state.write().await;
+            match tz {
+                Some(tz) => state.chat_timezones.insert(chat_id, tz),
+                None => state.chat_timezones.remove(&chat_id),
+            };
+            timezone_view(runtime.units.with_timezone(tz), None)
+        }
+        Action::SetAutoRefresh(mins) => {
+            {
+                let mut state = runtime.shared_state.write().await;
+                match mins {
+                    Some(mins) => state.chat_auto_refresh_mins.insert(chat_id, mins),
+                    None => state.chat_auto_refresh_mins.remove(&chat_id),
+                };
+            }
+            dashboard_view(runtime, chat_id, &units).await
+        }
+        Action::AutoRefreshUsage => {
+            let current = runtime
+                .shared_state
+                .read()
+                .await
+                .chat_auto_refresh_mins
+                .get(&chat_id)
+                .map(|mins| format!("каждые {mins} мин"))
+                .unwrap_or_else(|| "выключено".to_string());
+            RenderedView {
+                text: format!(
+                    "🔄 <b>Автообновление дашборда</b>\n\nСейчас: {current}\n\nФормат: <code>/autorefresh 5</code> (минуты, 1–{AUTO_REFRESH_MAX_MINS}) или <code>/autorefresh off</code>"
+                ),
+                keyboard: main_menu(),
+            }
+        }
+        Action::TimezoneUsage => timezone_view(
+            units,
+            Some("Формат: <code>/timezone Europe/Moscow</code> или <code>/timezone reset</code>"),
+        ),
+        Action::SetThresholdUsage => {
+            let state = runtime.shared_state.read().await;
+            thresholds_view(
+                &state,
+                chat_id,
+                &runtime.cfg,
+                Some("Неверная команда. Формат: <code>/set cpu_temp 90</code> или <code>/set cpu_temp reset</code>"),
+            )
+        }
     }
 }
 
-async fn toggle_resource_alert(
-    runtime: &TelegramRuntime,
-    chat_id: i64,
-    kind: ResourceAlertKind,
-    default_enabled: bool,
-) -> RenderedView {
-    let mut state = runtime.shared_state.write().await;
-    let current = state.resource_alert_enabled_for_chat(chat_id, kind);
-    state.set_resource_alert_enabled_for_chat(chat_id, kind, !current);
-    let enabled = state.alerts_enabled_for_chat(chat_id, default_enabled);
-    let text = format_alerts_page(&state, chat_id, default_enabled);
-    let keyboard = alerts_menu(&state, chat_id, enabled);
-    RenderedView { text, keyboard }
+async fn dashboard_view(runtime: &TelegramRuntime, chat_id: i64, units: &Units) -> RenderedView {
+    let has_hosts = !runtime.hosts.read().await.is_empty();
+    let auto_refresh = runtime
+        .shared_state
+        .read()
+        .await
+        .chat_auto_refresh_mins
+        .get(&chat_id)
+        .copied();
+    let mut view = host_view(runtime, chat_id, |state, _| RenderedView {
+        text: format_status(state, &runtime.cfg, units),
+        keyboard: dashboard_menu(has_hosts),
+    })
+    .await;
+    if let Some(mins) = auto_refresh {
+        view.text
+            .push_str(&format!("\n\n<i>🔄 Автообновление каждые {mins} мин</i>"));
+    }
+    view
 }
 
-fn alert_kind_title(kind: ResourceAlertKind) -> &'static str {
-    match kind {
-        ResourceAlertKind::CpuTemp => "CPU температура",
-        ResourceAlertKind::GpuTemp => "GPU температура",
-        ResourceAlertKind::CpuLoad => "CPU нагрузка",
-        ResourceAlertKind::GpuLoad => "GPU нагрузка",
-        ResourceAlertKind::RamUsage => "RAM использование",
+async fn chat_units(runtime: &TelegramRuntime, chat_id: i64) -> Units {
+    let tz = runtime
+        .shared_state
+        .read()
+        .await
+        .chat_timezones
+        .get(&chat_id)
+        .copied();
+    runtime.units.with_timezone(tz)
+}
+
+fn timezone_view(units: Units, notice: Option<&str>) -> RenderedView {
+    let mut text = format!(
+        "🕒 <b>Часовой пояс</b>\n\nСейчас: {}",
+        units.datetime(now_unix())
+    );
+    if let Some(notice) = notice {
+        text.push_str(&format!("\n\n{notice}"));
+    }
+    RenderedView {
+        text,
+        keyboard: main_menu(),
+    }
+}
+
+fn thresholds_view(
+    state: &State,
+    chat_id: i64,
+    cfg: &TelegramConfig,
+    notice: Option<&str>,
+) -> RenderedView {
+    RenderedView {
+        text: format_thresholds_page(state, chat_id, cfg, notice),
+        keyboard: thresholds_menu(state, chat_id, cfg),
+    }
+}
+
+fn format_threshold_value(kind: ResourceAlertKind, value: f64) -> String {
+    if kind.is_temperature() {
+        format!("{value:.0}°C")
+    } else {
+        format!("{value:.0}%")
+    }
+}
+
+// Кнопки ссылаются на действие по имени: после смены конфигурации старая кнопка не запустит другую команду.
+fn allowed_remote_action<'a>(
+    cfg: &'a TelegramConfig,
+    chat_id: i64,
+    name: &str,
+) -> Option<&'a crate::config::RemoteActionConfig> {
+    cfg.actions
+        .iter()
+        .find(|action| action.name == name)
+        .filter(|action| action.allowed_for_chat(chat_id))
+}
+
+// Команда может работать до timeout_secs: обработчик кнопки не ждёт её, результат дописывается в сообщение.
+fn spawn_remote_action(
+    bot: Bot,
+    chat: ChatId,
+    runtime: TelegramRuntime,
+    action: crate::config::RemoteActionConfig,
+) {
+    tokio::spawn(async move {
+        let chat_id = chat.0;
+        info!(chat_id, action = %action.name, "выполнение удалённого действия");
+        let outcome = run_action(&action).await;
+        if outcome.success {
+            info!(chat_id, action = %action.name, "удалённое действие выполнено");
+        } else {
+            warn!(
+                chat_id,
+                action = %action.name,
+                exit_code = ?outcome.exit_code,
+                "удалённое действие завершилось с ошибкой"
+            );
+        }
+        let text = format_action_outcome(&action.name, &outcome);
+        let view = remote_actions_view(&runtime.cfg, chat_id, Some(&text));
+        let result = upsert_dashboard_message(&bot, chat, &runtime, view).await;
+        runtime.health.observe(&result, now_unix());
+        if let Err(err) = result {
+            warn!(chat_id, error = %err, "не удалось отправить результат удалённого действия");
+        }
+    });
+}
+
+fn format_action_outcome(name: &str, outcome: &crate::actions::ActionOutcome) -> String {
+    let status = format!(
+        "{} «{}»: {}{}",
+        if outcome.success { "✅" } else { "❌" },
+        escape_html(name),
+        if outcome.success {
+            "выполнено"
+        } else {
+            "ошибка"
+        },
+        outcome
+            .exit_code
+            .map(|c| format!(" (код {c})"))
+            .unwrap_or_default()
+    );
+    if outcome.output.is_empty() {
+        status
+    } else {
+        format!("{status}\n<pre>{}</pre>", escape_html(&outcome.output))
+    }
+}
+
+fn remote_actions_view(cfg: &TelegramConfig, chat_id: i64, notice: Option<&str>) -> RenderedView {
+    let mut rows = cfg
+        .actions
+        .iter()
+        .filter(|action| action.allowed_for_chat(chat_id))
+        .map(|action| {
+            vec![InlineKeyboardButton::callback(
+                format!("⚙ {}", action.name),
+                format!("act_ask_{}", action.name),
+            )]
+        })
+        .collect::<Vec<_>>();
+    let mut text = Vec::new();
+    if let Some(notice) = notice {
+        text.push(notice.to_string());
+        text.push(String::new());
+    }
+    text.push("⚙ <b>Действия</b>".to_string());
+    if rows.is_empty() {
+        text.push("\nНет доступных действий.".to_string());
+    } else {
+        text.push("\nВыберите действие. Перед выполнением потребуется подтверждение.".to_string());
+    }
+    rows.push(vec![InlineKeyboardButton::callback("⬅ Назад", "dashboard")]);
+    RenderedView {
+        text: text.join("\n"),
+        keyboard: InlineKeyboardMarkup::new(rows),
+    }
+}
+
+fn format_logs(entries: &[LogEntry], units: &Units) -> String {
+    if entries.is_empty() {
+        return "📜 <b>Журнал</b>\n\nЗаписей нет.".to_string();
+    }
+    let mut lines = Vec::new();
+    let mut size = 0_usize;
+    for e in entries.iter().rev() {
+        let line = format!(
+            "{} {} {}",
+            units.datetime(e.ts_unix),
+            e.level,
+            escape_html(&e.message)
+        );
+        size += line.len() + 1;
+        if size > MESSAGE_TEXT_LIMIT {
+            break;
+        }
+        lines.push(line);
+    }
+    lines.reverse();
+    format!("📜 <b>Журнал</b>\n\n<pre>{}</pre>", lines.join("\n"))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn format_thresholds_page(
+    state: &State,
+    chat_id: i64,
+    cfg: &TelegramConfig,
+    notice: Option<&str>,
+) -> String {
+    let mut lines = Vec::new();
+    if let Some(notice) = notice {
+        lines.push(notice.to_string());
+        lines.push(String::new());
+    }
+    lines.push("<b>Пороги уведомлений</b>".to_string());
+    for kind in ResourceAlertKind::ALL {
+        let value = state.resource_threshold_for_chat(chat_id, kind, &cfg.alerts);
+        let global = kind.global_threshold(&cfg.alerts);
+        let suffix = if value == global {
+            String::new()
+        } else {
+            format!(" (глобально {})", format_threshold_value(kind, global))
+        };
+        lines.push(format!(
+            "• {} [<code>{}</code>]: {}{}",
+            alert_kind_title(kind),
+            kind.key(),
+            format_threshold_value(kind, value),
+            suffix
+        ));
+    }
+    lines.push(String::new());
+    lines.push("Изменить: <code>/set &lt;тип&gt; &lt;значение|reset&gt;</code>".to_string());
+    lines.join("\n")
+}
+
+fn thresholds_menu(state: &State, chat_id: i64, cfg: &TelegramConfig) -> InlineKeyboardMarkup {
+    let mut rows = ResourceAlertKind::ALL
+        .into_iter()
+        .map(|kind| {
+            let value = state.resource_threshold_for_chat(chat_id, kind, &cfg.alerts);
+            vec![
+                InlineKeyboardButton::callback(
+                    format!("−{THRESHOLD_STEP:.0}"),
+                    format!("thr_{}_down", kind.key()),
+                ),
+                InlineKeyboardButton::callback(
+                    format!(
+                        "{}: {}",
+                        alert_kind_title(kind),
+                        format_threshold_value(kind, value)
+                    ),
+                    "thresholds",
+                ),
+                InlineKeyboardButton::callback(
+                    format!("+{THRESHOLD_STEP:.0}"),
+                    format!("thr_{}_up", kind.key()),
+                ),
+            ]
+        })
+        .collect::<Vec<_>>();
+    rows.push(vec![
+        InlineKeyboardButton::callback("↺ Сбросить", "thr_reset"),
+        InlineKeyboardButton::callback("⬅ Назад", "alerts"),
+    ]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+async fn toggle_resource_alert(
+    runtime: &TelegramRuntime,
+    chat_id: i64,
+    kind: ResourceAlertKind,
+    default_enabled: bool,
+) -> RenderedView {
+    let units = chat_units(runtime, chat_id).await;
+    let mut state = runtime.shared_state.write().await;
+    let current = state.resource_alert_enabled_for_chat(chat_id, kind);
+    state.set_resource_alert_enabled_for_chat(chat_id, kind, !current);
+    let enabled = state.alerts_enabled_for_chat(chat_id, default_enabled);
+    let text = format_alerts_page(&state, chat_id, default_enabled, &units);
+    let keyboard = alerts_menu(&state, chat_id, enabled);
+    RenderedView { text, keyboard }
+}
+
+async fn snooze_resource_alert(
+    runtime: &TelegramRuntime,
+    chat_id: i64,
+    kind: ResourceAlertKind,
+    hours: u64,
+) -> i64 {
+    let now = now_unix();
+    let until = now + hours as i64 * 3600;
+    runtime
+        .shared_state
+        .write()
+        .await
+        .snooze_resource_alert(chat_id, kind, until, now);
+    until
+}
+
+fn alert_kind_title(kind: ResourceAlertKind) -> &'static str {
+    match kind {
+        ResourceAlertKind::CpuTemp => "CPU температура",
+        ResourceAlertKind::GpuTemp => "GPU температура",
+        ResourceAlertKind::CpuLoad => "CPU нагрузка",
+        ResourceAlertKind::GpuLoad => "GPU нагрузка",
+        ResourceAlertKind::RamUsage => "RAM использование",
         ResourceAlertKind::DiskUsage => "Диск заполнение",
     }
 }
 
-fn format_alerts_page(state: &State, chat_id: i64, default_enabled: bool) -> String {
+fn format_alerts_page(state: &State, chat_id: i64, default_enabled: bool, units: &Units) -> String {
     let global = state.alerts_enabled_for_chat(chat_id, default_enabled);
     let mut lines = vec!["<b>Настройки уведомлений</b>".to_string()];
     lines.push(format!(
@@ -458,6 +1482,19 @@ fn format_alerts_page(state: &State, chat_id: i64, default_enabled: bool) -> Str
         lines.push(format!("{} {}", mark, alert_kind_title(kind)));
     }
 
+    let snoozes = state.resource_snoozes_for_chat(chat_id, now_unix());
+    if !snoozes.is_empty() {
+        lines.push(String::new());
+        lines.push("Отложены:".to_string());
+        for (kind, until) in snoozes {
+            lines.push(format!(
+                "💤 {} до {}",
+                alert_kind_title(kind),
+                units.datetime(until)
+            ));
+        }
+    }
+
     lines.join("\n")
 }
 
@@ -474,7 +1511,7 @@ fn alerts_menu(state: &State, chat_id: i64, alerts_enabled: bool) -> InlineKeybo
         InlineKeyboardButton::callback(format!("{} {}", icon, alert_kind_title(kind)), data)
     };
 
-    InlineKeyboardMarkup::new(vec![
+    let mut rows = vec![
         vec![InlineKeyboardButton::callback(
             button_title,
             "alerts_toggle",
@@ -502,8 +1539,276 @@ fn alerts_menu(state: &State, chat_id: i64, alerts_enabled: bool) -> InlineKeybo
             row_button(ResourceAlertKind::RamUsage, "alerts_ram_usage_toggle"),
             row_button(ResourceAlertKind::DiskUsage, "alerts_disk_usage_toggle"),
         ],
-        vec![InlineKeyboardButton::callback("⬅ Назад", "dashboard")],
-    ])
+    ];
+    if !state
+        .resource_snoozes_for_chat(chat_id, now_unix())
+        .is_empty()
+    {
+        rows.push(vec![InlineKeyboardButton::callback(
+            "⏰ Снять отсрочки",
+            "snooze_clear",
+        )]);
+    }
+    rows.push(vec![InlineKeyboardButton::callback(
+        "🎚 Пороги",
+        "thresholds",
+    )]);
+    rows.push(vec![InlineKeyboardButton::callback("⬅ Назад", "dashboard")]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pager {
+    page: usize,
+    pages: usize,
+}
+
+impl Pager {
+    fn new(total: usize, per_page: usize, page: usize) -> Self {
+        let pages = total.div_ceil(per_page).max(1);
+        Self {
+            page: page.min(pages - 1),
+            pages,
+        }
+    }
+
+    fn slice<'a, T>(&self, items: &'a [T], per_page: usize) -> &'a [T] {
+        let start = (self.page * per_page).min(items.len());
+        let end = (start + per_page).min(items.len());
+        &items[start..end]
+    }
+
+    fn footer(&self) -> String {
+        if self.pages > 1 {
+            format!("\nСтраница {}/{}", self.page + 1, self.pages)
+        } else {
+            String::new()
+        }
+    }
+}
+
+fn paged_menu(view: &str, pager: Pager) -> InlineKeyboardMarkup {
+    let mut rows = Vec::new();
+    if pager.pages > 1 {
+        let mut nav = Vec::new();
+        if pager.page > 0 {
+            nav.push(InlineKeyboardButton::callback(
+                "◀",
+                format!("{view}_p{}", pager.page - 1),
+            ));
+        }
+        nav.push(InlineKeyboardButton::callback(
+            format!("{}/{}", pager.page + 1, pager.pages),
+            format!("{view}_p{}", pager.page),
+        ));
+        if pager.page + 1 < pager.pages {
+            nav.push(InlineKeyboardButton::callback(
+                "▶",
+                format!("{view}_p{}", pager.page + 1),
+            ));
+        }
+        rows.push(nav);
+    }
+    rows.extend(main_menu().inline_keyboard);
+    InlineKeyboardMarkup::new(rows)
+}
+
+// Выбранный в чате хост агрегатора подменяет локальное состояние во всех обзорных видах.
+async fn host_view(
+    runtime: &TelegramRuntime,
+    chat_id: i64,
+    render: impl FnOnce(&Snapshot, &VecDeque<SpeedSample>) -> RenderedView,
+) -> RenderedView {
+    let selected = runtime.selected_hosts.lock().await.get(&chat_id).cloned();
+    if let Some(name) = selected {
+        let host = runtime
+            .hosts
+            .read()
+            .await
+            .iter()
+            .find(|host| host.name == name)
+            .cloned();
+        match host {
+            Some(HostState {
+                state: Some(remote),
+                up,
+                error,
+                last_success_unix,
+                ..
+            }) => {
+                let mut view = render(&remote, &VecDeque::new());
+                let chat_units = chat_units(runtime, chat_id).await;
+                let mut banner = format!("🖥 Хост: <b>{}</b>", escape_html(&name));
+                if !up {
+                    banner.push_str(&format!(
+                        "\n⚠ Нет связи: {}\nДанные на {}",
+                        escape_html(error.as_deref().unwrap_or("н/д")),
+                        last_success_unix
+                            .map(|ts| chat_units.datetime(ts))
+                            .unwrap_or_default()
+                    ));
+                }
+                view.text = format!("{banner}\n\n{}", view.text);
+                return view;
+            }
+            Some(host) => {
+                return RenderedView {
+                    text: format!(
+                        "🖥 Хост: <b>{}</b>\n\nДанных пока нет: {}",
+                        escape_html(&name),
+                        escape_html(host.error.as_deref().unwrap_or("ожидание первого опроса"))
+                    ),
+                    keyboard: hosts_menu(&runtime.hosts.read().await, Some(&name)),
+                };
+            }
+            None => {
+                runtime.selected_hosts.lock().await.remove(&chat_id);
+            }
+        }
+    }
+
+    let snapshot = runtime.snapshot.load();
+    let history = runtime.speed_history.lock().await;
+    render(&snapshot, &history)
+}
+
+async fn hosts_view(runtime: &TelegramRuntime, chat_id: i64) -> RenderedView {
+    let selected = runtime.selected_hosts.lock().await.get(&chat_id).cloned();
+    let hosts = runtime.hosts.read().await;
+    if hosts.is_empty() {
+        return RenderedView {
+            text: "Агрегатор не настроен: список aggregator.hosts пуст.".to_string(),
+            keyboard: main_menu(),
+        };
+    }
+    let mut lines = vec!["<b>Хосты</b>".to_string()];
+    for host in hosts.iter() {
+        let status = match (&host.state, host.up) {
+            (Some(state), true) => format!(
+                "CPU {:.0}%, RAM {:.0}%",
+                state.cpu_usage_percent,
+                percent(
+                    state.memory_used_bytes as f64,
+                    state.memory_total_bytes as f64
+                )
+            ),
+            _ => escape_html(host.error.as_deref().unwrap_or("нет данных")),
+        };
+        lines.push(format!(
+            "{} <b>{}</b>: {}",
+            if host.up { "🟢" } else { "🔴" },
+            escape_html(&host.name),
+            status
+        ));
+    }
+    RenderedView {
+        text: lines.join("\n"),
+        keyboard: hosts_menu(&hosts, selected.as_deref()),
+    }
+}
+
+async fn compare_view(runtime: &TelegramRuntime, chat_id: i64) -> RenderedView {
+    let selected = runtime.selected_hosts.lock().await.get(&chat_id).cloned();
+    let hosts = runtime.hosts.read().await;
+    if hosts.is_empty() {
+        return RenderedView {
+            text: "Агрегатор не настроен: список aggregator.hosts пуст.".to_string(),
+            keyboard: main_menu(),
+        };
+    }
+    RenderedView {
+        text: format_compare(&runtime.snapshot.load(), &hosts),
+        // Кнопки хостов открывают дашборд выбранного хоста.
+        keyboard: hosts_menu(&hosts, selected.as_deref()),
+    }
+}
+
+fn format_compare(local: &Snapshot, hosts: &[HostState]) -> String {
+    const NAME_WIDTH: usize = 14;
+    let row = |name: &str, state: Option<&Snapshot>| {
+        let name: String = name.chars().take(NAME_WIDTH).collect();
+        let Some(state) = state else {
+            return format!("{name:<NAME_WIDTH$} нет связи");
+        };
+        let ram = percent(
+            state.memory_used_bytes as f64,
+            state.memory_total_bytes as f64,
+        );
+        let disk = state
+            .disks
+            .iter()
+            .map(disk_used_pct)
+            .max_by(f64::total_cmp)
+            .map(|v| format!("{v:.0}%"))
+            .unwrap_or_else(|| "-".to_string());
+        let checks = &state.checks;
+        let total = checks.http.len() + checks.tcp.len() + checks.ssh.len();
+        let up = checks.http.iter().filter(|c| c.up).count()
+            + checks.tcp.iter().filter(|c| c.up).count()
+            + checks.ssh.iter().filter(|c| c.up).count();
+        let checks = if total == 0 {
+            "-".to_string()
+        } else {
+            format!("{up}/{total}{}", if up < total { " ✗" } else { "" })
+        };
+        format!(
+            "{name:<NAME_WIDTH$} {:>4} {:>4} {disk:>5} {checks}",
+            format!("{:.0}%", state.cpu_usage_percent),
+            format!("{ram:.0}%"),
+        )
+    };
+    let mut lines = vec![
+        format!(
+            "{:<NAME_WIDTH$} {:>4} {:>4} {:>5} Проверки",
+            "Хост", "CPU", "RAM", "Диск"
+        ),
+        row(
+            local.host_name.as_deref().unwrap_or("локальный"),
+            (local.last_collect_timestamp_seconds > 0).then_some(local),
+        ),
+    ];
+    lines.extend(
+        hosts
+            .iter()
+            .map(|host| row(&host.name, host.state.as_ref().filter(|_| host.up))),
+    );
+    format!(
+        "📊 <b>Сравнение хостов</b>\n<pre>{}</pre>\nДиск — самый заполненный раздел, проверки — работающие из всех.",
+        escape_html(&lines.join("\n"))
+    )
+}
+
+fn hosts_menu(hosts: &[HostState], selected: Option<&str>) -> InlineKeyboardMarkup {
+    let mark = |current: bool| if current { "✅ " } else { "" };
+    let mut rows = vec![vec![InlineKeyboardButton::callback(
+        format!("{}Локальный", mark(selected.is_none())),
+        "host_local",
+    )]];
+    for (idx, host) in hosts.iter().enumerate() {
+        rows.push(vec![InlineKeyboardButton::callback(
+            format!(
+                "{}{} {}",
+                mark(selected == Some(host.name.as_str())),
+                if host.up { "🟢" } else { "🔴" },
+                host.name
+            ),
+            format!("host_{idx}"),
+        )]);
+    }
+    rows.push(vec![InlineKeyboardButton::callback("⬅ Назад", "dashboard")]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+fn dashboard_menu(has_hosts: bool) -> InlineKeyboardMarkup {
+    let menu = main_menu();
+    if has_hosts {
+        menu.append_row(vec![
+            InlineKeyboardButton::callback("🖥 Хосты", "hosts"),
+            InlineKeyboardButton::callback("📊 Сравнить", "compare"),
+        ])
+    } else {
+        menu
+    }
 }
 
 fn main_menu() -> InlineKeyboardMarkup {
@@ -520,11 +1825,123 @@ fn main_menu() -> InlineKeyboardMarkup {
         ],
         vec![
             InlineKeyboardButton::callback("Уведомления", "alerts"),
+            InlineKeyboardButton::callback("Журнал", "logs"),
             InlineKeyboardButton::callback("Помощь", "help"),
         ],
+        vec![
+            InlineKeyboardButton::callback("Проверки", "checks"),
+            InlineKeyboardButton::callback("⚙ Действия", "actions"),
+        ],
     ])
 }
 
+#[derive(Debug)]
+struct InlineArticle {
+    id: &'static str,
+    title: String,
+    description: String,
+    text: String,
+}
+
+// Сводка отдаётся на любой запрос, отдельные карточки — по началу ключевого слова
+// (`@bot cpu`, `@bot temp`).
+fn inline_articles(state: &Snapshot, query: &str, units: &Units) -> Vec<InlineArticle> {
+    if state.last_collect_timestamp_seconds <= 0 {
+        return vec![InlineArticle {
+            id: "empty",
+            title: "Данных пока нет".to_string(),
+            description: "Агент ещё не завершил первый сбор".to_string(),
+            text: "<b>monitord</b>: данных пока нет".to_string(),
+        }];
+    }
+    let header = format!(
+        "🖥 <b>{}</b> · {}",
+        escape_html(state.host_name.as_deref().unwrap_or("monitord")),
+        units.datetime(state.last_collect_timestamp_seconds)
+    );
+    let cpu = format!(
+        "🧠 CPU: {}% | 🌡 {}",
+        units.number(state.cpu_usage_percent, 1),
+        format_cpu_temp(state, units)
+    );
+    let ram_pct = percent(
+        state.memory_used_bytes as f64,
+        state.memory_total_bytes as f64,
+    );
+    let ram = format!(
+        "💾 RAM: {} ({ram_pct:.0}%)",
+        units.gigabytes_of(state.memory_used_bytes, state.memory_total_bytes)
+    );
+    let mut temps = state
+        .temps
+        .iter()
+        .map(|t| (t.sensor.as_str(), t.temperature_celsius))
+        .chain(
+            state
+                .gpus
+                .iter()
+                .filter_map(|g| g.temperature_celsius.map(|v| (g.name.as_str(), v))),
+        )
+        .collect::<Vec<_>>();
+    temps.sort_by(|a, b| b.1.total_cmp(&a.1));
+    // Описание карточки — обычный текст, без HTML-экранирования.
+    let hottest = temps
+        .first()
+        .map(|(name, v)| format!("{name}: {}", units.temperature(*v, 1)));
+    let temps = temps
+        .iter()
+        .take(6)
+        .map(|(name, v)| format!("• {}: {}", escape_html(name), units.temperature(*v, 1)))
+        .collect::<Vec<_>>();
+
+    let mut articles = vec![InlineArticle {
+        id: "status",
+        title: "Сводка: CPU, RAM, температуры".to_string(),
+        description: format!(
+            "CPU {}% · RAM {ram_pct:.0}% · {}",
+            units.number(state.cpu_usage_percent, 1),
+            format_cpu_temp(state, units)
+        ),
+        text: [header.clone(), cpu.clone(), ram.clone()]
+            .into_iter()
+            .chain(temps.first().map(|_| "🌡 Температуры:".to_string()))
+            .chain(temps.iter().take(3).cloned())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }];
+    let query = query.trim().to_lowercase();
+    let wanted = |keywords: &[&str]| {
+        query.is_empty() || keywords.iter().any(|k| k.starts_with(query.as_str()))
+    };
+    if wanted(&["cpu", "цп", "процессор"]) {
+        articles.push(InlineArticle {
+            id: "cpu",
+            title: format!("CPU {}%", units.number(state.cpu_usage_percent, 1)),
+            description: format!("Температура {}", format_cpu_temp(state, units)),
+            text: format!("{header}\n{cpu}"),
+        });
+    }
+    if wanted(&["ram", "mem", "memory", "память"]) {
+        articles.push(InlineArticle {
+            id: "ram",
+            title: format!("RAM {ram_pct:.0}%"),
+            description: units.gigabytes_of(state.memory_used_bytes, state.memory_total_bytes),
+            text: format!("{header}\n{ram}"),
+        });
+    }
+    if let Some(hottest) =
+        hottest.filter(|_| wanted(&["temp", "sensors", "температура", "сенсоры"]))
+    {
+        articles.push(InlineArticle {
+            id: "temps",
+            title: "Температуры".to_string(),
+            description: hottest,
+            text: format!("{header}\n🌡 Температуры:\n{}", temps.join("\n")),
+        });
+    }
+    articles
+}
+
 fn help_text() -> String {
     [
         "<b>Команды</b>",
@@ -534,19 +1951,80 @@ fn help_text() -> String {
         "• /network - трафик по интерфейсам",
         "• /speed - speedtest интернета",
         "• /disks - диски",
+        "• /du - размер отслеживаемых каталогов",
         "• /gpu - видеокарта",
+        "• /checks - проверки и процентили задержки",
         "• /alerts_status - статус уведомлений",
+        "• /thresholds - пороги уведомлений для этого чата",
+        "• /set &lt;тип&gt; &lt;значение|reset&gt; - изменить порог",
+        "• /timezone &lt;пояс|reset&gt; - часовой пояс времени в сообщениях чата",
+        "• /autorefresh &lt;минуты|off&gt; - автообновление дашборда в этом чате",
+        "• 💤 под ресурсным уведомлением - отложить его вид на 1, 6 или 24 часа",
+        "• /logs - последние записи журнала агента",
+        "• /actions - удалённые действия (с подтверждением)",
+        "• /export - снимок состояния файлом (JSON + CSV истории)",
+        "• /hosts - выбор хоста агрегатора для обзорных экранов",
+        "• /compare - CPU, RAM, диски и проверки всех хостов агрегатора в одной таблице",
+        "• /pair &lt;код&gt; - привязать личный чат (если включено)",
+        "• @бот [cpu|ram|temp] в любом чате - inline-сводка для пересылки",
     ]
     .join("\n")
 }
 
+async fn send_state_export(
+    bot: &Bot,
+    chat_id: ChatId,
+    runtime: &TelegramRuntime,
+) -> ResponseResult<()> {
+    let (json, host) = {
+        let snapshot = runtime.snapshot.load();
+        (
+            serde_json::to_vec_pretty(&**snapshot).unwrap_or_default(),
+            snapshot
+                .host_name
+                .clone()
+                .unwrap_or_else(|| "host".to_string()),
+        )
+    };
+    let ts = now_unix();
+    bot.send_document(
+        chat_id,
+        InputFile::memory(json).file_name(format!("monitord-{host}-{ts}.json")),
+    )
+    .caption("Снимок /api/state")
+    .await?;
+
+    let history = runtime.speed_history.lock().await.clone();
+    if !history.is_empty() {
+        bot.send_document(
+            chat_id,
+            InputFile::memory(speed_history_csv(&history))
+                .file_name(format!("monitord-{host}-{ts}-speed.csv")),
+        )
+        .caption("История скорости сети")
+        .await?;
+    }
+    Ok(())
+}
+
+fn speed_history_csv(history: &VecDeque<SpeedSample>) -> String {
+    let mut out = String::from("ts_unix,rx_bytes_per_sec,tx_bytes_per_sec\n");
+    for s in history {
+        out.push_str(&format!("{},{},{}\n", s.ts, s.rx, s.tx));
+    }
+    out
+}
+
 async fn consume_rate_limit(runtime: &TelegramRuntime, chat_id: i64) -> bool {
     let now = now_unix();
-    let mut limiter = runtime.limiter.lock().await;
-    limiter.allow(chat_id, now)
+    let allowed = runtime.limiter.lock().await.allow(chat_id, now);
+    if !allowed {
+        runtime.health.throttled();
+    }
+    allowed
 }
 
-fn make_speed_sample(state: &State) -> SpeedSample {
+fn make_speed_sample(state: &Snapshot, now: i64) -> SpeedSample {
     let (rx, tx) = if let Some(speed) = state.internet_speed.as_ref() {
         let down = (speed.download_mbps.max(0.0) * 1_000_000.0 / 8.0).round() as u64;
         let up = (speed.upload_mbps.max(0.0) * 1_000_000.0 / 8.0).round() as u64;
@@ -554,20 +2032,25 @@ fn make_speed_sample(state: &State) -> SpeedSample {
     } else {
         network_speed_totals(state)
     };
-    SpeedSample {
-        ts: now_unix(),
-        rx,
-        tx,
+    SpeedSample { ts: now, rx, tx }
+}
+
+// История скорости пополняется с каждого такта сбора, а не только при открытии вида в боте.
+pub async fn run_speed_history(history: SpeedHistory, mut events: broadcast::Receiver<Event>) {
+    while let Some(event) = next_event(&mut events).await {
+        if let Event::SnapshotUpdated { now, snapshot } = event {
+            push_speed_sample(&history, make_speed_sample(&snapshot, now), now).await;
+        }
     }
 }
 
-async fn push_speed_sample(runtime: &TelegramRuntime, sample: SpeedSample) {
+async fn push_speed_sample(history: &SpeedHistory, sample: SpeedSample, now: i64) {
     const WINDOW_SECS: i64 = 600;
     const MAX_POINTS: usize = 600;
-    let mut history = runtime.speed_history.lock().await;
+    let mut history = history.lock().await;
     history.push_back(sample);
 
-    let cutoff = now_unix().saturating_sub(WINDOW_SECS);
+    let cutoff = now.saturating_sub(WINDOW_SECS);
     while history.len() > MAX_POINTS {
         history.pop_front();
     }
@@ -580,15 +2063,19 @@ async fn push_speed_sample(runtime: &TelegramRuntime, sample: SpeedSample) {
     }
 }
 
+// Telegram отклоняет сообщения длиннее 4096 символов, поэтому длинный вид делится на части:
+// в сообщении показывается первая, остальные открываются кнопкой «Показать ещё».
 async fn upsert_dashboard_message(
     bot: &Bot,
     chat_id: ChatId,
     runtime: &TelegramRuntime,
     view: RenderedView,
 ) -> ResponseResult<()> {
+    let view = fit_view(runtime, chat_id.0, view).await;
+
     let existing = {
         let map = runtime.dashboard_messages.lock().await;
-        map.get(&chat_id.0).copied()
+        map.get(&chat_id.0).map(|msg| msg.id)
     };
 
     if let Some(msg_id) = existing {
@@ -609,116 +2096,2195 @@ async fn upsert_dashboard_message(
         .await?;
 
     let mut map = runtime.dashboard_messages.lock().await;
-    map.insert(chat_id.0, sent.id.0);
+    map.insert(
+        chat_id.0,
+        DashboardMessage {
+            id: sent.id.0,
+            used_unix: now_unix(),
+        },
+    );
     Ok(())
 }
 
-pub async fn send_alert_events(
-    bot: &Bot,
-    cfg: &TelegramConfig,
-    state: Arc<RwLock<State>>,
-    events: &[AlertEvent],
-) -> usize {
-    if events.is_empty() {
-        return 0;
+async fn fit_view(runtime: &TelegramRuntime, chat_id: i64, view: RenderedView) -> RenderedView {
+    if view.text.len() <= MESSAGE_TEXT_LIMIT {
+        return view;
     }
-    let mut sent = 0_usize;
+    let pages = ViewPages::new(&view.text, view.keyboard);
+    let first = pages.view(0);
+    runtime.view_pages.lock().await.insert(chat_id, pages);
+    first
+}
 
-    for chat_id in &cfg.allowed_chat_ids {
-        let (enabled, checks_enabled) = {
-            let guard = state.read().await;
-            (
-                guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default),
-                guard.check_alerts_enabled_for_chat(*chat_id),
-            )
-        };
-        if !enabled || !checks_enabled {
-            continue;
+impl ViewPages {
+    fn new(text: &str, keyboard: InlineKeyboardMarkup) -> Self {
+        // Запас под строку «Часть N из M».
+        let mut pages = split_html(text, MESSAGE_TEXT_LIMIT - 64);
+        if pages.len() > MAX_VIEW_PAGES {
+            pages.truncate(MAX_VIEW_PAGES);
+            if let Some(last) = pages.last_mut() {
+                last.push_str("\n… текст сокращён");
+            }
         }
+        Self { pages, keyboard }
+    }
 
-        let lines = events
-            .iter()
-            .filter(|e| !matches!(e.kind, AlertEventKind::Repeat))
-            .map(format_alert_event)
-            .collect::<Vec<_>>();
-        if lines.is_empty() {
-            continue;
+    fn view(&self, page: usize) -> RenderedView {
+        let page = page.min(self.pages.len().saturating_sub(1));
+        let total = self.pages.len();
+        let mut text = self.pages.get(page).cloned().unwrap_or_default();
+        if total > 1 {
+            text.push_str(&format!("\n\n<i>Часть {} из {total}</i>", page + 1));
         }
-
-        let text = format!("<b>Уведомления по проверкам</b>\n{}", lines.join("\n"));
-        if let Err(err) = bot
-            .send_message(ChatId(*chat_id), text)
-            .parse_mode(ParseMode::Html)
-            .reply_markup(main_menu())
-            .await
+        let mut nav = Vec::new();
+        if page > 0 {
+            nav.push(InlineKeyboardButton::callback(
+                "◀",
+                format!("more_{}", page - 1),
+            ));
+        }
+        if page + 1 < total {
+            nav.push(InlineKeyboardButton::callback(
+                "Показать ещё ▶",
+                format!("more_{}", page + 1),
+            ));
+        }
+        let mut rows = Vec::new();
+        if !nav.is_empty() {
+            rows.push(nav);
+        }
+        rows.extend(self.keyboard.inline_keyboard.iter().cloned());
+        RenderedView {
+            text,
+            keyboard: InlineKeyboardMarkup::new(rows),
+        }
+    }
+}
+
+// Делит HTML по строкам на части не длиннее limit байт. Теги, открытые на границе части,
+// закрываются в её конце и открываются заново в начале следующей; слишком длинные строки
+// режутся между тегами и HTML-сущностями.
+fn split_html(text: &str, limit: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    for piece in text
+        .split_inclusive('\n')
+        .flat_map(|line| split_long_line(line, limit / 2))
+    {
+        let mut after = open.clone();
+        track_tags(piece, &mut after);
+        if !page.is_empty() && page.len() + piece.len() + closing_tags(&after).len() > limit {
+            page.push_str(&closing_tags(&open));
+            pages.push(std::mem::take(&mut page));
+            page = open.concat();
+        }
+        page.push_str(piece);
+        open = after;
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn split_long_line(line: &str, max: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_tag = false;
+    let mut in_entity = false;
+    for (idx, ch) in line.char_indices() {
+        if !in_tag && !in_entity && idx - start >= max {
+            pieces.push(&line[start..idx]);
+            start = idx;
+        }
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            '&' => in_entity = true,
+            ';' => in_entity = false,
+            _ => {}
+        }
+    }
+    pieces.push(&line[start..]);
+    pieces
+}
+
+// Открывающие теги хранятся целиком (вместе с атрибутами), чтобы их можно было повторить.
+fn track_tags<'a>(piece: &'a str, open: &mut Vec<&'a str>) {
+    let mut rest = piece;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..=start + len];
+        if let Some(name) = tag.strip_prefix("</") {
+            let name = name.trim_end_matches('>');
+            if let Some(pos) = open.iter().rposition(|t| tag_name(t) == name) {
+                open.remove(pos);
+            }
+        } else {
+            open.push(tag);
+        }
+        rest = &rest[start + len + 1..];
+    }
+}
+
+fn tag_name(tag: &str) -> &str {
+    tag.trim_start_matches('<')
+        .trim_end_matches('>')
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+}
+
+fn closing_tags(open: &[&str]) -> String {
+    open.iter()
+        .rev()
+        .map(|tag| format!("</{}>", tag_name(tag)))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct AlertBatch {
+    pending: HashMap<i64, PendingAlerts>,
+    router: Router,
+    // Чаты, получившие строки в текущем такте: им достаются ссылки из telegram.alerts.links.
+    touched: HashSet<i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AlertSection {
+    Checks,
+    Resources,
+    Rules,
+}
+
+#[derive(Debug, Default)]
+struct PendingAlerts {
+    since_unix: i64,
+    check_lines: Vec<String>,
+    resource_lines: Vec<String>,
+    rule_lines: Vec<String>,
+    links: Vec<AlertLink>,
+    // Виды ресурсных уведомлений в пачке, под которыми ставятся кнопки отсрочки.
+    snooze_kinds: Vec<ResourceAlertKind>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AlertLink {
+    label: String,
+    url: reqwest::Url,
+}
+
+// Больше кнопок-ссылок под одним сообщением не добавляется.
+const MAX_ALERT_LINKS: usize = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct AlertBatchSent {
+    pub checks: usize,
+    pub resources: usize,
+    pub rules: usize,
+}
+
+impl AlertBatchSent {
+    fn record(&self, metrics: &Metrics) {
+        for (kind, count) in [
+            ("check", self.checks),
+            ("resource", self.resources),
+            ("rule", self.rules),
+        ] {
+            for _ in 0..count {
+                metrics.inc_alert_sent(kind);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TickAlerts {
+    checks: Vec<AlertEvent>,
+    resources: Vec<ResourceAlert>,
+    sensors: Vec<SensorAlert>,
+    forecasts: Vec<DiskForecast>,
+    rules: Vec<RuleAlert>,
+    mounts: Vec<MountChange>,
+    read_only: Vec<ReadOnlyMount>,
+    disk_latency: Vec<DiskLatencyAlert>,
+    dir_sizes: Vec<DirSizeAlert>,
+    net_errors: Vec<NetErrorAlert>,
+    cpu_steal: Vec<CpuStealAlert>,
+    time_sync: Vec<TimeSyncAlert>,
+    page_files: Vec<PageFileAlert>,
+    wireguard: Vec<WireguardAlert>,
+    temp_rises: Vec<TempRiseAlert>,
+    reboots: Vec<HostReboot>,
+}
+
+impl TickAlerts {
+    fn push(&mut self, alert: Alert) {
+        match alert {
+            Alert::Check(event) => self.checks.push(event),
+            Alert::Resource(alert) => self.resources.push(alert),
+            Alert::Sensor(alert) => self.sensors.push(alert),
+            Alert::DiskForecast(forecast) => self.forecasts.push(forecast),
+            Alert::Rule(alert) => self.rules.push(alert),
+            Alert::Mount(change) => self.mounts.push(change),
+            Alert::ReadOnly(mount) => self.read_only.push(mount),
+            Alert::DiskLatency(alert) => self.disk_latency.push(alert),
+            Alert::DirSize(alert) => self.dir_sizes.push(alert),
+            Alert::NetErrors(alert) => self.net_errors.push(alert),
+            Alert::CpuSteal(alert) => self.cpu_steal.push(alert),
+            Alert::TimeSync(alert) => self.time_sync.push(alert),
+            Alert::PageFile(alert) => self.page_files.push(alert),
+            Alert::Wireguard(alert) => self.wireguard.push(alert),
+            Alert::TempRise(alert) => self.temp_rises.push(alert),
+            Alert::HostReboot(reboot) => self.reboots.push(reboot),
+            Alert::Watchdog { .. } | Alert::Collector(_) | Alert::Fleet(_) | Alert::External(_) => {
+            }
+        }
+    }
+
+    fn notifications(&self) -> Vec<Notification> {
+        let mut out = Vec::new();
+        out.extend(
+            self.checks
+                .iter()
+                .filter(|e| !matches!(e.kind, AlertEventKind::Repeat))
+                .map(Notification::from_alert_event),
+        );
+        out.extend(self.resources.iter().map(Notification::from_resource_alert));
+        out.extend(self.sensors.iter().map(Notification::from_sensor_alert));
+        out.extend(self.forecasts.iter().map(Notification::from_disk_forecast));
+        out.extend(self.rules.iter().map(Notification::from_rule_alert));
+        out.extend(self.mounts.iter().map(Notification::from_mount_change));
+        out.extend(
+            self.read_only
+                .iter()
+                .map(Notification::from_read_only_mount),
+        );
+        out.extend(
+            self.disk_latency
+                .iter()
+                .map(Notification::from_disk_latency_alert),
+        );
+        out.extend(self.dir_sizes.iter().map(Notification::from_dir_size_alert));
+        out.extend(
+            self.net_errors
+                .iter()
+                .map(Notification::from_net_error_alert),
+        );
+        out.extend(
+            self.cpu_steal
+                .iter()
+                .map(Notification::from_cpu_steal_alert),
+        );
+        out.extend(
+            self.time_sync
+                .iter()
+                .map(Notification::from_time_sync_alert),
+        );
+        out.extend(
+            self.page_files
+                .iter()
+                .map(Notification::from_pagefile_alert),
+        );
+        out.extend(
+            self.wireguard
+                .iter()
+                .map(Notification::from_wireguard_alert),
+        );
+        out.extend(
+            self.temp_rises
+                .iter()
+                .map(Notification::from_temp_rise_alert),
+        );
+        out.extend(self.reboots.iter().map(Notification::from_host_reboot));
+        out
+    }
+}
+
+// Ссылки для уведомлений такта по шаблонам telegram.alerts.links; одинаковые URL схлопываются.
+fn alert_links(
+    links: &[AlertLinkConfig],
+    notifications: &[Notification],
+    host: Option<&str>,
+    now: i64,
+) -> Vec<AlertLink> {
+    let env = minijinja::Environment::new();
+    let mut out: Vec<AlertLink> = Vec::new();
+    for notification in notifications {
+        for link in links
+            .iter()
+            .filter(|l| l.filter.matches(notification.kind, &notification.subject))
         {
-            warn!(chat_id = *chat_id, error = %err, "не удалось отправить уведомления по проверкам");
+            let context = minijinja::context! {
+                host => url_encode(host.unwrap_or_default()),
+                kind => notification.kind,
+                subject => url_encode(&notification.subject),
+                severity => notification.severity.key(),
+                from => (now - link.range_secs as i64) * 1000,
+                to => now * 1000,
+            };
+            let rendered = env
+                .render_str(&link.url, context)
+                .map_err(|err| err.to_string())
+                .and_then(|url| reqwest::Url::parse(&url).map_err(|err| err.to_string()));
+            match rendered {
+                Ok(url) if !out.iter().any(|l| l.url == url) => out.push(AlertLink {
+                    label: link.label.clone(),
+                    url,
+                }),
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(label = %link.label, error = %err, "не удалось собрать ссылку для уведомления")
+                }
+            }
+        }
+    }
+    out
+}
+
+fn url_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
         } else {
-            sent += lines.len();
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn run_alert_delivery(
+    bot: Bot,
+    mut cfg: TelegramConfig,
+    units: Units,
+    state: Arc<RwLock<State>>,
+    router: Router,
+    metrics: Arc<Metrics>,
+    health: BotHealth,
+    mut events: broadcast::Receiver<Event>,
+) {
+    let mut batch = AlertBatch::with_router(router.clone());
+    let mut tick = TickAlerts::default();
+    while let Some(event) = next_event(&mut events).await {
+        match event {
+            Event::AlertFired { alert, .. } | Event::AlertResolved { alert, .. } => match alert {
+                Alert::Watchdog { stale, age_secs } => {
+                    let sent =
+                        send_watchdog_alert(&bot, &health, &cfg, &state, &router, stale, age_secs)
+                            .await;
+                    for _ in 0..sent {
+                        metrics.inc_alert_sent("watchdog");
+                    }
+                }
+                Alert::Collector(alert) => {
+                    let sent =
+                        send_collector_alert(&bot, &health, &cfg, &state, &router, &alert).await;
+                    for _ in 0..sent {
+                        metrics.inc_alert_sent("collector");
+                    }
+                }
+                Alert::Fleet(alert) => {
+                    let sent = send_fleet_alert(&bot, &health, &cfg, &state, &router, &alert).await;
+                    for _ in 0..sent {
+                        metrics.inc_alert_sent("fleet");
+                    }
+                }
+                Alert::External(alert) => {
+                    let sent =
+                        send_external_alert(&bot, &health, &cfg, &state, &router, &alert).await;
+                    for _ in 0..sent {
+                        metrics.inc_alert_sent("external");
+                    }
+                }
+                alert => tick.push(alert),
+            },
+            Event::SnapshotUpdated { now, .. } => {
+                let alerts = std::mem::take(&mut tick);
+                let links = if cfg.alerts.links.is_empty() {
+                    Vec::new()
+                } else {
+                    let host = state.read().await.host_name.clone();
+                    alert_links(
+                        &cfg.alerts.links,
+                        &alerts.notifications(),
+                        host.as_deref(),
+                        now,
+                    )
+                };
+                queue_alert_events(&cfg, &state, &alerts.checks, &mut batch, now).await;
+                queue_text_alerts(&cfg, &units, &state, &alerts.resources, &mut batch, now).await;
+                queue_sensor_alerts(&cfg, &units, &state, &alerts.sensors, &mut batch, now).await;
+                queue_disk_forecasts(&cfg, &state, &alerts.forecasts, &mut batch, now).await;
+                queue_rule_alerts(&cfg, &state, &alerts.rules, &mut batch, now).await;
+                queue_mount_changes(&cfg, &units, &state, &alerts.mounts, &mut batch, now).await;
+                queue_read_only_mounts(&cfg, &state, &alerts.read_only, &mut batch, now).await;
+                queue_disk_latency_alerts(&cfg, &state, &alerts.disk_latency, &mut batch, now)
+                    .await;
+                queue_dir_size_alerts(&cfg, &units, &state, &alerts.dir_sizes, &mut batch, now)
+                    .await;
+                queue_net_error_alerts(&cfg, &state, &alerts.net_errors, &mut batch, now).await;
+                queue_cpu_steal_alerts(&cfg, &units, &state, &alerts.cpu_steal, &mut batch, now)
+                    .await;
+                queue_time_sync_alerts(&cfg, &state, &alerts.time_sync, &mut batch, now).await;
+                queue_pagefile_alerts(&cfg, &units, &state, &alerts.page_files, &mut batch, now)
+                    .await;
+                queue_wireguard_alerts(&cfg, &state, &alerts.wireguard, &mut batch, now).await;
+                queue_temp_rise_alerts(&cfg, &units, &state, &alerts.temp_rises, &mut batch, now)
+                    .await;
+                for reboot in &alerts.reboots {
+                    queue_host_reboot(&cfg, &units, &state, reboot, &mut batch, now).await;
+                }
+                batch.attach_links(&links);
+                flush_alert_batch(&bot, &health, &mut batch, now, cfg.alerts.debounce_secs)
+                    .await
+                    .record(&metrics);
+                report_bot_health(&bot, &health, &cfg, &state, now).await;
+            }
+            Event::ConfigReloaded {
+                config,
+                restart_required,
+                ..
+            } => {
+                cfg.alerts = config.telegram.alerts.clone();
+                let host = state.read().await.host_name.clone();
+                let text = format_config_reloaded(host.as_deref(), &restart_required);
+                send_service_notice(&bot, &health, &cfg, &state, &text).await;
+            }
+            Event::ConfigRejected { error, .. } => {
+                let host = state.read().await.host_name.clone();
+                let text = format_config_rejected(host.as_deref(), &error);
+                send_service_notice(&bot, &health, &cfg, &state, &text).await;
+            }
+            Event::AgentStopping { reason } if cfg.alerts.notify_on_shutdown => {
+                let host = state.read().await.host_name.clone();
+                let text = format_agent_stopping(host.as_deref(), reason);
+                send_service_notice(&bot, &health, &cfg, &state, &text).await;
+            }
+            Event::AgentStopping { .. } => {}
+        }
+    }
+    flush_alert_batch(&bot, &health, &mut batch, now_unix(), 0)
+        .await
+        .record(&metrics);
+}
+
+impl AlertBatch {
+    fn with_router(router: Router) -> Self {
+        Self {
+            router,
+            ..Self::default()
+        }
+    }
+
+    fn push(&mut self, section: AlertSection, chat_id: i64, lines: &[String], now: i64) {
+        match section {
+            AlertSection::Checks => self.push_checks(chat_id, lines, now),
+            AlertSection::Resources => self.push_resources(chat_id, lines, now),
+            AlertSection::Rules => self.push_rules(chat_id, lines, now),
+        }
+    }
+
+    fn entry(&mut self, chat_id: i64, now: i64) -> &mut PendingAlerts {
+        self.touched.insert(chat_id);
+        self.pending
+            .entry(chat_id)
+            .or_insert_with(|| PendingAlerts {
+                since_unix: now,
+                ..PendingAlerts::default()
+            })
+    }
+
+    fn push_checks(&mut self, chat_id: i64, lines: &[String], now: i64) {
+        if !lines.is_empty() {
+            self.entry(chat_id, now)
+                .check_lines
+                .extend_from_slice(lines);
+        }
+    }
+
+    fn push_resources(&mut self, chat_id: i64, lines: &[String], now: i64) {
+        if !lines.is_empty() {
+            self.entry(chat_id, now)
+                .resource_lines
+                .extend_from_slice(lines);
+        }
+    }
+
+    fn push_rules(&mut self, chat_id: i64, lines: &[String], now: i64) {
+        if !lines.is_empty() {
+            self.entry(chat_id, now).rule_lines.extend_from_slice(lines);
+        }
+    }
+
+    fn push_snooze_kinds(&mut self, chat_id: i64, kinds: &[ResourceAlertKind]) {
+        let Some(pending) = self.pending.get_mut(&chat_id) else {
+            return;
+        };
+        for kind in kinds {
+            if !pending.snooze_kinds.contains(kind) {
+                pending.snooze_kinds.push(*kind);
+            }
+        }
+    }
+
+    fn attach_links(&mut self, links: &[AlertLink]) {
+        for chat_id in self.touched.drain() {
+            let Some(pending) = self.pending.get_mut(&chat_id) else {
+                continue;
+            };
+            for link in links {
+                if pending.links.len() < MAX_ALERT_LINKS && !pending.links.contains(link) {
+                    pending.links.push(link.clone());
+                }
+            }
+        }
+    }
+
+    fn take_due(&mut self, now: i64, window_secs: u64) -> Vec<(i64, PendingAlerts)> {
+        let due = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.saturating_sub(p.since_unix) >= window_secs as i64)
+            .map(|(chat_id, _)| *chat_id)
+            .collect::<Vec<_>>();
+        let mut batches = due
+            .into_iter()
+            .filter_map(|chat_id| self.pending.remove(&chat_id).map(|p| (chat_id, p)))
+            .collect::<Vec<_>>();
+        batches.sort_by_key(|(chat_id, _)| *chat_id);
+        batches
+    }
+}
+
+async fn queue_alert_events(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    events: &[AlertEvent],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let events = route_alerts(
+        cfg,
+        state,
+        batch,
+        events,
+        AlertSection::Checks,
+        now,
+        Notification::from_alert_event,
+        |e, guard| {
+            (!matches!(e.kind, AlertEventKind::Repeat))
+                .then(|| format_alert_event(e, guard.host_name.as_deref(), &cfg.alerts.templates))
+        },
+    )
+    .await;
+    if events.is_empty() {
+        return;
+    }
+    let host = state.read().await.host_name.clone();
+    let visible = events
+        .iter()
+        .filter(|e| !matches!(e.kind, AlertEventKind::Repeat))
+        .collect::<Vec<_>>();
+    let lines = visible
+        .iter()
+        .map(|e| format_alert_event(e, host.as_deref(), &cfg.alerts.templates))
+        .collect::<Vec<_>>();
+    let critical_lines = visible
+        .iter()
+        .filter(|e| e.severity == AlertSeverity::Critical)
+        .map(|e| format_alert_event(e, host.as_deref(), &cfg.alerts.templates))
+        .collect::<Vec<_>>();
+
+    let recipients = alert_recipients(cfg, state).await;
+    for chat_id in &recipients {
+        let (enabled, checks_enabled) = {
+            let guard = state.read().await;
+            (
+                guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default),
+                guard.check_alerts_enabled_for_chat(*chat_id),
+            )
+        };
+        if !enabled || !checks_enabled {
+            continue;
+        }
+        batch.push_checks(*chat_id, &lines, now);
+    }
+
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_checks(chat_id, &critical_lines, now);
+    }
+}
+
+async fn queue_text_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[ResourceAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    // Маршруты подчиняются общему cooldown, а повторы для отдельных чатов идут только в чаты без маршрута.
+    let (broadcast, chat_only): (Vec<_>, Vec<_>) =
+        alerts.iter().cloned().partition(|alert| alert.broadcast);
+    let mut alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        &broadcast,
+        AlertSection::Resources,
+        now,
+        Notification::from_resource_alert,
+        |alert, guard| format_resource_alert_global(alert, guard, cfg, units),
+    )
+    .await;
+    let unrouted_broadcast = alerts.len();
+    alerts.extend(chat_only.into_iter().filter(|alert| {
+        batch
+            .router
+            .destinations(&Notification::from_resource_alert(alert))
+            .is_none()
+    }));
+    if alerts.is_empty() {
+        return;
+    }
+
+    let recipients = alert_recipients(cfg, state).await;
+    for chat_id in &recipients {
+        let (enabled, filtered) = {
+            let guard = state.read().await;
+            let enabled = guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default);
+            let filtered = alerts
+                .iter()
+                .filter(|alert| alert.chat_ids.contains(chat_id))
+                .filter_map(|alert| {
+                    let (severity, threshold) = if alert.recovered {
+                        (
+                            alert.severity,
+                            guard.resource_clear_threshold(
+                                alert.kind,
+                                alert.subject.as_deref(),
+                                &cfg.alerts,
+                            ),
+                        )
+                    } else {
+                        guard.resource_severity_for_chat(
+                            *chat_id,
+                            alert.kind,
+                            alert.subject.as_deref(),
+                            alert.value,
+                            &cfg.alerts,
+                        )?
+                    };
+                    let text = format_resource_alert(
+                        alert,
+                        severity,
+                        threshold,
+                        guard.host_name.as_deref(),
+                        &cfg.alerts.templates,
+                        units,
+                    );
+                    Some((alert, text))
+                })
+                .collect::<Vec<_>>();
+            (enabled, filtered)
+        };
+        if !enabled {
+            continue;
+        }
+        let (snooze_kinds, filtered_texts): (Vec<_>, Vec<_>) = filtered
+            .into_iter()
+            .map(|(alert, text)| ((!alert.recovered).then_some(alert.kind), text))
+            .unzip();
+        batch.push_resources(*chat_id, &filtered_texts, now);
+        batch.push_snooze_kinds(
+            *chat_id,
+            &snooze_kinds.into_iter().flatten().collect::<Vec<_>>(),
+        );
+    }
+
+    let critical_texts = {
+        let guard = state.read().await;
+        alerts[..unrouted_broadcast]
+            .iter()
+            .filter(|alert| alert.severity == AlertSeverity::Critical)
+            .filter_map(|alert| format_resource_alert_global(alert, &guard, cfg, units))
+            .collect::<Vec<_>>()
+    };
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_resources(chat_id, &critical_texts, now);
+    }
+}
+
+async fn queue_sensor_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[SensorAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_sensor_alert,
+        |a, guard| {
+            Some(format_sensor_alert(
+                a,
+                guard.host_name.as_deref(),
+                &cfg.alerts.templates,
+                units,
+            ))
+        },
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(|a| format_sensor_alert(a, guard.host_name.as_deref(), &cfg.alerts.templates, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_resources(chat_id, &lines, now);
+    }
+}
+
+async fn queue_disk_forecasts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    forecasts: &[DiskForecast],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let forecasts = route_alerts(
+        cfg,
+        state,
+        batch,
+        forecasts,
+        AlertSection::Resources,
+        now,
+        Notification::from_disk_forecast,
+        |f, guard| {
+            Some(format_disk_forecast(
+                f,
+                guard.host_name.as_deref(),
+                &cfg.alerts.templates,
+            ))
+        },
+    )
+    .await;
+    if forecasts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = forecasts
+        .iter()
+        .map(|f| format_disk_forecast(f, guard.host_name.as_deref(), &cfg.alerts.templates))
+        .collect::<Vec<_>>();
+    for chat_id in recipients {
+        if !guard.alerts_enabled_for_chat(chat_id, cfg.alerts.enabled_by_default)
+            || !guard.resource_alert_enabled_for_chat(chat_id, ResourceAlertKind::DiskUsage)
+        {
+            continue;
+        }
+        batch.push_resources(chat_id, &lines, now);
+    }
+}
+
+async fn queue_rule_alerts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    alerts: &[RuleAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Rules,
+        now,
+        Notification::from_rule_alert,
+        |a, _| Some(format_rule_alert(a)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let lines = alerts.iter().map(format_rule_alert).collect::<Vec<_>>();
+    let critical_lines = alerts
+        .iter()
+        .filter(|a| a.severity == AlertSeverity::Critical)
+        .map(format_rule_alert)
+        .collect::<Vec<_>>();
+
+    let guard = state.read().await;
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_rules(*chat_id, &lines, now);
+        }
+    }
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_rules(chat_id, &critical_lines, now);
+    }
+}
+
+async fn queue_mount_changes(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    changes: &[MountChange],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let changes = route_alerts(
+        cfg,
+        state,
+        batch,
+        changes,
+        AlertSection::Resources,
+        now,
+        Notification::from_mount_change,
+        |c, _| Some(format_mount_change(c, units)),
+    )
+    .await;
+    if changes.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = changes
+        .iter()
+        .map(|c| format_mount_change(c, units))
+        .collect::<Vec<_>>();
+    let critical_lines = changes
+        .iter()
+        .filter(|c| !c.appeared)
+        .map(|c| format_mount_change(c, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_resources(chat_id, &critical_lines, now);
+    }
+}
+
+async fn queue_read_only_mounts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    mounts: &[ReadOnlyMount],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let mounts = route_alerts(
+        cfg,
+        state,
+        batch,
+        mounts,
+        AlertSection::Resources,
+        now,
+        Notification::from_read_only_mount,
+        |m, _| Some(format_read_only_mount(m)),
+    )
+    .await;
+    if mounts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = mounts
+        .iter()
+        .map(format_read_only_mount)
+        .collect::<Vec<_>>();
+    let critical_lines = mounts
+        .iter()
+        .filter(|m| !m.recovered)
+        .map(format_read_only_mount)
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_resources(chat_id, &critical_lines, now);
+    }
+}
+
+async fn queue_disk_latency_alerts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    alerts: &[DiskLatencyAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_disk_latency_alert,
+        |a, _| Some(format_disk_latency_alert(a)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(format_disk_latency_alert)
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_dir_size_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[DirSizeAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_dir_size_alert,
+        |a, _| Some(format_dir_size_alert(a, units)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(|a| format_dir_size_alert(a, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_net_error_alerts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    alerts: &[NetErrorAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_net_error_alert,
+        |a, _| Some(format_net_error_alert(a)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(format_net_error_alert)
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_wireguard_alerts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    alerts: &[WireguardAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_wireguard_alert,
+        |a, _| Some(format_wireguard_alert(a)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(format_wireguard_alert)
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_time_sync_alerts(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    alerts: &[TimeSyncAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_time_sync_alert,
+        |a, _| Some(format_time_sync_alert(a)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(format_time_sync_alert)
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_cpu_steal_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[CpuStealAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_cpu_steal_alert,
+        |a, _| Some(format_cpu_steal_alert(a, units)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(|a| format_cpu_steal_alert(a, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_pagefile_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[PageFileAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_pagefile_alert,
+        |a, _| Some(format_pagefile_alert(a, units)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(|a| format_pagefile_alert(a, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_temp_rise_alerts(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    alerts: &[TempRiseAlert],
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let alerts = route_alerts(
+        cfg,
+        state,
+        batch,
+        alerts,
+        AlertSection::Resources,
+        now,
+        Notification::from_temp_rise_alert,
+        |a, _| Some(format_temp_rise_alert(a, units)),
+    )
+    .await;
+    if alerts.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = alerts
+        .iter()
+        .map(|a| format_temp_rise_alert(a, units))
+        .collect::<Vec<_>>();
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+}
+
+async fn queue_host_reboot(
+    cfg: &TelegramConfig,
+    units: &Units,
+    state: &RwLock<State>,
+    reboot: &HostReboot,
+    batch: &mut AlertBatch,
+    now: i64,
+) {
+    let unrouted = route_alerts(
+        cfg,
+        state,
+        batch,
+        std::slice::from_ref(reboot),
+        AlertSection::Resources,
+        now,
+        Notification::from_host_reboot,
+        |r, guard| Some(format_host_reboot(r, guard.host_name.as_deref(), units)),
+    )
+    .await;
+    if unrouted.is_empty() {
+        return;
+    }
+    let recipients = alert_recipients(cfg, state).await;
+    let guard = state.read().await;
+    let lines = [format_host_reboot(
+        reboot,
+        guard.host_name.as_deref(),
+        units,
+    )];
+    for chat_id in &recipients {
+        if guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default) {
+            let chat_units = units.with_timezone(guard.chat_timezones.get(chat_id).copied());
+            let lines = [format_host_reboot(
+                reboot,
+                guard.host_name.as_deref(),
+                &chat_units,
+            )];
+            batch.push_resources(*chat_id, &lines, now);
+        }
+    }
+    for chat_id in critical_only_chats(cfg, &recipients) {
+        batch.push_resources(chat_id, &lines, now);
+    }
+}
+
+async fn send_watchdog_alert(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    router: &Router,
+    stale: bool,
+    age_secs: i64,
+) -> usize {
+    let notification = Notification::watchdog(stale, age_secs);
+    let targets = immediate_alert_targets(cfg, state, router, &notification).await;
+    let host = state
+        .read()
+        .await
+        .host_name
+        .as_ref()
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    let text = if stale {
+        format!(
+            "🚨 <b>Сбор данных остановился{host}</b>\nПоследнее обновление {} назад. Данные и уведомления могут быть неактуальны.",
+            human_uptime(0, age_secs)
+        )
+    } else {
+        format!("✅ <b>Сбор данных{host} возобновился</b>")
+    };
+    send_immediate_alert(bot, health, &targets, &text, "сторожевого таймера").await
+}
+
+async fn send_collector_alert(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    router: &Router,
+    alert: &CollectorAlert,
+) -> usize {
+    let notification = Notification::from_collector_alert(alert);
+    let targets = immediate_alert_targets(cfg, state, router, &notification).await;
+    send_immediate_alert(
+        bot,
+        health,
+        &targets,
+        &format_collector_alert(alert),
+        "о сборщике",
+    )
+    .await
+}
+
+fn format_collector_alert(alert: &CollectorAlert) -> String {
+    if alert.recovered {
+        return format!(
+            "✅ <b>Сборщик {} снова отдаёт данные</b>",
+            escape_html(&alert.collector)
+        );
+    }
+    format!(
+        "⚠ <b>Сборщик {} деградировал</b>\nНет данных {} тактов подряд{}",
+        escape_html(&alert.collector),
+        alert.consecutive_failures,
+        alert
+            .error
+            .as_deref()
+            .map(|e| format!(": {}", escape_html(e)))
+            .unwrap_or_default()
+    )
+}
+
+async fn send_fleet_alert(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    router: &Router,
+    alert: &FleetAlert,
+) -> usize {
+    let notification = Notification::from_fleet_alert(alert);
+    let targets = immediate_alert_targets(cfg, state, router, &notification).await;
+    send_immediate_alert(bot, health, &targets, &format_fleet_alert(alert), "о хосте").await
+}
+
+fn format_fleet_alert(alert: &FleetAlert) -> String {
+    match alert {
+        FleetAlert::Host { name, up: true, .. } => {
+            format!("✅ <b>Хост {} снова на связи</b>", escape_html(name))
+        }
+        FleetAlert::Host { name, error, .. } => format!(
+            "🚨 <b>Хост {} недоступен</b>{}",
+            escape_html(name),
+            error
+                .as_deref()
+                .map(|e| format!("\n{}", escape_html(e)))
+                .unwrap_or_default()
+        ),
+        FleetAlert::Check { host, check, up } => format!(
+            "{} <b>{}</b>: проверка «{}» {}",
+            if *up { "🟢" } else { "🔴" },
+            escape_html(host),
+            escape_html(check),
+            if *up {
+                "восстановлена"
+            } else {
+                "недоступна"
+            }
+        ),
+    }
+}
+
+async fn send_external_alert(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    router: &Router,
+    alert: &ExternalAlert,
+) -> usize {
+    let notification = Notification::from_external_alert(alert);
+    let targets = immediate_alert_targets(cfg, state, router, &notification).await;
+    let host = state.read().await.host_name.clone();
+    let text = format_external_alert(alert, host.as_deref());
+    send_immediate_alert(bot, health, &targets, &text, "из внешнего источника").await
+}
+
+fn format_external_alert(alert: &ExternalAlert, host: Option<&str>) -> String {
+    let host = host
+        .map(|h| format!(" ({})", escape_html(h)))
+        .unwrap_or_default();
+    let icon = match (alert.recovered, alert.severity) {
+        (true, _) => "✅",
+        (false, AlertSeverity::Warning) => "⚠",
+        (false, AlertSeverity::Critical) => "🚨",
+    };
+    let mut text = format!("{icon} <b>{}</b>{host}", escape_html(&alert.name));
+    if alert.recovered {
+        text.push_str(": снято");
+    }
+    if let Some(message) = alert.message.as_deref() {
+        text.push_str(&format!("\n{}", escape_html(message)));
+    }
+    text
+}
+
+// Срочные уведомления уходят сразу, минуя группировку до следующего сбора.
+async fn immediate_alert_targets(
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    router: &Router,
+    notification: &Notification,
+) -> Vec<i64> {
+    let recipients = alert_recipients(cfg, state).await;
+    let routed = router.destinations(notification);
+    let guard = state.read().await;
+    let candidates = match &routed {
+        Some(dest) => dest.telegram_chat_ids.clone(),
+        None => recipients.clone(),
+    };
+    let mut targets = candidates
+        .into_iter()
+        .filter(|id| guard.alerts_enabled_for_chat(*id, cfg.alerts.enabled_by_default))
+        .collect::<Vec<_>>();
+    if routed.is_none() && notification.severity == AlertSeverity::Critical {
+        targets.extend(critical_only_chats(cfg, &recipients));
+    }
+    targets
+}
+
+async fn send_immediate_alert(
+    bot: &Bot,
+    health: &BotHealth,
+    targets: &[i64],
+    text: &str,
+    what: &str,
+) -> usize {
+    let mut sent = 0;
+    for &chat_id in targets {
+        let result = bot
+            .send_message(ChatId(chat_id), text.to_string())
+            .parse_mode(ParseMode::Html)
+            .await;
+        health.observe_delivery(&result, now_unix());
+        match result {
+            Ok(_) => sent += 1,
+            Err(err) => {
+                warn!(chat_id, error = %err, "не удалось отправить уведомление {what}");
+            }
+        }
+    }
+    sent
+}
+
+async fn send_service_notice(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    text: &str,
+) {
+    let recipients = alert_recipients(cfg, state).await;
+    let targets = {
+        let guard = state.read().await;
+        recipients
+            .into_iter()
+            .filter(|id| guard.alerts_enabled_for_chat(*id, cfg.alerts.enabled_by_default))
+            .collect::<Vec<_>>()
+    };
+    for chat_id in targets {
+        let result = bot
+            .send_message(ChatId(chat_id), text)
+            .parse_mode(ParseMode::Html)
+            .await;
+        health.observe_delivery(&result, now_unix());
+        if let Err(err) = result {
+            warn!(chat_id, error = %err, "не удалось отправить служебное сообщение");
+        }
+    }
+}
+
+async fn report_bot_health(
+    bot: &Bot,
+    health: &BotHealth,
+    cfg: &TelegramConfig,
+    state: &RwLock<State>,
+    now: i64,
+) {
+    if let Some(streak) = health.overdue(now, cfg.alerts.delivery_warn_after_secs) {
+        error!(
+            since = streak.since,
+            errors = streak.errors,
+            "!!! запросы к Telegram API не проходят {} с: уведомления в Telegram теряются",
+            now - streak.since
+        );
+        let host = state.read().await.host_name.clone();
+        let text = format_delivery_failing(host.as_deref(), &streak, now);
+        send_service_notice(bot, health, cfg, state, &text).await;
+    }
+    if let Some((streak, recovered_at)) = health.take_recovered() {
+        warn!(
+            since = streak.since,
+            errors = streak.errors,
+            "доставка в Telegram восстановлена через {} с",
+            recovered_at - streak.since
+        );
+        let host = state.read().await.host_name.clone();
+        let text = format_delivery_recovered(host.as_deref(), &streak, recovered_at);
+        send_service_notice(bot, health, cfg, state, &text).await;
+    }
+}
+
+fn format_delivery_failing(host: Option<&str>, streak: &FailureStreak, now: i64) -> String {
+    let host = host
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    format!(
+        "⚠️ <b>Сообщения в Telegram{host} не доставляются</b>\nОшибок подряд: {} за {} мин. Уведомления могут теряться.",
+        streak.errors,
+        (now - streak.since) / 60
+    )
+}
+
+fn format_delivery_recovered(
+    host: Option<&str>,
+    streak: &FailureStreak,
+    recovered_at: i64,
+) -> String {
+    let host = host
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    format!(
+        "✅ <b>Доставка в Telegram{host} восстановлена</b>\nСообщения не доходили {} мин (ошибок: {}), часть уведомлений за это время потеряна.",
+        (recovered_at - streak.since) / 60,
+        streak.errors
+    )
+}
+
+fn format_config_reloaded(host: Option<&str>, restart_required: &[&str]) -> String {
+    let host = host
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    let mut text = format!("⚙️ <b>Конфигурация{host} перезагружена</b>");
+    if !restart_required.is_empty() {
+        text.push_str(&format!(
+            "\nТребуют перезапуска: <code>{}</code>",
+            restart_required.join(", ")
+        ));
+    }
+    text
+}
+
+fn format_agent_stopping(host: Option<&str>, reason: &str) -> String {
+    let host = host
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    format!("⏹ <b>monitord{host} останавливается</b> ({reason})")
+}
+
+fn format_config_rejected(host: Option<&str>, error: &str) -> String {
+    let host = host
+        .map(|h| format!(" на {}", escape_html(h)))
+        .unwrap_or_default();
+    format!(
+        "❌ <b>Новая конфигурация{host} отклонена</b>\nПродолжаю работать со старой.\n<code>{}</code>",
+        escape_html(error)
+    )
+}
+
+async fn flush_alert_batch(
+    bot: &Bot,
+    health: &BotHealth,
+    batch: &mut AlertBatch,
+    now: i64,
+    window_secs: u64,
+) -> AlertBatchSent {
+    let mut sent = AlertBatchSent::default();
+    for (chat_id, pending) in batch.take_due(now, window_secs) {
+        // Длинная пачка уходит несколькими сообщениями; кнопки — под последним.
+        let parts = format_alert_batch(&pending);
+        let last = parts.len().saturating_sub(1);
+        let mut delivered = true;
+        for (idx, text) in parts.into_iter().enumerate() {
+            let request = bot
+                .send_message(ChatId(chat_id), text)
+                .parse_mode(ParseMode::Html);
+            let result = if idx == last {
+                request
+                    .reply_markup(alert_keyboard(&pending.links, &pending.snooze_kinds))
+                    .await
+            } else {
+                request.await
+            };
+            health.observe_delivery(&result, now_unix());
+            if let Err(err) = result {
+                warn!(chat_id, part = idx + 1, error = %err, "не удалось отправить уведомления");
+                delivered = false;
+                break;
+            }
+        }
+        if !delivered {
+            continue;
+        }
+        sent.checks += pending.check_lines.len();
+        sent.resources += pending.resource_lines.len();
+        sent.rules += pending.rule_lines.len();
+    }
+    sent
+}
+
+fn alert_keyboard(links: &[AlertLink], snooze_kinds: &[ResourceAlertKind]) -> InlineKeyboardMarkup {
+    let mut rows = links
+        .iter()
+        .map(|link| {
+            vec![InlineKeyboardButton::url(
+                link.label.clone(),
+                link.url.clone(),
+            )]
+        })
+        .collect::<Vec<_>>();
+    for kind in snooze_kinds {
+        rows.push(
+            SNOOZE_HOURS
+                .iter()
+                .enumerate()
+                .map(|(idx, hours)| {
+                    let label = if idx == 0 {
+                        format!("💤 {} {}ч", alert_kind_title(*kind), hours)
+                    } else {
+                        format!("{}ч", hours)
+                    };
+                    InlineKeyboardButton::callback(
+                        label,
+                        format!("snooze_{}_{}", kind.key(), hours),
+                    )
+                })
+                .collect(),
+        );
+    }
+    rows.extend(main_menu().inline_keyboard);
+    InlineKeyboardMarkup::new(rows)
+}
+
+fn format_alert_batch(pending: &PendingAlerts) -> Vec<String> {
+    let mut sections = Vec::new();
+    if !pending.check_lines.is_empty() {
+        sections.push(("<b>Уведомления по проверкам</b>", &pending.check_lines));
+    }
+    if !pending.resource_lines.is_empty() {
+        sections.push(("<b>Ресурсные уведомления</b>", &pending.resource_lines));
+    }
+    if !pending.rule_lines.is_empty() {
+        sections.push(("<b>Правила</b>", &pending.rule_lines));
+    }
+
+    let mut text = String::new();
+    for (title, lines) in sections {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(title);
+        for line in lines.iter() {
+            text.push('\n');
+            text.push_str(line);
         }
     }
-    sent
+    // Ничего не отбрасывается: то, что не влезло в одно сообщение, уходит следующими.
+    split_html(&text, MESSAGE_TEXT_LIMIT)
 }
 
-pub async fn send_text_alerts(
-    bot: &Bot,
+#[allow(clippy::too_many_arguments)]
+async fn route_alerts<T: Clone>(
     cfg: &TelegramConfig,
-    state: Arc<RwLock<State>>,
-    alerts: &[ResourceAlert],
-) -> usize {
-    if alerts.is_empty() {
-        return 0;
+    state: &RwLock<State>,
+    batch: &mut AlertBatch,
+    items: &[T],
+    section: AlertSection,
+    now: i64,
+    notification: impl Fn(&T) -> Notification,
+    format: impl Fn(&T, &State) -> Option<String>,
+) -> Vec<T> {
+    let guard = state.read().await;
+    let mut unrouted = Vec::new();
+    for item in items {
+        let Some(dest) = batch.router.destinations(&notification(item)) else {
+            unrouted.push(item.clone());
+            continue;
+        };
+        let Some(line) = format(item, &guard) else {
+            continue;
+        };
+        for chat_id in dest.telegram_chat_ids {
+            if guard.alerts_enabled_for_chat(chat_id, cfg.alerts.enabled_by_default) {
+                batch.push(section, chat_id, std::slice::from_ref(&line), now);
+            }
+        }
     }
-    let mut sent = 0_usize;
+    unrouted
+}
 
-    for chat_id in &cfg.allowed_chat_ids {
-        let (enabled, filtered_texts) = {
-            let guard = state.read().await;
-            let enabled = guard.alerts_enabled_for_chat(*chat_id, cfg.alerts.enabled_by_default);
-            let filtered = alerts
-                .iter()
-                .filter(|alert| guard.resource_alert_enabled_for_chat(*chat_id, alert.kind))
-                .map(|alert| alert.text.clone())
-                .collect::<Vec<_>>();
-            (enabled, filtered)
+fn format_resource_alert_global(
+    alert: &ResourceAlert,
+    state: &State,
+    cfg: &TelegramConfig,
+    units: &Units,
+) -> Option<String> {
+    let (severity, threshold) = if alert.recovered {
+        (
+            alert.severity,
+            state.resource_clear_threshold(alert.kind, alert.subject.as_deref(), &cfg.alerts),
+        )
+    } else {
+        AlertSeverity::classify(
+            alert.value,
+            alert
+                .kind
+                .base_threshold(alert.subject.as_deref(), &cfg.alerts),
+            None,
+        )?
+    };
+    Some(format_resource_alert(
+        alert,
+        severity,
+        threshold,
+        state.host_name.as_deref(),
+        &cfg.alerts.templates,
+        units,
+    ))
+}
+
+async fn alert_recipients(cfg: &TelegramConfig, state: &RwLock<State>) -> Vec<i64> {
+    let mut recipients = cfg.allowed_chat_ids.clone();
+    let guard = state.read().await;
+    let mut paired = guard
+        .paired_chat_ids
+        .iter()
+        .copied()
+        .filter(|id| !recipients.contains(id))
+        .collect::<Vec<_>>();
+    paired.sort_unstable();
+    recipients.extend(paired);
+    recipients
+}
+
+fn critical_only_chats<'a>(
+    cfg: &'a TelegramConfig,
+    recipients: &'a [i64],
+) -> impl Iterator<Item = i64> + 'a {
+    cfg.critical_chat_ids
+        .iter()
+        .copied()
+        .filter(|id| !recipients.contains(id))
+}
+
+fn format_resource_alert(
+    alert: &ResourceAlert,
+    severity: AlertSeverity,
+    threshold: f64,
+    host: Option<&str>,
+    templates: &HashMap<String, String>,
+    units: &Units,
+) -> String {
+    let (unit, value, threshold) = if alert.kind.is_temperature() {
+        (
+            units.temperature_unit(),
+            units.temperature_value(alert.value),
+            units.temperature_value(threshold),
+        )
+    } else {
+        ("%", alert.value, threshold)
+    };
+    let template_key = if alert.recovered {
+        "resource_recovered"
+    } else {
+        alert.kind.key()
+    };
+    if let Some(template) = templates.get(template_key) {
+        let ctx = minijinja::context! {
+            kind => alert.kind.key(),
+            severity => severity.key(),
+            recovered => alert.recovered,
+            title => alert_kind_title(alert.kind),
+            value => value,
+            threshold => threshold,
+            unit => unit,
+            subject => alert.subject.as_deref(),
+            host => host,
+            gpu_context => minijinja::value::Serde(&alert.gpu_context),
         };
-        if !enabled {
-            continue;
+        if let Some(text) = render_alert_template(template_key, template, ctx) {
+            return text;
         }
-        if filtered_texts.is_empty() {
-            continue;
+    }
+
+    let subject = alert
+        .subject
+        .as_ref()
+        .map(|s| format!("\nДиск: {}", escape_html(s)))
+        .unwrap_or_default();
+    if alert.recovered {
+        return format!(
+            "✅ <b>{} в норме</b>{subject}\nТекущее значение: {}{unit} (порог сброса {}{unit})",
+            alert_kind_title(alert.kind),
+            units.number(value, 1),
+            units.number(threshold, 1)
+        );
+    }
+
+    let title = match alert.kind {
+        ResourceAlertKind::GpuLoad => "<b>Высокая нагрузка GPU</b>",
+        ResourceAlertKind::GpuTemp => "<b>Высокая температура GPU</b>",
+        ResourceAlertKind::CpuTemp => "<b>Высокая температура CPU</b>",
+        ResourceAlertKind::CpuLoad => "<b>Высокая нагрузка CPU</b>",
+        ResourceAlertKind::RamUsage => "<b>Высокое использование RAM</b>",
+        ResourceAlertKind::DiskUsage => "<b>Высокая заполненность диска</b>",
+    };
+    let icon = match severity {
+        AlertSeverity::Warning => "⚠",
+        AlertSeverity::Critical => "🚨",
+    };
+    let gpu_context = alert
+        .gpu_context
+        .as_ref()
+        .map(|c| format_gpu_alert_context(c, units))
+        .unwrap_or_default();
+    format!(
+        "{icon} {title}{subject}\nТекущее значение: {}{unit} (порог {}{unit}){gpu_context}",
+        units.number(value, 1),
+        units.number(threshold, 1)
+    )
+}
+
+fn format_gpu_alert_context(context: &GpuAlertContext, units: &Units) -> String {
+    let mut out = String::new();
+    let percent = |v: Option<f64>| {
+        v.map(|v| format!("{}%", units.number(v, 0)))
+            .unwrap_or_else(|| "н/д".to_string())
+    };
+    if context.encoder_percent.is_some() || context.decoder_percent.is_some() {
+        out.push_str(&format!(
+            "\nКодировщик: {}, декодер: {}",
+            percent(context.encoder_percent),
+            percent(context.decoder_percent)
+        ));
+    }
+    if !context.processes.is_empty() {
+        out.push_str("\nПроцессы:");
+        for p in &context.processes {
+            let memory = p
+                .memory_mb
+                .map(|mb| format!(", {}", units.gigabytes(mb * 1024 * 1024)))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "\n• {} ({}): {}{memory}",
+                escape_html(&p.name),
+                p.pid,
+                percent(p.sm_percent)
+            ));
         }
+    }
+    out
+}
 
-        let text = format!(
-            "<b>Ресурсные уведомления</b>\n{}",
-            filtered_texts.join("\n")
+fn format_rule_alert(alert: &RuleAlert) -> String {
+    let text = alert.message.as_deref().unwrap_or(alert.expr.as_str());
+    if alert.recovered {
+        return format!(
+            "✅ Правило '{}' больше не выполняется",
+            escape_html(&alert.name)
         );
-        if let Err(err) = bot
-            .send_message(ChatId(*chat_id), text)
-            .parse_mode(ParseMode::Html)
-            .reply_markup(main_menu())
-            .await
-        {
-            warn!(chat_id = *chat_id, error = %err, "не удалось отправить ресурсные уведомления");
-        } else {
-            sent += filtered_texts.len();
+    }
+    let icon = match alert.severity {
+        AlertSeverity::Warning => "⚠",
+        AlertSeverity::Critical => "🚨",
+    };
+    format!(
+        "{icon} Правило '{}': {}",
+        escape_html(&alert.name),
+        escape_html(text)
+    )
+}
+
+fn format_sensor_alert(
+    alert: &SensorAlert,
+    host: Option<&str>,
+    templates: &HashMap<String, String>,
+    units: &Units,
+) -> String {
+    let label = alert.label.as_deref().unwrap_or(alert.sensor.as_str());
+    if let Some(template) = templates.get("sensor_temp") {
+        let ctx = minijinja::context! {
+            label => label,
+            sensor => alert.sensor.as_str(),
+            value => units.temperature_value(alert.value),
+            threshold => units.temperature_value(alert.threshold),
+            unit => units.temperature_unit(),
+            recovered => alert.recovered,
+            host => host,
+        };
+        if let Some(text) = render_alert_template("sensor_temp", template, ctx) {
+            return text;
+        }
+    }
+    let (icon, title, threshold_label) = if alert.recovered {
+        ("✅", "Температура в норме", "порог сброса")
+    } else {
+        ("🌡", "Высокая температура", "порог")
+    };
+    format!(
+        "{icon} <b>{title}: {}</b>\nДатчик: {}\nТекущее значение: {} ({threshold_label} {})",
+        escape_html(label),
+        escape_html(&alert.sensor),
+        units.temperature(alert.value, 1),
+        units.temperature(alert.threshold, 1)
+    )
+}
+
+fn format_disk_forecast(
+    forecast: &DiskForecast,
+    host: Option<&str>,
+    templates: &HashMap<String, String>,
+) -> String {
+    let eta = human_uptime(0, forecast.seconds_until_full as i64);
+    if let Some(template) = templates.get("disk_forecast") {
+        let ctx = minijinja::context! {
+            mount => forecast.mount.as_str(),
+            used_percent => forecast.used_percent,
+            seconds_until_full => forecast.seconds_until_full,
+            eta => eta.as_str(),
+            host => host,
+        };
+        if let Some(text) = render_alert_template("disk_forecast", template, ctx) {
+            return text;
+        }
+    }
+    format!(
+        "📉 <b>Диск скоро заполнится</b>\nДиск: {}\nЗаполнен: {:.1}%, прогноз заполнения через ~{eta}",
+        escape_html(&forecast.mount),
+        forecast.used_percent
+    )
+}
+
+fn format_temp_rise_alert(alert: &TempRiseAlert, units: &Units) -> String {
+    if alert.recovered {
+        return format!(
+            "✅ <b>Рост температуры прекратился: {}</b>\nТекущее значение: {}",
+            escape_html(&alert.sensor),
+            units.temperature(alert.temperature_celsius, 1)
+        );
+    }
+    let unit = units.temperature_unit();
+    format!(
+        "📈 <b>Температура быстро растёт: {}</b>\n{}{unit}/мин (порог {}{unit}/мин), сейчас {}",
+        escape_html(&alert.sensor),
+        units.number(units.temperature_delta_value(alert.rate_celsius_per_min), 1),
+        units.number(units.temperature_delta_value(alert.threshold), 1),
+        units.temperature(alert.temperature_celsius, 1)
+    )
+}
+
+fn format_disk_latency_alert(alert: &DiskLatencyAlert) -> String {
+    if alert.recovered {
+        return format!(
+            "✅ <b>Задержка диска {} в норме</b>\n{:.1} мс",
+            escape_html(&alert.device),
+            alert.await_ms
+        );
+    }
+    format!(
+        "🐢 <b>Диск {} отвечает медленно</b>\nСреднее время операции {:.1} мс (порог {:.1} мс)",
+        escape_html(&alert.device),
+        alert.await_ms,
+        alert.threshold_ms
+    )
+}
+
+fn format_dir_size_alert(alert: &DirSizeAlert, units: &Units) -> String {
+    if alert.recovered {
+        return format!(
+            "✅ <b>Каталог {} уменьшился</b>\n{}",
+            escape_html(&alert.path),
+            units.gigabytes(alert.size_bytes)
+        );
+    }
+    format!(
+        "📂 <b>Каталог {} разросся</b>\n{} (порог {})",
+        escape_html(&alert.path),
+        units.gigabytes(alert.size_bytes),
+        units.gigabytes(alert.threshold_bytes)
+    )
+}
+
+fn format_net_error_alert(alert: &NetErrorAlert) -> String {
+    if alert.recovered {
+        return format!(
+            "✅ <b>Ошибки на интерфейсе {} прекратились</b>",
+            escape_html(&alert.iface)
+        );
+    }
+    format!(
+        "📶 <b>Ошибки на интерфейсе {}</b>\n{:.1} ошибок/мин (порог {:.1})",
+        escape_html(&alert.iface),
+        alert.errors_per_min,
+        alert.threshold
+    )
+}
+
+fn format_wireguard_alert(alert: &WireguardAlert) -> String {
+    let peer = escape_html(wireguard_short_key(&alert.public_key));
+    if alert.recovered {
+        return format!(
+            "✅ <b>WireGuard {}: пир {} снова на связи</b>\nРукопожатие {} назад",
+            escape_html(&alert.iface),
+            peer,
+            human_uptime(0, alert.handshake_age_secs)
+        );
+    }
+    format!(
+        "🔐 <b>WireGuard {}: пир {} не отвечает</b>\n{}Последнее рукопожатие {} назад (порог {})",
+        escape_html(&alert.iface),
+        peer,
+        alert
+            .endpoint
+            .as_deref()
+            .map(|e| format!("Endpoint: {}\n", escape_html(e)))
+            .unwrap_or_default(),
+        human_uptime(0, alert.handshake_age_secs),
+        human_uptime(0, alert.threshold_secs as i64)
+    )
+}
+
+fn format_cpu_steal_alert(alert: &CpuStealAlert, units: &Units) -> String {
+    let hypervisor = alert
+        .virtualization
+        .as_deref()
+        .map(|v| format!(" ({})", escape_html(v)))
+        .unwrap_or_default();
+    if alert.recovered {
+        return format!(
+            "✅ <b>Steal CPU снизился</b>{hypervisor}\n{}%",
+            units.number(alert.steal_percent, 1)
+        );
+    }
+    format!(
+        "🕳 <b>Гипервизор отнимает CPU</b>{hypervisor}\nSteal: {}% (порог {}%)",
+        units.number(alert.steal_percent, 1),
+        units.number(alert.threshold, 1)
+    )
+}
+
+fn format_time_sync_alert(alert: &TimeSyncAlert) -> String {
+    let source = escape_html(&alert.source);
+    if alert.recovered {
+        return format!("✅ <b>Время синхронизировано</b> ({source})");
+    }
+    if !alert.synchronized {
+        return format!("🕰 <b>Часы не синхронизированы</b> ({source})");
+    }
+    format!(
+        "🕰 <b>Часы уходят</b> ({source})\nСмещение {:+.1} мс (допустимо {:.1} мс)",
+        alert.offset_seconds.unwrap_or_default() * 1000.0,
+        alert.max_offset_ms.unwrap_or_default()
+    )
+}
+
+fn format_pagefile_alert(alert: &PageFileAlert, units: &Units) -> String {
+    let usage = units.gigabytes_of(alert.pagefile_used_bytes, alert.pagefile_total_bytes);
+    if alert.recovered {
+        return format!(
+            "✅ <b>Файл подкачки освободился</b>\n{} ({:.0}%)",
+            usage, alert.usage_percent
+        );
+    }
+    format!(
+        "📄 <b>Файл подкачки заполнен</b>\n{} ({:.0}%, порог {:.0}%)",
+        usage, alert.usage_percent, alert.threshold
+    )
+}
+
+fn format_mount_change(change: &MountChange, units: &Units) -> String {
+    let title = if change.appeared {
+        "💽 <b>Подключён новый диск</b>"
+    } else {
+        "🚨 <b>Диск пропал</b>"
+    };
+    format!(
+        "{title}\nТочка монтирования: {}\nРазмер: {}",
+        escape_html(&change.mount),
+        units.gigabytes(change.total_bytes)
+    )
+}
+
+fn format_read_only_mount(mount: &ReadOnlyMount) -> String {
+    if mount.recovered {
+        return format!(
+            "✅ <b>Диск снова доступен для записи</b>\nТочка монтирования: {}",
+            escape_html(&mount.mount)
+        );
+    }
+    let reason = match &mount.write_error {
+        Some(err) => format!("Пробная запись: {}", escape_html(err)),
+        None => {
+            "Перемонтирован только для чтения — вероятно, после ошибок ввода-вывода".to_string()
+        }
+    };
+    format!(
+        "🚨 <b>Диск недоступен для записи</b>\nТочка монтирования: {}\n{reason}",
+        escape_html(&mount.mount)
+    )
+}
+
+fn format_host_reboot(reboot: &HostReboot, host: Option<&str>, units: &Units) -> String {
+    let host = host
+        .map(|h| format!(" {}", escape_html(h)))
+        .unwrap_or_default();
+    format!(
+        "🔄 <b>Хост{host} перезагружен</b>\nВремя загрузки: {}\nАптайм до перезагрузки: {}",
+        units.datetime(reboot.boot_time_unix),
+        human_uptime(0, reboot.previous_uptime_secs as i64)
+    )
+}
+
+// Строковые значения экранируются для ParseMode::Html; разметка в самом шаблоне остаётся как есть,
+// а уже готовый HTML можно вставить фильтром `safe`.
+fn render_alert_template(key: &str, template: &str, ctx: minijinja::Value) -> Option<String> {
+    let mut env = minijinja::Environment::new();
+    env.set_formatter(|out, state, value| match value.as_str() {
+        Some(text) if !value.is_safe() => out
+            .write_str(&escape_html(text))
+            .map_err(minijinja::Error::from),
+        _ => minijinja::escape_formatter(out, state, value),
+    });
+    match env.render_str(template, ctx) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            warn!(template = key, error = %err, "не удалось отрендерить шаблон уведомления");
+            None
         }
     }
-    sent
 }
 
-fn format_alert_event(event: &AlertEvent) -> String {
+fn format_alert_event(
+    event: &AlertEvent,
+    host: Option<&str>,
+    templates: &HashMap<String, String>,
+) -> String {
     let check_kind = match event.check_id.kind {
         CheckKind::Http => "HTTP",
         CheckKind::Tcp => "TCP",
+        CheckKind::Ssh => "SSH",
     };
+    // Повторы в Telegram не отправляются, поэтому отдельного шаблона у них нет.
+    let template_key = match event.kind {
+        AlertEventKind::Down | AlertEventKind::Repeat => "check_down",
+        AlertEventKind::Recovered => "check_recovered",
+    };
+    if let Some(template) = templates.get(template_key) {
+        let ctx = minijinja::context! {
+            check_kind => check_kind,
+            name => event.check_id.name.as_str(),
+            severity => event.severity.key(),
+            host => host,
+            upstream => event.upstream.as_deref(),
+            latency_ms => event.latency_ms,
+            status_code => event.status_code,
+            error => event.error.as_deref(),
+        };
+        if let Some(text) = render_alert_template(template_key, template, ctx) {
+            return text;
+        }
+    }
     let label = match event.kind {
         AlertEventKind::Down => "НЕДОСТУПЕН",
         AlertEventKind::Repeat => "НЕДОСТУПЕН (повтор)",
         AlertEventKind::Recovered => "ВОССТАНОВЛЕН",
     };
 
-    format!("{check_kind} '{}' - <b>{label}</b>", event.check_id.name)
+    let icon = match (&event.kind, event.severity) {
+        (AlertEventKind::Recovered, _) | (_, AlertSeverity::Critical) => "",
+        (_, AlertSeverity::Warning) => "⚠ ",
+    };
+    let upstream = event
+        .upstream
+        .as_ref()
+        .map(|parent| {
+            format!(
+                "\n↳ затронута вышестоящей проверкой '{}'",
+                escape_html(parent)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "{icon}{check_kind} '{}' - <b>{label}</b>{upstream}",
+        escape_html(&event.check_id.name)
+    )
 }
 
 pub fn should_handle_message(is_private: bool, chat_id: i64, allowed: &HashSet<i64>) -> bool {
@@ -732,7 +4298,7 @@ struct RateLimiter {
 }
 
 #[derive(Debug, Clone)]
-struct SpeedSample {
+pub struct SpeedSample {
     ts: i64,
     rx: u64,
     tx: u64,
@@ -763,15 +4329,21 @@ impl RateLimiter {
         queue.push_back(now_unix);
         true
     }
+
+    // Убирает чаты, у которых не осталось запросов за последнюю минуту.
+    fn prune(&mut self, now_unix: i64) {
+        self.timestamps_by_chat
+            .retain(|_, queue| queue.back().is_some_and(|ts| now_unix - ts < 60));
+    }
 }
 
-fn format_status(state: &State, cfg: &TelegramConfig) -> String {
+fn format_status(state: &Snapshot, cfg: &TelegramConfig, units: &Units) -> String {
     let uptime = human_uptime(state.started_at_unix, now_unix());
     let ram_pct = percent(
         state.memory_used_bytes as f64,
         state.memory_total_bytes as f64,
     );
-    let cpu_temp = format_cpu_temp(state);
+    let cpu_temp = format_cpu_temp(state, units);
     let (net_rx, net_tx) = network_speed_totals(state);
 
     let disks = state
@@ -780,10 +4352,9 @@ fn format_status(state: &State, cfg: &TelegramConfig) -> String {
         .take(2)
         .map(|d| {
             format!(
-                "• {}: {:.1}/{:.1} ГБ ({:.0}%)",
-                d.mount,
-                bytes_to_gb(d.used_bytes),
-                bytes_to_gb(d.total_bytes),
+                "• {}: {} ({:.0}%)",
+                escape_html(&d.mount),
+                units.gigabytes_of(d.used_bytes, d.total_bytes),
                 disk_used_pct(d)
             )
         })
@@ -797,15 +4368,15 @@ fn format_status(state: &State, cfg: &TelegramConfig) -> String {
         .map(|g| {
             format!(
                 "• {} | load {} | temp {} | mem {}",
-                g.name,
+                escape_html(&g.name),
                 g.utilization_percent
                     .map(|v| format!("{v:.0}%"))
                     .unwrap_or_else(|| "н/д".to_string()),
                 g.temperature_celsius
-                    .map(|v| format!("{v:.1}°C"))
+                    .map(|v| units.temperature(v, 1))
                     .unwrap_or_else(|| "н/д".to_string()),
                 match (g.memory_used_bytes, g.memory_total_bytes) {
-                    (Some(u), Some(t)) => format!("{:.1}/{:.1} ГБ", bytes_to_gb(u), bytes_to_gb(t)),
+                    (Some(u), Some(t)) => units.gigabytes_of(u, t),
                     _ => "н/д".to_string(),
                 }
             )
@@ -823,25 +4394,47 @@ fn format_status(state: &State, cfg: &TelegramConfig) -> String {
             }
         ),
         format!("⏱ Аптайм: {}", uptime),
-        format!("🧠 CPU: {:.1}% | 🌡 {}", state.cpu_usage_percent, cpu_temp),
         format!(
-            "💾 RAM: {:.1}/{:.1} ГБ ({:.0}%)",
-            bytes_to_gb(state.memory_used_bytes),
-            bytes_to_gb(state.memory_total_bytes),
+            "🧠 CPU: {}% | 🌡 {}{}",
+            units.number(state.cpu_usage_percent, 1),
+            cpu_temp,
+            state
+                .cpu_steal
+                .as_ref()
+                .filter(|_| state.virtualization.is_some())
+                .map(|s| format!(" | steal {}%", units.number(s.steal_percent, 1)))
+                .unwrap_or_default()
+        ),
+        format!(
+            "💾 RAM: {} ({:.0}%)",
+            units.gigabytes_of(state.memory_used_bytes, state.memory_total_bytes),
             ram_pct
         ),
         format!(
             "🌐 Сеть: ↓ {} / ↑ {}",
-            bytes_per_sec_human(net_rx),
-            bytes_per_sec_human(net_tx)
+            units.rate(net_rx as f64),
+            units.rate(net_tx as f64)
         ),
     ];
 
+    if let Some(p) = state
+        .page_file
+        .as_ref()
+        .filter(|p| p.pagefile_total_bytes > 0)
+    {
+        out.push(format!(
+            "📄 Подкачка: {} ({:.0}%) | commit {}",
+            units.gigabytes_of(p.pagefile_used_bytes, p.pagefile_total_bytes),
+            p.pagefile_usage_percent(),
+            units.gigabytes_of(p.commit_used_bytes, p.commit_limit_bytes)
+        ));
+    }
+
     if let Some(s) = state.internet_speed.as_ref() {
         out.push(format!(
-            "🚀 Интернет: ↓ {:.1} Mbps / ↑ {:.1} Mbps{}",
-            s.download_mbps,
-            s.upload_mbps,
+            "🚀 Интернет: ↓ {} / ↑ {}{}",
+            units.megabits(s.download_mbps),
+            units.megabits(s.upload_mbps),
             s.latency_ms
                 .map(|v| format!(" | ping {:.0} ms", v))
                 .unwrap_or_default()
@@ -858,98 +4451,222 @@ fn format_status(state: &State, cfg: &TelegramConfig) -> String {
         out.push(gpus);
     }
 
+    let threshold = cfg.alerts.collector_failures.threshold_intervals;
+    let degraded = state
+        .collectors
+        .iter()
+        .filter(|(_, health)| health.consecutive_failures >= threshold)
+        .map(|(name, health)| {
+            format!(
+                "• {}: {} тактов без данных{}",
+                escape_html(name),
+                health.consecutive_failures,
+                health
+                    .last_error
+                    .as_deref()
+                    .map(|e| format!(" ({})", escape_html(e)))
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    if !degraded.is_empty() {
+        out.push("\n⚠ Деградировавшие сборщики:".to_string());
+        out.push(degraded.join("\n"));
+    }
+
     out.push(format!(
         "\n🕒 {}",
-        format_last_collect_line(state.last_collect_timestamp_seconds)
+        format_last_collect_line(state.last_collect_timestamp_seconds, units)
     ));
     if let Some(base) = cfg.public_base_url.as_ref() {
         out.push(format!(
             "🔗 /metrics: {}/metrics",
-            base.trim_end_matches('/')
+            escape_html(base.trim_end_matches('/'))
         ));
     }
 
     out.join("\n")
 }
 
-fn format_system(state: &State) -> String {
+fn format_system(state: &Snapshot, units: &Units) -> String {
     let ram_pct = percent(
         state.memory_used_bytes as f64,
         state.memory_total_bytes as f64,
     );
     format!(
-        "🖥 <b>Система</b>\n\nХост: {}\nОС: {} {}\nЯдро: {}\nCPU: {}\nЯдер: {}\nПроцессов: {}\nCPU temp: {}\nRAM: {:.1}/{:.1} ГБ ({:.0}%)\n\n🕒 {}",
-        state.host_name.clone().unwrap_or_else(|| "н/д".to_string()),
-        state.os_name.clone().unwrap_or_else(|| "н/д".to_string()),
-        state.os_version.clone().unwrap_or_default(),
-        state.kernel_version.clone().unwrap_or_else(|| "н/д".to_string()),
-        state.cpu_brand.clone().unwrap_or_else(|| "н/д".to_string()),
+        "🖥 <b>Система</b>\n\nХост: {}\nОС: {} {}\nЯдро: {}\nCPU: {}\nЯдер: {}\nПроцессов: {}\nCPU temp: {}\nRAM: {} ({:.0}%){}{}{}\n\n🕒 {}",
+        escape_html(state.host_name.as_deref().unwrap_or("н/д")),
+        escape_html(state.os_name.as_deref().unwrap_or("н/д")),
+        escape_html(state.os_version.as_deref().unwrap_or_default()),
+        escape_html(state.kernel_version.as_deref().unwrap_or("н/д")),
+        escape_html(state.cpu_brand.as_deref().unwrap_or("н/д")),
         state.cpu_core_count,
         state.process_count,
-        format_cpu_temp(state),
-        bytes_to_gb(state.memory_used_bytes),
-        bytes_to_gb(state.memory_total_bytes),
+        format_cpu_temp(state, units),
+        units.gigabytes_of(state.memory_used_bytes, state.memory_total_bytes),
         ram_pct,
-        format_last_collect_line(state.last_collect_timestamp_seconds),
+        format_virtualization(state, units),
+        format_time_sync(state),
+        format_container(state, units),
+        format_last_collect_line(state.last_collect_timestamp_seconds, units),
+    )
+}
+
+// Строки про виртуализацию показываются только внутри ВМ: на железе steal всегда нулевой.
+fn format_virtualization(state: &Snapshot, units: &Units) -> String {
+    let Some(hypervisor) = state.virtualization.as_deref() else {
+        return String::new();
+    };
+    format!(
+        "\nВиртуализация: {}\nSteal: {}",
+        escape_html(hypervisor),
+        state
+            .cpu_steal
+            .as_ref()
+            .map(|s| format!("{}%", units.number(s.steal_percent, 1)))
+            .unwrap_or_else(|| "н/д".to_string())
+    )
+}
+
+fn format_container(state: &Snapshot, units: &Units) -> String {
+    let Some(cg) = state.cgroup.as_ref() else {
+        return String::new();
+    };
+    let mut limits = Vec::new();
+    if let Some(cores) = cg.cpu_limit_cores {
+        limits.push(format!("CPU {} ядер", units.number(cores, 2)));
+    }
+    if let Some(limit) = cg.memory_limit_bytes {
+        limits.push(format!("RAM {}", units.gigabytes(limit)));
+    }
+    format!(
+        "\nКонтейнер: {} (cgroup v{}), лимиты: {}",
+        escape_html(cg.runtime.as_deref().unwrap_or("н/д")),
+        cg.version,
+        if limits.is_empty() {
+            "нет".to_string()
+        } else {
+            limits.join(", ")
+        }
+    )
+}
+
+fn format_time_sync(state: &Snapshot) -> String {
+    let Some(sync) = state.time_sync.as_ref() else {
+        return String::new();
+    };
+    let details = [
+        Some(escape_html(&sync.source)),
+        sync.server.as_deref().map(escape_html),
+        sync.offset_seconds
+            .map(|o| format!("смещение {:+.1} мс", o * 1000.0)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(", ");
+    format!(
+        "\nВремя: {} ({details})",
+        if sync.synchronized {
+            "синхронизировано"
+        } else {
+            "⚠️ не синхронизировано"
+        }
     )
 }
 
-fn format_sensors(state: &State) -> String {
+fn format_sensors(state: &Snapshot, page: usize, units: &Units) -> (String, Pager) {
+    const PER_PAGE: usize = 15;
     if state.sensors.is_empty() {
-        return "📟 <b>Сенсоры</b>\n\nНет данных.".to_string();
+        return (
+            "📟 <b>Сенсоры</b>\n\nНет данных.".to_string(),
+            Pager::new(0, PER_PAGE, 0),
+        );
     }
     let mut grouped: HashMap<&str, usize> = HashMap::new();
-    for s in &state.sensors {
+    for s in state.sensors.iter() {
         *grouped.entry(s.sensor_type.as_str()).or_insert(0) += 1;
     }
     let mut rows = grouped.into_iter().collect::<Vec<_>>();
-    rows.sort_by(|a, b| b.1.cmp(&a.1));
+    rows.sort_by_key(|r| std::cmp::Reverse(r.1));
     let summary = rows
         .iter()
-        .take(10)
         .map(|(t, c)| format!("• {}: {}", sensor_type_ru(t), c))
         .collect::<Vec<_>>()
         .join("\n");
 
-    format!(
-        "📟 <b>Сенсоры</b>\n\nВсего: {}\n\n{}\n\n🕒 {}",
-        state.sensors.len(),
-        summary,
-        format_last_collect_line(state.last_collect_timestamp_seconds),
+    let mut sensors = state.sensors.iter().collect::<Vec<_>>();
+    sensors.sort_by(|a, b| {
+        a.sensor_type
+            .cmp(&b.sensor_type)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    let pager = Pager::new(sensors.len(), PER_PAGE, page);
+    let list = pager
+        .slice(&sensors, PER_PAGE)
+        .iter()
+        .map(|s| {
+            let value = if s.sensor_type.eq_ignore_ascii_case("temperature") {
+                units.temperature(s.value, 2)
+            } else {
+                units.number(s.value, 2)
+            };
+            format!(
+                "• {} / {}: {value}",
+                sensor_type_ru(&s.sensor_type),
+                escape_html(&s.name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (
+        format!(
+            "📟 <b>Сенсоры</b>\n\nВсего: {}\n\n{}\n\n{}{}\n\n🕒 {}",
+            state.sensors.len(),
+            summary,
+            list,
+            pager.footer(),
+            format_last_collect_line(state.last_collect_timestamp_seconds, units),
+        ),
+        pager,
     )
 }
 
-fn format_network(state: &State) -> String {
-    let mut ifaces = state.net.clone();
+fn format_network(state: &Snapshot, page: usize, units: &Units) -> (String, Pager) {
+    const PER_PAGE: usize = 8;
+    let mut ifaces = state.net.to_vec();
     ifaces.sort_by(|a, b| {
         let a_total = a.rx_bytes_per_sec.saturating_add(a.tx_bytes_per_sec);
         let b_total = b.rx_bytes_per_sec.saturating_add(b.tx_bytes_per_sec);
         b_total.cmp(&a_total)
     });
 
-    let lines = ifaces
+    let pager = Pager::new(ifaces.len(), PER_PAGE, page);
+    let lines = pager
+        .slice(&ifaces, PER_PAGE)
         .iter()
-        .take(8)
         .map(|n| {
             format!(
                 "• {}: ↓ {} / ↑ {}",
-                n.iface,
-                bytes_per_sec_human(n.rx_bytes_per_sec),
-                bytes_per_sec_human(n.tx_bytes_per_sec)
+                escape_html(&n.iface),
+                units.rate(n.rx_bytes_per_sec as f64),
+                units.rate(n.tx_bytes_per_sec as f64)
             )
         })
         .collect::<Vec<_>>()
         .join("\n");
     let (rx, tx) = network_speed_totals(state);
+    let wireguard = format_wireguard_peers(state, units);
 
     let internet_line = state
         .internet_speed
         .as_ref()
         .map(|s| {
             format!(
-                "🚀 Интернет: ↓ {:.1} Mbps / ↑ {:.1} Mbps{}",
-                s.download_mbps,
-                s.upload_mbps,
+                "🚀 Интернет: ↓ {} / ↑ {}{}",
+                units.megabits(s.download_mbps),
+                units.megabits(s.upload_mbps),
                 s.latency_ms
                     .map(|v| format!(" | ping {:.0} ms", v))
                     .unwrap_or_default()
@@ -957,21 +4674,55 @@ fn format_network(state: &State) -> String {
         })
         .unwrap_or_else(|| "🚀 Интернет speedtest: н/д".to_string());
 
-    format!(
-        "🌐 <b>Сеть</b>\n\nИтого: ↓ {} / ↑ {}\n{}\n\n{}\n\n🕒 {}",
-        bytes_per_sec_human(rx),
-        bytes_per_sec_human(tx),
+    let text = format!(
+        "🌐 <b>Сеть</b>\n\nИтого: ↓ {} / ↑ {}\n{}\n\n{}{}{}\n\n🕒 {}",
+        units.rate(rx as f64),
+        units.rate(tx as f64),
         internet_line,
         if lines.is_empty() {
             "н/д".to_string()
         } else {
             lines
         },
-        format_last_collect_line(state.last_collect_timestamp_seconds),
-    )
+        pager.footer(),
+        wireguard,
+        format_last_collect_line(state.last_collect_timestamp_seconds, units),
+    );
+    (text, pager)
+}
+
+// Пиры WireGuard выводятся целиком под списком интерфейсов, без постраничной разбивки.
+fn format_wireguard_peers(state: &Snapshot, units: &Units) -> String {
+    if state.wireguard.is_empty() {
+        return String::new();
+    }
+    let lines = state
+        .wireguard
+        .iter()
+        .map(|p| {
+            let handshake = p
+                .handshake_age_secs(state.last_collect_timestamp_seconds)
+                .map(|age| format!("{} назад", human_uptime(0, age)))
+                .unwrap_or_else(|| "не было".to_string());
+            format!(
+                "• {} {}{}: рукопожатие {}, ↓ {} / ↑ {}",
+                escape_html(&p.iface),
+                escape_html(p.short_key()),
+                p.endpoint
+                    .as_deref()
+                    .map(|e| format!(" ({})", escape_html(e)))
+                    .unwrap_or_default(),
+                handshake,
+                units.gigabytes(p.rx_bytes_total),
+                units.gigabytes(p.tx_bytes_total)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("\n\n🔐 <b>WireGuard</b>\n{lines}")
 }
 
-fn format_speedtest(state: &State, history: &VecDeque<SpeedSample>) -> String {
+fn format_speedtest(state: &Snapshot, history: &VecDeque<SpeedSample>, units: &Units) -> String {
     let now = now_unix();
     let cutoff = now.saturating_sub(60);
     let mut points: Vec<&SpeedSample> = history.iter().filter(|x| x.ts >= cutoff).collect();
@@ -1009,9 +4760,9 @@ fn format_speedtest(state: &State, history: &VecDeque<SpeedSample>) -> String {
         .as_ref()
         .map(|s| {
             format!(
-                "Измерено: ↓ {:.1} Mbps / ↑ {:.1} Mbps{}",
-                s.download_mbps,
-                s.upload_mbps,
+                "Измерено: ↓ {} / ↑ {}{}",
+                units.megabits(s.download_mbps),
+                units.megabits(s.upload_mbps),
                 s.latency_ms
                     .map(|v| format!(" | ping {:.0} ms", v))
                     .unwrap_or_default()
@@ -1020,52 +4771,154 @@ fn format_speedtest(state: &State, history: &VecDeque<SpeedSample>) -> String {
         .unwrap_or_else(|| "Измерено: н/д".to_string());
 
     format!(
-        "🚀 <b>Speedtest</b>\n\n{}\nТекущая: ↓ {} / ↑ {}\nСредняя (1 мин): ↓ {} / ↑ {}\nПик (1 мин): ↓ {} / ↑ {}\nПик суммарно: {}\n\n🕒 {}",
+        "🚀 <b>Speedtest</b>\n\n{}\nТекущая: ↓ {} / ↑ {}\nСредняя (1 мин): ↓ {} / ↑ {}\nПик (1 мин): ↓ {} / ↑ {}\nПик суммарно: {}{}\n\n🕒 {}",
         measured,
-        bytes_per_sec_human(cur_rx),
-        bytes_per_sec_human(cur_tx),
-        bytes_per_sec_human(avg_rx.round() as u64),
-        bytes_per_sec_human(avg_tx.round() as u64),
-        bytes_per_sec_human(peak_rx),
-        bytes_per_sec_human(peak_tx),
-        bytes_per_sec_human(peak_total),
-        format_last_collect_line(state.last_collect_timestamp_seconds),
+        units.rate(cur_rx as f64),
+        units.rate(cur_tx as f64),
+        units.rate(avg_rx.round()),
+        units.rate(avg_tx.round()),
+        units.rate(peak_rx as f64),
+        units.rate(peak_tx as f64),
+        units.rate(peak_total as f64),
+        format_speedtest_stats(&state.speedtest_stats, units),
+        format_last_collect_line(state.last_collect_timestamp_seconds, units),
     )
 }
 
-fn format_disks(state: &State) -> String {
-    let mut disks = state.disks.clone();
+fn format_speedtest_stats(stats: &[SpeedtestStats], units: &Units) -> String {
+    let range = |r: &SpeedRange| {
+        format!(
+            "{} / {} / {}",
+            units.megabits(r.min),
+            units.megabits(r.avg),
+            units.megabits(r.max)
+        )
+    };
+    let mut out = String::new();
+    for window in stats {
+        let title = match window.window_hours {
+            24 => "24 ч".to_string(),
+            hours if hours % 24 == 0 => format!("{} дн", hours / 24),
+            hours => format!("{hours} ч"),
+        };
+        out.push_str(&format!(
+            "\n\n📈 <b>За {title}</b> ({} замеров, мин / сред / макс)\n↓ {}\n↑ {}",
+            window.samples,
+            range(&window.download_mbps),
+            range(&window.upload_mbps)
+        ));
+        if let Some(latency) = &window.latency_ms {
+            out.push_str(&format!(
+                "\nping {:.0} / {:.0} / {:.0} ms",
+                latency.min, latency.avg, latency.max
+            ));
+        }
+    }
+    out
+}
+
+fn format_disks(state: &Snapshot, page: usize, units: &Units) -> (String, Pager) {
+    const PER_PAGE: usize = 10;
+    let mut disks = state.disks.to_vec();
     disks.sort_by(|a, b| disk_used_pct(b).total_cmp(&disk_used_pct(a)));
-    let lines = disks
+    let pager = Pager::new(disks.len(), PER_PAGE, page);
+    let lines = pager
+        .slice(&disks, PER_PAGE)
         .iter()
         .map(|d| {
+            let temp = d
+                .temperature_celsius
+                .map(|t| format!(", {}", units.temperature(t, 0)))
+                .unwrap_or_default();
             format!(
-                "• {}: {:.1}/{:.1} ГБ ({:.0}%)",
-                d.mount,
-                bytes_to_gb(d.used_bytes),
-                bytes_to_gb(d.total_bytes),
-                disk_used_pct(d)
+                "• {}: {} ({:.0}%){}{}",
+                escape_html(&d.mount),
+                units.gigabytes_of(d.used_bytes, d.total_bytes),
+                disk_used_pct(d),
+                temp,
+                if d.writable() { "" } else { " 🔒" }
             )
         })
         .collect::<Vec<_>>()
         .join("\n");
 
-    format!(
-        "💽 <b>Диски</b>\n\n{}\n\n🕒 {}",
+    let text = format!(
+        "💽 <b>Диски</b>\n\n{}{}{}\n\n🕒 {}",
         if lines.is_empty() {
             "н/д".to_string()
         } else {
             lines
         },
-        format_last_collect_line(state.last_collect_timestamp_seconds),
-    )
+        pager.footer(),
+        format_disk_io(state),
+        format_last_collect_line(state.last_collect_timestamp_seconds, units),
+    );
+    (text, pager)
+}
+
+fn format_dir_sizes(state: &Snapshot, units: &Units) -> String {
+    if state.dir_sizes.is_empty() {
+        return "📂 <b>Каталоги</b>\n\nНет данных: задайте dir_sizes.paths в конфигурации."
+            .to_string();
+    }
+    let blocks = state
+        .dir_sizes
+        .iter()
+        .map(|d| {
+            let header = format!("<b>{}</b>", escape_html(&d.path));
+            if let Some(err) = &d.error {
+                return format!("{header}\n⚠️ {}", escape_html(err));
+            }
+            let mut lines = vec![format!(
+                "{header}: {} (обход {} назад)",
+                units.gigabytes(d.size_bytes),
+                human_uptime(d.scanned_at_unix, now_unix())
+            )];
+            lines.extend(d.top.iter().map(|e| {
+                format!(
+                    "• {}: {} ({:.0}%)",
+                    escape_html(&e.name),
+                    units.gigabytes(e.size_bytes),
+                    percent(e.size_bytes as f64, d.size_bytes as f64)
+                )
+            }));
+            if d.skipped_entries > 0 {
+                lines.push(format!("Недоступно записей: {}", d.skipped_entries));
+            }
+            lines.join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!("📂 <b>Каталоги</b>\n\n{blocks}")
+}
+
+fn format_disk_io(state: &Snapshot) -> String {
+    if state.disk_io.is_empty() {
+        return String::new();
+    }
+    let lines = state
+        .disk_io
+        .iter()
+        .map(|d| {
+            format!(
+                "• {}: {:.0} оп/с, await {:.1} мс (чтение {:.1} / запись {:.1})",
+                escape_html(&d.device),
+                d.ops_per_sec,
+                d.await_ms,
+                d.read_await_ms,
+                d.write_await_ms
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("\n\n⏱ <b>Задержка ввода-вывода</b>\n{lines}")
 }
 
-fn format_gpu_details(state: &State) -> String {
+fn format_gpu_details(state: &Snapshot, units: &Units) -> String {
     if state.gpus.is_empty() {
         return format!(
             "🎮 <b>GPU</b>\n\nНет данных\n\n🕒 {}",
-            format_last_collect_line(state.last_collect_timestamp_seconds)
+            format_last_collect_line(state.last_collect_timestamp_seconds, units)
         );
     }
 
@@ -1075,22 +4928,23 @@ fn format_gpu_details(state: &State) -> String {
         .map(|g| {
             let util = g
                 .utilization_percent
-                .map(|v| format!("{v:.1}%"))
+                .map(|v| format!("{}%", units.number(v, 1)))
                 .unwrap_or_else(|| "н/д".to_string());
             let temp = g
                 .temperature_celsius
-                .map(|v| format!("{v:.1}°C"))
+                .map(|v| units.temperature(v, 1))
                 .unwrap_or_else(|| "н/д".to_string());
             let mem = match (g.memory_used_bytes, g.memory_total_bytes) {
-                (Some(used), Some(total)) => {
-                    format!("{:.1}/{:.1} ГБ", bytes_to_gb(used), bytes_to_gb(total))
-                }
-                (Some(used), None) => format!("{:.1} ГБ", bytes_to_gb(used)),
+                (Some(used), Some(total)) => units.gigabytes_of(used, total),
+                (Some(used), None) => units.gigabytes(used),
                 _ => "н/д".to_string(),
             };
             format!(
                 "• {}\n  load {} | temp {} | mem {}",
-                g.name, util, temp, mem
+                escape_html(&g.name),
+                util,
+                temp,
+                mem
             )
         })
         .collect::<Vec<_>>()
@@ -1099,10 +4953,111 @@ fn format_gpu_details(state: &State) -> String {
     format!(
         "🎮 <b>GPU</b>\n\n{}\n\n🕒 {}",
         rows,
-        format_last_collect_line(state.last_collect_timestamp_seconds)
+        format_last_collect_line(state.last_collect_timestamp_seconds, units)
+    )
+}
+
+// Для выбранного удалённого хоста истории нет: спарклайны строятся только по локальным проверкам.
+async fn check_sparklines(runtime: &TelegramRuntime, chat_id: i64) -> HashMap<String, String> {
+    if runtime.selected_hosts.lock().await.contains_key(&chat_id) {
+        return HashMap::new();
+    }
+    let since = now_unix() - SPARKLINE_WINDOW_SECS;
+    let state = runtime.shared_state.read().await;
+    state
+        .metric_history
+        .iter()
+        .filter(|((metric, _), _)| *metric == "check_latency_ms")
+        .filter_map(|((_, name), samples)| {
+            let values = samples
+                .iter()
+                .filter(|s| s.ts_unix >= since)
+                .map(|s| s.value)
+                .collect::<Vec<_>>();
+            Some((name.clone(), sparkline(&values, SPARKLINE_WIDTH)?))
+        })
+        .collect()
+}
+
+// Значения усредняются по width равным отрезкам и масштабируются от минимума до максимума.
+fn sparkline(values: &[f64], width: usize) -> Option<String> {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    if values.len() < 2 || width == 0 {
+        return None;
+    }
+    let width = width.min(values.len());
+    let points = (0..width)
+        .map(|i| {
+            let chunk = &values[i * values.len() / width..(i + 1) * values.len() / width];
+            chunk.iter().sum::<f64>() / chunk.len() as f64
+        })
+        .collect::<Vec<_>>();
+    let min = points.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = points.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+    Some(
+        points
+            .iter()
+            .map(|v| {
+                if span <= 0.0 {
+                    BARS[0]
+                } else {
+                    BARS[(((v - min) / span) * (BARS.len() - 1) as f64).round() as usize]
+                }
+            })
+            .collect(),
     )
 }
 
+fn format_checks(
+    state: &Snapshot,
+    page: usize,
+    sparklines: &HashMap<String, String>,
+    units: &Units,
+) -> (String, Pager) {
+    const PER_PAGE: usize = 10;
+    let runs = state.check_runs();
+    let pager = Pager::new(runs.len(), PER_PAGE, page);
+    let lines = pager
+        .slice(&runs, PER_PAGE)
+        .iter()
+        .map(|&(kind, name, up, latency_ms)| {
+            let percentiles = state
+                .check_latency_for(kind, name)
+                .map(|l| {
+                    format!(
+                        "\n  p50 {} / p95 {} / p99 {} ms ({} замеров)",
+                        l.p50_ms, l.p95_ms, l.p99_ms, l.samples
+                    )
+                })
+                .unwrap_or_default();
+            let trend = sparklines
+                .get(name)
+                .map(|line| format!(" {line}"))
+                .unwrap_or_default();
+            format!(
+                "{} {} <b>{}</b>: {latency_ms} ms{trend}{percentiles}",
+                if up { "🟢" } else { "🔴" },
+                kind.to_uppercase(),
+                escape_html(name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let text = format!(
+        "🩺 <b>Проверки</b>\n\n{}{}\n\n🕒 {}",
+        if lines.is_empty() {
+            "Проверки не настроены.".to_string()
+        } else {
+            lines
+        },
+        pager.footer(),
+        format_last_collect_line(state.last_collect_timestamp_seconds, units),
+    );
+    (text, pager)
+}
+
 fn sensor_type_ru(t: &str) -> &'static str {
     match t.to_ascii_lowercase().as_str() {
         "temperature" => "Температура",
@@ -1119,45 +5074,11 @@ fn sensor_type_ru(t: &str) -> &'static str {
     }
 }
 
-fn format_cpu_temp(state: &State) -> String {
-    cpu_temperature_from_state(state)
-        .map(|v| format!("{:.1}°C", v))
-        .unwrap_or_else(|| "н/д".to_string())
-}
-
-fn cpu_temperature_from_state(state: &State) -> Option<f64> {
-    let preferred = [
-        "cpu",
-        "package",
-        "tctl",
-        "tdie",
-        "coretemp",
-        "k10temp",
-        "thermal zone",
-        "_tz",
-    ];
-    let mut picked: Option<f64> = None;
-
-    for t in &state.temps {
-        let sensor = t.sensor.to_lowercase();
-        if preferred.iter().any(|k| sensor.contains(k)) {
-            picked = Some(picked.map_or(t.temperature_celsius, |p| p.max(t.temperature_celsius)));
-        }
-    }
-
-    if picked.is_some() {
-        return picked;
-    }
-
+fn format_cpu_temp(state: &Snapshot, units: &Units) -> String {
     state
-        .temps
-        .iter()
-        .filter(|t| {
-            let sensor = t.sensor.to_lowercase();
-            !sensor.contains("gpu") && !sensor.contains("nvidia") && !sensor.contains("amdgpu")
-        })
-        .map(|t| t.temperature_celsius)
-        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .cpu_temperature_celsius
+        .map(|v| units.temperature(v, 1))
+        .unwrap_or_else(|| "н/д".to_string())
 }
 
 fn disk_used_pct(d: &crate::state::DiskStat) -> f64 {
@@ -1172,11 +5093,7 @@ fn percent(used: f64, total: f64) -> f64 {
     }
 }
 
-fn bytes_to_gb(bytes: u64) -> f64 {
-    (bytes as f64) / 1024.0 / 1024.0 / 1024.0
-}
-
-fn network_speed_totals(state: &State) -> (u64, u64) {
+fn network_speed_totals(state: &Snapshot) -> (u64, u64) {
     state.net.iter().fold((0_u64, 0_u64), |acc, n| {
         (
             acc.0.saturating_add(n.rx_bytes_per_sec),
@@ -1185,29 +5102,7 @@ fn network_speed_totals(state: &State) -> (u64, u64) {
     })
 }
 
-fn bytes_per_sec_human(v: u64) -> String {
-    const KB: f64 = 1024.0;
-    const MB: f64 = 1024.0 * 1024.0;
-    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
-
-    let vf = v as f64;
-    if vf >= GB {
-        format!("{:.2} GB/s", vf / GB)
-    } else if vf >= MB {
-        format!("{:.2} MB/s", vf / MB)
-    } else if vf >= KB {
-        format!("{:.2} KB/s", vf / KB)
-    } else {
-        format!("{} B/s", v)
-    }
-}
-
-fn format_unix(ts: i64) -> String {
-    let st = UNIX_EPOCH + Duration::from_secs(ts.max(0) as u64);
-    humantime::format_rfc3339_seconds(st).to_string()
-}
-
-fn format_last_collect_line(last_collect_ts: i64) -> String {
+fn format_last_collect_line(last_collect_ts: i64, units: &Units) -> String {
     if last_collect_ts <= 0 {
         return "Последнее обновление: н/д".to_string();
     }
@@ -1224,7 +5119,7 @@ fn format_last_collect_line(last_collect_ts: i64) -> String {
 
     format!(
         "Последнее обновление: {} ({})",
-        format_unix(last_collect_ts),
+        units.datetime(last_collect_ts),
         relative
     )
 }
@@ -1266,6 +5161,427 @@ mod tests {
         assert!(should_handle_message(true, 100, &allowed));
     }
 
+    #[test]
+    fn alert_batch_waits_for_debounce_window() {
+        let mut batch = AlertBatch::default();
+        batch.push_checks(1, &["down".to_string()], 100);
+        batch.push_resources(1, &["cpu".to_string()], 110);
+        batch.push_resources(2, &["ram".to_string()], 120);
+
+        assert!(batch.take_due(125, 30).is_empty());
+        let due = batch.take_due(130, 30);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, 1);
+        assert_eq!(
+            format_alert_batch(&due[0].1),
+            ["<b>Уведомления по проверкам</b>\ndown\n\n<b>Ресурсные уведомления</b>\ncpu"]
+        );
+        assert_eq!(batch.take_due(120, 0).len(), 1);
+    }
+
+    #[test]
+    fn alert_batch_overflow_is_sent_as_follow_up_messages() {
+        let mut batch = AlertBatch::default();
+        let lines = (0..300)
+            .map(|idx| format!("🔴 <b>Проверка</b> check-{idx:03} недоступна: таймаут"))
+            .collect::<Vec<_>>();
+        batch.push_checks(1, &lines, 100);
+        batch.push_resources(1, &["cpu".to_string()], 100);
+        let due = batch.take_due(200, 0);
+        let parts = format_alert_batch(&due[0].1);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.len() <= MESSAGE_TEXT_LIMIT));
+        for line in lines.iter().chain(["cpu".to_string()].iter()) {
+            assert!(
+                parts.iter().any(|part| part.lines().any(|l| l == line)),
+                "строка потеряна: {line}"
+            );
+        }
+    }
+
+    #[test]
+    fn alert_links_are_rendered_and_attached_to_touched_chats() {
+        let link = |url: &str, kinds: &[&str]| AlertLinkConfig {
+            label: "Открыть график".to_string(),
+            url: url.to_string(),
+            range_secs: 3600,
+            filter: crate::config::NotifierFilter {
+                kinds: kinds.iter().map(|k| k.to_string()).collect(),
+                subjects: Vec::new(),
+            },
+        };
+        let notification = |kind, subject: &str| Notification {
+            kind,
+            subject: subject.to_string(),
+            severity: AlertSeverity::Critical,
+            recovered: false,
+            message: String::new(),
+        };
+        let links = alert_links(
+            &[
+                link(
+                    "https://grafana.local/d/host?var-host={{ host }}&var-check={{ subject }}&from={{ from }}&to={{ to }}",
+                    &["check"],
+                ),
+                link("https://status.local/{{ kind }}", &[]),
+            ],
+            &[
+                notification("check", "api & db"),
+                notification("resource", "cpu"),
+                notification("check", "api & db"),
+            ],
+            Some("web 1"),
+            7200,
+        );
+        let urls: Vec<_> = links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://grafana.local/d/host?var-host=web%201&var-check=api%20%26%20db&from=3600000&to=7200000",
+                "https://status.local/check",
+                "https://status.local/resource",
+            ]
+        );
+
+        let mut batch = AlertBatch::default();
+        batch.push_checks(1, &["down".to_string()], 100);
+        batch.attach_links(&links);
+        batch.push_resources(2, &["cpu".to_string()], 110);
+        batch.attach_links(&[]);
+        let due = batch.take_due(200, 0);
+        assert_eq!(due[0].1.links, links);
+        assert!(due[1].1.links.is_empty());
+        let keyboard = alert_keyboard(&due[0].1.links, &due[0].1.snooze_kinds);
+        assert_eq!(keyboard.inline_keyboard[0][0].text, "Открыть график");
+        assert_eq!(
+            keyboard.inline_keyboard.len(),
+            3 + main_menu().inline_keyboard.len()
+        );
+    }
+
+    #[test]
+    fn snooze_buttons_follow_resource_alerts() {
+        assert!(matches!(
+            Action::from_callback("snooze_cpu_load_6"),
+            Some(Action::Snooze(ResourceAlertKind::CpuLoad, 6))
+        ));
+        assert!(matches!(
+            Action::from_callback("snooze_clear"),
+            Some(Action::ClearSnoozes)
+        ));
+        assert!(Action::from_callback("snooze_cpu_load_5").is_none());
+        assert!(Action::from_callback("snooze_fan_1").is_none());
+
+        let mut batch = AlertBatch::default();
+        batch.push_resources(1, &["cpu".to_string()], 100);
+        batch.push_snooze_kinds(1, &[ResourceAlertKind::CpuLoad]);
+        batch.push_snooze_kinds(1, &[ResourceAlertKind::CpuLoad]);
+        // Чату без строк в пачке кнопки не нужны.
+        batch.push_snooze_kinds(2, &[ResourceAlertKind::RamUsage]);
+        let due = batch.take_due(200, 0);
+        assert_eq!(due.len(), 1);
+        let keyboard = alert_keyboard(&due[0].1.links, &due[0].1.snooze_kinds);
+        let row: Vec<_> = keyboard.inline_keyboard[0]
+            .iter()
+            .map(|b| b.text.as_str())
+            .collect();
+        assert_eq!(row, ["💤 CPU нагрузка 1ч", "6ч", "24ч"]);
+        assert_eq!(
+            keyboard.inline_keyboard.len(),
+            1 + main_menu().inline_keyboard.len()
+        );
+
+        let mut state = State::new(0);
+        state.snooze_resource_alert(1, ResourceAlertKind::CpuLoad, 3_700, 100);
+        assert!(state.resource_alert_snoozed(1, ResourceAlertKind::CpuLoad, 3_600));
+        assert!(!state.resource_alert_snoozed(1, ResourceAlertKind::RamUsage, 3_600));
+        assert!(!state.resource_alert_snoozed(1, ResourceAlertKind::CpuLoad, 3_700));
+        // Отсрочка не выключает вид: после неё уведомления приходят как раньше.
+        assert!(state.resource_alert_enabled_for_chat(1, ResourceAlertKind::CpuLoad));
+        state.snooze_resource_alert(1, ResourceAlertKind::RamUsage, 9_000, 5_000);
+        assert_eq!(
+            state.resource_snoozes_for_chat(1, 5_000),
+            vec![(ResourceAlertKind::RamUsage, 9_000)]
+        );
+    }
+
+    #[test]
+    fn host_selection_callbacks_and_menu() {
+        assert!(matches!(
+            Action::from_callback("host_local"),
+            Some(Action::SelectHost(None))
+        ));
+        assert!(matches!(
+            Action::from_callback("host_1"),
+            Some(Action::SelectHost(Some(1)))
+        ));
+        assert!(matches!(
+            Action::from_callback("hosts"),
+            Some(Action::Hosts)
+        ));
+        assert!(Action::from_callback("host_x").is_none());
+
+        let host = |name: &str, up| HostState {
+            name: name.to_string(),
+            source: crate::aggregator::HostSource::Pull,
+            url: Some(format!("http://{name}:9108")),
+            up,
+            last_success_unix: None,
+            error: None,
+            state: None,
+        };
+        let menu = hosts_menu(&[host("db", true), host("web", false)], Some("web"));
+        let labels: Vec<_> = menu
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .map(|b| b.text.as_str())
+            .collect();
+        assert_eq!(labels, ["Локальный", "🟢 db", "✅ 🔴 web", "⬅ Назад"]);
+    }
+
+    #[test]
+    fn remote_actions_are_keyed_by_name_and_need_explicit_chats() {
+        let action = |name: &str, chats: Vec<i64>| crate::config::RemoteActionConfig {
+            name: name.to_string(),
+            command: "true".to_string(),
+            args: Vec::new(),
+            allowed_chat_ids: chats,
+            timeout_secs: 5,
+        };
+        let mut cfg = TelegramConfig {
+            actions: vec![action("reboot", Vec::new()), action("backup", vec![7])],
+            ..TelegramConfig::default()
+        };
+
+        let view = remote_actions_view(&cfg, 7, None);
+        let callbacks: Vec<_> = view
+            .keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .filter_map(|b| match &b.kind {
+                teloxide::types::InlineKeyboardButtonKind::CallbackData(data) => {
+                    Some(data.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(callbacks, ["act_ask_backup", "dashboard"]);
+        assert!(allowed_remote_action(&cfg, 7, "backup").is_some());
+        assert!(allowed_remote_action(&cfg, 7, "reboot").is_none());
+        assert!(allowed_remote_action(&cfg, 8, "backup").is_none());
+
+        // После перезагрузки конфигурации кнопка с прежним именем не запускает чужую команду.
+        cfg.actions = vec![action("restart", vec![7]), action("backup", vec![7])];
+        assert_eq!(
+            allowed_remote_action(&cfg, 7, "backup").map(|a| a.name.as_str()),
+            Some("backup")
+        );
+    }
+
+    #[test]
+    fn bot_health_warns_once_and_reports_recovery() {
+        let metrics = Metrics::new().unwrap();
+        let health = BotHealth::new(metrics.clone());
+        let blocked: Result<(), _> =
+            Err(teloxide::RequestError::Api(teloxide::ApiError::BotBlocked));
+        let not_modified: Result<(), _> = Err(teloxide::RequestError::Api(
+            teloxide::ApiError::MessageNotModified,
+        ));
+
+        health.observe(&Ok::<_, teloxide::RequestError>(()), 100);
+        health.observe_delivery(&blocked, 200);
+        health.observe(&not_modified, 250);
+        assert_eq!(health.take_recovered(), None);
+        // Короткий сбой, закрытый успешным запросом, не считается потерей связи.
+        health.observe_delivery(&blocked, 300);
+        assert_eq!(health.overdue(800, 600), None);
+        health.observe_delivery(&blocked, 900);
+        let streak = health.overdue(900, 600).unwrap();
+        assert_eq!((streak.since, streak.errors), (300, 2));
+        assert_eq!(health.overdue(1_000, 600), None);
+        assert_eq!(health.overdue(900, 0), None);
+        assert_eq!(
+            metrics.agent_telegram_failing_since_timestamp_seconds.get(),
+            300.0
+        );
+
+        health.observe(&Ok::<_, teloxide::RequestError>(()), 1_200);
+        let (recovered, at) = health.take_recovered().unwrap();
+        assert_eq!((recovered.since, at), (300, 1_200));
+        assert_eq!(health.take_recovered(), None);
+        assert_eq!(
+            metrics.agent_telegram_failing_since_timestamp_seconds.get(),
+            0.0
+        );
+        assert_eq!(
+            metrics
+                .agent_telegram_api_errors_total
+                .with_label_values(&["api"])
+                .get(),
+            3.0
+        );
+        assert_eq!(
+            metrics
+                .agent_notifications_failed_total
+                .with_label_values(&["telegram"])
+                .get(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn checks_view_shows_latency_sparkline() {
+        assert_eq!(sparkline(&[10.0], 12), None);
+        assert_eq!(sparkline(&[5.0, 5.0, 5.0], 12).as_deref(), Some("▁▁▁"));
+        assert_eq!(
+            sparkline(&[0.0, 20.0, 40.0, 70.0, 100.0, 100.0], 3).as_deref(),
+            Some("▁▅█")
+        );
+
+        let mut state = Snapshot::default();
+        for name in ["api", "db"] {
+            Arc::make_mut(&mut state.checks)
+                .http
+                .push(crate::state::HttpCheckResult {
+                    name: name.to_string(),
+                    up: true,
+                    latency_ms: 42,
+                    status_code: 200,
+                    error: None,
+                    affected_by_upstream: None,
+                    cached: false,
+                });
+        }
+        let sparklines = HashMap::from([("api".to_string(), "▁▃▇".to_string())]);
+        let (text, _) = format_checks(&state, 0, &sparklines, &Units::default());
+        assert!(text.contains("<b>api</b>: 42 ms ▁▃▇"));
+        assert!(text.contains("<b>db</b>: 42 ms\n"));
+    }
+
+    #[test]
+    fn compare_table_lists_every_host() {
+        assert!(matches!(
+            Action::from_command("/compare"),
+            Some(Action::Compare)
+        ));
+        let mut local = Snapshot {
+            host_name: Some("gw".to_string()),
+            last_collect_timestamp_seconds: 1,
+            cpu_usage_percent: 12.4,
+            memory_used_bytes: 1,
+            memory_total_bytes: 4,
+            ..Snapshot::default()
+        };
+        Arc::make_mut(&mut local.checks)
+            .http
+            .push(crate::state::HttpCheckResult {
+                name: "api".to_string(),
+                up: false,
+                latency_ms: 0,
+                status_code: 0,
+                error: None,
+                affected_by_upstream: None,
+                cached: false,
+            });
+        let host = |name: &str, up, state: Option<Snapshot>| HostState {
+            name: name.to_string(),
+            source: crate::aggregator::HostSource::Pull,
+            url: None,
+            up,
+            last_success_unix: None,
+            error: None,
+            state,
+        };
+        let text = format_compare(
+            &local,
+            &[
+                host("db", true, Some(Snapshot::default())),
+                host("web", false, Some(Snapshot::default())),
+            ],
+        );
+        let rows: Vec<_> = text.lines().skip(1).take(4).collect();
+        assert_eq!(
+            rows,
+            [
+                "<pre>Хост            CPU  RAM  Диск Проверки",
+                "gw              12%  25%     - 0/1 ✗",
+                "db               0%   0%     - -",
+                "web            нет связи</pre>",
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_refresh_command_and_schedule() {
+        assert!(matches!(
+            Action::from_command("/autorefresh 5"),
+            Some(Action::SetAutoRefresh(Some(5)))
+        ));
+        assert!(matches!(
+            Action::from_command("/autorefresh off"),
+            Some(Action::SetAutoRefresh(None))
+        ));
+        for bad in ["/autorefresh", "/autorefresh 0.5", "/autorefresh 5000"] {
+            assert!(matches!(
+                Action::from_command(bad),
+                Some(Action::AutoRefreshUsage)
+            ));
+        }
+        assert!(Action::SetAutoRefresh(None).shows_dashboard());
+        assert!(!Action::System.shows_dashboard());
+
+        // Чат 1 обновлён 5 минут назад, чат 2 — минуту назад, у чата 3 автообновление выключено.
+        let live = HashMap::from([(1, 1_000), (2, 1_240), (3, 0)]);
+        let intervals = HashMap::from([(1, 5), (2, 5)]);
+        assert_eq!(due_auto_refreshes(&live, &intervals, 1_300), [1]);
+    }
+
+    #[test]
+    fn inline_query_filters_cards_by_keyword() {
+        let units = Units::default();
+        let mut state = Snapshot::default();
+        assert_eq!(inline_articles(&state, "cpu", &units)[0].id, "empty");
+
+        state.last_collect_timestamp_seconds = 1_700_000_000;
+        state.host_name = Some("web<1>".to_string());
+        state.cpu_usage_percent = 37.5;
+        state.memory_used_bytes = 4 << 30;
+        state.memory_total_bytes = 16 << 30;
+        Arc::make_mut(&mut state.temps).push(crate::state::TempStat {
+            sensor: "nvme0".to_string(),
+            temperature_celsius: 48.0,
+            critical_temperature_celsius: None,
+            device: None,
+        });
+
+        let ids = |query: &str| {
+            inline_articles(&state, query, &units)
+                .iter()
+                .map(|a| a.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(""), ["status", "cpu", "ram", "temps"]);
+        assert_eq!(ids("CPU"), ["status", "cpu"]);
+        assert_eq!(ids("темп"), ["status", "temps"]);
+        assert_eq!(ids("disk"), ["status"]);
+
+        let status = &inline_articles(&state, "", &units)[0];
+        assert!(status.text.starts_with("🖥 <b>web&lt;1&gt;</b>"));
+        assert!(status.text.contains("🧠 CPU: 37.5%"));
+        assert!(status.text.contains("• nvme0: 48"));
+        assert!(status.description.contains("RAM 25%"));
+    }
+
+    #[test]
+    fn pair_command_parsing() {
+        assert_eq!(parse_pair_command("/pair ABCD2345"), Some("ABCD2345"));
+        assert_eq!(parse_pair_command("/pair@monitord_bot abc"), Some("abc"));
+        assert_eq!(parse_pair_command("/pair"), None);
+        assert_eq!(parse_pair_command("/status"), None);
+    }
+
     #[test]
     fn rate_limiter_enforces_limit() {
         let mut limiter = RateLimiter::new(2);
@@ -1274,4 +5590,371 @@ mod tests {
         assert!(!limiter.allow(1, 30));
         assert!(limiter.allow(1, 71));
     }
+
+    #[tokio::test]
+    async fn speed_history_is_fed_from_every_snapshot() {
+        let bus = crate::events::EventBus::default();
+        let history = SpeedHistory::default();
+        let task = tokio::spawn(run_speed_history(history.clone(), bus.subscribe()));
+        let mut state = State::new(0);
+        for now in [100, 110, 900] {
+            state.net = vec![crate::state::NetStat {
+                iface: "eth0".to_string(),
+                rx_bytes_total: 0,
+                tx_bytes_total: 0,
+                rx_bytes_per_sec: now as u64,
+                tx_bytes_per_sec: 1,
+                ..Default::default()
+            }]
+            .into();
+            bus.publish(Event::SnapshotUpdated {
+                now,
+                snapshot: Arc::new(state.snapshot()),
+            });
+        }
+        drop(bus);
+        task.await.unwrap();
+
+        // Сэмплы старше окна в 10 минут отбрасываются.
+        let history = history.lock().await;
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].ts, history[0].rx, history[0].tx), (900, 900, 1));
+    }
+
+    #[test]
+    fn stale_chat_entries_are_evicted() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.allow(1, 10));
+        assert!(limiter.allow(2, 50));
+        limiter.prune(80);
+        assert_eq!(
+            limiter
+                .timestamps_by_chat
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+
+        let mut dashboards = HashMap::from([
+            (
+                1,
+                DashboardMessage {
+                    id: 10,
+                    used_unix: 0,
+                },
+            ),
+            (
+                2,
+                DashboardMessage {
+                    id: 20,
+                    used_unix: 50_000,
+                },
+            ),
+        ]);
+        prune_dashboards(&mut dashboards, CHAT_STATE_TTL_SECS + 10);
+        assert_eq!(dashboards.keys().copied().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn long_view_is_split_into_balanced_pages() {
+        let lines = (0..400)
+            .map(|i| format!("sensor {i}: <b>{i}°C</b> &amp; ok"))
+            .collect::<Vec<_>>();
+        let text = format!(
+            "🌡 <b>Датчики</b>\n<pre>{}</pre>\n{}",
+            lines.join("\n"),
+            "x".repeat(5000)
+        );
+        let pages = ViewPages::new(&text, main_menu());
+        assert!(pages.pages.len() > 2);
+        for page in &pages.pages {
+            assert!(page.len() <= MESSAGE_TEXT_LIMIT);
+            let mut open = Vec::new();
+            track_tags(page, &mut open);
+            assert!(open.is_empty(), "{page}");
+        }
+        assert!(pages.pages[1].starts_with("<pre>sensor"));
+        let joined = pages.pages.concat().replace("</pre><pre>", "");
+        assert_eq!(joined, text);
+
+        let first = pages.view(0);
+        assert_eq!(
+            first.keyboard.inline_keyboard[0].len(),
+            1,
+            "на первой части только «Показать ещё»"
+        );
+        let last = pages.view(usize::MAX);
+        assert!(last
+            .text
+            .ends_with(&format!("<i>Часть {0} из {0}</i>", pages.pages.len())));
+    }
+
+    #[test]
+    fn resource_alert_uses_template_when_configured() {
+        let alert = ResourceAlert {
+            kind: ResourceAlertKind::DiskUsage,
+            severity: AlertSeverity::Critical,
+            value: 97.34,
+            subject: Some("/data".to_string()),
+            recovered: false,
+            gpu_context: None,
+            chat_ids: Vec::new(),
+            broadcast: true,
+        };
+        let mut templates = HashMap::new();
+        let default_text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            95.0,
+            Some("srv"),
+            &templates,
+            &Units::default(),
+        );
+        assert!(default_text.contains("Диск: /data"));
+
+        templates.insert(
+            "disk_usage".to_string(),
+            "{{ host }}: {{ subject }} {{ value|round(1) }}{{ unit }} > {{ threshold }}"
+                .to_string(),
+        );
+        let text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            95.0,
+            Some("srv"),
+            &templates,
+            &Units::default(),
+        );
+        assert_eq!(text, "srv: /data 97.3% > 95.0");
+
+        let recovered = ResourceAlert {
+            value: 80.0,
+            recovered: true,
+            gpu_context: None,
+            ..alert
+        };
+        let text = format_resource_alert(
+            &recovered,
+            AlertSeverity::Critical,
+            85.0,
+            Some("srv"),
+            &templates,
+            &Units::default(),
+        );
+        assert!(text.starts_with("✅ <b>Диск заполнение в норме</b>"));
+    }
+
+    #[test]
+    fn dynamic_names_are_html_escaped() {
+        let alert = ResourceAlert {
+            kind: ResourceAlertKind::DiskUsage,
+            severity: AlertSeverity::Critical,
+            value: 97.0,
+            subject: Some("/mnt/<b>a&b".to_string()),
+            recovered: false,
+            gpu_context: None,
+            chat_ids: Vec::new(),
+            broadcast: true,
+        };
+        let mut templates = HashMap::new();
+        let text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            95.0,
+            None,
+            &templates,
+            &Units::default(),
+        );
+        assert!(text.contains("Диск: /mnt/&lt;b&gt;a&amp;b\n"));
+
+        templates.insert(
+            "disk_usage".to_string(),
+            "<b>{{ host }}</b> {{ subject }} {{ '<i>ok</i>'|safe }}".to_string(),
+        );
+        let text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            95.0,
+            Some("web<1>"),
+            &templates,
+            &Units::default(),
+        );
+        assert_eq!(text, "<b>web&lt;1&gt;</b> /mnt/&lt;b&gt;a&amp;b <i>ok</i>");
+
+        let event = AlertEvent {
+            check_id: crate::state::CheckId {
+                kind: CheckKind::Http,
+                name: "api <prod>".to_string(),
+            },
+            kind: AlertEventKind::Down,
+            severity: AlertSeverity::Critical,
+            upstream: Some("gw&lb".to_string()),
+            latency_ms: 1200,
+            status_code: Some(503),
+            error: Some("unexpected status 503 <gw>".to_string()),
+        };
+        assert_eq!(
+            format_alert_event(&event, None, &HashMap::new()),
+            "HTTP 'api &lt;prod&gt;' - <b>НЕДОСТУПЕН</b>\n↳ затронута вышестоящей проверкой 'gw&amp;lb'"
+        );
+        templates.insert(
+            "check_down".to_string(),
+            "{{ name }}: {{ status_code }} за {{ latency_ms }} мс ({{ error }})".to_string(),
+        );
+        assert_eq!(
+            format_alert_event(&event, None, &templates),
+            "api &lt;prod&gt;: 503 за 1200 мс (unexpected status 503 &lt;gw&gt;)"
+        );
+    }
+
+    #[test]
+    fn gpu_alert_lists_top_processes() {
+        let alert = ResourceAlert {
+            kind: ResourceAlertKind::GpuLoad,
+            severity: AlertSeverity::Critical,
+            value: 98.0,
+            subject: None,
+            recovered: false,
+            gpu_context: Some(GpuAlertContext {
+                encoder_percent: Some(40.0),
+                decoder_percent: Some(0.0),
+                processes: vec![crate::state::GpuProcess {
+                    pid: 4242,
+                    name: "python".to_string(),
+                    sm_percent: Some(91.0),
+                    memory_mb: Some(2048),
+                }],
+            }),
+            chat_ids: Vec::new(),
+            broadcast: true,
+        };
+        let mut templates = HashMap::new();
+        let text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            92.0,
+            None,
+            &templates,
+            &Units::default(),
+        );
+        assert!(text.contains("Кодировщик: 40%, декодер: 0%"));
+        assert!(text.contains("• python (4242): 91%, 2.0 ГиБ"), "{text}");
+
+        templates.insert(
+            "gpu_load".to_string(),
+            "{{ gpu_context.processes[0].name }} {{ gpu_context.encoder_percent }}".to_string(),
+        );
+        let text = format_resource_alert(
+            &alert,
+            AlertSeverity::Critical,
+            92.0,
+            None,
+            &templates,
+            &Units::default(),
+        );
+        assert_eq!(text, "python 40.0");
+    }
+
+    #[test]
+    fn service_messages_escape_errors() {
+        assert_eq!(
+            format_config_reloaded(Some("srv"), &["listen", "routes"]),
+            "⚙️ <b>Конфигурация на srv перезагружена</b>\nТребуют перезапуска: <code>listen, routes</code>"
+        );
+        assert!(format_config_rejected(None, "interval_secs <1>")
+            .ends_with("<code>interval_secs &lt;1&gt;</code>"));
+        assert_eq!(
+            format_agent_stopping(Some("srv"), "SIGTERM"),
+            "⏹ <b>monitord на srv останавливается</b> (SIGTERM)"
+        );
+    }
+
+    #[test]
+    fn host_reboot_alert_mentions_previous_uptime() {
+        let reboot = HostReboot {
+            previous_uptime_secs: 90_000,
+            boot_time_unix: 0,
+        };
+        let text = format_host_reboot(&reboot, Some("srv<1>"), &Units::default());
+        assert!(text.starts_with("🔄 <b>Хост srv&lt;1&gt; перезагружен</b>"));
+        assert!(text.contains("1970-01-01T00:00:00Z"));
+        assert!(text.contains("1д 1ч 0м"));
+    }
+
+    #[test]
+    fn speed_history_csv_has_header_and_rows() {
+        let history: VecDeque<SpeedSample> = [SpeedSample {
+            ts: 10,
+            rx: 1,
+            tx: 2,
+        }]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            speed_history_csv(&history),
+            "ts_unix,rx_bytes_per_sec,tx_bytes_per_sec\n10,1,2\n"
+        );
+    }
+
+    #[test]
+    fn pager_clamps_and_slices() {
+        let items = (0..23).collect::<Vec<_>>();
+        let pager = Pager::new(items.len(), 10, 5);
+        assert_eq!(pager, Pager { page: 2, pages: 3 });
+        assert_eq!(pager.slice(&items, 10), &[20, 21, 22]);
+        assert_eq!(Pager::new(0, 10, 0).pages, 1);
+        assert!(matches!(
+            Action::from_callback("disks_p2"),
+            Some(Action::Disks(2))
+        ));
+        assert!(matches!(
+            Action::from_callback("du"),
+            Some(Action::DirSizes)
+        ));
+    }
+
+    #[test]
+    fn set_threshold_command_parsing() {
+        assert!(matches!(
+            Action::from_command("/set cpu_temp 90"),
+            Some(Action::SetThreshold(ResourceAlertKind::CpuTemp, Some(v))) if v == 90.0
+        ));
+        assert!(matches!(
+            Action::from_command("/set disk_usage reset"),
+            Some(Action::SetThreshold(ResourceAlertKind::DiskUsage, None))
+        ));
+        assert!(matches!(
+            Action::from_command("/set ram_usage 150"),
+            Some(Action::SetThresholdUsage)
+        ));
+        assert!(matches!(
+            Action::from_command("/set unknown 10"),
+            Some(Action::SetThresholdUsage)
+        ));
+        assert!(matches!(
+            Action::from_command("/timezone Europe/Moscow"),
+            Some(Action::SetTimezone(Some(chrono_tz::Europe::Moscow)))
+        ));
+        assert!(matches!(
+            Action::from_command("/timezone reset"),
+            Some(Action::SetTimezone(None))
+        ));
+        assert!(matches!(
+            Action::from_command("/timezone Mars/Olympus"),
+            Some(Action::TimezoneUsage)
+        ));
+        assert!(matches!(
+            Action::from_callback("thr_gpu_load_down"),
+            Some(Action::StepThreshold(ResourceAlertKind::GpuLoad, v)) if v < 0.0
+        ));
+        assert!(matches!(
+            Action::from_callback("act_ask_Restart nginx"),
+            Some(Action::ConfirmRemoteCommand(name)) if name == "Restart nginx"
+        ));
+        assert!(matches!(
+            Action::from_callback("act_run_run_backup"),
+            Some(Action::RunRemoteCommand(name)) if name == "run_backup"
+        ));
+    }
 }
@@ -0,0 +1,166 @@
+use crate::config::{ByteUnits, DecimalSeparator, DisplayConfig, RateUnits, TemperatureUnit};
+use chrono::DateTime;
+use chrono_tz::Tz;
+
+// Форматирование величин по настройкам display: все представления выводят их одинаково.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Units(DisplayConfig);
+
+impl From<&DisplayConfig> for Units {
+    fn from(cfg: &DisplayConfig) -> Self {
+        Self(*cfg)
+    }
+}
+
+impl Units {
+    // Часовой пояс чата перекрывает общий из display.timezone.
+    pub fn with_timezone(mut self, timezone: Option<Tz>) -> Self {
+        if timezone.is_some() {
+            self.0.timezone = timezone;
+        }
+        self
+    }
+
+    pub fn datetime(&self, ts: i64) -> String {
+        let utc = DateTime::from_timestamp(ts.max(0), 0).unwrap_or_default();
+        match self.0.timezone {
+            Some(tz) => utc
+                .with_timezone(&tz)
+                .format("%Y-%m-%d %H:%M:%S %Z")
+                .to_string(),
+            None => utc.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        }
+    }
+
+    pub fn number(&self, value: f64, precision: usize) -> String {
+        let text = format!("{value:.precision$}");
+        match self.0.decimal_separator {
+            DecimalSeparator::Dot => text,
+            DecimalSeparator::Comma => text.replace('.', ","),
+        }
+    }
+
+    // Объёмы памяти и дисков всегда выводятся в гигабайтах.
+    pub fn gigabytes(&self, bytes: u64) -> String {
+        format!(
+            "{} {}",
+            self.number(self.gigabytes_value(bytes), 1),
+            self.gigabyte_label()
+        )
+    }
+
+    pub fn gigabytes_of(&self, used: u64, total: u64) -> String {
+        format!(
+            "{}/{} {}",
+            self.number(self.gigabytes_value(used), 1),
+            self.number(self.gigabytes_value(total), 1),
+            self.gigabyte_label()
+        )
+    }
+
+    pub fn rate(&self, bytes_per_sec: f64) -> String {
+        let (value, base, labels) = match (self.0.rate, self.0.bytes) {
+            (RateUnits::Bits, _) => (bytes_per_sec * 8.0, 1000.0, ["bps", "Kbps", "Mbps", "Gbps"]),
+            (RateUnits::Bytes, ByteUnits::Binary) => {
+                (bytes_per_sec, 1024.0, ["B/s", "KiB/s", "MiB/s", "GiB/s"])
+            }
+            (RateUnits::Bytes, ByteUnits::Decimal) => {
+                (bytes_per_sec, 1000.0, ["B/s", "kB/s", "MB/s", "GB/s"])
+            }
+        };
+        let mut scaled = value;
+        let mut idx = 0;
+        while scaled >= base && idx < labels.len() - 1 {
+            scaled /= base;
+            idx += 1;
+        }
+        if idx == 0 {
+            format!("{value:.0} {}", labels[0])
+        } else {
+            format!("{} {}", self.number(scaled, 2), labels[idx])
+        }
+    }
+
+    // Результаты speedtest хранятся в мегабитах в секунду.
+    pub fn megabits(&self, mbps: f64) -> String {
+        self.rate(mbps * 125_000.0)
+    }
+
+    pub fn temperature(&self, celsius: f64, precision: usize) -> String {
+        format!(
+            "{}{}",
+            self.number(self.temperature_value(celsius), precision),
+            self.temperature_unit()
+        )
+    }
+
+    pub fn temperature_value(&self, celsius: f64) -> f64 {
+        match self.0.temperature {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    // Разность температур (скорость роста) пересчитывается без смещения шкалы.
+    pub fn temperature_delta_value(&self, celsius: f64) -> f64 {
+        match self.0.temperature {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0,
+        }
+    }
+
+    pub fn temperature_unit(&self) -> &'static str {
+        match self.0.temperature {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+
+    fn gigabytes_value(&self, bytes: u64) -> f64 {
+        match self.0.bytes {
+            ByteUnits::Binary => bytes as f64 / 1024.0 / 1024.0 / 1024.0,
+            ByteUnits::Decimal => bytes as f64 / 1e9,
+        }
+    }
+
+    fn gigabyte_label(&self) -> &'static str {
+        match self.0.bytes {
+            ByteUnits::Binary => "ГиБ",
+            ByteUnits::Decimal => "ГБ",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_follow_display_config() {
+        let binary = Units::default();
+        assert_eq!(binary.gigabytes_of(8 << 30, 16 << 30), "8.0/16.0 ГиБ");
+        assert_eq!(binary.rate(512.0), "512 B/s");
+        assert_eq!(binary.rate(1536.0), "1.50 KiB/s");
+        assert_eq!(binary.temperature(45.0, 1), "45.0°C");
+
+        let custom = Units::from(&DisplayConfig {
+            bytes: ByteUnits::Decimal,
+            rate: RateUnits::Bits,
+            temperature: TemperatureUnit::Fahrenheit,
+            decimal_separator: DecimalSeparator::Comma,
+            timezone: None,
+        });
+        assert_eq!(custom.gigabytes(2_500_000_000), "2,5 ГБ");
+        assert_eq!(custom.megabits(94.2), "94,20 Mbps");
+        assert_eq!(custom.rate(1_250_000_000.0), "10,00 Gbps");
+        assert_eq!(custom.temperature(100.0, 0), "212°F");
+
+        assert_eq!(binary.datetime(1_790_000_000), "2026-09-21T14:13:20Z");
+        let moscow = binary.with_timezone(Some(chrono_tz::Europe::Moscow));
+        assert_eq!(moscow.datetime(1_790_000_000), "2026-09-21 17:13:20 MSK");
+        assert_eq!(
+            moscow.with_timezone(None).datetime(1_790_000_000),
+            "2026-09-21 17:13:20 MSK"
+        );
+    }
+}
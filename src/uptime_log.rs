@@ -0,0 +1,98 @@
+use crate::state::CheckUptimeDay;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const UPTIME_FILE: &str = "check_uptime.jsonl";
+
+// Суточные итоги проверок, по одному JSON на строку; файл целиком перезаписывается при сохранении.
+pub struct UptimeLog {
+    path: PathBuf,
+}
+
+impl UptimeLog {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: data_dir.as_ref().join(UPTIME_FILE),
+        }
+    }
+
+    // Повреждённые строки пропускаются, как в истории speedtest.
+    pub fn load(&self, since_day: i64) -> io::Result<Vec<CheckUptimeDay>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        Ok(text
+            .lines()
+            .filter_map(|line| serde_json::from_str::<CheckUptimeDay>(line).ok())
+            .filter(|d| d.day >= since_day)
+            .collect())
+    }
+
+    pub fn rewrite<'a>(
+        &self,
+        days: impl IntoIterator<Item = &'a CheckUptimeDay>,
+    ) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for day in days {
+            text.push_str(
+                &serde_json::to_string(day)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            );
+            text.push('\n');
+        }
+        let tmp = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{HttpCheckResult, State, CHECK_UPTIME_DAYS};
+    use std::sync::Arc;
+
+    #[test]
+    fn daily_uptime_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("monitord-uptime-{}", std::process::id()));
+        let log = UptimeLog::new(&dir);
+        let day = 20_000_i64;
+        let mut state = State::default();
+        for (offset, up) in [(0, true), (1, false), (86_400, true)] {
+            Arc::make_mut(&mut state.checks).http = vec![HttpCheckResult {
+                name: "api".to_string(),
+                up,
+                latency_ms: 5,
+                status_code: 200,
+                error: None,
+                affected_by_upstream: None,
+                cached: false,
+            }];
+            state.record_check_uptime(day * 86_400 + offset);
+        }
+        log.rewrite(state.check_uptime.values()).unwrap();
+        fs::write(
+            &log.path,
+            fs::read_to_string(&log.path).unwrap() + "{\"day\":",
+        )
+        .unwrap();
+
+        let mut restarted = State::default();
+        restarted.restore_check_uptime(log.load(day).unwrap());
+        let first = restarted.check_uptime_day("api", day).unwrap();
+        assert_eq!((first.up, first.total), (1, 2));
+        let second = restarted.check_uptime_day("api", day + 1).unwrap();
+        assert_eq!((second.up, second.total), (1, 1));
+        assert!(log.load(day + 1).unwrap().iter().all(|d| d.day == day + 1));
+
+        restarted.record_check_uptime((day + CHECK_UPTIME_DAYS) * 86_400);
+        assert!(restarted.check_uptime_day("api", day).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}